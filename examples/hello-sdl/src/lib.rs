@@ -0,0 +1,32 @@
+//! The smallest app cargo-sdl-apk packages: a window filled with one color until it is closed.
+
+use sdl2::event::Event;
+use sdl2::pixels::Color;
+
+#[no_mangle]
+pub extern "C" fn SDL_main(_argc: i32, _argv: *const *const u8) -> i32 {
+  let sdl = sdl2::init().expect("unable to initialize SDL");
+  let video = sdl.video().expect("unable to initialize video");
+  let window = video
+    .window("Hello SDL", 0, 0)
+    .fullscreen_desktop()
+    .build()
+    .expect("unable to create window");
+  let mut canvas = window
+    .into_canvas()
+    .present_vsync()
+    .build()
+    .expect("unable to create canvas");
+  let mut events = sdl.event_pump().expect("unable to get the event pump");
+
+  loop {
+    for event in events.poll_iter() {
+      if let Event::Quit { .. } = event {
+        return 0;
+      }
+    }
+    canvas.set_draw_color(Color::RGB(40, 80, 160));
+    canvas.clear();
+    canvas.present();
+  }
+}
@@ -0,0 +1,113 @@
+use std::collections::BTreeSet;
+use std::path::Path;
+
+use crate::adb::*;
+use crate::apk_report::*;
+use crate::build_bin_as_lib::*;
+use crate::targets::*;
+
+/// Parses `ro.product.cpu.abilist`, the ABIs the device supports in order of preference.
+pub fn parse_abilist(abilist: &str) -> Vec<String> {
+  abilist
+    .trim()
+    .split(',')
+    .map(|abi| abi.trim().to_string())
+    .filter(|abi| !abi.is_empty())
+    .collect()
+}
+
+/// Checks that the device supports one of the ABIs of the APK. Otherwise the error names the
+/// target to build, with a hint depending on whether the package is configured for it.
+pub fn check_abi_compatibility(
+  apk_abis: &BTreeSet<String>,
+  device_abis: &[String],
+  configured_targets: &[&str],
+) -> Result<(), String> {
+  //without native libraries any device works
+  if apk_abis.is_empty() || device_abis.iter().any(|abi| apk_abis.contains(abi)) {
+    return Ok(());
+  }
+
+  let apk_abis: Vec<&str> = apk_abis.iter().map(|abi| abi.as_str()).collect();
+  let mut message = format!(
+    "The APK contains native libraries for {}, but the device only supports {}",
+    apk_abis.join(", "),
+    device_abis.join(", ")
+  );
+  let target = device_abis
+    .iter()
+    .find_map(|abi| get_rust_target_for_abi(abi));
+  match target {
+    Some(target) if configured_targets.contains(&target) => message.push_str(&format!(
+      ". The package is configured for {}, but it wasn't part of this build. Rebuild with \
+      `--target {}` included, or without --target to build all configured targets",
+      target, target
+    )),
+    Some(target) => message.push_str(&format!(". Build with `--target {}`", target)),
+    None => message.push_str(". None of the android targets can build for the device"),
+  }
+  Err(message)
+}
+
+/// Returns an explanation if the APK can't be installed on the connected device, instead of
+/// letting adb fail with INSTALL_FAILED_NO_MATCHING_ABIS.
+pub fn ensure_device_supports_apk(manifest_path: &Path, apk_path: &Path) -> Result<(), String> {
+  //adb reports a missing device itself
+  let Some(abilist) = adb_shell(&["getprop", "ro.product.cpu.abilist"]) else {
+    return Ok(());
+  };
+  let device_abis = parse_abilist(&abilist);
+  if device_abis.is_empty() || !apk_path.is_file() {
+    return Ok(());
+  }
+
+  check_abi_compatibility(
+    &read_apk_abis(apk_path),
+    &device_abis,
+    &get_android_targets(manifest_path)?,
+  )
+}
+
+#[cfg(test)]
+mod test {
+  use std::collections::BTreeSet;
+
+  use crate::abi_check::{check_abi_compatibility, parse_abilist};
+  use crate::build_bin_as_lib::DEFAULT_ANDROID_TARGETS;
+
+  #[test]
+  fn abilist() {
+    assert_eq!(
+      parse_abilist("x86_64,arm64-v8a\n"),
+      vec!["x86_64", "arm64-v8a"]
+    );
+    assert!(parse_abilist("").is_empty());
+  }
+
+  #[test]
+  fn compatibility() {
+    let arm64_only = BTreeSet::from(["arm64-v8a".to_string()]);
+    let emulator = parse_abilist("x86_64,x86");
+    assert!(
+      check_abi_compatibility(&arm64_only, &parse_abilist("arm64-v8a,armeabi-v7a"), &[]).is_ok()
+    );
+    assert!(check_abi_compatibility(&BTreeSet::new(), &emulator, &[]).is_ok());
+
+    let e = check_abi_compatibility(&arm64_only, &emulator, DEFAULT_ANDROID_TARGETS).unwrap_err();
+    assert!(
+      e.contains("for arm64-v8a, but the device only supports x86_64, x86"),
+      "{}",
+      e
+    );
+    assert!(e.contains("wasn't part of this build"), "{}", e);
+    assert!(e.contains("`--target x86_64-linux-android`"), "{}", e);
+
+    let e =
+      check_abi_compatibility(&arm64_only, &emulator, &["aarch64-linux-android"]).unwrap_err();
+    assert!(
+      e.ends_with("Build with `--target x86_64-linux-android`"),
+      "{}",
+      e
+    );
+  }
+}
@@ -0,0 +1,663 @@
+use std::env;
+use std::ffi::OsString;
+use std::fmt::{Display, Formatter};
+use std::fs::metadata;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::str::FromStr;
+use std::sync::Mutex;
+
+use lazy_static::lazy_static;
+
+use toml::value::Value;
+
+use crate::command_runner::*;
+use crate::error::*;
+use crate::local_config::*;
+use crate::util::*;
+
+lazy_static! {
+  static ref SELECTED_DEVICE: Mutex<Option<String>> = Mutex::new(None);
+}
+
+/// Key of the local config pinning the device used if none is requested.
+pub const DEFAULT_DEVICE_KEY: &str = "default_device";
+
+/// First sdk version supporting incremental installs.
+const INCREMENTAL_INSTALL_MIN_SDK_VERSION: u32 = 30;
+
+/// Failure codes of `adb install` meaning the installed app is signed with another key.
+const SIGNATURE_MISMATCH_FAILURES: &[&str] = &[
+  "INSTALL_FAILED_UPDATE_INCOMPATIBLE",
+  "INSTALL_PARSE_FAILED_INCONSISTENT_CERTIFICATES",
+];
+
+/// Explanations of the failure codes of `adb install`.
+const INSTALL_FAILURE_EXPLANATIONS: &[(&str, &str)] = &[
+  (
+    "INSTALL_FAILED_UPDATE_INCOMPATIBLE",
+    "the installed app is signed with a different key",
+  ),
+  (
+    "INSTALL_PARSE_FAILED_INCONSISTENT_CERTIFICATES",
+    "the installed app is signed with a different key",
+  ),
+  (
+    "INSTALL_FAILED_VERSION_DOWNGRADE",
+    "the installed app has a higher version_code, uninstall it or raise the version_code",
+  ),
+  (
+    "INSTALL_FAILED_INSUFFICIENT_STORAGE",
+    "the device doesn't have enough free storage",
+  ),
+  (
+    "INSTALL_FAILED_OLDER_SDK",
+    "the Android version of the device is older than the min_sdk_version of the app",
+  ),
+  (
+    "INSTALL_FAILED_NO_MATCHING_ABIS",
+    "the APK has no native library for the ABI of the device, build it for the device's target",
+  ),
+  (
+    "INSTALL_FAILED_DUPLICATE_PERMISSION",
+    "another installed app defines a permission this app defines",
+  ),
+  (
+    "INSTALL_FAILED_CONFLICTING_PROVIDER",
+    "another installed app uses the authority of a content provider of this app",
+  ),
+  (
+    "INSTALL_FAILED_TEST_ONLY",
+    "the APK is marked as test only and can only be installed with `adb install -t`",
+  ),
+  (
+    "INSTALL_FAILED_USER_RESTRICTED",
+    "the device denied installing over USB, allow it in the developer options",
+  ),
+  (
+    "INSTALL_FAILED_VERIFICATION_FAILURE",
+    "package verification on the device rejected the APK",
+  ),
+  (
+    "INSTALL_FAILED_MISSING_SHARED_LIBRARY",
+    "the app requires a shared library the device doesn't have",
+  ),
+  (
+    "INSTALL_PARSE_FAILED_NO_CERTIFICATES",
+    "the APK isn't signed",
+  ),
+  (
+    "INSTALL_FAILED_INVALID_APK",
+    "the APK is invalid or damaged",
+  ),
+];
+
+pub fn get_adb_path() -> PathBuf {
+  get_env_path("ANDROID_HOME").join("platform-tools/adb")
+}
+
+/// An adb command for the selected device. Without a selected device adb picks the device itself.
+pub fn adb_command() -> Command {
+  let mut command = Command::new(get_adb_path());
+  if let Some(serial) = SELECTED_DEVICE.lock().unwrap().as_ref() {
+    command.args(["-s", serial]);
+  }
+  command
+}
+
+/// A device in the output of `adb devices -l`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AdbDevice {
+  pub serial: String,
+  /// `device` if it is usable, otherwise e.g. `unauthorized` or `offline`.
+  pub state: String,
+  /// The rest of the line, like `product:redfin model:Pixel_5 device:redfin`.
+  pub details: String,
+}
+
+impl AdbDevice {
+  pub fn is_ready(&self) -> bool {
+    self.state == "device"
+  }
+}
+
+impl Display for AdbDevice {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}", self.serial)?;
+    if !self.is_ready() {
+      write!(f, " ({})", self.state)?;
+    }
+    if !self.details.is_empty() {
+      write!(f, " {}", self.details)?;
+    }
+    Ok(())
+  }
+}
+
+/// Parses the output of `adb devices -l`.
+pub fn parse_adb_devices(output: &str) -> Vec<AdbDevice> {
+  output
+    .lines()
+    .filter(|line| !line.starts_with("List of devices") && !line.starts_with('*'))
+    .filter_map(|line| {
+      let mut parts = line.split_whitespace();
+      let serial = parts.next()?.to_string();
+      let state = parts.next()?.to_string();
+      Some(AdbDevice {
+        serial,
+        state,
+        details: parts.collect::<Vec<_>>().join(" "),
+      })
+    })
+    .collect()
+}
+
+pub fn list_adb_devices() -> Result<Vec<AdbDevice>> {
+  let output = run_command_output(Command::new(get_adb_path()).args(["devices", "-l"]))
+    .map_err(|e| Error::Config(format!("Unable to run {}: {}", get_adb_path().display(), e)))?;
+  if !output.status.success() {
+    return Err(Error::command(get_adb_path().as_os_str(), output.status));
+  }
+  Ok(parse_adb_devices(&String::from_utf8_lossy(&output.stdout)))
+}
+
+fn format_device_list(devices: &[AdbDevice]) -> String {
+  if devices.is_empty() {
+    return "no devices are connected".to_string();
+  }
+  let list: Vec<String> = devices.iter().map(|d| format!("\n  {}", d)).collect();
+  format!("connected devices:{}", list.concat())
+}
+
+/// Picks the device to use: the requested one, else `ANDROID_SERIAL`, else the `default_device`
+/// of the local config, else the only connected device. Returns the serial and where it came from.
+pub fn resolve_device(
+  requested: Option<&str>,
+  android_serial: Option<&str>,
+  default_device: Option<&str>,
+  devices: &[AdbDevice],
+) -> std::result::Result<(String, &'static str), String> {
+  let choice = requested
+    .map(|serial| (serial, "--device"))
+    .or_else(|| android_serial.map(|serial| (serial, "ANDROID_SERIAL")))
+    .or_else(|| default_device.map(|serial| (serial, "default_device")));
+  if let Some((serial, source)) = choice {
+    return match devices.iter().find(|d| d.serial == serial) {
+      Some(device) if device.is_ready() => Ok((serial.to_string(), source)),
+      Some(device) => Err(format!(
+        "Device {} from {} is {}",
+        serial, source, device.state
+      )),
+      None => Err(format!(
+        "Device {} from {} isn't connected, {}",
+        serial,
+        source,
+        format_device_list(devices)
+      )),
+    };
+  }
+
+  let ready: Vec<&AdbDevice> = devices.iter().filter(|d| d.is_ready()).collect();
+  match ready.as_slice() {
+    [device] => Ok((device.serial.clone(), "the only connected device")),
+    [] => Err(format!("No usable device, {}", format_device_list(devices))),
+    _ => Err(format!(
+      "Multiple devices are connected, select one with --device <serial>, ANDROID_SERIAL or \
+      `cargo sdl-apk devices --set-default <serial>`, {}",
+      format_device_list(devices)
+    )),
+  }
+}
+
+/// Selects the device all following adb commands use, see [resolve_device]. A device selected
+/// before is kept unless another one is requested, so a device connected in the meantime is never
+/// picked up.
+pub fn select_device(manifest_path: &Path, requested: Option<&str>) -> Result<String> {
+  if let Some(selected) = SELECTED_DEVICE.lock().unwrap().as_ref() {
+    if requested.is_none() || requested == Some(selected.as_str()) {
+      return Ok(selected.clone());
+    }
+  }
+
+  let android_serial = env::var("ANDROID_SERIAL").ok().filter(|s| !s.is_empty());
+  let default_device =
+    get_local_config_string(manifest_path.parent().unwrap(), DEFAULT_DEVICE_KEY)?;
+  let (serial, source) = resolve_device(
+    requested,
+    android_serial.as_deref(),
+    default_device.as_deref(),
+    &list_adb_devices()?,
+  )?;
+  println!("Using device {} ({})", serial, source);
+  *SELECTED_DEVICE.lock().unwrap() = Some(serial.clone());
+  Ok(serial)
+}
+
+/// Location of the v4 signature apksigner and gradle write next to the apk.
+pub fn get_idsig_path(apk_path: &Path) -> PathBuf {
+  let mut path = OsString::from(apk_path.as_os_str());
+  path.push(".idsig");
+  PathBuf::from(path)
+}
+
+/// Returns the v4 signature of the apk, unless it is missing or left over from an earlier build.
+fn find_current_idsig(apk_path: &Path) -> Option<PathBuf> {
+  let idsig_path = get_idsig_path(apk_path);
+  let idsig_modified = metadata(&idsig_path).and_then(|m| m.modified()).ok()?;
+  let apk_modified = metadata(apk_path).and_then(|m| m.modified()).ok()?;
+  (idsig_modified >= apk_modified).then_some(idsig_path)
+}
+
+/// Runs `adb shell` on the device, returning the output or `None` if adb failed.
+pub fn adb_shell(args: &[&str]) -> Option<String> {
+  let output = run_command_output(adb_command().arg("shell").args(args))
+    .ok()
+    .filter(|o| o.status.success())?;
+  Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+fn device_supports_incremental_install() -> bool {
+  let sdk_version: Option<u32> =
+    adb_shell(&["getprop", "ro.build.version.sdk"]).and_then(|v| v.parse().ok());
+  sdk_version.is_some_and(|v| v >= INCREMENTAL_INSTALL_MIN_SDK_VERSION)
+    && adb_shell(&["pm", "has-feature", "android.software.incremental_delivery"]).as_deref()
+      == Some("true")
+}
+
+/// Checks the `dumpsys package` output for an incremental install still waiting for data, which
+/// is what a failed incremental install leaves behind.
+pub fn is_partially_installed(dumpsys_output: &str) -> bool {
+  dumpsys_output
+    .lines()
+    .map(|line| line.trim())
+    .any(|line| line == "isLoading=true" || line.starts_with("loadingProgress=0"))
+}
+
+/// Removes the remains of a failed incremental install, keeping the app data.
+fn remove_partial_install(app_id: &str) {
+  let partial = adb_shell(&["dumpsys", "package", app_id])
+    .map(|output| is_partially_installed(&output))
+    .unwrap_or(false);
+  if partial {
+    println!("Removing partially installed {}...", app_id);
+    run_command_checked(adb_command().args(["shell", "pm", "uninstall", "-k", app_id]));
+  }
+}
+
+/// A port on the device forwarded to a port of the host with `adb reverse`, so the app reaches e.g.
+/// a dev server on the workstation at localhost.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReversePort {
+  pub device: u16,
+  pub host: u16,
+}
+
+impl FromStr for ReversePort {
+  type Err = String;
+
+  /// Parses `port` or `port:remote`, the port on the device and the one on the host.
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let parse = |port: &str| {
+      port
+        .parse::<u16>()
+        .ok()
+        .filter(|port| *port != 0)
+        .ok_or_else(|| format!("invalid port \"{}\", expected port or port:remote", s))
+    };
+    match s.split_once(':') {
+      Some((device, host)) => Ok(Self {
+        device: parse(device)?,
+        host: parse(host)?,
+      }),
+      None => {
+        let port = parse(s)?;
+        Ok(Self {
+          device: port,
+          host: port,
+        })
+      }
+    }
+  }
+}
+
+impl Display for ReversePort {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    write!(f, "device tcp:{} -> host tcp:{}", self.device, self.host)
+  }
+}
+
+/// Parses `adb_reverse` of the android metadata, a list of ports or `"port:remote"` strings.
+pub fn parse_reverse_ports(value: &Value) -> Result<Vec<ReversePort>, String> {
+  let ports = value
+    .as_array()
+    .ok_or("adb_reverse must be a list of ports")?;
+  ports
+    .iter()
+    .map(|port| match port {
+      Value::Integer(port) => u16::try_from(*port)
+        .map_err(|_| format!("invalid port {} in adb_reverse", port))?
+        .to_string()
+        .parse(),
+      Value::String(port) => port.parse(),
+      _ => Err(format!("invalid port {} in adb_reverse", port)),
+    })
+    .collect()
+}
+
+/// Reads `adb_reverse` of the android metadata.
+pub fn get_metadata_reverse_ports(manifest_path: &Path) -> Result<Vec<ReversePort>, String> {
+  match get_toml_entry(
+    manifest_path,
+    ["package", "metadata", "android", "adb_reverse"],
+  )? {
+    Some(value) => parse_reverse_ports(&value),
+    None => Ok(vec![]),
+  }
+}
+
+/// Forwards the ports of the device to the host. Devices before Android 5.0 don't support
+/// `adb reverse`, which is only a warning.
+pub fn setup_reverse_ports(ports: &[ReversePort]) {
+  for port in ports {
+    let status = run_command_output(adb_command().args([
+      "reverse",
+      &format!("tcp:{}", port.device),
+      &format!("tcp:{}", port.host),
+    ]))
+    .map(|output| output.status.success());
+    if status.unwrap_or(false) {
+      println!("Reverse forwarding {}", port);
+    } else {
+      eprintln!(
+        "WARNING: Unable to forward {}, adb reverse needs Android 5.0 or newer.",
+        port
+      );
+    }
+  }
+}
+
+/// Removes the forwards of `setup_reverse_ports`.
+pub fn remove_reverse_ports(ports: &[ReversePort]) {
+  for port in ports {
+    let _ = run_command_output(adb_command().args([
+      "reverse",
+      "--remove",
+      &format!("tcp:{}", port.device),
+    ]));
+  }
+}
+
+/// The failure code in the output of `adb install`, e.g. `INSTALL_FAILED_UPDATE_INCOMPATIBLE`.
+pub fn parse_install_failure(output: &str) -> Option<String> {
+  let start = output.find("INSTALL_")?;
+  Some(
+    output[start..]
+      .chars()
+      .take_while(|c| c.is_ascii_uppercase() || *c == '_')
+      .collect(),
+  )
+}
+
+pub fn explain_install_failure(code: &str) -> Option<&'static str> {
+  INSTALL_FAILURE_EXPLANATIONS
+    .iter()
+    .find(|(c, _)| *c == code)
+    .map(|(_, explanation)| *explanation)
+}
+
+/// The digest of the signer of the installed app in the `dumpsys package` output.
+pub fn parse_installed_signer(dumpsys_output: &str) -> Option<String> {
+  let line = dumpsys_output
+    .lines()
+    .map(|line| line.trim())
+    .find(|line| line.starts_with("signatures=PackageSignatures{"))?;
+  //`signatures:[..]` since Android 9, a plain list before
+  let list = match line.find("signatures:[") {
+    Some(index) => &line[index + "signatures:".len()..],
+    None => &line[line.find('[')?..],
+  };
+  Some(list[1..list.find(']')?].to_string()).filter(|signer| !signer.is_empty())
+}
+
+fn adb_install(apk_path: &Path) -> std::process::Output {
+  run_command_captured(adb_command().args(["install", "-r"]).arg(apk_path))
+}
+
+/// Installs the apk on the device. If a v4 signature exists and the device supports it, the apk
+/// is installed incrementally, falling back to a regular install if that fails. If the installed
+/// app is signed with another key, e.g. by a build of Android Studio, `force_reinstall`
+/// uninstalls it first, deleting its data.
+pub fn install_apk(apk_path: &Path, app_id: &str, force_reinstall: bool) -> Result<()> {
+  if find_current_idsig(apk_path).is_some() && device_supports_incremental_install() {
+    let status = run_command(
+      adb_command()
+        .args(["install", "-r", "--incremental"])
+        .arg(apk_path),
+    );
+    if status.success() {
+      return Ok(());
+    }
+
+    eprintln!("Incremental install failed, falling back to a regular install.");
+    remove_partial_install(app_id);
+  }
+
+  let output = adb_install(apk_path);
+  if output.status.success() {
+    return Ok(());
+  }
+  let failure = parse_install_failure(&format!(
+    "{}{}",
+    String::from_utf8_lossy(&output.stdout),
+    String::from_utf8_lossy(&output.stderr)
+  ));
+  let Some(code) = failure else {
+    return Err(Error::command(get_adb_path().as_os_str(), output.status));
+  };
+
+  if SIGNATURE_MISMATCH_FAILURES.contains(&code.as_str()) {
+    eprintln!(
+      "The installed {} is signed with a different key than the new APK, e.g. by a build of \
+      another tool or machine.",
+      app_id
+    );
+    if let Some(signer) =
+      adb_shell(&["dumpsys", "package", app_id]).and_then(|o| parse_installed_signer(&o))
+    {
+      eprintln!("Signer digest of the installed app: {}", signer);
+    }
+    if !force_reinstall {
+      return Err(Error::Config(format!(
+        "Installing failed with {}, use --force-reinstall to uninstall the installed app first, \
+        which deletes its data",
+        code
+      )));
+    }
+    eprintln!(
+      "WARNING: Uninstalling {} because of --force-reinstall, all of its data is deleted.",
+      app_id
+    );
+    check_command(adb_command().args(["uninstall", app_id]))?;
+    let output = adb_install(apk_path);
+    if !output.status.success() {
+      return Err(Error::command(get_adb_path().as_os_str(), output.status));
+    }
+    return Ok(());
+  }
+
+  Err(Error::Config(match explain_install_failure(&code) {
+    Some(explanation) => format!("Installing failed with {}: {}", code, explanation),
+    None => format!("Installing failed with {}, see the adb output above", code),
+  }))
+}
+
+#[cfg(test)]
+mod test {
+  use std::fs::write;
+  use std::path::Path;
+  use std::thread::sleep;
+  use std::time::Duration;
+
+  use tempfile::tempdir;
+
+  use crate::adb::{
+    explain_install_failure, find_current_idsig, get_idsig_path, is_partially_installed,
+    parse_adb_devices, parse_install_failure, parse_installed_signer, parse_reverse_ports,
+    resolve_device, ReversePort,
+  };
+
+  #[test]
+  fn idsig_next_to_apk() {
+    assert_eq!(
+      get_idsig_path(Path::new("out/app-release.apk")),
+      Path::new("out/app-release.apk.idsig")
+    );
+
+    let dir = tempdir().unwrap();
+    let apk = dir.path().join("app.apk");
+    write(&apk, "apk").unwrap();
+    assert_eq!(find_current_idsig(&apk), None);
+
+    write(get_idsig_path(&apk), "idsig").unwrap();
+    assert_eq!(find_current_idsig(&apk), Some(get_idsig_path(&apk)));
+
+    //an apk rebuilt without v4 signing
+    sleep(Duration::from_millis(20));
+    write(&apk, "new apk").unwrap();
+    assert_eq!(find_current_idsig(&apk), None);
+  }
+
+  #[test]
+  fn partial_install() {
+    let installed = "Packages:\n  Package [com.example.app] (1a2b3c):\n    isLoading=false\n    loadingProgress=100%\n";
+    let partial = "Packages:\n  Package [com.example.app] (1a2b3c):\n    isLoading=true\n    loadingProgress=0%\n";
+    assert!(!is_partially_installed(installed));
+    assert!(is_partially_installed(partial));
+    assert!(!is_partially_installed(""));
+  }
+
+  #[test]
+  fn install_failures() {
+    let output = "Performing Streamed Install\nadb: failed to install app.apk: Failure \
+      [INSTALL_FAILED_UPDATE_INCOMPATIBLE: Package com.example.game signatures do not match \
+      newer version; ignoring!]\n";
+    assert_eq!(
+      parse_install_failure(output).as_deref(),
+      Some("INSTALL_FAILED_UPDATE_INCOMPATIBLE")
+    );
+    assert_eq!(
+      parse_install_failure("Failure [INSTALL_FAILED_OLDER_SDK]").as_deref(),
+      Some("INSTALL_FAILED_OLDER_SDK")
+    );
+    assert_eq!(parse_install_failure("Success\n"), None);
+    assert!(explain_install_failure("INSTALL_FAILED_OLDER_SDK")
+      .unwrap()
+      .contains("min_sdk_version"));
+    assert_eq!(
+      explain_install_failure("INSTALL_FAILED_SOMETHING_NEW"),
+      None
+    );
+  }
+
+  #[test]
+  fn installed_signer() {
+    let dumpsys = "Packages:\n  Package [com.example.game] (1a2b3c):\n    \
+      signatures=PackageSignatures{9fbbc6a version:2, signatures:[1fae6b4e], past signatures:[]}\n";
+    assert_eq!(parse_installed_signer(dumpsys).as_deref(), Some("1fae6b4e"));
+    let old_dumpsys = "    signatures=PackageSignatures{419a6c3 [3a9a6c0d]}\n";
+    assert_eq!(
+      parse_installed_signer(old_dumpsys).as_deref(),
+      Some("3a9a6c0d")
+    );
+    assert_eq!(parse_installed_signer("Unable to find package"), None);
+  }
+
+  #[test]
+  fn reverse_ports() {
+    assert_eq!(
+      "3000".parse(),
+      Ok(ReversePort {
+        device: 3000,
+        host: 3000
+      })
+    );
+    assert_eq!(
+      "8080:3000".parse(),
+      Ok(ReversePort {
+        device: 8080,
+        host: 3000
+      })
+    );
+    assert!("0".parse::<ReversePort>().is_err());
+    assert!("3000:http".parse::<ReversePort>().is_err());
+    assert!("70000".parse::<ReversePort>().is_err());
+
+    let metadata = "adb_reverse = [3000, \"8081:8080\"]"
+      .parse::<toml::Table>()
+      .unwrap();
+    assert_eq!(
+      parse_reverse_ports(&metadata["adb_reverse"]),
+      Ok(vec![
+        ReversePort {
+          device: 3000,
+          host: 3000
+        },
+        ReversePort {
+          device: 8081,
+          host: 8080
+        }
+      ])
+    );
+    assert!(parse_reverse_ports(&toml::Value::Integer(3000)).is_err());
+    assert!(parse_reverse_ports(&toml::Value::Array(vec![toml::Value::Integer(-1)])).is_err());
+  }
+
+  #[test]
+  fn device_selection() {
+    let devices = parse_adb_devices(
+      "* daemon started successfully\nList of devices attached\nR58M123ABC             device usb:1-1 product:a52q model:SM_A525F\nemulator-5554          device product:sdk_gphone64 model:sdk_gphone64\n0A141FDD40091F         unauthorized usb:1-2 transport_id:3\n\n",
+    );
+    assert_eq!(devices.len(), 3);
+    assert_eq!(devices[0].serial, "R58M123ABC");
+    assert_eq!(devices[2].state, "unauthorized");
+    assert_eq!(
+      devices[2].to_string(),
+      "0A141FDD40091F (unauthorized) usb:1-2 transport_id:3"
+    );
+
+    assert_eq!(
+      resolve_device(Some("emulator-5554"), Some("R58M123ABC"), None, &devices),
+      Ok(("emulator-5554".to_string(), "--device"))
+    );
+    assert_eq!(
+      resolve_device(None, Some("R58M123ABC"), Some("emulator-5554"), &devices),
+      Ok(("R58M123ABC".to_string(), "ANDROID_SERIAL"))
+    );
+    assert_eq!(
+      resolve_device(None, None, Some("emulator-5554"), &devices),
+      Ok(("emulator-5554".to_string(), "default_device"))
+    );
+    let e = resolve_device(None, None, None, &devices).unwrap_err();
+    assert!(e.starts_with("Multiple devices are connected"), "{}", e);
+    assert!(
+      e.contains("\n  emulator-5554 product:sdk_gphone64"),
+      "{}",
+      e
+    );
+    assert!(resolve_device(Some("0A141FDD40091F"), None, None, &devices)
+      .unwrap_err()
+      .ends_with("is unauthorized"));
+    assert!(resolve_device(None, None, Some("missing"), &devices)
+      .unwrap_err()
+      .contains("isn't connected"));
+
+    //the unauthorized device can't be meant
+    assert_eq!(
+      resolve_device(None, None, None, &devices[1..]),
+      Ok(("emulator-5554".to_string(), "the only connected device"))
+    );
+    assert!(resolve_device(None, None, None, &[])
+      .unwrap_err()
+      .contains("no devices are connected"));
+  }
+}
@@ -0,0 +1,1115 @@
+use std::collections::BTreeMap;
+use std::fs::{canonicalize, read_to_string};
+use std::io::{stderr, IsTerminal};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::abi_check::*;
+use crate::adb::*;
+use crate::android_project::*;
+use crate::arm_mode::*;
+use crate::artifacts::*;
+use crate::baseline_profile::*;
+use crate::bridge_methods::*;
+use crate::build_bin_as_lib::*;
+use crate::build_info::*;
+use crate::cargo_profile::*;
+use crate::ci::*;
+use crate::cli::NetworkArgs;
+use crate::command_runner::*;
+use crate::error::*;
+use crate::features::*;
+use crate::flavors::*;
+use crate::hooks::*;
+use crate::last_build::*;
+use crate::launch_profile::*;
+use crate::local_config::*;
+use crate::native_libs::*;
+use crate::offline::*;
+use crate::packaging::*;
+use crate::placeholders::*;
+use crate::preflight::*;
+use crate::project_template::*;
+use crate::reproducible::*;
+use crate::rust_logcat::*;
+use crate::sdk::*;
+use crate::sdl_defaults::*;
+use crate::signing::*;
+use crate::symbols_bundle::*;
+use crate::targets::*;
+use crate::template_compat::*;
+use crate::util::*;
+use crate::warnings::*;
+use crate::BuildProfile;
+
+/// Keystore options of release builds, combined with `[package.metadata.android.signing]`. Without
+/// a keystore in either, release builds are signed with the Android debug keystore.
+///
+/// ```
+/// use cargo_sdl_apk::Signing;
+///
+/// let signing = Signing::new()
+///   .keystore("release.jks")
+///   .keystore_pass("env:KS_PASS")
+///   .v4_signing(false);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Signing {
+  keystore: Option<PathBuf>,
+  keystore_pass: Option<String>,
+  key_pass: Option<String>,
+  v4_signing: Option<bool>,
+}
+
+impl Signing {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Keystore file to sign with, relative to the manifest dir.
+  pub fn keystore(mut self, keystore: impl Into<PathBuf>) -> Self {
+    self.keystore = Some(keystore.into());
+    self
+  }
+
+  /// Keystore password in apksigner's format, e.g. `pass:secret` or `env:KS_PASS`.
+  pub fn keystore_pass(mut self, pass: impl Into<String>) -> Self {
+    self.keystore_pass = Some(pass.into());
+    self
+  }
+
+  /// Key password in apksigner's format, defaults to the keystore password.
+  pub fn key_pass(mut self, pass: impl Into<String>) -> Self {
+    self.key_pass = Some(pass.into());
+    self
+  }
+
+  /// Whether to create a v4 signature for incremental installs. By default it is created if the
+  /// min sdk version and the build-tools allow it.
+  pub fn v4_signing(mut self, enabled: bool) -> Self {
+    self.v4_signing = Some(enabled);
+    self
+  }
+}
+
+/// Builds the APK of a package, like `cargo sdl-apk build`.
+///
+/// ```no_run
+/// use cargo_sdl_apk::{AndroidBuild, BuildProfile, Signing};
+///
+/// let output = AndroidBuild::new("games/rocks/Cargo.toml")
+///   .profile(BuildProfile::Release)
+///   .targets(["aarch64-linux-android", "armv7-linux-androideabi"])
+///   .signing(Signing::new().keystore("release.jks").keystore_pass("env:KS_PASS"))
+///   .assets("games/rocks/assets")
+///   .build()?;
+/// println!("Built {} into {}", output.app_id, output.apk.display());
+///
+/// cargo_sdl_apk::install(&output)?;
+/// # Ok::<(), cargo_sdl_apk::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct AndroidBuild {
+  manifest_path: PathBuf,
+  build_target: BuildTarget,
+  profile: BuildProfile,
+  cargo_profile: Option<String>,
+  targets: Vec<String>,
+  signing: Signing,
+  assets: Option<PathBuf>,
+  reproducible: bool,
+  install_sdk_packages: bool,
+  accept_licenses: bool,
+  keep_local: bool,
+  allow_missing_icon: bool,
+  allow_32bit_only: bool,
+  strict_sdl_check: bool,
+  features: FeatureSelection,
+  flavor: Option<String>,
+  metadata_profile: Option<String>,
+  reverse_ports: Vec<ReversePort>,
+  gradle_tasks: Vec<String>,
+  artifacts: Vec<Artifact>,
+  project_dir: Option<PathBuf>,
+  gradle_user_home: Option<PathBuf>,
+  symbols_bundle: Option<PathBuf>,
+  device: Option<String>,
+  rust_logcat: bool,
+  launch: Launch,
+  verbose: bool,
+  network: NetworkArgs,
+  deny_warnings: bool,
+  allowed_warnings: Vec<String>,
+}
+
+/// The result of a build.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct BuildOutput {
+  /// The APK, copied to `target/<profile>/app.apk`.
+  pub apk: PathBuf,
+  /// The AAB if one was built, signed with the same key as the APK and copied to
+  /// `target/<profile>/app.aab`.
+  pub aab: Option<PathBuf>,
+  /// The v4 signature next to the APK, if one was created.
+  pub idsig: Option<PathBuf>,
+  /// The native library packaged as `libmain.so`, by target triple.
+  pub libraries: BTreeMap<String, PathBuf>,
+  /// The application id, including the suffix of the profile.
+  pub app_id: String,
+  pub profile: BuildProfile,
+  /// The generated android project.
+  pub project_dir: PathBuf,
+  /// The manifest the APK was built from.
+  pub manifest_path: PathBuf,
+  /// Who signed the APK, unknown for an earlier build.
+  pub signing_mode: Option<SigningMode>,
+  /// The product flavor built.
+  pub flavor: Option<String>,
+  /// Ports `run` forwards from the device to the host while the app runs.
+  pub reverse_ports: Vec<ReversePort>,
+  /// The `extra_gradle_tasks` run after the assembly, with their durations.
+  pub extra_gradle_tasks: Vec<(String, Duration)>,
+  /// Serial of the device `install` and `run` use. If `None`, `ANDROID_SERIAL`, the
+  /// `default_device` of the local config or the only connected device is used.
+  pub device: Option<String>,
+  /// Whether `run` filters the log for Rust, see [AndroidBuild::rust_logcat].
+  pub rust_logcat: bool,
+  /// The intent extras and launch profile `run` starts the app with.
+  pub launch: Launch,
+  /// The symbols collected with [AndroidBuild::symbols_bundle].
+  pub symbols_bundle: Option<SymbolsBundle>,
+}
+
+impl AndroidBuild {
+  /// Creates a debug build of the bin target of the package, for the targets configured in the
+  /// android metadata.
+  pub fn new(manifest_path: impl Into<PathBuf>) -> Self {
+    Self {
+      manifest_path: manifest_path.into(),
+      build_target: BuildTarget::Bin,
+      profile: BuildProfile::Debug,
+      cargo_profile: None,
+      targets: vec![],
+      signing: Signing::default(),
+      assets: None,
+      reproducible: false,
+      install_sdk_packages: false,
+      accept_licenses: false,
+      keep_local: false,
+      allow_missing_icon: false,
+      allow_32bit_only: false,
+      strict_sdl_check: false,
+      features: FeatureSelection::default(),
+      flavor: None,
+      metadata_profile: None,
+      reverse_ports: vec![],
+      gradle_tasks: vec![],
+      artifacts: vec![],
+      project_dir: None,
+      gradle_user_home: None,
+      symbols_bundle: None,
+      device: None,
+      rust_logcat: false,
+      launch: Launch::default(),
+      verbose: false,
+      network: NetworkArgs::default(),
+      deny_warnings: false,
+      allowed_warnings: vec![],
+    }
+  }
+
+  /// Builds the example of the given name instead of the bin target.
+  pub fn example(mut self, name: impl Into<String>) -> Self {
+    self.build_target = BuildTarget::Example(name.into());
+    self
+  }
+
+  pub fn profile(mut self, profile: BuildProfile) -> Self {
+    self.profile = profile;
+    self
+  }
+
+  /// Builds the native library with the cargo profile, like `cargo build --profile <name>`. It
+  /// replaces the build profile: `dev` builds a debug app, `release` a release app and a custom
+  /// profile the one of the built-in profile it inherits from.
+  pub fn cargo_profile(mut self, name: impl Into<String>) -> Self {
+    self.cargo_profile = Some(name.into());
+    self
+  }
+
+  /// The cargo profile to build with, the requested one or the one of the build profile.
+  fn resolve_cargo_profile(&self, manifest_path: &Path) -> Result<CargoProfile> {
+    match &self.cargo_profile {
+      Some(name) => Ok(get_cargo_profile(manifest_path, name)?),
+      None => Ok(CargoProfile::for_build_profile(self.profile)),
+    }
+  }
+
+  /// The android targets to build, e.g. `aarch64-linux-android`. Defaults to the configured ones.
+  pub fn targets<I, S>(mut self, targets: I) -> Self
+  where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+  {
+    self.targets = targets
+      .into_iter()
+      .map(|t| t.as_ref().to_string())
+      .collect();
+    self
+  }
+
+  /// Features to activate for all targets, in addition to the `target_features` of the android
+  /// metadata. Each entry may list several features separated by commas or spaces, like cargo's
+  /// `--features`.
+  pub fn features<I, S>(mut self, features: I) -> Self
+  where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+  {
+    let features: Vec<S> = features.into_iter().collect();
+    self.features.features = split_features(&features);
+    self
+  }
+
+  pub fn all_features(mut self, all_features: bool) -> Self {
+    self.features.all_features = all_features;
+    self
+  }
+
+  /// Don't activate the default features. Default features listed in `target_features` are still
+  /// activated for their target.
+  pub fn no_default_features(mut self, no_default_features: bool) -> Self {
+    self.features.no_default_features = no_default_features;
+    self
+  }
+
+  /// Print details of the build configuration, like the features each target is built with.
+  pub fn verbose(mut self, verbose: bool) -> Self {
+    self.verbose = verbose;
+    self
+  }
+
+  /// Fail the build after it finished when it raised warnings that aren't allowed.
+  pub fn deny_warnings(mut self, deny_warnings: bool) -> Self {
+    self.deny_warnings = deny_warnings;
+    self
+  }
+
+  /// Silence the warnings of the id, e.g. `default-icon`. `--explain <id>` describes an id.
+  pub fn allow_warning(mut self, id: impl Into<String>) -> Self {
+    self.allowed_warnings.push(id.into());
+    self
+  }
+
+  /// The product flavor of `[[package.metadata.android.flavors]]` to build. Packages with flavors
+  /// need exactly one selected.
+  pub fn flavor(mut self, name: impl Into<String>) -> Self {
+    self.flavor = Some(name.into());
+    self
+  }
+
+  /// The profile whose `[package.metadata.android.profile.<name>]` overrides apply, instead of
+  /// the one named like the build profile, `debug` or `release`.
+  pub fn metadata_profile(mut self, name: impl Into<String>) -> Self {
+    self.metadata_profile = Some(name.into());
+    self
+  }
+
+  /// Signing of release builds.
+  /// Forwards a port of the device to the host while the app runs, in addition to the
+  /// `adb_reverse` ports of the android metadata.
+  pub fn reverse_port(mut self, port: ReversePort) -> Self {
+    self.reverse_ports.push(port);
+    self
+  }
+
+  /// Runs a gradle task together with the task assembling the APK, e.g. a task of an additional
+  /// gradle module.
+  pub fn gradle_task(mut self, task: impl Into<String>) -> Self {
+    self.gradle_tasks.push(task.into());
+    self
+  }
+
+  /// Builds the artifacts, e.g. the APK and the AAB for Google Play in one gradle run. The APK is
+  /// always among them. Without this `artifacts` of the android metadata is used, by default only
+  /// the APK.
+  pub fn artifacts(mut self, artifacts: impl IntoIterator<Item = Artifact>) -> Self {
+    self.artifacts = artifacts.into_iter().collect();
+    self
+  }
+
+  /// Generates the android project in the directory instead of `target/android-project-<profile>`,
+  /// so parallel builds of the same package don't share it.
+  pub fn project_dir(mut self, project_dir: impl Into<PathBuf>) -> Self {
+    self.project_dir = Some(project_dir.into());
+    self
+  }
+
+  /// Starts gradle with this `GRADLE_USER_HOME`, e.g. a cache dir mounted in CI so the gradle
+  /// distribution and dependencies aren't downloaded by every job. Overrides the env var and
+  /// `gradle_user_home` of the android metadata.
+  pub fn gradle_user_home(mut self, gradle_user_home: impl Into<PathBuf>) -> Self {
+    self.gradle_user_home = Some(gradle_user_home.into());
+    self
+  }
+
+  /// Collects the unstripped libraries, a `native-debug-symbols.zip`, the R8 mapping if there is
+  /// one and a `symbols.json` describing them in this directory after a release build, for
+  /// uploading to crash reporting services. See [BuildOutput::symbols_bundle].
+  pub fn symbols_bundle(mut self, dir: impl Into<PathBuf>) -> Self {
+    self.symbols_bundle = Some(dir.into());
+    self
+  }
+
+  /// Serial of the device to install and run the APK on, like `adb -s`.
+  pub fn device(mut self, serial: impl Into<String>) -> Self {
+    self.device = Some(serial.into());
+    self
+  }
+
+  /// Makes `run` print the log of the app with Rust panics as one block, demangled symbols and the
+  /// frames of libmain.so in crash dumps resolved with the local symbols.
+  pub fn rust_logcat(mut self, rust_logcat: bool) -> Self {
+    self.rust_logcat = rust_logcat;
+    self
+  }
+
+  /// An extra of the intent `run` starts the app with, after the ones of the launch profile.
+  pub fn intent_extra(mut self, extra: IntentExtra) -> Self {
+    self.launch.extras.push(extra);
+    self
+  }
+
+  /// The launch profile `[launch.profile.<name>]` of the local config `run` starts the app with.
+  /// It is remembered for the device and used again by later runs without extras.
+  pub fn launch_profile(mut self, name: impl Into<String>) -> Self {
+    self.launch.profile = Some(name.into());
+    self
+  }
+
+  /// Makes `run` start the app without the launch profile last used on the device, and forget it.
+  pub fn no_launch_profile(mut self, no_launch_profile: bool) -> Self {
+    self.launch.no_profile = no_launch_profile;
+    self
+  }
+
+  pub fn signing(mut self, signing: Signing) -> Self {
+    self.signing = signing;
+    self
+  }
+
+  /// Directory copied to the assets of the APK.
+  pub fn assets(mut self, assets_dir: impl Into<PathBuf>) -> Self {
+    self.assets = Some(assets_dir.into());
+    self
+  }
+
+  /// Produce byte-identical unsigned APKs for unchanged sources, see `build --reproducible`.
+  pub fn reproducible(mut self, reproducible: bool) -> Self {
+    self.reproducible = reproducible;
+    self
+  }
+
+  /// Install missing build-tools and platforms using sdkmanager, optionally accepting their
+  /// licenses.
+  pub fn install_sdk_packages(mut self, install: bool, accept_licenses: bool) -> Self {
+    self.install_sdk_packages = install;
+    self.accept_licenses = accept_licenses;
+    self
+  }
+
+  /// Don't regenerate files in the android project which were modified by hand.
+  pub fn keep_local(mut self, keep_local: bool) -> Self {
+    self.keep_local = keep_local;
+    self
+  }
+
+  /// Build with the default icon if the configured icon can't be used, instead of failing.
+  pub fn allow_missing_icon(mut self, allow: bool) -> Self {
+    self.allow_missing_icon = allow;
+    self
+  }
+
+  /// Build a release with 32-bit targets lacking their 64-bit counterpart, which Google Play
+  /// rejects, e.g. for sideloading.
+  pub fn allow_32bit_only(mut self, allow: bool) -> Self {
+    self.allow_32bit_only = allow;
+    self
+  }
+
+  /// Fail if the SDL source doesn't match the sdl2 crate.
+  pub fn strict_sdl_check(mut self, strict: bool) -> Self {
+    self.strict_sdl_check = strict;
+    self
+  }
+
+  /// Run without accessing the network, failing if anything needed isn't available locally.
+  pub fn offline(mut self, offline: bool) -> Self {
+    self.network.offline = offline;
+    self
+  }
+
+  pub(crate) fn network(mut self, network: NetworkArgs) -> Self {
+    self.network = network;
+    self
+  }
+
+  /// The features selected for the build, with the `cargo_features` of the flavor.
+  fn flavor_features(&self, flavor: Option<&Flavor>) -> FeatureSelection {
+    let mut features = self.features.clone();
+    for feature in flavor.iter().flat_map(|flavor| &flavor.cargo_features) {
+      if !features.features.contains(feature) {
+        features.features.push(feature.clone());
+      }
+    }
+    features
+  }
+
+  /// Generates the android project of the build from its template, without building the native
+  /// library or running gradle, and returns its dir. Useful to inspect the generated manifest and
+  /// build.gradle, or to test templates without an Android sdk. Signing isn't configured.
+  pub fn generate_project(&self) -> Result<PathBuf> {
+    let manifest_path = canonicalize(&self.manifest_path)
+      .map_err(|_| Error::Config(format!("manifest {:?} not found", self.manifest_path)))?;
+    anchor_env_paths();
+    configure_warnings(&self.allowed_warnings, self.deny_warnings).map_err(Error::Config)?;
+    //the manifest is read all over the build, fail early with the location of syntax errors
+    read_toml_file(&manifest_path)?;
+    let cargo_profile = self.resolve_cargo_profile(&manifest_path)?;
+    let profile = cargo_profile.build_profile;
+    let manifest_dir = manifest_path.parent().unwrap();
+    set_metadata_profile(Some(
+      self
+        .metadata_profile
+        .clone()
+        .unwrap_or_else(|| profile.to_string()),
+    ));
+    let project_dir =
+      resolve_android_project_dir(manifest_dir, profile, self.project_dir.as_deref());
+    let flavors = get_flavors(&manifest_path)?;
+    let flavor = select_flavor(&flavors, self.flavor.as_deref())?;
+    let targets = select_android_targets(&manifest_path, &self.targets)?;
+    let targets: Vec<&str> = targets.iter().map(|t| t.as_str()).collect();
+    let haptics = uses_haptics_with(
+      &manifest_path,
+      &self.flavor_features(flavor.as_ref()),
+      &targets,
+    )?;
+    let project_dir = generate_android_project(
+      &manifest_path,
+      profile,
+      ProjectOptions {
+        keep_local: self.keep_local,
+        allow_missing_icon: self.allow_missing_icon,
+        flavors,
+        flavor,
+        project_dir: self.project_dir.as_ref().map(|_| project_dir),
+        build_info: Some(get_build_info(&manifest_path, profile)?),
+        haptics,
+        verbose: self.verbose,
+        ..ProjectOptions::default()
+      },
+    )?;
+    print_warning_summary();
+    check_denied_warnings().map_err(Error::Config)?;
+    Ok(project_dir)
+  }
+
+  /// Builds the APK. The output of the build steps is printed and written to the build log in the
+  /// target dir.
+  pub fn build(&self) -> Result<BuildOutput> {
+    let manifest_path = canonicalize(&self.manifest_path)
+      .map_err(|_| Error::Config(format!("manifest {:?} not found", self.manifest_path)))?;
+    anchor_env_paths();
+    configure_warnings(&self.allowed_warnings, self.deny_warnings).map_err(Error::Config)?;
+    //the manifest is read all over the build, fail early with the location of syntax errors
+    read_toml_file(&manifest_path)?;
+    let cargo_profile = self.resolve_cargo_profile(&manifest_path)?;
+    let profile = cargo_profile.build_profile;
+    let manifest_dir = manifest_path.parent().unwrap();
+    init_build_log(manifest_dir);
+    set_network_args(self.network);
+    //a typo in the launch profile shouldn't surface only after the build
+    if let Some(name) = &self.launch.profile {
+      parse_launch_profile(&read_local_config(manifest_dir)?, name)?;
+    }
+    let metadata_profile = self
+      .metadata_profile
+      .clone()
+      .unwrap_or_else(|| profile.to_string());
+    set_metadata_profile(Some(metadata_profile.clone()));
+    if self.verbose {
+      for value in get_profile_overrides(&manifest_path, &metadata_profile)? {
+        println!("profile {}: {}", metadata_profile, value);
+      }
+    }
+    let mut reverse_ports = get_metadata_reverse_ports(&manifest_path)?;
+    for port in &self.reverse_ports {
+      reverse_ports.retain(|p| p.device != port.device);
+      reverse_ports.push(*port);
+    }
+    let artifacts = select_artifacts(&manifest_path, &self.artifacts)?;
+    let project_dir =
+      resolve_android_project_dir(manifest_dir, profile, self.project_dir.as_deref());
+    let flavors = get_flavors(&manifest_path)?;
+    let flavor = select_flavor(&flavors, self.flavor.as_deref())?;
+    if let Some(flavor) = &flavor {
+      println!("Building flavor {}", flavor.name);
+    }
+    let features = self.flavor_features(flavor.as_ref());
+    run_preflight_checks(&manifest_path, self.strict_sdl_check)?;
+    ensure_sdk_packages(
+      &manifest_path,
+      self.install_sdk_packages,
+      self.accept_licenses,
+    )?;
+    check_template_compatibility(&manifest_path)?;
+
+    if self.reproducible {
+      let epoch = get_source_date_epoch(manifest_dir);
+      println!("Building reproducibly with SOURCE_DATE_EPOCH={}", epoch);
+      std::env::set_var("SOURCE_DATE_EPOCH", epoch);
+    }
+
+    let signing = &self.signing;
+    let signing_config = get_signing_config(
+      &manifest_path,
+      signing
+        .keystore
+        .as_ref()
+        .map(|path| path.to_string_lossy().to_string()),
+      signing.keystore_pass.clone(),
+      signing.key_pass.clone(),
+    )?;
+    let v4_signing = get_v4_signing(&manifest_path, signing.v4_signing)?;
+    let signing_mode = get_signing_mode(&manifest_path)?;
+    if artifacts.contains(&Artifact::Aab)
+      && profile == BuildProfile::Release
+      && signing_mode == SigningMode::Standalone
+    {
+      //fail before cargo runs if the AAB can't be signed like the APK
+      if let Some(signing_config) = &signing_config {
+        if let Err(e) = GradleSigning::for_aab(signing_config) {
+          return Err(Error::Config(format!(
+            "Invalid signing configuration: {}",
+            e
+          )));
+        }
+      }
+    }
+
+    //the same values reach Rust at compile time and Java as BuildConfig fields
+    let build_info = get_build_info(&manifest_path, profile)?;
+    build_info.set_cargo_env();
+    set_bridge_env(&manifest_path)?;
+
+    let targets = select_android_targets(&manifest_path, &self.targets)?;
+    let targets: Vec<&str> = targets.iter().map(|t| t.as_str()).collect();
+    if profile == BuildProfile::Release && !self.allow_32bit_only {
+      check_64bit_targets(&targets)?;
+    }
+    check_arm_mode(&manifest_path, &targets)?;
+    let activity_kind = get_activity_kind(&manifest_path)?;
+    if activity_kind == ActivityKind::Sdl {
+      build_sdl_for_android(&manifest_path, &targets)?;
+    }
+    let target_artifacts = build_bin_as_lib(
+      &manifest_path,
+      self.build_target.clone(),
+      &targets,
+      &cargo_profile,
+      &features,
+      self.verbose,
+    )?;
+    for library in target_artifacts.values() {
+      match activity_kind {
+        ActivityKind::Sdl => check_entry_point(library, SDL_MAIN_SYMBOL)?,
+        ActivityKind::Native => {
+          if let Err(warning) = check_entry_point(library, NATIVE_ACTIVITY_SYMBOL) {
+            warn(&ENTRY_POINT, warning);
+          }
+        }
+      }
+    }
+    let hook_assets = run_prepackage_hook(&manifest_path, profile, &targets)?;
+
+    let assets = match &self.assets {
+      Some(dir) => Some(
+        canonicalize(dir).map_err(|_| Error::Config(format!("assets dir {:?} not found", dir)))?,
+      ),
+      None => None,
+    };
+    let project_build = build_android_project(
+      &manifest_path,
+      &target_artifacts,
+      profile,
+      signing_config,
+      v4_signing,
+      self.reproducible,
+      ProjectOptions {
+        keep_local: self.keep_local,
+        allow_missing_icon: self.allow_missing_icon,
+        assets,
+        signing_mode,
+        flavors,
+        flavor: flavor.clone(),
+        gradle_tasks: self.gradle_tasks.clone(),
+        hook_assets,
+        artifacts: artifacts.clone(),
+        gradle_progress: !self.verbose && !is_ci_mode() && stderr().is_terminal(),
+        project_dir: self.project_dir.as_ref().map(|_| project_dir.clone()),
+        build_info: Some(build_info),
+        gradle_user_home: get_configured_gradle_user_home(
+          &manifest_path,
+          self.gradle_user_home.as_deref(),
+        )?,
+        haptics: uses_haptics_with(&manifest_path, &features, &targets)?,
+        verbose: self.verbose,
+      },
+    )?;
+    let flavor_name = flavor.as_ref().map(|f| f.name.clone());
+    let apk = copy_apk(
+      &manifest_path,
+      &project_dir,
+      profile,
+      flavor_name.as_deref(),
+    );
+    check_packaged_apk(&apk, &get_packaging(&manifest_path)?)?;
+    //gradle only compiles the baseline profile into non-debuggable builds
+    let check_baseline_profile =
+      profile == BuildProfile::Release && get_baseline_profile(&manifest_path)?.is_some();
+    if check_baseline_profile {
+      check_compiled_baseline_profile(&apk, APK_PROFILE_ENTRY)?;
+    }
+    let idsig = Some(get_idsig_path(&apk)).filter(|path| path.is_file());
+    let aab = artifacts.contains(&Artifact::Aab).then(|| {
+      copy_aab(
+        &manifest_path,
+        &project_dir,
+        profile,
+        flavor_name.as_deref(),
+      )
+    });
+    if let Some(aab) = aab.as_ref().filter(|_| check_baseline_profile) {
+      check_compiled_baseline_profile(aab, AAB_PROFILE_ENTRY)?;
+    }
+    //debug builds are always signed by gradle, with the debug key
+    let signing_mode = match profile {
+      BuildProfile::Debug => SigningMode::Gradle,
+      BuildProfile::Release => signing_mode,
+    };
+    let app_id = get_flavor_app_id(&manifest_path, profile, flavor.as_ref())?;
+    let symbols_dir = project_dir
+      .join("app/src")
+      .join(flavor_name.as_deref().unwrap_or("main"))
+      .join("jniLibs");
+    let symbols_bundle = match (&self.symbols_bundle, profile) {
+      (Some(dir), BuildProfile::Release) => {
+        let build_gradle = read_to_string(project_dir.join("app/build.gradle.kts"))
+          .or_else(|_| read_to_string(project_dir.join("app/build.gradle")))
+          .unwrap_or_default();
+        let mapping = project_dir.join(get_variant_mapping_file(flavor_name.as_deref(), profile));
+        let bundle = create_symbols_bundle(
+          dir,
+          &symbols_dir,
+          Some(mapping.as_path()).filter(|mapping| mapping.is_file()),
+          SymbolsVersion {
+            app_id: app_id.clone(),
+            version_code: parse_version_code(&build_gradle),
+            version_name: parse_version_name(&build_gradle),
+          },
+        )?;
+        println!(
+          "Symbols bundle: {} ({}{})",
+          bundle.dir.display(),
+          bundle
+            .libraries
+            .keys()
+            .cloned()
+            .collect::<Vec<_>>()
+            .join(", "),
+          if bundle.mapping.is_some() {
+            ", mapping.txt"
+          } else {
+            ""
+          }
+        );
+        Some(bundle)
+      }
+      (Some(_), BuildProfile::Debug) => {
+        warn(
+          &SYMBOLS_BUNDLE,
+          "Symbols bundles are only collected for release builds.",
+        );
+        None
+      }
+      (None, _) => None,
+    };
+    record_last_build(
+      manifest_dir,
+      profile,
+      &LastBuild {
+        apk: apk.clone(),
+        symbols_dir,
+        project_dir: project_dir.clone(),
+        app_id: app_id.clone(),
+        flavor: flavor_name.clone(),
+      },
+    );
+    print_warning_summary();
+    check_denied_warnings().map_err(Error::Config)?;
+    match project_build.apk_signer {
+      Some(apk_signer) => println!("Built {}, signed by {}", apk.display(), apk_signer),
+      None => println!("Built {}, signed by {}", apk.display(), signing_mode),
+    }
+    if let Some(aab) = &aab {
+      println!("Built {}", aab.display());
+    }
+    //build ids tell which build a library on a device or in a crash report came from
+    let mut abis = vec![];
+    for (target, library) in &target_artifacts {
+      let abi = get_target_android_name(&manifest_path, target)?;
+      let build_id = read_build_id(library).unwrap_or_else(|| "unknown".to_string());
+      abis.push(format!("{} build-id {}", abi, build_id));
+    }
+    abis.sort();
+    println!("Native libraries: {}", abis.join(", "));
+    for (task, duration) in &project_build.extra_gradle_tasks {
+      println!("Ran {} in {:.1}s", task, duration.as_secs_f64());
+    }
+    run_postbuild_hook(&manifest_path, profile, &apk, aab.as_deref())?;
+
+    Ok(BuildOutput {
+      apk,
+      aab,
+      idsig,
+      libraries: target_artifacts.into_iter().collect(),
+      app_id,
+      profile,
+      project_dir,
+      manifest_path,
+      signing_mode: Some(signing_mode),
+      flavor: flavor_name,
+      reverse_ports,
+      extra_gradle_tasks: project_build.extra_gradle_tasks,
+      device: self.device.clone(),
+      rust_logcat: self.rust_logcat,
+      launch: self.launch.clone(),
+      symbols_bundle,
+    })
+  }
+}
+
+/// Whether the features any of the targets is built with mention haptics, see [uses_haptics].
+fn uses_haptics_with(
+  manifest_path: &Path,
+  features: &FeatureSelection,
+  targets: &[&str],
+) -> Result<bool> {
+  let selections: Vec<FeatureSelection> =
+    get_target_feature_selections(manifest_path, features, targets, false)?
+      .into_iter()
+      .map(|(_, selection)| selection)
+      .collect();
+  Ok(uses_haptics(manifest_path, &selections)?)
+}
+
+impl BuildOutput {
+  /// The output of the last build of the profile, without building. Its libraries are unknown.
+  pub(crate) fn from_last_build(manifest_path: &Path, profile: BuildProfile) -> Result<Self> {
+    let manifest_dir = manifest_path.parent().unwrap();
+    if let Ok(last_build) = read_last_build(manifest_dir, profile) {
+      if last_build.apk.is_file() {
+        return Ok(Self {
+          idsig: Some(get_idsig_path(&last_build.apk)).filter(|path| path.is_file()),
+          aab: None,
+          apk: last_build.apk,
+          libraries: BTreeMap::new(),
+          app_id: last_build.app_id,
+          profile,
+          project_dir: last_build.project_dir,
+          manifest_path: manifest_path.to_path_buf(),
+          signing_mode: None,
+          flavor: last_build.flavor,
+          reverse_ports: get_metadata_reverse_ports(manifest_path)?,
+          extra_gradle_tasks: vec![],
+          device: None,
+          rust_logcat: false,
+          launch: Launch::default(),
+          symbols_bundle: None,
+        });
+      }
+    }
+    //builds of earlier versions weren't recorded
+    let project_dir = get_android_project_dir(manifest_dir, profile);
+    let apk = get_path_to_apk(&project_dir, profile, None);
+    if !apk.is_file() {
+      return Err(Error::Config(format!(
+        "No {} APK at {}, build it with `cargo sdl-apk build` first",
+        profile,
+        apk.display()
+      )));
+    }
+    Ok(Self {
+      idsig: Some(get_idsig_path(&apk)).filter(|path| path.is_file()),
+      aab: None,
+      apk,
+      libraries: BTreeMap::new(),
+      app_id: get_effective_app_id(manifest_path, profile)?,
+      profile,
+      project_dir,
+      manifest_path: manifest_path.to_path_buf(),
+      signing_mode: None,
+      flavor: None,
+      reverse_ports: get_metadata_reverse_ports(manifest_path)?,
+      extra_gradle_tasks: vec![],
+      device: None,
+      rust_logcat: false,
+      launch: Launch::default(),
+      symbols_bundle: None,
+    })
+  }
+}
+
+/// The APK gradle built.
+fn get_path_to_apk(
+  project_dir: &Path,
+  build_profile: BuildProfile,
+  flavor: Option<&str>,
+) -> PathBuf {
+  project_dir
+    .join(get_variant_apk_dir(flavor, build_profile))
+    .join(format!(
+      "app-{}.apk",
+      get_variant_file_name(flavor, build_profile)
+    ))
+}
+
+/// Where a package is copied to, `target/<profile>/app.<extension>` or `app-<flavor>.<extension>`
+/// for a flavor.
+fn get_target_package_path(
+  manifest_dir: &Path,
+  build_profile: BuildProfile,
+  flavor: Option<&str>,
+  extension: &str,
+) -> PathBuf {
+  manifest_dir
+    .join("target")
+    .join(build_profile.to_string())
+    .join(match flavor {
+      Some(flavor) => format!("app-{}.{}", flavor, extension),
+      None => format!("app.{}", extension),
+    })
+}
+
+/// Copies the AAB gradle bundled next to the APK, returning the copy, or the AAB of gradle if
+/// copying failed.
+fn copy_aab(
+  manifest_path: &Path,
+  project_dir: &Path,
+  build_profile: BuildProfile,
+  flavor: Option<&str>,
+) -> PathBuf {
+  let manifest_dir = manifest_path.parent().unwrap();
+  let aab_path = get_path_to_aab(project_dir, build_profile, flavor);
+  let target_path = get_target_package_path(manifest_dir, build_profile, flavor, "aab");
+  if let Err(e) = std::fs::copy(&aab_path, &target_path) {
+    eprintln!(
+      "Failed to copy aab from {} to {}: {}",
+      aab_path.display(),
+      target_path.display(),
+      e
+    );
+    return aab_path;
+  }
+  target_path
+}
+
+/// Copies the APK gradle built to `target/<profile>/app.apk`, or `app-<flavor>.apk` for a flavor,
+/// returning the copy, or the APK of gradle if copying failed.
+fn copy_apk(
+  manifest_path: &Path,
+  project_dir: &Path,
+  build_profile: BuildProfile,
+  flavor: Option<&str>,
+) -> PathBuf {
+  let manifest_dir = manifest_path.parent().unwrap();
+  let apk_path = get_path_to_apk(project_dir, build_profile, flavor);
+  let target_path = get_target_package_path(manifest_dir, build_profile, flavor, "apk");
+  if let Err(e) = std::fs::copy(&apk_path, &target_path) {
+    eprintln!(
+      "Failed to copy apk from {} to {}: {}",
+      apk_path.display(),
+      target_path.display(),
+      e
+    );
+    return apk_path;
+  }
+
+  //keep the v4 signature next to the apk for incremental installs
+  let idsig_path = get_idsig_path(&apk_path);
+  let target_idsig_path = get_idsig_path(&target_path);
+  if idsig_path.is_file() {
+    if let Err(e) = std::fs::copy(&idsig_path, &target_idsig_path) {
+      eprintln!(
+        "Failed to copy v4 signature from {} to {}: {}",
+        idsig_path.display(),
+        target_idsig_path.display(),
+        e
+      );
+    }
+  } else {
+    let _ = std::fs::remove_file(&target_idsig_path);
+  }
+  target_path
+}
+
+/// Installs the built APK on the connected device with adb, like `cargo sdl-apk install`.
+pub fn install(output: &BuildOutput) -> Result<()> {
+  select_device(&output.manifest_path, output.device.as_deref())?;
+  ensure_device_supports_apk(&output.manifest_path, &output.apk)?;
+  install_apk(&output.apk, &output.app_id, false)
+}
+
+/// Like [install], but if the installed app is signed with another key it is uninstalled first,
+/// like `cargo sdl-apk install --force-reinstall`. This deletes the data of the app.
+pub fn force_reinstall(output: &BuildOutput) -> Result<()> {
+  select_device(&output.manifest_path, output.device.as_deref())?;
+  ensure_device_supports_apk(&output.manifest_path, &output.apk)?;
+  install_apk(&output.apk, &output.app_id, true)
+}
+
+/// Starts the installed app and prints its log until it exits or adb is interrupted, like
+/// `cargo sdl-apk run`. The reverse ports are forwarded until then, and forwarded again if the
+/// device reconnects.
+pub fn run(output: &BuildOutput) -> Result<()> {
+  select_device(&output.manifest_path, output.device.as_deref())?;
+  let ports = &output.reverse_ports;
+  //Ctrl+C ends logcat, but the forwards still need to be removed afterwards
+  let interrupted = Arc::new(AtomicBool::new(false));
+  if !ports.is_empty() {
+    let handler_flag = interrupted.clone();
+    if let Err(e) = ctrlc::set_handler(move || handler_flag.store(true, Ordering::SeqCst)) {
+      eprintln!("WARNING: Unable to handle Ctrl+C: {}", e);
+    }
+    setup_reverse_ports(ports);
+  }
+  let result = launch_and_log(output, &interrupted);
+  remove_reverse_ports(ports);
+  result
+}
+
+fn launch_and_log(output: &BuildOutput, interrupted: &AtomicBool) -> Result<()> {
+  check_command(adb_command().args(["shell", "am", "force-stop", &output.app_id]))?;
+
+  let activity = format!(
+    "{}/{}",
+    output.app_id,
+    get_activity_kind(&output.manifest_path)
+      .map_err(Error::Config)?
+      .activity_class(&get_android_app_id(&output.manifest_path)?)
+  );
+  let serial = select_device(&output.manifest_path, output.device.as_deref())?;
+  let extras = resolve_launch_extras(
+    output.manifest_path.parent().unwrap(),
+    &serial,
+    &output.launch,
+  )
+  .map_err(Error::Config)?;
+  check_command(
+    adb_command()
+      .arg("shell")
+      .args(am_start_args(&activity, &extras)),
+  )?;
+
+  let pid = run_command_output(adb_command().args(["shell", "pidof", &output.app_id]))
+    .ok()
+    .and_then(|output| {
+      String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .parse::<u32>()
+        .ok()
+    })
+    .ok_or_else(|| {
+      Error::Config(format!(
+        "{} isn't running, it probably crashed on start",
+        output.app_id
+      ))
+    })?;
+  println!("Launched with PID: {}", pid);
+
+  //crash dumps are logged by debuggerd, so the rust filter needs the whole log
+  let mut rust_filter = output.rust_logcat.then(|| {
+    let symbols_dir = output
+      .project_dir
+      .join("app/src")
+      .join(output.flavor.as_deref().unwrap_or("main"))
+      .join("jniLibs");
+    RustLogFilter::new(pid, Some(Symbolizer::new(symbols_dir)))
+  });
+  loop {
+    let mut logcat = adb_command();
+    logcat.arg("logcat");
+    let status = match &mut rust_filter {
+      Some(filter) => stream_rust_logcat(&mut logcat, filter),
+      None => run_command(logcat.args(["-v", "color", "--pid", &pid.to_string()])),
+    };
+    if status.success() || interrupted.load(Ordering::SeqCst) {
+      return Ok(());
+    }
+    if output.reverse_ports.is_empty() {
+      return Err(Error::command(logcat.get_program(), status));
+    }
+    println!("Lost the device, waiting for it to reconnect...");
+    let status = run_command(adb_command().arg("wait-for-device"));
+    if interrupted.load(Ordering::SeqCst) {
+      return Ok(());
+    }
+    if !status.success() {
+      return Err(Error::command(get_adb_path().as_os_str(), status));
+    }
+    setup_reverse_ports(&output.reverse_ports);
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::fs::{create_dir_all, write};
+
+  use tempfile::tempdir;
+
+  use crate::android_build::{AndroidBuild, BuildOutput};
+  use crate::error::Error;
+  use crate::BuildProfile;
+
+  #[test]
+  fn errors_instead_of_exits() {
+    let dir = tempdir().unwrap();
+    let manifest = dir.path().join("Cargo.toml");
+    assert!(matches!(
+      AndroidBuild::new(&manifest).build(),
+      Err(Error::Config(message)) if message.contains("not found")
+    ));
+
+    write(&manifest, "[package]\nname = \"game\"\n").unwrap();
+    let error = BuildOutput::from_last_build(&manifest, BuildProfile::Release).unwrap_err();
+    assert!(error.to_string().starts_with("No release APK at "));
+    assert_eq!(error.exit_code(), 1);
+    //invalid metadata found while generating the project
+    write(
+      &manifest,
+      "[package]\nname = \"game\"\nversion = \"0.1.0\"\n\n[package.metadata.android]\n\
+      activity_kind = \"native\"\ngoogle_services = \"google-services.json\"\n",
+    )
+    .unwrap();
+    create_dir_all(dir.path().join("src")).unwrap();
+    write(dir.path().join("src/main.rs"), "fn main() {}\n").unwrap();
+    assert!(matches!(
+      AndroidBuild::new(&manifest).generate_project(),
+      Err(Error::Config(message)) if message.contains("google_services file")
+    ));
+  }
+}
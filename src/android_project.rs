@@ -1,387 +1,1858 @@
-use std::collections::HashMap;
-use std::fs;
-use std::fs::{copy, create_dir_all, read_to_string, write};
-use std::path::Path;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{
+  copy, create_dir_all, read, read_dir, read_to_string, remove_dir_all, remove_file, rename, write,
+};
+use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::time::{Duration, Instant};
 
-use fs_extra::{copy_items, dir::CopyOptions, remove_items};
-use lazy_static::lazy_static;
-use regex::{Regex, RegexBuilder};
-use symlink::symlink_dir;
+use fs_extra::remove_items;
+use symlink::{remove_symlink_dir, symlink_dir};
 
+use crate::app_info::*;
+use crate::application::*;
+use crate::arm_mode::*;
+use crate::artifacts::*;
+use crate::baseline_profile::*;
+use crate::bridge_methods::*;
+use crate::build_bin_as_lib::*;
+use crate::build_info::*;
+use crate::ci::*;
+use crate::command_runner::*;
+use crate::error::*;
+use crate::flavors::*;
+use crate::generated_files::*;
+use crate::google_services::*;
+use crate::gradle_diagnostics::*;
+use crate::gradle_dsl::*;
+use crate::gradle_progress::*;
+use crate::icon::*;
+use crate::keystore::*;
+use crate::local_properties::*;
+use crate::manifest::*;
+use crate::manifest_check::*;
+use crate::ndk::*;
+use crate::offline::*;
+use crate::packaging::*;
+use crate::placeholders::*;
+use crate::prebuilt_sdl::*;
+use crate::presets::*;
+use crate::project_template::*;
+use crate::reproducible::*;
+use crate::sdk::*;
+use crate::sdl_cache::*;
+use crate::sdl_defaults::*;
+use crate::sdl_java::*;
+use crate::signing::*;
+use crate::splash::*;
+use crate::split_debuginfo::*;
+use crate::targets::*;
+use crate::template_compat::*;
+use crate::template_prune::*;
+use crate::theme::*;
 use crate::util::*;
+use crate::warnings::*;
 use crate::BuildProfile;
 
-pub fn build_sdl_for_android(targets: &Vec<&str>, profile: BuildProfile) {
-  let p = Path::new(&*get_env_var("ANDROID_NDK_HOME")).join("ndk-build");
-
-  assert!(Command::new(&p)
-    .args([
-      "NDK_PROJECT_PATH=.",
-      "APP_BUILD_SCRIPT=./Android.mk",
-      "APP_PLATFORM=android-19"
-    ])
-    .current_dir(&*get_env_var("SDL"))
-    .status()
-    .expect(&format!("Failed to execute command: {:?}", p))
-    .success());
-
-  for rust_name in targets {
-    let android_name = get_target_android_name(rust_name);
-    let rust_dir = Path::new("target")
-      .join(rust_name)
-      .join(profile.to_string())
-      .join("deps");
-
-    create_dir_all(rust_dir).expect("Unable to create target dir");
-    let src = Path::new(&*get_env_var("SDL"))
-      .join("libs")
-      .join(android_name)
-      .join("libSDL2.so");
-    let dest = Path::new("target")
-      .join(rust_name)
+/// Directory the SDL libraries for the given target are staged in, to be linked against and
+/// packaged from.
+pub fn get_sdl_staging_dir(manifest_dir: &Path, rust_target_name: &str) -> PathBuf {
+  manifest_dir.join("target/sdl-libs").join(rust_target_name)
+}
+
+/// Copies the file unless the destination already has the same content. The copy is written next
+/// to the destination first and then renamed, so concurrent builds never see a partial file.
+fn stage_file(src: &Path, dest: &Path) {
+  let content = read(src).unwrap_or_else(|_| panic!("Unable to read {:?}", src));
+  if read(dest).ok().as_ref() == Some(&content) {
+    return;
+  }
+
+  let mut tmp = dest.as_os_str().to_owned();
+  tmp.push(format!(".{}.tmp", std::process::id()));
+  write(&tmp, &content).unwrap_or_else(|_| panic!("Unable to write {:?}", tmp));
+  rename(&tmp, dest).unwrap_or_else(|_| panic!("Unable to move {:?} to {:?}", tmp, dest));
+}
+
+/// Stages the SDL library for the target, where the linker and the android project take it from.
+fn stage_sdl_lib(manifest_dir: &Path, rust_target_name: &str, src: &Path) {
+  let staging_dir = get_sdl_staging_dir(manifest_dir, rust_target_name);
+  create_dir_all(&staging_dir)
+    .unwrap_or_else(|_| panic!("Unable to create directory {:?}", staging_dir));
+  stage_file(src, &staging_dir.join("libSDL2.so"));
+
+  //older versions copied SDL into cargo's deps dirs
+  for profile in [BuildProfile::Debug, BuildProfile::Release] {
+    let old_copy = manifest_dir
+      .join("target")
+      .join(rust_target_name)
       .join(profile.to_string())
       .join("deps/libSDL2.so");
-    copy(&src, &dest).expect(&format!(
-      "Unable to copy SDL dependencies from {:?} to {:?}",
-      src, dest
-    ));
+    if old_copy.is_file() {
+      let _ = remove_file(old_copy);
+    }
   }
 }
 
-pub fn get_target_android_name(rust_target_name: &str) -> &str {
-  match rust_target_name {
-    "aarch64-linux-android" => "arm64-v8a",
-    "armv7-linux-androideabi" => "armeabi-v7a",
-    "i686-linux-android" => "x86",
-    "x86_64-linux-android" => "x86_64",
+/// Builds SDL with ndk-build and stages it for the targets. Builds are cached per SDL source, NDK
+/// version, platform and `sdl_build` config, so ndk-build only runs if one of the ABIs isn't
+/// cached yet. With `prebuilt_sdl_libs` the prebuilt libraries are staged instead.
+pub fn build_sdl_for_android(manifest_path: &Path, targets: &Vec<&str>) -> Result<()> {
+  let manifest_dir = manifest_path.parent().unwrap();
+  let android_names = targets
+    .iter()
+    .map(|target| get_target_android_name(manifest_path, target))
+    .collect::<Result<Vec<String>, String>>()
+    .map_err(Error::Config)?;
+  if let Some(pattern) = get_prebuilt_sdl_libs(manifest_path)? {
+    println!("Using prebuilt SDL libraries {}", pattern);
+    let libraries = resolve_prebuilt_sdl_libs(manifest_dir, &pattern, &android_names)?;
+    for (rust_name, library) in targets.iter().zip(&libraries) {
+      stage_sdl_lib(manifest_dir, rust_name, library);
+    }
+    return Ok(());
+  }
+
+  let sdl_dir = get_env_path("SDL");
+  let ndk_dir = get_env_path("ANDROID_NDK_HOME");
+  let mut build_config = get_sdl_build_config(manifest_path)?;
+  if let Some(mode) = get_arm_mode(manifest_path)? {
+    build_config.args.extend(mode.ndk_build_args());
+  }
+  //the Application.mk of SDL only builds the default abis, others need to be named
+  if targets
+    .iter()
+    .any(|target| !DEFAULT_ANDROID_TARGETS.contains(target))
+  {
+    build_config
+      .args
+      .push(format!("APP_ABI={}", android_names.join(" ")));
+  }
+  let cache = SdlBuildKey::read(&sdl_dir, &ndk_dir, &build_config)
+    .zip(get_sdl_cache_dir())
+    .map(|(key, cache_dir)| (cache_dir.join(key.hash()), key));
+  let is_cached = |entry: &Path| {
+    android_names
+      .iter()
+      .all(|android_name| get_cached_sdl_lib(entry, android_name).is_file())
+  };
+
+  let libs_dir = match &cache {
+    Some((entry, _)) if is_cached(entry) => {
+      println!("Using cached SDL build {}", entry.display());
+      entry.clone()
+    }
     _ => {
-      panic!("Unknown target: {}", rust_target_name)
+      check_command(
+        ndk_script_command(&ndk_dir.join("ndk-build"))
+          .args(build_config.ndk_build_args())
+          .envs(&build_config.env)
+          .current_dir(&sdl_dir),
+      )?;
+      let built = sdl_dir.join("libs");
+      match &cache {
+        Some((entry, key)) => match store_sdl_build(entry, key, &built) {
+          Ok(()) => entry.clone(),
+          Err(e) => {
+            warn(
+              &SDL_CACHE,
+              format!(
+                "Unable to cache the SDL build in {}: {}",
+                entry.display(),
+                e
+              ),
+            );
+            built
+          }
+        },
+        None => built,
+      }
     }
+  };
+
+  for (rust_name, android_name) in targets.iter().zip(&android_names) {
+    stage_sdl_lib(
+      manifest_dir,
+      rust_name,
+      &get_cached_sdl_lib(&libs_dir, android_name),
+    );
   }
+  Ok(())
 }
 
-pub fn get_android_app_id(manifest_path: &Path) -> String {
-  get_toml_string(
-    manifest_path,
-    vec!["package", "metadata", "android", "package_name"],
+pub fn get_android_app_id(manifest_path: &Path) -> Result<String, TomlError> {
+  Ok(
+    get_toml_string(
+      manifest_path,
+      vec!["package", "metadata", "android", "package_name"],
+    )?
+    .unwrap_or("org.libsdl.app".to_string()),
   )
-  .unwrap_or("org.libsdl.app".to_string())
 }
 
-fn create_android_project(manifest_path: &Path, target_artifacts: &HashMap<String, String>) {
-  let manifest_dir = manifest_path.parent().unwrap();
+/// Suffix of the application id for the profile, from `debug_application_id_suffix` for debug
+/// builds so they can be installed next to the release version.
+pub fn get_application_id_suffix(
+  manifest_path: &Path,
+  profile: BuildProfile,
+) -> Result<Option<String>, TomlError> {
+  match profile {
+    BuildProfile::Debug => get_toml_string(
+      manifest_path,
+      [
+        "package",
+        "metadata",
+        "android",
+        "debug_application_id_suffix",
+      ],
+    ),
+    BuildProfile::Release => Ok(None),
+  }
+}
 
-  let appid = get_android_app_id(manifest_path);
-  let appname = get_toml_string(
-    manifest_path,
-    vec!["package", "metadata", "android", "title"],
-  )
-  .unwrap_or("Untitled".to_string());
-  let app_icon = get_toml_string(
+/// Suffix of the app name shown by the launcher, from `debug_app_name_suffix` for debug builds.
+pub fn get_app_name_suffix(
+  manifest_path: &Path,
+  profile: BuildProfile,
+) -> Result<Option<String>, TomlError> {
+  match profile {
+    BuildProfile::Debug => get_toml_string(
+      manifest_path,
+      ["package", "metadata", "android", "debug_app_name_suffix"],
+    ),
+    BuildProfile::Release => Ok(None),
+  }
+}
+
+/// The application id the app of the given profile is installed as.
+pub fn get_effective_app_id(
+  manifest_path: &Path,
+  profile: BuildProfile,
+) -> Result<String, TomlError> {
+  get_flavor_app_id(manifest_path, profile, None)
+}
+
+/// The application id the app of the given profile and flavor is installed as. Gradle appends the
+/// suffix of the flavor before the one of the profile.
+pub fn get_flavor_app_id(
+  manifest_path: &Path,
+  profile: BuildProfile,
+  flavor: Option<&Flavor>,
+) -> Result<String, TomlError> {
+  let mut appid = get_android_app_id(manifest_path)?;
+  if let Some(suffix) = flavor.and_then(|f| f.application_id_suffix.as_ref()) {
+    appid.push_str(suffix);
+  }
+  if let Some(suffix) = get_application_id_suffix(manifest_path, profile)? {
+    appid.push_str(&suffix);
+  }
+  Ok(appid)
+}
+
+/// The `<appid>/<activity>` component launching the app of the given profile.
+pub fn get_launch_component(manifest_path: &Path, profile: BuildProfile) -> Result<String, String> {
+  Ok(format!(
+    "{}/{}",
+    get_effective_app_id(manifest_path, profile)?,
+    get_activity_kind(manifest_path)?.activity_class(&get_android_app_id(manifest_path)?)
+  ))
+}
+
+/// Returns the directory of the generated android project for the given profile. Each profile
+/// gets its own project so debug and release builds don't invalidate each other's gradle state.
+pub fn get_android_project_dir(manifest_dir: &Path, profile: BuildProfile) -> PathBuf {
+  manifest_dir
+    .join("target")
+    .join(format!("android-project-{}", profile))
+}
+
+/// The directory to generate the android project in, the given one or else the one of the
+/// profile. A relative directory is relative to the working directory.
+pub fn resolve_android_project_dir(
+  manifest_dir: &Path,
+  profile: BuildProfile,
+  project_dir: Option<&Path>,
+) -> PathBuf {
+  match project_dir {
+    Some(dir) if dir.is_absolute() => dir.to_path_buf(),
+    Some(dir) => std::env::current_dir()
+      .expect("Unable to get the working directory")
+      .join(dir),
+    None => get_android_project_dir(manifest_dir, profile),
+  }
+}
+
+/// Points `target/android-project` to the project of the given profile, for backwards
+/// compatibility with tools expecting the project at its old location.
+fn link_latest_android_project(manifest_dir: &Path, profile: BuildProfile) -> Result<(), String> {
+  let link = manifest_dir.join("target/android-project");
+  //the old project dir holds the release key older versions generated
+  migrate_legacy_release_keystore(manifest_dir)?;
+
+  if link.is_symlink() {
+    remove_symlink_dir(&link)
+      .map_err(|e| format!("Unable to remove the link {}: {}", link.display(), e))?;
+  } else if link.is_dir() {
+    //it may have been edited by hand, so it is kept
+    let old = manifest_dir.join("target/android-project.old");
+    if old.exists() {
+      return Err(format!(
+        "{} is a directory, but android projects are now generated per profile. Move it out of \
+        the way, {} exists already",
+        link.display(),
+        old.display()
+      ));
+    }
+    rename(&link, &old).map_err(|e| {
+      format!(
+        "Unable to move {} to {}: {}",
+        link.display(),
+        old.display(),
+        e
+      )
+    })?;
+    println!(
+      "Moved {} to {}, android projects are now generated per profile",
+      link.display(),
+      old.display()
+    );
+  }
+
+  if let Err(e) = symlink_dir(format!("android-project-{}", profile), &link) {
+    eprintln!(
+      "Failed to link {:?} to the {} project: {}",
+      link, profile, e
+    );
+  }
+  Ok(())
+}
+
+/// Removes the generated android projects of all profiles.
+pub fn clean_android_projects(manifest_dir: &Path) {
+  let link = manifest_dir.join("target/android-project");
+  if link.is_symlink() {
+    remove_symlink_dir(&link).unwrap_or_else(|_| panic!("unable to remove link {:?}", link));
+  }
+
+  for profile in [BuildProfile::Debug, BuildProfile::Release] {
+    let project_dir = get_android_project_dir(manifest_dir, profile);
+    if project_dir.is_dir() {
+      println!("Removing {:?}", project_dir);
+      remove_dir_all(&project_dir).unwrap_or_else(|_| panic!("unable to remove {:?}", project_dir));
+    }
+  }
+}
+
+/// Removes the libraries of ABIs which aren't built, left in the jniLibs dir by builds for other
+/// targets. Gradle would package them, stale, next to the ones of this build.
+fn remove_stale_jni_libs(jni_libs_dir: &Path, abis: &[String]) -> Result<(), String> {
+  let Ok(entries) = read_dir(jni_libs_dir) else {
+    return Ok(());
+  };
+  for entry in entries.flatten() {
+    let path = entry.path();
+    let stale = entry
+      .file_name()
+      .to_str()
+      .is_none_or(|abi| !abis.iter().any(|a| a == abi));
+    if stale && path.is_dir() {
+      remove_dir_all(&path).map_err(|e| format!("Unable to remove {:?}: {}", path, e))?;
+    }
+  }
+  Ok(())
+}
+
+/// Options for generating the android project.
+#[derive(Debug, Clone, Default)]
+pub struct ProjectOptions {
+  /// Don't regenerate files which were modified by hand.
+  pub keep_local: bool,
+  /// Keep the default icon if the configured one can't be used, instead of failing.
+  pub allow_missing_icon: bool,
+  /// Directory copied to the assets of the APK.
+  pub assets: Option<PathBuf>,
+  /// Who signs release builds.
+  pub signing_mode: SigningMode,
+  /// All flavors of the package, written to build.gradle.
+  pub flavors: Vec<Flavor>,
+  /// The flavor built, whose source sets get the native library and its assets.
+  pub flavor: Option<Flavor>,
+  /// Gradle tasks run together with the assemble task.
+  pub gradle_tasks: Vec<String>,
+  /// Assets generated by the prepackage hook, added to the assets of the APK.
+  pub hook_assets: Option<PathBuf>,
+  /// The packages built, the APK and possibly the AAB.
+  pub artifacts: Vec<Artifact>,
+  /// Show a progress bar instead of the output of gradle, which is printed only if it fails.
+  pub gradle_progress: bool,
+  /// Directory to generate the project in instead of the one of the profile, e.g. to isolate
+  /// parallel builds of the same package.
+  pub project_dir: Option<PathBuf>,
+  /// Written to app/build.gradle as `BuildConfig` fields.
+  pub build_info: Option<BuildInfo>,
+  /// Passed to gradle as `GRADLE_USER_HOME`, see [get_configured_gradle_user_home].
+  pub gradle_user_home: Option<PathBuf>,
+  /// Whether the cargo features mention haptics, which adds the vibrate permission of
+  /// `sdl_defaults`.
+  pub haptics: bool,
+  /// Print details of the generation, like the `sdl_defaults` added.
+  pub verbose: bool,
+}
+
+/// Reads `extra_gradle_tasks` of the android metadata, tasks run after the APK was assembled.
+pub fn get_extra_gradle_tasks(manifest_path: &Path) -> Result<Vec<String>, String> {
+  match get_toml_entry(
     manifest_path,
-    vec!["package", "metadata", "android", "icon"],
-  );
+    ["package", "metadata", "android", "extra_gradle_tasks"],
+  )? {
+    None => Ok(vec![]),
+    Some(value) => value
+      .as_array()
+      .and_then(|tasks| {
+        tasks
+          .iter()
+          .map(|t| t.as_str().map(|t| t.to_string()))
+          .collect::<Option<Vec<String>>>()
+      })
+      .ok_or_else(|| "extra_gradle_tasks must be a list of gradle tasks".to_string()),
+  }
+}
 
-  // Copy template project from SDL
-  copy_items(
-    &[Path::new(&*get_env_var("SDL")).join("android-project")],
-    Path::new(manifest_dir).join("target"),
-    &CopyOptions::new().skip_exist(true),
+/// Copies the content of the given dir to the assets of the source set, replacing the assets
+/// copied before if `replace` is set.
+fn copy_assets(
+  assets_dir: &Path,
+  project_dir: &Path,
+  source_set: &str,
+  replace: bool,
+) -> Result<(), String> {
+  if !assets_dir.is_dir() {
+    return Err(format!("assets dir {:?} not found", assets_dir));
+  }
+  let dest = project_dir.join("app/src").join(source_set).join("assets");
+  if replace && dest.exists() {
+    remove_dir_all(&dest).map_err(|e| format!("Unable to remove {:?}: {}", dest, e))?;
+  }
+  create_dir_all(&dest).map_err(|e| format!("Unable to create directory {:?}: {}", dest, e))?;
+  fs_extra::dir::copy(
+    assets_dir,
+    &dest,
+    &fs_extra::dir::CopyOptions::new().content_only(true),
   )
-  .unwrap();
+  .map_err(|e| format!("Unable to copy assets from {:?}: {}", assets_dir, e))?;
+  Ok(())
+}
+
+fn create_android_project(
+  manifest_path: &Path,
+  target_artifacts: &HashMap<String, PathBuf>,
+  profile: BuildProfile,
+  v4_signing: bool,
+  gradle_signing: Option<&GradleSigning>,
+  options: ProjectOptions,
+) -> Result<()> {
+  let manifest_dir = manifest_path.parent().unwrap();
+  let project_dir =
+    resolve_android_project_dir(manifest_dir, profile, options.project_dir.as_deref());
+
+  let appid = get_android_app_id(manifest_path)?;
+  let (mut appname, title_source) = resolve_app_title(manifest_path)?;
+  println!("App title \"{}\" from {}", appname, title_source);
+  if let Some(suffix) = get_app_name_suffix(manifest_path, profile)? {
+    appname.push_str(&suffix);
+  }
+  let app_id_suffix = get_application_id_suffix(manifest_path, profile)?;
+  let app_icon = resolve_app_icon(manifest_path)?;
+  match &app_icon {
+    Some((icon, source)) => println!("App icon {} from {}", icon.display(), source),
+    None => println!("App icon from {}", InfoSource::Template),
+  }
+
+  // Copy the project template
+  let activity_kind = get_activity_kind(manifest_path).map_err(Error::Config)?;
+  let template = ProjectTemplate::for_manifest(manifest_path).map_err(Error::Config)?;
+  template.copy_to(&project_dir);
+  let sdl_java = match activity_kind {
+    ActivityKind::Sdl => get_sdl_java(manifest_path).map_err(Error::Config)?,
+    ActivityKind::Native => SdlJava::Template,
+  };
+  if sdl_java != SdlJava::Template {
+    remove_template_sdl_java(&project_dir);
+  }
+
+  // Check for hand edits of generated files before overwriting them
+  let mut generated = GeneratedFiles::load(&project_dir, options.keep_local);
+  generated.warn_about_modified();
+
+  //copy app icon
+  let mut has_icon = false;
+  if let Some((icon_path, source)) = &app_icon {
+    match copy_icon(icon_path, &project_dir, &mut generated) {
+      Ok(()) => has_icon = true,
+      //icons which weren't configured for android are only used if they fit
+      Err(e) if options.allow_missing_icon || !source.is_explicit() => warn(
+        &DEFAULT_ICON,
+        format!("{}, building with the default icon.", e),
+      ),
+      Err(e) => {
+        return Err(Error::Config(format!(
+          "{}. Use --allow-missing-icon to build with the default icon anyway",
+          e
+        )))
+      }
+    }
+  }
 
   // Create main activity class
-  let java_main_folder = manifest_dir
-    .join("target/android-project/app/src/main/java")
-    .join(str::replace(&appid, ".", "/"));
-  create_dir_all(java_main_folder.clone()).unwrap();
-  let main_class = "
+  let bridge_methods = get_bridge_methods(manifest_path).map_err(Error::Config)?;
+  check_bridge_activity(activity_kind, &bridge_methods).map_err(Error::Config)?;
+  if activity_kind == ActivityKind::Sdl {
+    let java_main_folder = project_dir
+      .join("app/src/main/java")
+      .join(str::replace(&appid, ".", "/"));
+    create_dir_all(java_main_folder.clone()).unwrap();
+    let main_class = "
 		package $APP;
 
 		import org.libsdl.app.SDLActivity;
 
 		public class MainActivity extends SDLActivity {
-
+$METHODS
 		}
 	";
-  let main_class = str::replace(main_class, "$APP", &appid);
-  write(java_main_folder.join("MainActivity.java"), &main_class).expect("Unable to write file");
+    let main_class = str::replace(main_class, "$APP", &appid)
+      .replace("$METHODS", &render_java_methods(&bridge_methods));
+    let main_class_file = format!(
+      "app/src/main/java/{}/MainActivity.java",
+      str::replace(&appid, ".", "/")
+    );
+    if generated.claim(&main_class_file) {
+      write(project_dir.join(main_class_file), &main_class).expect("Unable to write file");
+    }
+  }
 
   // Change project files
+  //old templates without applicationId in build.gradle take the app id from the manifest package,
+  //the activity must then be referred to by its full name, as it stays in the base package
+  let dsl = template.gradle_dsl();
+  let app_build_gradle = dsl.file_name("app/build.gradle");
+  let gradle_application_id = template
+    .read(&app_build_gradle)
+    .map(|content| content.contains("applicationId"))
+    .unwrap_or(false);
+  let manifest_package;
+  let qualified_main_activity;
+  let mut manifest_replacements = vec![];
+  if let (Some(suffix), false) = (&app_id_suffix, gradle_application_id) {
+    manifest_package = format!("package=\"{}{}\"", appid, suffix);
+    qualified_main_activity = format!("android:name=\"{}.MainActivity\"", appid);
+    manifest_replacements.push(("package=\"org.libsdl.app\"", &*manifest_package));
+    manifest_replacements.push(("android:name=\"SDLActivity\"", &*qualified_main_activity));
+  }
+  manifest_replacements.push(("SDLActivity", "MainActivity"));
+  manifest_replacements.push(("org.libsdl.app", &*appid));
+  if activity_kind == ActivityKind::Native && has_icon {
+    //the native template has no default icon to refer to
+    manifest_replacements.push((
+      "android:hasCode=\"false\"",
+      "android:hasCode=\"false\"\n        android:icon=\"@mipmap/ic_launcher\"",
+    ));
+  }
   change_android_project_file(
-    manifest_dir,
+    &project_dir,
+    &template,
     "app/src/main/AndroidManifest.xml",
-    vec![("SDLActivity", "MainActivity"), ("org.libsdl.app", &*appid)],
+    manifest_replacements,
+    &mut generated,
   );
 
-  change_android_project_file(
-    manifest_dir,
-    "app/build.gradle",
-    vec![("org.libsdl.app", &*appid)],
+  let mut build_gradle_replacements = vec![("org.libsdl.app", appid.clone())];
+  let android_block_anchor = dsl.android_block_anchor();
+  if activity_kind == ActivityKind::Sdl {
+    //SDL is packaged from the staging dir, so the copy built by gradle is redundant
+    let pick_first = match dsl {
+      GradleDsl::Groovy => dsl.call("pickFirst", &[&dsl.string("lib/*/libSDL2.so")]),
+      GradleDsl::Kotlin => format!("jniLibs.pickFirsts += {}", dsl.string("lib/*/libSDL2.so")),
+    };
+    build_gradle_replacements.push((
+      android_block_anchor,
+      dsl.insert_android_block(&format!(
+        "    packagingOptions {{\n        {}\n    }}",
+        pick_first
+      )),
+    ));
+  }
+  if let (Some(suffix), true) = (&app_id_suffix, gradle_application_id) {
+    build_gradle_replacements.push((
+      "    buildTypes {",
+      format!(
+        "    buildTypes {{\n        {}\n            {}\n        }}",
+        dsl.element_block("debug", true),
+        dsl.assign("applicationIdSuffix", &format!("\"{}\"", suffix))
+      ),
+    ));
+  }
+  if v4_signing {
+    //release builds are signed by apksigner, debug builds by gradle
+    build_gradle_replacements.push((
+      android_block_anchor,
+      dsl.insert_android_block(&format!(
+        "    signingConfigs {{\n        {}\n            {}\n        }}\n    }}",
+        dsl.element_block("debug", true),
+        dsl.assign("enableV4Signing", "true")
+      )),
+    ));
+  }
+  if !options.flavors.is_empty() {
+    build_gradle_replacements.push((
+      android_block_anchor,
+      dsl.insert_android_block(&build_gradle_product_flavors(&options.flavors, dsl)),
+    ));
+  }
+  if let Some(gradle_signing) = gradle_signing {
+    build_gradle_replacements.push((
+      android_block_anchor,
+      dsl.insert_android_block(&gradle_signing.build_gradle_block(v4_signing, dsl)),
+    ));
+  }
+  let google_services = get_google_services_file(manifest_path).map_err(Error::Config)?;
+  let agp_version = template
+    .read_gradle("build.gradle")
+    .and_then(|content| parse_android_gradle_plugin_version(&content));
+  let min_sdk_version = get_min_sdk_version(manifest_path).map_err(Error::Config)?;
+  let multidex = is_multidex_enabled(manifest_path).map_err(Error::Config)?;
+  let baseline_profile = get_baseline_profile(manifest_path).map_err(Error::Config)?;
+  if baseline_profile.is_some() && !supports_baseline_profile(agp_version.as_deref()) {
+    return Err(Error::Config(format!(
+      "baseline_profile needs the Android Gradle Plugin {} or newer, the template uses {}",
+      BASELINE_PROFILE_AGP_VERSION,
+      agp_version.as_deref().unwrap_or_default()
+    )));
+  }
+  if baseline_profile == Some(BaselineProfile::Default) && activity_kind != ActivityKind::Sdl {
+    return Err(Error::Config(
+      "baseline_profile = true ships a profile of the SDL Java code, set the path of a profile \
+      for a NativeActivity"
+        .to_string(),
+    ));
+  }
+  //gradle and Android Studio use the sdk and NDK of this build rather than their own
+  let ndk_dir = std::env::var_os("ANDROID_NDK_HOME").map(PathBuf::from);
+  write_local_properties(
+    &project_dir,
+    std::env::var_os("ANDROID_HOME")
+      .map(PathBuf::from)
+      .as_deref(),
+    ndk_dir.as_deref(),
+  )
+  .map_err(Error::Config)?;
+  let ndk_version = ndk_dir
+    .as_deref()
+    .and_then(get_ndk_version)
+    .filter(|_| supports_ndk_version(agp_version.as_deref()));
+  let packaging = get_packaging(manifest_path).map_err(Error::Config)?;
+  for block in packaging.build_gradle_blocks(agp_version.as_deref(), dsl) {
+    build_gradle_replacements.push((android_block_anchor, dsl.insert_android_block(&block)));
+  }
+  let prune_template = get_prune_template(manifest_path).map_err(Error::Config)?;
+  let mut prune_record = PruneRecord::default();
+  //the ndk-build of the template builds the optional SDL libraries, unless it's off for Maven
+  if prune_template && activity_kind == ActivityKind::Sdl && sdl_java == SdlJava::Template {
+    let keep = get_keep_sdl_libs(manifest_path).map_err(Error::Config)?;
+    let loaded = template
+      .read(SDL_ACTIVITY_SOURCE)
+      .map(|source| loaded_sdl_libs(&source))
+      .unwrap_or_default();
+    for lib in OPTIONAL_SDL_LIBS {
+      if loaded.iter().any(|l| l == lib) && !keep.iter().any(|k| k == lib) {
+        println!(
+          "Note: Keeping lib{}.so, the SDLActivity of this SDL version loads it.",
+          lib
+        );
+      }
+    }
+    let excluded = get_excluded_sdl_libs(&keep, &loaded);
+    if let Some(block) = sdl_lib_excludes_block(&excluded, agp_version.as_deref(), dsl) {
+      build_gradle_replacements.push((android_block_anchor, dsl.insert_android_block(&block)));
+    }
+    prune_record.sdl_libs = excluded.iter().map(|lib| lib.to_string()).collect();
+  }
+  if let Some(application_class) = get_application_class(manifest_path).map_err(Error::Config)? {
+    check_application_class(manifest_path, &application_class).map_err(Error::Config)?;
+    if multidex && needs_multidex_library(min_sdk_version) {
+      println!(
+        "Note: {} needs to extend {} or call MultiDex.install(this) for multidex below api level 21.",
+        application_class, MULTIDEX_APPLICATION_CLASS
+      );
+    }
+  }
+  let metadata_min_sdk_version =
+    get_metadata_min_sdk_version(manifest_path).map_err(Error::Config)?;
+  transform_android_project_file(
+    &project_dir,
+    &template,
+    &app_build_gradle,
+    &mut generated,
+    |mut content| {
+      for (from, to) in build_gradle_replacements {
+        content = content.replace(from, &to);
+      }
+      //after the replacements, which would replace the group org.libsdl.app with the app id
+      if let SdlJava::Maven(coordinates) = &sdl_java {
+        content = add_sdl_java_dependency(&content, coordinates, dsl);
+      }
+      if let Some(min_sdk_version) = metadata_min_sdk_version {
+        content = replace_min_sdk_version(&content, min_sdk_version);
+      }
+      if google_services.is_some() {
+        content = apply_google_services_plugin(&content, dsl);
+      }
+      if multidex {
+        content = enable_multidex(&content, min_sdk_version, dsl);
+      }
+      if baseline_profile.is_some() {
+        content = add_profileinstaller_dependency(&content, dsl);
+      }
+      if let Some(build_info) = &options.build_info {
+        content = add_build_config_fields(&content, build_info, agp_version.as_deref(), dsl);
+      }
+      if let Some(ndk_version) = &ndk_version {
+        content = set_ndk_version(&content, ndk_version, dsl);
+      }
+      content
+    },
   );
+  let android_x =
+    (multidex && needs_multidex_library(min_sdk_version)) || baseline_profile.is_some();
+  let maven_sdl_java = sdl_java != SdlJava::Template;
+  if android_x || maven_sdl_java {
+    transform_android_project_file(
+      &project_dir,
+      &template,
+      "gradle.properties",
+      &mut generated,
+      |mut content| {
+        if android_x {
+          content = enable_android_x(&content);
+        }
+        if maven_sdl_java {
+          content = exclude_native_libs(&content);
+        }
+        content
+      },
+    );
+  }
+
+  // Add firebase configuration
+  if let Some(google_services) = &google_services {
+    //the top-level build.gradle is only generated when needed, the classpath alone is harmless
+    transform_android_project_file(
+      &project_dir,
+      &template,
+      &dsl.file_name("build.gradle"),
+      &mut generated,
+      |content| add_google_services_classpath(&content, dsl),
+    );
+    if generated.claim("app/google-services.json") {
+      copy(
+        google_services,
+        project_dir.join("app/google-services.json"),
+      )
+      .unwrap_or_else(|_| panic!("Unable to copy {:?}", google_services));
+    }
+  }
+
+  // Ship the baseline profile, which gradle compiles into release builds
+  if let Some(baseline_profile) = &baseline_profile {
+    if generated.claim(BASELINE_PROFILE_PATH) {
+      let content = read_baseline_profile(
+        baseline_profile,
+        manifest_dir,
+        &activity_kind.activity_class(&appid),
+      )
+      .map_err(Error::Config)?;
+      let path = project_dir.join(BASELINE_PROFILE_PATH);
+      write(&path, content).unwrap_or_else(|_| panic!("Unable to write {:?}", path));
+    }
+  }
 
   change_android_project_file(
-    manifest_dir,
+    &project_dir,
+    &template,
     "app/src/main/res/values/strings.xml",
     vec![("Game", &*appname)],
+    &mut generated,
   );
 
-  //add permission entries
-  for permission in get_toml_string_vec(
-    manifest_path,
-    ["package", "metadata", "android", "permissions"],
-  )
-  .unwrap_or(vec![])
-  {
-    println!("Adding permission entry for permission {}", permission);
-    add_uses_permission_entry(manifest_dir, &permission);
+  // Generate the theme configuring the window, merged with the styles of the template
+  let theme_config = get_theme_config(manifest_path).map_err(Error::Config)?;
+  if let Some(theme_config) = &theme_config {
+    let parent = get_parent_theme(
+      template
+        .read("app/src/main/AndroidManifest.xml")
+        .and_then(|content| AndroidManifest::new(content).application_attribute("theme")),
+    );
+    for (file_name, with_display_cutout) in [
+      ("app/src/main/res/values/styles.xml", false),
+      ("app/src/main/res/values-v28/styles.xml", true),
+    ] {
+      if !generated.claim(file_name) {
+        continue;
+      }
+      let style = theme_config.render_style(&parent, with_display_cutout);
+      let content = merge_style(template.read(file_name).as_deref(), &style);
+      let path = project_dir.join(file_name);
+      create_dir_all(path.parent().unwrap()).unwrap();
+      write(&path, content).unwrap_or_else(|_| panic!("unable to write file {:?}", path));
+    }
   }
 
-  // Remove C sources
-  remove_items(&[manifest_dir.join("target/android-project/app/jni/src")]).unwrap();
+  // Generate the splash screen, a platform one on android 12 and a themed window background before
+  if let Some(splash_config) = get_splash_config(manifest_path).map_err(Error::Config)? {
+    write_splash_drawables(&splash_config, &project_dir, &mut generated)?;
 
-  // Link SDL into project
-  if !manifest_dir
-    .join("target/android-project/app/jni/SDL")
-    .is_dir()
-  {
-    symlink_dir(
-      Path::new(&*get_env_var("SDL")),
-      manifest_dir.join("target/android-project/app/jni/SDL"),
+    let template_manifest = template
+      .read("app/src/main/AndroidManifest.xml")
+      .map(AndroidManifest::new);
+    let parent = template_manifest
+      .as_ref()
+      .and_then(|manifest| manifest.activity_attribute("theme"))
+      .unwrap_or_else(|| {
+        if theme_config.is_some() {
+          format!("@style/{}", GENERATED_THEME_NAME)
+        } else {
+          get_parent_theme(
+            template_manifest
+              .as_ref()
+              .and_then(|manifest| manifest.application_attribute("theme")),
+          )
+        }
+      });
+    for (file_name, platform_splash_screen) in [
+      ("app/src/main/res/values/themes.xml", false),
+      ("app/src/main/res/values-v31/themes.xml", true),
+    ] {
+      if !generated.claim(file_name) {
+        continue;
+      }
+      let style = splash_config.render_style(&parent, platform_splash_screen);
+      let content = merge_style(template.read(file_name).as_deref(), &style);
+      let path = project_dir.join(file_name);
+      create_dir_all(path.parent().unwrap()).unwrap();
+      write(&path, content).unwrap_or_else(|_| panic!("unable to write file {:?}", path));
+    }
+  }
+
+  // Add permissions and sdk restrictions
+  if generated.claim("app/src/main/AndroidManifest.xml") {
+    //placeholders only apply to user-provided templates, the SDL one has none
+    if let Some(template_dir) =
+      get_custom_template_dir(manifest_path)?.filter(|_| activity_kind == ActivityKind::Sdl)
+    {
+      let placeholders =
+        get_placeholders(manifest_path, profile, options.flavor.as_ref()).map_err(Error::Config)?;
+      substitute_template_file(
+        &template_dir,
+        &project_dir,
+        "app/src/main/AndroidManifest.xml",
+        &placeholders,
+      )
+      .map_err(Error::Config)?;
+    }
+    let sdl_defaults_exclude = if activity_kind == ActivityKind::Sdl
+      && get_sdl_defaults_enabled(manifest_path).map_err(Error::Config)?
+    {
+      Some(get_sdl_defaults_exclude(manifest_path).map_err(Error::Config)?)
+    } else {
+      None
+    };
+    update_android_manifest(
+      manifest_path,
+      &project_dir,
+      sdl_defaults_exclude.as_deref(),
+      &options,
     )
-    .unwrap();
+    .map_err(Error::Config)?;
   }
+  // Catch broken manifest output here instead of in the gradle build
+  check_generated_manifest(&project_dir.join("app/src/main/AndroidManifest.xml"))?;
+
+  if activity_kind == ActivityKind::Sdl {
+    // Remove C sources
+    remove_items(&[project_dir.join("app/jni/src")]).unwrap();
 
-  // Copy libmain.so to all targets
+    // Link SDL into project, unless the Java glue comes from Maven and the template's ndk-build
+    // is off
+    let sdl_link = project_dir.join("app/jni/SDL");
+    if maven_sdl_java {
+      if sdl_link.is_symlink() {
+        remove_symlink_dir(&sdl_link).unwrap_or_else(|_| panic!("Unable to remove {:?}", sdl_link));
+      }
+    } else if !sdl_link.is_dir() {
+      symlink_dir(get_env_path("SDL"), &sdl_link).unwrap();
+    }
+  }
+
+  // Copy libmain.so to all targets, into the source set of the flavor if there is one
+  let lib_source_set = options
+    .flavor
+    .as_ref()
+    .map(|f| f.name.as_str())
+    .unwrap_or("main");
+  let split_debuginfo = get_split_debuginfo(manifest_path).map_err(Error::Config)?;
+  let abis = target_artifacts
+    .keys()
+    .map(|target| get_target_android_name(manifest_path, target))
+    .collect::<Result<Vec<_>, _>>()
+    .map_err(Error::Config)?;
+  for source_set in ["main", lib_source_set] {
+    remove_stale_jni_libs(
+      &project_dir.join("app/src").join(source_set).join("jniLibs"),
+      &abis,
+    )
+    .map_err(Error::Config)?;
+  }
   for (target, artifact) in target_artifacts {
-    let target_android_name = get_target_android_name(target);
+    let target_android_name =
+      get_target_android_name(manifest_path, target).map_err(Error::Config)?;
     //println!("{:?}",target);
 
-    let android_dir = manifest_dir
-      .join("target/android-project/app/src/main/jniLibs")
-      .join(target_android_name);
+    let android_dir = project_dir
+      .join("app/src/main/jniLibs")
+      .join(&target_android_name);
+    let lib_dir = project_dir
+      .join("app/src")
+      .join(lib_source_set)
+      .join("jniLibs")
+      .join(&target_android_name);
 
     create_dir_all(&android_dir).unwrap();
-    copy(artifact, android_dir.join("libmain.so")).unwrap();
-  }
-
-  //copy app icon
-  if let Some(icon_path) = app_icon {
-    let icon_path = manifest_dir.join(icon_path);
-    for res in vec!["m", "h", "xh", "xxh", "xxxh"] {
-      let dest = manifest_dir.join(format!(
-        "target/android-project/app/src/main/res/mipmap-{}dpi/ic_launcher.png",
-        res
-      ));
-      if let Err(e) = copy(&icon_path, &dest) {
-        eprintln!(
-          "Failed to copy icon from {:?} to {:?}: {}",
-          icon_path, dest, e
+    create_dir_all(&lib_dir).unwrap();
+    if options.flavor.is_some() {
+      //gradle fails on a libmain.so in both the main and the flavor source set
+      let _ = remove_file(android_dir.join("libmain.so"));
+    }
+    if generated.claim(&format!(
+      "app/src/{}/jniLibs/{}/libmain.so",
+      lib_source_set, target_android_name
+    )) {
+      copy(artifact, lib_dir.join("libmain.so")).unwrap();
+      //gradle only packages the .so files of jniLibs, the debug info stays with the symbols
+      let collected = collect_split_debuginfo(artifact, &lib_dir.join("libmain.so"))?;
+      if split_debuginfo == SplitDebuginfo::Packed && !collected {
+        warn(
+          &SPLIT_DEBUGINFO,
+          format!(
+            "No split debug info for {}, the profile builds without debug info.",
+            target_android_name
+          ),
         );
       }
     }
+
+    let sdl_lib = get_sdl_staging_dir(manifest_dir, target).join("libSDL2.so");
+    if activity_kind == ActivityKind::Sdl
+      && generated.claim(&format!(
+        "app/src/main/jniLibs/{}/libSDL2.so",
+        target_android_name
+      ))
+    {
+      copy(&sdl_lib, android_dir.join("libSDL2.so"))
+        .unwrap_or_else(|_| panic!("Unable to copy {:?}", sdl_lib));
+    }
+  }
+
+  if let Some(assets_dir) = &options.assets {
+    copy_assets(assets_dir, &project_dir, "main", true)?;
+  }
+  if let Some(hook_assets) = &options.hook_assets {
+    copy_assets(hook_assets, &project_dir, "main", options.assets.is_none())?;
+  }
+  if let Some(Flavor {
+    name,
+    assets: Some(assets_dir),
+    ..
+  }) = &options.flavor
+  {
+    copy_assets(assets_dir, &project_dir, name, true)?;
   }
-}
 
-lazy_static! {
-  static ref MANIFEST_TAG_CONTENT_REGEX: Regex = RegexBuilder::new("<manifest.*?>(.*)</manifest>")
-    .dot_matches_new_line(true)
-    .build()
-    .expect("invalid manifest tag regex");
+  // Prune what the app doesn't reference of the SDL template, after everything referencing it
+  if prune_template && activity_kind == ActivityKind::Sdl {
+    prune_record.resources =
+      prune_template_resources(&project_dir, &get_env_path("SDL").join("android-project"));
+  }
+  prune_record.save(&project_dir).map_err(Error::Config)?;
+
+  generated.save();
+
+  //a custom project dir is left out of target/
+  if options.project_dir.is_none() {
+    link_latest_android_project(manifest_dir, profile).map_err(Error::Config)?;
+  }
+  Ok(())
 }
 
-fn add_uses_permission_entry(manifest_dir: &Path, permission: &str) {
-  let path = manifest_dir.join("target/android-project/app/src/main/AndroidManifest.xml");
-  let mut content = read_to_string(&path).expect(&format!("can't read manifest {:?}", path));
-  let captures = MANIFEST_TAG_CONTENT_REGEX
-    .captures(&content)
-    .expect("can't find manifest tag content");
-  let content_match = captures.get(1).expect("can't get content of manifest tag");
-  let tag_content = content_match.as_str();
-
-  let permission_entry = format!(
-    "\t<uses-permission android:name=\"android.permission.{}\"/>\n",
-    permission.to_uppercase()
-  );
-  if tag_content.contains(&permission_entry) {
-    return;
+fn update_android_manifest(
+  manifest_path: &Path,
+  project_dir: &Path,
+  sdl_defaults_exclude: Option<&[String]>,
+  options: &ProjectOptions,
+) -> Result<(), String> {
+  let path = project_dir.join("app/src/main/AndroidManifest.xml");
+  let mut manifest = AndroidManifest::load(&path);
+
+  apply_configured_preset(manifest_path, &mut manifest)?;
+
+  apply_configured_wear(manifest_path, &mut manifest)?;
+
+  //before the permissions of the metadata, which replace a default of the same permission
+  if let Some(exclude) = sdl_defaults_exclude {
+    for default in apply_sdl_defaults(&mut manifest, exclude, options.haptics) {
+      if options.verbose {
+        println!("Adding sdl default {}: {}", default.name, default.reason);
+      }
+    }
+  }
+
+  if get_theme_config(manifest_path)?.is_some() {
+    manifest.set_application_attribute("theme", &format!("@style/{}", GENERATED_THEME_NAME));
+  }
+
+  if let Some(application_class) =
+    get_effective_application_class(manifest_path, get_min_sdk_version(manifest_path)?)?
+  {
+    manifest.set_application_attribute("name", &application_class);
+  }
+
+  if get_splash_config(manifest_path)?.is_some() {
+    manifest.set_activity_attribute("theme", &format!("@style/{}", SPLASH_THEME_NAME));
+  }
+
+  if let Some(orientation) = get_toml_string(
+    manifest_path,
+    ["package", "metadata", "android", "orientation"],
+  )? {
+    manifest.set_activity_attribute("screenOrientation", &orientation);
+  }
+
+  if get_toml_bool(
+    manifest_path,
+    ["package", "metadata", "android", "large_screens"],
+  )?
+  .unwrap_or(false)
+  {
+    if let Some(warning) = apply_large_screens(&mut manifest) {
+      warn(&LARGE_SCREENS, warning);
+    }
+  }
+
+  if let Some(permissions) = get_toml_entry(
+    manifest_path,
+    ["package", "metadata", "android", "permissions"],
+  )? {
+    for permission in parse_permissions(&permissions)? {
+      println!("Adding permission entry for permission {}", permission.name);
+      manifest.add_permission(&permission);
+    }
   }
 
-  content.insert_str(content_match.end(), &permission_entry);
+  if let Some(queries) =
+    get_toml_entry(manifest_path, ["package", "metadata", "android", "queries"])?
+  {
+    manifest.add_queries(&parse_queries(&queries)?);
+  }
 
-  write(&path, &content).expect("can't write to manifest file");
+  if let Some(max_sdk_version) = get_toml_integer(
+    manifest_path,
+    ["package", "metadata", "android", "max_sdk_version"],
+  )? {
+    manifest.set_max_sdk_version(
+      u32::try_from(max_sdk_version)
+        .map_err(|_| format!("invalid max_sdk_version: {}", max_sdk_version))?,
+    );
+  }
+
+  let target_sdk_version =
+    get_template_target_sdk_version(&ProjectTemplate::for_manifest(manifest_path)?);
+  if target_sdk_version.is_some_and(|v| v >= EXPORTED_REQUIRED_SDK_VERSION) {
+    let overrides = match get_toml_entry(
+      manifest_path,
+      ["package", "metadata", "android", "exported"],
+    )? {
+      Some(value) => parse_exported_overrides(&value)?,
+      None => BTreeMap::new(),
+    };
+    for component in manifest.add_exported_attributes(&overrides) {
+      println!("Adding android:exported to {}", component);
+    }
+  }
+
+  manifest.save(&path);
+  Ok(())
 }
 
 fn change_android_project_file(
-  manifest_dir: &Path,
+  project_dir: &Path,
+  template: &ProjectTemplate,
   file_name: &str,
   replacements: Vec<(&str, &str)>,
+  generated: &mut GeneratedFiles,
 ) {
-  //read contents from original location, because we are replacing expected values, not modified ones
-  let path = Path::new(&get_env_var("SDL"))
-    .join("android-project")
-    .join(file_name);
-  let mut content = read_to_string(&path).expect(&format!("can't read project file: {:?}", path));
+  transform_android_project_file(
+    project_dir,
+    template,
+    file_name,
+    generated,
+    |mut content| {
+      for (from, to) in replacements {
+        content = content.replace(from, to);
+      }
+      content
+    },
+  );
+}
 
-  for (from, to) in replacements {
-    content = content.replace(from, to);
+fn transform_android_project_file(
+  project_dir: &Path,
+  template: &ProjectTemplate,
+  file_name: &str,
+  generated: &mut GeneratedFiles,
+  transform: impl FnOnce(String) -> String,
+) {
+  if !generated.claim(file_name) {
+    return;
   }
 
-  let path = manifest_dir.join("target/android-project").join(file_name);
-  write(&path, &content).expect(&format!("unable to write file {:?}", path));
+  //read contents from the template, because we are replacing expected values, not modified ones
+  let content = template
+    .read(file_name)
+    .unwrap_or_else(|| panic!("can't read project file: {:?}", file_name));
+  let content = transform(content);
+
+  let path = project_dir.join(file_name);
+  write(&path, &content).unwrap_or_else(|_| panic!("unable to write file {:?}", path));
 }
 
-pub fn sign_android(manifest_path: &Path, ks_file: Option<String>, ks_pass: Option<String>) {
+/// Generates the android project without native libraries, signing or running gradle, returning
+/// its dir.
+pub fn generate_android_project(
+  manifest_path: &Path,
+  profile: BuildProfile,
+  options: ProjectOptions,
+) -> Result<PathBuf> {
   let manifest_dir = manifest_path.parent().unwrap();
-  let release_dir = manifest_dir.join("target/android-project/app/build/outputs/apk/release");
+  let project_dir =
+    resolve_android_project_dir(manifest_dir, profile, options.project_dir.as_deref());
+  let v4_signing = false;
+  create_android_project(
+    manifest_path,
+    &HashMap::new(),
+    profile,
+    v4_signing,
+    None,
+    options,
+  )?;
+  Ok(project_dir)
+}
+
+/// The key to sign the release APK with. Falls back to the release key older versions generated,
+/// moved out of `target/` first, else to the debug keystore gradle also uses.
+pub fn signing_or_default_keystore(
+  manifest_dir: &Path,
+  signing: Option<SigningConfig>,
+) -> Result<SigningConfig, String> {
+  let signing = match signing {
+    Some(signing) => signing,
+    None => default_release_signing(manifest_dir)?,
+  };
+  println!("Using keyfile: {}", signing.signer.keystore.display());
+  Ok(signing)
+}
+
+fn default_release_signing(manifest_dir: &Path) -> Result<SigningConfig, String> {
+  //also with a custom project dir, or when only signing, which don't link the old project dir
+  migrate_legacy_release_keystore(manifest_dir)?;
+  let generated_key_path = get_release_keystore_path(manifest_dir);
+  if generated_key_path.is_file() {
+    return Ok(get_generated_release_signing_config(generated_key_path));
+  }
+  let key_path = ensure_debug_keystore();
+  warn_with_fix(
+    &DEBUG_KEYSTORE,
+    "No keystore configured, signing the release APK with the debug keystore. The resulting APK \
+    is NOT suitable for distribution!",
+    "Pass --ks-file and --ks-pass to sign with your own key.",
+  );
+  Ok(get_debug_signing_config(key_path))
+}
+
+/// Signs the release APK gradle built, with apksigner or, if the build-tools lack it, a v1
+/// signature of jarsigner. Returns who signed it.
+pub fn sign_android(
+  project_dir: &Path,
+  signing: SigningConfig,
+  v4_signing: bool,
+  reproducible: bool,
+  flavor: Option<&str>,
+  min_sdk_version: Option<u32>,
+  packaging: &Packaging,
+) -> Result<ApkSigner> {
+  let release_dir = project_dir.join(get_variant_apk_dir(flavor, BuildProfile::Release));
+  let variant = get_variant_file_name(flavor, BuildProfile::Release);
+  let unsigned_apk = release_dir.join(format!("app-{}-unsigned.apk", variant));
+  let aligned_apk = release_dir.join(format!("app-{}-unsigned-aligned.apk", variant));
+  let signed_apk = release_dir.join(format!("app-{}.apk", variant));
   //println!("{:?}",release_dir);
 
   // Find android build tools.
-  let tool_paths =
-    fs::read_dir(Path::new(&*get_env_var("ANDROID_HOME")).join("build-tools")).unwrap();
-  let mut tool_paths: Vec<String> = tool_paths
-    .map(|d| {
-      d.unwrap()
-        .path()
-        .file_name()
-        .unwrap()
-        .to_os_string()
-        .into_string()
-        .unwrap()
-    })
-    .collect();
-  tool_paths.sort();
-  tool_paths.reverse();
-  let tools_version = tool_paths[0].clone();
+  let tools_version = find_build_tools_version(&get_env_path("ANDROID_HOME"))
+    .ok_or_else(|| {
+      Error::Config("No build-tools found, install them with sdkmanager or rerun the build with --install-sdk-packages".to_string())
+    })?;
   println!("Using build-tools: {}", tools_version);
+  packaging
+    .check_build_tools(&tools_version)
+    .map_err(Error::Config)?;
+  let build_tools_dir = get_env_path("ANDROID_HOME")
+    .join("build-tools")
+    .join(&tools_version);
+
+  let apksigner_path = build_tools_dir.join("apksigner");
+  let apksigner_found = apksigner_path.is_file();
+  let apk_signer = choose_apk_signer(
+    apksigner_found,
+    !apksigner_found && is_jdk_tool_available("jarsigner"),
+    min_sdk_version,
+  )
+  .map_err(Error::Config)?;
+
+  if let Some(next_signer) = &signing.next_signer {
+    println!("Rotating to keyfile: {}", next_signer.keystore.display());
+  }
+
+  // Normalize the unsigned apk, zipalign restores the alignment afterwards.
+  if reproducible {
+    normalize_zip(&unsigned_apk);
+  }
 
-  // Determine key file. Generate if needed.
-  let (key_file, key_pass) = if ks_file.is_some() {
-    (ks_file.unwrap(), ks_pass.expect("Need keystore password"))
-  } else {
-    let key_path = release_dir.join("app-release.jks");
-    if !key_path.exists() {
-      println!("Generating keyfile...");
-      assert!(Command::new("keytool")
-        .arg("-genkey")
-        .arg("-dname")
-        .arg("CN=Unknown, OU=Unknown, O=Unknown, L=Unknown, S=Unknown, C=Unknown")
-        .arg("-storepass")
-        .arg("android")
-        .arg("-keystore")
-        .arg(key_path.clone())
-        .arg("-keyalg")
-        .arg("RSA")
-        .arg("-keysize")
-        .arg("2048")
-        .arg("-validity")
-        .arg("10000")
-        .status()
-        .unwrap()
-        .success());
-    }
-
-    (
-      key_path.into_os_string().into_string().unwrap(),
-      "pass:android".to_string(),
+  let zipalign = |input: &Path, output: &Path| {
+    check_command(
+      Command::new(build_tools_dir.join("zipalign"))
+        .arg("-v")
+        .arg("-f")
+        .args(packaging.zipalign_args())
+        .arg("4")
+        .arg(input)
+        .arg(output),
     )
   };
 
-  println!("Using keyfile: {}", key_file);
+  if apk_signer == ApkSigner::Jarsigner {
+    warn_with_fix(
+      &JARSIGNER,
+      format!(
+        "apksigner is missing from build-tools {}, signing with jarsigner instead. The APK only \
+        gets a v1 signature, without the v2+ schemes devices from Android 11 on require for \
+        min_sdk_version 30 and above{}.",
+        tools_version,
+        if v4_signing {
+          ", and no v4 signature"
+        } else {
+          ""
+        }
+      ),
+      "Install build-tools with apksigner with sdkmanager to sign it properly.",
+    );
 
-  // Run zipalign.
-  let zipalign_path = Path::new(&*get_env_var("ANDROID_HOME"))
-    .join("build-tools")
-    .join(tools_version.clone())
-    .join("zipalign");
-
-  assert!(Command::new(zipalign_path)
-    .arg("-v")
-    .arg("-f")
-    .arg("-p")
-    .arg("4")
-    .arg(release_dir.join("app-release-unsigned.apk"))
-    .arg(release_dir.join("app-release-unsigned-aligned.apk"))
-    .status()
-    .unwrap()
-    .success());
+    let signing = GradleSigning::for_jarsigner(&signing, "APK")
+      .map_err(|e| Error::Config(format!("Invalid signing configuration: {}", e)))?;
+    //jarsigner moves the entries, so the APK is aligned after signing
+    let jar_signed_apk = release_dir.join(format!("app-{}-unaligned.apk", variant));
+    copy(&unsigned_apk, &jar_signed_apk)
+      .map_err(|e| Error::Config(format!("unable to copy {:?}: {}", unsigned_apk, e)))?;
+    check_command(
+      Command::new(get_jdk_tool("jarsigner"))
+        .args(signing.jarsigner_args(&jar_signed_apk))
+        .envs(signing.jarsigner_envs()),
+    )?;
+    zipalign(&jar_signed_apk, &signed_apk)?;
+    return Ok(apk_signer);
+  }
+
+  zipalign(&unsigned_apk, &aligned_apk)?;
 
   // Run apksigner
-  let apksigner_path = Path::new(&*get_env_var("ANDROID_HOME"))
-    .join("build-tools")
-    .join(tools_version.clone())
-    .join("apksigner");
-
-  assert!(Command::new(apksigner_path)
-    .arg("sign")
-    .arg("-ks")
-    .arg(key_file)
-    .arg("-ks-pass")
-    .arg(key_pass)
-    .arg("-out")
-    .arg(release_dir.join("app-release.apk"))
-    .arg(release_dir.join("app-release-unsigned-aligned.apk"))
-    .status()
-    .unwrap()
-    .success());
+  let mut apksigner = Command::new(apksigner_path);
+  apksigner.args(build_apksigner_args(
+    &signing,
+    v4_signing,
+    &aligned_apk,
+    &signed_apk,
+  ));
+  let output = run_command_captured(&mut apksigner);
+  if !output.status.success() {
+    let mut combined = String::from_utf8_lossy(&output.stdout).to_string();
+    combined.push_str(&String::from_utf8_lossy(&output.stderr));
+    if let Some(explanation) = diagnose_apksigner_failure(&combined) {
+      eprintln!();
+      eprintln!("{}", explanation);
+    }
+    return Err(Error::command(apksigner.get_program(), output.status));
+  }
+  Ok(apk_signer)
+}
+
+/// The AAB gradle bundled for the variant.
+pub fn get_path_to_aab(project_dir: &Path, profile: BuildProfile, flavor: Option<&str>) -> PathBuf {
+  project_dir
+    .join(get_variant_bundle_dir(flavor, profile))
+    .join(format!(
+      "app-{}.aab",
+      get_variant_file_name(flavor, profile)
+    ))
+}
+
+/// Signs the release AAB in place with jarsigner, with the same key as the APK.
+fn sign_aab(
+  project_dir: &Path,
+  signing: &SigningConfig,
+  reproducible: bool,
+  flavor: Option<&str>,
+) -> Result<()> {
+  let aab = get_path_to_aab(project_dir, BuildProfile::Release, flavor);
+  if reproducible {
+    normalize_zip(&aab);
+  }
+  let signing = GradleSigning::for_aab(signing)
+    .map_err(|e| Error::Config(format!("Invalid signing configuration: {}", e)))?;
+  check_command(
+    Command::new(get_jdk_tool("jarsigner"))
+      .args(signing.jarsigner_args(&aab))
+      .envs(signing.jarsigner_envs()),
+  )
 }
 
 // keytool -android blabla -genkey -v -keystore my-release-key.jks -keyalg RSA -keysize 2048 -validity 10000 -alias my-alias
 // /home/micke/Android/Sdk/build-tools/30.0.3/zipalign -v -p 4 app-release-unsigned.apk app-release-unsigned-aligned.apk
 // /home/micke/Android/Sdk/build-tools/30.0.3/apksigner sign -ks my-release-key.jks -ks-pass pass:android -out app-release.apk app-release-unsigned-aligned.apk
 
+/// What [build_android_project] did besides building the APK.
+#[derive(Debug, Clone, Default)]
+pub struct ProjectBuild {
+  /// The `extra_gradle_tasks` run after the assembly with their durations.
+  pub extra_gradle_tasks: Vec<(String, Duration)>,
+  /// Who signed the release APK, `None` if gradle did.
+  pub apk_signer: Option<ApkSigner>,
+}
+
+/// Generates and builds the android project, signing release builds unless gradle does.
 pub fn build_android_project(
   manifest_path: &Path,
-  target_artifacts: &HashMap<String, String>,
+  target_artifacts: &HashMap<String, PathBuf>,
   profile: BuildProfile,
-  ks_file: Option<String>,
-  ks_pass: Option<String>,
-) {
+  signing: Option<SigningConfig>,
+  v4_signing: bool,
+  reproducible: bool,
+  options: ProjectOptions,
+) -> Result<ProjectBuild> {
   let manifest_dir = manifest_path.parent().unwrap();
+  let extra_tasks = get_extra_gradle_tasks(manifest_path)?;
+
+  let flavor = options.flavor.as_ref().map(|f| f.name.clone());
+  let gradle_tasks = options.gradle_tasks.clone();
+  let bundle = options.artifacts.contains(&Artifact::Aab);
+  let gradle_progress = options.gradle_progress;
+  let gradle_user_home = options.gradle_user_home.clone();
+  let project_dir =
+    resolve_android_project_dir(manifest_dir, profile, options.project_dir.as_deref());
+  let gradle_signing = match (profile, options.signing_mode) {
+    (BuildProfile::Release, SigningMode::Gradle) => {
+      if reproducible {
+        warn(
+          &UNNORMALIZED_APK,
+          "APKs signed by gradle are not normalized for reproducible builds.",
+        );
+      }
+      let signing =
+        signing_or_default_keystore(manifest_dir, signing.clone()).map_err(Error::Config)?;
+      Some(
+        GradleSigning::from_config(&signing)
+          .map_err(|e| Error::Config(format!("Invalid signing configuration: {}", e)))?,
+      )
+    }
+    _ => None,
+  };
+
+  create_android_project(
+    manifest_path,
+    target_artifacts,
+    profile,
+    v4_signing,
+    gradle_signing.as_ref(),
+    options,
+  )?;
 
-  create_android_project(manifest_path, target_artifacts);
+  //the native template comes without a gradle wrapper
+  let has_wrapper = project_dir.join("gradlew").is_file();
+  let wrapper_properties =
+    read_to_string(project_dir.join("gradle/wrapper/gradle-wrapper.properties")).ok();
+  let gradle_home = match gradle_user_home.clone() {
+    Some(gradle_home) => Some(gradle_home),
+    None => get_gradle_user_home(manifest_path, None)?,
+  };
+  if let (true, Some(properties), Some(gradle_home)) =
+    (has_wrapper, wrapper_properties, gradle_home)
+  {
+    println!(
+      "{}",
+      check_wrapper_distribution(&properties, &gradle_home, is_offline()).map_err(Error::Config)?
+    );
+  }
 
-  let gradle_task = match profile {
-    BuildProfile::Debug => "assembleDebug",
-    BuildProfile::Release => "assembleRelease",
+  let run_gradle = |tasks: &[String]| -> Result<()> {
+    let mut gradle = Command::new(if has_wrapper { "./gradlew" } else { "gradle" });
+    gradle.args(tasks).current_dir(&project_dir);
+    if let Some(gradle_user_home) = &gradle_user_home {
+      gradle.env("GRADLE_USER_HOME", gradle_user_home);
+    }
+    if is_offline() {
+      gradle.arg("--offline");
+    }
+    if is_ci_mode() {
+      gradle.arg("--console=plain");
+    }
+    if reproducible {
+      gradle
+        .arg("--init-script")
+        .arg(write_reproducible_init_script(&project_dir));
+    }
+    if let Some(gradle_signing) = &gradle_signing {
+      gradle.args(gradle_signing.gradle_args());
+    }
+    let output = if gradle_progress {
+      run_gradle_with_progress(&mut gradle, &project_dir, tasks)
+    } else {
+      run_command_captured(&mut gradle)
+    };
+    if !output.status.success() {
+      let mut combined = String::from_utf8_lossy(&output.stdout).to_string();
+      combined.push_str(&String::from_utf8_lossy(&output.stderr));
+      print_gradle_diagnoses(&diagnose_gradle_failure(&combined));
+      return Err(Error::command(gradle.get_program(), output.status));
+    }
+    Ok(())
   };
 
-  assert!(Command::new("./gradlew")
-    .args([gradle_task])
-    .current_dir(manifest_dir.join("./target/android-project"))
-    .status()
-    .unwrap()
-    .success());
+  //the APK of the assemble task is the one signed and copied, whatever else runs
+  let mut tasks = vec![get_assemble_task(flavor.as_deref(), profile)];
+  if bundle {
+    tasks.push(get_bundle_task(flavor.as_deref(), profile));
+  }
+  tasks.extend(gradle_tasks);
+  run_gradle(&tasks)?;
 
-  if matches!(profile, BuildProfile::Release) {
-    sign_android(manifest_path, ks_file, ks_pass);
+  let mut extra_durations = vec![];
+  for task in extra_tasks {
+    let start = Instant::now();
+    run_gradle(std::slice::from_ref(&task))?;
+    extra_durations.push((task, start.elapsed()));
   }
+
+  let mut apk_signer = None;
+  if matches!(profile, BuildProfile::Release) && gradle_signing.is_none() {
+    let signing = signing_or_default_keystore(manifest_dir, signing).map_err(Error::Config)?;
+    apk_signer = Some(sign_android(
+      &project_dir,
+      signing.clone(),
+      v4_signing,
+      reproducible,
+      flavor.as_deref(),
+      get_min_sdk_version(manifest_path).map_err(Error::Config)?,
+      &get_packaging(manifest_path).map_err(Error::Config)?,
+    )?);
+    if bundle {
+      sign_aab(&project_dir, &signing, reproducible, flavor.as_deref())?;
+    }
+  }
+  Ok(ProjectBuild {
+    extra_gradle_tasks: extra_durations,
+    apk_signer,
+  })
 }
 
 #[cfg(test)]
 mod test {
-  use crate::android_project::MANIFEST_TAG_CONTENT_REGEX;
+  use std::collections::HashMap;
+  use std::fs::{create_dir_all, read, read_dir, read_to_string, write};
+  use std::path::Path;
+
+  use tempfile::tempdir;
+
+  use crate::android_project::{
+    create_android_project, get_android_project_dir, get_sdl_staging_dir,
+    link_latest_android_project, resolve_android_project_dir, signing_or_default_keystore,
+    stage_file, ProjectOptions,
+  };
+  use crate::build_info::BuildInfo;
+  use crate::flavors::get_flavors;
+  use crate::keystore::get_release_keystore_path;
+  use crate::BuildProfile;
 
   #[test]
-  fn manifest_regex() {
-    let mut manifest_file_content =
-      String::from("<some header>\n<manifest option1\n\toption2>\n\t<hello world>\n</manifest>\n");
-    let captures = MANIFEST_TAG_CONTENT_REGEX
-      .captures(&manifest_file_content)
-      .unwrap();
-    let content_match = captures.get(1).unwrap();
-    assert_eq!(content_match.as_str(), "\n\t<hello world>\n");
+  fn staging() {
+    assert_eq!(
+      get_sdl_staging_dir(Path::new("/project"), "x86_64-linux-android"),
+      Path::new("/project/target/sdl-libs/x86_64-linux-android")
+    );
 
-    manifest_file_content.insert_str(content_match.end(), "\t<permission>\n");
+    let dir = tempdir().unwrap();
+    let src = dir.path().join("libSDL2.so");
+    let dest = dir.path().join("staged.so");
+    write(&src, "v1").unwrap();
+    stage_file(&src, &dest);
+    stage_file(&src, &dest);
+    write(&src, "v2").unwrap();
+    stage_file(&src, &dest);
+    assert_eq!(read(&dest).unwrap(), b"v2");
+    //no temporary files are left behind
+    assert_eq!(read_dir(dir.path()).unwrap().count(), 2);
+  }
 
-    let captures = MANIFEST_TAG_CONTENT_REGEX
-      .captures(&manifest_file_content)
-      .unwrap();
-    let content_match = captures.get(1).unwrap();
+  #[test]
+  fn project_in_path_with_spaces_and_unicode() {
+    let dir = tempdir().unwrap();
+    let manifest_dir = dir.path().join("my games/Spiel für Jürgen");
+    let assets_dir = manifest_dir.join("game assets");
+    create_dir_all(&assets_dir).unwrap();
+    write(assets_dir.join("größe.txt"), "ok").unwrap();
+    let manifest_path = manifest_dir.join("Cargo.toml");
+    write(
+      &manifest_path,
+      "[package]\nname = \"spiel\"\n\n[package.metadata.android]\nactivity_kind = \"native\"\n",
+    )
+    .unwrap();
+    let artifact = manifest_dir.join("target/aarch64-linux-android/debug/libspiel.so");
+    create_dir_all(artifact.parent().unwrap()).unwrap();
+    write(&artifact, "lib").unwrap();
+
+    let artifacts = HashMap::from([("aarch64-linux-android".to_string(), artifact)]);
+    let options = ProjectOptions {
+      assets: Some(assets_dir),
+      ..ProjectOptions::default()
+    };
+    create_android_project(
+      &manifest_path,
+      &artifacts,
+      BuildProfile::Debug,
+      false,
+      None,
+      options,
+    )
+    .unwrap();
+
+    let project_dir = get_android_project_dir(&manifest_dir, BuildProfile::Debug);
+    assert_eq!(
+      read(project_dir.join("app/src/main/jniLibs/arm64-v8a/libmain.so")).unwrap(),
+      b"lib"
+    );
+    assert_eq!(
+      read_to_string(project_dir.join("app/src/main/assets/größe.txt")).unwrap(),
+      "ok"
+    );
+    assert!(project_dir
+      .join("app/src/main/AndroidManifest.xml")
+      .is_file());
+  }
+
+  #[test]
+  fn old_project_dir_moved_aside() {
+    let dir = tempdir().unwrap();
+    let old_project = dir.path().join("target/android-project");
+    create_dir_all(old_project.join("app")).unwrap();
+    write(old_project.join("app/build.gradle"), "edited").unwrap();
+    create_dir_all(get_android_project_dir(dir.path(), BuildProfile::Debug)).unwrap();
+
+    link_latest_android_project(dir.path(), BuildProfile::Debug).unwrap();
+    assert!(old_project.is_symlink());
+    assert_eq!(
+      read_to_string(
+        dir
+          .path()
+          .join("target/android-project.old/app/build.gradle")
+      )
+      .unwrap(),
+      "edited"
+    );
+
+    //relinking replaces only the link
+    link_latest_android_project(dir.path(), BuildProfile::Release).unwrap();
+    assert!(old_project.is_symlink());
+
+    //a second old project dir doesn't replace the first one
+    symlink::remove_symlink_dir(&old_project).unwrap();
+    create_dir_all(&old_project).unwrap();
+    assert!(link_latest_android_project(dir.path(), BuildProfile::Debug).is_err());
+    assert!(old_project.is_dir() && !old_project.is_symlink());
+  }
+
+  #[test]
+  fn legacy_release_key_migrated_when_signing() {
+    let dir = tempdir().unwrap();
+    let legacy = dir
+      .path()
+      .join("target/android-project/app/build/outputs/apk/release/app-release.jks");
+    create_dir_all(legacy.parent().unwrap()).unwrap();
+    write(&legacy, "key").unwrap();
+
+    let signing = signing_or_default_keystore(dir.path(), None).unwrap();
     assert_eq!(
-      content_match.as_str(),
-      "\n\t<hello world>\n\t<permission>\n"
+      signing.signer.keystore,
+      get_release_keystore_path(dir.path())
     );
+    assert!(!legacy.exists());
+    assert_eq!(read(&signing.signer.keystore).unwrap(), b"key");
+  }
+
+  #[test]
+  fn custom_project_dir() {
+    let dir = tempdir().unwrap();
+    let manifest_path = dir.path().join("Cargo.toml");
+    write(
+      &manifest_path,
+      "[package]\nname = \"game\"\n\n[package.metadata.android]\nactivity_kind = \"native\"\n",
+    )
+    .unwrap();
+    let artifact = dir
+      .path()
+      .join("target/x86_64-linux-android/release/libgame.so");
+    create_dir_all(artifact.parent().unwrap()).unwrap();
+    write(&artifact, "lib").unwrap();
+
+    let project_dir = dir.path().join("ci/release-job");
+    assert_eq!(
+      resolve_android_project_dir(dir.path(), BuildProfile::Release, Some(&project_dir)),
+      project_dir
+    );
+    let options = ProjectOptions {
+      project_dir: Some(project_dir.clone()),
+      ..ProjectOptions::default()
+    };
+    create_android_project(
+      &manifest_path,
+      &HashMap::from([("x86_64-linux-android".to_string(), artifact)]),
+      BuildProfile::Release,
+      false,
+      None,
+      options,
+    )
+    .unwrap();
+
+    assert!(project_dir
+      .join("app/src/main/jniLibs/x86_64/libmain.so")
+      .is_file());
+    assert!(!get_android_project_dir(dir.path(), BuildProfile::Release).exists());
+    assert!(!dir.path().join("target/android-project").exists());
+  }
+
+  #[test]
+  fn flavor_source_sets() {
+    let dir = tempdir().unwrap();
+    let manifest_path = dir.path().join("Cargo.toml");
+    write(
+      &manifest_path,
+      r#"[package]
+name = "game"
+
+[package.metadata.android]
+activity_kind = "native"
+
+[[package.metadata.android.flavors]]
+name = "demo"
+application_id_suffix = ".demo"
+assets = "demo assets"
+
+[[package.metadata.android.flavors]]
+name = "full"
+"#,
+    )
+    .unwrap();
+    create_dir_all(dir.path().join("demo assets")).unwrap();
+    write(dir.path().join("demo assets/level.txt"), "1").unwrap();
+    let artifact = dir.path().join("libgame.so");
+    write(&artifact, "lib").unwrap();
+
+    let flavors = get_flavors(&manifest_path).unwrap();
+    let options = ProjectOptions {
+      flavor: Some(flavors[0].clone()),
+      flavors,
+      ..ProjectOptions::default()
+    };
+    create_android_project(
+      &manifest_path,
+      &HashMap::from([("x86_64-linux-android".to_string(), artifact)]),
+      BuildProfile::Release,
+      false,
+      None,
+      options,
+    )
+    .unwrap();
+
+    let app_dir = get_android_project_dir(dir.path(), BuildProfile::Release).join("app");
+    assert!(app_dir.join("src/demo/jniLibs/x86_64/libmain.so").is_file());
+    assert!(!app_dir.join("src/main/jniLibs/x86_64/libmain.so").exists());
+    assert!(app_dir.join("src/demo/assets/level.txt").is_file());
+    let build_gradle = read_to_string(app_dir.join("build.gradle")).unwrap();
+    assert!(build_gradle.contains("    productFlavors {\n        demo {"));
+    assert!(build_gradle.contains("applicationIdSuffix '.demo'"));
+  }
+
+  #[test]
+  fn stale_abis_removed() {
+    let dir = tempdir().unwrap();
+    let manifest_path = dir.path().join("Cargo.toml");
+    write(
+      &manifest_path,
+      "[package]\nname = \"game\"\n\n[package.metadata.android]\nactivity_kind = \"native\"\n\n\
+      [[package.metadata.android.flavors]]\nname = \"demo\"\n",
+    )
+    .unwrap();
+    let artifact = dir.path().join("libgame.so");
+    write(&artifact, "lib").unwrap();
+    let flavors = get_flavors(&manifest_path).unwrap();
+    let build = |targets: &[&str]| {
+      let options = ProjectOptions {
+        flavor: Some(flavors[0].clone()),
+        flavors: flavors.clone(),
+        ..ProjectOptions::default()
+      };
+      create_android_project(
+        &manifest_path,
+        &targets
+          .iter()
+          .map(|target| (target.to_string(), artifact.clone()))
+          .collect(),
+        BuildProfile::Debug,
+        false,
+        None,
+        options,
+      )
+      .unwrap();
+    };
+
+    build(&["aarch64-linux-android", "x86_64-linux-android"]);
+    let src_dir = get_android_project_dir(dir.path(), BuildProfile::Debug).join("app/src");
+    assert!(src_dir.join("demo/jniLibs/x86_64/libmain.so").is_file());
+    //e.g. the libSDL2.so of an earlier build of an SDL app
+    create_dir_all(src_dir.join("main/jniLibs/x86")).unwrap();
+    write(src_dir.join("main/jniLibs/x86/libSDL2.so"), "sdl").unwrap();
+
+    build(&["aarch64-linux-android"]);
+    assert!(src_dir.join("demo/jniLibs/arm64-v8a/libmain.so").is_file());
+    assert!(!src_dir.join("demo/jniLibs/x86_64").exists());
+    assert!(!src_dir.join("main/jniLibs/x86_64").exists());
+    assert!(!src_dir.join("main/jniLibs/x86").exists());
+  }
+
+  /// Generates the project from a template in the fixtures and returns its app build file.
+  fn generate_from_template(template: &str, build_file: &str) -> String {
+    let dir = tempdir().unwrap();
+    let template_dir = Path::new(env!("CARGO_MANIFEST_DIR"))
+      .join("tests/fixtures/templates")
+      .join(template);
+    let manifest_path = dir.path().join("Cargo.toml");
+    write(
+      &manifest_path,
+      format!(
+        r#"[package]
+name = "game"
+
+[package.metadata.android]
+package_name = "com.example.game"
+project_template = {:?}
+debug_application_id_suffix = ".debug"
+min_sdk_version = 24
+sdl_java = {{ maven = "org.libsdl.android:SDL3:3.2.0" }}
+
+[[package.metadata.android.flavors]]
+name = "demo"
+application_id_suffix = ".demo"
+"#,
+        template_dir.display().to_string()
+      ),
+    )
+    .unwrap();
+    let options = ProjectOptions {
+      flavors: get_flavors(&manifest_path).unwrap(),
+      build_info: Some(BuildInfo {
+        profile: BuildProfile::Debug,
+        crate_version: "1.0.0".to_string(),
+        git_hash: None,
+      }),
+      ..ProjectOptions::default()
+    };
+    create_android_project(
+      &manifest_path,
+      &HashMap::new(),
+      BuildProfile::Debug,
+      false,
+      None,
+      options,
+    )
+    .unwrap();
+
+    let project_dir = get_android_project_dir(dir.path(), BuildProfile::Debug);
+    let other_file = match build_file {
+      "app/build.gradle" => "app/build.gradle.kts",
+      _ => "app/build.gradle",
+    };
+    assert!(!project_dir.join(other_file).exists());
+    read_to_string(project_dir.join(build_file)).unwrap()
+  }
+
+  #[test]
+  fn kotlin_dsl_template() {
+    let groovy = generate_from_template("groovy", "app/build.gradle");
+    let kotlin = generate_from_template("kts", "app/build.gradle.kts");
+    //the same changes in the syntax of each template
+    let expected = [
+      (
+        "namespace 'com.example.game'",
+        "namespace = \"com.example.game\"",
+      ),
+      (
+        "applicationId 'com.example.game'",
+        "applicationId = \"com.example.game\"",
+      ),
+      ("minSdk 24", "minSdk = 24"),
+      (
+        "        debug {\n            applicationIdSuffix \".debug\"\n",
+        "        getByName(\"debug\") {\n            applicationIdSuffix = \".debug\"\n",
+      ),
+      (
+        "pickFirst 'lib/*/libSDL2.so'",
+        "jniLibs.pickFirsts += \"lib/*/libSDL2.so\"",
+      ),
+      (
+        "        demo {\n            dimension 'version'\n            applicationIdSuffix '.demo'\n",
+        "        create(\"demo\") {\n            dimension = \"version\"\n            applicationIdSuffix = \".demo\"\n",
+      ),
+      (
+        "    implementation 'org.libsdl.android:SDL3:3.2.0'\n",
+        "    implementation(\"org.libsdl.android:SDL3:3.2.0\")\n",
+      ),
+      (
+        "buildConfigField 'String', 'CRATE_VERSION', '\"1.0.0\"'",
+        "buildConfigField(\"String\", \"CRATE_VERSION\", \"\\\"1.0.0\\\"\")",
+      ),
+      ("buildConfig true", "buildConfig = true"),
+    ];
+    for (groovy_text, kotlin_text) in expected {
+      assert!(groovy.contains(groovy_text), "{}\n{}", groovy_text, groovy);
+      assert!(kotlin.contains(kotlin_text), "{}\n{}", kotlin_text, kotlin);
+    }
+    for content in [&groovy, &kotlin] {
+      assert!(!content.contains("org.libsdl.app"), "{}", content);
+    }
   }
 }
@@ -0,0 +1,220 @@
+use std::fs::{create_dir_all, write, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use zip::ZipArchive;
+
+use crate::adb::*;
+use crate::command_runner::*;
+use crate::monkey::*;
+
+/// Exit code of `test-launch` if the app stopped responding, to tell ANRs apart from crashes.
+pub const ANR_EXIT_CODE: i32 = 3;
+
+/// Traces file of devices before Android 10, readable with adb.
+const LEGACY_TRACES_PATH: &str = "/data/anr/traces.txt";
+
+/// Directory of the ANR traces inside a bugreport.
+const BUGREPORT_ANR_DIR: &str = "FS/data/anr/";
+
+/// Banner ndk-stack needs before the frames it symbolicates.
+const NDK_STACK_BANNER: &str = "*** *** *** *** *** *** *** *** *** *** *** *** *** *** *** ***";
+
+/// The stack of the main thread of the app in ANR traces, from the dump of the process whose
+/// command line is the app id.
+pub fn extract_main_thread_stack(traces: &str, app_id: &str) -> Option<String> {
+  let mut in_process = false;
+  let mut stack: Vec<&str> = vec![];
+  for line in traces.lines() {
+    if line.starts_with("----- pid ") {
+      in_process = false;
+    } else if line.trim() == format!("Cmd line: {}", app_id) {
+      in_process = true;
+    } else if in_process && line.starts_with("\"main\"") {
+      stack.push(line);
+    } else if !stack.is_empty() {
+      if line.trim().is_empty() {
+        break;
+      }
+      stack.push(line);
+    }
+  }
+  (!stack.is_empty()).then(|| stack.join("\n"))
+}
+
+/// The native frames of a stack in the format of tombstones, which ndk-stack understands.
+pub fn get_native_frames(stack: &str) -> Vec<String> {
+  stack
+    .lines()
+    .filter_map(|line| line.trim().strip_prefix("native: "))
+    .map(|frame| frame.to_string())
+    .collect()
+}
+
+/// Reads the newest ANR traces of a bugreport mentioning the app.
+fn read_bugreport_traces(bugreport: &Path, app_id: &str) -> Option<String> {
+  let mut archive = ZipArchive::new(File::open(bugreport).ok()?).ok()?;
+  let mut names: Vec<String> = archive
+    .file_names()
+    .filter(|name| name.starts_with(BUGREPORT_ANR_DIR))
+    .map(|name| name.to_string())
+    .collect();
+  //the files are named by date, newest last
+  names.sort();
+  for name in names.iter().rev() {
+    let mut content = String::new();
+    if archive
+      .by_name(name)
+      .ok()?
+      .read_to_string(&mut content)
+      .is_ok()
+      && content.contains(&format!("Cmd line: {}", app_id))
+    {
+      return Some(content);
+    }
+  }
+  None
+}
+
+/// Gets the ANR traces of the device. Newer devices don't let adb read them, they are taken from a
+/// bugreport saved to the dir instead, which takes a while.
+fn pull_anr_traces(dir: &Path, app_id: &str) -> Option<String> {
+  let legacy = adb_shell(&["cat", LEGACY_TRACES_PATH])
+    .filter(|traces| traces.contains(&format!("Cmd line: {}", app_id)));
+  if legacy.is_some() {
+    return legacy;
+  }
+
+  println!("Taking a bugreport for the ANR traces, this can take a few minutes...");
+  let bugreport = dir.join("bugreport.zip");
+  let status = run_command_output(adb_command().args(["bugreport"]).arg(&bugreport))
+    .map(|output| output.status.success());
+  if !status.unwrap_or(false) {
+    return None;
+  }
+  read_bugreport_traces(&bugreport, app_id)
+}
+
+/// Pulls the ANR traces of the app, prints the stack of its main thread with symbolicated native
+/// frames and saves it to `target/anr`. Returns the saved files.
+pub fn report_anr(manifest_dir: &Path, app_id: &str) -> Vec<PathBuf> {
+  let dir = manifest_dir.join("target/anr");
+  create_dir_all(&dir).unwrap_or_else(|_| panic!("Unable to create directory {:?}", dir));
+  let Some(traces) = pull_anr_traces(&dir, app_id) else {
+    eprintln!("WARNING: Unable to get the ANR traces from the device.");
+    return vec![];
+  };
+  let traces_path = dir.join("traces.txt");
+  write(&traces_path, &traces).unwrap_or_else(|_| panic!("Unable to write {:?}", traces_path));
+  let mut files = vec![traces_path];
+
+  let Some(stack) = extract_main_thread_stack(&traces, app_id) else {
+    eprintln!("WARNING: No main thread of {} in the ANR traces.", app_id);
+    return files;
+  };
+  eprintln!("Main thread of {}:", app_id);
+  eprintln!("{}", stack);
+
+  let frames = get_native_frames(&stack);
+  if !frames.is_empty() {
+    let frames_path = dir.join("native-frames.txt");
+    write(
+      &frames_path,
+      format!("{}\n{}\n", NDK_STACK_BANNER, frames.join("\n")),
+    )
+    .unwrap_or_else(|_| panic!("Unable to write {:?}", frames_path));
+    if let Some(symbolicated) = symbolicate(manifest_dir, &frames_path) {
+      eprintln!("{}", symbolicated);
+      let symbolicated_path = dir.join("main-thread-symbolicated.txt");
+      write(&symbolicated_path, symbolicated)
+        .unwrap_or_else(|_| panic!("Unable to write {:?}", symbolicated_path));
+      files.push(symbolicated_path);
+    }
+  }
+  files
+}
+
+#[cfg(test)]
+mod test {
+  use std::fs::File;
+  use std::io::Write;
+
+  use tempfile::tempdir;
+  use zip::write::FileOptions;
+  use zip::ZipWriter;
+
+  use crate::anr::{extract_main_thread_stack, get_native_frames, read_bugreport_traces};
+
+  const TRACES: &str = r#"
+----- pid 600 at 2023-10-01 12:00:00 -----
+Cmd line: system_server
+
+"main" prio=5 tid=1 Native
+  at android.os.MessageQueue.nativePollOnce(Native method)
+
+----- end 600 -----
+
+----- pid 4242 at 2023-10-01 12:00:00 -----
+Cmd line: com.example.game
+
+"Signal Catcher" daemon prio=10 tid=4 Runnable
+  | group="system" sCount=0 dsCount=0
+
+"main" prio=5 tid=1 Native
+  | group="main" sCount=1 dsCount=0 flags=1 obj=0x72e0c3f8 self=0xb400007a
+  native: #00 pc 000000000004f6d0  /apex/com.android.runtime/lib64/bionic/libc.so (syscall+32)
+  native: #01 pc 00000000000a1b2c  /data/app/com.example.game/lib/arm64/libmain.so (offset 0x1000)
+  at org.libsdl.app.SDLActivity.nativeRunMain(Native method)
+  at org.libsdl.app.SDLMain.run(SDLActivity.java:1836)
+
+"SDLThread" prio=5 tid=20 Runnable
+----- end 4242 -----
+"#;
+
+  #[test]
+  fn main_thread_stack() {
+    let stack = extract_main_thread_stack(TRACES, "com.example.game").unwrap();
+    assert!(stack.starts_with("\"main\" prio=5 tid=1 Native"));
+    assert!(stack.ends_with("at org.libsdl.app.SDLMain.run(SDLActivity.java:1836)"));
+    assert!(!stack.contains("system_server"));
+    assert!(!stack.contains("SDLThread"));
+    assert_eq!(extract_main_thread_stack(TRACES, "com.other"), None);
+
+    assert_eq!(
+      get_native_frames(&stack),
+      vec![
+        "#00 pc 000000000004f6d0  /apex/com.android.runtime/lib64/bionic/libc.so (syscall+32)",
+        "#01 pc 00000000000a1b2c  /data/app/com.example.game/lib/arm64/libmain.so (offset 0x1000)"
+      ]
+    );
+  }
+
+  #[test]
+  fn traces_from_bugreport() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("bugreport.zip");
+    let mut zip = ZipWriter::new(File::create(&path).unwrap());
+    for (name, content) in [
+      (
+        "FS/data/anr/anr_2023-10-01-11-00-00-000",
+        "Cmd line: com.other\n",
+      ),
+      ("FS/data/anr/anr_2023-10-01-12-00-00-000", TRACES),
+      (
+        "FS/data/anr/anr_2023-10-01-13-00-00-000",
+        "Cmd line: com.other\n",
+      ),
+      ("bugreport.txt", "Cmd line: com.example.game\n"),
+    ] {
+      zip.start_file(name, FileOptions::default()).unwrap();
+      zip.write_all(content.as_bytes()).unwrap();
+    }
+    zip.finish().unwrap();
+
+    assert_eq!(
+      read_bugreport_traces(&path, "com.example.game").as_deref(),
+      Some(TRACES)
+    );
+    assert_eq!(read_bugreport_traces(&path, "com.missing"), None);
+  }
+}
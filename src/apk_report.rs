@@ -0,0 +1,273 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::{Read, Seek};
+use std::path::Path;
+
+use zip::ZipArchive;
+
+use crate::template_prune::*;
+
+const BYTES_PER_MB: f64 = 1024.0 * 1024.0;
+
+/// The number of largest entries listed in the report.
+const TOP_ENTRY_COUNT: usize = 10;
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ApkEntryCategory {
+  NativeLibs(String),
+  Dex,
+  Resources,
+  Assets,
+  MetaInf,
+  Other,
+}
+
+impl ApkEntryCategory {
+  pub fn of(entry_name: &str) -> Self {
+    if let Some(lib_path) = entry_name.strip_prefix("lib/") {
+      if let Some((abi, _)) = lib_path.split_once('/') {
+        return ApkEntryCategory::NativeLibs(abi.to_string());
+      }
+    }
+
+    if entry_name.starts_with("assets/") {
+      ApkEntryCategory::Assets
+    } else if entry_name.starts_with("res/") || entry_name == "resources.arsc" {
+      ApkEntryCategory::Resources
+    } else if entry_name.starts_with("META-INF/") {
+      ApkEntryCategory::MetaInf
+    } else if entry_name.ends_with(".dex") {
+      ApkEntryCategory::Dex
+    } else {
+      ApkEntryCategory::Other
+    }
+  }
+
+  pub fn describe(&self) -> String {
+    match self {
+      ApkEntryCategory::NativeLibs(abi) => format!("native libs ({})", abi),
+      ApkEntryCategory::Dex => "dex".to_string(),
+      ApkEntryCategory::Resources => "resources".to_string(),
+      ApkEntryCategory::Assets => "assets".to_string(),
+      ApkEntryCategory::MetaInf => "META-INF".to_string(),
+      ApkEntryCategory::Other => "other".to_string(),
+    }
+  }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ApkSizes {
+  pub entries: usize,
+  pub compressed: u64,
+  pub uncompressed: u64,
+}
+
+#[derive(Debug)]
+pub struct ApkEntry {
+  pub name: String,
+  pub compressed: u64,
+  pub uncompressed: u64,
+}
+
+#[derive(Debug)]
+pub struct ApkReport {
+  pub file_size: u64,
+  pub categories: BTreeMap<ApkEntryCategory, ApkSizes>,
+  /// All entries, largest compressed size first.
+  pub entries: Vec<ApkEntry>,
+}
+
+pub fn create_apk_report(apk_path: &Path) -> ApkReport {
+  let file = File::open(apk_path).unwrap_or_else(|_| panic!("can't open apk {:?}", apk_path));
+  let file_size = file.metadata().unwrap().len();
+  read_apk_report(file, file_size)
+    .unwrap_or_else(|e| panic!("unable to read apk {:?}: {}", apk_path, e))
+}
+
+fn read_apk_report<R: Read + Seek>(reader: R, file_size: u64) -> zip::result::ZipResult<ApkReport> {
+  let mut archive = ZipArchive::new(reader)?;
+
+  let mut categories: BTreeMap<ApkEntryCategory, ApkSizes> = BTreeMap::new();
+  let mut entries = vec![];
+  for i in 0..archive.len() {
+    let file = archive.by_index_raw(i)?;
+    if file.is_dir() {
+      continue;
+    }
+
+    let sizes = categories
+      .entry(ApkEntryCategory::of(file.name()))
+      .or_default();
+    sizes.entries += 1;
+    sizes.compressed += file.compressed_size();
+    sizes.uncompressed += file.size();
+
+    entries.push(ApkEntry {
+      name: file.name().to_string(),
+      compressed: file.compressed_size(),
+      uncompressed: file.size(),
+    });
+  }
+  entries.sort_by(|a, b| b.compressed.cmp(&a.compressed).then(a.name.cmp(&b.name)));
+
+  Ok(ApkReport {
+    file_size,
+    categories,
+    entries,
+  })
+}
+
+/// The ABIs the APK contains native libraries for.
+pub fn read_apk_abis(apk_path: &Path) -> BTreeSet<String> {
+  create_apk_report(apk_path)
+    .categories
+    .into_keys()
+    .filter_map(|category| match category {
+      ApkEntryCategory::NativeLibs(abi) => Some(abi),
+      _ => None,
+    })
+    .collect()
+}
+
+fn format_size(bytes: u64) -> String {
+  if bytes as f64 >= BYTES_PER_MB {
+    format!("{:.2} MB", bytes as f64 / BYTES_PER_MB)
+  } else {
+    format!("{:.1} KB", bytes as f64 / 1024.0)
+  }
+}
+
+pub fn print_apk_report(report: &ApkReport) {
+  println!("APK size: {}", format_size(report.file_size));
+  println!();
+  println!(
+    "{:<28} {:>8} {:>14} {:>14}",
+    "category", "entries", "compressed", "uncompressed"
+  );
+  for (category, sizes) in &report.categories {
+    println!(
+      "{:<28} {:>8} {:>14} {:>14}",
+      category.describe(),
+      sizes.entries,
+      format_size(sizes.compressed),
+      format_size(sizes.uncompressed)
+    );
+  }
+  println!();
+  println!("Largest entries:");
+  for entry in report.entries.iter().take(TOP_ENTRY_COUNT) {
+    println!(
+      "  {:>12} {:>12}  {}",
+      format_size(entry.compressed),
+      format_size(entry.uncompressed),
+      entry.name
+    );
+  }
+}
+
+/// Prints what pruning the template saved in the last generation of the project, if it pruned
+/// anything. Left out libraries count with their size as gradle built them.
+pub fn print_prune_savings(project_dir: &Path) {
+  let Some(record) = PruneRecord::load(project_dir) else {
+    return;
+  };
+  println!();
+  println!("Pruned template:");
+  if !record.resources.is_empty() {
+    println!(
+      "  {:>12}  {} unreferenced resources",
+      format_size(record.resources_size()),
+      record.resources.len()
+    );
+  }
+  for lib in &record.sdl_libs {
+    let sizes = get_excluded_sdl_lib_sizes(project_dir, lib);
+    let abis: Vec<&str> = sizes.keys().map(String::as_str).collect();
+    println!(
+      "  {:>12}  lib{}.so left out{}",
+      format_size(sizes.values().sum()),
+      lib,
+      if abis.is_empty() {
+        String::new()
+      } else {
+        format!(" ({})", abis.join(", "))
+      }
+    );
+  }
+}
+
+/// Fails if the apk is larger than the given limit in MB.
+pub fn check_apk_size_limit(report: &ApkReport, size_limit: f64) -> Result<(), String> {
+  let size = report.file_size as f64 / BYTES_PER_MB;
+  if size > size_limit {
+    return Err(format!(
+      "APK size of {:.2} MB exceeds the limit of {} MB",
+      size, size_limit
+    ));
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod test {
+  use std::io::{Cursor, Write};
+
+  use zip::write::FileOptions;
+  use zip::{CompressionMethod, ZipWriter};
+
+  use crate::apk_report::{read_apk_report, ApkEntryCategory};
+
+  #[test]
+  fn categorize_entries() {
+    assert_eq!(
+      ApkEntryCategory::of("lib/arm64-v8a/libmain.so"),
+      ApkEntryCategory::NativeLibs("arm64-v8a".to_string())
+    );
+    assert_eq!(
+      ApkEntryCategory::of("assets/music.ogg"),
+      ApkEntryCategory::Assets
+    );
+    assert_eq!(
+      ApkEntryCategory::of("res/mipmap-hdpi/ic_launcher.png"),
+      ApkEntryCategory::Resources
+    );
+    assert_eq!(
+      ApkEntryCategory::of("resources.arsc"),
+      ApkEntryCategory::Resources
+    );
+    assert_eq!(ApkEntryCategory::of("classes2.dex"), ApkEntryCategory::Dex);
+    assert_eq!(
+      ApkEntryCategory::of("META-INF/CERT.SF"),
+      ApkEntryCategory::MetaInf
+    );
+    assert_eq!(
+      ApkEntryCategory::of("AndroidManifest.xml"),
+      ApkEntryCategory::Other
+    );
+  }
+
+  #[test]
+  fn report_sums_categories() {
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    let stored = FileOptions::default().compression_method(CompressionMethod::Stored);
+    for (name, size) in [
+      ("lib/arm64-v8a/libmain.so", 300),
+      ("lib/arm64-v8a/libSDL2.so", 200),
+      ("lib/x86_64/libmain.so", 100),
+      ("classes.dex", 50),
+    ] {
+      writer.start_file(name, stored).unwrap();
+      writer.write_all(&vec![0; size]).unwrap();
+    }
+    let content = writer.finish().unwrap().into_inner();
+    let file_size = content.len() as u64;
+
+    let report = read_apk_report(Cursor::new(content), file_size).unwrap();
+    let arm64 = report.categories[&ApkEntryCategory::NativeLibs("arm64-v8a".to_string())];
+    assert_eq!(arm64.entries, 2);
+    assert_eq!(arm64.uncompressed, 500);
+    assert_eq!(report.categories[&ApkEntryCategory::Dex].compressed, 50);
+    assert_eq!(report.entries[0].name, "lib/arm64-v8a/libmain.so");
+    assert_eq!(report.entries.len(), 4);
+  }
+}
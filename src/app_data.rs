@@ -0,0 +1,153 @@
+use std::fs::create_dir_all;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use crate::adb::*;
+use crate::command_runner::*;
+
+/// Directory inside the app's data dir which is pulled and pushed.
+const APP_FILES_DIR: &str = "files";
+
+/// Checks the `dumpsys package` output for the installed app.
+pub fn is_installed(dumpsys_output: &str, app_id: &str) -> bool {
+  dumpsys_output.contains(&format!("Package [{}]", app_id))
+}
+
+/// Checks the `dumpsys package` output for the debuggable flag, which `run-as` requires.
+pub fn is_debuggable(dumpsys_output: &str) -> bool {
+  dumpsys_output
+    .lines()
+    .map(|line| line.trim())
+    .filter(|line| line.starts_with("flags=[") || line.starts_with("pkgFlags=["))
+    .any(|line| line.split_whitespace().any(|flag| flag == "DEBUGGABLE"))
+}
+
+/// Exits with an explanation unless the app is installed as debuggable build.
+fn ensure_debuggable(app_id: &str) {
+  let dumpsys = adb_shell(&["dumpsys", "package", app_id]).unwrap_or_default();
+  if !is_installed(&dumpsys, app_id) {
+    eprintln!("Error: {} is not installed on the device.", app_id);
+    std::process::exit(1);
+  }
+  if !is_debuggable(&dumpsys) {
+    eprintln!(
+      "Error: The installed {} is not debuggable. App data can only be accessed with run-as for debug builds, install one with `cargo sdl-apk install` first.",
+      app_id
+    );
+    std::process::exit(1);
+  }
+}
+
+/// Pipes the output of the first command into the second, exiting if either fails.
+fn pipe_commands(source: &mut Command, sink: &mut Command) {
+  let mut source_child = source.stdout(Stdio::piped()).spawn().unwrap_or_else(|e| {
+    panic!(
+      "Failed to execute command: {:?}: {}",
+      source.get_program(),
+      e
+    )
+  });
+  let sink_status = sink
+    .stdin(source_child.stdout.take().unwrap())
+    .status()
+    .unwrap_or_else(|e| panic!("Failed to execute command: {:?}: {}", sink.get_program(), e));
+  let source_status = source_child.wait().expect("Failed to wait for command");
+
+  if !source_status.success() {
+    exit_with_command_failure(source.get_program(), source_status);
+  }
+  if !sink_status.success() {
+    exit_with_command_failure(sink.get_program(), sink_status);
+  }
+}
+
+/// Copies the files dir of the app into the destination dir.
+pub fn pull_app_data(app_id: &str, dest: &Path) {
+  ensure_debuggable(app_id);
+  create_dir_all(dest).unwrap_or_else(|_| panic!("Unable to create directory {:?}", dest));
+
+  println!("Pulling data of {} to {}...", app_id, dest.display());
+  pipe_commands(
+    adb_command().args([
+      "exec-out",
+      "run-as",
+      app_id,
+      "tar",
+      "-cf",
+      "-",
+      "-C",
+      APP_FILES_DIR,
+      ".",
+    ]),
+    Command::new("tar").arg("-xf").arg("-").arg("-C").arg(dest),
+  );
+}
+
+/// Copies the content of the source dir into the files dir of the app, replacing files with the
+/// same name.
+pub fn push_app_data(app_id: &str, src: &Path) {
+  if !src.is_dir() {
+    eprintln!("Error: {:?} is not a directory.", src);
+    std::process::exit(1);
+  }
+  ensure_debuggable(app_id);
+
+  println!("Pushing {} to the data of {}...", src.display(), app_id);
+  //the app is stopped, so it doesn't overwrite the pushed files with its state in memory
+  run_command_checked(adb_command().args(["shell", "am", "force-stop", app_id]));
+  run_command_checked(adb_command().args([
+    "shell",
+    "run-as",
+    app_id,
+    "mkdir",
+    "-p",
+    APP_FILES_DIR,
+  ]));
+  pipe_commands(
+    Command::new("tar")
+      .arg("-cf")
+      .arg("-")
+      .arg("-C")
+      .arg(src)
+      .arg("."),
+    adb_command().args([
+      "exec-in",
+      "run-as",
+      app_id,
+      "tar",
+      "-xf",
+      "-",
+      "-C",
+      APP_FILES_DIR,
+    ]),
+  );
+}
+
+/// Deletes all data of the app. Unlike pulling and pushing, this works for release builds too.
+pub fn clear_app_data(app_id: &str) {
+  run_command_checked(adb_command().args(["shell", "pm", "clear", app_id]));
+}
+
+#[cfg(test)]
+mod test {
+  use crate::app_data::{is_debuggable, is_installed};
+
+  const DEBUG_DUMPSYS: &str = "Packages:
+  Package [com.example.game] (8f2c1a4):
+    userId=10123
+    pkg=Package{5d1e0b2 com.example.game}
+    flags=[ DEBUGGABLE HAS_CODE ALLOW_CLEAR_USER_DATA ALLOW_BACKUP ]
+    privateFlags=[ PRIVATE_FLAG_ACTIVITIES_RESIZEABLE_VIA_SDK_VERSION ]
+";
+
+  #[test]
+  fn debuggable_flag() {
+    assert!(is_installed(DEBUG_DUMPSYS, "com.example.game"));
+    assert!(!is_installed("", "com.example.game"));
+    assert!(is_debuggable(DEBUG_DUMPSYS));
+    assert!(!is_debuggable(&DEBUG_DUMPSYS.replace("DEBUGGABLE ", "")));
+    assert!(is_debuggable(
+      "  Package [com.example.game] (8f2c1a4):\n    pkgFlags=[ DEBUGGABLE HAS_CODE ]\n"
+    ));
+  }
+}
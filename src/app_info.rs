@@ -0,0 +1,193 @@
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+
+use toml::Value;
+
+use crate::util::*;
+
+/// Icon used if neither the android nor the bundle metadata configures one.
+const DEFAULT_ICON_PATH: &str = "assets/icon.png";
+
+/// Where a value of the app was taken from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InfoSource {
+  /// The android metadata.
+  AndroidMetadata,
+  /// `package.metadata.bundle`, used by other bundlers like cargo-bundle.
+  BundleMetadata,
+  /// The package name.
+  PackageName,
+  /// `assets/icon.png` of the package.
+  DefaultIconFile,
+  /// Nothing configured, the value of the template is kept.
+  Template,
+}
+
+impl InfoSource {
+  /// Whether the value was configured explicitly for android, so a problem with it is an error.
+  pub fn is_explicit(&self) -> bool {
+    *self == InfoSource::AndroidMetadata
+  }
+}
+
+impl Display for InfoSource {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match self {
+      InfoSource::AndroidMetadata => write!(f, "package.metadata.android"),
+      InfoSource::BundleMetadata => write!(f, "package.metadata.bundle"),
+      InfoSource::PackageName => write!(f, "package.name"),
+      InfoSource::DefaultIconFile => write!(f, "{}", DEFAULT_ICON_PATH),
+      InfoSource::Template => write!(f, "the project template"),
+    }
+  }
+}
+
+/// Converts a package name to a title, e.g. `my-cool_game` to `My Cool Game`.
+pub fn title_case(package_name: &str) -> String {
+  package_name
+    .split(['-', '_'])
+    .filter(|word| !word.is_empty())
+    .map(|word| {
+      let mut chars = word.chars();
+      match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+      }
+    })
+    .collect::<Vec<String>>()
+    .join(" ")
+}
+
+/// The title of the app: `title` of the android metadata, or the package name in title case.
+pub fn resolve_app_title(manifest_path: &Path) -> Result<(String, InfoSource), TomlError> {
+  if let Some(title) = get_toml_string(manifest_path, ["package", "metadata", "android", "title"])?
+  {
+    return Ok((title, InfoSource::AndroidMetadata));
+  }
+  Ok(match get_toml_string(manifest_path, ["package", "name"])? {
+    Some(name) => (title_case(&name), InfoSource::PackageName),
+    None => ("Untitled".to_string(), InfoSource::Template),
+  })
+}
+
+/// Picks the icon of the bundle metadata, which is a path or a list of paths of the icon in
+/// different sizes. The largest existing one is used.
+fn get_bundle_icon(manifest_dir: &Path, value: &Value) -> Option<PathBuf> {
+  let paths: Vec<PathBuf> = match value {
+    Value::String(path) => vec![manifest_dir.join(path)],
+    Value::Array(paths) => paths
+      .iter()
+      .filter_map(|path| path.as_str())
+      .map(|path| manifest_dir.join(path))
+      .collect(),
+    _ => vec![],
+  };
+  paths
+    .into_iter()
+    .filter(|path| path.is_file())
+    .max_by_key(|path| {
+      image::image_dimensions(path)
+        .map(|(width, height)| width.min(height))
+        .unwrap_or(0)
+    })
+}
+
+/// The icon of the app, in this order: `icon` of the android metadata, `icon` of the bundle
+/// metadata and `assets/icon.png`. Returns `None` if none is configured or exists, which keeps the
+/// icon of the template.
+pub fn resolve_app_icon(manifest_path: &Path) -> Result<Option<(PathBuf, InfoSource)>, TomlError> {
+  let manifest_dir = manifest_path.parent().unwrap();
+  if let Some(icon) = get_toml_string(manifest_path, ["package", "metadata", "android", "icon"])? {
+    return Ok(Some((manifest_dir.join(icon), InfoSource::AndroidMetadata)));
+  }
+  if let Some(icon) = get_toml_entry(manifest_path, ["package", "metadata", "bundle", "icon"])?
+    .and_then(|value| get_bundle_icon(manifest_dir, &value))
+  {
+    return Ok(Some((icon, InfoSource::BundleMetadata)));
+  }
+  let default_icon = manifest_dir.join(DEFAULT_ICON_PATH);
+  Ok(
+    default_icon
+      .is_file()
+      .then_some((default_icon, InfoSource::DefaultIconFile)),
+  )
+}
+
+#[cfg(test)]
+mod test {
+  use std::fs::{create_dir_all, write};
+
+  use image::{DynamicImage, RgbImage};
+  use tempfile::tempdir;
+
+  use crate::app_info::{resolve_app_icon, resolve_app_title, title_case, InfoSource};
+
+  #[test]
+  fn titles() {
+    assert_eq!(title_case("my-cool_game"), "My Cool Game");
+    assert_eq!(title_case("game"), "Game");
+    assert_eq!(title_case("--x"), "X");
+
+    let dir = tempdir().unwrap();
+    let manifest = dir.path().join("Cargo.toml");
+    write(&manifest, "[package]\nname = \"space-rocks\"\n").unwrap();
+    assert_eq!(
+      resolve_app_title(&manifest).unwrap(),
+      ("Space Rocks".to_string(), InfoSource::PackageName)
+    );
+
+    write(
+      &manifest,
+      "[package]\nname = \"space-rocks\"\n\n[package.metadata.android]\ntitle = \"Rocks!\"\n",
+    )
+    .unwrap();
+    assert_eq!(
+      resolve_app_title(&manifest).unwrap(),
+      ("Rocks!".to_string(), InfoSource::AndroidMetadata)
+    );
+  }
+
+  #[test]
+  fn icons() {
+    let dir = tempdir().unwrap();
+    let manifest = dir.path().join("Cargo.toml");
+    write(&manifest, "[package]\nname = \"game\"\n").unwrap();
+    assert_eq!(resolve_app_icon(&manifest).unwrap(), None);
+
+    create_dir_all(dir.path().join("assets")).unwrap();
+    write(dir.path().join("assets/icon.png"), "").unwrap();
+    assert_eq!(
+      resolve_app_icon(&manifest).unwrap(),
+      Some((
+        dir.path().join("assets/icon.png"),
+        InfoSource::DefaultIconFile
+      ))
+    );
+
+    //the largest existing icon of the bundle metadata
+    for (name, size) in [("icon32.png", 32), ("icon512.png", 512)] {
+      DynamicImage::ImageRgb8(RgbImage::new(size, size))
+        .save(dir.path().join(name))
+        .unwrap();
+    }
+    write(
+      &manifest,
+      "[package]\nname = \"game\"\n\n[package.metadata.bundle]\nicon = [\"icon32.png\", \"icon512.png\", \"missing.png\"]\n",
+    )
+    .unwrap();
+    assert_eq!(
+      resolve_app_icon(&manifest).unwrap(),
+      Some((dir.path().join("icon512.png"), InfoSource::BundleMetadata))
+    );
+
+    write(
+      &manifest,
+      "[package]\nname = \"game\"\n\n[package.metadata.bundle]\nicon = [\"icon32.png\"]\n\n[package.metadata.android]\nicon = \"android.png\"\n",
+    )
+    .unwrap();
+    assert_eq!(
+      resolve_app_icon(&manifest).unwrap(),
+      Some((dir.path().join("android.png"), InfoSource::AndroidMetadata))
+    );
+  }
+}
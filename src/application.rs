@@ -0,0 +1,212 @@
+use std::fs::read_dir;
+use std::path::Path;
+
+use crate::gradle_dsl::*;
+use crate::util::*;
+use crate::warnings::*;
+
+/// First api level with native multidex support. Below it the multidex library loads the
+/// secondary dex files.
+const NATIVE_MULTIDEX_MIN_SDK_VERSION: u32 = 21;
+
+const MULTIDEX_DEPENDENCY: &str = "androidx.multidex:multidex:2.0.1";
+
+/// Application class installing the multidex library, used if no application class is configured.
+pub const MULTIDEX_APPLICATION_CLASS: &str = "androidx.multidex.MultiDexApplication";
+
+/// Reads `application_class` of the android metadata, the fully qualified name of the
+/// `android.app.Application` subclass to use.
+pub fn get_application_class(manifest_path: &Path) -> Result<Option<String>, String> {
+  Ok(get_toml_string(
+    manifest_path,
+    ["package", "metadata", "android", "application_class"],
+  )?)
+}
+
+/// Reads `multidex` of the android metadata.
+pub fn is_multidex_enabled(manifest_path: &Path) -> Result<bool, String> {
+  Ok(
+    get_toml_bool(
+      manifest_path,
+      ["package", "metadata", "android", "multidex"],
+    )?
+    .unwrap_or(false),
+  )
+}
+
+/// Whether the multidex library is needed, as the platform doesn't support multidex natively.
+pub fn needs_multidex_library(min_sdk_version: Option<u32>) -> bool {
+  min_sdk_version.is_none_or(|v| v < NATIVE_MULTIDEX_MIN_SDK_VERSION)
+}
+
+/// The application class set on the manifest: the configured one, or the one of the multidex
+/// library if it is needed.
+pub fn get_effective_application_class(
+  manifest_path: &Path,
+  min_sdk_version: Option<u32>,
+) -> Result<Option<String>, String> {
+  if let Some(application_class) = get_application_class(manifest_path)? {
+    return Ok(Some(application_class));
+  }
+  Ok(
+    (is_multidex_enabled(manifest_path)? && needs_multidex_library(min_sdk_version))
+      .then(|| MULTIDEX_APPLICATION_CLASS.to_string()),
+  )
+}
+
+/// Searches the directory for a java or kotlin source file of the class, skipping target dirs and
+/// hidden dirs.
+pub fn find_class_source(dir: &Path, class_name: &str) -> bool {
+  let relative = class_name.replace('.', "/");
+  let candidates = [format!("{}.java", relative), format!("{}.kt", relative)];
+  if candidates.iter().any(|c| dir.join(c).is_file()) {
+    return true;
+  }
+
+  let Ok(entries) = read_dir(dir) else {
+    return false;
+  };
+  entries.filter_map(|e| e.ok()).any(|e| {
+    let name = e.file_name();
+    let name = name.to_string_lossy();
+    e.path().is_dir()
+      && name != "target"
+      && !name.starts_with('.')
+      && find_class_source(&e.path(), class_name)
+  })
+}
+
+/// Warns if the configured application class can't be found in the sources of the package, so it
+/// has to come from a gradle dependency.
+pub fn check_application_class(manifest_path: &Path, class_name: &str) -> Result<(), String> {
+  if !class_name.contains('.') {
+    return Err(format!(
+      "application_class \"{}\" must be a fully qualified class name, e.g. \"com.example.MyApp\"",
+      class_name
+    ));
+  }
+  if !find_class_source(manifest_path.parent().unwrap(), class_name) {
+    warn(
+      &APPLICATION_CLASS,
+      format!(
+        "No source of the application class {} found in the package. The build fails unless a \
+        gradle dependency provides it.",
+        class_name
+      ),
+    );
+  }
+  Ok(())
+}
+
+/// Enables multidex in app/build.gradle and adds the multidex library if the min sdk version needs
+/// it. Content already enabling multidex is returned unchanged.
+pub fn enable_multidex(build_gradle: &str, min_sdk_version: Option<u32>, dsl: GradleDsl) -> String {
+  if build_gradle.contains("multiDexEnabled") {
+    return build_gradle.to_string();
+  }
+  let mut content = insert_after_line(
+    build_gradle,
+    |l| l.starts_with("minSdkVersion") || l.starts_with("minSdk "),
+    &dsl.assign("multiDexEnabled", "true"),
+    "",
+  )
+  .unwrap_or_else(|| {
+    warn(
+      &MULTIDEX,
+      "Unable to enable multidex, no minSdkVersion found in build.gradle.",
+    );
+    build_gradle.to_string()
+  });
+
+  if !needs_multidex_library(min_sdk_version) || content.contains("androidx.multidex:") {
+    return content;
+  }
+  let dependency = dsl.call("implementation", &[&dsl.string(MULTIDEX_DEPENDENCY)]);
+  insert_after_line(&content, |l| l == "dependencies {", &dependency, "    ").unwrap_or_else(|| {
+    content.push_str(&format!("\ndependencies {{\n    {}\n}}\n", dependency));
+    content
+  })
+}
+
+/// Enables AndroidX in gradle.properties, which the android gradle plugin requires for androidx
+/// dependencies.
+pub fn enable_android_x(gradle_properties: &str) -> String {
+  if gradle_properties.contains("android.useAndroidX") {
+    return gradle_properties.to_string();
+  }
+  let mut content = gradle_properties.to_string();
+  if !content.is_empty() && !content.ends_with('\n') {
+    content.push('\n');
+  }
+  content.push_str("android.useAndroidX=true\n");
+  content
+}
+
+#[cfg(test)]
+mod test {
+  use std::fs::{create_dir_all, write};
+
+  use tempfile::tempdir;
+
+  use crate::application::{enable_android_x, enable_multidex, find_class_source};
+  use crate::gradle_dsl::GradleDsl;
+
+  const BUILD_GRADLE: &str = "android {
+    defaultConfig {
+        minSdkVersion 16
+        targetSdkVersion 31
+    }
+}
+
+dependencies {
+    implementation fileTree(include: ['*.jar'], dir: 'libs')
+}
+";
+
+  #[test]
+  fn multidex() {
+    let content = enable_multidex(BUILD_GRADLE, Some(16), GradleDsl::Groovy);
+    assert!(content.contains("        minSdkVersion 16\n        multiDexEnabled true\n"));
+    assert!(
+      content.contains("dependencies {\n    implementation 'androidx.multidex:multidex:2.0.1'\n")
+    );
+    assert_eq!(
+      enable_multidex(&content, Some(16), GradleDsl::Groovy),
+      content
+    );
+
+    let content = enable_multidex(BUILD_GRADLE, Some(21), GradleDsl::Groovy);
+    assert!(content.contains("multiDexEnabled true"));
+    assert!(!content.contains("androidx.multidex"));
+
+    let kts =
+      "android {\n    defaultConfig {\n        minSdk = 16\n    }\n}\n\ndependencies {\n}\n";
+    let content = enable_multidex(kts, Some(16), GradleDsl::Kotlin);
+    assert!(content.contains("        minSdk = 16\n        multiDexEnabled = true\n"));
+    assert!(content.contains("    implementation(\"androidx.multidex:multidex:2.0.1\")\n"));
+  }
+
+  #[test]
+  fn android_x() {
+    let content = enable_android_x("org.gradle.jvmargs=-Xmx1536m");
+    assert_eq!(
+      content,
+      "org.gradle.jvmargs=-Xmx1536m\nandroid.useAndroidX=true\n"
+    );
+    assert_eq!(enable_android_x(&content), content);
+  }
+
+  #[test]
+  fn class_sources() {
+    let dir = tempdir().unwrap();
+    assert!(!find_class_source(dir.path(), "com.example.MyApp"));
+
+    create_dir_all(dir.path().join("target/java/com/example")).unwrap();
+    write(dir.path().join("target/java/com/example/MyApp.java"), "").unwrap();
+    assert!(!find_class_source(dir.path(), "com.example.MyApp"));
+
+    create_dir_all(dir.path().join("android/java/com/example")).unwrap();
+    write(dir.path().join("android/java/com/example/MyApp.kt"), "").unwrap();
+    assert!(find_class_source(dir.path(), "com.example.MyApp"));
+  }
+}
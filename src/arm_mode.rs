@@ -0,0 +1,173 @@
+use std::path::Path;
+
+use crate::sdk::*;
+use crate::util::*;
+
+/// The target using the arm mode, the only android target where NEON is optional.
+pub const ARM_MODE_TARGET: &str = "armv7-linux-androideabi";
+
+/// First sdk version whose armeabi-v7a devices all support NEON. Android has no feature to filter
+/// devices without NEON by, so the min sdk version keeps them from installing NEON builds.
+pub const NEON_MIN_SDK_VERSION: u32 = 23;
+
+/// Whether armeabi-v7a code uses NEON, configured by `arm_mode` in the android metadata.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArmMode {
+  /// NEON for Rust and SDL, for devices with NEON only.
+  Neon,
+  /// No NEON at all, for every armeabi-v7a device.
+  Generic,
+}
+
+impl ArmMode {
+  pub fn parse(value: &str) -> Option<Self> {
+    match value {
+      "neon" => Some(ArmMode::Neon),
+      "generic" => Some(ArmMode::Generic),
+      _ => None,
+    }
+  }
+
+  /// Rustflags building the armeabi-v7a library for the mode. Rust doesn't use NEON for the
+  /// target by default.
+  pub fn rustflags(&self) -> Vec<String> {
+    match self {
+      ArmMode::Neon => vec!["-C".to_string(), "target-feature=+neon".to_string()],
+      ArmMode::Generic => vec![],
+    }
+  }
+
+  /// ndk-build arguments building SDL for the mode. The NDK enables NEON by default, so generic
+  /// builds need to disable it.
+  pub fn ndk_build_args(&self) -> Vec<String> {
+    match self {
+      ArmMode::Neon => vec!["LOCAL_ARM_NEON=true".to_string()],
+      ArmMode::Generic => vec!["LOCAL_ARM_NEON=false".to_string()],
+    }
+  }
+}
+
+/// Reads `arm_mode` of the android metadata. Without it the defaults of rustc and the NDK apply.
+pub fn get_arm_mode(manifest_path: &Path) -> Result<Option<ArmMode>, String> {
+  match get_toml_string(
+    manifest_path,
+    ["package", "metadata", "android", "arm_mode"],
+  )? {
+    None => Ok(None),
+    Some(mode) => ArmMode::parse(&mode).map(Some).ok_or_else(|| {
+      format!(
+        "Unknown arm_mode \"{}\", expected \"neon\" or \"generic\"",
+        mode
+      )
+    }),
+  }
+}
+
+/// Whether the rustflags turn NEON on (`Some(true)`) or off (`Some(false)`).
+pub fn get_rustflags_neon(rustflags: &[String]) -> Option<bool> {
+  let mut neon = None;
+  for (i, flag) in rustflags.iter().enumerate() {
+    let features = match flag.strip_prefix("-C") {
+      Some("") => rustflags.get(i + 1).map(|f| f.as_str()),
+      Some(codegen) => Some(codegen),
+      None => None,
+    };
+    let Some(features) = features.and_then(|f| f.strip_prefix("target-feature=")) else {
+      continue;
+    };
+    for feature in features.split(',') {
+      match feature {
+        "+neon" => neon = Some(true),
+        "-neon" => neon = Some(false),
+        _ => {}
+      }
+    }
+  }
+  neon
+}
+
+/// Checks that the rustflags of the armeabi-v7a library match the mode, e.g. that no NEON flag
+/// from `RUSTFLAGS` or the cargo config slips into a generic build.
+pub fn check_arm_rustflags(mode: ArmMode, rustflags: &[String]) -> Result<(), String> {
+  match (mode, get_rustflags_neon(rustflags)) {
+    (ArmMode::Generic, Some(true)) => Err(format!(
+      "arm_mode is \"generic\", but the rustflags of {} enable NEON. Remove target-feature=+neon \
+      or set arm_mode = \"neon\"",
+      ARM_MODE_TARGET
+    )),
+    (ArmMode::Neon, Some(false)) => Err(format!(
+      "arm_mode is \"neon\", but the rustflags of {} disable NEON. Remove target-feature=-neon \
+      or set arm_mode = \"generic\"",
+      ARM_MODE_TARGET
+    )),
+    _ => Ok(()),
+  }
+}
+
+/// Checks that devices without NEON can't install a NEON build.
+pub fn check_arm_min_sdk_version(
+  mode: ArmMode,
+  min_sdk_version: Option<u32>,
+) -> Result<(), String> {
+  match (mode, min_sdk_version) {
+    (ArmMode::Neon, Some(actual)) if actual < NEON_MIN_SDK_VERSION => Err(format!(
+      "arm_mode = \"neon\" needs a min sdk version of at least {} so devices without NEON can't install the app, but it is {}. Set min_sdk_version = {} in the android metadata or use arm_mode = \"generic\"",
+      NEON_MIN_SDK_VERSION, actual, NEON_MIN_SDK_VERSION
+    )),
+    (ArmMode::Neon, None) => Err(format!(
+      "arm_mode = \"neon\" needs a min sdk version of at least {}, but it can't be determined. Set min_sdk_version = {} in the android metadata",
+      NEON_MIN_SDK_VERSION, NEON_MIN_SDK_VERSION
+    )),
+    _ => Ok(()),
+  }
+}
+
+/// Checks the arm mode against the min sdk version if armeabi-v7a is built.
+pub fn check_arm_mode(manifest_path: &Path, targets: &[&str]) -> Result<(), String> {
+  match get_arm_mode(manifest_path)? {
+    Some(mode) if targets.contains(&ARM_MODE_TARGET) => {
+      check_arm_min_sdk_version(mode, get_min_sdk_version(manifest_path)?)
+    }
+    _ => Ok(()),
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use crate::arm_mode::{
+    check_arm_min_sdk_version, check_arm_rustflags, get_rustflags_neon, ArmMode,
+  };
+
+  fn flags(flags: &str) -> Vec<String> {
+    flags.split_whitespace().map(|f| f.to_string()).collect()
+  }
+
+  #[test]
+  fn neon_rustflags() {
+    assert_eq!(get_rustflags_neon(&ArmMode::Neon.rustflags()), Some(true));
+    assert_eq!(get_rustflags_neon(&ArmMode::Generic.rustflags()), None);
+    assert_eq!(
+      get_rustflags_neon(&flags("-Ctarget-feature=+v7,+neon")),
+      Some(true)
+    );
+    assert_eq!(
+      get_rustflags_neon(&flags("-C target-feature=+neon -C target-feature=-neon")),
+      Some(false)
+    );
+    assert_eq!(get_rustflags_neon(&flags("-C opt-level=s")), None);
+  }
+
+  #[test]
+  fn mismatched_arm_mode() {
+    let neon_flags = flags("-C target-feature=+neon");
+    assert!(check_arm_rustflags(ArmMode::Generic, &neon_flags).is_err());
+    assert!(check_arm_rustflags(ArmMode::Neon, &neon_flags).is_ok());
+    assert!(check_arm_rustflags(ArmMode::Generic, &flags("-C opt-level=s")).is_ok());
+    assert!(check_arm_rustflags(ArmMode::Neon, &flags("-C target-feature=-neon")).is_err());
+
+    assert!(check_arm_min_sdk_version(ArmMode::Neon, Some(23)).is_ok());
+    assert!(check_arm_min_sdk_version(ArmMode::Neon, Some(19)).is_err());
+    assert!(check_arm_min_sdk_version(ArmMode::Neon, None).is_err());
+    assert!(check_arm_min_sdk_version(ArmMode::Generic, Some(16)).is_ok());
+  }
+}
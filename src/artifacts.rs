@@ -0,0 +1,127 @@
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::util::*;
+
+/// A package format the build produces.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Artifact {
+  /// The APK, installed by `install` and `run` and used for sideloading.
+  Apk,
+  /// The Android App Bundle uploaded to Google Play.
+  Aab,
+}
+
+impl Display for Artifact {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Artifact::Apk => write!(f, "apk"),
+      Artifact::Aab => write!(f, "aab"),
+    }
+  }
+}
+
+impl FromStr for Artifact {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s.trim() {
+      "apk" => Ok(Artifact::Apk),
+      "aab" => Ok(Artifact::Aab),
+      _ => Err(format!("unknown artifact \"{}\", expected apk or aab", s)),
+    }
+  }
+}
+
+/// Sorts out duplicates of the artifacts, requiring the APK, which `install` and `run` need.
+pub fn check_artifacts(mut artifacts: Vec<Artifact>) -> Result<Vec<Artifact>, String> {
+  artifacts.sort();
+  artifacts.dedup();
+  if !artifacts.contains(&Artifact::Apk) {
+    return Err("artifacts must include apk, which install and run use, e.g. apk,aab".to_string());
+  }
+  Ok(artifacts)
+}
+
+/// The artifacts to build, the requested ones or else `artifacts` of the android metadata like
+/// `["apk", "aab"]`, by default only the APK.
+pub fn select_artifacts(
+  manifest_path: &Path,
+  requested: &[Artifact],
+) -> Result<Vec<Artifact>, String> {
+  if !requested.is_empty() {
+    return check_artifacts(requested.to_vec());
+  }
+  match get_toml_entry(
+    manifest_path,
+    ["package", "metadata", "android", "artifacts"],
+  )? {
+    None => Ok(vec![Artifact::Apk]),
+    Some(value) => {
+      let names = value
+        .as_array()
+        .and_then(|names| {
+          names
+            .iter()
+            .map(|n| n.as_str())
+            .collect::<Option<Vec<&str>>>()
+        })
+        .ok_or_else(|| "artifacts must be a list like [\"apk\", \"aab\"]".to_string())?;
+      check_artifacts(
+        names
+          .iter()
+          .map(|n| n.parse())
+          .collect::<Result<Vec<Artifact>, String>>()?,
+      )
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::fs::write;
+
+  use tempfile::tempdir;
+
+  use crate::artifacts::{check_artifacts, select_artifacts, Artifact};
+
+  #[test]
+  fn artifact_selection() {
+    assert_eq!(
+      check_artifacts(vec![Artifact::Aab, Artifact::Apk, Artifact::Aab]),
+      Ok(vec![Artifact::Apk, Artifact::Aab])
+    );
+    assert!(check_artifacts(vec![Artifact::Aab])
+      .unwrap_err()
+      .contains("must include apk"));
+    assert!("apks".parse::<Artifact>().is_err());
+
+    let dir = tempdir().unwrap();
+    let manifest_path = dir.path().join("Cargo.toml");
+    write(&manifest_path, "[package]\nname = \"game\"\n").unwrap();
+    assert_eq!(
+      select_artifacts(&manifest_path, &[]),
+      Ok(vec![Artifact::Apk])
+    );
+    write(
+      &manifest_path,
+      "[package]\nname = \"game\"\n\n[package.metadata.android]\nartifacts = [\"apk\", \"aab\"]\n",
+    )
+    .unwrap();
+    assert_eq!(
+      select_artifacts(&manifest_path, &[]),
+      Ok(vec![Artifact::Apk, Artifact::Aab])
+    );
+    assert_eq!(
+      select_artifacts(&manifest_path, &[Artifact::Apk]),
+      Ok(vec![Artifact::Apk])
+    );
+    write(
+      &manifest_path,
+      "[package]\nname = \"game\"\n\n[package.metadata.android]\nartifacts = [\"aab\"]\n",
+    )
+    .unwrap();
+    assert!(select_artifacts(&manifest_path, &[]).is_err());
+  }
+}
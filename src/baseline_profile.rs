@@ -0,0 +1,217 @@
+use std::cmp::Ordering;
+use std::fs::{read_to_string, File};
+use std::path::{Path, PathBuf};
+
+use toml::value::Value;
+use zip::ZipArchive;
+
+use crate::gradle_dsl::*;
+use crate::sdk::*;
+use crate::util::*;
+
+/// Installs the profile on devices whose Play Store doesn't, added as dependency of the app.
+const PROFILEINSTALLER_DEPENDENCY: &str = "androidx.profileinstaller:profileinstaller:1.3.1";
+
+/// First version of the Android Gradle Plugin compiling `baseline-prof.txt` into the APK.
+pub const BASELINE_PROFILE_AGP_VERSION: &str = "7.1";
+
+/// Where the Android Gradle Plugin expects the human readable profile of the app.
+pub const BASELINE_PROFILE_PATH: &str = "app/src/main/baseline-prof.txt";
+
+/// The compiled profile inside a release APK.
+pub const APK_PROFILE_ENTRY: &str = "assets/dexopt/baseline.prof";
+
+/// The compiled profile inside an AAB, which Play turns into the one of the APKs it serves.
+pub const AAB_PROFILE_ENTRY: &str =
+  "BUNDLE-METADATA/com.android.tools.build.profiles/baseline.prof";
+
+/// The baseline profile of `baseline_profile` in the android metadata.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BaselineProfile {
+  /// The profile of the SDL Java glue, from `baseline_profile = true`.
+  Default,
+  /// A profile of the package, relative to its manifest dir.
+  File(PathBuf),
+}
+
+/// Parses `baseline_profile` of the android metadata, `true` or the path of a profile.
+pub fn parse_baseline_profile(value: &Value) -> Result<Option<BaselineProfile>, String> {
+  match value {
+    Value::Boolean(true) => Ok(Some(BaselineProfile::Default)),
+    Value::Boolean(false) => Ok(None),
+    Value::String(path) => Ok(Some(BaselineProfile::File(PathBuf::from(path)))),
+    _ => Err("baseline_profile must be true or the path of a baseline-prof.txt".to_string()),
+  }
+}
+
+/// Reads `baseline_profile` of the android metadata.
+pub fn get_baseline_profile(manifest_path: &Path) -> Result<Option<BaselineProfile>, String> {
+  match get_toml_entry(
+    manifest_path,
+    ["package", "metadata", "android", "baseline_profile"],
+  )? {
+    Some(value) => parse_baseline_profile(&value),
+    None => Ok(None),
+  }
+}
+
+/// The profile shipped for `baseline_profile = true`: the startup path of the SDL Java glue, which
+/// runs before the native code, and of the activity of the app. Native code isn't covered by
+/// baseline profiles.
+pub fn default_baseline_profile(activity_class: &str) -> String {
+  let activity = activity_class.replace('.', "/");
+  format!(
+    "# Generated by cargo-sdl-apk for baseline_profile = true\n\
+    HSPL{};->**(**)**\n\
+    HSPLorg/libsdl/app/**;->**(**)**\n\
+    L{};\n\
+    Lorg/libsdl/app/**;\n",
+    activity, activity
+  )
+}
+
+/// The content of the profile written to the project.
+pub fn read_baseline_profile(
+  profile: &BaselineProfile,
+  manifest_dir: &Path,
+  activity_class: &str,
+) -> Result<String, String> {
+  match profile {
+    BaselineProfile::Default => Ok(default_baseline_profile(activity_class)),
+    BaselineProfile::File(path) => {
+      let path = manifest_dir.join(path);
+      read_to_string(&path)
+        .map_err(|e| format!("Unable to read baseline_profile {}: {}", path.display(), e))
+    }
+  }
+}
+
+/// Whether the Android Gradle Plugin of the template compiles baseline profiles, assumed for
+/// unknown ones.
+pub fn supports_baseline_profile(agp_version: Option<&str>) -> bool {
+  agp_version.is_none_or(|v| compare_versions(v, BASELINE_PROFILE_AGP_VERSION) != Ordering::Less)
+}
+
+/// Adds the profileinstaller library to app/build.gradle, unless it is a dependency already.
+pub fn add_profileinstaller_dependency(build_gradle: &str, dsl: GradleDsl) -> String {
+  if build_gradle.contains("androidx.profileinstaller:") {
+    return build_gradle.to_string();
+  }
+  let dependency = dsl.call(
+    "implementation",
+    &[&dsl.string(PROFILEINSTALLER_DEPENDENCY)],
+  );
+  insert_after_line(build_gradle, |l| l == "dependencies {", &dependency, "    ").unwrap_or_else(
+    || {
+      format!(
+        "{}\ndependencies {{\n    {}\n}}\n",
+        build_gradle, dependency
+      )
+    },
+  )
+}
+
+/// Checks that the package contains the compiled profile entry, e.g. [APK_PROFILE_ENTRY].
+pub fn check_compiled_baseline_profile(package: &Path, entry: &str) -> Result<(), String> {
+  let file = File::open(package).map_err(|e| format!("can't open {:?}: {}", package, e))?;
+  let archive =
+    ZipArchive::new(file).map_err(|e| format!("unable to read {:?}: {}", package, e))?;
+  if archive.file_names().any(|name| name == entry) {
+    return Ok(());
+  }
+  Err(format!(
+    "{} has no {}, the baseline profile wasn't compiled in. Check the gradle output for \
+    warnings about {}",
+    package.display(),
+    entry,
+    BASELINE_PROFILE_PATH
+  ))
+}
+
+#[cfg(test)]
+mod test {
+  use std::fs::File;
+  use std::io::Write;
+  use std::path::PathBuf;
+
+  use tempfile::tempdir;
+  use toml::value::Value;
+  use zip::write::FileOptions;
+  use zip::ZipWriter;
+
+  use crate::baseline_profile::{
+    add_profileinstaller_dependency, check_compiled_baseline_profile, default_baseline_profile,
+    parse_baseline_profile, supports_baseline_profile, BaselineProfile, APK_PROFILE_ENTRY,
+  };
+  use crate::gradle_dsl::GradleDsl;
+
+  #[test]
+  fn baseline_profile_config() {
+    assert_eq!(
+      Ok(Some(BaselineProfile::Default)),
+      parse_baseline_profile(&Value::Boolean(true))
+    );
+    assert_eq!(Ok(None), parse_baseline_profile(&Value::Boolean(false)));
+    assert_eq!(
+      Ok(Some(BaselineProfile::File(PathBuf::from(
+        "android/baseline-prof.txt"
+      )))),
+      parse_baseline_profile(&Value::String("android/baseline-prof.txt".to_string()))
+    );
+    assert!(parse_baseline_profile(&Value::Integer(1)).is_err());
+
+    assert!(supports_baseline_profile(None));
+    assert!(supports_baseline_profile(Some("8.1.1")));
+    assert!(!supports_baseline_profile(Some("7.0.4")));
+
+    assert_eq!(
+      "# Generated by cargo-sdl-apk for baseline_profile = true\n\
+      HSPLcom/example/game/MainActivity;->**(**)**\n\
+      HSPLorg/libsdl/app/**;->**(**)**\n\
+      Lcom/example/game/MainActivity;\n\
+      Lorg/libsdl/app/**;\n",
+      default_baseline_profile("com.example.game.MainActivity")
+    );
+  }
+
+  #[test]
+  fn profileinstaller_dependency() {
+    let groovy = "android {\n}\n\ndependencies {\n    implementation fileTree(include: ['*.jar'], dir: 'libs')\n}\n";
+    let content = add_profileinstaller_dependency(groovy, GradleDsl::Groovy);
+    assert_eq!(
+      "android {\n}\n\ndependencies {\n    implementation 'androidx.profileinstaller:profileinstaller:1.3.1'\n    implementation fileTree(include: ['*.jar'], dir: 'libs')\n}\n",
+      content
+    );
+    assert_eq!(
+      content,
+      add_profileinstaller_dependency(&content, GradleDsl::Groovy)
+    );
+    assert!(add_profileinstaller_dependency("android {\n}\n", GradleDsl::Kotlin).ends_with(
+      "\ndependencies {\n    implementation(\"androidx.profileinstaller:profileinstaller:1.3.1\")\n}\n"
+    ));
+  }
+
+  #[test]
+  fn compiled_baseline_profile() {
+    let dir = tempdir().unwrap();
+    let apk = dir.path().join("app.apk");
+    let mut writer = ZipWriter::new(File::create(&apk).unwrap());
+    writer
+      .start_file("classes.dex", FileOptions::default())
+      .unwrap();
+    writer.write_all(b"dex").unwrap();
+    writer.finish().unwrap();
+    assert!(check_compiled_baseline_profile(&apk, APK_PROFILE_ENTRY).is_err());
+
+    let mut writer = ZipWriter::new(File::create(&apk).unwrap());
+    writer
+      .start_file(APK_PROFILE_ENTRY, FileOptions::default())
+      .unwrap();
+    writer.write_all(b"pro").unwrap();
+    writer.finish().unwrap();
+    assert_eq!(
+      Ok(()),
+      check_compiled_baseline_profile(&apk, APK_PROFILE_ENTRY)
+    );
+  }
+}
@@ -0,0 +1,672 @@
+use std::env;
+use std::fs::{create_dir_all, read_to_string, write};
+use std::path::{Path, PathBuf};
+
+use toml::value::Value;
+use toml::Table;
+
+use crate::android_project::*;
+use crate::project_template::*;
+use crate::util::*;
+
+/// Environment variable cargo builds with, the path of the generated `bridge.rs`, e.g.
+/// `include!(env!("CARGO_SDL_APK_BRIDGE"));`.
+pub const BRIDGE_ENV: &str = "CARGO_SDL_APK_BRIDGE";
+
+/// A type of a parameter or the result of a bridge method, named like in Java.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JavaType {
+  Void,
+  Boolean,
+  Byte,
+  Char,
+  Short,
+  Int,
+  Long,
+  Float,
+  Double,
+  String,
+  /// A class by its fully qualified name, e.g. `android.net.Uri`, with `$` before nested classes.
+  Object(String),
+  Array(Box<JavaType>),
+}
+
+/// Rust keywords and the `env` parameter, which can't name a parameter or wrapper though they are
+/// fine in Java.
+const RESERVED_RUST_NAMES: &[&str] = &[
+  "as", "async", "await", "crate", "dyn", "env", "extern", "fn", "impl", "in", "let", "loop",
+  "match", "mod", "move", "mut", "pub", "ref", "self", "struct", "trait", "type", "unsafe", "use",
+  "where",
+];
+
+fn to_snake_case(name: &str) -> String {
+  let chars: Vec<char> = name.chars().collect();
+  let mut snake_case = String::new();
+  for (i, c) in chars.iter().enumerate() {
+    if c.is_ascii_uppercase() && i > 0 {
+      let after_lower = chars[i - 1].is_ascii_lowercase() || chars[i - 1].is_ascii_digit();
+      let acronym_end = chars[i - 1].is_ascii_uppercase()
+        && chars
+          .get(i + 1)
+          .is_some_and(|next| next.is_ascii_lowercase());
+      if after_lower || acronym_end {
+        snake_case.push('_');
+      }
+    }
+    snake_case.push(c.to_ascii_lowercase());
+  }
+  snake_case
+}
+
+fn is_java_identifier(name: &str) -> bool {
+  name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_' || c == '$')
+    && name
+      .chars()
+      .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+}
+
+impl JavaType {
+  /// Parses a Java type like `int`, `String`, `byte[]` or `android.net.Uri`. Classes other than
+  /// `String` and `Object` need their package.
+  pub fn parse(value: &str) -> Result<Self, String> {
+    let value = value.trim();
+    if let Some(element) = value.strip_suffix("[]") {
+      return match JavaType::parse(element)? {
+        JavaType::Void => Err("there are no arrays of void".to_string()),
+        element => Ok(JavaType::Array(Box::new(element))),
+      };
+    }
+    Ok(match value {
+      "void" => JavaType::Void,
+      "boolean" => JavaType::Boolean,
+      "byte" => JavaType::Byte,
+      "char" => JavaType::Char,
+      "short" => JavaType::Short,
+      "int" => JavaType::Int,
+      "long" => JavaType::Long,
+      "float" => JavaType::Float,
+      "double" => JavaType::Double,
+      "String" | "java.lang.String" => JavaType::String,
+      "Object" => JavaType::Object("java.lang.Object".to_string()),
+      class if class.contains('.') && class.split('.').all(is_java_identifier) => {
+        JavaType::Object(class.to_string())
+      }
+      class if is_java_identifier(class) => {
+        return Err(format!(
+          "unknown type {}, classes other than String and Object need their package, e.g. \
+          android.net.Uri",
+          class
+        ))
+      }
+      _ => return Err(format!("invalid Java type \"{}\"", value)),
+    })
+  }
+
+  /// The type in Java sources.
+  pub fn java_name(&self) -> String {
+    match self {
+      JavaType::Void => "void".to_string(),
+      JavaType::Boolean => "boolean".to_string(),
+      JavaType::Byte => "byte".to_string(),
+      JavaType::Char => "char".to_string(),
+      JavaType::Short => "short".to_string(),
+      JavaType::Int => "int".to_string(),
+      JavaType::Long => "long".to_string(),
+      JavaType::Float => "float".to_string(),
+      JavaType::Double => "double".to_string(),
+      JavaType::String => "String".to_string(),
+      JavaType::Object(class) => class.replace('$', "."),
+      JavaType::Array(element) => format!("{}[]", element.java_name()),
+    }
+  }
+
+  /// The JNI type descriptor, e.g. `I` for `int` and `[Ljava/lang/String;` for `String[]`.
+  pub fn descriptor(&self) -> String {
+    match self {
+      JavaType::Void => "V".to_string(),
+      JavaType::Boolean => "Z".to_string(),
+      JavaType::Byte => "B".to_string(),
+      JavaType::Char => "C".to_string(),
+      JavaType::Short => "S".to_string(),
+      JavaType::Int => "I".to_string(),
+      JavaType::Long => "J".to_string(),
+      JavaType::Float => "F".to_string(),
+      JavaType::Double => "D".to_string(),
+      JavaType::String => "Ljava/lang/String;".to_string(),
+      JavaType::Object(class) => format!("L{};", class.replace('.', "/")),
+      JavaType::Array(element) => format!("[{}", element.descriptor()),
+    }
+  }
+
+  /// The type of a parameter of the Rust wrapper. Strings are converted by the wrapper, other
+  /// objects are passed as they are.
+  fn rust_param_type(&self) -> &'static str {
+    match self {
+      JavaType::Void => "()",
+      JavaType::Boolean => "bool",
+      JavaType::Byte => "i8",
+      JavaType::Char => "u16",
+      JavaType::Short => "i16",
+      JavaType::Int => "i32",
+      JavaType::Long => "i64",
+      JavaType::Float => "f32",
+      JavaType::Double => "f64",
+      JavaType::String => "&str",
+      JavaType::Object(_) | JavaType::Array(_) => "&JObject<'_>",
+    }
+  }
+
+  /// The `JValue` passing the parameter of the Rust wrapper.
+  fn rust_jvalue(&self, name: &str) -> String {
+    match self {
+      JavaType::Boolean => format!("JValue::Bool({} as u8)", name),
+      JavaType::Byte => format!("JValue::Byte({})", name),
+      JavaType::Char => format!("JValue::Char({})", name),
+      JavaType::Short => format!("JValue::Short({})", name),
+      JavaType::Int => format!("JValue::Int({})", name),
+      JavaType::Long => format!("JValue::Long({})", name),
+      JavaType::Float => format!("JValue::Float({})", name),
+      JavaType::Double => format!("JValue::Double({})", name),
+      JavaType::String => format!("JValue::Object(&{})", name),
+      JavaType::Void | JavaType::Object(_) | JavaType::Array(_) => {
+        format!("JValue::Object({})", name)
+      }
+    }
+  }
+
+  /// The result type of the Rust wrapper and the `JValueOwned` accessor converting to it.
+  fn rust_return(&self) -> (&'static str, &'static str) {
+    match self {
+      JavaType::Void => ("()", "v"),
+      JavaType::Boolean => ("bool", "z"),
+      JavaType::Byte => ("i8", "b"),
+      JavaType::Char => ("u16", "c"),
+      JavaType::Short => ("i16", "s"),
+      JavaType::Int => ("i32", "i"),
+      JavaType::Long => ("i64", "j"),
+      JavaType::Float => ("f32", "f"),
+      JavaType::Double => ("f64", "d"),
+      JavaType::String => ("String", "l"),
+      JavaType::Object(_) | JavaType::Array(_) => ("JObject<'local>", "l"),
+    }
+  }
+}
+
+/// A static method of `[[package.metadata.android.bridge_methods]]` generated into MainActivity,
+/// with its body from a snippet file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BridgeMethod {
+  pub name: String,
+  /// The parameters by name, `arg<index>` unless given like `"String text"`.
+  pub params: Vec<(String, JavaType)>,
+  pub returns: JavaType,
+  /// The Java statements of the body.
+  pub body: String,
+}
+
+impl BridgeMethod {
+  /// The JNI signature, e.g. `(Ljava/lang/String;I)V`.
+  pub fn signature(&self) -> String {
+    format!(
+      "({}){}",
+      self
+        .params
+        .iter()
+        .map(|(_, param)| param.descriptor())
+        .collect::<String>(),
+      self.returns.descriptor()
+    )
+  }
+
+  /// The name of the Rust wrapper, the method name in snake case, e.g. `open_url` for `openURL`.
+  pub fn rust_name(&self) -> String {
+    to_snake_case(&self.name)
+  }
+}
+
+fn parse_bridge_method(table: &Table, manifest_dir: &Path) -> Result<BridgeMethod, String> {
+  let get_string = |key: &str| match table.get(key) {
+    None => Ok(None),
+    Some(Value::String(s)) => Ok(Some(s.clone())),
+    Some(_) => Err(format!("{} of a bridge method must be a string", key)),
+  };
+  let name = get_string("name")?.ok_or("every bridge method needs a name")?;
+  if !is_java_identifier(&name) || name.contains('$') {
+    return Err(format!("invalid bridge method name \"{}\"", name));
+  }
+  let rust_name = |name: &str| {
+    if RESERVED_RUST_NAMES.contains(&name) {
+      Err(format!(
+        "{} can't be used as name in the Rust bridge, rename it",
+        name
+      ))
+    } else {
+      Ok(())
+    }
+  };
+  let context = |e: String| format!("bridge method {}: {}", name, e);
+  let params = match table.get("params") {
+    None => vec![],
+    Some(Value::Array(params)) => params
+      .iter()
+      .enumerate()
+      .map(|(i, param)| {
+        let param = param.as_str().ok_or("params must be strings")?;
+        match param.trim().rsplit_once(char::is_whitespace) {
+          Some((param_type, param_name)) if is_java_identifier(param_name) => {
+            rust_name(param_name)?;
+            Ok((param_name.to_string(), JavaType::parse(param_type)?))
+          }
+          Some(_) => Err(format!("invalid parameter \"{}\"", param)),
+          None => Ok((format!("arg{}", i), JavaType::parse(param)?)),
+        }
+      })
+      .collect::<Result<Vec<_>, String>>()
+      .map_err(context)?,
+    Some(_) => return Err(context("params must be an array of types".to_string())),
+  };
+  if params.iter().any(|(_, param)| *param == JavaType::Void) {
+    return Err(context("a parameter can't be void".to_string()));
+  }
+  let returns = match get_string("returns")? {
+    None => JavaType::Void,
+    Some(returns) => JavaType::parse(&returns).map_err(context)?,
+  };
+  rust_name(&to_snake_case(&name)).map_err(context)?;
+  let body_file = get_string("body_file")?.ok_or_else(|| context("body_file is missing".into()))?;
+  let body_file = manifest_dir.join(body_file);
+  let body = read_to_string(&body_file)
+    .map_err(|e| context(format!("can't read {}: {}", body_file.display(), e)))?;
+  Ok(BridgeMethod {
+    name,
+    params,
+    returns,
+    body,
+  })
+}
+
+/// Parses the `bridge_methods` android metadata, an array of method tables. Body files are
+/// resolved against the manifest dir.
+pub fn parse_bridge_methods(
+  value: &Value,
+  manifest_dir: &Path,
+) -> Result<Vec<BridgeMethod>, String> {
+  let methods = value.as_array().ok_or(
+    "bridge_methods must be an array of tables, [[package.metadata.android.bridge_methods]]",
+  )?;
+  let mut parsed: Vec<BridgeMethod> = vec![];
+  for method in methods {
+    let table = method
+      .as_table()
+      .ok_or("every bridge method must be a table")?;
+    let method = parse_bridge_method(table, manifest_dir)?;
+    //the Rust wrappers can't be overloaded
+    if parsed.iter().any(|m| m.name == method.name) {
+      return Err(format!("bridge method {} is defined twice", method.name));
+    }
+    parsed.push(method);
+  }
+  Ok(parsed)
+}
+
+/// Reads the bridge methods of the android metadata.
+pub fn get_bridge_methods(manifest_path: &Path) -> Result<Vec<BridgeMethod>, String> {
+  match get_toml_entry(
+    manifest_path,
+    ["package", "metadata", "android", "bridge_methods"],
+  )? {
+    Some(value) => parse_bridge_methods(&value, manifest_path.parent().unwrap()),
+    None => Ok(vec![]),
+  }
+}
+
+/// The methods as members of MainActivity, indented with tabs like its class.
+pub fn render_java_methods(methods: &[BridgeMethod]) -> String {
+  methods
+    .iter()
+    .map(|method| {
+      let params: Vec<String> = method
+        .params
+        .iter()
+        .map(|(name, param)| format!("{} {}", param.java_name(), name))
+        .collect();
+      let body: String = method
+        .body
+        .trim_end()
+        .lines()
+        .map(|line| match line.trim_end() {
+          "" => "\n".to_string(),
+          line => format!("\t\t\t\t{}\n", line),
+        })
+        .collect();
+      format!(
+        "\n\t\t\tpublic static {} {}({}) {{\n{}\t\t\t}}\n",
+        method.returns.java_name(),
+        method.name,
+        params.join(", "),
+        body
+      )
+    })
+    .collect()
+}
+
+/// A Rust module calling the methods with the `jni` crate, `activity_class` is the fully
+/// qualified name of MainActivity. The class is looked up with `FindClass`, which finds app
+/// classes on threads started by Java, like SDL's main thread.
+pub fn render_rust_bridge(methods: &[BridgeMethod], activity_class: &str) -> String {
+  let mut bridge = format!(
+    "// Generated by cargo-sdl-apk from bridge_methods in the android metadata, don't edit.\n\
+    \n\
+    #[allow(unused_imports)]\n\
+    use jni::objects::{{JObject, JString, JValue}};\n\
+    use jni::JNIEnv;\n\
+    \n\
+    /// The activity declaring the bridge methods.\n\
+    pub const MAIN_ACTIVITY_CLASS: &str = \"{}\";\n",
+    activity_class.replace('.', "/")
+  );
+  for method in methods {
+    let (return_type, accessor) = method.returns.rust_return();
+    let mut params = vec!["env: &mut JNIEnv<'local>".to_string()];
+    let mut conversions = String::new();
+    for (name, param) in &method.params {
+      params.push(format!("{}: {}", name, param.rust_param_type()));
+      if *param == JavaType::String {
+        conversions.push_str(&format!("  let {} = env.new_string({})?;\n", name, name));
+      }
+    }
+    let args: Vec<String> = method
+      .params
+      .iter()
+      .map(|(name, param)| param.rust_jvalue(name))
+      .collect();
+    let call = format!(
+      "env\n    .call_static_method(\n      MAIN_ACTIVITY_CLASS,\n      \"{}\",\n      \"{}\",\n      &[{}],\n    )?\n    .{}()",
+      method.name,
+      method.signature(),
+      args.join(", "),
+      accessor
+    );
+    let body = match method.returns {
+      JavaType::String => format!(
+        "  let result = {}?;\n  Ok(env.get_string(&JString::from(result))?.into())\n",
+        call
+      ),
+      _ => format!("  {}\n", call),
+    };
+    bridge.push_str(&format!(
+      "\n/// Calls `{}.{}{}`.\n#[allow(clippy::too_many_arguments)]\npub fn {}<'local>({}) -> jni::errors::Result<{}> {{\n{}{}}}\n",
+      activity_class.rsplit('.').next().unwrap(),
+      method.name,
+      method.signature(),
+      method.rust_name(),
+      params.join(", "),
+      return_type,
+      conversions,
+      body
+    ));
+  }
+  bridge
+}
+
+/// Writes the Rust bridge to `target/android-bridge/bridge.rs`, returning its path.
+pub fn write_rust_bridge(
+  manifest_dir: &Path,
+  methods: &[BridgeMethod],
+  activity_class: &str,
+) -> Result<PathBuf, String> {
+  let dir = manifest_dir.join("target/android-bridge");
+  let path = dir.join("bridge.rs");
+  create_dir_all(&dir)
+    .and_then(|_| write(&path, render_rust_bridge(methods, activity_class)))
+    .map_err(|e| format!("unable to write {}: {}", path.display(), e))?;
+  Ok(path)
+}
+
+/// Bridge methods are generated into the MainActivity of SDL, a native activity has no class to
+/// add them to.
+pub fn check_bridge_activity(
+  activity_kind: ActivityKind,
+  methods: &[BridgeMethod],
+) -> Result<(), String> {
+  if activity_kind == ActivityKind::Native && !methods.is_empty() {
+    return Err(
+      "bridge_methods are generated into the MainActivity of SDL, they can't be used with \
+      activity_kind = \"native\""
+        .to_string(),
+    );
+  }
+  Ok(())
+}
+
+/// Writes the Rust bridge of the bridge methods and points [BRIDGE_ENV] at it for the cargo build,
+/// removing the variable of an earlier build in the same process if there are none.
+pub fn set_bridge_env(manifest_path: &Path) -> Result<(), String> {
+  let methods = get_bridge_methods(manifest_path)?;
+  check_bridge_activity(get_activity_kind(manifest_path)?, &methods)?;
+  if methods.is_empty() {
+    env::remove_var(BRIDGE_ENV);
+    return Ok(());
+  }
+  let path = write_rust_bridge(
+    manifest_path.parent().unwrap(),
+    &methods,
+    &ActivityKind::Sdl.activity_class(&get_android_app_id(manifest_path)?),
+  )?;
+  env::set_var(BRIDGE_ENV, path);
+  Ok(())
+}
+
+#[cfg(test)]
+mod test {
+  use std::fs::write;
+
+  use tempfile::tempdir;
+  use toml::Table;
+
+  use crate::bridge_methods::{
+    parse_bridge_methods, render_java_methods, render_rust_bridge, BridgeMethod, JavaType,
+  };
+
+  #[test]
+  fn java_type_descriptors() {
+    let descriptor = |value: &str| JavaType::parse(value).map(|t| t.descriptor());
+    assert_eq!(Ok("V".to_string()), descriptor("void"));
+    assert_eq!(Ok("Z".to_string()), descriptor("boolean"));
+    assert_eq!(Ok("B".to_string()), descriptor("byte"));
+    assert_eq!(Ok("C".to_string()), descriptor("char"));
+    assert_eq!(Ok("S".to_string()), descriptor("short"));
+    assert_eq!(Ok("I".to_string()), descriptor("int"));
+    assert_eq!(Ok("J".to_string()), descriptor("long"));
+    assert_eq!(Ok("F".to_string()), descriptor("float"));
+    assert_eq!(Ok("D".to_string()), descriptor("double"));
+    assert_eq!(Ok("Ljava/lang/String;".to_string()), descriptor("String"));
+    assert_eq!(
+      Ok("Ljava/lang/String;".to_string()),
+      descriptor("java.lang.String")
+    );
+    assert_eq!(Ok("Ljava/lang/Object;".to_string()), descriptor("Object"));
+    assert_eq!(
+      Ok("Landroid/net/Uri;".to_string()),
+      descriptor("android.net.Uri")
+    );
+    assert_eq!(
+      Ok("Landroid/app/Notification$Builder;".to_string()),
+      descriptor("android.app.Notification$Builder")
+    );
+    assert_eq!(Ok("[B".to_string()), descriptor("byte[]"));
+    assert_eq!(Ok("[[I".to_string()), descriptor("int[][]"));
+    assert_eq!(
+      Ok("[Ljava/lang/String;".to_string()),
+      descriptor("String[]")
+    );
+
+    assert!(descriptor("Uri")
+      .unwrap_err()
+      .contains("need their package"));
+    assert!(descriptor("void[]").is_err());
+    assert!(descriptor("int x").is_err());
+    assert!(descriptor("android..Uri").is_err());
+    assert!(descriptor("").is_err());
+  }
+
+  #[test]
+  fn java_type_names() {
+    let name = |value: &str| JavaType::parse(value).unwrap().java_name();
+    assert_eq!("int", name("int"));
+    assert_eq!("String", name("java.lang.String"));
+    assert_eq!("String[]", name("String[]"));
+    assert_eq!(
+      "android.app.Notification.Builder",
+      name("android.app.Notification$Builder")
+    );
+    assert_eq!("byte[][]", name("byte[][]"));
+  }
+
+  fn method(name: &str, params: &[&str], returns: &str) -> BridgeMethod {
+    BridgeMethod {
+      name: name.to_string(),
+      params: params
+        .iter()
+        .enumerate()
+        .map(|(i, p)| (format!("arg{}", i), JavaType::parse(p).unwrap()))
+        .collect(),
+      returns: JavaType::parse(returns).unwrap(),
+      body: String::new(),
+    }
+  }
+
+  #[test]
+  fn method_signatures() {
+    assert_eq!("()V", method("vibrate", &[], "void").signature());
+    assert_eq!(
+      "(Ljava/lang/String;)V",
+      method("shareText", &["String"], "void").signature()
+    );
+    assert_eq!(
+      "(IJZ[B)Ljava/lang/String;",
+      method("encode", &["int", "long", "boolean", "byte[]"], "String").signature()
+    );
+    assert_eq!(
+      "(Landroid/net/Uri;DF)[I",
+      method("sizes", &["android.net.Uri", "double", "float"], "int[]").signature()
+    );
+
+    assert_eq!("share_text", method("shareText", &[], "void").rust_name());
+    assert_eq!("open_url", method("openURL", &[], "void").rust_name());
+    assert_eq!(
+      "get_http_client",
+      method("getHTTPClient", &[], "void").rust_name()
+    );
+    assert_eq!("vibrate2x", method("vibrate2x", &[], "void").rust_name());
+  }
+
+  #[test]
+  fn bridge_methods() {
+    let dir = tempdir().unwrap();
+    write(
+      dir.path().join("share.java.snippet"),
+      "android.content.Intent intent = new android.content.Intent(android.content.Intent.ACTION_SEND);\n\
+      intent.putExtra(android.content.Intent.EXTRA_TEXT, text);\n\
+      \n\
+      mSingleton.startActivity(intent);\n",
+    )
+    .unwrap();
+    write(dir.path().join("rating.java.snippet"), "return 5;").unwrap();
+    let metadata = r#"
+[[bridge_methods]]
+name = "shareText"
+params = ["String text"]
+body_file = "share.java.snippet"
+
+[[bridge_methods]]
+name = "rating"
+params = ["int", "boolean force"]
+returns = "int"
+body_file = "rating.java.snippet"
+"#
+    .parse::<Table>()
+    .unwrap();
+    let methods = parse_bridge_methods(&metadata["bridge_methods"], dir.path()).unwrap();
+    assert_eq!(
+      vec![
+        ("arg0".to_string(), JavaType::Int),
+        ("force".to_string(), JavaType::Boolean)
+      ],
+      methods[1].params
+    );
+
+    assert_eq!(
+      "\n\t\t\tpublic static void shareText(String text) {\n\
+      \t\t\t\tandroid.content.Intent intent = new android.content.Intent(android.content.Intent.ACTION_SEND);\n\
+      \t\t\t\tintent.putExtra(android.content.Intent.EXTRA_TEXT, text);\n\
+      \n\
+      \t\t\t\tmSingleton.startActivity(intent);\n\
+      \t\t\t}\n\
+      \n\t\t\tpublic static int rating(int arg0, boolean force) {\n\
+      \t\t\t\treturn 5;\n\
+      \t\t\t}\n",
+      render_java_methods(&methods)
+    );
+
+    let bridge = render_rust_bridge(&methods, "com.example.game.MainActivity");
+    assert!(
+      bridge.contains("pub const MAIN_ACTIVITY_CLASS: &str = \"com/example/game/MainActivity\";")
+    );
+    assert!(bridge.contains(
+      "pub fn share_text<'local>(env: &mut JNIEnv<'local>, text: &str) -> jni::errors::Result<()> {\n  \
+      let text = env.new_string(text)?;\n  \
+      env\n    .call_static_method(\n      MAIN_ACTIVITY_CLASS,\n      \"shareText\",\n      \
+      \"(Ljava/lang/String;)V\",\n      &[JValue::Object(&text)],\n    )?\n    .v()\n}\n"
+    ));
+    assert!(bridge.contains(
+      "pub fn rating<'local>(env: &mut JNIEnv<'local>, arg0: i32, force: bool) -> jni::errors::Result<i32> {"
+    ));
+    assert!(bridge.contains("&[JValue::Int(arg0), JValue::Bool(force as u8)],"));
+    assert!(bridge.contains("/// Calls `MainActivity.rating(IZ)I`."));
+  }
+
+  #[test]
+  fn invalid_bridge_methods() {
+    let dir = tempdir().unwrap();
+    write(dir.path().join("body.snippet"), "").unwrap();
+    let parse = |metadata: &str| {
+      let metadata = metadata.parse::<Table>().unwrap();
+      parse_bridge_methods(&metadata["bridge_methods"], dir.path())
+    };
+    assert!(parse("bridge_methods = { name = \"a\" }").is_err());
+    assert!(parse("[[bridge_methods]]\nbody_file = \"body.snippet\"")
+      .unwrap_err()
+      .contains("needs a name"));
+    assert!(
+      parse("[[bridge_methods]]\nname = \"share-text\"\nbody_file = \"body.snippet\"")
+        .unwrap_err()
+        .contains("invalid bridge method name")
+    );
+    assert!(
+      parse("[[bridge_methods]]\nname = \"a\"\nbody_file = \"missing.snippet\"")
+        .unwrap_err()
+        .starts_with("bridge method a: can't read")
+    );
+    assert!(parse(
+      "[[bridge_methods]]\nname = \"a\"\nparams = [\"void\"]\nbody_file = \"body.snippet\""
+    )
+    .unwrap_err()
+    .contains("can't be void"));
+    assert!(
+      parse("[[bridge_methods]]\nname = \"move\"\nbody_file = \"body.snippet\"")
+        .unwrap_err()
+        .contains("can't be used as name in the Rust bridge")
+    );
+    assert!(parse(
+      "[[bridge_methods]]\nname = \"a\"\nparams = [\"int env\"]\nbody_file = \"body.snippet\""
+    )
+    .is_err());
+    assert!(parse(
+      "[[bridge_methods]]\nname = \"a\"\nbody_file = \"body.snippet\"\n\
+      [[bridge_methods]]\nname = \"a\"\nparams = [\"int\"]\nbody_file = \"body.snippet\""
+    )
+    .unwrap_err()
+    .contains("defined twice"));
+  }
+}
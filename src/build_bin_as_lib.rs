@@ -1,52 +1,119 @@
-use std::collections::HashMap;
-use std::path::Path;
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::ffi::{OsStr, OsString};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::sync::Mutex;
+use std::time::SystemTime;
 
-use cargo::core::compiler::{BuildConfig, CompileKind, CompileMode, CompileTarget, Executor};
-use cargo::core::resolver::CliFeatures;
-use cargo::core::{PackageId, Target, TargetKind, Workspace};
+use cargo::core::compiler::{BuildConfig, CompileMode, Executor, Unit};
+use cargo::core::{Package, PackageId, Target, TargetKind, Workspace};
 use cargo::ops::{CompileFilter, CompileOptions, FilterRule, LibRule, Packages};
 use cargo::util::Config as CargoConfig;
 use cargo::CargoResult;
 use cargo_util::ProcessBuilder;
 
-use crate::util::*;
+use crate::arm_mode::*;
+use crate::cargo_profile::*;
+use crate::ci::*;
+use crate::error::*;
+use crate::features::*;
+use crate::native_libs::*;
+use crate::ndk::*;
+use crate::offline::*;
+use crate::presets::*;
+use crate::release_preset::*;
+use crate::rustflags::*;
+use crate::split_debuginfo::*;
+use crate::targets::*;
 use crate::BuildProfile;
 
-fn get_target_linker(rust_target_name: &str) -> &str {
-  match rust_target_name {
-    "aarch64-linux-android" => {
-      "toolchains/llvm/prebuilt/linux-x86_64/bin/aarch64-linux-android26-clang"
-    }
-    "armv7-linux-androideabi" => {
-      "toolchains/llvm/prebuilt/linux-x86_64/bin/armv7a-linux-androideabi26-clang"
-    }
-    "i686-linux-android" => "toolchains/llvm/prebuilt/linux-x86_64/bin/i686-linux-android26-clang",
-    "x86_64-linux-android" => {
-      "toolchains/llvm/prebuilt/linux-x86_64/bin/x86_64-linux-android26-clang"
-    }
-    _ => {
-      panic!("Unknown target: {}", rust_target_name)
-    }
-  }
+pub fn get_target_linker_path(rust_target_name: &str) -> PathBuf {
+  get_ndk_toolchain_dir()
+    .join("bin")
+    .join(get_target_linker(rust_target_name))
+}
+
+fn android_target_env_with(toolchain_dir: &Path, rust_target_name: &str) -> Vec<(String, PathBuf)> {
+  let linker = toolchain_dir
+    .join("bin")
+    .join(get_target_linker(rust_target_name));
+  let ar = toolchain_dir.join("bin/llvm-ar");
+  let env_triple = rust_target_name.replace('-', "_");
+
+  vec![
+    (
+      format!("CARGO_TARGET_{}_LINKER", env_triple.to_uppercase()),
+      linker.clone(),
+    ),
+    (format!("CC_{}", env_triple), linker),
+    (format!("AR_{}", env_triple), ar),
+  ]
+}
+
+/// Environment variables which make plain cargo invocations compile and link for the given android
+/// target with the NDK toolchain, for cargo itself as well as for build scripts using the cc crate.
+pub fn get_android_target_env(rust_target_name: &str) -> Vec<(String, PathBuf)> {
+  android_target_env_with(&get_ndk_toolchain_dir(), rust_target_name)
 }
 
 pub struct LibExecutor {
-  linkers: HashMap<String, String>,
-  out: Arc<Mutex<HashMap<String, String>>>,
+  linkers: HashMap<String, PathBuf>,
+  /// Crate name of the target packaged as libmain.so, see [get_native_crate_name].
+  crate_name: String,
+  out: Arc<Mutex<HashMap<String, PathBuf>>>,
 }
 
 impl LibExecutor {
-  pub fn new(linkers: HashMap<String, String>) -> Self {
+  pub fn new(linkers: HashMap<String, PathBuf>, crate_name: String) -> Self {
     Self {
       linkers,
+      crate_name,
       out: Arc::new(Mutex::new(HashMap::new())),
     }
   }
 }
 
+/// Picks the native library of the crate from the file names rustc prints for its outputs, e.g.
+/// `libengine.so` out of `libengine.rlib` and `libengine.so` of a lib with both crate types.
+pub fn find_native_library<'a>(file_names: &'a str, crate_name: &str) -> Option<&'a str> {
+  let prefix = format!("lib{}", crate_name);
+  file_names
+    .lines()
+    .map(|line| line.trim())
+    .find(|name| name.starts_with(&prefix) && name.ends_with(".so"))
+}
+
+/// The target of the package built as the native library: the bin or the example, or if the
+/// package has no bin its `[lib]` with the cdylib crate type.
+fn get_native_target<'a>(package: &'a Package, build_target: &BuildTarget) -> Result<&'a Target> {
+  let targets = package.targets();
+  match build_target {
+    BuildTarget::Example(name) => targets
+      .iter()
+      .find(|t| t.is_exe_example() && t.name() == name)
+      .ok_or_else(|| Error::Config(format!("no example named {} found", name))),
+    BuildTarget::Bin => targets
+      .iter()
+      .find(|t| t.is_bin())
+      .or_else(|| targets.iter().find(|t| t.is_cdylib()))
+      .ok_or_else(|| {
+        Error::Config(format!(
+          "{} has neither a bin target nor a lib with crate-type = [\"cdylib\"] to build the app from",
+          package.name()
+        ))
+      }),
+  }
+}
+
 impl Executor for LibExecutor {
+  //a fresh native library isn't passed to exec, so it would be missing from the reported ones
+  fn force_rebuild(&self, unit: &Unit) -> bool {
+    unit.mode == CompileMode::Build
+      && unit.target.crate_name() == self.crate_name
+      && (unit.target.is_bin() || unit.target.is_exe_example() || unit.target.is_cdylib())
+  }
+
   fn exec(
     &self,
     cmd: &ProcessBuilder,
@@ -56,40 +123,45 @@ impl Executor for LibExecutor {
     on_stdout_line: &mut dyn FnMut(&str) -> CargoResult<()>,
     on_stderr_line: &mut dyn FnMut(&str) -> CargoResult<()>,
   ) -> CargoResult<()> {
+    let is_bin = target.kind() == &TargetKind::Bin || target.kind() == &TargetKind::ExampleBin;
     if mode == CompileMode::Build
-      && (target.kind() == &TargetKind::Bin || target.kind() == &TargetKind::ExampleBin)
+      && target.crate_name() == self.crate_name
+      && (is_bin || target.is_cdylib())
     {
-      let args: Vec<&str> = cmd.get_args().map(|s| s.to_str().unwrap()).collect();
-      let mut new_args = vec![];
+      //paths in the arguments may not be valid unicode, so they are kept as they are
+      let args: Vec<&OsStr> = cmd.get_args().map(|s| s.as_os_str()).collect();
+      let mut new_args: Vec<OsString> = vec![];
       let mut i = 0;
-      let mut out_dir = "".to_string();
+      let mut out_dir = PathBuf::new();
       let mut target_triple = "".to_string();
       while i < args.len() {
-        if args[i] == "--crate-type" && args[i + 1] == "bin" {
-          new_args.push("--crate-type");
-          new_args.push("dylib");
+        if is_bin && args[i] == "--crate-type" && args[i + 1] == "bin" {
+          new_args.push("--crate-type".into());
+          new_args.push("dylib".into());
           i += 2;
         } else if args[i] == "--out-dir" {
-          out_dir = args[i + 1].to_string();
-          new_args.push(args[i]);
-          new_args.push(args[i + 1]);
+          out_dir = PathBuf::from(args[i + 1]);
+          new_args.push(args[i].into());
+          new_args.push(args[i + 1].into());
           i += 2;
         } else if args[i] == "--target" {
-          target_triple = args[i + 1].to_string();
-          new_args.push(args[i]);
-          new_args.push(args[i + 1]);
+          target_triple = args[i + 1].to_string_lossy().into_owned();
+          new_args.push(args[i].into());
+          new_args.push(args[i + 1].into());
           i += 2;
         } else {
-          new_args.push(args[i]);
+          new_args.push(args[i].into());
           i += 1;
         }
       }
 
-      let mut linker_arg = "linker=".to_string();
-      linker_arg.push_str(&*self.linkers.get(&target_triple).unwrap());
-
-      new_args.push("-C");
-      new_args.push(&*linker_arg);
+      //builds for other targets are rejected once the artifacts are collected
+      if let Some(linker) = self.linkers.get(&target_triple) {
+        let mut linker_arg = OsString::from("linker=");
+        linker_arg.push(linker);
+        new_args.push("-C".into());
+        new_args.push(linker_arg);
+      }
 
       //println!("the new args: {:?}",new_args.join(" "));
 
@@ -101,9 +173,16 @@ impl Executor for LibExecutor {
 
       let stdout = cmd.arg("--print").arg("file-names").exec_with_output()?;
       let stdout = String::from_utf8(stdout.stdout).unwrap();
-      let stdout = stdout.lines().next().unwrap();
-      let p = Path::new(&*out_dir).join(stdout.clone());
-      let p = p.into_os_string().into_string().unwrap();
+      //the name of the library follows the crate name, which may differ from the package name
+      let file_name = find_native_library(&stdout, &self.crate_name).ok_or_else(|| {
+        std::io::Error::other(format!(
+          "no native library lib{}.so among the outputs {:?} of {}",
+          self.crate_name,
+          stdout.lines().collect::<Vec<_>>(),
+          target.name()
+        ))
+      })?;
+      let p = out_dir.join(file_name);
 
       self.out.lock().unwrap().insert(target_triple, p);
     } else {
@@ -116,66 +195,270 @@ impl Executor for LibExecutor {
   }
 }
 
+/// Targets built unless a preset restricts them.
+pub const DEFAULT_ANDROID_TARGETS: &[&str] = &[
+  "aarch64-linux-android",
+  "armv7-linux-androideabi",
+  "i686-linux-android",
+  "x86_64-linux-android",
+];
+
+/// The targets to build the package for.
+pub fn get_android_targets(manifest_path: &Path) -> Result<Vec<&'static str>, String> {
+  Ok(
+    get_preset(manifest_path)?
+      .and_then(|preset| preset.targets)
+      .unwrap_or(DEFAULT_ANDROID_TARGETS)
+      .to_vec(),
+  )
+}
+
+/// The targets selected with `--target`, or the targets of the package if none were selected. Besides
+/// the known targets, the ones of `extra_targets` can be selected.
+pub fn select_android_targets(
+  manifest_path: &Path,
+  requested: &[String],
+) -> Result<Vec<String>, String> {
+  if requested.is_empty() {
+    return Ok(
+      get_android_targets(manifest_path)?
+        .into_iter()
+        .map(|t| t.to_string())
+        .collect(),
+    );
+  }
+  let extra_targets = get_extra_targets(manifest_path)?;
+  requested
+    .iter()
+    .map(|target| resolve_target_android_name(target, &extra_targets).map(|_| target.clone()))
+    .collect()
+}
+
+pub fn is_android_target(rust_target_name: &str) -> bool {
+  rust_target_name.ends_with("-linux-android") || rust_target_name.ends_with("-linux-androideabi")
+}
+
+/// Reads `build.target` of the cargo configuration, which the targets passed to cargo override.
+fn get_configured_build_targets(cargo_config: &CargoConfig) -> Vec<String> {
+  cargo_config
+    .build_config()
+    .ok()
+    .and_then(|build| build.target.as_ref())
+    .and_then(|target| target.values(cargo_config).ok())
+    .unwrap_or_default()
+}
+
+/// Makes sure all collected artifacts were built for one of the requested android targets, so no
+/// library for the host is packaged.
+pub fn validate_artifacts(
+  artifacts: &HashMap<String, PathBuf>,
+  targets: &[&str],
+) -> Result<(), String> {
+  for (target, artifact) in artifacts {
+    let in_target_dir = artifact
+      .components()
+      .any(|c| c.as_os_str() == target.as_str());
+    if !targets.contains(&target.as_str()) || !is_android_target(target) || !in_target_dir {
+      return Err(format!(
+        "cargo built {} for {}, which is not one of the android targets {}. It would have been \
+        packaged as libmain.so and fail to load on devices. Check build.target in your \
+        .cargo/config.toml",
+        artifact.display(),
+        if target.is_empty() {
+          "the host"
+        } else {
+          target
+        },
+        targets.join(", ")
+      ));
+    }
+  }
+  Ok(())
+}
+
+/// The target of the package built as the native library of the app.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum BuildTarget {
+  /// The bin target.
   Bin,
+  /// The example of the given name.
   Example(String),
 }
 
+/// Creates the cargo config used for building, configured like a plain cargo invocation would be.
+/// With `env` the environment cargo sees is replaced, `cli_config` are `--config` values.
+pub fn create_cargo_config(
+  env: Option<HashMap<String, String>>,
+  cli_config: &[String],
+) -> CargoConfig {
+  let mut cargo_config = CargoConfig::default().unwrap();
+  if let Some(env) = env {
+    cargo_config.set_env(env);
+  }
+  let network = get_network_args();
+  cargo_config
+    .configure(
+      0,                                         // verbose
+      false,                                     // quiet
+      is_ci_mode().then_some("never"),           // color
+      cargo_config.frozen() || network.frozen,   // frozen
+      cargo_config.locked() || network.locked,   // locked
+      cargo_config.offline() || network.offline, // offline
+      &None,                                     // target dir
+      &[],                                       // unstable flags
+      cli_config,                                // cli config
+    )
+    .unwrap();
+  cargo_config
+}
+
+/// The target dir cargo builds the package into, the one of its workspace, `CARGO_TARGET_DIR` or
+/// `build.target-dir`, rather than `target/` next to the manifest.
+pub fn get_cargo_target_dir(manifest_path: &Path) -> PathBuf {
+  let cargo_config = create_cargo_config(None, &[]);
+  Workspace::new(manifest_path, &cargo_config)
+    .map(|workspace| workspace.target_dir().into_path_unlocked())
+    .unwrap_or_else(|_| manifest_path.parent().unwrap().join("target"))
+}
+
+/// Groups the targets into cargo invocations. cargo applies env rustflags and the feature flags to
+/// all targets alike, so targets with different rustflags or features are built in separate runs.
+pub fn group_cargo_invocations(
+  target_rustflags: Vec<(String, Vec<String>)>,
+  target_features: Vec<(String, FeatureSelection)>,
+) -> BTreeMap<(Vec<String>, FeatureSelection), Vec<String>> {
+  let mut features: HashMap<String, FeatureSelection> = target_features.into_iter().collect();
+  let mut invocations: BTreeMap<(Vec<String>, FeatureSelection), Vec<String>> = BTreeMap::new();
+  for (target, rustflags) in target_rustflags {
+    let selection = features.remove(&target).unwrap_or_default();
+    invocations
+      .entry((rustflags, selection))
+      .or_default()
+      .push(target);
+  }
+  invocations
+}
+
 pub fn build_bin_as_lib(
   manifest_path: &Path,
   build_target: BuildTarget,
   targets: &Vec<&str>,
-  profile: BuildProfile,
-) -> HashMap<String, String> {
-  let mut linkers: HashMap<String, String> = HashMap::new();
-  for t in targets {
-    linkers.insert(
-      t.to_string(),
-      Path::new(&*get_env_var("ANDROID_NDK_HOME"))
-        .join(get_target_linker(t))
-        .into_os_string()
-        .into_string()
-        .unwrap(),
+  profile: &CargoProfile,
+  features: &FeatureSelection,
+  verbose: bool,
+) -> Result<HashMap<String, PathBuf>> {
+  let build_start = SystemTime::now();
+  get_split_debuginfo(manifest_path)?;
+  let target_rustflags = get_target_rustflags(manifest_path, targets).map_err(Error::Config)?;
+  if let Some(mode) = get_arm_mode(manifest_path)? {
+    for (target, rustflags) in &target_rustflags {
+      if target == ARM_MODE_TARGET {
+        check_arm_rustflags(mode, rustflags)?;
+      }
+    }
+  }
+  let invocations = group_cargo_invocations(
+    target_rustflags,
+    get_target_feature_selections(manifest_path, features, targets, verbose)?,
+  );
+
+  let configured_targets = get_configured_build_targets(&create_cargo_config(None, &[]));
+  if configured_targets.iter().any(|t| !is_android_target(t)) {
+    println!(
+      "Overriding build.target = {:?} of the cargo configuration with the android targets",
+      configured_targets
     );
   }
 
-  let mut cargo_config = CargoConfig::default().unwrap();
-  cargo_config
-    .configure(
-      0,                      // verbose
-      false,                  // quiet
-      None,                   // color
-      cargo_config.frozen(),  // frozen
-      cargo_config.locked(),  // locked
-      cargo_config.offline(), // offline
-      &None,                  // target dir
-      &[],                    // unstable flags
-      &[],                    // cli config
-    )
-    .unwrap();
+  let cli_config = match profile.build_profile {
+    BuildProfile::Release => get_release_preset_config(manifest_path).map_err(Error::Config)?,
+    BuildProfile::Debug => vec![],
+  };
 
-  let workspace = Workspace::new(manifest_path, &cargo_config).unwrap();
+  let mut out = HashMap::new();
+  for ((rustflags, features), targets) in invocations {
+    let targets: Vec<&str> = targets.iter().map(|t| t.as_str()).collect();
+    out.extend(build_targets_as_lib(
+      manifest_path,
+      build_target.clone(),
+      &targets,
+      profile,
+      &rustflags,
+      &features,
+      &cli_config,
+    )?);
+  }
 
-  let mut build_config =
-    BuildConfig::new(&cargo_config, None, false, &[], CompileMode::Build).unwrap();
+  validate_artifacts(&out, targets)?;
+  check_native_libraries(&out, targets, profile.dir_name(), build_start)?;
+  Ok(out)
+}
 
-  build_config.requested_profile = profile.get_requested_profile().into();
+fn build_targets_as_lib(
+  manifest_path: &Path,
+  build_target: BuildTarget,
+  targets: &[&str],
+  profile: &CargoProfile,
+  rustflags: &[String],
+  features: &FeatureSelection,
+  cli_config: &[String],
+) -> Result<HashMap<String, PathBuf>> {
+  let mut linkers: HashMap<String, PathBuf> = HashMap::new();
+  for t in targets {
+    linkers.insert(t.to_string(), get_target_linker_path(t));
+  }
 
-  build_config.requested_kinds = targets
-    .iter()
-    .map(|s| CompileKind::Target(CompileTarget::new(s).unwrap()))
+  //cargo only reads unicode variables, others would make env::vars() panic
+  let mut env: HashMap<String, String> = env::vars_os()
+    .filter_map(|(k, v)| Some((k.into_string().ok()?, v.into_string().ok()?)))
     .collect();
+  env.remove("RUSTFLAGS");
+  env.insert(
+    "CARGO_ENCODED_RUSTFLAGS".to_string(),
+    encode_rustflags(rustflags),
+  );
+  let cargo_config = create_cargo_config(Some(env), cli_config);
+
+  let workspace =
+    Workspace::new(manifest_path, &cargo_config).map_err(|e| Error::Config(format!("{:#}", e)))?;
+  let native_target = get_native_target(
+    workspace
+      .current()
+      .map_err(|e| Error::Config(format!("{:#}", e)))?,
+    &build_target,
+  )?;
+  let crate_name = native_target.crate_name();
+  let builds_lib = native_target.is_lib();
+
+  //passing the targets explicitly overrides build.target of the cargo configuration
+  let requested_targets: Vec<String> = targets.iter().map(|t| t.to_string()).collect();
+  let mut build_config = BuildConfig::new(
+    &cargo_config,
+    None,
+    false,
+    &requested_targets,
+    CompileMode::Build,
+  )
+  .unwrap();
+
+  build_config.requested_profile = profile.name.as_str().into();
 
   let compile_options = CompileOptions {
     build_config,
-    cli_features: CliFeatures::new_all(false),
+    cli_features: features
+      .to_cli_features()
+      .map_err(|e| Error::Config(format!("{:#}", e)))?,
     spec: Packages::Packages(Vec::new()),
     filter: CompileFilter::Only {
       all_targets: false,
-      lib: LibRule::False,
+      lib: if builds_lib {
+        LibRule::True
+      } else {
+        LibRule::False
+      },
       bins: match build_target {
-        BuildTarget::Bin => FilterRule::All,
-        BuildTarget::Example(_) => FilterRule::Just(vec![]),
+        BuildTarget::Bin if !builds_lib => FilterRule::All,
+        _ => FilterRule::Just(vec![]),
       },
       examples: match build_target {
         BuildTarget::Bin => FilterRule::Just(vec![]),
@@ -191,10 +474,196 @@ pub fn build_bin_as_lib(
     honor_rust_version: true,
   };
 
-  let executor = Arc::new(LibExecutor::new(linkers));
+  let executor = Arc::new(LibExecutor::new(linkers, crate_name));
   let executor_dyn: Arc<dyn Executor> = executor.clone();
-  cargo::ops::compile_with_exec(&workspace, &compile_options, &executor_dyn).unwrap();
+  cargo::ops::compile_with_exec(&workspace, &compile_options, &executor_dyn)
+    .map_err(|e| Error::Cargo(format!("{:#}", e)))?;
 
   let out = &*executor.out.lock().unwrap();
-  out.clone()
+  Ok(out.clone())
+}
+
+#[cfg(test)]
+mod test {
+  use std::collections::HashMap;
+  use std::fs::{create_dir_all, write};
+  use std::path::{Path, PathBuf};
+
+  use cargo::core::Workspace;
+  use tempfile::tempdir;
+
+  use crate::build_bin_as_lib::{
+    android_target_env_with, create_cargo_config, find_native_library, get_cargo_target_dir,
+    get_native_target, group_cargo_invocations, validate_artifacts, BuildTarget,
+  };
+  use crate::features::FeatureSelection;
+
+  #[test]
+  fn target_env_names() {
+    let env = android_target_env_with(
+      Path::new("/ndk/toolchains/llvm/prebuilt/linux-x86_64"),
+      "armv7-linux-androideabi",
+    );
+    let linker = PathBuf::from(
+      "/ndk/toolchains/llvm/prebuilt/linux-x86_64/bin/armv7a-linux-androideabi26-clang",
+    );
+    assert_eq!(
+      env,
+      vec![
+        (
+          "CARGO_TARGET_ARMV7_LINUX_ANDROIDEABI_LINKER".to_string(),
+          linker.clone()
+        ),
+        ("CC_armv7_linux_androideabi".to_string(), linker),
+        (
+          "AR_armv7_linux_androideabi".to_string(),
+          PathBuf::from("/ndk/toolchains/llvm/prebuilt/linux-x86_64/bin/llvm-ar")
+        ),
+      ]
+    );
+  }
+
+  #[test]
+  fn host_artifacts_rejected() {
+    let targets = ["aarch64-linux-android", "x86_64-linux-android"];
+    let mut artifacts: HashMap<String, PathBuf> = HashMap::new();
+    artifacts.insert(
+      "aarch64-linux-android".to_string(),
+      "/p/target/aarch64-linux-android/debug/deps/libgame.so".into(),
+    );
+    assert!(validate_artifacts(&artifacts, &targets).is_ok());
+
+    artifacts.insert("".to_string(), "/p/target/debug/deps/libgame.so".into());
+    assert!(validate_artifacts(&artifacts, &targets)
+      .unwrap_err()
+      .contains("for the host"));
+
+    artifacts.remove("");
+    artifacts.insert(
+      "x86_64-unknown-linux-gnu".to_string(),
+      "/p/target/x86_64-unknown-linux-gnu/debug/deps/libgame.so".into(),
+    );
+    assert!(validate_artifacts(&artifacts, &targets).is_err());
+  }
+
+  #[test]
+  fn cargo_invocations() {
+    let flags = |f: &[&str]| f.iter().map(|f| f.to_string()).collect::<Vec<String>>();
+    let features = |f: &[&str]| FeatureSelection {
+      features: flags(f),
+      ..FeatureSelection::default()
+    };
+    let invocations = group_cargo_invocations(
+      vec![
+        ("aarch64-linux-android".to_string(), flags(&[])),
+        ("armv7-linux-androideabi".to_string(), flags(&[])),
+        ("x86_64-linux-android".to_string(), flags(&[])),
+        (
+          "i686-linux-android".to_string(),
+          flags(&["-C", "opt-level=s"]),
+        ),
+      ],
+      vec![
+        ("aarch64-linux-android".to_string(), features(&["vulkan"])),
+        ("armv7-linux-androideabi".to_string(), features(&["gles2"])),
+        ("x86_64-linux-android".to_string(), features(&["vulkan"])),
+        ("i686-linux-android".to_string(), features(&["vulkan"])),
+      ],
+    );
+    let invocations: Vec<(Vec<String>, Vec<String>)> = invocations
+      .into_iter()
+      .map(|((rustflags, features), targets)| {
+        let mut args = features.cargo_args();
+        args.extend(rustflags);
+        (args, targets)
+      })
+      .collect();
+    assert_eq!(
+      invocations,
+      vec![
+        (
+          flags(&["--features", "gles2"]),
+          flags(&["armv7-linux-androideabi"])
+        ),
+        (
+          flags(&["--features", "vulkan"]),
+          flags(&["aarch64-linux-android", "x86_64-linux-android"])
+        ),
+        (
+          flags(&["--features", "vulkan", "-C", "opt-level=s"]),
+          flags(&["i686-linux-android"])
+        ),
+      ]
+    );
+  }
+
+  #[test]
+  fn native_library_names() {
+    assert_eq!(
+      find_native_library("libengine.rlib\nlibengine.so\n", "engine"),
+      Some("libengine.so")
+    );
+    assert_eq!(
+      find_native_library("libmy_game-3f2a.so\n", "my_game"),
+      Some("libmy_game-3f2a.so")
+    );
+    assert_eq!(find_native_library("libmy_game.so\n", "engine"), None);
+  }
+
+  #[test]
+  fn custom_lib_name() {
+    let dir = tempdir().unwrap();
+    let manifest_path = dir.path().join("Cargo.toml");
+    write(
+      &manifest_path,
+      include_str!("../tests/fixtures/manifests/custom_lib_name.toml"),
+    )
+    .unwrap();
+    create_dir_all(dir.path().join("src/bin")).unwrap();
+    write(dir.path().join("src/lib.rs"), "").unwrap();
+
+    let cargo_config = create_cargo_config(None, &[]);
+    let native_target = |manifest_path: &Path| {
+      let workspace = Workspace::new(manifest_path, &cargo_config).unwrap();
+      let target = get_native_target(workspace.current().unwrap(), &BuildTarget::Bin).unwrap();
+      (target.crate_name(), target.is_lib())
+    };
+    //without a bin the library is the cdylib lib, named after the lib instead of the package
+    assert_eq!(native_target(&manifest_path), ("engine".to_string(), true));
+
+    //a bin is built as the library, with the dashes of its name replaced like cargo does
+    write(dir.path().join("src/bin/my-game.rs"), "fn main() {}").unwrap();
+    assert_eq!(
+      native_target(&manifest_path),
+      ("my_game".to_string(), false)
+    );
+  }
+
+  #[test]
+  fn workspace_target_dir() {
+    let dir = tempdir().unwrap();
+    let member_dir = dir.path().join("game");
+    create_dir_all(member_dir.join("src")).unwrap();
+    write(member_dir.join("src/lib.rs"), "").unwrap();
+    write(
+      member_dir.join("Cargo.toml"),
+      "[package]\nname = \"game\"\nversion = \"0.1.0\"\n",
+    )
+    .unwrap();
+    assert_eq!(
+      member_dir.join("target"),
+      get_cargo_target_dir(&member_dir.join("Cargo.toml"))
+    );
+
+    //members build into the target dir of the workspace
+    write(
+      dir.path().join("Cargo.toml"),
+      "[workspace]\nmembers = [\"game\"]\n",
+    )
+    .unwrap();
+    assert_eq!(
+      dir.path().join("target"),
+      get_cargo_target_dir(&member_dir.join("Cargo.toml"))
+    );
+  }
 }
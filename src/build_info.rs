@@ -0,0 +1,209 @@
+use std::cmp::Ordering;
+use std::env;
+use std::path::Path;
+use std::process::Command;
+
+use crate::command_runner::*;
+use crate::gradle_dsl::*;
+use crate::sdk::*;
+use crate::util::*;
+use crate::warnings::*;
+use crate::BuildProfile;
+
+/// Prefix of the environment variables cargo builds with, e.g. `CARGO_SDL_APK_GIT_HASH`.
+pub const BUILD_INFO_ENV_PREFIX: &str = "CARGO_SDL_APK_";
+
+/// Facts about the build both sides of the app see: Java as `BuildConfig` fields, Rust as
+/// `CARGO_SDL_APK_<field>` environment variables at compile time, e.g.
+/// `option_env!("CARGO_SDL_APK_GIT_HASH")`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BuildInfo {
+  pub profile: BuildProfile,
+  pub crate_version: String,
+  /// The commit checked out, `None` outside of a git repository.
+  pub git_hash: Option<String>,
+}
+
+impl BuildInfo {
+  /// The fields by name, `GIT_HASH` only if there is one.
+  pub fn fields(&self) -> Vec<(&'static str, String)> {
+    let mut fields = vec![
+      ("CARGO_PROFILE", self.profile.to_string()),
+      ("CRATE_VERSION", self.crate_version.clone()),
+    ];
+    if let Some(git_hash) = &self.git_hash {
+      fields.push(("GIT_HASH", git_hash.clone()));
+    }
+    fields
+  }
+
+  /// Sets the environment variables of the fields for the cargo build, removing a `GIT_HASH` of an
+  /// earlier build in the same process.
+  pub fn set_cargo_env(&self) {
+    if self.git_hash.is_none() {
+      env::remove_var(format!("{}GIT_HASH", BUILD_INFO_ENV_PREFIX));
+    }
+    for (name, value) in self.fields() {
+      env::set_var(format!("{}{}", BUILD_INFO_ENV_PREFIX, name), value);
+    }
+  }
+}
+
+/// The commit checked out in the dir, `None` if it isn't in a git repository or git is missing.
+pub fn get_git_hash(dir: &Path) -> Option<String> {
+  run_command_output(
+    Command::new("git")
+      .args(["rev-parse", "HEAD"])
+      .current_dir(dir),
+  )
+  .ok()
+  .filter(|output| output.status.success())
+  .and_then(|output| String::from_utf8(output.stdout).ok())
+  .map(|hash| hash.trim().to_string())
+  .filter(|hash| !hash.is_empty())
+}
+
+pub fn get_build_info(manifest_path: &Path, profile: BuildProfile) -> Result<BuildInfo, TomlError> {
+  Ok(BuildInfo {
+    profile,
+    crate_version: get_toml_string(manifest_path, ["package", "version"])?
+      .unwrap_or_else(|| "0.0.0".to_string()),
+    git_hash: get_git_hash(manifest_path.parent().unwrap()),
+  })
+}
+
+/// A Java string literal, quoted as gradle string, e.g. `'"1.0"'`.
+fn java_string_literal(value: &str, dsl: GradleDsl) -> String {
+  dsl.string(&format!(
+    "\"{}\"",
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+  ))
+}
+
+/// Adds the fields as `buildConfigField` entries to the defaultConfig block of app/build.gradle.
+/// From Android Gradle Plugin 8 on, the `BuildConfig` class is only generated if it's enabled,
+/// which is done as well.
+pub fn add_build_config_fields(
+  build_gradle: &str,
+  info: &BuildInfo,
+  agp_version: Option<&str>,
+  dsl: GradleDsl,
+) -> String {
+  let mut content = build_gradle.to_string();
+  //inserted after the same line, so in reverse
+  for (name, value) in info.fields().iter().rev() {
+    let field = dsl.call(
+      "buildConfigField",
+      &[
+        &dsl.string("String"),
+        &dsl.string(name),
+        &java_string_literal(value, dsl),
+      ],
+    );
+    content = insert_after_line(&content, |l| l == "defaultConfig {", &field, "    ")
+      .unwrap_or_else(|| {
+        warn(
+          &BUILD_CONFIG,
+          "Unable to add the BuildConfig fields, no defaultConfig found in build.gradle.",
+        );
+        content
+      });
+  }
+
+  let enabled = dsl.assign("buildConfig", "true");
+  let disabled = dsl.assign("buildConfig", "false");
+  if content.contains(&disabled) {
+    content = content.replace(&disabled, &enabled);
+  } else if agp_version.is_some_and(|v| compare_versions(v, "8") != Ordering::Less)
+    && !content.contains(&enabled)
+  {
+    content = insert_after_line(&content, |l| l == "android {", "buildFeatures {", "    ")
+      .and_then(|c| insert_after_line(&c, |l| l == "buildFeatures {", "}", ""))
+      .and_then(|c| insert_after_line(&c, |l| l == "buildFeatures {", &enabled, "    "))
+      .unwrap_or(content);
+  }
+  content
+}
+
+#[cfg(test)]
+mod test {
+  use crate::build_info::{add_build_config_fields, BuildInfo};
+  use crate::gradle_dsl::GradleDsl;
+  use crate::BuildProfile;
+
+  const BUILD_GRADLE: &str = "android {
+    compileSdk 34
+    defaultConfig {
+        minSdk 21
+    }
+}
+";
+
+  fn info(git_hash: Option<&str>) -> BuildInfo {
+    BuildInfo {
+      profile: BuildProfile::Release,
+      crate_version: "1.2.0-beta.1".to_string(),
+      git_hash: git_hash.map(|h| h.to_string()),
+    }
+  }
+
+  #[test]
+  fn build_config_fields() {
+    let content = add_build_config_fields(
+      BUILD_GRADLE,
+      &info(Some("0123abcd")),
+      Some("8.1.1"),
+      GradleDsl::Groovy,
+    );
+    assert_eq!(
+      content,
+      "android {
+    buildFeatures {
+        buildConfig true
+    }
+    compileSdk 34
+    defaultConfig {
+        buildConfigField 'String', 'CARGO_PROFILE', '\"release\"'
+        buildConfigField 'String', 'CRATE_VERSION', '\"1.2.0-beta.1\"'
+        buildConfigField 'String', 'GIT_HASH', '\"0123abcd\"'
+        minSdk 21
+    }
+}
+"
+    );
+
+    //before AGP 8 BuildConfig is generated by default
+    let content =
+      add_build_config_fields(BUILD_GRADLE, &info(None), Some("7.0.3"), GradleDsl::Groovy);
+    assert!(!content.contains("buildFeatures"));
+    assert!(!content.contains("GIT_HASH"));
+
+    let disabled = BUILD_GRADLE.replace(
+      "android {",
+      "android {\n    buildFeatures {\n        buildConfig false\n    }",
+    );
+    let content = add_build_config_fields(&disabled, &info(None), None, GradleDsl::Groovy);
+    assert!(content.contains("        buildConfig true\n"));
+    assert!(!content.contains("buildConfig false"));
+
+    let kts =
+      "android {\n    compileSdk = 34\n    defaultConfig {\n        minSdk = 21\n    }\n}\n";
+    let content = add_build_config_fields(kts, &info(None), Some("8.2.0"), GradleDsl::Kotlin);
+    assert!(content.contains("    buildFeatures {\n        buildConfig = true\n    }\n"));
+    assert!(content
+      .contains("        buildConfigField(\"String\", \"CARGO_PROFILE\", \"\\\"release\\\"\")\n"));
+  }
+
+  #[test]
+  fn quoting() {
+    let mut info = info(None);
+    info.crate_version = "1.0 \"it's\" \\o/".to_string();
+    let content = add_build_config_fields(BUILD_GRADLE, &info, None, GradleDsl::Groovy);
+    assert!(
+      content.contains(r#"buildConfigField 'String', 'CRATE_VERSION', '"1.0 \\"it\'s\\" \\\\o/"'"#)
+    );
+    let content = add_build_config_fields(BUILD_GRADLE, &info, None, GradleDsl::Kotlin);
+    assert!(content
+      .contains(r#"buildConfigField("String", "CRATE_VERSION", "\"1.0 \\\"it's\\\" \\\\o/\"")"#));
+  }
+}
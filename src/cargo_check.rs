@@ -0,0 +1,188 @@
+use std::env;
+use std::ffi::OsString;
+use std::path::Path;
+use std::process::Command;
+
+use crate::build_bin_as_lib::*;
+use crate::command_runner::*;
+use crate::features::*;
+use crate::offline::*;
+use crate::rustflags::*;
+use crate::util::*;
+use crate::BuildProfile;
+
+/// The android target checked when none is given.
+pub const DEFAULT_CHECK_TARGET: &str = "aarch64-linux-android";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckTool {
+  Check,
+  Clippy,
+}
+
+impl CheckTool {
+  fn cargo_command(&self) -> &str {
+    match self {
+      CheckTool::Check => "check",
+      CheckTool::Clippy => "clippy",
+    }
+  }
+}
+
+/// Builds the argument list of the cargo invocation for checking the given target.
+pub fn build_check_args(
+  tool: CheckTool,
+  manifest_path: &Path,
+  target: &str,
+  build_target: &BuildTarget,
+  release: bool,
+  features: Option<&str>,
+  extra_args: &[String],
+) -> Vec<OsString> {
+  let mut args: Vec<OsString> = vec![
+    tool.cargo_command().into(),
+    "--manifest-path".into(),
+    manifest_path.into(),
+    "--target".into(),
+    target.into(),
+  ];
+
+  if let BuildTarget::Example(example) = build_target {
+    args.push("--example".into());
+    args.push(example.into());
+  }
+  if release {
+    args.push("--release".into());
+  }
+  if let Some(features) = features {
+    args.push("--features".into());
+    args.push(features.into());
+  }
+
+  args.extend(extra_args.iter().map(OsString::from));
+  args
+}
+
+/// Type checks (or lints) the crate for a single android target, with the same NDK toolchain
+/// setup as a full build but without building SDL, the android project or the apk.
+pub fn check_android(
+  tool: CheckTool,
+  manifest_path: &Path,
+  target: &str,
+  build_target: &BuildTarget,
+  release: bool,
+  features: Option<&str>,
+  extra_args: &[String],
+) {
+  set_metadata_profile(Some(
+    if release {
+      BuildProfile::Release
+    } else {
+      BuildProfile::Debug
+    }
+    .to_string(),
+  ));
+  let features = get_check_features(manifest_path, target, features).unwrap_or_else(|e| {
+    eprintln!("Error: {}.", e);
+    std::process::exit(1);
+  });
+  let mut args = build_check_args(
+    tool,
+    manifest_path,
+    target,
+    build_target,
+    release,
+    features.as_deref(),
+    extra_args,
+  );
+  //before the extra args, which may end the options with `--`
+  args.splice(
+    1..1,
+    get_network_args()
+      .cargo_args()
+      .into_iter()
+      .map(OsString::from),
+  );
+
+  let cargo = env::var_os("CARGO").unwrap_or("cargo".into());
+  let rustflags = get_target_rustflags(manifest_path, &[target])
+    .unwrap_or_else(|e| {
+      eprintln!("Error: {}.", e);
+      std::process::exit(1);
+    })
+    .remove(0)
+    .1;
+  let status = run_command(
+    Command::new(&cargo)
+      .args(&args)
+      .envs(get_android_target_env(target))
+      .env_remove("RUSTFLAGS")
+      .env("CARGO_ENCODED_RUSTFLAGS", encode_rustflags(&rustflags)),
+  );
+
+  if !status.success() {
+    std::process::exit(status.code().unwrap_or(1));
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::path::Path;
+
+  use crate::build_bin_as_lib::BuildTarget;
+  use crate::cargo_check::{build_check_args, CheckTool};
+
+  #[test]
+  fn check_args() {
+    let args = build_check_args(
+      CheckTool::Check,
+      Path::new("/project/Cargo.toml"),
+      "aarch64-linux-android",
+      &BuildTarget::Bin,
+      false,
+      None,
+      &[],
+    );
+    assert_eq!(
+      args,
+      vec![
+        "check",
+        "--manifest-path",
+        "/project/Cargo.toml",
+        "--target",
+        "aarch64-linux-android"
+      ]
+    );
+  }
+
+  #[test]
+  fn clippy_args_with_options() {
+    let args = build_check_args(
+      CheckTool::Clippy,
+      Path::new("/project/Cargo.toml"),
+      "x86_64-linux-android",
+      &BuildTarget::Example("demo".to_string()),
+      true,
+      Some("gles2 audio"),
+      &["--".to_string(), "-D".to_string(), "warnings".to_string()],
+    );
+    assert_eq!(
+      args,
+      vec![
+        "clippy",
+        "--manifest-path",
+        "/project/Cargo.toml",
+        "--target",
+        "x86_64-linux-android",
+        "--example",
+        "demo",
+        "--release",
+        "--features",
+        "gles2 audio",
+        "--",
+        "-D",
+        "warnings"
+      ]
+    );
+  }
+}
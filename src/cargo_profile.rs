@@ -0,0 +1,151 @@
+use std::path::Path;
+
+use toml::Table;
+
+use crate::util::*;
+use crate::BuildProfile;
+
+/// The cargo profile the native library is built with: `dev`, `release` or a custom profile of the
+/// workspace root manifest inheriting from one of them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CargoProfile {
+  pub name: String,
+  /// Whether the profile builds a debug or a release app, by the built-in profile it inherits.
+  pub build_profile: BuildProfile,
+}
+
+impl CargoProfile {
+  /// The built-in profile of the app's build type, which `cargo build` and `cargo build --release`
+  /// use.
+  pub fn for_build_profile(build_profile: BuildProfile) -> Self {
+    CargoProfile {
+      name: match build_profile {
+        BuildProfile::Debug => "dev",
+        BuildProfile::Release => "release",
+      }
+      .to_string(),
+      build_profile,
+    }
+  }
+
+  /// The dir in `target/<triple>/` cargo builds the profile into: `debug` for `dev` and `test`,
+  /// `release` for `bench` and the name of the profile otherwise.
+  pub fn dir_name(&self) -> &str {
+    match self.name.as_str() {
+      "dev" | "test" => "debug",
+      "bench" => "release",
+      name => name,
+    }
+  }
+}
+
+/// Whether the profile builds a debug or a release app, following `inherits` of custom profiles in
+/// `[profile]` of the workspace root manifest until a built-in profile.
+pub fn resolve_cargo_profile(root_manifest: &Table, name: &str) -> Result<CargoProfile, String> {
+  let mut current = name.to_string();
+  let mut chain = vec![];
+  let build_profile = loop {
+    match current.as_str() {
+      "dev" | "test" => break BuildProfile::Debug,
+      "release" | "bench" => break BuildProfile::Release,
+      _ => {}
+    }
+    if chain.contains(&current) {
+      chain.push(current);
+      return Err(format!(
+        "the profiles inherit from each other in a loop: {}",
+        chain.join(" -> ")
+      ));
+    }
+    let Some(profile) = root_manifest
+      .get("profile")
+      .and_then(|profiles| profiles.get(&current))
+    else {
+      return Err(format!(
+        "profile `{}` is not defined in [profile] of the workspace root manifest",
+        current
+      ));
+    };
+    let Some(inherits) = profile
+      .get("inherits")
+      .and_then(|inherits| inherits.as_str())
+    else {
+      return Err(format!(
+        "profile `{}` must set `inherits` to a profile it is based on, e.g. \"release\"",
+        current
+      ));
+    };
+    chain.push(current);
+    current = inherits.to_string();
+  };
+  Ok(CargoProfile {
+    name: name.to_string(),
+    build_profile,
+  })
+}
+
+/// Resolves the profile from the workspace root manifest of the package, see
+/// [resolve_cargo_profile].
+pub fn get_cargo_profile(manifest_path: &Path, name: &str) -> Result<CargoProfile, String> {
+  let root_manifest =
+    find_workspace_root_manifest(manifest_path)?.unwrap_or_else(|| manifest_path.to_path_buf());
+  resolve_cargo_profile(&read_toml_file(&root_manifest)?, name)
+}
+
+#[cfg(test)]
+mod test {
+  use toml::Table;
+
+  use crate::cargo_profile::{resolve_cargo_profile, CargoProfile};
+  use crate::BuildProfile;
+
+  #[test]
+  fn cargo_profiles() {
+    let manifest = r#"
+[profile.dist]
+inherits = "release"
+lto = true
+
+[profile.dist-small]
+inherits = "dist"
+opt-level = "z"
+
+[profile.fast-dev]
+inherits = "dev"
+
+[profile.broken]
+debug = true
+
+[profile.a]
+inherits = "b"
+
+[profile.b]
+inherits = "a"
+"#
+    .parse::<Table>()
+    .unwrap();
+
+    let dev = resolve_cargo_profile(&manifest, "dev").unwrap();
+    assert_eq!(CargoProfile::for_build_profile(BuildProfile::Debug), dev);
+    assert_eq!("debug", dev.dir_name());
+    let release = resolve_cargo_profile(&manifest, "release").unwrap();
+    assert_eq!(BuildProfile::Release, release.build_profile);
+    assert_eq!("release", release.dir_name());
+
+    let dist_small = resolve_cargo_profile(&manifest, "dist-small").unwrap();
+    assert_eq!(BuildProfile::Release, dist_small.build_profile);
+    assert_eq!("dist-small", dist_small.dir_name());
+    let fast_dev = resolve_cargo_profile(&manifest, "fast-dev").unwrap();
+    assert_eq!(BuildProfile::Debug, fast_dev.build_profile);
+    assert_eq!("fast-dev", fast_dev.dir_name());
+
+    assert!(resolve_cargo_profile(&manifest, "missing").is_err());
+    assert!(resolve_cargo_profile(&manifest, "broken")
+      .unwrap_err()
+      .contains("must set `inherits`"));
+    assert_eq!(
+      Err("the profiles inherit from each other in a loop: a -> b -> a".to_string()),
+      resolve_cargo_profile(&manifest, "a")
+    );
+  }
+}
@@ -0,0 +1,82 @@
+use std::io::{stdin, stdout, BufRead, Write};
+use std::sync::Mutex;
+
+use lazy_static::lazy_static;
+
+lazy_static! {
+  static ref CI_MODE: Mutex<bool> = Mutex::new(false);
+}
+
+/// Env var CI services set to `true`, enabling CI mode unless `--no-ci` is given.
+pub const CI_ENV: &str = "CI";
+
+/// Decides whether to run non-interactively: `--ci`, or else `CI=true` unless `--no-ci` is given.
+pub fn resolve_ci_mode(ci: bool, no_ci: bool, ci_env: Option<&str>) -> bool {
+  ci || (!no_ci && ci_env.is_some_and(|value| value.eq_ignore_ascii_case("true") || value == "1"))
+}
+
+/// Enables CI mode for the rest of the run: prompts fail, cargo output isn't colored and gradle
+/// shows no progress bar.
+pub fn set_ci_mode(ci: bool) {
+  *CI_MODE.lock().unwrap() = ci;
+}
+
+pub fn is_ci_mode() -> bool {
+  *CI_MODE.lock().unwrap()
+}
+
+/// The error for a prompt in CI mode, naming how to provide the answer non-interactively.
+pub fn non_interactive_error(prompt: &str, hint: &str) -> String {
+  format!(
+    "Can't ask \"{}\" as prompts are disabled by --ci or {}=true. {}",
+    prompt, CI_ENV, hint
+  )
+}
+
+/// Fails in CI mode, before starting something that will wait for the user.
+pub fn ensure_interactive(prompt: &str, hint: &str) -> Result<(), String> {
+  if is_ci_mode() {
+    return Err(non_interactive_error(prompt, hint));
+  }
+  Ok(())
+}
+
+/// Asks the user and returns the entered line, or fails in CI mode. Every prompt goes through
+/// here, `hint` tells how to pass the answer with a flag, env var or the package metadata instead.
+pub fn prompt_or_fail(prompt: &str, hint: &str) -> Result<String, String> {
+  ensure_interactive(prompt, hint)?;
+  print!("{} ", prompt);
+  let _ = stdout().flush();
+  let mut line = String::new();
+  stdin()
+    .lock()
+    .read_line(&mut line)
+    .map_err(|e| format!("unable to read the answer to \"{}\": {}", prompt, e))?;
+  Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+#[cfg(test)]
+mod test {
+  use crate::ci::{non_interactive_error, resolve_ci_mode};
+
+  #[test]
+  fn ci_mode() {
+    assert!(!resolve_ci_mode(false, false, None));
+    assert!(resolve_ci_mode(true, false, None));
+    assert!(resolve_ci_mode(false, false, Some("true")));
+    assert!(resolve_ci_mode(false, false, Some("TRUE")));
+    assert!(resolve_ci_mode(false, false, Some("1")));
+    assert!(!resolve_ci_mode(false, false, Some("false")));
+    assert!(!resolve_ci_mode(false, true, Some("true")));
+    assert!(resolve_ci_mode(true, true, Some("true")));
+
+    assert_eq!(
+      "Can't ask \"Press Enter to stop debugging.\" as prompts are disabled by --ci or CI=true. \
+      Debugging needs a terminal, pass --no-ci",
+      non_interactive_error(
+        "Press Enter to stop debugging.",
+        "Debugging needs a terminal, pass --no-ci"
+      )
+    );
+  }
+}
@@ -0,0 +1,1066 @@
+use std::ffi::OsString;
+use std::fs::canonicalize;
+use std::path::{Path, PathBuf};
+
+use clap::{Args, Parser, Subcommand};
+use clap_complete::Shell;
+
+use crate::adb::ReversePort;
+use crate::android_build::{AndroidBuild, Signing};
+use crate::artifacts::Artifact;
+use crate::cargo_check::DEFAULT_CHECK_TARGET;
+use crate::last_build::BuildArtifact;
+use crate::launch_profile::IntentExtra;
+use crate::preflight::SdlVersion;
+use crate::signing::{get_signing_config, get_v4_signing, SigningConfig};
+use crate::BuildProfile;
+
+/// Build APKs with Rust and SDL.
+#[derive(Debug, Parser)]
+#[command(name = "cargo-sdl-apk", bin_name = "cargo sdl-apk", version)]
+#[command(arg_required_else_help = true)]
+pub struct Cli {
+  #[command(subcommand)]
+  pub command: SdlApkCommand,
+  /// Never prompt, fail instead, and print no colors or progress bars. Enabled by CI=true.
+  #[arg(long, global = true)]
+  pub ci: bool,
+  /// Allow prompts even though CI=true is set.
+  #[arg(long, global = true, conflicts_with = "ci")]
+  pub no_ci: bool,
+  /// Describe the warning of the id, e.g. `default-icon`, instead of running the command.
+  #[arg(long, global = true, value_name = "ID")]
+  pub explain: Option<String>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum SdlApkCommand {
+  /// Build APK from bin target.
+  Build(BuildArgs),
+  /// Build APK and run it on a device using adb.
+  Run(BuildArgs),
+  /// Build APK and install it on a device using adb.
+  Install(BuildArgs),
+  /// Sign the last release APK.
+  Sign(SignArgs),
+  /// Run cargo check for an android target.
+  Check(CheckArgs),
+  /// Run cargo clippy for an android target.
+  Clippy(CheckArgs),
+  /// Build and install the debug APK, start it waiting for a debugger and attach lldb-server.
+  Debug(DebugArgs),
+  /// Install the debug APK, check that it launches without crashing and uninstall it again. Exits
+  /// with a non-zero code if the launch failed, 3 if the app stopped responding.
+  TestLaunch(TestLaunchArgs),
+  /// Install the debug APK and stress test it with the platform monkey tool. Exits with a non-zero
+  /// code if the app crashed or stopped responding.
+  Monkey(MonkeyArgs),
+  /// Copy the files dir of the installed debug build from the device.
+  PullData(PullDataArgs),
+  /// Copy a dir into the files dir of the installed debug build.
+  PushData(PushDataArgs),
+  /// Delete all data of the installed app.
+  ClearData(AppArgs),
+  /// Collect the newest native crashes of the installed app with symbolicated frames, the logcat
+  /// around them and the installed version into a dir with a summary.md.
+  CrashReport(CrashReportArgs),
+  /// Check the build environment for problems.
+  Doctor(DoctorArgs),
+  /// Locate the Android sdk, NDK, JDK and SDL checkout, or download SDL, and save them to the user
+  /// config, so the env vars don't need to be set.
+  Setup(SetupArgs),
+  /// List connected devices and pin the one to use by default.
+  Devices(DevicesArgs),
+  /// Generate the android project without building it, or with --diff print what generating would
+  /// change.
+  Generate(GenerateArgs),
+  /// Remove the generated android projects.
+  Clean(ManifestArgs),
+  /// Print the path of an output of the last build, without building.
+  Where(WhereArgs),
+  /// Print the SDL versions the SDL sys crates expect, or download the source of one.
+  Sdl(SdlArgs),
+  /// Manage the SDL builds shared between projects.
+  Cache {
+    #[command(subcommand)]
+    command: CacheCommand,
+  },
+  /// Print a shell completion script.
+  Completions {
+    /// Shell to generate the completions for.
+    shell: Shell,
+  },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum CacheCommand {
+  /// Remove all cached SDL builds.
+  Clean,
+  /// Print the location, content and size of the cache.
+  Info,
+}
+
+#[derive(Debug, Args)]
+pub struct SdlArgs {
+  /// List the SDL versions each sys crate version expects and the downloaded and cached ones.
+  #[arg(long, required_unless_present = "fetch", conflicts_with = "fetch")]
+  pub list: bool,
+  /// Download the source of the SDL version, verified with its checksum, and print its dir to
+  /// use as SDL.
+  #[arg(long, value_name = "VERSION")]
+  pub fetch: Option<SdlVersion>,
+  /// SHA-256 of the source zip, for a version whose checksum isn't pinned.
+  #[arg(long, value_name = "HEX", requires = "fetch")]
+  pub sha256: Option<String>,
+  /// Path to Cargo.toml, whose sdl_checksums are used if it exists.
+  #[arg(long, value_name = "PATH", default_value = "Cargo.toml")]
+  pub manifest_path: PathBuf,
+}
+
+#[derive(Debug, Args)]
+pub struct ManifestArgs {
+  /// Path to Cargo.toml.
+  #[arg(long, value_name = "PATH", default_value = "Cargo.toml")]
+  pub manifest_path: PathBuf,
+}
+
+impl ManifestArgs {
+  pub fn resolve(&self) -> PathBuf {
+    canonicalize(&self.manifest_path).unwrap_or_else(|_| {
+      eprintln!("Error: manifest {:?} not found.", self.manifest_path);
+      std::process::exit(1);
+    })
+  }
+}
+
+#[derive(Debug, Args)]
+pub struct DeviceArgs {
+  /// Serial of the device to use, as listed by `cargo sdl-apk devices`. Defaults to
+  /// ANDROID_SERIAL, the pinned default device or the only connected device.
+  #[arg(long, value_name = "SERIAL")]
+  pub device: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct SigningArgs {
+  /// Keystore file for signing. If omitted the Android debug keystore
+  /// (~/.android/debug.keystore) is used.
+  #[arg(long, alias = "ks", value_name = "FILE")]
+  pub ks_file: Option<String>,
+  /// Keystore pass, e.g. `pass:PASS`. Only required if a key file is specified.
+  #[arg(long, value_name = "PASS")]
+  pub ks_pass: Option<String>,
+  /// Key pass, e.g. `pass:PASS`. Defaults to the keystore pass.
+  #[arg(long, value_name = "PASS")]
+  pub key_pass: Option<String>,
+  /// Create a v4 signature (`.apk.idsig`) for incremental installs. Enabled by default if the min
+  /// sdk version and the build-tools allow it.
+  #[arg(long, value_name = "BOOL", num_args = 0..=1, default_missing_value = "true")]
+  pub v4_signing: Option<bool>,
+}
+
+impl SigningArgs {
+  /// The keystore file given on the command line, relative to the working directory unlike the one
+  /// of the metadata, which is relative to the manifest.
+  fn absolute_ks_file(&self) -> Option<String> {
+    self
+      .ks_file
+      .as_ref()
+      .map(|file| match std::path::absolute(file) {
+        Ok(path) => path.to_string_lossy().to_string(),
+        Err(_) => file.clone(),
+      })
+  }
+
+  /// Combines the keystore options with the signing metadata of the package.
+  pub fn resolve(&self, manifest_path: &Path) -> Option<SigningConfig> {
+    get_signing_config(
+      manifest_path,
+      self.absolute_ks_file(),
+      self.ks_pass.clone(),
+      self.key_pass.clone(),
+    )
+    .unwrap_or_else(|e| {
+      eprintln!("Error: {}.", e);
+      std::process::exit(1);
+    })
+  }
+
+  pub fn resolve_v4_signing(&self, manifest_path: &Path) -> bool {
+    get_v4_signing(manifest_path, self.v4_signing).unwrap_or_else(|e| {
+      eprintln!("Error: {}.", e);
+      std::process::exit(1);
+    })
+  }
+
+  /// The signing options of the library API.
+  pub fn to_signing(&self) -> Signing {
+    let mut signing = Signing::new();
+    if let Some(ks_file) = self.absolute_ks_file() {
+      signing = signing.keystore(ks_file);
+    }
+    if let Some(ks_pass) = &self.ks_pass {
+      signing = signing.keystore_pass(ks_pass);
+    }
+    if let Some(key_pass) = &self.key_pass {
+      signing = signing.key_pass(key_pass);
+    }
+    if let Some(v4_signing) = self.v4_signing {
+      signing = signing.v4_signing(v4_signing);
+    }
+    signing
+  }
+}
+
+/// Network options forwarded to every cargo invocation.
+#[derive(Debug, Args, Clone, Copy, Default)]
+pub struct NetworkArgs {
+  /// Require Cargo.lock to be up to date.
+  #[arg(long)]
+  pub locked: bool,
+  /// Require Cargo.lock and the cache to be up to date, implies --offline.
+  #[arg(long)]
+  pub frozen: bool,
+  /// Run without accessing the network, failing if anything needed isn't available locally.
+  #[arg(long)]
+  pub offline: bool,
+}
+
+impl NetworkArgs {
+  /// The flags to pass on to a cargo process.
+  pub fn cargo_args(&self) -> Vec<&'static str> {
+    let mut args = vec![];
+    if self.locked {
+      args.push("--locked");
+    }
+    if self.frozen {
+      args.push("--frozen");
+    }
+    if self.offline {
+      args.push("--offline");
+    }
+    args
+  }
+}
+
+#[derive(Debug, Args)]
+pub struct BuildArgs {
+  #[command(flatten)]
+  pub manifest: ManifestArgs,
+  /// Build or run crate example.
+  #[arg(long, value_name = "EXAMPLE")]
+  pub example: Option<String>,
+  /// Build in release mode.
+  #[arg(long)]
+  pub release: bool,
+  /// Build with this cargo profile, e.g. `dev` or a custom `[profile.<NAME>]`. A custom profile
+  /// builds a release app if it inherits from `release`.
+  #[arg(long, value_name = "NAME", conflicts_with = "release")]
+  pub profile: Option<String>,
+  /// Android target to build, e.g. `aarch64-linux-android`. Can be repeated. Defaults to all
+  /// android targets, or the ones of the preset.
+  #[arg(long = "target", value_name = "TRIPLE")]
+  pub targets: Vec<String>,
+  /// Features to activate for all targets, separated by commas or spaces. The target_features of
+  /// the android metadata are added for their target.
+  #[arg(short = 'F', long, value_name = "FEATURES")]
+  pub features: Vec<String>,
+  /// Activate all features.
+  #[arg(long)]
+  pub all_features: bool,
+  /// Don't activate the default features, unless target_features lists them.
+  #[arg(long)]
+  pub no_default_features: bool,
+  /// Print details of the build configuration, like the features of each target.
+  #[arg(short, long)]
+  pub verbose: bool,
+  /// Product flavor of the android metadata to build. Required if the package has flavors.
+  #[arg(long, value_name = "NAME")]
+  pub flavor: Option<String>,
+  /// Apply the `[package.metadata.android.profile.<NAME>]` overrides instead of the ones of the
+  /// build profile, `debug` or `release`.
+  #[arg(long, value_name = "NAME")]
+  pub metadata_profile: Option<String>,
+  #[command(flatten)]
+  pub signing: SigningArgs,
+  /// Produce byte-identical unsigned APKs for unchanged sources by normalizing timestamps and
+  /// entry order.
+  #[arg(long)]
+  pub reproducible: bool,
+  /// Print a breakdown of the APK contents after building.
+  #[arg(long)]
+  pub size_report: bool,
+  /// Fail if the built APK is larger than the given size in MB.
+  #[arg(long, value_name = "MB")]
+  pub size_limit: Option<f64>,
+  /// Install missing build-tools and platforms using sdkmanager.
+  #[arg(long)]
+  pub install_sdk_packages: bool,
+  /// Accept the sdk licenses when installing packages.
+  #[arg(long)]
+  pub accept_licenses: bool,
+  /// Don't regenerate files in the android project which were modified by hand.
+  #[arg(long)]
+  pub keep_local: bool,
+  /// Build with the default icon if the configured icon can't be used, instead of failing.
+  #[arg(long)]
+  pub allow_missing_icon: bool,
+  /// Build a release for 32-bit targets without their 64-bit counterpart, which Google Play
+  /// rejects, e.g. for sideloading.
+  #[arg(long = "allow-32bit-only")]
+  pub allow_32bit_only: bool,
+  /// Directory copied to the assets of the APK.
+  #[arg(long, value_name = "DIR")]
+  pub assets: Option<PathBuf>,
+  /// Fail if the SDL source doesn't match the sdl2 crate.
+  #[arg(long)]
+  pub strict_sdl_check: bool,
+  /// Open the directory of the built APK in the file manager.
+  #[arg(long)]
+  pub open: bool,
+  /// Uninstall the app first if the installed one is signed with another key. Deletes its data.
+  #[arg(long)]
+  pub force_reinstall: bool,
+  /// Gradle task to run together with the assemble task, e.g. of an additional module. Can be
+  /// given multiple times.
+  #[arg(long, value_name = "TASK")]
+  pub gradle_task: Vec<String>,
+  /// Fail when the build raised warnings, for strict CI.
+  #[arg(long)]
+  pub deny_warnings: bool,
+  /// Don't print the warnings of the id, e.g. `default-icon`. Can be given multiple times.
+  #[arg(long, value_name = "ID")]
+  pub allow: Vec<String>,
+  /// Forward a port of the device to the host while the app runs, in addition to adb_reverse.
+  #[arg(long, value_name = "PORT[:REMOTE]")]
+  pub reverse: Vec<ReversePort>,
+  /// Generate the android project in this directory instead of `target/android-project-<profile>`,
+  /// e.g. to isolate parallel CI builds of the same checkout.
+  #[arg(long, value_name = "PATH")]
+  pub project_dir: Option<PathBuf>,
+  /// Run gradle with this GRADLE_USER_HOME, e.g. a cache dir mounted in CI to reuse the gradle
+  /// distribution. Overrides the env var and `gradle_user_home` of the android metadata.
+  #[arg(long, value_name = "PATH")]
+  pub gradle_user_home: Option<PathBuf>,
+  /// Collect the unstripped libraries, native-debug-symbols.zip, the R8 mapping and a
+  /// symbols.json in this directory after a release build, for crash reporting services.
+  #[arg(long, value_name = "DIR")]
+  pub symbols_bundle: Option<PathBuf>,
+  /// Packages to build, `apk,aab` also bundles an AAB for Google Play in the same gradle run.
+  /// Overrides `artifacts` of the android metadata.
+  #[arg(long, value_name = "ARTIFACTS", value_delimiter = ',')]
+  pub artifacts: Vec<Artifact>,
+  #[command(flatten)]
+  pub device: DeviceArgs,
+  /// Print the log of `run` for Rust: panics as one block, demangled symbols and the frames of
+  /// libmain.so in crash dumps resolved with the local symbols.
+  #[arg(long)]
+  pub rust: bool,
+  /// Intent extra `run` starts the app with, `key=value` for a string or typed like `int:level=3`,
+  /// `long:`, `float:`, `bool:` and `string:`. Can be given multiple times.
+  #[arg(long, value_name = "[TYPE:]KEY=VALUE")]
+  pub extra: Vec<IntentExtra>,
+  /// Start the app with the extras of `[launch.profile.<NAME>]` of the local config. Remembered
+  /// for the device, `run` without extras uses it again.
+  #[arg(long, value_name = "NAME")]
+  pub launch_profile: Option<String>,
+  /// Start the app without the launch profile last used on the device, and forget it.
+  #[arg(long, conflicts_with = "launch_profile")]
+  pub no_launch_profile: bool,
+  #[command(flatten)]
+  pub network: NetworkArgs,
+}
+
+impl BuildArgs {
+  /// The build of the library API with these options.
+  pub fn to_android_build(&self, manifest_path: &Path) -> AndroidBuild {
+    let mut build = AndroidBuild::new(manifest_path)
+      .profile(self.build_profile())
+      .targets(&self.targets)
+      .features(&self.features)
+      .all_features(self.all_features)
+      .no_default_features(self.no_default_features)
+      .verbose(self.verbose)
+      .signing(self.signing.to_signing())
+      .reproducible(self.reproducible)
+      .install_sdk_packages(self.install_sdk_packages, self.accept_licenses)
+      .keep_local(self.keep_local)
+      .allow_missing_icon(self.allow_missing_icon)
+      .allow_32bit_only(self.allow_32bit_only)
+      .strict_sdl_check(self.strict_sdl_check)
+      .rust_logcat(self.rust)
+      .network(self.network)
+      .deny_warnings(self.deny_warnings)
+      .no_launch_profile(self.no_launch_profile);
+    if let Some(example) = &self.example {
+      build = build.example(example);
+    }
+    if let Some(assets) = &self.assets {
+      build = build.assets(assets);
+    }
+    if let Some(flavor) = &self.flavor {
+      build = build.flavor(flavor);
+    }
+    if let Some(metadata_profile) = &self.metadata_profile {
+      build = build.metadata_profile(metadata_profile);
+    }
+    if let Some(profile) = &self.profile {
+      build = build.cargo_profile(profile);
+    }
+    for port in &self.reverse {
+      build = build.reverse_port(*port);
+    }
+    for task in &self.gradle_task {
+      build = build.gradle_task(task);
+    }
+    for id in &self.allow {
+      build = build.allow_warning(id);
+    }
+    for extra in &self.extra {
+      build = build.intent_extra(extra.clone());
+    }
+    if let Some(name) = &self.launch_profile {
+      build = build.launch_profile(name);
+    }
+    if let Some(project_dir) = &self.project_dir {
+      build = build.project_dir(project_dir);
+    }
+    if let Some(gradle_user_home) = &self.gradle_user_home {
+      build = build.gradle_user_home(gradle_user_home);
+    }
+    if let Some(symbols_bundle) = &self.symbols_bundle {
+      build = build.symbols_bundle(symbols_bundle);
+    }
+    if !self.artifacts.is_empty() {
+      build = build.artifacts(self.artifacts.clone());
+    }
+    if let Some(device) = &self.device.device {
+      build = build.device(device);
+    }
+    build
+  }
+
+  pub fn build_profile(&self) -> BuildProfile {
+    if self.release {
+      BuildProfile::Release
+    } else {
+      BuildProfile::Debug
+    }
+  }
+}
+
+#[derive(Debug, Args)]
+pub struct SignArgs {
+  #[command(flatten)]
+  pub manifest: ManifestArgs,
+  #[command(flatten)]
+  pub signing: SigningArgs,
+  /// Normalize the unsigned APK before signing, see `build --reproducible`.
+  #[arg(long)]
+  pub reproducible: bool,
+  /// Product flavor whose APK to sign. Required if the package has flavors.
+  #[arg(long, value_name = "NAME")]
+  pub flavor: Option<String>,
+  /// The android project the APK was built in, if `build` was given `--project-dir`.
+  #[arg(long, value_name = "PATH")]
+  pub project_dir: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+pub struct CheckArgs {
+  #[command(flatten)]
+  pub manifest: ManifestArgs,
+  /// Android target to check.
+  #[arg(long, value_name = "TRIPLE", default_value = DEFAULT_CHECK_TARGET)]
+  pub target: String,
+  /// Check crate example.
+  #[arg(long, value_name = "EXAMPLE")]
+  pub example: Option<String>,
+  /// Check in release mode.
+  #[arg(long)]
+  pub release: bool,
+  /// Features to check with.
+  #[arg(long, value_name = "FEATURES")]
+  pub features: Option<String>,
+  #[command(flatten)]
+  pub network: NetworkArgs,
+  /// Extra arguments passed on to cargo.
+  #[arg(last = true)]
+  pub extra_args: Vec<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct DebugArgs {
+  #[command(flatten)]
+  pub build: BuildArgs,
+  /// Start lldb connected to the app instead of printing the commands to connect.
+  #[arg(long)]
+  pub launch_lldb: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct TestLaunchArgs {
+  #[command(flatten)]
+  pub manifest: ManifestArgs,
+  #[command(flatten)]
+  pub device: DeviceArgs,
+  /// Seconds to wait for the app to be ready.
+  #[arg(long, value_name = "SECONDS", default_value_t = 60)]
+  pub timeout: u64,
+  /// Save a screenshot of the device to the given png file before uninstalling.
+  #[arg(long, value_name = "PATH")]
+  pub screenshot: Option<PathBuf>,
+  /// Logcat text marking the app as ready. Defaults to SDL's onCreate or the first frame being
+  /// displayed.
+  #[arg(long, value_name = "TEXT")]
+  pub ready_marker: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct MonkeyArgs {
+  #[command(flatten)]
+  pub manifest: ManifestArgs,
+  #[command(flatten)]
+  pub device: DeviceArgs,
+  /// Number of events to inject.
+  #[arg(long, value_name = "COUNT", default_value_t = 5000)]
+  pub events: u32,
+  /// Seed of the event sequence, to reproduce an earlier run. Random if omitted.
+  #[arg(long, value_name = "N")]
+  pub seed: Option<u64>,
+  /// Delay between events in milliseconds.
+  #[arg(long, value_name = "MS", default_value_t = 50)]
+  pub throttle: u32,
+}
+
+#[derive(Debug, Args)]
+pub struct AppArgs {
+  #[command(flatten)]
+  pub manifest: ManifestArgs,
+  #[command(flatten)]
+  pub device: DeviceArgs,
+  /// Use the release build's application id.
+  #[arg(long)]
+  pub release: bool,
+}
+
+impl AppArgs {
+  pub fn build_profile(&self) -> BuildProfile {
+    if self.release {
+      BuildProfile::Release
+    } else {
+      BuildProfile::Debug
+    }
+  }
+}
+
+#[derive(Debug, Args)]
+pub struct GenerateArgs {
+  #[command(flatten)]
+  pub manifest: ManifestArgs,
+  /// Generate the project of a release build.
+  #[arg(long)]
+  pub release: bool,
+  /// Product flavor of the android metadata to generate. Required if the package has flavors.
+  #[arg(long, value_name = "NAME")]
+  pub flavor: Option<String>,
+  /// Apply the `[package.metadata.android.profile.<NAME>]` overrides instead of the ones of the
+  /// build profile, `debug` or `release`.
+  #[arg(long, value_name = "NAME")]
+  pub metadata_profile: Option<String>,
+  /// Directory of the android project, instead of `target/android-project-<profile>`.
+  #[arg(long, value_name = "PATH")]
+  pub project_dir: Option<PathBuf>,
+  /// Don't write the project, print a unified diff of the files generating would create or change
+  /// in it instead.
+  #[arg(long)]
+  pub diff: bool,
+  /// Diff against the unchanged project template instead of the current android project.
+  #[arg(long, requires = "diff")]
+  pub against_template: bool,
+}
+
+impl GenerateArgs {
+  /// The build of the library API with these options.
+  pub fn to_android_build(&self, manifest_path: &Path) -> AndroidBuild {
+    let mut build = AndroidBuild::new(manifest_path).profile(self.build_profile());
+    if let Some(flavor) = &self.flavor {
+      build = build.flavor(flavor);
+    }
+    if let Some(metadata_profile) = &self.metadata_profile {
+      build = build.metadata_profile(metadata_profile);
+    }
+    if let Some(project_dir) = &self.project_dir {
+      build = build.project_dir(project_dir);
+    }
+    build
+  }
+
+  pub fn build_profile(&self) -> BuildProfile {
+    if self.release {
+      BuildProfile::Release
+    } else {
+      BuildProfile::Debug
+    }
+  }
+}
+
+#[derive(Debug, Args)]
+pub struct WhereArgs {
+  #[command(flatten)]
+  pub manifest: ManifestArgs,
+  /// Print the paths of the last release build.
+  #[arg(long)]
+  pub release: bool,
+  /// Print the path of the APK, the default.
+  #[arg(long, conflicts_with_all = ["project", "symbols"])]
+  pub apk: bool,
+  /// Print the path of the generated android project.
+  #[arg(long, conflicts_with = "symbols")]
+  pub project: bool,
+  /// Print the path of the unstripped native libraries, one dir per abi.
+  #[arg(long)]
+  pub symbols: bool,
+}
+
+impl WhereArgs {
+  pub fn artifact(&self) -> BuildArtifact {
+    if self.project {
+      BuildArtifact::Project
+    } else if self.symbols {
+      BuildArtifact::Symbols
+    } else {
+      BuildArtifact::Apk
+    }
+  }
+
+  pub fn build_profile(&self) -> BuildProfile {
+    if self.release {
+      BuildProfile::Release
+    } else {
+      BuildProfile::Debug
+    }
+  }
+}
+
+#[derive(Debug, Args)]
+pub struct PullDataArgs {
+  #[command(flatten)]
+  pub app: AppArgs,
+  /// Directory to copy the app's files to.
+  pub dest: PathBuf,
+}
+
+#[derive(Debug, Args)]
+pub struct PushDataArgs {
+  #[command(flatten)]
+  pub app: AppArgs,
+  /// Directory whose content is copied into the app's files dir.
+  pub src: PathBuf,
+}
+
+#[derive(Debug, Args)]
+pub struct CrashReportArgs {
+  #[command(flatten)]
+  pub app: AppArgs,
+  /// Directory to write the report to. Defaults to target/crash-report.
+  #[arg(long, value_name = "DIR")]
+  pub out: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+pub struct DevicesArgs {
+  #[command(flatten)]
+  pub manifest: ManifestArgs,
+  /// Pin the device to use if neither --device nor ANDROID_SERIAL selects one. Saved in the
+  /// local config of the package, .cargo-sdl-apk/config.toml.
+  #[arg(long, value_name = "SERIAL", conflicts_with = "clear_default")]
+  pub set_default: Option<String>,
+  /// Unpin the default device.
+  #[arg(long)]
+  pub clear_default: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct SetupArgs {
+  /// The Android sdk, instead of asking for ANDROID_HOME.
+  #[arg(long, value_name = "DIR")]
+  pub android_home: Option<PathBuf>,
+  /// The NDK, instead of asking for ANDROID_NDK_HOME.
+  #[arg(long, value_name = "DIR")]
+  pub ndk: Option<PathBuf>,
+  /// The JDK, instead of asking for JAVA_HOME.
+  #[arg(long, value_name = "DIR")]
+  pub java_home: Option<PathBuf>,
+  /// The SDL source checkout, instead of asking for SDL.
+  #[arg(long, value_name = "DIR")]
+  pub sdl: Option<PathBuf>,
+  /// Download the source of the SDL version and use it as SDL, like `sdl --fetch`.
+  #[arg(long, value_name = "VERSION", conflicts_with = "sdl")]
+  pub fetch_sdl: Option<SdlVersion>,
+  /// SHA-256 of the source zip of --fetch-sdl, for a version whose checksum isn't pinned.
+  #[arg(long, value_name = "HEX", requires = "fetch_sdl")]
+  pub sdl_sha256: Option<String>,
+  /// Don't ask, take the given and detected dirs and fail if a required one is missing.
+  #[arg(long)]
+  pub non_interactive: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct DoctorArgs {
+  #[command(flatten)]
+  pub manifest: ManifestArgs,
+  /// Also check that everything needed for a build with --offline is available locally.
+  #[arg(long)]
+  pub offline: bool,
+}
+
+/// Removes the extra "sdl-apk" argument cargo inserts when invoked as `cargo sdl-apk`, so both
+/// invocation forms are handled the same.
+pub fn normalize_args<I, T>(args: I) -> Vec<OsString>
+where
+  I: IntoIterator<Item = T>,
+  T: Into<OsString>,
+{
+  let mut args: Vec<OsString> = args.into_iter().map(|a| a.into()).collect();
+  if args.len() > 1 && args[1] == "sdl-apk" {
+    args.remove(1);
+  }
+  args
+}
+
+pub fn parse_cli_from<I, T>(args: I) -> Result<Cli, clap::Error>
+where
+  I: IntoIterator<Item = T>,
+  T: Into<OsString>,
+{
+  Cli::try_parse_from(normalize_args(args))
+}
+
+#[cfg(test)]
+mod test {
+  use std::path::PathBuf;
+
+  use clap::error::ErrorKind;
+  use clap::CommandFactory;
+
+  use crate::artifacts::Artifact;
+  use crate::cli::{parse_cli_from, CacheCommand, Cli, SdlApkCommand};
+  use crate::last_build::BuildArtifact;
+  use crate::preflight::SdlVersion;
+  use crate::BuildProfile;
+
+  #[test]
+  fn verify_cli() {
+    Cli::command().debug_assert();
+  }
+
+  #[test]
+  fn cargo_subcommand_invocation() {
+    for argv in [
+      vec!["cargo-sdl-apk", "sdl-apk", "build", "--release"],
+      vec!["cargo-sdl-apk", "build", "--release"],
+    ] {
+      match parse_cli_from(argv).unwrap().command {
+        SdlApkCommand::Build(args) => assert!(args.release),
+        c => panic!("unexpected command {:?}", c),
+      }
+    }
+  }
+
+  #[test]
+  fn sdl_apk_only() {
+    let error = parse_cli_from(["cargo-sdl-apk", "sdl-apk"]).unwrap_err();
+    assert_eq!(
+      error.kind(),
+      ErrorKind::DisplayHelpOnMissingArgumentOrSubcommand
+    );
+  }
+
+  #[test]
+  fn unknown_subcommand() {
+    let error = parse_cli_from(["cargo-sdl-apk", "sdl-apk", "frobnicate"]).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::InvalidSubcommand);
+  }
+
+  #[test]
+  fn check_extra_args() {
+    let argv = [
+      "cargo-sdl-apk",
+      "clippy",
+      "--target",
+      "x86_64-linux-android",
+      "--",
+      "--all-targets",
+      "--",
+      "-D",
+      "warnings",
+    ];
+    match parse_cli_from(argv).unwrap().command {
+      SdlApkCommand::Clippy(args) => {
+        assert_eq!(args.target, "x86_64-linux-android");
+        assert_eq!(
+          args.extra_args,
+          vec!["--all-targets", "--", "-D", "warnings"]
+        );
+      }
+      c => panic!("unexpected command {:?}", c),
+    }
+  }
+
+  #[test]
+  fn signing_args() {
+    match parse_cli_from([
+      "cargo-sdl-apk",
+      "sign",
+      "--ks",
+      "key.jks",
+      "--ks-pass",
+      "pass:x",
+    ])
+    .unwrap()
+    .command
+    {
+      SdlApkCommand::Sign(args) => {
+        assert_eq!(args.signing.ks_file.as_deref(), Some("key.jks"));
+        assert_eq!(args.signing.ks_pass.as_deref(), Some("pass:x"));
+      }
+      c => panic!("unexpected command {:?}", c),
+    }
+  }
+
+  #[test]
+  fn warning_args() {
+    let cli = parse_cli_from([
+      "cargo-sdl-apk",
+      "build",
+      "--deny-warnings",
+      "--allow",
+      "default-icon",
+      "--allow",
+      "multidex",
+    ])
+    .unwrap();
+    assert_eq!(cli.explain, None);
+    match cli.command {
+      SdlApkCommand::Build(args) => {
+        assert!(args.deny_warnings);
+        assert_eq!(args.allow, vec!["default-icon", "multidex"]);
+      }
+      c => panic!("unexpected command {:?}", c),
+    }
+    let cli = parse_cli_from(["cargo-sdl-apk", "build", "--explain", "default-icon"]).unwrap();
+    assert_eq!(cli.explain.as_deref(), Some("default-icon"));
+  }
+
+  #[test]
+  fn launch_args() {
+    match parse_cli_from([
+      "cargo-sdl-apk",
+      "run",
+      "--extra",
+      "int:level=3",
+      "--extra",
+      "name=it's me",
+      "--launch-profile",
+      "boss",
+    ])
+    .unwrap()
+    .command
+    {
+      SdlApkCommand::Run(args) => {
+        assert_eq!(
+          args.extra,
+          vec![
+            "int:level=3".parse().unwrap(),
+            "name=it's me".parse().unwrap()
+          ]
+        );
+        assert_eq!(args.launch_profile.as_deref(), Some("boss"));
+      }
+      c => panic!("unexpected command {:?}", c),
+    }
+    assert!(parse_cli_from(["cargo-sdl-apk", "run", "--extra", "int:level=x"]).is_err());
+    assert!(parse_cli_from([
+      "cargo-sdl-apk",
+      "run",
+      "--launch-profile",
+      "boss",
+      "--no-launch-profile"
+    ])
+    .is_err());
+  }
+
+  #[test]
+  fn artifacts_arg() {
+    match parse_cli_from([
+      "cargo-sdl-apk",
+      "build",
+      "--release",
+      "--artifacts",
+      "apk,aab",
+    ])
+    .unwrap()
+    .command
+    {
+      SdlApkCommand::Build(args) => assert_eq!(args.artifacts, vec![Artifact::Apk, Artifact::Aab]),
+      c => panic!("unexpected command {:?}", c),
+    }
+    assert!(parse_cli_from(["cargo-sdl-apk", "build", "--artifacts", "apks"]).is_err());
+  }
+
+  #[test]
+  fn v4_signing_flag() {
+    let v4_signing =
+      |args: &[&str]| match parse_cli_from(["cargo-sdl-apk", "build"].iter().chain(args))
+        .unwrap()
+        .command
+      {
+        SdlApkCommand::Build(args) => args.signing.v4_signing,
+        c => panic!("unexpected command {:?}", c),
+      };
+    assert_eq!(v4_signing(&[]), None);
+    assert_eq!(v4_signing(&["--v4-signing"]), Some(true));
+    assert_eq!(v4_signing(&["--v4-signing=false"]), Some(false));
+  }
+
+  #[test]
+  fn test_launch_args() {
+    match parse_cli_from(["cargo-sdl-apk", "test-launch", "--screenshot", "launch.png"])
+      .unwrap()
+      .command
+    {
+      SdlApkCommand::TestLaunch(args) => {
+        assert_eq!(args.timeout, 60);
+        assert_eq!(args.screenshot.unwrap(), PathBuf::from("launch.png"));
+      }
+      c => panic!("unexpected command {:?}", c),
+    }
+  }
+
+  #[test]
+  fn network_args() {
+    match parse_cli_from(["cargo-sdl-apk", "build", "--locked", "--offline"])
+      .unwrap()
+      .command
+    {
+      SdlApkCommand::Build(args) => {
+        assert_eq!(args.network.cargo_args(), vec!["--locked", "--offline"]);
+      }
+      c => panic!("unexpected command {:?}", c),
+    }
+  }
+
+  #[test]
+  fn cache_commands() {
+    match parse_cli_from(["cargo-sdl-apk", "sdl-apk", "cache", "clean"])
+      .unwrap()
+      .command
+    {
+      SdlApkCommand::Cache {
+        command: CacheCommand::Clean,
+      } => {}
+      c => panic!("unexpected command {:?}", c),
+    }
+    assert!(parse_cli_from(["cargo-sdl-apk", "cache"]).is_err());
+  }
+
+  #[test]
+  fn sdl_fetch_args() {
+    match parse_cli_from(["cargo-sdl-apk", "sdl", "--fetch", "2.30.2"])
+      .unwrap()
+      .command
+    {
+      SdlApkCommand::Sdl(args) => {
+        assert!(!args.list);
+        assert_eq!(args.fetch, Some(SdlVersion(2, 30, 2)));
+      }
+      c => panic!("unexpected command {:?}", c),
+    }
+    assert!(parse_cli_from(["cargo-sdl-apk", "sdl", "--fetch", "2.30"]).is_err());
+    assert!(parse_cli_from(["cargo-sdl-apk", "sdl", "--list", "--fetch", "2.30.2"]).is_err());
+
+    let setup = [
+      "cargo-sdl-apk",
+      "setup",
+      "--non-interactive",
+      "--fetch-sdl",
+      "2.30.3",
+    ];
+    match parse_cli_from(setup.iter().chain(&["--sdl-sha256", "ab"]))
+      .unwrap()
+      .command
+    {
+      SdlApkCommand::Setup(args) => {
+        assert_eq!(args.fetch_sdl, Some(SdlVersion(2, 30, 3)));
+        assert_eq!(args.sdl_sha256.as_deref(), Some("ab"));
+      }
+      c => panic!("unexpected command {:?}", c),
+    }
+    assert!(parse_cli_from(setup.iter().chain(&["--sdl", "SDL"])).is_err());
+  }
+
+  #[test]
+  fn device_args() {
+    match parse_cli_from(["cargo-sdl-apk", "run", "--device", "emulator-5554"])
+      .unwrap()
+      .command
+    {
+      SdlApkCommand::Run(args) => {
+        assert_eq!(args.device.device.as_deref(), Some("emulator-5554"))
+      }
+      c => panic!("unexpected command {:?}", c),
+    }
+    match parse_cli_from(["cargo-sdl-apk", "pull-data", "--device", "R58M", "out"])
+      .unwrap()
+      .command
+    {
+      SdlApkCommand::PullData(args) => assert_eq!(args.app.device.device.as_deref(), Some("R58M")),
+      c => panic!("unexpected command {:?}", c),
+    }
+    assert!(parse_cli_from([
+      "cargo-sdl-apk",
+      "devices",
+      "--set-default",
+      "R58M",
+      "--clear-default"
+    ])
+    .is_err());
+  }
+
+  #[test]
+  fn where_args() {
+    match parse_cli_from(["cargo-sdl-apk", "where", "--symbols", "--release"])
+      .unwrap()
+      .command
+    {
+      SdlApkCommand::Where(args) => {
+        assert_eq!(args.artifact(), BuildArtifact::Symbols);
+        assert_eq!(args.build_profile(), BuildProfile::Release);
+      }
+      c => panic!("unexpected command {:?}", c),
+    }
+    assert!(parse_cli_from(["cargo-sdl-apk", "where", "--apk", "--project"]).is_err());
+  }
+
+  #[test]
+  fn profile_args() {
+    match parse_cli_from(["cargo-sdl-apk", "build", "--profile", "dist"])
+      .unwrap()
+      .command
+    {
+      SdlApkCommand::Build(args) => assert_eq!(args.profile.as_deref(), Some("dist")),
+      c => panic!("unexpected command {:?}", c),
+    }
+    assert!(parse_cli_from(["cargo-sdl-apk", "build", "--release", "--profile", "dev"]).is_err());
+  }
+
+  #[test]
+  fn ci_args() {
+    let cli = parse_cli_from(["cargo-sdl-apk", "sdl-apk", "build", "--ci"]).unwrap();
+    assert!(cli.ci && !cli.no_ci);
+    let cli = parse_cli_from(["cargo-sdl-apk", "--no-ci", "doctor"]).unwrap();
+    assert!(!cli.ci && cli.no_ci);
+    assert!(parse_cli_from(["cargo-sdl-apk", "build", "--ci", "--no-ci"]).is_err());
+  }
+}
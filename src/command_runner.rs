@@ -0,0 +1,564 @@
+use std::ffi::OsStr;
+use std::fs::{create_dir_all, rename, File};
+use std::io::{stderr, stdout, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{ChildStdin, Command, ExitStatus, Output, Stdio};
+use std::sync::{Arc, Mutex, Once};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use lazy_static::lazy_static;
+
+use crate::error::*;
+
+/// Location of the build log, relative to the manifest dir.
+const BUILD_LOG_PATH: &str = "target/cargo-sdl-apk.log";
+
+lazy_static! {
+  static ref BUILD_LOG: Mutex<Option<BuildLog>> = Mutex::new(None);
+}
+
+static PANIC_HOOK: Once = Once::new();
+
+/// Log file receiving the full invocation and output of every spawned command, regardless of what
+/// is shown on the console.
+#[derive(Clone)]
+pub struct BuildLog {
+  path: PathBuf,
+  file: Arc<Mutex<File>>,
+}
+
+impl BuildLog {
+  /// Creates a new log at the given path. An existing log is kept as `<path>.1`.
+  pub fn create(path: &Path) -> Self {
+    if let Some(dir) = path.parent() {
+      create_dir_all(dir).unwrap_or_else(|_| panic!("Unable to create directory {:?}", dir));
+    }
+    if path.exists() {
+      let mut rotated = path.as_os_str().to_owned();
+      rotated.push(".1");
+      rename(path, &rotated).unwrap_or_else(|_| panic!("Unable to rotate build log {:?}", path));
+    }
+
+    let file = File::create(path).unwrap_or_else(|_| panic!("Unable to create log {:?}", path));
+    Self {
+      path: path.to_path_buf(),
+      file: Arc::new(Mutex::new(file)),
+    }
+  }
+
+  pub fn path(&self) -> &Path {
+    &self.path
+  }
+
+  fn write(&self, data: &[u8]) {
+    //logging must never fail the build
+    let _ = self.file.lock().unwrap().write_all(data);
+  }
+
+  fn write_line(&self, line: &str) {
+    self.write(format!("{}\n", line).as_bytes());
+  }
+}
+
+/// Starts the build log in the target dir of the given manifest dir. Panics from here on point to
+/// the log, as the console output alone is often not enough to tell what went wrong.
+pub fn init_build_log(manifest_dir: &Path) {
+  let log = BuildLog::create(&manifest_dir.join(BUILD_LOG_PATH));
+  *BUILD_LOG.lock().unwrap() = Some(log);
+
+  //builds through the library may start several logs, the hook is only chained once
+  PANIC_HOOK.call_once(|| {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+      default_hook(info);
+      let log_path = BUILD_LOG
+        .try_lock()
+        .ok()
+        .and_then(|log| log.as_ref().map(|log| log.path().to_path_buf()));
+      if let Some(log_path) = log_path {
+        eprintln!("The full build log is at {}", log_path.display());
+      }
+    }));
+  });
+}
+
+fn current_build_log() -> Option<BuildLog> {
+  BUILD_LOG.lock().unwrap().clone()
+}
+
+/// Formats a point in time as UTC, e.g. `2023-04-01 12:00:00 UTC`.
+pub fn format_timestamp(time: SystemTime) -> String {
+  let secs = time
+    .duration_since(UNIX_EPOCH)
+    .map(|d| d.as_secs())
+    .unwrap_or(0);
+  let (days, secs_of_day) = (secs / 86400, secs % 86400);
+
+  //civil date from days since the epoch, see http://howardhinnant.github.io/date_algorithms.html
+  let z = days as i64 + 719468;
+  let era = z.div_euclid(146097);
+  let doe = z.rem_euclid(146097);
+  let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+  let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+  let mp = (5 * doy + 2) / 153;
+  let day = doy - (153 * mp + 2) / 5 + 1;
+  let month = if mp < 10 { mp + 3 } else { mp - 9 };
+  let year = yoe + era * 400 + i64::from(month <= 2);
+
+  format!(
+    "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+    year,
+    month,
+    day,
+    secs_of_day / 3600,
+    secs_of_day / 60 % 60,
+    secs_of_day % 60
+  )
+}
+
+/// Renders a command like it could be typed into a shell, including the working directory and the
+/// environment variables added to it.
+pub fn describe_command(command: &Command) -> String {
+  let mut description = String::new();
+  if let Some(dir) = command.get_current_dir() {
+    description.push_str(&format!("cd {} && ", dir.display()));
+  }
+  for (key, value) in command.get_envs() {
+    match value {
+      Some(value) => description.push_str(&format!(
+        "{}={} ",
+        key.to_string_lossy(),
+        value.to_string_lossy()
+      )),
+      None => description.push_str(&format!("-u {} ", key.to_string_lossy())),
+    }
+  }
+  description.push_str(&command.get_program().to_string_lossy());
+  for arg in command.get_args() {
+    description.push(' ');
+    description.push_str(&redact_password_property(&arg.to_string_lossy()));
+  }
+  description
+}
+
+/// Hides the value of a gradle property holding a password, like `-PkeyPassword=secret`, which is
+/// passed on the command line but must not end up in the build log.
+fn redact_password_property(arg: &str) -> String {
+  match arg.strip_prefix("-P").and_then(|p| p.split_once('=')) {
+    Some((name, _)) if name.ends_with("Password") => format!("-P{}=***", name),
+    _ => arg.to_string(),
+  }
+}
+
+type StdinFeeder = Box<dyn FnOnce(ChildStdin) + Send>;
+
+type LineHandler = Box<dyn FnMut(&str) + Send>;
+
+/// Passes the complete lines of `pending` to the handler, keeping an unfinished last line.
+fn handle_lines(pending: &mut Vec<u8>, on_line: &mut LineHandler) {
+  while let Some(end) = pending.iter().position(|b| *b == b'\n') {
+    let line: Vec<u8> = pending.drain(..=end).collect();
+    on_line(String::from_utf8_lossy(&line).trim_end_matches(['\n', '\r']));
+  }
+}
+
+/// Copies the output of a child process into the log and, if requested, to the console, line by
+/// line to a handler and into a buffer.
+fn forward_output<R: Read + Send + 'static>(
+  mut source: R,
+  log: Option<BuildLog>,
+  echo: Option<fn() -> Box<dyn Write>>,
+  mut on_line: Option<LineHandler>,
+  capture: bool,
+) -> thread::JoinHandle<Vec<u8>> {
+  thread::spawn(move || {
+    let mut captured = vec![];
+    let mut pending = vec![];
+    let mut buf = [0u8; 8192];
+    while let Ok(n) = source.read(&mut buf) {
+      if n == 0 {
+        break;
+      }
+      if let Some(log) = &log {
+        log.write(&buf[..n]);
+      }
+      if let Some(echo) = echo {
+        let mut out = echo();
+        let _ = out.write_all(&buf[..n]);
+        let _ = out.flush();
+      }
+      if let Some(on_line) = &mut on_line {
+        pending.extend_from_slice(&buf[..n]);
+        handle_lines(&mut pending, on_line);
+      }
+      if capture {
+        captured.extend_from_slice(&buf[..n]);
+      }
+    }
+    if let Some(on_line) = &mut on_line {
+      if !pending.is_empty() {
+        pending.push(b'\n');
+        handle_lines(&mut pending, on_line);
+      }
+    }
+    captured
+  })
+}
+
+fn run_with_log(
+  command: &mut Command,
+  log: Option<BuildLog>,
+  echo: bool,
+  capture: bool,
+  feed_stdin: Option<StdinFeeder>,
+  on_stdout_line: Option<LineHandler>,
+) -> std::io::Result<Output> {
+  let description = describe_command(command);
+  if let Some(log) = &log {
+    log.write_line(&format!(
+      "==> [{}] {}",
+      format_timestamp(SystemTime::now()),
+      description
+    ));
+  }
+
+  if feed_stdin.is_some() {
+    command.stdin(Stdio::piped());
+  }
+  let start = Instant::now();
+  let mut child = command
+    .stdout(Stdio::piped())
+    .stderr(Stdio::piped())
+    .spawn()
+    .map_err(|e| {
+      if let Some(log) = &log {
+        log.write_line(&format!("<== failed to start: {}", e));
+      }
+      e
+    })?;
+
+  if let Some(feed_stdin) = feed_stdin {
+    let stdin = child.stdin.take().unwrap();
+    thread::spawn(move || feed_stdin(stdin));
+  }
+
+  let stdout_handle = forward_output(
+    child.stdout.take().unwrap(),
+    log.clone(),
+    if echo {
+      Some(|| Box::new(stdout()))
+    } else {
+      None
+    },
+    on_stdout_line,
+    capture,
+  );
+  let stderr_handle = forward_output(
+    child.stderr.take().unwrap(),
+    log.clone(),
+    if echo {
+      Some(|| Box::new(stderr()))
+    } else {
+      None
+    },
+    None,
+    capture,
+  );
+
+  let status = child.wait().expect("Failed to wait for command");
+  let stdout = stdout_handle.join().unwrap_or_default();
+  let stderr = stderr_handle.join().unwrap_or_default();
+
+  if let Some(log) = &log {
+    log.write_line(&format!(
+      "<== {} after {:.2}s",
+      status,
+      start.elapsed().as_secs_f64()
+    ));
+  }
+
+  Ok(Output {
+    status,
+    stdout,
+    stderr,
+  })
+}
+
+fn spawn_failed(command: &Command, e: std::io::Error) -> ! {
+  panic!(
+    "Failed to execute command: {:?}: {}",
+    command.get_program(),
+    e
+  )
+}
+
+/// Like `run_command_output`, but each line of stdout is passed to `on_line` while the command
+/// runs, e.g. to show its progress.
+pub fn run_command_with_line_handler<F>(command: &mut Command, on_line: F) -> Output
+where
+  F: FnMut(&str) + Send + 'static,
+{
+  run_with_log(
+    command,
+    current_build_log(),
+    false,
+    true,
+    None,
+    Some(Box::new(on_line)),
+  )
+  .unwrap_or_else(|e| spawn_failed(command, e))
+}
+
+/// Runs the command with its output shown on the console and written to the build log.
+pub fn run_command(command: &mut Command) -> ExitStatus {
+  run_with_log(command, current_build_log(), true, false, None, None)
+    .unwrap_or_else(|e| spawn_failed(command, e))
+    .status
+}
+
+/// Like `run_command`, additionally returning the output for inspection.
+pub fn run_command_captured(command: &mut Command) -> Output {
+  run_with_log(command, current_build_log(), true, true, None, None)
+    .unwrap_or_else(|e| spawn_failed(command, e))
+}
+
+/// Like `run_command`, with stdin of the command fed by the given function.
+pub fn run_command_with_stdin<F>(command: &mut Command, feed_stdin: F) -> ExitStatus
+where
+  F: FnOnce(ChildStdin) + Send + 'static,
+{
+  run_with_log(
+    command,
+    current_build_log(),
+    true,
+    false,
+    Some(Box::new(feed_stdin)),
+    None,
+  )
+  .unwrap_or_else(|e| spawn_failed(command, e))
+  .status
+}
+
+/// Runs the command and returns its output instead of showing it. The output is still logged.
+pub fn run_command_output(command: &mut Command) -> std::io::Result<Output> {
+  run_with_log(command, current_build_log(), false, true, None, None)
+}
+
+/// Like `run_command_output`, but kills the command if it doesn't finish within the timeout, in
+/// which case `None` is returned.
+pub fn run_command_output_with_timeout(
+  command: &mut Command,
+  timeout: Duration,
+) -> std::io::Result<Option<Output>> {
+  let log = current_build_log();
+  if let Some(log) = &log {
+    log.write_line(&format!(
+      "==> [{}] {}",
+      format_timestamp(SystemTime::now()),
+      describe_command(command)
+    ));
+  }
+
+  let start = Instant::now();
+  let mut child = command
+    .stdin(Stdio::null())
+    .stdout(Stdio::piped())
+    .stderr(Stdio::piped())
+    .spawn()?;
+  let stdout_handle = forward_output(child.stdout.take().unwrap(), log.clone(), None, None, true);
+  let stderr_handle = forward_output(child.stderr.take().unwrap(), log.clone(), None, None, true);
+
+  let status = loop {
+    if let Some(status) = child.try_wait()? {
+      break status;
+    }
+    if start.elapsed() >= timeout {
+      let _ = child.kill();
+      let _ = child.wait();
+      if let Some(log) = &log {
+        log.write_line(&format!("<== killed after {}s", timeout.as_secs()));
+      }
+      //the output threads aren't joined, as processes spawned by the command may keep the pipes
+      //open
+      return Ok(None);
+    }
+    thread::sleep(Duration::from_millis(50));
+  };
+  let stdout = stdout_handle.join().unwrap_or_default();
+  let stderr = stderr_handle.join().unwrap_or_default();
+
+  if let Some(log) = &log {
+    log.write_line(&format!(
+      "<== {} after {:.2}s",
+      status,
+      start.elapsed().as_secs_f64()
+    ));
+  }
+
+  Ok(Some(Output {
+    status,
+    stdout,
+    stderr,
+  }))
+}
+
+/// Runs the command like `run_command` and exits with an error pointing to the build log if it
+/// fails.
+pub fn run_command_checked(command: &mut Command) {
+  if let Err(e) = check_command(command) {
+    exit_with_error(&e);
+  }
+}
+
+/// Runs the command like `run_command`, returning an error if it fails.
+pub fn check_command(command: &mut Command) -> Result<()> {
+  let status = run_command(command);
+  if !status.success() {
+    return Err(Error::command(command.get_program(), status));
+  }
+  Ok(())
+}
+
+/// Exits with an error about the failed command, pointing to the build log.
+pub fn exit_with_command_failure(program: &OsStr, status: ExitStatus) -> ! {
+  exit_with_error(&Error::command(program, status))
+}
+
+/// Prints the error and exits with its exit code. Failed commands point to the build log.
+pub fn exit_with_error(error: &Error) -> ! {
+  eprintln!("Error: {}.", error);
+  if let (Error::Command { .. }, Some(log)) = (error, current_build_log()) {
+    eprintln!("The full build log is at {}", log.path().display());
+  }
+  std::process::exit(error.exit_code());
+}
+
+#[cfg(test)]
+mod test {
+  use std::fs::{read_to_string, write};
+  use std::process::Command;
+  use std::sync::{Arc, Mutex};
+  use std::time::{Duration, UNIX_EPOCH};
+
+  use tempfile::tempdir;
+
+  use crate::command_runner::{
+    describe_command, format_timestamp, run_command_output_with_timeout, run_with_log, BuildLog,
+  };
+
+  #[test]
+  fn timestamps() {
+    assert_eq!(format_timestamp(UNIX_EPOCH), "1970-01-01 00:00:00 UTC");
+    assert_eq!(
+      format_timestamp(UNIX_EPOCH + Duration::from_secs(1709210096)),
+      "2024-02-29 12:34:56 UTC"
+    );
+  }
+
+  #[test]
+  fn command_description() {
+    let mut command = Command::new("gradlew");
+    command
+      .arg("assembleDebug")
+      .env("JAVA_HOME", "/opt/jdk")
+      .current_dir("/project");
+    assert_eq!(
+      describe_command(&command),
+      "cd /project && JAVA_HOME=/opt/jdk gradlew assembleDebug"
+    );
+
+    let mut command = Command::new("gradlew");
+    command.args([
+      "assembleRelease",
+      "-PcargoSdlApkStorePassword=secret",
+      "-PversionName=1.0",
+    ]);
+    assert_eq!(
+      describe_command(&command),
+      "gradlew assembleRelease -PcargoSdlApkStorePassword=*** -PversionName=1.0"
+    );
+  }
+
+  #[test]
+  #[cfg(unix)]
+  fn failing_command_output_is_logged() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("target/cargo-sdl-apk.log");
+
+    let log = BuildLog::create(&path);
+    let output = run_with_log(
+      Command::new("sh").args([
+        "-c",
+        "for i in $(seq 1 2000); do echo line $i; done; echo broken >&2; exit 3",
+      ]),
+      Some(log),
+      false,
+      true,
+      None,
+      None,
+    )
+    .unwrap();
+    assert_eq!(output.status.code(), Some(3));
+    assert_eq!(output.stderr, b"broken\n");
+
+    let content = read_to_string(&path).unwrap();
+    assert!(content.contains("sh -c for i in"));
+    assert!(content.contains("line 1\n"));
+    assert!(content.contains("line 2000\n"));
+    assert!(content.contains("broken\n"));
+    assert!(content.contains("<== exit status: 3 after"));
+  }
+
+  #[test]
+  #[cfg(unix)]
+  fn output_lines_are_handled() {
+    let lines = Arc::new(Mutex::new(vec![]));
+    let handled = lines.clone();
+    let output = run_with_log(
+      Command::new("sh").args(["-c", "printf 'one\\r\\ntwo\\n\\nlast'"]),
+      None,
+      false,
+      true,
+      None,
+      Some(Box::new(move |line: &str| {
+        handled.lock().unwrap().push(line.to_string())
+      })),
+    )
+    .unwrap();
+    assert_eq!(output.stdout, b"one\r\ntwo\n\nlast");
+    assert_eq!(*lines.lock().unwrap(), vec!["one", "two", "", "last"]);
+  }
+
+  #[test]
+  fn previous_log_is_rotated() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("cargo-sdl-apk.log");
+    write(&path, "old run").unwrap();
+
+    let log = BuildLog::create(&path);
+    assert_eq!(log.path(), path);
+    assert_eq!(
+      read_to_string(dir.path().join("cargo-sdl-apk.log.1")).unwrap(),
+      "old run"
+    );
+    assert_eq!(read_to_string(&path).unwrap(), "");
+  }
+
+  #[test]
+  #[cfg(unix)]
+  fn command_timeout() {
+    let output = run_command_output_with_timeout(
+      Command::new("sh").args(["-c", "echo done"]),
+      Duration::from_secs(10),
+    )
+    .unwrap()
+    .unwrap();
+    assert_eq!(output.stdout, b"done\n");
+
+    let output =
+      run_command_output_with_timeout(Command::new("sleep").arg("10"), Duration::from_millis(100))
+        .unwrap();
+    assert!(output.is_none());
+  }
+}
@@ -0,0 +1,365 @@
+use std::path::Path;
+use std::time::Duration;
+
+use clap::error::ErrorKind;
+use clap::CommandFactory;
+use clap_complete::generate;
+
+use crate::adb::*;
+use crate::android_build::*;
+use crate::android_project::*;
+use crate::anr::*;
+use crate::apk_report::*;
+use crate::app_data::*;
+use crate::build_bin_as_lib::*;
+use crate::cargo_check::*;
+use crate::ci::*;
+use crate::cli::*;
+use crate::command_runner::*;
+use crate::crash_report::*;
+use crate::debugger::*;
+use crate::flavors::*;
+use crate::last_build::*;
+use crate::local_config::*;
+use crate::monkey::*;
+use crate::offline::*;
+use crate::packaging::*;
+use crate::preflight::*;
+use crate::project_diff::*;
+use crate::sdk::*;
+use crate::sdl_cache::*;
+use crate::sdl_fetch::*;
+use crate::setup::*;
+use crate::smoke_test::*;
+use crate::user_config::*;
+use crate::util::*;
+use crate::warnings::*;
+use crate::BuildProfile;
+
+fn build_android(manifest_path: &Path, args: &BuildArgs) -> BuildOutput {
+  let output = args
+    .to_android_build(manifest_path)
+    .build()
+    .unwrap_or_else(|e| exit_with_error(&e));
+
+  if args.size_report || args.size_limit.is_some() {
+    let report = create_apk_report(&output.apk);
+    if args.size_report {
+      print_apk_report(&report);
+      print_prune_savings(&output.project_dir);
+    }
+    if let Some(size_limit) = args.size_limit {
+      check_apk_size_limit(&report, size_limit).unwrap_or_else(|e| exit_with_error(&e.into()));
+    }
+  }
+  if args.open {
+    reveal_in_file_manager(&output.apk);
+  }
+  output
+}
+
+fn install_android(output: &BuildOutput, force: bool) {
+  let result = if force {
+    force_reinstall(output)
+  } else {
+    install(output)
+  };
+  result.unwrap_or_else(|e| exit_with_error(&e));
+}
+
+/// Installs the last debug build, without building it first.
+fn install_debug_apk(manifest_path: &Path) {
+  let output = BuildOutput::from_last_build(manifest_path, BuildProfile::Debug)
+    .unwrap_or_else(|e| exit_with_error(&e));
+  install_android(&output, false);
+}
+
+fn test_launch(args: TestLaunchArgs) {
+  let manifest_path = args.manifest.resolve();
+  let manifest_dir = manifest_path.parent().unwrap();
+  init_build_log(manifest_dir);
+  use_device(&manifest_path, &args.device);
+
+  install_debug_apk(&manifest_path);
+
+  let timeout = Duration::from_secs(args.timeout);
+  let app_id = get_effective_app_id(&manifest_path, BuildProfile::Debug)
+    .unwrap_or_else(|e| exit_with_error(&e.into()));
+  let outcome = run_launch_test(
+    &app_id,
+    &get_launch_component(&manifest_path, BuildProfile::Debug)
+      .unwrap_or_else(|e| exit_with_error(&e.into())),
+    args.ready_marker.as_deref(),
+    timeout,
+    args.screenshot.as_deref(),
+  );
+  if outcome.is_success() {
+    println!("{}", outcome.describe(timeout));
+  } else if let LaunchOutcome::Anr(_) = outcome {
+    eprintln!("Error: {}", outcome.describe(timeout));
+    for file in report_anr(manifest_dir, &app_id) {
+      eprintln!("Saved {}", file.display());
+    }
+    std::process::exit(ANR_EXIT_CODE);
+  } else {
+    eprintln!("Error: {}", outcome.describe(timeout));
+    std::process::exit(1);
+  }
+}
+
+fn monkey(args: MonkeyArgs) {
+  let manifest_path = args.manifest.resolve();
+  init_build_log(manifest_path.parent().unwrap());
+  use_device(&manifest_path, &args.device);
+  install_debug_apk(&manifest_path);
+
+  let seed = args.seed.unwrap_or_else(random_seed);
+  if !run_monkey(&manifest_path, args.events, seed, args.throttle) {
+    std::process::exit(1);
+  }
+}
+
+/// Selects the device of the options for all following adb commands.
+fn use_device(manifest_path: &Path, args: &DeviceArgs) {
+  select_device(manifest_path, args.device.as_deref()).unwrap_or_else(|e| exit_with_error(&e));
+}
+
+fn devices(args: DevicesArgs) {
+  let manifest_dir = args.manifest.resolve().parent().unwrap().to_path_buf();
+  if let Some(serial) = &args.set_default {
+    set_local_config_value(
+      &manifest_dir,
+      DEFAULT_DEVICE_KEY,
+      Some(serial.as_str().into()),
+    )
+    .unwrap_or_else(|e| exit_with_error(&e.into()));
+    println!("Pinned {} as default device", serial);
+  } else if args.clear_default {
+    set_local_config_value(&manifest_dir, DEFAULT_DEVICE_KEY, None)
+      .unwrap_or_else(|e| exit_with_error(&e.into()));
+    println!("Unpinned the default device");
+  }
+
+  let default_device = get_local_config_string(&manifest_dir, DEFAULT_DEVICE_KEY)
+    .unwrap_or_else(|e| exit_with_error(&e.into()));
+  let devices = list_adb_devices().unwrap_or_else(|e| exit_with_error(&e));
+  if devices.is_empty() {
+    println!("No devices connected");
+  }
+  for device in &devices {
+    if default_device.as_deref() == Some(device.serial.as_str()) {
+      println!("{} [default]", device);
+    } else {
+      println!("{}", device);
+    }
+  }
+  if let Some(default_device) = default_device {
+    if !devices.iter().any(|d| d.serial == default_device) {
+      println!("Default device {} isn't connected", default_device);
+    }
+  }
+}
+
+fn check(tool: CheckTool, args: CheckArgs) {
+  let build_target = match &args.example {
+    None => BuildTarget::Bin,
+    Some(s) => BuildTarget::Example(s.clone()),
+  };
+  let manifest_path = args.manifest.resolve();
+  init_build_log(manifest_path.parent().unwrap());
+  set_network_args(args.network);
+  //like build, so an unknown triple is an error instead of failing deep in the toolchain setup
+  select_android_targets(&manifest_path, std::slice::from_ref(&args.target))
+    .unwrap_or_else(|e| exit_with_error(&e.into()));
+  check_android(
+    tool,
+    &manifest_path,
+    &args.target,
+    &build_target,
+    args.release,
+    args.features.as_deref(),
+    &args.extra_args,
+  );
+}
+
+fn generate_project(args: GenerateArgs) {
+  let manifest_path = args.manifest.resolve();
+  let build = args.to_android_build(&manifest_path);
+  if !args.diff {
+    let project_dir = build
+      .generate_project()
+      .unwrap_or_else(|e| exit_with_error(&e));
+    println!("Generated {}", project_dir.display());
+    return;
+  }
+  let baseline = if args.against_template {
+    DiffBaseline::Template
+  } else {
+    DiffBaseline::Project(resolve_android_project_dir(
+      manifest_path.parent().unwrap(),
+      args.build_profile(),
+      args.project_dir.as_deref(),
+    ))
+  };
+  let diff =
+    diff_generated_project(&manifest_path, build, baseline).unwrap_or_else(|e| exit_with_error(&e));
+  print!("{}", diff);
+}
+
+pub fn main() {
+  let cli = match parse_cli_from(std::env::args_os()) {
+    Ok(cli) => cli,
+    Err(e) if e.kind() == ErrorKind::InvalidSubcommand => {
+      eprintln!("{}", e.render());
+      println!("{}", Cli::command().render_help());
+      std::process::exit(2);
+    }
+    Err(e) => e.exit(),
+  };
+  set_ci_mode(resolve_ci_mode(
+    cli.ci,
+    cli.no_ci,
+    std::env::var(CI_ENV).ok().as_deref(),
+  ));
+  if let Some(id) = &cli.explain {
+    match explain_warning(id) {
+      Ok(explanation) => println!("{}", explanation),
+      Err(e) => exit_with_error(&e.into()),
+    }
+    return;
+  }
+  apply_user_config_env();
+  anchor_env_paths();
+  resolve_android_home_env();
+
+  match cli.command {
+    SdlApkCommand::Build(args) => {
+      build_android(&args.manifest.resolve(), &args);
+    }
+    SdlApkCommand::Install(args) => {
+      let output = build_android(&args.manifest.resolve(), &args);
+      install_android(&output, args.force_reinstall);
+    }
+    SdlApkCommand::Run(args) => {
+      let output = build_android(&args.manifest.resolve(), &args);
+      install_android(&output, args.force_reinstall);
+      run(&output).unwrap_or_else(|e| exit_with_error(&e));
+    }
+    SdlApkCommand::Sign(args) => {
+      let manifest_path = args.manifest.resolve();
+      init_build_log(manifest_path.parent().unwrap());
+      set_metadata_profile(Some(BuildProfile::Release.to_string()));
+      let flavor = get_selected_flavor(&manifest_path, args.flavor.as_deref())
+        .unwrap_or_else(|e| exit_with_error(&e.into()));
+      sign_android(
+        &resolve_android_project_dir(
+          manifest_path.parent().unwrap(),
+          BuildProfile::Release,
+          args.project_dir.as_deref(),
+        ),
+        signing_or_default_keystore(
+          manifest_path.parent().unwrap(),
+          args.signing.resolve(&manifest_path),
+        )
+        .unwrap_or_else(|e| exit_with_error(&e.into())),
+        args.signing.resolve_v4_signing(&manifest_path),
+        args.reproducible,
+        flavor.as_ref().map(|f| f.name.as_str()),
+        get_min_sdk_version(&manifest_path).unwrap_or_else(|e| exit_with_error(&e.into())),
+        &get_packaging(&manifest_path).unwrap_or_else(|e| exit_with_error(&e.into())),
+      )
+      .map(|apk_signer| println!("Signed by {}", apk_signer))
+      .unwrap_or_else(|e| exit_with_error(&e));
+    }
+    SdlApkCommand::Check(args) => check(CheckTool::Check, args),
+    SdlApkCommand::Clippy(args) => check(CheckTool::Clippy, args),
+    SdlApkCommand::Debug(args) => {
+      ensure_interactive(DEBUG_STOP_PROMPT, DEBUG_NON_INTERACTIVE_HINT)
+        .unwrap_or_else(|e| exit_with_error(&e.into()));
+      if args.build.release {
+        eprintln!("Error: Release builds are not debuggable, drop --release.");
+        std::process::exit(1);
+      }
+      let manifest_path = args.build.manifest.resolve();
+      let output = build_android(&manifest_path, &args.build);
+      install_android(&output, args.build.force_reinstall);
+      debug_android(&manifest_path, args.launch_lldb);
+    }
+    SdlApkCommand::TestLaunch(args) => test_launch(args),
+    SdlApkCommand::Monkey(args) => monkey(args),
+    SdlApkCommand::PullData(args) => {
+      let manifest_path = args.app.manifest.resolve();
+      use_device(&manifest_path, &args.app.device);
+      pull_app_data(
+        &get_effective_app_id(&manifest_path, args.app.build_profile())
+          .unwrap_or_else(|e| exit_with_error(&e.into())),
+        &args.dest,
+      );
+    }
+    SdlApkCommand::PushData(args) => {
+      let manifest_path = args.app.manifest.resolve();
+      use_device(&manifest_path, &args.app.device);
+      push_app_data(
+        &get_effective_app_id(&manifest_path, args.app.build_profile())
+          .unwrap_or_else(|e| exit_with_error(&e.into())),
+        &args.src,
+      );
+    }
+    SdlApkCommand::ClearData(args) => {
+      let manifest_path = args.manifest.resolve();
+      use_device(&manifest_path, &args.device);
+      clear_app_data(
+        &get_effective_app_id(&manifest_path, args.build_profile())
+          .unwrap_or_else(|e| exit_with_error(&e.into())),
+      );
+    }
+    SdlApkCommand::CrashReport(args) => {
+      let manifest_path = args.app.manifest.resolve();
+      let manifest_dir = manifest_path.parent().unwrap();
+      use_device(&manifest_path, &args.app.device);
+      let profile = args.app.build_profile();
+      let summary = collect_crash_report(
+        &get_effective_app_id(&manifest_path, profile)
+          .unwrap_or_else(|e| exit_with_error(&e.into())),
+        &get_last_build_symbols_dir(manifest_dir, profile),
+        &args
+          .out
+          .unwrap_or_else(|| manifest_dir.join("target/crash-report")),
+      );
+      println!("Saved {}", summary.display());
+    }
+    SdlApkCommand::Doctor(args) => {
+      if !run_doctor(&args.manifest.resolve(), args.offline) {
+        std::process::exit(1);
+      }
+    }
+    SdlApkCommand::Setup(args) => run_setup(&args).unwrap_or_else(|e| exit_with_error(&e.into())),
+    SdlApkCommand::Devices(args) => devices(args),
+    SdlApkCommand::Generate(args) => generate_project(args),
+    SdlApkCommand::Clean(args) => clean_android_projects(args.resolve().parent().unwrap()),
+    SdlApkCommand::Where(args) => {
+      let manifest_path = args.manifest.resolve();
+      let path = get_last_build_artifact(
+        manifest_path.parent().unwrap(),
+        args.build_profile(),
+        args.artifact(),
+      )
+      .unwrap_or_else(|e| exit_with_error(&e.into()));
+      println!("{}", path.display());
+    }
+    SdlApkCommand::Sdl(args) if args.list => print_sdl_versions(),
+    SdlApkCommand::Sdl(args) => run_sdl_fetch(&args).unwrap_or_else(|e| exit_with_error(&e.into())),
+    SdlApkCommand::Cache { command } => match command {
+      CacheCommand::Clean => clean_sdl_cache(),
+      CacheCommand::Info => print_sdl_cache_info(),
+    },
+    SdlApkCommand::Completions { shell } => {
+      generate(
+        shell,
+        &mut Cli::command(),
+        "cargo-sdl-apk",
+        &mut std::io::stdout(),
+      );
+    }
+  }
+}
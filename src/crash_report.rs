@@ -0,0 +1,587 @@
+use std::fs::{create_dir_all, write, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use zip::ZipArchive;
+
+use crate::adb::*;
+use crate::command_runner::*;
+use crate::rust_logcat::*;
+
+/// Where debuggerd writes the tombstones, only readable with adb on some devices.
+const TOMBSTONES_DIR: &str = "/data/tombstones";
+
+/// Directory of the tombstones inside a bugreport.
+const BUGREPORT_TOMBSTONES_DIR: &str = "FS/data/tombstones/";
+
+/// Number of the newest tombstones of the app put into the report.
+const MAX_TOMBSTONES: usize = 3;
+
+/// Seconds of logcat before and after the crash put into the report.
+const LOGCAT_SECONDS_BEFORE: u64 = 60;
+const LOGCAT_SECONDS_AFTER: u64 = 10;
+
+/// Lines of logcat put into the report if the time of the crash is unknown.
+const LOGCAT_TAIL_LINES: usize = 2000;
+
+/// Lines of the backtrace shown in the summary.
+const SUMMARY_BACKTRACE_LINES: usize = 30;
+
+lazy_static! {
+  //`Timestamp: 2023-10-01 12:00:07.481920345+0200` of a tombstone
+  static ref TOMBSTONE_TIME_REGEX: Regex =
+    Regex::new(r"^\d{4}-(\d\d)-(\d\d) (\d\d):(\d\d):(\d\d)").expect("invalid tombstone time regex");
+  //`10-01 12:00:07.482  4242  4270 F libc    : ...` of `logcat -v threadtime`
+  static ref LOGCAT_TIME_REGEX: Regex =
+    Regex::new(r"^(\d\d)-(\d\d) (\d\d):(\d\d):(\d\d)").expect("invalid logcat time regex");
+}
+
+/// How the tombstones were read from the device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TombstoneSource {
+  /// Directly from /data/tombstones.
+  Device,
+  /// From a bugreport, as adb can't read /data/tombstones without root.
+  Bugreport,
+}
+
+/// A tombstone debuggerd wrote for a native crash.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tombstone {
+  /// The file name, e.g. `tombstone_03`.
+  pub name: String,
+  pub content: String,
+}
+
+impl Tombstone {
+  fn header(&self, key: &str) -> Option<&str> {
+    self
+      .content
+      .lines()
+      .take_while(|line| !line.starts_with("backtrace:"))
+      .find_map(|line| line.strip_prefix(key))
+      .map(|value| value.trim().trim_matches('\''))
+  }
+
+  /// When the app crashed, e.g. `2023-10-01 12:00:07.481920345+0200`. Tombstones of older
+  /// Android versions don't have it.
+  pub fn timestamp(&self) -> Option<&str> {
+    self.header("Timestamp:")
+  }
+
+  /// The signal line, e.g. `signal 6 (SIGABRT), code -1 (SI_QUEUE), fault addr --------`.
+  pub fn signal(&self) -> Option<&str> {
+    self
+      .content
+      .lines()
+      .find(|line| line.starts_with("signal "))
+      .map(|line| line.trim())
+  }
+
+  pub fn abort_message(&self) -> Option<&str> {
+    self.header("Abort message:")
+  }
+
+  /// The line with the pid and the name of the crashed thread.
+  pub fn thread(&self) -> Option<&str> {
+    self
+      .content
+      .lines()
+      .find(|line| line.starts_with("pid: "))
+      .map(|line| line.trim())
+  }
+
+  /// Whether the crashed process is the app, by its command line.
+  pub fn is_of_app(&self, app_id: &str) -> bool {
+    self.header("Cmdline:") == Some(app_id)
+      || self
+        .thread()
+        .is_some_and(|thread| thread.ends_with(&format!(">>> {} <<<", app_id)))
+  }
+}
+
+/// The frames of the crashed thread in a tombstone, with the resolved lines of a symbolicated one.
+pub fn get_backtrace(tombstone: &str) -> Vec<&str> {
+  tombstone
+    .lines()
+    .skip_while(|line| !line.starts_with("backtrace:"))
+    .skip(1)
+    .take_while(|line| !line.trim().is_empty())
+    .collect()
+}
+
+/// Keeps the tombstones of the app, newest first, as far as their timestamp tells.
+pub fn select_app_tombstones(mut tombstones: Vec<Tombstone>, app_id: &str) -> Vec<Tombstone> {
+  tombstones.retain(|tombstone| tombstone.is_of_app(app_id));
+  //the timestamps have the same format, so they sort as strings, those without one last
+  tombstones.sort_by(|a, b| b.timestamp().cmp(&a.timestamp()));
+  tombstones.truncate(MAX_TOMBSTONES);
+  tombstones
+}
+
+/// The tombstones in a bugreport. The protobuf versions of newer devices are skipped, the text
+/// version is next to them.
+pub fn read_bugreport_tombstones(bugreport: &Path) -> Option<Vec<Tombstone>> {
+  let mut archive = ZipArchive::new(File::open(bugreport).ok()?).ok()?;
+  let names: Vec<String> = archive
+    .file_names()
+    .filter(|name| name.starts_with(BUGREPORT_TOMBSTONES_DIR) && !name.ends_with(".pb"))
+    .map(|name| name.to_string())
+    .collect();
+  let mut tombstones = vec![];
+  for name in names {
+    let mut content = String::new();
+    if archive
+      .by_name(&name)
+      .ok()?
+      .read_to_string(&mut content)
+      .is_ok()
+    {
+      tombstones.push(Tombstone {
+        name: name[BUGREPORT_TOMBSTONES_DIR.len()..].to_string(),
+        content,
+      });
+    }
+  }
+  Some(tombstones)
+}
+
+/// Reads the tombstones from /data/tombstones, `None` if adb isn't allowed to.
+fn read_device_tombstones() -> Option<Vec<Tombstone>> {
+  let listing = adb_shell(&["ls", TOMBSTONES_DIR])?;
+  let mut tombstones = vec![];
+  for name in listing
+    .split_whitespace()
+    .filter(|name| name.starts_with("tombstone") && !name.ends_with(".pb"))
+  {
+    let content = adb_shell(&["cat", &format!("{}/{}", TOMBSTONES_DIR, name)])?;
+    tombstones.push(Tombstone {
+      name: name.to_string(),
+      content,
+    });
+  }
+  Some(tombstones)
+}
+
+/// Gets the tombstones of the device. Without access to /data/tombstones they are taken from a
+/// bugreport saved to the dir instead, which takes a while.
+fn pull_tombstones(dir: &Path) -> Option<(Vec<Tombstone>, TombstoneSource)> {
+  if let Some(tombstones) = read_device_tombstones() {
+    return Some((tombstones, TombstoneSource::Device));
+  }
+
+  println!(
+    "No access to {}, taking a bugreport for the tombstones, this can take a few minutes...",
+    TOMBSTONES_DIR
+  );
+  let bugreport = dir.join("bugreport.zip");
+  let status = run_command_output(adb_command().args(["bugreport"]).arg(&bugreport))
+    .map(|output| output.status.success());
+  if !status.unwrap_or(false) {
+    return None;
+  }
+  read_bugreport_tombstones(&bugreport).map(|tombstones| (tombstones, TombstoneSource::Bugreport))
+}
+
+/// Seconds since the start of the year, close enough to compare times of the same days.
+fn seconds_of_year(captures: &regex::Captures) -> Option<u64> {
+  let field = |i: usize| captures.get(i)?.as_str().parse::<u64>().ok();
+  Some(((field(1)? * 31 + field(2)?) * 24 + field(3)?) * 3600 + field(4)? * 60 + field(5)?)
+}
+
+/// The lines of `logcat -v threadtime` from a minute before a crash at the tombstone timestamp
+/// until shortly after, or the end of the log if the time of the crash is unknown.
+pub fn get_logcat_window(logcat: &str, crash_timestamp: Option<&str>) -> Vec<String> {
+  let crash_time = crash_timestamp
+    .and_then(|timestamp| TOMBSTONE_TIME_REGEX.captures(timestamp))
+    .and_then(|captures| seconds_of_year(&captures));
+  let lines: Vec<&str> = logcat.lines().collect();
+  let Some(crash_time) = crash_time else {
+    return lines[lines.len().saturating_sub(LOGCAT_TAIL_LINES)..]
+      .iter()
+      .map(|line| line.to_string())
+      .collect();
+  };
+
+  let mut in_window = false;
+  lines
+    .into_iter()
+    .filter(|line| {
+      //lines without a time continue the previous one
+      if let Some(time) = LOGCAT_TIME_REGEX
+        .captures(line)
+        .and_then(|captures| seconds_of_year(&captures))
+      {
+        in_window =
+          time + LOGCAT_SECONDS_BEFORE >= crash_time && time <= crash_time + LOGCAT_SECONDS_AFTER;
+      }
+      in_window
+    })
+    .map(|line| line.to_string())
+    .collect()
+}
+
+/// The version of the installed app, from `dumpsys package`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InstalledVersion {
+  pub version_name: Option<String>,
+  pub version_code: Option<String>,
+  pub last_update_time: Option<String>,
+}
+
+pub fn parse_installed_version(dumpsys_output: &str) -> InstalledVersion {
+  let value = |key: &str| {
+    dumpsys_output.lines().find_map(|line| {
+      let start = line.find(key)? + key.len();
+      let value = &line[start..];
+      //versionCode shares its line with the sdk versions
+      let value = match key {
+        "lastUpdateTime=" => value.trim(),
+        _ => value.split_whitespace().next()?,
+      };
+      Some(value.to_string())
+    })
+  };
+  InstalledVersion {
+    version_name: value("versionName="),
+    version_code: value("versionCode="),
+    last_update_time: value("lastUpdateTime="),
+  }
+}
+
+/// The device the report is about.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DeviceInfo {
+  pub model: Option<String>,
+  pub release: Option<String>,
+  pub sdk: Option<String>,
+  pub abi: Option<String>,
+}
+
+fn get_device_info() -> DeviceInfo {
+  let prop = |name: &str| adb_shell(&["getprop", name]).filter(|value| !value.is_empty());
+  DeviceInfo {
+    model: prop("ro.product.model"),
+    release: prop("ro.build.version.release"),
+    sdk: prop("ro.build.version.sdk"),
+    abi: prop("ro.product.cpu.abi"),
+  }
+}
+
+/// The tombstone with the frames of libmain.so resolved below them, against the local symbols.
+pub fn symbolicate_tombstone(tombstone: &Tombstone, symbolizer: &mut Symbolizer) -> String {
+  let mut lines = vec![];
+  for line in tombstone.content.lines() {
+    lines.push(line.to_string());
+    if let Some(frame) = parse_native_frame(line) {
+      lines.extend(symbolizer.resolve(&frame));
+    }
+  }
+  lines.join("\n") + "\n"
+}
+
+/// Everything collected for the report, rendered to summary.md.
+#[derive(Debug, Clone)]
+pub struct CrashReport {
+  pub app_id: String,
+  pub device: DeviceInfo,
+  pub version: InstalledVersion,
+  pub source: Option<TombstoneSource>,
+  /// The tombstones of the app with their symbolicated versions, newest first.
+  pub tombstones: Vec<(Tombstone, String)>,
+  /// Lines in logcat.txt, `None` if logcat couldn't be read.
+  pub logcat_lines: Option<usize>,
+}
+
+fn or_unknown(value: &Option<String>) -> &str {
+  value.as_deref().unwrap_or("unknown")
+}
+
+impl CrashReport {
+  pub fn render_summary(&self) -> String {
+    let mut summary = format!("# Crash report for {}\n\n", self.app_id);
+    summary.push_str(&format!(
+      "- Device: {} (Android {}, SDK {}, {})\n",
+      or_unknown(&self.device.model),
+      or_unknown(&self.device.release),
+      or_unknown(&self.device.sdk),
+      or_unknown(&self.device.abi)
+    ));
+    summary.push_str(&format!(
+      "- Installed version: {} ({}), updated {}\n",
+      or_unknown(&self.version.version_name),
+      or_unknown(&self.version.version_code),
+      or_unknown(&self.version.last_update_time)
+    ));
+    summary.push_str(&format!(
+      "- Tombstones: {}\n",
+      match self.source {
+        Some(TombstoneSource::Device) => format!("read from {}", TOMBSTONES_DIR),
+        Some(TombstoneSource::Bugreport) => "extracted from bugreport.zip".to_string(),
+        None => "not accessible".to_string(),
+      }
+    ));
+    summary.push_str(&match self.logcat_lines {
+      Some(lines) => format!("- Logcat: {} lines around the crash in logcat.txt\n", lines),
+      None => "- Logcat: not readable\n".to_string(),
+    });
+
+    if self.tombstones.is_empty() {
+      summary.push_str(&format!("\nNo tombstone of {} found.\n", self.app_id));
+    }
+    for (tombstone, symbolicated) in &self.tombstones {
+      summary.push_str(&format!("\n## {}\n\n", tombstone.name));
+      for (label, value) in [
+        ("Time", tombstone.timestamp()),
+        ("Thread", tombstone.thread()),
+        ("Signal", tombstone.signal()),
+        ("Abort message", tombstone.abort_message()),
+      ] {
+        if let Some(value) = value {
+          summary.push_str(&format!("- {}: {}\n", label, value));
+        }
+      }
+      summary.push_str(&format!(
+        "- Files: [{0}.txt]({0}.txt), [{0}-symbolicated.txt]({0}-symbolicated.txt)\n",
+        tombstone.name
+      ));
+
+      let backtrace = get_backtrace(symbolicated);
+      if !backtrace.is_empty() {
+        summary.push_str("\n```text\n");
+        summary.push_str(&backtrace[..backtrace.len().min(SUMMARY_BACKTRACE_LINES)].join("\n"));
+        summary.push_str("\n```\n");
+      }
+    }
+    summary
+  }
+}
+
+/// Collects the newest tombstones of the app with their frames resolved against the local symbols,
+/// the logcat around the crash and the installed version into the dir, with a summary.md. Returns
+/// the summary.
+pub fn collect_crash_report(app_id: &str, symbols_dir: &Path, dir: &Path) -> PathBuf {
+  create_dir_all(dir).unwrap_or_else(|_| panic!("Unable to create directory {:?}", dir));
+  let write_file = |name: &str, content: &str| {
+    let path = dir.join(name);
+    write(&path, content).unwrap_or_else(|_| panic!("Unable to write {:?}", path));
+  };
+
+  let (tombstones, source) = match pull_tombstones(dir) {
+    Some((tombstones, source)) => (select_app_tombstones(tombstones, app_id), Some(source)),
+    None => {
+      eprintln!("WARNING: Unable to get the tombstones from the device.");
+      (vec![], None)
+    }
+  };
+  let mut symbolizer = Symbolizer::new(symbols_dir);
+  let tombstones: Vec<(Tombstone, String)> = tombstones
+    .into_iter()
+    .map(|tombstone| {
+      let symbolicated = symbolicate_tombstone(&tombstone, &mut symbolizer);
+      write_file(&format!("{}.txt", tombstone.name), &tombstone.content);
+      write_file(
+        &format!("{}-symbolicated.txt", tombstone.name),
+        &symbolicated,
+      );
+      (tombstone, symbolicated)
+    })
+    .collect();
+
+  let logcat = run_command_output(adb_command().args([
+    "logcat",
+    "-d",
+    "-v",
+    "threadtime",
+    "-b",
+    "main,system,crash",
+  ]))
+  .ok()
+  .filter(|output| output.status.success());
+  let logcat_lines = logcat.map(|output| {
+    let window = get_logcat_window(
+      &String::from_utf8_lossy(&output.stdout),
+      tombstones
+        .first()
+        .and_then(|(tombstone, _)| tombstone.timestamp()),
+    );
+    write_file("logcat.txt", &(window.join("\n") + "\n"));
+    window.len()
+  });
+
+  let version = adb_shell(&["dumpsys", "package", app_id])
+    .map(|output| parse_installed_version(&output))
+    .unwrap_or_default();
+
+  let report = CrashReport {
+    app_id: app_id.to_string(),
+    device: get_device_info(),
+    version,
+    source,
+    tombstones,
+    logcat_lines,
+  };
+  write_file("summary.md", &report.render_summary());
+  dir.join("summary.md")
+}
+
+#[cfg(test)]
+mod test {
+  use std::fs::File;
+  use std::io::Write;
+
+  use tempfile::tempdir;
+  use zip::write::FileOptions;
+  use zip::ZipWriter;
+
+  use crate::crash_report::{
+    get_backtrace, get_logcat_window, parse_installed_version, read_bugreport_tombstones,
+    select_app_tombstones, CrashReport, DeviceInfo, InstalledVersion, Tombstone, TombstoneSource,
+  };
+
+  const TOMBSTONE: &str = include_str!("../tests/fixtures/tombstones/arm64.txt");
+
+  fn tombstone(name: &str, content: &str) -> Tombstone {
+    Tombstone {
+      name: name.to_string(),
+      content: content.to_string(),
+    }
+  }
+
+  #[test]
+  fn tombstone_fields() {
+    let tombstone = tombstone("tombstone_03", TOMBSTONE);
+    assert_eq!(
+      tombstone.timestamp(),
+      Some("2023-10-01 12:00:07.481920345+0200")
+    );
+    assert_eq!(
+      tombstone.signal(),
+      Some("signal 6 (SIGABRT), code -1 (SI_QUEUE), fault addr --------")
+    );
+    assert_eq!(
+      tombstone.abort_message(),
+      Some("thread '<unnamed>' panicked at src/main.rs:42:5")
+    );
+    assert!(tombstone.is_of_app("com.example.game"));
+    assert!(!tombstone.is_of_app("com.example"));
+    assert_eq!(get_backtrace(TOMBSTONE).len(), 4);
+
+    //before Android 11 there is no Cmdline
+    let legacy = TOMBSTONE.replace("Cmdline: com.example.game\n", "");
+    assert!(self::tombstone("tombstone_00", &legacy).is_of_app("com.example.game"));
+  }
+
+  #[test]
+  fn tombstone_selection() {
+    let older = TOMBSTONE.replace("2023-10-01 12:00:07", "2023-09-30 18:00:00");
+    let other = TOMBSTONE.replace("com.example.game", "com.example.other");
+    let selected = select_app_tombstones(
+      vec![
+        tombstone("tombstone_00", &older),
+        tombstone("tombstone_01", &other),
+        tombstone("tombstone_02", TOMBSTONE),
+      ],
+      "com.example.game",
+    );
+    let names: Vec<&str> = selected.iter().map(|t| t.name.as_str()).collect();
+    assert_eq!(names, vec!["tombstone_02", "tombstone_00"]);
+  }
+
+  #[test]
+  fn bugreport_tombstones() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("bugreport.zip");
+    let mut zip = ZipWriter::new(File::create(&path).unwrap());
+    for (name, content) in [
+      ("FS/data/tombstones/tombstone_00", TOMBSTONE),
+      ("FS/data/tombstones/tombstone_00.pb", "\x08\x01"),
+      (
+        "FS/data/anr/anr_2023-10-01-12-00-00-000",
+        "----- pid 4242 -----",
+      ),
+    ] {
+      zip.start_file(name, FileOptions::default()).unwrap();
+      zip.write_all(content.as_bytes()).unwrap();
+    }
+    zip.finish().unwrap();
+
+    let tombstones = read_bugreport_tombstones(&path).unwrap();
+    assert_eq!(tombstones, vec![tombstone("tombstone_00", TOMBSTONE)]);
+  }
+
+  #[test]
+  fn logcat_window() {
+    let logcat = "10-01 11:58:00.000  4242  4242 I game    : too early
+10-01 11:59:10.000  4242  4242 I game    : loading level
+continued line
+10-01 12:00:07.482  4242  4270 F libc    : Fatal signal 6 (SIGABRT)
+10-01 12:00:30.000  1000  1000 I system  : too late
+";
+    assert_eq!(
+      get_logcat_window(logcat, Some("2023-10-01 12:00:07.481920345+0200")),
+      vec![
+        "10-01 11:59:10.000  4242  4242 I game    : loading level",
+        "continued line",
+        "10-01 12:00:07.482  4242  4270 F libc    : Fatal signal 6 (SIGABRT)",
+      ]
+    );
+    assert_eq!(get_logcat_window(logcat, None).len(), 5);
+  }
+
+  #[test]
+  fn installed_version() {
+    let dumpsys = "Packages:
+  Package [com.example.game] (4b1c2d3):
+    versionCode=12 minSdk=21 targetSdk=33
+    versionName=1.2.0
+    lastUpdateTime=2023-10-01 11:42:13
+";
+    assert_eq!(
+      parse_installed_version(dumpsys),
+      InstalledVersion {
+        version_name: Some("1.2.0".to_string()),
+        version_code: Some("12".to_string()),
+        last_update_time: Some("2023-10-01 11:42:13".to_string()),
+      }
+    );
+  }
+
+  #[test]
+  fn summary() {
+    let symbolicated = TOMBSTONE.replace(
+      "libmain.so (BuildId: 4f3a9b)\n      #02",
+      "libmain.so (BuildId: 4f3a9b)\n    game::update at src/main.rs:42\n      #02",
+    );
+    let report = CrashReport {
+      app_id: "com.example.game".to_string(),
+      device: DeviceInfo {
+        model: Some("Pixel 6".to_string()),
+        release: Some("13".to_string()),
+        sdk: Some("33".to_string()),
+        abi: Some("arm64-v8a".to_string()),
+      },
+      version: InstalledVersion::default(),
+      source: Some(TombstoneSource::Bugreport),
+      tombstones: vec![(tombstone("tombstone_03", TOMBSTONE), symbolicated)],
+      logcat_lines: Some(120),
+    };
+    let summary = report.render_summary();
+    assert!(summary.starts_with("# Crash report for com.example.game\n\n- Device: Pixel 6 (Android 13, SDK 33, arm64-v8a)\n- Installed version: unknown (unknown), updated unknown\n- Tombstones: extracted from bugreport.zip\n"));
+    assert!(summary.contains("\n## tombstone_03\n\n- Time: 2023-10-01 12:00:07.481920345+0200\n"));
+    assert!(summary.contains("- Files: [tombstone_03.txt](tombstone_03.txt), [tombstone_03-symbolicated.txt](tombstone_03-symbolicated.txt)\n"));
+    assert!(summary.contains("libmain.so (BuildId: 4f3a9b)\n    game::update at src/main.rs:42\n"));
+
+    let empty = CrashReport {
+      tombstones: vec![],
+      source: None,
+      logcat_lines: None,
+      ..report
+    };
+    assert!(empty
+      .render_summary()
+      .ends_with("- Tombstones: not accessible\n- Logcat: not readable\n\nNo tombstone of com.example.game found.\n"));
+  }
+}
@@ -0,0 +1,388 @@
+use std::fs::read_dir;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::channel;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::adb::*;
+use crate::android_project::*;
+use crate::build_bin_as_lib::*;
+use crate::ci::*;
+use crate::command_runner::*;
+use crate::last_build::*;
+use crate::ndk::*;
+use crate::targets::*;
+use crate::util::*;
+use crate::BuildProfile;
+
+/// Where lldb-server is pushed to before it is copied into the app's code_cache with run-as.
+const LLDB_SERVER_TMP_PATH: &str = "/data/local/tmp/lldb-server";
+/// How long to wait for the process of the app after starting it.
+const PROCESS_START_TIMEOUT: Duration = Duration::from_secs(10);
+const JDWP_HANDSHAKE: &[u8] = b"JDWP-Handshake";
+
+/// The architecture dir of lldb-server in the NDK for an android abi.
+pub fn get_lldb_arch(abi: &str) -> Option<&'static str> {
+  match abi {
+    "arm64-v8a" => Some("aarch64"),
+    "armeabi-v7a" => Some("arm"),
+    "x86" => Some("i386"),
+    "x86_64" => Some("x86_64"),
+    "riscv64" => Some("riscv64"),
+    _ => None,
+  }
+}
+
+/// Finds lldb-server for the architecture in the NDK. Its location contains the clang version,
+/// which differs between NDK releases, as does the lib dir name.
+pub fn find_lldb_server(ndk_home: &Path, arch: &str) -> Option<PathBuf> {
+  let toolchain = get_ndk_toolchain_dir_in(ndk_home);
+  for lib_dir in ["lib", "lib64"] {
+    let Ok(versions) = read_dir(toolchain.join(lib_dir).join("clang")) else {
+      continue;
+    };
+    let mut candidates: Vec<PathBuf> = versions
+      .filter_map(|entry| entry.ok())
+      .map(|entry| {
+        entry
+          .path()
+          .join("lib/linux")
+          .join(arch)
+          .join("lldb-server")
+      })
+      .filter(|path| path.is_file())
+      .collect();
+    candidates.sort();
+    if let Some(path) = candidates.pop() {
+      return Some(path);
+    }
+  }
+  None
+}
+
+/// The lldb shipped with the NDK, falling back to the one on the path.
+fn get_lldb_path() -> PathBuf {
+  let lldb = get_ndk_toolchain_dir().join("bin/lldb");
+  if lldb.is_file() {
+    lldb
+  } else {
+    PathBuf::from("lldb")
+  }
+}
+
+/// Quotes a path for an lldb command line if it contains whitespace or quotes.
+fn quote_lldb_path(path: &Path) -> String {
+  let path = path.display().to_string();
+  if path.contains(|c: char| c.is_whitespace() || c == '"' || c == '\\') {
+    format!("\"{}\"", path.replace('\\', "\\\\").replace('"', "\\\""))
+  } else {
+    path
+  }
+}
+
+/// The lldb commands attaching to the lldb-server forwarded to the given port.
+pub fn get_lldb_commands(symbol_dirs: &[PathBuf], port: u16) -> Vec<String> {
+  let mut commands = vec!["platform select remote-android".to_string()];
+  for dir in symbol_dirs {
+    commands.push(format!(
+      "settings append target.exec-search-paths {}",
+      quote_lldb_path(dir)
+    ));
+  }
+  commands.push(format!("gdb-remote localhost:{}", port));
+  commands
+}
+
+fn adb_checked_output(args: &[&str]) -> String {
+  let mut command = adb_command();
+  command.args(args);
+  let output = run_command_output(&mut command)
+    .unwrap_or_else(|e| panic!("Failed to execute command: adb: {}", e));
+  if !output.status.success() {
+    exit_with_command_failure(command.get_program(), output.status);
+  }
+  String::from_utf8_lossy(&output.stdout).trim().to_string()
+}
+
+/// Forwards a free local port to the socket on the device, returning the port.
+fn forward(remote: &str) -> u16 {
+  let port = adb_checked_output(&["forward", "tcp:0", remote]);
+  port.parse().unwrap_or_else(|_| {
+    eprintln!("Error: Unexpected output of adb forward: {:?}", port);
+    std::process::exit(1);
+  })
+}
+
+fn wait_for_pid(app_id: &str) -> u32 {
+  let start = Instant::now();
+  while start.elapsed() < PROCESS_START_TIMEOUT {
+    if let Some(pid) = adb_shell(&["pidof", app_id]).and_then(|pid| pid.parse().ok()) {
+      return pid;
+    }
+    thread::sleep(Duration::from_millis(200));
+  }
+  eprintln!(
+    "Error: {} didn't start within {}s.",
+    app_id,
+    PROCESS_START_TIMEOUT.as_secs()
+  );
+  std::process::exit(1);
+}
+
+/// Lets the app continue past "Waiting For Debugger" by connecting to its JDWP port. The
+/// handshake only completes once lldb resumes the process, so this runs in the background, keeping
+/// the connection open until the app exits.
+fn release_java_debugger(port: u16) {
+  thread::spawn(move || {
+    let Ok(mut stream) = TcpStream::connect(("localhost", port)) else {
+      return;
+    };
+    let mut reply = [0u8; JDWP_HANDSHAKE.len()];
+    if stream.write_all(JDWP_HANDSHAKE).is_err() || stream.read_exact(&mut reply).is_err() {
+      return;
+    }
+    let mut buf = [0u8; 1024];
+    while matches!(stream.read(&mut buf), Ok(n) if n > 0) {}
+  });
+}
+
+/// Everything set up on the device and the host for a debugging session, removed on drop.
+struct DebugSession<'a> {
+  app_id: &'a str,
+  lldb_server: Option<Child>,
+  ports: Vec<u16>,
+}
+
+impl Drop for DebugSession<'_> {
+  fn drop(&mut self) {
+    println!("Cleaning up debugging session...");
+    if let Some(mut lldb_server) = self.lldb_server.take() {
+      let _ = lldb_server.kill();
+      let _ = lldb_server.wait();
+    }
+    let _ = run_command_output(adb_command().args([
+      "shell",
+      "run-as",
+      self.app_id,
+      "killall",
+      "lldb-server",
+    ]));
+    for port in &self.ports {
+      let _ =
+        run_command_output(adb_command().args(["forward", "--remove", &format!("tcp:{}", port)]));
+    }
+  }
+}
+
+/// Starts the lldb-server attaching to the process on the device. It runs in its own process
+/// group, so pressing Ctrl+C in lldb doesn't terminate it.
+fn spawn_lldb_server(app_id: &str, socket: &str, pid: u32) -> Child {
+  let mut command = adb_command();
+  command
+    .args(["shell", "run-as", app_id, "./code_cache/lldb-server"])
+    .args(["gdbserver", &format!("unix-abstract://{}", socket)])
+    .args(["--attach", &pid.to_string()])
+    .stdin(Stdio::null());
+  #[cfg(unix)]
+  std::os::unix::process::CommandExt::process_group(&mut command, 0);
+  command
+    .spawn()
+    .unwrap_or_else(|e| panic!("Failed to execute command: adb: {}", e))
+}
+
+/// Asked while the debug session runs without lldb.
+pub const DEBUG_STOP_PROMPT: &str = "Press Enter to stop debugging.";
+pub const DEBUG_NON_INTERACTIVE_HINT: &str =
+  "Debug sessions need a terminal, run them without --ci or with --no-ci.";
+
+/// Waits until the user presses enter or Ctrl+C, or the lldb-server exits.
+fn wait_for_session_end(session: &mut DebugSession, interrupted: &AtomicBool) {
+  let (sender, receiver) = channel();
+  thread::spawn(move || {
+    let _ = prompt_or_fail(DEBUG_STOP_PROMPT, DEBUG_NON_INTERACTIVE_HINT);
+    let _ = sender.send(());
+  });
+
+  loop {
+    if receiver.recv_timeout(Duration::from_millis(100)).is_ok()
+      || interrupted.load(Ordering::SeqCst)
+    {
+      return;
+    }
+    let server_exited = session
+      .lldb_server
+      .as_mut()
+      .map(|server| !matches!(server.try_wait(), Ok(None)))
+      .unwrap_or(true);
+    if server_exited {
+      println!("lldb-server exited.");
+      return;
+    }
+  }
+}
+
+/// Starts the installed debug build waiting for a debugger and attaches lldb-server to it. The
+/// commands to connect lldb are printed, or lldb is started with them.
+pub fn debug_android(manifest_path: &Path, launch_lldb: bool) {
+  let manifest_dir = manifest_path.parent().unwrap();
+  let app_id = get_effective_app_id(manifest_path, BuildProfile::Debug)
+    .unwrap_or_else(|e| exit_with_error(&e.into()));
+
+  let abi = adb_shell(&["getprop", "ro.product.cpu.abi"]).unwrap_or_else(|| {
+    eprintln!("Error: Unable to read the abi of the device.");
+    std::process::exit(1);
+  });
+  let (Some(arch), Some(rust_target)) = (get_lldb_arch(&abi), get_rust_target_for_abi(&abi)) else {
+    eprintln!("Error: Debugging on {} devices is not supported.", abi);
+    std::process::exit(1);
+  };
+  let lldb_server =
+    find_lldb_server(&get_env_path("ANDROID_NDK_HOME"), arch).unwrap_or_else(|| {
+      eprintln!("Error: No lldb-server for {} found in the NDK.", arch);
+      std::process::exit(1);
+    });
+
+  let interrupted = Arc::new(AtomicBool::new(false));
+  let handler_flag = interrupted.clone();
+  if let Err(e) = ctrlc::set_handler(move || handler_flag.store(true, Ordering::SeqCst)) {
+    eprintln!("WARNING: Unable to handle Ctrl+C: {}", e);
+  }
+
+  run_command_checked(adb_command().args(["shell", "am", "force-stop", &app_id]));
+  println!("Starting {} waiting for a debugger...", app_id);
+  run_command_checked(
+    adb_command().args([
+      "shell",
+      "am",
+      "start",
+      "-D",
+      "-n",
+      &get_launch_component(manifest_path, BuildProfile::Debug)
+        .unwrap_or_else(|e| exit_with_error(&e.into())),
+    ]),
+  );
+  let pid = wait_for_pid(&app_id);
+
+  run_command_checked(
+    adb_command()
+      .args(["push"])
+      .arg(&lldb_server)
+      .arg(LLDB_SERVER_TMP_PATH),
+  );
+  run_command_checked(adb_command().args([
+    "shell",
+    "run-as",
+    &app_id,
+    "sh",
+    "-c",
+    &format!(
+      "'cp {} code_cache/lldb-server && chmod 700 code_cache/lldb-server'",
+      LLDB_SERVER_TMP_PATH
+    ),
+  ]));
+
+  let mut session = DebugSession {
+    app_id: &app_id,
+    lldb_server: None,
+    ports: vec![],
+  };
+  let socket = format!("/{}/lldb-server.sock", app_id);
+  session.lldb_server = Some(spawn_lldb_server(&app_id, &socket, pid));
+  let lldb_port = forward(&format!("localabstract:{}", socket));
+  session.ports.push(lldb_port);
+  let jdwp_port = forward(&format!("jdwp:{}", pid));
+  session.ports.push(jdwp_port);
+  release_java_debugger(jdwp_port);
+
+  let symbol_dirs = vec![
+    get_cargo_target_dir(manifest_path)
+      .join(rust_target)
+      .join("debug"),
+    get_last_build_symbols_dir(manifest_dir, BuildProfile::Debug).join(&abi),
+  ];
+  let commands = get_lldb_commands(&symbol_dirs, lldb_port);
+
+  if launch_lldb {
+    let mut lldb = Command::new(get_lldb_path());
+    for command in &commands {
+      lldb.arg("-o").arg(command);
+    }
+    if let Err(e) = lldb.status() {
+      eprintln!("Error: Unable to start lldb: {}", e);
+    }
+  } else {
+    println!(
+      "lldb-server is attached to {} (pid {}). Connect with:",
+      app_id, pid
+    );
+    println!();
+    println!("lldb");
+    for command in &commands {
+      println!("(lldb) {}", command);
+    }
+    println!();
+    println!("The app continues once lldb resumes it.");
+    wait_for_session_end(&mut session, &interrupted);
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::fs::{create_dir_all, write};
+  use std::path::PathBuf;
+
+  use tempfile::tempdir;
+
+  use crate::debugger::{find_lldb_server, get_lldb_commands};
+  use crate::targets::get_rust_target_for_abi;
+
+  #[test]
+  fn abi_targets() {
+    assert_eq!(
+      get_rust_target_for_abi("arm64-v8a"),
+      Some("aarch64-linux-android")
+    );
+    assert_eq!(get_rust_target_for_abi("mips"), None);
+  }
+
+  #[test]
+  fn lldb_server_in_ndk() {
+    let ndk = tempdir().unwrap();
+    assert_eq!(find_lldb_server(ndk.path(), "aarch64"), None);
+
+    let dir = ndk
+      .path()
+      .join("toolchains/llvm/prebuilt/linux-x86_64/lib/clang/17/lib/linux/aarch64");
+    create_dir_all(&dir).unwrap();
+    write(dir.join("lldb-server"), "").unwrap();
+    assert_eq!(
+      find_lldb_server(ndk.path(), "aarch64"),
+      Some(dir.join("lldb-server"))
+    );
+    assert_eq!(find_lldb_server(ndk.path(), "x86_64"), None);
+  }
+
+  #[test]
+  fn lldb_commands() {
+    assert_eq!(
+      get_lldb_commands(
+        &[PathBuf::from("/game/target/aarch64-linux-android/debug")],
+        41234
+      ),
+      vec![
+        "platform select remote-android",
+        "settings append target.exec-search-paths /game/target/aarch64-linux-android/debug",
+        "gdb-remote localhost:41234",
+      ]
+    );
+    assert_eq!(
+      get_lldb_commands(&[PathBuf::from("/my games/Spiel für Jürgen/target")], 1)[1],
+      "settings append target.exec-search-paths \"/my games/Spiel für Jürgen/target\""
+    );
+  }
+}
@@ -0,0 +1,54 @@
+use std::ffi::OsStr;
+use std::fmt::{Display, Formatter};
+use std::process::ExitStatus;
+
+/// Error of a build, install or run.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+  /// The package, its android metadata or the build environment can't be built with, e.g. an
+  /// unknown target or a missing SDL checkout.
+  Config(String),
+  /// A command run by the build failed, e.g. ndk-build, gradle or adb. Its output is in the build
+  /// log.
+  Command { program: String, status: ExitStatus },
+  /// cargo failed to build the package, its diagnostics were printed already.
+  Cargo(String),
+}
+
+impl Error {
+  pub(crate) fn command(program: &OsStr, status: ExitStatus) -> Self {
+    Error::Command {
+      program: program.to_string_lossy().to_string(),
+      status,
+    }
+  }
+
+  /// Exit code of the binary for this error, the one of the failed command if there is one.
+  pub fn exit_code(&self) -> i32 {
+    match self {
+      Error::Command { status, .. } => status.code().filter(|c| *c != 0).unwrap_or(1),
+      _ => 1,
+    }
+  }
+}
+
+impl Display for Error {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Error::Config(message) => write!(f, "{}", message),
+      Error::Command { program, status } => write!(f, "{} failed ({})", program, status),
+      Error::Cargo(message) => write!(f, "cargo build failed: {}", message),
+    }
+  }
+}
+
+impl std::error::Error for Error {}
+
+impl From<String> for Error {
+  fn from(message: String) -> Self {
+    Error::Config(message)
+  }
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
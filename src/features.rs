@@ -0,0 +1,357 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+use cargo::core::resolver::CliFeatures;
+use cargo::CargoResult;
+use toml::{Table, Value};
+
+use crate::targets::*;
+use crate::util::*;
+
+/// The cargo features to build with, like the `--features`, `--all-features` and
+/// `--no-default-features` flags of cargo.
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct FeatureSelection {
+  pub features: Vec<String>,
+  pub all_features: bool,
+  pub no_default_features: bool,
+}
+
+/// Splits feature flags like cargo does, separated by commas or whitespace.
+pub fn split_features<S: AsRef<str>>(features: &[S]) -> Vec<String> {
+  features
+    .iter()
+    .flat_map(|f| f.as_ref().split([',', ' ', '\t']))
+    .filter(|f| !f.is_empty())
+    .map(|f| f.to_string())
+    .collect()
+}
+
+impl FeatureSelection {
+  /// The flags of a cargo invocation selecting these features.
+  pub fn cargo_args(&self) -> Vec<String> {
+    let mut args = vec![];
+    if !self.features.is_empty() {
+      args.push("--features".to_string());
+      args.push(self.features.join(","));
+    }
+    if self.all_features {
+      args.push("--all-features".to_string());
+    }
+    if self.no_default_features {
+      args.push("--no-default-features".to_string());
+    }
+    args
+  }
+
+  /// The features of the package's `[features]` table these flags enable, with the entries they
+  /// enable in turn, like `dep:gilrs` or `sdl2/mixer`.
+  pub fn enabled_features(&self, features_table: &Table) -> BTreeSet<String> {
+    let mut pending: Vec<String> = if self.all_features {
+      features_table.keys().cloned().collect()
+    } else {
+      let mut pending = self.features.clone();
+      if !self.no_default_features {
+        pending.push("default".to_string());
+      }
+      pending
+    };
+    let mut enabled = BTreeSet::new();
+    while let Some(feature) = pending.pop() {
+      if !enabled.insert(feature.clone()) {
+        continue;
+      }
+      if let Some(Value::Array(entries)) = features_table.get(&feature) {
+        pending.extend(
+          entries
+            .iter()
+            .filter_map(|e| e.as_str().map(str::to_string)),
+        );
+      }
+    }
+    enabled
+  }
+
+  pub fn to_cli_features(&self) -> CargoResult<CliFeatures> {
+    CliFeatures::from_command_line(&self.features, self.all_features, !self.no_default_features)
+  }
+
+  /// Adds the `target_features` of a target. Features of the target are always enabled, even the
+  /// default ones with `--no-default-features`. Returns notes on how conflicts were resolved.
+  pub fn merge_target_features(
+    &self,
+    target: &str,
+    target_features: &[String],
+    default_features: &[String],
+  ) -> (FeatureSelection, Vec<String>) {
+    let mut merged = self.clone();
+    let mut notes = vec![];
+    if self.all_features && !target_features.is_empty() {
+      notes.push(format!(
+        "{}: target_features {} are already enabled by --all-features",
+        target,
+        target_features.join(", ")
+      ));
+    }
+    for feature in target_features {
+      if merged.features.contains(feature) {
+        notes.push(format!(
+          "{}: {} is enabled by both the feature flags and target_features",
+          target, feature
+        ));
+        continue;
+      }
+      if self.no_default_features && (feature == "default" || default_features.contains(feature)) {
+        notes.push(format!(
+          "{}: target_features enables the default feature {} despite --no-default-features",
+          target, feature
+        ));
+      }
+      merged.features.push(feature.clone());
+    }
+    (merged, notes)
+  }
+}
+
+/// Parses the `target_features` android metadata, a table of feature lists keyed by target triple.
+/// Targets need to be known or listed in `extra_targets`.
+pub fn parse_target_features(
+  value: &Value,
+  extra_targets: &BTreeMap<String, String>,
+) -> Result<BTreeMap<String, Vec<String>>, String> {
+  let Value::Table(targets) = value else {
+    return Err("target_features must be a table of feature lists by target".to_string());
+  };
+  let mut target_features = BTreeMap::new();
+  for (target, features) in targets {
+    resolve_target_android_name(target, extra_targets)
+      .map_err(|e| format!("{} in target_features", e))?;
+    let features = features
+      .as_array()
+      .and_then(|features| {
+        features
+          .iter()
+          .map(|f| f.as_str().map(|f| f.to_string()))
+          .collect::<Option<Vec<String>>>()
+      })
+      .ok_or_else(|| format!("target_features.{} must be a list of features", target))?;
+    target_features.insert(target.clone(), features);
+  }
+  Ok(target_features)
+}
+
+/// Reads `target_features` of the android metadata.
+pub fn get_target_features(manifest_path: &Path) -> Result<BTreeMap<String, Vec<String>>, String> {
+  match get_toml_entry(
+    manifest_path,
+    ["package", "metadata", "android", "target_features"],
+  )? {
+    Some(value) => parse_target_features(&value, &get_extra_targets(manifest_path)?),
+    None => Ok(BTreeMap::new()),
+  }
+}
+
+/// Reads the default features of the package.
+pub fn get_default_features(manifest_path: &Path) -> Result<Vec<String>, TomlError> {
+  Ok(
+    get_toml_entry(manifest_path, ["features", "default"])?
+      .and_then(|value| {
+        value.as_array().map(|features| {
+          features
+            .iter()
+            .filter_map(|f| f.as_str().map(|f| f.to_string()))
+            .collect()
+        })
+      })
+      .unwrap_or_default(),
+  )
+}
+
+/// The features to build each of the targets with, the given ones merged with the
+/// `target_features` of the package. Notes on conflicts are printed with `verbose`.
+pub fn get_target_feature_selections(
+  manifest_path: &Path,
+  features: &FeatureSelection,
+  targets: &[&str],
+  verbose: bool,
+) -> Result<Vec<(String, FeatureSelection)>, String> {
+  let target_features = get_target_features(manifest_path)?;
+  let default_features = get_default_features(manifest_path)?;
+  Ok(
+    targets
+      .iter()
+      .map(|target| {
+        let (selection, notes) = features.merge_target_features(
+          target,
+          target_features
+            .get(*target)
+            .map(|f| f.as_slice())
+            .unwrap_or_default(),
+          &default_features,
+        );
+        if verbose {
+          for note in notes {
+            println!("{}", note);
+          }
+          println!("{}: cargo {}", target, selection.cargo_args().join(" "));
+        }
+        (target.to_string(), selection)
+      })
+      .collect(),
+  )
+}
+
+/// The `--features` of checking the target, the given ones merged with its `target_features`.
+pub fn get_check_features(
+  manifest_path: &Path,
+  target: &str,
+  features: Option<&str>,
+) -> Result<Option<String>, String> {
+  let selection = FeatureSelection {
+    features: split_features(&features.into_iter().collect::<Vec<&str>>()),
+    ..FeatureSelection::default()
+  };
+  let target_features = get_target_features(manifest_path)?;
+  let (merged, _) = selection.merge_target_features(
+    target,
+    target_features
+      .get(target)
+      .map(|f| f.as_slice())
+      .unwrap_or_default(),
+    &[],
+  );
+  Ok((!merged.features.is_empty()).then(|| merged.features.join(",")))
+}
+
+#[cfg(test)]
+mod test {
+  use std::collections::BTreeMap;
+
+  use toml::Table;
+
+  use crate::features::{parse_target_features, split_features, FeatureSelection};
+
+  fn selection(features: &[&str], all: bool, no_default: bool) -> FeatureSelection {
+    FeatureSelection {
+      features: split_features(features),
+      all_features: all,
+      no_default_features: no_default,
+    }
+  }
+
+  fn merged_args(global: &FeatureSelection, target_features: &[&str]) -> Vec<String> {
+    let target_features = split_features(target_features);
+    let default_features = vec!["gles2".to_string()];
+    let (merged, _) = global.merge_target_features(
+      "armv7-linux-androideabi",
+      &target_features,
+      &default_features,
+    );
+    merged.cargo_args()
+  }
+
+  #[test]
+  fn target_features() {
+    let value = r#"
+[target_features]
+armv7-linux-androideabi = ["gles2"]
+aarch64-linux-android = ["vulkan", "hdr"]
+"#
+    .parse::<Table>()
+    .unwrap()["target_features"]
+      .clone();
+    let target_features = parse_target_features(&value, &BTreeMap::new()).unwrap();
+    assert_eq!(target_features["armv7-linux-androideabi"], vec!["gles2"]);
+    assert_eq!(
+      target_features["aarch64-linux-android"],
+      vec!["vulkan", "hdr"]
+    );
+
+    let invalid = "[t]\nx86_64-unknown-linux-gnu = [\"gles2\"]"
+      .parse::<Table>()
+      .unwrap()["t"]
+      .clone();
+    assert!(parse_target_features(&invalid, &BTreeMap::new()).is_err());
+    let invalid = "[t]\nx86-linux-android = \"gles2\""
+      .parse::<Table>()
+      .unwrap()["t"]
+      .clone();
+    assert!(parse_target_features(&invalid, &BTreeMap::new()).is_err());
+  }
+
+  #[test]
+  fn merged_argument_vectors() {
+    //nothing selected
+    assert!(merged_args(&selection(&[], false, false), &[]).is_empty());
+    //target features only
+    assert_eq!(
+      merged_args(&selection(&[], false, false), &["vulkan"]),
+      vec!["--features", "vulkan"]
+    );
+    //global features come first, duplicates are dropped
+    assert_eq!(
+      merged_args(
+        &selection(&["audio,vulkan"], false, false),
+        &["vulkan", "hdr"]
+      ),
+      vec!["--features", "audio,vulkan,hdr"]
+    );
+    //target features enable default features despite --no-default-features
+    assert_eq!(
+      merged_args(&selection(&["audio"], false, true), &["gles2"]),
+      vec!["--features", "audio,gles2", "--no-default-features"]
+    );
+    //--all-features is kept as is
+    assert_eq!(
+      merged_args(&selection(&[], true, false), &["vulkan"]),
+      vec!["--features", "vulkan", "--all-features"]
+    );
+  }
+
+  #[test]
+  fn conflict_notes() {
+    let default_features = vec!["gles2".to_string()];
+    let (_, notes) = selection(&["audio"], false, true).merge_target_features(
+      "armv7-linux-androideabi",
+      &["gles2".to_string(), "audio".to_string()],
+      &default_features,
+    );
+    assert_eq!(
+      notes,
+      vec![
+        "armv7-linux-androideabi: target_features enables the default feature gles2 despite --no-default-features",
+        "armv7-linux-androideabi: audio is enabled by both the feature flags and target_features",
+      ]
+    );
+    let (_, notes) =
+      selection(&[], true, false).merge_target_features("x86", &["hdr".to_string()], &[]);
+    assert_eq!(
+      notes,
+      vec!["x86: target_features hdr are already enabled by --all-features"]
+    );
+  }
+
+  #[test]
+  fn enabled_features() {
+    let table = r#"
+default = ["audio"]
+audio = ["sdl2/mixer"]
+rumble = ["dep:gilrs", "controllers"]
+controllers = []
+"#
+    .parse::<Table>()
+    .unwrap();
+    let enabled = |selection: FeatureSelection| -> Vec<String> {
+      selection.enabled_features(&table).into_iter().collect()
+    };
+    assert_eq!(
+      vec!["audio", "default", "sdl2/mixer"],
+      enabled(selection(&[], false, false))
+    );
+    assert_eq!(
+      vec!["controllers", "dep:gilrs", "rumble"],
+      enabled(selection(&["rumble"], false, true))
+    );
+    assert_eq!(6, enabled(selection(&[], true, true)).len());
+  }
+}
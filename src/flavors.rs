@@ -0,0 +1,346 @@
+use std::path::{Path, PathBuf};
+
+use toml::value::Value;
+use toml::Table;
+
+use crate::gradle_dsl::*;
+use crate::util::*;
+use crate::BuildProfile;
+
+/// The flavor dimension all product flavors belong to.
+const FLAVOR_DIMENSION: &str = "version";
+
+/// Names gradle uses for source sets or build types, which a flavor can't be named.
+const RESERVED_FLAVOR_NAMES: &[&str] = &["main", "test", "androidTest", "debug", "release"];
+
+/// A product flavor of `[[package.metadata.android.flavors]]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Flavor {
+  pub name: String,
+  /// Appended to the application id, before the suffix of the profile.
+  pub application_id_suffix: Option<String>,
+  /// Features the native library of the flavor is built with.
+  pub cargo_features: Vec<String>,
+  /// Directory copied to the assets of the flavor.
+  pub assets: Option<PathBuf>,
+}
+
+fn is_valid_flavor_name(name: &str) -> bool {
+  name.starts_with(|c: char| c.is_ascii_lowercase())
+    && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    && !RESERVED_FLAVOR_NAMES.contains(&name)
+}
+
+fn parse_flavor(table: &Table, manifest_dir: &Path) -> Result<Flavor, String> {
+  let get_string = |key: &str| match table.get(key) {
+    None => Ok(None),
+    Some(Value::String(s)) => Ok(Some(s.clone())),
+    Some(_) => Err(format!("{} of a flavor must be a string", key)),
+  };
+  let name = get_string("name")?.ok_or("every flavor needs a name")?;
+  if !is_valid_flavor_name(&name) {
+    return Err(format!(
+      "invalid flavor name \"{}\", it must start with a lowercase letter, contain only letters, \
+      digits and underscores and not be one of {}",
+      name,
+      RESERVED_FLAVOR_NAMES.join(", ")
+    ));
+  }
+  let cargo_features = match table.get("cargo_features") {
+    None => vec![],
+    Some(value) => value
+      .as_array()
+      .and_then(|features| {
+        features
+          .iter()
+          .map(|f| f.as_str().map(|f| f.to_string()))
+          .collect::<Option<Vec<String>>>()
+      })
+      .ok_or_else(|| {
+        format!(
+          "cargo_features of flavor {} must be a list of features",
+          name
+        )
+      })?,
+  };
+  Ok(Flavor {
+    application_id_suffix: get_string("application_id_suffix")?,
+    cargo_features,
+    assets: get_string("assets")?.map(|assets| manifest_dir.join(assets)),
+    name,
+  })
+}
+
+/// Parses the `flavors` android metadata, an array of flavor tables. Relative asset dirs are
+/// resolved against the manifest dir.
+pub fn parse_flavors(value: &Value, manifest_dir: &Path) -> Result<Vec<Flavor>, String> {
+  let flavors = value
+    .as_array()
+    .ok_or("flavors must be an array of tables, [[package.metadata.android.flavors]]")?;
+  let mut parsed: Vec<Flavor> = vec![];
+  for flavor in flavors {
+    let table = flavor.as_table().ok_or("every flavor must be a table")?;
+    let flavor = parse_flavor(table, manifest_dir)?;
+    if parsed.iter().any(|f| f.name == flavor.name) {
+      return Err(format!("flavor {} is defined twice", flavor.name));
+    }
+    parsed.push(flavor);
+  }
+  Ok(parsed)
+}
+
+/// Reads the flavors of the android metadata.
+pub fn get_flavors(manifest_path: &Path) -> Result<Vec<Flavor>, String> {
+  match get_toml_entry(manifest_path, ["package", "metadata", "android", "flavors"])? {
+    Some(value) => parse_flavors(&value, manifest_path.parent().unwrap()),
+    None => Ok(vec![]),
+  }
+}
+
+/// Picks the flavor selected with `--flavor`. Packages with flavors need exactly one selected.
+pub fn select_flavor(
+  flavors: &[Flavor],
+  requested: Option<&str>,
+) -> Result<Option<Flavor>, String> {
+  let names = || {
+    flavors
+      .iter()
+      .map(|f| f.name.as_str())
+      .collect::<Vec<&str>>()
+      .join(", ")
+  };
+  match requested {
+    None if flavors.is_empty() => Ok(None),
+    None => Err(format!(
+      "The package has the flavors {}, select one with --flavor",
+      names()
+    )),
+    Some(name) if flavors.is_empty() => Err(format!(
+      "Flavor {} selected, but the package defines no flavors",
+      name
+    )),
+    Some(name) => flavors
+      .iter()
+      .find(|f| f.name == name)
+      .cloned()
+      .map(Some)
+      .ok_or_else(|| format!("Unknown flavor {}, expected one of {}", name, names())),
+  }
+}
+
+/// Reads the flavors of the package and picks the selected one.
+pub fn get_selected_flavor(
+  manifest_path: &Path,
+  requested: Option<&str>,
+) -> Result<Option<Flavor>, String> {
+  select_flavor(&get_flavors(manifest_path)?, requested)
+}
+
+/// The `productFlavors` block of app/build.gradle.
+pub fn build_gradle_product_flavors(flavors: &[Flavor], dsl: GradleDsl) -> String {
+  let dimension = dsl.string(FLAVOR_DIMENSION);
+  let mut lines = vec![
+    match dsl {
+      GradleDsl::Groovy => format!("    flavorDimensions {}", dimension),
+      GradleDsl::Kotlin => format!("    flavorDimensions += {}", dimension),
+    },
+    "    productFlavors {".to_string(),
+  ];
+  for flavor in flavors {
+    lines.push(format!(
+      "        {}",
+      dsl.element_block(&flavor.name, false)
+    ));
+    lines.push(format!(
+      "            {}",
+      dsl.assign("dimension", &dimension)
+    ));
+    if let Some(suffix) = &flavor.application_id_suffix {
+      lines.push(format!(
+        "            {}",
+        dsl.assign("applicationIdSuffix", &dsl.string(suffix))
+      ));
+    }
+    lines.push("        }".to_string());
+  }
+  lines.push("    }".to_string());
+  lines.join("\n")
+}
+
+/// The name of the build variant in output file names, e.g. `demo-release`.
+pub fn get_variant_file_name(flavor: Option<&str>, profile: BuildProfile) -> String {
+  match flavor {
+    Some(flavor) => format!("{}-{}", flavor, profile),
+    None => profile.to_string(),
+  }
+}
+
+/// The directory gradle puts the APKs of the variant in, relative to the android project.
+pub fn get_variant_apk_dir(flavor: Option<&str>, profile: BuildProfile) -> PathBuf {
+  let mut dir = PathBuf::from("app/build/outputs/apk");
+  if let Some(flavor) = flavor {
+    dir.push(flavor);
+  }
+  dir.join(profile.to_string())
+}
+
+/// The R8 mapping of the variant gradle writes if the build is minified, relative to the android
+/// project.
+pub fn get_variant_mapping_file(flavor: Option<&str>, profile: BuildProfile) -> PathBuf {
+  PathBuf::from("app/build/outputs/mapping")
+    .join(get_variant_name(flavor, profile))
+    .join("mapping.txt")
+}
+
+fn capitalize(s: &str) -> String {
+  let mut chars = s.chars();
+  chars
+    .next()
+    .map(|first| first.to_uppercase().chain(chars).collect::<String>())
+    .unwrap_or_default()
+}
+
+/// The name of the build variant in gradle, e.g. `demoRelease`.
+fn get_variant_name(flavor: Option<&str>, profile: BuildProfile) -> String {
+  match flavor {
+    Some(flavor) => format!("{}{}", flavor, capitalize(&profile.to_string())),
+    None => profile.to_string(),
+  }
+}
+
+/// The gradle task assembling the variant, e.g. `assembleDemoRelease`.
+pub fn get_assemble_task(flavor: Option<&str>, profile: BuildProfile) -> String {
+  format!("assemble{}", capitalize(&get_variant_name(flavor, profile)))
+}
+
+/// The gradle task bundling the variant into an AAB, e.g. `bundleDemoRelease`.
+pub fn get_bundle_task(flavor: Option<&str>, profile: BuildProfile) -> String {
+  format!("bundle{}", capitalize(&get_variant_name(flavor, profile)))
+}
+
+/// The directory gradle puts the AAB of the variant in, relative to the android project. Unlike
+/// the APKs, the variant is one dir named like `demoRelease`.
+pub fn get_variant_bundle_dir(flavor: Option<&str>, profile: BuildProfile) -> PathBuf {
+  PathBuf::from("app/build/outputs/bundle").join(get_variant_name(flavor, profile))
+}
+
+#[cfg(test)]
+mod test {
+  use std::path::{Path, PathBuf};
+
+  use toml::Table;
+
+  use crate::flavors::{
+    build_gradle_product_flavors, get_assemble_task, get_bundle_task, get_variant_apk_dir,
+    get_variant_bundle_dir, get_variant_file_name, parse_flavors, select_flavor,
+  };
+  use crate::gradle_dsl::GradleDsl;
+  use crate::BuildProfile;
+
+  fn parse(metadata: &str) -> Result<Vec<crate::flavors::Flavor>, String> {
+    let table = metadata.parse::<Table>().unwrap();
+    parse_flavors(&table["flavors"], Path::new("/game"))
+  }
+
+  #[test]
+  fn flavors() {
+    let flavors = parse(
+      r#"
+[[flavors]]
+name = "demo"
+application_id_suffix = ".demo"
+cargo_features = ["demo"]
+assets = "assets/demo"
+
+[[flavors]]
+name = "full"
+"#,
+    )
+    .unwrap();
+    assert_eq!(flavors.len(), 2);
+    assert_eq!(flavors[0].application_id_suffix.as_deref(), Some(".demo"));
+    assert_eq!(flavors[0].cargo_features, vec!["demo"]);
+    assert_eq!(flavors[0].assets, Some(PathBuf::from("/game/assets/demo")));
+    assert_eq!(flavors[1].cargo_features, Vec::<String>::new());
+
+    assert_eq!(
+      build_gradle_product_flavors(&flavors, GradleDsl::Groovy),
+      "    flavorDimensions 'version'
+    productFlavors {
+        demo {
+            dimension 'version'
+            applicationIdSuffix '.demo'
+        }
+        full {
+            dimension 'version'
+        }
+    }"
+    );
+    assert_eq!(
+      build_gradle_product_flavors(&flavors[..1], GradleDsl::Kotlin),
+      "    flavorDimensions += \"version\"
+    productFlavors {
+        create(\"demo\") {
+            dimension = \"version\"
+            applicationIdSuffix = \".demo\"
+        }
+    }"
+    );
+
+    assert!(parse("[[flavors]]\nname = \"Demo\"").is_err());
+    assert!(parse("[[flavors]]\nname = \"main\"").is_err());
+    assert!(parse("[[flavors]]\nname = \"demo\"\n[[flavors]]\nname = \"demo\"").is_err());
+    assert!(parse("[[flavors]]\nname = \"demo\"\ncargo_features = \"demo\"").is_err());
+  }
+
+  #[test]
+  fn flavor_selection() {
+    let flavors = parse("[[flavors]]\nname = \"demo\"\n[[flavors]]\nname = \"full\"").unwrap();
+    assert_eq!(
+      select_flavor(&flavors, Some("full")).unwrap().unwrap().name,
+      "full"
+    );
+    assert!(select_flavor(&flavors, None)
+      .unwrap_err()
+      .contains("demo, full"));
+    assert!(select_flavor(&flavors, Some("paid")).is_err());
+    assert_eq!(select_flavor(&[], None), Ok(None));
+    assert!(select_flavor(&[], Some("demo")).is_err());
+  }
+
+  #[test]
+  fn variant_names() {
+    assert_eq!(
+      get_assemble_task(Some("demo"), BuildProfile::Release),
+      "assembleDemoRelease"
+    );
+    assert_eq!(
+      get_assemble_task(None, BuildProfile::Debug),
+      "assembleDebug"
+    );
+    assert_eq!(
+      get_bundle_task(Some("demo"), BuildProfile::Release),
+      "bundleDemoRelease"
+    );
+    assert_eq!(
+      get_variant_bundle_dir(Some("demo"), BuildProfile::Release),
+      Path::new("app/build/outputs/bundle/demoRelease")
+    );
+    assert_eq!(
+      get_variant_bundle_dir(None, BuildProfile::Release),
+      Path::new("app/build/outputs/bundle/release")
+    );
+    assert_eq!(
+      get_variant_file_name(Some("demo"), BuildProfile::Debug),
+      "demo-debug"
+    );
+    assert_eq!(
+      get_variant_apk_dir(Some("demo"), BuildProfile::Release),
+      Path::new("app/build/outputs/apk/demo/release")
+    );
+    assert_eq!(
+      get_variant_apk_dir(None, BuildProfile::Debug),
+      Path::new("app/build/outputs/apk/debug")
+    );
+  }
+}
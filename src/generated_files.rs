@@ -0,0 +1,171 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{read, read_to_string, write};
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+use crate::warnings::*;
+
+/// Name of the file inside the android project recording the hashes of all generated files.
+const GENERATED_FILES_RECORD: &str = ".cargo-sdl-apk-generated";
+
+/// Keeps track of the files this tool generates inside the android project, so that files which
+/// were modified by hand since the last generation can be detected before they are overwritten.
+pub struct GeneratedFiles {
+  project_dir: PathBuf,
+  keep_local: bool,
+  /// Hashes recorded by the previous generation, keyed by path relative to the project dir.
+  recorded: BTreeMap<String, String>,
+  /// Files (relative to the project dir) modified since the last generation.
+  modified: BTreeSet<String>,
+  /// Files (relative to the project dir) generated in this run.
+  generated: BTreeSet<String>,
+}
+
+fn hash_file(path: &Path) -> Option<String> {
+  let content = read(path).ok()?;
+  Some(format!("{:x}", Sha256::digest(content)))
+}
+
+fn parse_record(content: &str) -> BTreeMap<String, String> {
+  content
+    .lines()
+    .filter_map(|line| line.split_once("  "))
+    .map(|(hash, path)| (path.to_string(), hash.to_string()))
+    .collect()
+}
+
+impl GeneratedFiles {
+  /// Loads the hashes recorded by the previous generation and compares them with the current
+  /// files. With `keep_local` modified files won't be regenerated.
+  pub fn load(project_dir: &Path, keep_local: bool) -> Self {
+    let recorded = read_to_string(project_dir.join(GENERATED_FILES_RECORD))
+      .map(|content| parse_record(&content))
+      .unwrap_or_default();
+
+    let modified = recorded
+      .iter()
+      .filter(|(path, hash)| match hash_file(&project_dir.join(path)) {
+        Some(current) => &current != *hash,
+        None => false,
+      })
+      .map(|(path, _)| path.clone())
+      .collect();
+
+    Self {
+      project_dir: project_dir.to_path_buf(),
+      keep_local,
+      recorded,
+      modified,
+      generated: BTreeSet::new(),
+    }
+  }
+
+  pub fn modified(&self) -> &BTreeSet<String> {
+    &self.modified
+  }
+
+  /// Prints a warning listing the files modified since the last generation.
+  pub fn warn_about_modified(&self) {
+    if self.modified.is_empty() {
+      return;
+    }
+
+    let paths: Vec<String> = self
+      .modified()
+      .iter()
+      .map(|path| self.project_dir.join(path).display().to_string())
+      .collect();
+    warn_with_fix(
+      &MODIFIED_FILES,
+      format!(
+        "The following generated files were modified by hand: {}",
+        paths.join(", ")
+      ),
+      if self.keep_local {
+        "Keeping the local versions because of --keep-local."
+      } else {
+        "These changes will be overwritten, use --keep-local to keep them."
+      },
+    );
+  }
+
+  /// Registers the given file (relative to the project dir) as generated and returns whether it
+  /// should be written, which is not the case for locally modified files with `--keep-local`.
+  pub fn claim(&mut self, path: &str) -> bool {
+    self.generated.insert(path.to_string());
+    !(self.keep_local && self.modified.contains(path))
+  }
+
+  /// Records the hashes of all generated files for the next generation. Kept local versions keep
+  /// their previous hash, so they are still reported (and protected) next time.
+  pub fn save(&self) {
+    let mut content = String::new();
+    for path in &self.generated {
+      let hash = if self.keep_local && self.modified.contains(path) {
+        self.recorded.get(path).cloned()
+      } else {
+        hash_file(&self.project_dir.join(path))
+      };
+      if let Some(hash) = hash {
+        content.push_str(&format!("{}  {}\n", hash, path));
+      }
+    }
+
+    let record = self.project_dir.join(GENERATED_FILES_RECORD);
+    write(&record, content).unwrap_or_else(|_| panic!("unable to write file {:?}", record));
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::fs::write;
+
+  use tempfile::tempdir;
+
+  use crate::generated_files::GeneratedFiles;
+
+  #[test]
+  fn detect_modified_files() {
+    let project = tempdir().unwrap();
+    write(project.path().join("build.gradle"), "generated").unwrap();
+    write(project.path().join("strings.xml"), "generated").unwrap();
+
+    let mut generated = GeneratedFiles::load(project.path(), false);
+    assert!(generated.modified().is_empty());
+    assert!(generated.claim("build.gradle"));
+    assert!(generated.claim("strings.xml"));
+    generated.save();
+
+    write(project.path().join("build.gradle"), "edited by hand").unwrap();
+
+    let mut generated = GeneratedFiles::load(project.path(), false);
+    assert_eq!(
+      generated.modified().iter().collect::<Vec<_>>(),
+      vec!["build.gradle"]
+    );
+    assert!(generated.claim("build.gradle"));
+
+    let mut generated = GeneratedFiles::load(project.path(), true);
+    assert!(!generated.claim("build.gradle"));
+    assert!(generated.claim("strings.xml"));
+  }
+
+  #[test]
+  fn kept_files_stay_recorded() {
+    let project = tempdir().unwrap();
+    write(project.path().join("build.gradle"), "generated").unwrap();
+    let mut generated = GeneratedFiles::load(project.path(), true);
+    generated.claim("build.gradle");
+    generated.save();
+
+    write(project.path().join("build.gradle"), "edited by hand").unwrap();
+    let mut generated = GeneratedFiles::load(project.path(), true);
+    assert!(!generated.claim("build.gradle"));
+    generated.save();
+
+    //the local version is still reported as modified on the next run
+    let generated = GeneratedFiles::load(project.path(), false);
+    assert_eq!(generated.modified().len(), 1);
+  }
+}
@@ -0,0 +1,230 @@
+use std::path::{Path, PathBuf};
+
+use crate::gradle_dsl::*;
+use crate::util::*;
+use crate::warnings::*;
+
+/// Version of the gradle plugin processing google-services.json.
+const GOOGLE_SERVICES_PLUGIN_VERSION: &str = "4.3.15";
+const GOOGLE_SERVICES_PLUGIN_ID: &str = "com.google.gms.google-services";
+/// Dependency added if the app has no play services or firebase dependency, since the plugin
+/// only generates resources for those.
+const DEFAULT_PLAY_SERVICES_DEPENDENCY: &str =
+  "com.google.android.gms:play-services-basement:18.2.0";
+
+/// Returns the google-services.json configured by `google_services` in the android metadata.
+pub fn get_google_services_file(manifest_path: &Path) -> Result<Option<PathBuf>, String> {
+  let Some(file) = get_toml_string(
+    manifest_path,
+    ["package", "metadata", "android", "google_services"],
+  )?
+  else {
+    return Ok(None);
+  };
+  let path = manifest_path.parent().unwrap().join(&file);
+  if !path.is_file() {
+    return Err(format!("google_services file {:?} not found", path));
+  }
+  Ok(Some(path))
+}
+
+/// Adds the google services plugin to the top-level build.gradle, either as buildscript classpath
+/// or to the plugins block. Content already referring to the plugin is returned unchanged.
+pub fn add_google_services_classpath(build_gradle: &str, dsl: GradleDsl) -> String {
+  if build_gradle.contains("com.google.gms:google-services")
+    || build_gradle.contains(GOOGLE_SERVICES_PLUGIN_ID)
+  {
+    return build_gradle.to_string();
+  }
+
+  let classpath = dsl.call(
+    "classpath",
+    &[&dsl.string(&format!(
+      "com.google.gms:google-services:{}",
+      GOOGLE_SERVICES_PLUGIN_VERSION
+    ))],
+  );
+  let plugin = format!(
+    "{} version {} apply false",
+    dsl.call("id", &[&dsl.string(GOOGLE_SERVICES_PLUGIN_ID)]),
+    dsl.string(GOOGLE_SERVICES_PLUGIN_VERSION)
+  );
+
+  insert_after_line(
+    build_gradle,
+    |l| l.starts_with("classpath") && l.contains("com.android.tools.build:gradle"),
+    &classpath,
+    "",
+  )
+  .or_else(|| {
+    insert_after_line(
+      build_gradle,
+      |l| l.starts_with("id") && l.contains("com.android.application"),
+      &plugin,
+      "",
+    )
+  })
+  .unwrap_or_else(|| {
+    warn(
+      &GOOGLE_SERVICES,
+      "Unable to add the google services plugin to build.gradle, neither the android gradle \
+      plugin classpath nor a plugins block was found.",
+    );
+    build_gradle.to_string()
+  })
+}
+
+/// Applies the google services plugin in app/build.gradle and adds a play services dependency if
+/// there is none. Content already applying the plugin is returned unchanged.
+pub fn apply_google_services_plugin(build_gradle: &str, dsl: GradleDsl) -> String {
+  if build_gradle.contains(GOOGLE_SERVICES_PLUGIN_ID) {
+    return build_gradle.to_string();
+  }
+
+  let mut content = insert_after_line(
+    build_gradle,
+    |l| l.starts_with("id") && l.contains("com.android.application"),
+    &dsl.call("id", &[&dsl.string(GOOGLE_SERVICES_PLUGIN_ID)]),
+    "",
+  )
+  .unwrap_or_else(|| {
+    //google recommends applying the plugin at the bottom of the file
+    let mut content = build_gradle.to_string();
+    if !content.ends_with('\n') {
+      content.push('\n');
+    }
+    let apply = match dsl {
+      GradleDsl::Groovy => format!("apply plugin: {}", dsl.string(GOOGLE_SERVICES_PLUGIN_ID)),
+      GradleDsl::Kotlin => format!("apply(plugin = {})", dsl.string(GOOGLE_SERVICES_PLUGIN_ID)),
+    };
+    content.push_str(&format!("\n{}\n", apply));
+    content
+  });
+
+  if content.contains("com.google.android.gms:") || content.contains("com.google.firebase:") {
+    return content;
+  }
+  let dependency = dsl.call(
+    "implementation",
+    &[&dsl.string(DEFAULT_PLAY_SERVICES_DEPENDENCY)],
+  );
+  insert_after_line(&content, |l| l == "dependencies {", &dependency, "    ").unwrap_or_else(|| {
+    content.push_str(&format!("\ndependencies {{\n    {}\n}}\n", dependency));
+    content
+  })
+}
+
+#[cfg(test)]
+mod test {
+  use crate::google_services::{add_google_services_classpath, apply_google_services_plugin};
+  use crate::gradle_dsl::GradleDsl;
+
+  const BUILDSCRIPT_TOP_LEVEL: &str = "buildscript {
+    dependencies {
+        classpath 'com.android.tools.build:gradle:7.0.3'
+    }
+}
+";
+
+  const PLUGINS_TOP_LEVEL: &str = "plugins {
+    id 'com.android.application' version '8.1.0' apply false
+}
+";
+
+  #[test]
+  fn classpath_buildscript() {
+    let content = add_google_services_classpath(BUILDSCRIPT_TOP_LEVEL, GradleDsl::Groovy);
+    assert_eq!(
+      content,
+      "buildscript {
+    dependencies {
+        classpath 'com.android.tools.build:gradle:7.0.3'
+        classpath 'com.google.gms:google-services:4.3.15'
+    }
+}
+"
+    );
+    assert_eq!(
+      add_google_services_classpath(&content, GradleDsl::Groovy),
+      content
+    );
+  }
+
+  #[test]
+  fn classpath_plugins_block() {
+    let content = add_google_services_classpath(PLUGINS_TOP_LEVEL, GradleDsl::Groovy);
+    assert_eq!(
+      content,
+      "plugins {
+    id 'com.android.application' version '8.1.0' apply false
+    id 'com.google.gms.google-services' version '4.3.15' apply false
+}
+"
+    );
+    assert_eq!(
+      add_google_services_classpath(&content, GradleDsl::Groovy),
+      content
+    );
+  }
+
+  #[test]
+  fn apply_legacy() {
+    let content = apply_google_services_plugin(
+      "apply plugin: 'com.android.application'\n\nandroid {\n}\n\ndependencies {\n    implementation fileTree(include: ['*.jar'], dir: 'libs')\n}\n",
+      GradleDsl::Groovy,
+    );
+    assert_eq!(
+      content,
+      "apply plugin: 'com.android.application'\n\nandroid {\n}\n\ndependencies {\n    implementation 'com.google.android.gms:play-services-basement:18.2.0'\n    implementation fileTree(include: ['*.jar'], dir: 'libs')\n}\n\napply plugin: 'com.google.gms.google-services'\n"
+    );
+    assert_eq!(
+      apply_google_services_plugin(&content, GradleDsl::Groovy),
+      content
+    );
+  }
+
+  #[test]
+  fn apply_plugins_block() {
+    let content = apply_google_services_plugin(
+      "plugins {\n    id 'com.android.application'\n}\n\ndependencies {\n    implementation platform('com.google.firebase:firebase-bom:32.2.0')\n}\n",
+      GradleDsl::Groovy,
+    );
+    assert_eq!(
+      content,
+      "plugins {\n    id 'com.android.application'\n    id 'com.google.gms.google-services'\n}\n\ndependencies {\n    implementation platform('com.google.firebase:firebase-bom:32.2.0')\n}\n"
+    );
+  }
+
+  #[test]
+  fn kotlin_dsl() {
+    let content = add_google_services_classpath(
+      "plugins {\n    id(\"com.android.application\") version \"8.2.0\" apply false\n}\n",
+      GradleDsl::Kotlin,
+    );
+    assert!(content
+      .contains("\n    id(\"com.google.gms.google-services\") version \"4.3.15\" apply false\n"));
+    let content = apply_google_services_plugin(
+      "plugins {\n    id(\"com.android.application\")\n}\n\ndependencies {\n}\n",
+      GradleDsl::Kotlin,
+    );
+    assert_eq!(
+      content,
+      "plugins {\n    id(\"com.android.application\")\n    id(\"com.google.gms.google-services\")\n}\n\ndependencies {\n    implementation(\"com.google.android.gms:play-services-basement:18.2.0\")\n}\n"
+    );
+    assert!(
+      apply_google_services_plugin("android {\n}\n", GradleDsl::Kotlin)
+        .contains("\napply(plugin = \"com.google.gms.google-services\")\n")
+    );
+  }
+
+  #[test]
+  fn dependencies_block_added() {
+    let content = apply_google_services_plugin(
+      "apply plugin: 'com.android.application'\n",
+      GradleDsl::Groovy,
+    );
+    assert!(content.ends_with(
+      "dependencies {\n    implementation 'com.google.android.gms:play-services-basement:18.2.0'\n}\n"
+    ));
+  }
+}
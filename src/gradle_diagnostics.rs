@@ -0,0 +1,172 @@
+use regex::Regex;
+
+/// Number of lines shown around the line matching a failure signature.
+const EXCERPT_CONTEXT_LINES: usize = 2;
+
+/// A well-known cause of gradle failures, recognized by a regex matching a line of the output.
+pub struct GradleFailureSignature {
+  pub name: &'static str,
+  pub pattern: &'static str,
+  pub explanation: &'static str,
+}
+
+/// Known gradle failures. Add new entries here, together with a fixture of the gradle output in
+/// tests/fixtures/gradle.
+pub const GRADLE_FAILURE_SIGNATURES: &[GradleFailureSignature] = &[
+  GradleFailureSignature {
+    name: "license not accepted",
+    pattern: r"License for package .* not accepted|licen[cs]es have not been accepted",
+    explanation: "The licenses of the required sdk packages were not accepted. Rerun with \
+      --install-sdk-packages --accept-licenses, or accept them with `sdkmanager --licenses`.",
+  },
+  GradleFailureSignature {
+    name: "missing sdk platform",
+    pattern: r"Failed to find (target with hash string|Platform SDK with path)",
+    explanation: "The sdk platform the project compiles against is not installed. Rerun with \
+      --install-sdk-packages or install it with sdkmanager.",
+  },
+  GradleFailureSignature {
+    name: "resource error",
+    pattern: r"AAPT: error:|Android resource linking failed",
+    explanation: "A resource of the android project is invalid or missing, e.g. an icon \
+      referenced by the manifest. Check the icon and title in the android metadata.",
+  },
+  GradleFailureSignature {
+    name: "java version mismatch",
+    pattern: r"Unsupported class file major version|requires Java \d+ to run|Unsupported Java",
+    explanation: "The java version is not supported by the gradle version of the project. The \
+      SDL template needs jdk17, point JAVA_HOME to it.",
+  },
+  GradleFailureSignature {
+    name: "namespace not specified",
+    pattern: r"Namespace not specified",
+    explanation: "The android gradle plugin is too new for the project template, which declares \
+      the package in AndroidManifest.xml instead of a namespace in app/build.gradle. Use the \
+      gradle plugin version of the SDL template.",
+  },
+  GradleFailureSignature {
+    name: "duplicate class",
+    pattern: r"Duplicate class \S+ found in modules",
+    explanation: "A class is included twice, usually because a dependency bundles classes the \
+      project already contains, like the SDL Java sources. Remove one of them. For an SDL AAR, \
+      set sdl_java = { maven = \"<group>:<artifact>:<version>\" } in the android metadata \
+      instead of adding the dependency, which removes the SDL sources of the template.",
+  },
+];
+
+/// A recognized failure together with the part of the output showing it.
+pub struct GradleDiagnosis {
+  pub signature: &'static GradleFailureSignature,
+  pub excerpt: Vec<String>,
+}
+
+/// Scans the output of a failed gradle run for known failure signatures.
+pub fn diagnose_gradle_failure(output: &str) -> Vec<GradleDiagnosis> {
+  let lines: Vec<&str> = output.lines().collect();
+
+  GRADLE_FAILURE_SIGNATURES
+    .iter()
+    .filter_map(|signature| {
+      let regex = Regex::new(signature.pattern).expect("invalid gradle failure signature");
+      let index = lines.iter().position(|line| regex.is_match(line))?;
+      let start = index.saturating_sub(EXCERPT_CONTEXT_LINES);
+      let end = (index + EXCERPT_CONTEXT_LINES + 1).min(lines.len());
+      Some(GradleDiagnosis {
+        signature,
+        excerpt: lines[start..end]
+          .iter()
+          .map(|line| line.to_string())
+          .collect(),
+      })
+    })
+    .collect()
+}
+
+pub fn print_gradle_diagnoses(diagnoses: &[GradleDiagnosis]) {
+  for diagnosis in diagnoses {
+    eprintln!();
+    eprintln!("Gradle failed: {}", diagnosis.signature.name);
+    for line in &diagnosis.excerpt {
+      eprintln!("  | {}", line);
+    }
+    eprintln!("{}", diagnosis.signature.explanation);
+  }
+  if !diagnoses.is_empty() {
+    eprintln!();
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use regex::Regex;
+
+  use crate::gradle_diagnostics::{diagnose_gradle_failure, GRADLE_FAILURE_SIGNATURES};
+
+  fn diagnosed_names(output: &str) -> Vec<&'static str> {
+    diagnose_gradle_failure(output)
+      .iter()
+      .map(|d| d.signature.name)
+      .collect()
+  }
+
+  #[test]
+  fn signatures_are_valid() {
+    for signature in GRADLE_FAILURE_SIGNATURES {
+      assert!(Regex::new(signature.pattern).is_ok(), "{}", signature.name);
+    }
+  }
+
+  #[test]
+  fn fixtures() {
+    for (fixture, expected) in [
+      (
+        include_str!("../tests/fixtures/gradle/missing_platform.log"),
+        "missing sdk platform",
+      ),
+      (
+        include_str!("../tests/fixtures/gradle/license_not_accepted.log"),
+        "license not accepted",
+      ),
+      (
+        include_str!("../tests/fixtures/gradle/aapt_resource_error.log"),
+        "resource error",
+      ),
+      (
+        include_str!("../tests/fixtures/gradle/java_version_mismatch.log"),
+        "java version mismatch",
+      ),
+      (
+        include_str!("../tests/fixtures/gradle/namespace_not_specified.log"),
+        "namespace not specified",
+      ),
+      (
+        include_str!("../tests/fixtures/gradle/duplicate_class.log"),
+        "duplicate class",
+      ),
+    ] {
+      assert_eq!(diagnosed_names(fixture), vec![expected]);
+    }
+  }
+
+  #[test]
+  fn excerpt() {
+    let diagnoses = diagnose_gradle_failure(include_str!(
+      "../tests/fixtures/gradle/missing_platform.log"
+    ));
+    assert_eq!(
+      diagnoses[0].excerpt,
+      vec![
+        "* What went wrong:",
+        "Could not determine the dependencies of task ':app:compileDebugJavaWithJavac'.",
+        "> Failed to find target with hash string 'android-31' in: /home/user/Android/Sdk",
+        "",
+        "* Try:",
+      ]
+    );
+  }
+
+  #[test]
+  fn unknown_failure() {
+    assert!(diagnosed_names("FAILURE: Build failed with an exception.\n").is_empty());
+  }
+}
@@ -0,0 +1,125 @@
+use crate::signing::*;
+
+/// The language of the gradle build files of a project template. Newer SDL templates use the
+/// Kotlin DSL with `build.gradle.kts` files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GradleDsl {
+  #[default]
+  Groovy,
+  Kotlin,
+}
+
+impl GradleDsl {
+  /// The name of a build file in this DSL, e.g. `app/build.gradle.kts` for `app/build.gradle`.
+  pub fn file_name(self, groovy_file_name: &str) -> String {
+    match self {
+      GradleDsl::Groovy => groovy_file_name.to_string(),
+      GradleDsl::Kotlin => format!("{}.kts", groovy_file_name),
+    }
+  }
+
+  /// A string literal, e.g. `'text'` or `"text"`.
+  pub fn string(self, s: &str) -> String {
+    match self {
+      GradleDsl::Groovy => groovy_string(s),
+      GradleDsl::Kotlin => format!(
+        "\"{}\"",
+        s.replace('\\', "\\\\")
+          .replace('"', "\\\"")
+          .replace('$', "\\$")
+      ),
+    }
+  }
+
+  /// Sets a property, e.g. `minSdk 21` or `minSdk = 21`.
+  pub fn assign(self, name: &str, value: &str) -> String {
+    match self {
+      GradleDsl::Groovy => format!("{} {}", name, value),
+      GradleDsl::Kotlin => format!("{} = {}", name, value),
+    }
+  }
+
+  /// A method call statement, e.g. `implementation 'a:b:1'` or `implementation("a:b:1")`.
+  pub fn call(self, name: &str, args: &[&str]) -> String {
+    match self {
+      GradleDsl::Groovy => format!("{} {}", name, args.join(", ")),
+      GradleDsl::Kotlin => format!("{}({})", name, args.join(", ")),
+    }
+  }
+
+  /// Opens the block of an element of a container like `buildTypes`, which Kotlin needs to
+  /// `create` if it isn't predefined.
+  pub fn element_block(self, name: &str, predefined: bool) -> String {
+    match (self, predefined) {
+      (GradleDsl::Groovy, _) => format!("{} {{", name),
+      (GradleDsl::Kotlin, true) => format!("getByName(\"{}\") {{", name),
+      (GradleDsl::Kotlin, false) => format!("create(\"{}\") {{", name),
+    }
+  }
+
+  /// Reads a gradle property as string, e.g. one passed with `-P`.
+  pub fn project_property(self, name: &str) -> String {
+    match self {
+      GradleDsl::Groovy => format!("project.property({})", self.string(name)),
+      GradleDsl::Kotlin => format!("project.property({}) as String", self.string(name)),
+    }
+  }
+
+  /// The text of app/build.gradle blocks of the android extension are inserted at.
+  pub fn android_block_anchor(self) -> &'static str {
+    match self {
+      GradleDsl::Groovy => "    lintOptions {",
+      //Kotlin templates have no lintOptions, which AGP 8 removed
+      GradleDsl::Kotlin => "android {\n",
+    }
+  }
+
+  /// Replacement of [GradleDsl::android_block_anchor] inserting the block.
+  pub fn insert_android_block(self, block: &str) -> String {
+    match self {
+      GradleDsl::Groovy => format!("{}\n    lintOptions {{", block),
+      GradleDsl::Kotlin => format!("android {{\n{}\n", block),
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use crate::gradle_dsl::GradleDsl;
+
+  #[test]
+  fn gradle_dsl() {
+    assert_eq!(
+      "app/build.gradle",
+      GradleDsl::Groovy.file_name("app/build.gradle")
+    );
+    assert_eq!(
+      "build.gradle.kts",
+      GradleDsl::Kotlin.file_name("build.gradle")
+    );
+    assert_eq!("'it\\'s'", GradleDsl::Groovy.string("it's"));
+    assert_eq!("\"a\\\"\\$b\"", GradleDsl::Kotlin.string("a\"$b"));
+
+    let dependency = GradleDsl::Kotlin.string("org.libsdl.android:SDL3:3.2.0");
+    assert_eq!(
+      "implementation(\"org.libsdl.android:SDL3:3.2.0\")",
+      GradleDsl::Kotlin.call("implementation", &[&dependency])
+    );
+    assert_eq!(
+      "multiDexEnabled = true",
+      GradleDsl::Kotlin.assign("multiDexEnabled", "true")
+    );
+    assert_eq!(
+      "create(\"demo\") {",
+      GradleDsl::Kotlin.element_block("demo", false)
+    );
+    assert_eq!(
+      "release {",
+      GradleDsl::Groovy.element_block("release", true)
+    );
+    assert_eq!(
+      "project.property(\"storePassword\") as String",
+      GradleDsl::Kotlin.project_property("storePassword")
+    );
+  }
+}
@@ -0,0 +1,312 @@
+use std::fs::{read_to_string, write};
+use std::io::{stderr, Write};
+use std::path::Path;
+use std::process::{Command, Output};
+use std::sync::{Arc, Mutex};
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::command_runner::*;
+
+/// Name of the file in the android project with the number of tasks the gradle invocations ran,
+/// the total of the next progress bar.
+const TASK_COUNT_FILE: &str = ".cargo-sdl-apk-gradle-tasks";
+
+/// Lines of stdout printed if gradle fails, stderr is printed in full.
+const FAILURE_TAIL_LINES: usize = 80;
+
+const PROGRESS_BAR_WIDTH: usize = 25;
+
+/// Longest progress line, so it doesn't wrap in a standard terminal.
+const PROGRESS_LINE_WIDTH: usize = 79;
+
+lazy_static! {
+  static ref ANSI_ESCAPE_REGEX: Regex =
+    Regex::new(r"\x1b\[[0-9;]*[A-Za-z]").expect("invalid ansi escape regex");
+  //plain console, e.g. `> Task :app:mergeDebugResources UP-TO-DATE`
+  static ref TASK_REGEX: Regex = Regex::new(
+    r"^> Task (:\S+)(?: (?:UP-TO-DATE|NO-SOURCE|SKIPPED|FROM-CACHE|FAILED))?$"
+  )
+  .expect("invalid gradle task regex");
+  //rich console, e.g. `<=====--------> 38% EXECUTING [12s]`
+  static ref PERCENT_REGEX: Regex =
+    Regex::new(r"^<[=\-]*> (\d+)% [A-Z]+").expect("invalid gradle percent regex");
+  //rich console, a running task like `> :app:packageDebug`
+  static ref RUNNING_TASK_REGEX: Regex =
+    Regex::new(r"^> (:\S+)").expect("invalid gradle running task regex");
+}
+
+/// A line of gradle's output the progress is based on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GradleEvent {
+  /// A task was run, skipped or found up to date, from the plain console.
+  TaskDone(String),
+  /// A task is running, from the rich console.
+  TaskRunning(String),
+  /// The progress of the rich console in percent.
+  Percent(u32),
+}
+
+/// Parses a line of the plain or the rich console, which has its escape sequences removed.
+pub fn parse_gradle_line(line: &str) -> Option<GradleEvent> {
+  let line = ANSI_ESCAPE_REGEX.replace_all(line, "");
+  let line = line.trim_end();
+  if let Some(captures) = TASK_REGEX.captures(line) {
+    return Some(GradleEvent::TaskDone(captures[1].to_string()));
+  }
+  if let Some(captures) = PERCENT_REGEX.captures(line) {
+    return captures[1].parse().ok().map(GradleEvent::Percent);
+  }
+  RUNNING_TASK_REGEX
+    .captures(line)
+    .map(|captures| GradleEvent::TaskRunning(captures[1].to_string()))
+}
+
+/// The progress of a gradle invocation, as the tasks done of those the last invocation ran.
+#[derive(Debug, Clone, Default)]
+pub struct GradleProgress {
+  expected_tasks: Option<usize>,
+  tasks_done: usize,
+  percent: Option<u32>,
+  current_task: Option<String>,
+}
+
+impl GradleProgress {
+  pub fn new(expected_tasks: Option<usize>) -> Self {
+    Self {
+      expected_tasks,
+      ..Self::default()
+    }
+  }
+
+  /// Updates the progress with a line of gradle's output, returning whether it changed.
+  pub fn handle_line(&mut self, line: &str) -> bool {
+    match parse_gradle_line(line) {
+      Some(GradleEvent::TaskDone(task)) => {
+        self.tasks_done += 1;
+        self.current_task = Some(task);
+      }
+      Some(GradleEvent::TaskRunning(task)) => self.current_task = Some(task),
+      Some(GradleEvent::Percent(percent)) => self.percent = Some(percent.min(100)),
+      None => return false,
+    }
+    true
+  }
+
+  pub fn tasks_done(&self) -> usize {
+    self.tasks_done
+  }
+
+  /// The progress line like cargo's, e.g. `      Gradle [======>      ] 12/17: :app:packageDebug`.
+  pub fn render(&self) -> String {
+    let (fraction, count) = match (self.percent, self.expected_tasks) {
+      (Some(percent), _) => (Some(percent as f64 / 100.0), format!("{}%", percent)),
+      (None, Some(expected)) if expected > 0 => {
+        //more tasks than last time, the estimate is off but the bar stays below full
+        let total = expected.max(self.tasks_done + 1);
+        (
+          Some(self.tasks_done as f64 / total as f64),
+          format!("{}/{}", self.tasks_done, total),
+        )
+      }
+      _ => (None, format!("{} tasks", self.tasks_done)),
+    };
+    let mut line = format!("{:>12} ", "Gradle");
+    if let Some(fraction) = fraction {
+      let filled = (fraction * PROGRESS_BAR_WIDTH as f64) as usize;
+      line.push('[');
+      line.push_str(&"=".repeat(filled));
+      if filled < PROGRESS_BAR_WIDTH {
+        line.push('>');
+        line.push_str(&" ".repeat(PROGRESS_BAR_WIDTH - filled - 1));
+      }
+      line.push_str("] ");
+    }
+    line.push_str(&count);
+    if let Some(task) = &self.current_task {
+      line.push_str(": ");
+      line.push_str(task);
+    }
+    line.chars().take(PROGRESS_LINE_WIDTH).collect()
+  }
+}
+
+/// The number of tasks the last successful gradle invocation with these tasks ran.
+pub fn read_task_count(project_dir: &Path, tasks: &[String]) -> Option<usize> {
+  let key = tasks.join(" ");
+  read_to_string(project_dir.join(TASK_COUNT_FILE))
+    .ok()?
+    .lines()
+    .filter_map(|line| line.rsplit_once('='))
+    .find(|(k, _)| *k == key)
+    .and_then(|(_, count)| count.parse().ok())
+}
+
+/// Records the number of tasks a gradle invocation with these tasks ran, keeping the counts of
+/// other invocations.
+pub fn record_task_count(project_dir: &Path, tasks: &[String], count: usize) {
+  let path = project_dir.join(TASK_COUNT_FILE);
+  let key = tasks.join(" ");
+  let mut lines: Vec<String> = read_to_string(&path)
+    .unwrap_or_default()
+    .lines()
+    .filter(|line| line.rsplit_once('=').is_some_and(|(k, _)| k != key))
+    .map(|line| line.to_string())
+    .collect();
+  lines.push(format!("{}={}", key, count));
+  //the progress bar works without it
+  let _ = write(&path, lines.join("\n") + "\n");
+}
+
+/// Prints the end of the output of a failed gradle invocation which was hidden behind the
+/// progress bar: the last lines of stdout and all of stderr, where gradle reports the failure.
+pub fn print_gradle_output_tail(output: &Output) {
+  let stdout = String::from_utf8_lossy(&output.stdout);
+  let lines: Vec<&str> = stdout.lines().collect();
+  if lines.len() > FAILURE_TAIL_LINES {
+    println!(
+      "... {} lines, see the build log",
+      lines.len() - FAILURE_TAIL_LINES
+    );
+  }
+  for line in &lines[lines.len().saturating_sub(FAILURE_TAIL_LINES)..] {
+    println!("{}", line);
+  }
+  eprint!("{}", String::from_utf8_lossy(&output.stderr));
+}
+
+/// Runs gradle with a progress bar on stderr instead of its output, which only goes to the build
+/// log. The total of the bar is the number of tasks the same invocation ran the last time.
+pub fn run_gradle_with_progress(
+  gradle: &mut Command,
+  project_dir: &Path,
+  tasks: &[String],
+) -> Output {
+  gradle.arg("--console=plain");
+  let progress = Arc::new(Mutex::new(GradleProgress::new(read_task_count(
+    project_dir,
+    tasks,
+  ))));
+  let shown = progress.clone();
+  let output = run_command_with_line_handler(gradle, move |line| {
+    let mut progress = shown.lock().unwrap();
+    if progress.handle_line(line) {
+      let mut err = stderr();
+      let _ = write!(err, "\r\x1b[2K{}", progress.render());
+      let _ = err.flush();
+    }
+  });
+  eprint!("\r\x1b[2K");
+
+  let tasks_done = progress.lock().unwrap().tasks_done();
+  if output.status.success() && tasks_done > 0 {
+    record_task_count(project_dir, tasks, tasks_done);
+  }
+  if !output.status.success() {
+    print_gradle_output_tail(&output);
+  }
+  output
+}
+
+#[cfg(test)]
+mod test {
+  use tempfile::tempdir;
+
+  use crate::gradle_progress::{
+    parse_gradle_line, read_task_count, record_task_count, GradleEvent, GradleProgress,
+  };
+
+  fn run(fixture: &str, expected_tasks: Option<usize>) -> GradleProgress {
+    let mut progress = GradleProgress::new(expected_tasks);
+    for line in fixture.lines() {
+      progress.handle_line(line);
+    }
+    progress
+  }
+
+  #[test]
+  fn line_formats() {
+    assert_eq!(
+      parse_gradle_line("> Task :app:mergeDebugResources UP-TO-DATE"),
+      Some(GradleEvent::TaskDone(
+        ":app:mergeDebugResources".to_string()
+      ))
+    );
+    assert_eq!(
+      parse_gradle_line("> Task :app:compileReleaseJavaWithJavac FROM-CACHE"),
+      Some(GradleEvent::TaskDone(
+        ":app:compileReleaseJavaWithJavac".to_string()
+      ))
+    );
+    assert_eq!(
+      parse_gradle_line("\x1b[2K> :app:packageDebug\x1b[0K"),
+      Some(GradleEvent::TaskRunning(":app:packageDebug".to_string()))
+    );
+    assert_eq!(
+      parse_gradle_line("<=====--------> 38% EXECUTING [12s]"),
+      Some(GradleEvent::Percent(38))
+    );
+    assert_eq!(parse_gradle_line("> Configure project :app"), None);
+    assert_eq!(
+      parse_gradle_line("> Task :app:lint is not a task line"),
+      None
+    );
+    assert_eq!(parse_gradle_line("BUILD SUCCESSFUL in 41s"), None);
+  }
+
+  #[test]
+  fn fixtures() {
+    let agp7 = run(
+      include_str!("../tests/fixtures/gradle/progress_agp7_plain.log"),
+      Some(20),
+    );
+    assert_eq!(agp7.tasks_done(), 17);
+    assert_eq!(
+      agp7.render(),
+      "      Gradle [=====================>   ] 17/20: :app:assembleDebug"
+    );
+
+    let agp8 = run(
+      include_str!("../tests/fixtures/gradle/progress_agp8_plain.log"),
+      None,
+    );
+    assert_eq!(agp8.tasks_done(), 13);
+    assert_eq!(agp8.render(), "      Gradle 13 tasks: :app:assembleRelease");
+
+    let rich = run(
+      include_str!("../tests/fixtures/gradle/progress_rich.log"),
+      None,
+    );
+    assert_eq!(
+      rich.render(),
+      "      Gradle [===================>     ] 77%: :app:packageDebug"
+    );
+  }
+
+  #[test]
+  fn estimate_above_expected() {
+    let progress = run("> Task :app:preBuild\n> Task :app:packageDebug\n", Some(1));
+    assert_eq!(
+      progress.render(),
+      "      Gradle [================>        ] 2/3: :app:packageDebug"
+    );
+  }
+
+  #[test]
+  fn task_counts() {
+    let dir = tempdir().unwrap();
+    let assemble = vec!["assembleDebug".to_string()];
+    let lint = vec!["assembleDebug".to_string(), "lintDebug".to_string()];
+    assert_eq!(read_task_count(dir.path(), &assemble), None);
+    record_task_count(dir.path(), &assemble, 17);
+    record_task_count(dir.path(), &lint, 40);
+    record_task_count(dir.path(), &assemble, 18);
+    assert_eq!(read_task_count(dir.path(), &assemble), Some(18));
+    assert_eq!(read_task_count(dir.path(), &lint), Some(40));
+    assert_eq!(
+      read_task_count(dir.path(), &["bundleDebug".to_string()]),
+      None
+    );
+  }
+}
@@ -0,0 +1,266 @@
+use std::fs::{create_dir_all, remove_dir_all};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use toml::value::Value;
+
+use crate::command_runner::*;
+use crate::util::*;
+use crate::BuildProfile;
+
+/// A command of the android metadata run during the build, either a plain command line like
+/// `"scripts/build_assets.sh"` or a table with `command`, `args` and `output_dir`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BuildHook {
+  /// The metadata key of the hook, for messages.
+  pub name: &'static str,
+  pub command: String,
+  pub args: Vec<String>,
+  /// Where the prepackage hook writes the assets it generates, relative to the manifest.
+  pub output_dir: Option<String>,
+}
+
+impl BuildHook {
+  pub fn parse(name: &'static str, value: &Value) -> Result<Self, String> {
+    let invalid = || {
+      format!(
+        "{} must be a command or a table with command, args and output_dir",
+        name
+      )
+    };
+    let mut hook = Self {
+      name,
+      command: String::new(),
+      args: vec![],
+      output_dir: None,
+    };
+    match value {
+      Value::String(command) => {
+        let mut words = command.split_whitespace().map(|w| w.to_string());
+        hook.command = words.next().ok_or_else(invalid)?;
+        hook.args = words.collect();
+      }
+      Value::Table(table) => {
+        for key in table.keys() {
+          if !["command", "args", "output_dir"].contains(&key.as_str()) {
+            return Err(format!("Unknown key {} of {}", key, name));
+          }
+        }
+        hook.command = table
+          .get("command")
+          .and_then(|c| c.as_str())
+          .ok_or_else(invalid)?
+          .to_string();
+        if let Some(args) = table.get("args") {
+          hook.args = args
+            .as_array()
+            .and_then(|args| {
+              args
+                .iter()
+                .map(|a| a.as_str().map(|a| a.to_string()))
+                .collect::<Option<Vec<String>>>()
+            })
+            .ok_or_else(|| format!("args of {} must be a list of strings", name))?;
+        }
+        if let Some(output_dir) = table.get("output_dir") {
+          hook.output_dir = Some(
+            output_dir
+              .as_str()
+              .ok_or_else(|| format!("output_dir of {} must be a path", name))?
+              .to_string(),
+          );
+        }
+      }
+      _ => return Err(invalid()),
+    }
+    Ok(hook)
+  }
+
+  /// The program to run. Commands with a path are relative to the manifest, others are looked up
+  /// on the PATH.
+  fn program(&self, manifest_dir: &Path) -> PathBuf {
+    if self.command.contains('/') || self.command.contains('\\') {
+      manifest_dir.join(&self.command)
+    } else {
+      PathBuf::from(&self.command)
+    }
+  }
+
+  /// Runs the hook in the manifest dir with its output shown, failing if it exits with an error.
+  pub fn run(&self, manifest_dir: &Path, env: &[(&str, String)]) -> Result<(), String> {
+    let program = self.program(manifest_dir);
+    if program.is_absolute() && !program.is_file() {
+      return Err(format!("{} {} not found", self.name, program.display()));
+    }
+    println!("Running {} {}", self.name, self.command);
+    let status = run_command(
+      Command::new(&program)
+        .args(&self.args)
+        .current_dir(manifest_dir)
+        .envs(env.iter().map(|(k, v)| (k, v))),
+    );
+    if !status.success() {
+      return Err(format!(
+        "{} {} failed ({}), see its output above",
+        self.name, self.command, status
+      ));
+    }
+    Ok(())
+  }
+}
+
+fn get_hook(manifest_path: &Path, name: &'static str) -> Result<Option<BuildHook>, String> {
+  get_toml_entry(manifest_path, ["package", "metadata", "android", name])?
+    .map(|value| BuildHook::parse(name, &value))
+    .transpose()
+}
+
+/// The assets dir the prepackage hook writes to, `output_dir` of the hook or else a dir in
+/// `target/` which is emptied before each run.
+pub fn get_hook_assets_dir(
+  manifest_dir: &Path,
+  profile: BuildProfile,
+  hook: &BuildHook,
+) -> PathBuf {
+  match &hook.output_dir {
+    Some(output_dir) => manifest_dir.join(output_dir),
+    None => manifest_dir
+      .join("target")
+      .join(format!("android-hook-assets-{}", profile)),
+  }
+}
+
+/// Runs `prepackage_hook` of the android metadata after cargo built the libraries and before the
+/// android project is assembled. Returns the dir with the assets it generated, which are added to
+/// the assets of the APK.
+pub fn run_prepackage_hook(
+  manifest_path: &Path,
+  profile: BuildProfile,
+  targets: &[&str],
+) -> Result<Option<PathBuf>, String> {
+  let Some(hook) = get_hook(manifest_path, "prepackage_hook")? else {
+    return Ok(None);
+  };
+  let manifest_dir = manifest_path.parent().unwrap();
+  let out_dir = get_hook_assets_dir(manifest_dir, profile, &hook);
+  if hook.output_dir.is_none() && out_dir.exists() {
+    remove_dir_all(&out_dir).map_err(|e| format!("Unable to remove {:?}: {}", out_dir, e))?;
+  }
+  create_dir_all(&out_dir)
+    .map_err(|e| format!("Unable to create directory {:?}: {}", out_dir, e))?;
+
+  hook.run(
+    manifest_dir,
+    &[
+      ("PROFILE", profile.to_string()),
+      ("TARGETS", targets.join(",")),
+      ("OUT_ASSETS_DIR", out_dir.to_string_lossy().to_string()),
+    ],
+  )?;
+  Ok(Some(out_dir))
+}
+
+/// Runs `postbuild_hook` of the android metadata with the path of the finished APK, as last
+/// argument and as `APK_PATH`, and the AAB as `AAB_PATH` if one was built.
+pub fn run_postbuild_hook(
+  manifest_path: &Path,
+  profile: BuildProfile,
+  apk: &Path,
+  aab: Option<&Path>,
+) -> Result<(), String> {
+  let Some(mut hook) = get_hook(manifest_path, "postbuild_hook")? else {
+    return Ok(());
+  };
+  hook.args.push(apk.to_string_lossy().to_string());
+  let mut env = vec![
+    ("PROFILE", profile.to_string()),
+    ("APK_PATH", apk.to_string_lossy().to_string()),
+  ];
+  if let Some(aab) = aab {
+    env.push(("AAB_PATH", aab.to_string_lossy().to_string()));
+  }
+  hook.run(manifest_path.parent().unwrap(), &env)
+}
+
+#[cfg(test)]
+mod test {
+  use std::fs::{read_to_string, write};
+
+  use tempfile::tempdir;
+  use toml::value::Value;
+
+  use crate::hooks::{run_prepackage_hook, BuildHook};
+  use crate::BuildProfile;
+
+  fn parse(toml: &str) -> Result<BuildHook, String> {
+    let table = toml.parse::<toml::Table>().unwrap();
+    BuildHook::parse("prepackage_hook", &table["hook"])
+  }
+
+  #[test]
+  fn hook_config() {
+    let hook = parse("hook = \"scripts/build_assets.sh --fast\"").unwrap();
+    assert_eq!(hook.command, "scripts/build_assets.sh");
+    assert_eq!(hook.args, vec!["--fast"]);
+    assert_eq!(hook.output_dir, None);
+
+    let hook = parse(
+      "hook = { command = \"python3\", args = [\"tools/pack atlas.py\"], output_dir = \"gen\" }",
+    )
+    .unwrap();
+    assert_eq!(hook.command, "python3");
+    assert_eq!(hook.args, vec!["tools/pack atlas.py"]);
+    assert_eq!(hook.output_dir.as_deref(), Some("gen"));
+
+    assert!(parse("hook = { args = [\"x\"] }").is_err());
+    assert!(parse("hook = { command = \"x\", arg = [] }")
+      .unwrap_err()
+      .contains("Unknown key arg"));
+    assert!(parse("hook = \"  \"").is_err());
+    assert!(BuildHook::parse("postbuild_hook", &Value::Integer(1)).is_err());
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn prepackage_hook_env() {
+    let dir = tempdir().unwrap();
+    let manifest_path = dir.path().join("Cargo.toml");
+    write(
+      &manifest_path,
+      r#"
+[package]
+name = "game"
+
+[package.metadata.android]
+prepackage_hook = { command = "sh", args = ["-c", "echo $PROFILE $TARGETS > $OUT_ASSETS_DIR/build.txt"] }
+"#,
+    )
+    .unwrap();
+    let out_dir = run_prepackage_hook(
+      &manifest_path,
+      BuildProfile::Release,
+      &["aarch64-linux-android", "x86_64-linux-android"],
+    )
+    .unwrap()
+    .unwrap();
+    assert_eq!(
+      out_dir,
+      dir.path().join("target/android-hook-assets-release")
+    );
+    assert_eq!(
+      read_to_string(out_dir.join("build.txt")).unwrap(),
+      "release aarch64-linux-android,x86_64-linux-android\n"
+    );
+
+    write(
+      &manifest_path,
+      "[package]\nname = \"game\"\n[package.metadata.android]\nprepackage_hook = \"scripts/missing.sh\"\n",
+    )
+    .unwrap();
+    assert!(
+      run_prepackage_hook(&manifest_path, BuildProfile::Debug, &[])
+        .unwrap_err()
+        .contains("not found")
+    );
+  }
+}
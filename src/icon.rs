@@ -0,0 +1,170 @@
+use std::fs::{create_dir_all, read_dir, remove_file, write};
+use std::io::Cursor;
+use std::path::Path;
+
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageFormat, ImageReader};
+
+use crate::generated_files::*;
+
+/// Launcher icon sizes in pixels per mipmap density.
+const ICON_DENSITIES: &[(&str, u32)] = &[
+  ("mdpi", 48),
+  ("hdpi", 72),
+  ("xhdpi", 96),
+  ("xxhdpi", 144),
+  ("xxxhdpi", 192),
+];
+
+/// Smallest accepted source icon, the size of the largest density, so icons are never upscaled.
+pub const MIN_ICON_SIZE: u32 = 192;
+
+/// Loads a PNG, JPEG or WebP image.
+pub fn load_image(path: &Path) -> Result<DynamicImage, String> {
+  ImageReader::open(path)
+    .and_then(|reader| reader.with_guessed_format())
+    .map_err(|e| format!("Unable to read image {:?}: {}", path, e))?
+    .decode()
+    .map_err(|e| format!("Unable to decode image {:?}: {}", path, e))
+}
+
+/// Loads a PNG, JPEG or WebP icon and checks its size.
+pub fn load_icon(path: &Path) -> Result<DynamicImage, String> {
+  let image = load_image(path)?;
+  if image.width() < MIN_ICON_SIZE || image.height() < MIN_ICON_SIZE {
+    return Err(format!(
+      "Icon {:?} is {}x{}, but needs to be at least {}x{}",
+      path,
+      image.width(),
+      image.height(),
+      MIN_ICON_SIZE,
+      MIN_ICON_SIZE
+    ));
+  }
+  Ok(image)
+}
+
+pub fn encode_png(image: &DynamicImage) -> Vec<u8> {
+  let mut png = vec![];
+  image
+    .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+    .expect("Unable to encode image");
+  png
+}
+
+/// Scales the icon to the given size and encodes it as PNG.
+pub fn render_icon(image: &DynamicImage, size: u32) -> Vec<u8> {
+  encode_png(&image.resize_exact(size, size, FilterType::Lanczos3))
+}
+
+/// Removes launcher icons of the template which would take precedence over or sit next to the
+/// generated PNGs, like the WebP icons and adaptive icon definitions of newer SDL templates.
+fn remove_template_icons(res_dir: &Path) {
+  let Ok(entries) = read_dir(res_dir) else {
+    return;
+  };
+  for dir in entries.filter_map(|e| e.ok()).map(|e| e.path()) {
+    let is_mipmap = dir
+      .file_name()
+      .and_then(|name| name.to_str())
+      .is_some_and(|name| name.starts_with("mipmap-"));
+    if !is_mipmap {
+      continue;
+    }
+    let Ok(files) = read_dir(&dir) else {
+      continue;
+    };
+    for file in files.filter_map(|e| e.ok()).map(|e| e.path()) {
+      let stem = file.file_stem().and_then(|stem| stem.to_str());
+      let is_png = file.extension().is_some_and(|ext| ext == "png");
+      if stem == Some("ic_launcher") && !is_png {
+        let _ = remove_file(&file);
+      }
+    }
+  }
+}
+
+/// Writes the icon into all mipmap densities of the android project, replacing the icons of the
+/// template.
+pub fn copy_icon(
+  icon_path: &Path,
+  project_dir: &Path,
+  generated: &mut GeneratedFiles,
+) -> Result<(), String> {
+  let image = load_icon(icon_path)?;
+
+  let res_dir = project_dir.join("app/src/main/res");
+  remove_template_icons(&res_dir);
+  for (density, size) in ICON_DENSITIES {
+    let icon_file = format!("app/src/main/res/mipmap-{}/ic_launcher.png", density);
+    if !generated.claim(&icon_file) {
+      continue;
+    }
+    let dest = project_dir.join(icon_file);
+    create_dir_all(dest.parent().unwrap())
+      .map_err(|e| format!("Unable to create directory {:?}: {}", dest.parent(), e))?;
+    write(&dest, render_icon(&image, *size))
+      .map_err(|e| format!("Unable to write icon {:?}: {}", dest, e))?;
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod test {
+  use std::fs::{create_dir_all, write};
+
+  use image::{DynamicImage, ImageFormat, ImageReader, RgbImage};
+  use tempfile::tempdir;
+
+  use crate::generated_files::GeneratedFiles;
+  use crate::icon::{copy_icon, load_icon};
+
+  #[test]
+  fn jpeg_icon_converted() {
+    let dir = tempdir().unwrap();
+    let icon = dir.path().join("icon.jpg");
+    DynamicImage::ImageRgb8(RgbImage::new(256, 256))
+      .save_with_format(&icon, ImageFormat::Jpeg)
+      .unwrap();
+
+    let project = dir.path().join("project");
+    let template_icons = project.join("app/src/main/res/mipmap-anydpi-v26");
+    create_dir_all(&template_icons).unwrap();
+    write(template_icons.join("ic_launcher.xml"), "<adaptive-icon />").unwrap();
+    create_dir_all(project.join("app/src/main/res/mipmap-hdpi")).unwrap();
+    write(
+      project.join("app/src/main/res/mipmap-hdpi/ic_launcher.webp"),
+      "old",
+    )
+    .unwrap();
+
+    let mut generated = GeneratedFiles::load(&project, false);
+    copy_icon(&icon, &project, &mut generated).unwrap();
+
+    let mdpi = ImageReader::open(project.join("app/src/main/res/mipmap-mdpi/ic_launcher.png"))
+      .unwrap()
+      .with_guessed_format()
+      .unwrap();
+    assert_eq!(mdpi.format(), Some(ImageFormat::Png));
+    assert_eq!(mdpi.decode().unwrap().width(), 48);
+    assert!(project
+      .join("app/src/main/res/mipmap-xxxhdpi/ic_launcher.png")
+      .is_file());
+    assert!(!template_icons.join("ic_launcher.xml").exists());
+    assert!(!project
+      .join("app/src/main/res/mipmap-hdpi/ic_launcher.webp")
+      .exists());
+  }
+
+  #[test]
+  fn bad_icons_rejected() {
+    let dir = tempdir().unwrap();
+    assert!(load_icon(&dir.path().join("missing.png")).is_err());
+
+    let small = dir.path().join("small.png");
+    DynamicImage::ImageRgb8(RgbImage::new(100, 100))
+      .save(&small)
+      .unwrap();
+    assert!(load_icon(&small).unwrap_err().contains("100x100"));
+  }
+}
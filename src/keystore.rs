@@ -0,0 +1,193 @@
+use std::env;
+use std::fs::{copy, create_dir_all, read, remove_file, rename};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::command_runner::*;
+use crate::local_config::*;
+
+/// Store and key password of the Android debug keystore.
+pub const DEBUG_KEYSTORE_PASS: &str = "android";
+pub const DEBUG_KEY_ALIAS: &str = "androiddebugkey";
+/// Keystore type of newly generated debug keystores, the default of keytool since Java 9.
+pub const DEBUG_KEYSTORE_TYPE: &str = "pkcs12";
+
+/// Resolves the location of the debug keystore the same way the Android tooling does:
+/// `$ANDROID_USER_HOME/debug.keystore`, falling back to `~/.android/debug.keystore`.
+fn debug_keystore_path_from(android_user_home: Option<String>, home: Option<PathBuf>) -> PathBuf {
+  if let Some(android_user_home) = android_user_home {
+    return Path::new(&android_user_home).join("debug.keystore");
+  }
+
+  home
+    .expect("Unable to determine home directory for the debug keystore")
+    .join(".android/debug.keystore")
+}
+
+pub fn get_debug_keystore_path() -> PathBuf {
+  let home = env::var_os("HOME")
+    .or_else(|| env::var_os("USERPROFILE"))
+    .map(PathBuf::from);
+  debug_keystore_path_from(env::var("ANDROID_USER_HOME").ok(), home)
+}
+
+/// Returns the path of the debug keystore, creating it with the canonical parameters if it doesn't
+/// exist yet.
+pub fn ensure_debug_keystore() -> PathBuf {
+  let path = get_debug_keystore_path();
+  if path.exists() {
+    return path;
+  }
+
+  println!("Generating debug keystore {:?}...", path);
+  if let Some(dir) = path.parent() {
+    create_dir_all(dir).unwrap_or_else(|_| panic!("Unable to create directory {:?}", dir));
+  }
+
+  run_command_checked(
+    Command::new("keytool")
+      .arg("-genkey")
+      .arg("-v")
+      .arg("-keystore")
+      .arg(&path)
+      .arg("-storetype")
+      .arg(DEBUG_KEYSTORE_TYPE)
+      .arg("-storepass")
+      .arg(DEBUG_KEYSTORE_PASS)
+      .arg("-alias")
+      .arg(DEBUG_KEY_ALIAS)
+      .arg("-keypass")
+      .arg(DEBUG_KEYSTORE_PASS)
+      .arg("-keyalg")
+      .arg("RSA")
+      .arg("-keysize")
+      .arg("2048")
+      .arg("-validity")
+      .arg("10000")
+      .arg("-dname")
+      .arg("CN=Android Debug,O=Android,C=US"),
+  );
+
+  path
+}
+
+/// Where older versions generated the key unconfigured release builds were signed with.
+const LEGACY_RELEASE_KEYSTORE: &str =
+  "target/android-project/app/build/outputs/apk/release/app-release.jks";
+/// Store password and alias of the generated release key, keytool's default alias.
+pub const GENERATED_RELEASE_KEYSTORE_PASS: &str = "android";
+pub const GENERATED_RELEASE_KEY_ALIAS: &str = "mykey";
+
+/// The release key generated by older versions once it is moved out of `target/`, used for
+/// release builds without a configured keystore.
+pub fn get_release_keystore_path(manifest_dir: &Path) -> PathBuf {
+  get_local_dir(manifest_dir).join("release.jks")
+}
+
+/// Moves the release key older versions generated in `target/` to the local directory, before the
+/// old project directory is removed. Apps signed with it can only be updated when signed with it
+/// again. Returns the new location if a key was moved.
+pub fn migrate_legacy_release_keystore(manifest_dir: &Path) -> Result<Option<PathBuf>, String> {
+  let legacy = manifest_dir.join(LEGACY_RELEASE_KEYSTORE);
+  if !legacy.is_file() {
+    return Ok(None);
+  }
+  let keystore = get_release_keystore_path(manifest_dir);
+  if keystore.exists() {
+    if read(&keystore).ok() == read(&legacy).ok() {
+      remove_file(&legacy).map_err(|e| format!("Unable to remove {}: {}", legacy.display(), e))?;
+      return Ok(None);
+    }
+    return Err(format!(
+      "Both {} and {} exist and differ. Keep the release key your published APKs are signed \
+      with at {} and remove the other one",
+      legacy.display(),
+      keystore.display(),
+      keystore.display()
+    ));
+  }
+
+  ensure_local_dir(manifest_dir)?;
+  //the local dir may be on another file system than target/
+  if rename(&legacy, &keystore).is_err() {
+    copy(&legacy, &keystore)
+      .and_then(|_| remove_file(&legacy))
+      .map_err(|e| {
+        format!(
+          "Unable to move {} to {}: {}",
+          legacy.display(),
+          keystore.display(),
+          e
+        )
+      })?;
+  }
+  println!(
+    "Moved the generated release key from {} to {}, which isn't removed with target/. APKs signed \
+    with it can only be updated with APKs signed with it, back it up or commit it.",
+    legacy.display(),
+    keystore.display()
+  );
+  Ok(Some(keystore))
+}
+
+#[cfg(test)]
+mod test {
+  use std::fs::{create_dir_all, read, read_to_string, write};
+  use std::path::PathBuf;
+
+  use tempfile::tempdir;
+
+  use crate::keystore::{
+    debug_keystore_path_from, get_release_keystore_path, migrate_legacy_release_keystore,
+    LEGACY_RELEASE_KEYSTORE,
+  };
+
+  #[test]
+  fn debug_keystore_location() {
+    assert_eq!(
+      debug_keystore_path_from(None, Some(PathBuf::from("/home/user"))),
+      PathBuf::from("/home/user/.android/debug.keystore")
+    );
+    assert_eq!(
+      debug_keystore_path_from(
+        Some("/opt/android-user".to_string()),
+        Some(PathBuf::from("/home/user"))
+      ),
+      PathBuf::from("/opt/android-user/debug.keystore")
+    );
+  }
+
+  #[test]
+  fn legacy_release_keystore_migration() {
+    let dir = tempdir().unwrap();
+    let keystore = get_release_keystore_path(dir.path());
+    assert_eq!(Ok(None), migrate_legacy_release_keystore(dir.path()));
+    assert!(!keystore.exists());
+
+    let legacy = dir.path().join(LEGACY_RELEASE_KEYSTORE);
+    create_dir_all(legacy.parent().unwrap()).unwrap();
+    write(&legacy, "old key").unwrap();
+    //the .gitignore of older versions ignoring the whole local dir is replaced
+    create_dir_all(dir.path().join(".cargo-sdl-apk")).unwrap();
+    write(dir.path().join(".cargo-sdl-apk/.gitignore"), "*\n").unwrap();
+    assert_eq!(
+      Ok(Some(keystore.clone())),
+      migrate_legacy_release_keystore(dir.path())
+    );
+    assert!(!legacy.exists());
+    assert_eq!(b"old key".to_vec(), read(&keystore).unwrap());
+    let gitignore = read_to_string(dir.path().join(".cargo-sdl-apk/.gitignore")).unwrap();
+    assert!(gitignore.contains("!release.jks"));
+
+    //a leftover copy of the moved key is removed
+    write(&legacy, "old key").unwrap();
+    assert_eq!(Ok(None), migrate_legacy_release_keystore(dir.path()));
+    assert!(!legacy.exists());
+
+    //a different key is never overwritten
+    write(&legacy, "other key").unwrap();
+    assert!(migrate_legacy_release_keystore(dir.path()).is_err());
+    assert_eq!(b"old key".to_vec(), read(&keystore).unwrap());
+    assert!(legacy.exists());
+  }
+}
@@ -0,0 +1,221 @@
+use std::fs::{canonicalize, read_to_string, write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::command_runner::*;
+use crate::warnings::*;
+use crate::BuildProfile;
+
+/// Name of the file in `target/<profile>` recording the outputs of the last build of the profile.
+const LAST_BUILD_FILE: &str = "cargo-sdl-apk-last-build";
+
+/// The outputs of the last build of a profile, so they can be found without building again.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LastBuild {
+  pub apk: PathBuf,
+  pub project_dir: PathBuf,
+  /// The jniLibs dir of the android project with the unstripped libraries, one dir per abi.
+  pub symbols_dir: PathBuf,
+  pub app_id: String,
+  pub flavor: Option<String>,
+}
+
+/// An output of the last build `cargo sdl-apk where` prints.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuildArtifact {
+  Apk,
+  Project,
+  Symbols,
+}
+
+impl LastBuild {
+  /// The record as `key=value` lines.
+  pub fn describe(&self) -> String {
+    let mut description = format!(
+      "apk={}\nproject={}\nsymbols={}\napp_id={}\n",
+      self.apk.display(),
+      self.project_dir.display(),
+      self.symbols_dir.display(),
+      self.app_id
+    );
+    if let Some(flavor) = &self.flavor {
+      description.push_str(&format!("flavor={}\n", flavor));
+    }
+    description
+  }
+
+  /// Parses a record written by [LastBuild::describe], `None` if an entry is missing.
+  pub fn parse(content: &str) -> Option<Self> {
+    let get = |key: &str| {
+      content
+        .lines()
+        .filter_map(|line| line.split_once('='))
+        .find(|(k, _)| *k == key)
+        .map(|(_, value)| value.to_string())
+    };
+    Some(Self {
+      apk: get("apk")?.into(),
+      project_dir: get("project")?.into(),
+      symbols_dir: get("symbols")?.into(),
+      app_id: get("app_id")?,
+      flavor: get("flavor"),
+    })
+  }
+
+  pub fn get_artifact(&self, artifact: BuildArtifact) -> &Path {
+    match artifact {
+      BuildArtifact::Apk => &self.apk,
+      BuildArtifact::Project => &self.project_dir,
+      BuildArtifact::Symbols => &self.symbols_dir,
+    }
+  }
+}
+
+fn get_last_build_path(manifest_dir: &Path, profile: BuildProfile) -> PathBuf {
+  manifest_dir
+    .join("target")
+    .join(profile.to_string())
+    .join(LAST_BUILD_FILE)
+}
+
+/// Records the outputs of a build of the profile.
+pub fn record_last_build(manifest_dir: &Path, profile: BuildProfile, last_build: &LastBuild) {
+  let path = get_last_build_path(manifest_dir, profile);
+  if let Err(e) = write(&path, last_build.describe()) {
+    warn(
+      &LAST_BUILD,
+      format!("Unable to record the build in {}: {}", path.display(), e),
+    );
+  }
+}
+
+/// Reads the outputs of the last build of the profile.
+pub fn read_last_build(manifest_dir: &Path, profile: BuildProfile) -> Result<LastBuild, String> {
+  let path = get_last_build_path(manifest_dir, profile);
+  let content = read_to_string(&path).map_err(|_| {
+    format!(
+      "No {} build recorded, build it with `cargo sdl-apk build` first",
+      profile
+    )
+  })?;
+  LastBuild::parse(&content).ok_or_else(|| {
+    format!(
+      "Invalid build record {}, build again to replace it",
+      path.display()
+    )
+  })
+}
+
+/// The unstripped libraries of the last build of the profile, one dir per abi, which may be in a
+/// project dir given with `--project-dir`. Without a record it is the jniLibs dir of the main
+/// source set of the profile's project.
+pub fn get_last_build_symbols_dir(manifest_dir: &Path, profile: BuildProfile) -> PathBuf {
+  read_last_build(manifest_dir, profile)
+    .map(|last_build| last_build.symbols_dir)
+    .unwrap_or_else(|_| {
+      manifest_dir
+        .join("target")
+        .join(format!("android-project-{}", profile))
+        .join("app/src/main/jniLibs")
+    })
+}
+
+/// The canonical path of an output of the last build of the profile, failing if it was removed
+/// since.
+pub fn get_last_build_artifact(
+  manifest_dir: &Path,
+  profile: BuildProfile,
+  artifact: BuildArtifact,
+) -> Result<PathBuf, String> {
+  let last_build = read_last_build(manifest_dir, profile)?;
+  let path = last_build.get_artifact(artifact);
+  canonicalize(path).map_err(|_| {
+    format!(
+      "{} of the last {} build doesn't exist anymore, build again",
+      path.display(),
+      profile
+    )
+  })
+}
+
+/// Opens the directory containing the file in the file manager of the platform.
+pub fn reveal_in_file_manager(path: &Path) {
+  let dir = path.parent().unwrap_or(path);
+  let program = if cfg!(target_os = "macos") {
+    "open"
+  } else if cfg!(windows) {
+    "explorer"
+  } else {
+    "xdg-open"
+  };
+  //explorer exits with 1 even if it opened the dir, so its status is ignored
+  if let Err(e) = run_command_output(Command::new(program).arg(dir)) {
+    eprintln!(
+      "WARNING: Unable to open {} with {}: {}",
+      dir.display(),
+      program,
+      e
+    );
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::fs::{create_dir_all, write};
+  use std::path::PathBuf;
+
+  use tempfile::tempdir;
+
+  use crate::last_build::{
+    get_last_build_artifact, read_last_build, record_last_build, BuildArtifact, LastBuild,
+  };
+  use crate::BuildProfile;
+
+  #[test]
+  fn last_build_record() {
+    let dir = tempdir().unwrap();
+    let manifest_dir = dir.path().join("my game");
+    let project_dir = manifest_dir.join("target/android-project-release");
+    create_dir_all(&project_dir).unwrap();
+    create_dir_all(manifest_dir.join("target/release")).unwrap();
+    let apk = manifest_dir.join("target/release/app-demo.apk");
+    write(&apk, "apk").unwrap();
+
+    assert!(read_last_build(&manifest_dir, BuildProfile::Release)
+      .unwrap_err()
+      .starts_with("No release build recorded"));
+
+    let last_build = LastBuild {
+      apk: apk.clone(),
+      symbols_dir: project_dir.join("app/src/demo/jniLibs"),
+      project_dir: project_dir.clone(),
+      app_id: "com.example.game.demo".to_string(),
+      flavor: Some("demo".to_string()),
+    };
+    record_last_build(&manifest_dir, BuildProfile::Release, &last_build);
+    assert_eq!(
+      read_last_build(&manifest_dir, BuildProfile::Release),
+      Ok(last_build)
+    );
+    assert!(read_last_build(&manifest_dir, BuildProfile::Debug).is_err());
+
+    assert_eq!(
+      get_last_build_artifact(&manifest_dir, BuildProfile::Release, BuildArtifact::Apk),
+      Ok(apk.canonicalize().unwrap())
+    );
+    //symbols of a build which didn't happen in this test
+    assert!(
+      get_last_build_artifact(&manifest_dir, BuildProfile::Release, BuildArtifact::Symbols)
+        .unwrap_err()
+        .contains("doesn't exist anymore")
+    );
+
+    assert_eq!(LastBuild::parse("apk=/game/app.apk\n"), None);
+    assert_eq!(
+      LastBuild::parse("apk=a\nproject=p\nsymbols=s\napp_id=com.example.game\n")
+        .unwrap()
+        .symbols_dir,
+      PathBuf::from("s")
+    );
+  }
+}
@@ -0,0 +1,489 @@
+use std::fmt::{Display, Formatter};
+use std::fs::{read_to_string, write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use toml::value::Value;
+use toml::Table;
+
+use crate::local_config::*;
+
+/// Key of the local config with the launch profiles.
+const LAUNCH_KEY: &str = "launch";
+
+/// File of the local directory with the launch profile last used on each device. It is kept apart
+/// from the hand written config.toml, which writing it would strip of its comments.
+const LAUNCH_LAST_FILE: &str = "launch-last.toml";
+
+/// The value of an intent extra, passed to `am start` with the option of its type.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExtraValue {
+  String(String),
+  Int(i32),
+  Long(i64),
+  Float(f32),
+  Bool(bool),
+}
+
+impl ExtraValue {
+  /// The option of `am start` for extras of the type.
+  fn am_option(&self) -> &'static str {
+    match self {
+      ExtraValue::String(_) => "--es",
+      ExtraValue::Int(_) => "--ei",
+      ExtraValue::Long(_) => "--el",
+      ExtraValue::Float(_) => "--ef",
+      ExtraValue::Bool(_) => "--ez",
+    }
+  }
+
+  /// Converts a value of a launch profile, integers out of the range of an int become longs.
+  fn from_toml(value: &Value) -> Option<Self> {
+    match value {
+      Value::String(value) => Some(ExtraValue::String(value.clone())),
+      Value::Integer(value) => Some(
+        i32::try_from(*value)
+          .map(ExtraValue::Int)
+          .unwrap_or(ExtraValue::Long(*value)),
+      ),
+      Value::Float(value) => Some(ExtraValue::Float(*value as f32)),
+      Value::Boolean(value) => Some(ExtraValue::Bool(*value)),
+      _ => None,
+    }
+  }
+}
+
+impl Display for ExtraValue {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match self {
+      ExtraValue::String(value) => write!(f, "{}", value),
+      ExtraValue::Int(value) => write!(f, "{}", value),
+      ExtraValue::Long(value) => write!(f, "{}", value),
+      ExtraValue::Float(value) => write!(f, "{}", value),
+      ExtraValue::Bool(value) => write!(f, "{}", value),
+    }
+  }
+}
+
+/// An extra of the intent `run` starts the app with, read by the app with e.g. `getIntExtra`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IntentExtra {
+  pub key: String,
+  pub value: ExtraValue,
+}
+
+impl IntentExtra {
+  /// The arguments of `am start` adding the extra, unquoted.
+  pub fn am_args(&self) -> [String; 3] {
+    [
+      self.value.am_option().to_string(),
+      self.key.clone(),
+      self.value.to_string(),
+    ]
+  }
+}
+
+impl FromStr for IntentExtra {
+  type Err = String;
+
+  /// Parses `key=value`, a string, or `int:key=3`, `long:key=3`, `float:key=0.5`, `bool:key=true`
+  /// and `string:key=value` for typed extras.
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let error = || {
+      format!(
+        "invalid extra \"{}\", expected [int:|long:|float:|bool:|string:]key=value",
+        s
+      )
+    };
+    let (key, value) = s.split_once('=').ok_or_else(error)?;
+    let (kind, key) = match key.split_once(':') {
+      Some((kind, key)) if ["int", "long", "float", "bool", "string"].contains(&kind) => {
+        (kind, key)
+      }
+      _ => ("string", key),
+    };
+    if key.is_empty() {
+      return Err(error());
+    }
+    let value = match kind {
+      "int" => ExtraValue::Int(value.parse().map_err(|_| error())?),
+      "long" => ExtraValue::Long(value.parse().map_err(|_| error())?),
+      "float" => ExtraValue::Float(value.parse().map_err(|_| error())?),
+      "bool" => ExtraValue::Bool(value.parse().map_err(|_| error())?),
+      _ => ExtraValue::String(value.to_string()),
+    };
+    Ok(Self {
+      key: key.to_string(),
+      value,
+    })
+  }
+}
+
+/// How `run` launches the app: the extras of `--extra` and the profile of `--launch-profile`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Launch {
+  pub extras: Vec<IntentExtra>,
+  /// A profile of `[launch.profile.<name>]` in the local config.
+  pub profile: Option<String>,
+  /// Launch without the profile last used on the device, and forget it.
+  pub no_profile: bool,
+}
+
+/// Quotes an argument for the shell of the device, which `adb shell` passes its joined arguments
+/// to. Arguments of only safe characters stay as they are.
+pub fn shell_quote(arg: &str) -> String {
+  let safe = |c: char| c.is_ascii_alphanumeric() || "-_./:=@%+,".contains(c);
+  if !arg.is_empty() && arg.chars().all(safe) {
+    return arg.to_string();
+  }
+  format!("'{}'", arg.replace('\'', "'\\''"))
+}
+
+/// The arguments of `adb shell` starting the activity with the extras, waiting for the launch.
+pub fn am_start_args(activity: &str, extras: &[IntentExtra]) -> Vec<String> {
+  let mut args: Vec<String> = ["am", "start", "-W", "-n", activity]
+    .iter()
+    .map(|arg| shell_quote(arg))
+    .collect();
+  for extra in extras {
+    args.extend(extra.am_args().iter().map(|arg| shell_quote(arg)));
+  }
+  args
+}
+
+fn get_launch_table<'a>(config: &'a Table, key: &str) -> Result<Option<&'a Table>, String> {
+  match config.get(LAUNCH_KEY).map(|launch| launch.get(key)) {
+    None | Some(None) => Ok(None),
+    Some(Some(Value::Table(table))) => Ok(Some(table)),
+    Some(Some(value)) => Err(format!(
+      "{}.{} of the local config must be a table, but is {}",
+      LAUNCH_KEY, key, value
+    )),
+  }
+}
+
+/// The extras of the launch profile `[launch.profile.<name>]` of the local config.
+pub fn parse_launch_profile(config: &Table, name: &str) -> Result<Vec<IntentExtra>, String> {
+  let profile = get_launch_table(config, "profile")?
+    .and_then(|profiles| profiles.get(name))
+    .ok_or_else(|| {
+      format!(
+        "launch profile {} not found, define it as [{}.profile.{}] in the local config",
+        name, LAUNCH_KEY, name
+      )
+    })?;
+  let extras = match profile.get("extras") {
+    None => return Ok(vec![]),
+    Some(Value::Table(extras)) => extras,
+    Some(value) => {
+      return Err(format!(
+        "extras of launch profile {} must be a table, but is {}",
+        name, value
+      ))
+    }
+  };
+  extras
+    .iter()
+    .map(|(key, value)| {
+      ExtraValue::from_toml(value)
+        .map(|value| IntentExtra {
+          key: key.clone(),
+          value,
+        })
+        .ok_or_else(|| {
+          format!(
+            "extra {} of launch profile {} must be a string, number or bool, but is {}",
+            key, name, value
+          )
+        })
+    })
+    .collect()
+}
+
+fn get_launch_last_path(manifest_dir: &Path) -> PathBuf {
+  get_local_dir(manifest_dir).join(LAUNCH_LAST_FILE)
+}
+
+/// Reads the launch profiles last used by device serial, empty if there are none.
+pub fn read_last_launch_profiles(manifest_dir: &Path) -> Result<Table, String> {
+  let path = get_launch_last_path(manifest_dir);
+  match read_to_string(&path) {
+    Ok(content) => content
+      .parse::<Table>()
+      .map_err(|e| format!("Invalid {}: {}", path.display(), e)),
+    Err(_) => Ok(Table::new()),
+  }
+}
+
+fn write_last_launch_profiles(manifest_dir: &Path, last: &Table) -> Result<(), String> {
+  ensure_local_dir(manifest_dir)?;
+  let path = get_launch_last_path(manifest_dir);
+  write(&path, last.to_string()).map_err(|e| format!("Unable to write {}: {}", path.display(), e))
+}
+
+/// The launch profile last used on the device.
+pub fn get_last_launch_profile(last: &Table, serial: &str) -> Option<String> {
+  last
+    .get(serial)
+    .and_then(|name| name.as_str())
+    .map(str::to_string)
+}
+
+/// Remembers the launch profile used on the device, or with `None` forgets it.
+pub fn set_last_launch_profile(last: &mut Table, serial: &str, profile: Option<&str>) {
+  match profile {
+    Some(profile) => last.insert(serial.to_string(), Value::String(profile.to_string())),
+    None => last.remove(serial),
+  };
+}
+
+/// The profile `run` uses on the device: the requested one, else the one last used on it unless
+/// `--extra` or `--no-launch-profile` was given.
+pub fn select_launch_profile(launch: &Launch, last: Option<&str>) -> Option<String> {
+  if launch.no_profile {
+    return None;
+  }
+  launch.profile.clone().or_else(|| {
+    last
+      .filter(|_| launch.extras.is_empty())
+      .map(str::to_string)
+  })
+}
+
+/// The extras `run` starts the app with on the device, those of the launch profile followed by
+/// the ones of `--extra`. The profile used is remembered for the device.
+pub fn resolve_launch_extras(
+  manifest_dir: &Path,
+  serial: &str,
+  launch: &Launch,
+) -> Result<Vec<IntentExtra>, String> {
+  let config = read_local_config(manifest_dir)?;
+  let mut last_profiles = read_last_launch_profiles(manifest_dir)?;
+  let last = get_last_launch_profile(&last_profiles, serial);
+  let profile = select_launch_profile(launch, last.as_deref());
+  let mut extras = match &profile {
+    Some(name) => parse_launch_profile(&config, name)?,
+    None => vec![],
+  };
+  if let Some(name) = &profile {
+    if launch.profile.is_none() {
+      println!("Launch profile {} (last used on {})", name, serial);
+    }
+  }
+  extras.extend(launch.extras.iter().cloned());
+  if (launch.profile.is_some() || launch.no_profile) && last != profile {
+    set_last_launch_profile(&mut last_profiles, serial, profile.as_deref());
+    write_last_launch_profiles(manifest_dir, &last_profiles)?;
+  }
+  Ok(extras)
+}
+
+#[cfg(test)]
+mod test {
+  use std::fs::{create_dir_all, read_to_string, write};
+  use std::process::Command;
+
+  use tempfile::tempdir;
+  use toml::Table;
+
+  use crate::launch_profile::{
+    am_start_args, get_last_launch_profile, parse_launch_profile, read_last_launch_profiles,
+    resolve_launch_extras, select_launch_profile, set_last_launch_profile, shell_quote, ExtraValue,
+    IntentExtra, Launch,
+  };
+  use crate::local_config::get_local_config_path;
+
+  fn extra(s: &str) -> IntentExtra {
+    s.parse().unwrap()
+  }
+
+  #[test]
+  fn extra_args() {
+    assert_eq!(
+      IntentExtra {
+        key: "level".to_string(),
+        value: ExtraValue::Int(3)
+      },
+      extra("int:level=3")
+    );
+    assert_eq!(
+      ["--ez", "cheats", "true"],
+      extra("bool:cheats=true").am_args()
+    );
+    assert_eq!(["--el", "seed", "-5"], extra("long:seed=-5").am_args());
+    assert_eq!(["--ef", "speed", "0.5"], extra("float:speed=0.5").am_args());
+    assert_eq!(["--es", "name", "a=b"], extra("name=a=b").am_args());
+    assert_eq!(["--es", "map", ""], extra("string:map=").am_args());
+    //only the known types are prefixes
+    assert_eq!(["--es", "url:x", "1"], extra("url:x=1").am_args());
+
+    for invalid in ["level", "=3", "int:=3", "int:level=three", "bool:cheats=1"] {
+      assert!(invalid.parse::<IntentExtra>().is_err(), "{}", invalid);
+    }
+  }
+
+  #[test]
+  fn shell_quoting() {
+    assert_eq!(
+      "org.example/.MainActivity",
+      shell_quote("org.example/.MainActivity")
+    );
+    assert_eq!("-5", shell_quote("-5"));
+    assert_eq!("''", shell_quote(""));
+    assert_eq!("'two words'", shell_quote("two words"));
+    assert_eq!("'it'\\''s'", shell_quote("it's"));
+    assert_eq!("'$HOME'", shell_quote("$HOME"));
+    assert_eq!("'a;reboot'", shell_quote("a;reboot"));
+
+    assert_eq!(
+      vec![
+        "am",
+        "start",
+        "-W",
+        "-n",
+        "org.example/org.libsdl.app.SDLActivity",
+        "--es",
+        "'player name'",
+        "'Bob'\\''s \"game\"'",
+        "--ei",
+        "level",
+        "3",
+      ],
+      am_start_args(
+        "org.example/org.libsdl.app.SDLActivity",
+        &[extra("player name=Bob's \"game\""), extra("int:level=3")]
+      )
+    );
+  }
+
+  #[test]
+  fn shell_quoting_round_trip() {
+    //the shell of the device is a POSIX sh like the one of the host
+    let values = [
+      "",
+      "plain",
+      "two words",
+      "it's",
+      "''",
+      "\"quoted\"",
+      "$HOME `id` $(id)",
+      "back\\slash",
+      "a;b&c|d>e<f",
+      "*?[x]~#!",
+      "tab\tand\nnewline",
+    ];
+    let script = format!(
+      "printf '%s\\0' {}",
+      values
+        .iter()
+        .map(|v| shell_quote(v))
+        .collect::<Vec<_>>()
+        .join(" ")
+    );
+    let Ok(output) = Command::new("sh").arg("-c").arg(script).output() else {
+      return;
+    };
+    let printed = String::from_utf8(output.stdout).unwrap();
+    let mut printed: Vec<&str> = printed.split('\0').collect();
+    assert_eq!(Some(""), printed.pop());
+    assert_eq!(values.to_vec(), printed);
+  }
+
+  #[test]
+  fn launch_profiles() {
+    let config: Table = r#"
+      default_device = "emulator-5554"
+
+      [launch.profile.level3]
+      extras = { level = 3, cheats = true, name = "speed run", seed = 8589934592, speed = 1.5 }
+
+      [launch.profile.empty]
+
+      [launch.profile.invalid]
+      extras = { list = [1] }
+    "#
+    .parse()
+    .unwrap();
+    let mut extras = parse_launch_profile(&config, "level3").unwrap();
+    extras.sort_by(|a, b| a.key.cmp(&b.key));
+    assert_eq!(
+      vec![
+        extra("bool:cheats=true"),
+        extra("int:level=3"),
+        extra("name=speed run"),
+        extra("long:seed=8589934592"),
+        extra("float:speed=1.5"),
+      ],
+      extras
+    );
+    assert_eq!(Ok(vec![]), parse_launch_profile(&config, "empty"));
+    assert!(parse_launch_profile(&config, "invalid").is_err());
+    assert!(parse_launch_profile(&config, "level4").is_err());
+  }
+
+  #[test]
+  fn last_launch_profiles() {
+    let dir = tempdir().unwrap();
+    let config_path = get_local_config_path(dir.path());
+    let config = "# levels to test\n[launch.profile.level3]\nextras = { level = 3 }\n";
+    create_dir_all(config_path.parent().unwrap()).unwrap();
+    write(&config_path, config).unwrap();
+
+    let mut last = read_last_launch_profiles(dir.path()).unwrap();
+    assert_eq!(None, get_last_launch_profile(&last, "emulator-5554"));
+    set_last_launch_profile(&mut last, "emulator-5554", Some("level3"));
+    set_last_launch_profile(&mut last, "R58M", Some("empty"));
+    assert_eq!(
+      Some("level3".to_string()),
+      get_last_launch_profile(&last, "emulator-5554")
+    );
+    set_last_launch_profile(&mut last, "R58M", None);
+    assert_eq!(None, get_last_launch_profile(&last, "R58M"));
+
+    let launch = Launch {
+      profile: Some("level3".to_string()),
+      ..Launch::default()
+    };
+    assert_eq!(
+      Ok(vec![extra("int:level=3")]),
+      resolve_launch_extras(dir.path(), "R58M", &launch)
+    );
+    assert_eq!(
+      Ok(vec![extra("int:level=3")]),
+      resolve_launch_extras(dir.path(), "R58M", &Launch::default())
+    );
+    //the hand written config keeps its comments
+    assert_eq!(config, read_to_string(&config_path).unwrap());
+  }
+
+  #[test]
+  fn launch_profile_selection() {
+    let bare = Launch::default();
+    assert_eq!(
+      Some("level3".to_string()),
+      select_launch_profile(&bare, Some("level3"))
+    );
+    assert_eq!(None, select_launch_profile(&bare, None));
+
+    let requested = Launch {
+      profile: Some("boss".to_string()),
+      ..Launch::default()
+    };
+    assert_eq!(
+      Some("boss".to_string()),
+      select_launch_profile(&requested, Some("level3"))
+    );
+
+    let extras = Launch {
+      extras: vec![extra("int:level=5")],
+      ..Launch::default()
+    };
+    assert_eq!(None, select_launch_profile(&extras, Some("level3")));
+
+    let none = Launch {
+      no_profile: true,
+      ..Launch::default()
+    };
+    assert_eq!(None, select_launch_profile(&none, Some("level3")));
+  }
+}
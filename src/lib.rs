@@ -0,0 +1,200 @@
+//! Build Android packages that use SDL.
+//!
+//! This is the library behind `cargo sdl-apk`, for build tools which build, install and run APKs
+//! without shelling out to the binary. Everything exported here follows semver; the binary's
+//! command line is a wrapper around it.
+//!
+//! ```no_run
+//! use cargo_sdl_apk::AndroidBuild;
+//!
+//! let output = AndroidBuild::new("Cargo.toml")
+//!   .targets(["aarch64-linux-android"])
+//!   .build()?;
+//! cargo_sdl_apk::install(&output)?;
+//! cargo_sdl_apk::run(&output)?;
+//! # Ok::<(), cargo_sdl_apk::Error>(())
+//! ```
+//!
+//! The environment is the same as for the binary: `ANDROID_HOME`, `ANDROID_NDK_HOME` and `SDL` must
+//! be set, and the android metadata of the package is read from its manifest. Progress is printed
+//! to stdout and the output of the spawned commands goes to `target/cargo-sdl-apk.log`.
+
+use std::fmt::{Display, Formatter};
+
+pub use adb::ReversePort;
+pub use android_build::{force_reinstall, install, run, AndroidBuild, BuildOutput, Signing};
+pub use artifacts::Artifact;
+pub use error::{Error, Result};
+pub use launch_profile::{ExtraValue, IntentExtra, Launch};
+pub use signing::SigningMode;
+pub use symbols_bundle::SymbolsBundle;
+
+mod android_build;
+
+mod error;
+
+mod commands;
+
+mod build_bin_as_lib;
+
+mod util;
+
+mod android_project;
+
+mod reproducible;
+
+mod apk_report;
+
+mod sdk;
+
+mod keystore;
+
+mod generated_files;
+
+mod preflight;
+
+mod cargo_check;
+
+mod cli;
+
+mod command_runner;
+
+mod rustflags;
+
+mod manifest;
+
+mod manifest_check;
+
+mod project_template;
+
+mod gradle_diagnostics;
+
+mod signing;
+
+mod adb;
+
+mod google_services;
+
+mod presets;
+
+mod theme;
+
+mod smoke_test;
+
+mod monkey;
+
+mod app_data;
+
+mod debugger;
+
+mod release_preset;
+
+mod offline;
+
+mod icon;
+
+mod splash;
+
+mod sdl_cache;
+
+mod abi_check;
+
+mod application;
+
+mod app_info;
+
+mod features;
+
+mod flavors;
+
+mod ndk;
+
+mod last_build;
+
+mod arm_mode;
+
+mod anr;
+
+mod template_compat;
+
+mod local_config;
+
+mod hooks;
+
+mod rust_logcat;
+
+mod artifacts;
+
+mod gradle_progress;
+
+mod build_info;
+
+mod crash_report;
+
+mod sdl_java;
+
+mod metadata_schema;
+
+mod ci;
+
+mod placeholders;
+
+mod user_config;
+
+mod setup;
+
+/// The profile to build with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuildProfile {
+  Debug,
+  Release,
+}
+
+impl Display for BuildProfile {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match self {
+      BuildProfile::Debug => write!(f, "debug"),
+      BuildProfile::Release => write!(f, "release"),
+    }
+  }
+}
+
+/// Runs the `cargo sdl-apk` command line.
+#[doc(hidden)]
+pub fn cli_main() {
+  commands::main()
+}
+
+mod targets;
+
+mod native_libs;
+
+mod gradle_dsl;
+
+mod packaging;
+
+mod symbols_bundle;
+
+mod prebuilt_sdl;
+
+mod cargo_profile;
+
+mod project_diff;
+
+mod split_debuginfo;
+
+mod bridge_methods;
+
+mod local_properties;
+
+mod template_prune;
+
+mod warnings;
+
+mod launch_profile;
+
+mod sdl_defaults;
+
+mod sdl_fetch;
+
+mod baseline_profile;
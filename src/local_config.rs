@@ -0,0 +1,130 @@
+use std::fs::{create_dir_all, read_to_string, write};
+use std::path::{Path, PathBuf};
+
+use toml::value::Value;
+use toml::Table;
+
+/// Directory next to Cargo.toml with the files of the tool which outlive `target/`: the settings of
+/// the developer's machine and the generated release key.
+const LOCAL_DIR: &str = ".cargo-sdl-apk";
+
+/// Keeps the machine specific files of the local directory out of git without touching the
+/// .gitignore of the project. The release key is left to be versioned, it can't be regenerated.
+const LOCAL_DIR_GITIGNORE: &str = "*\n!.gitignore\n!release.jks\n";
+
+/// The .gitignore of older versions, which ignored the whole directory.
+const OLD_LOCAL_DIR_GITIGNORE: &str = "*\n";
+
+pub fn get_local_dir(manifest_dir: &Path) -> PathBuf {
+  manifest_dir.join(LOCAL_DIR)
+}
+
+pub fn get_local_config_path(manifest_dir: &Path) -> PathBuf {
+  get_local_dir(manifest_dir).join("config.toml")
+}
+
+/// Creates the local directory with its .gitignore, which is updated if it is the one of an older
+/// version.
+pub fn ensure_local_dir(manifest_dir: &Path) -> Result<PathBuf, String> {
+  let dir = get_local_dir(manifest_dir);
+  create_dir_all(&dir).map_err(|e| format!("Unable to create {}: {}", dir.display(), e))?;
+  let gitignore = dir.join(".gitignore");
+  match read_to_string(&gitignore) {
+    Ok(content) if content != OLD_LOCAL_DIR_GITIGNORE => {}
+    _ => write(&gitignore, LOCAL_DIR_GITIGNORE)
+      .map_err(|e| format!("Unable to write {}: {}", gitignore.display(), e))?,
+  }
+  Ok(dir)
+}
+
+/// Reads the local config, empty if there is none.
+pub fn read_local_config(manifest_dir: &Path) -> Result<Table, String> {
+  let path = get_local_config_path(manifest_dir);
+  match read_to_string(&path) {
+    Ok(content) => content
+      .parse::<Table>()
+      .map_err(|e| format!("Invalid local config {}: {}", path.display(), e)),
+    Err(_) => Ok(Table::new()),
+  }
+}
+
+/// Reads a string of the local config.
+pub fn get_local_config_string(manifest_dir: &Path, key: &str) -> Result<Option<String>, String> {
+  match read_local_config(manifest_dir)?.get(key) {
+    None => Ok(None),
+    Some(Value::String(value)) => Ok(Some(value.clone())),
+    Some(value) => Err(format!(
+      "{} in {} must be a string, but is {}",
+      key,
+      get_local_config_path(manifest_dir).display(),
+      value
+    )),
+  }
+}
+
+/// Sets or with `None` removes a value of the local config, keeping the other values.
+pub fn set_local_config_value(
+  manifest_dir: &Path,
+  key: &str,
+  value: Option<Value>,
+) -> Result<(), String> {
+  let mut config = read_local_config(manifest_dir)?;
+  match value {
+    Some(value) => config.insert(key.to_string(), value),
+    None => config.remove(key),
+  };
+
+  ensure_local_dir(manifest_dir)?;
+  let path = get_local_config_path(manifest_dir);
+  write(&path, config.to_string()).map_err(|e| format!("Unable to write {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod test {
+  use std::fs::{read_to_string, write};
+
+  use tempfile::tempdir;
+  use toml::value::Value;
+
+  use crate::local_config::{
+    get_local_config_path, get_local_config_string, set_local_config_value,
+  };
+
+  #[test]
+  fn local_config_values() {
+    let dir = tempdir().unwrap();
+    assert_eq!(
+      get_local_config_string(dir.path(), "default_device"),
+      Ok(None)
+    );
+
+    set_local_config_value(
+      dir.path(),
+      "default_device",
+      Some(Value::String("R58M123ABC".to_string())),
+    )
+    .unwrap();
+    set_local_config_value(dir.path(), "other", Some(Value::Integer(1))).unwrap();
+    assert_eq!(
+      get_local_config_string(dir.path(), "default_device"),
+      Ok(Some("R58M123ABC".to_string()))
+    );
+    assert!(get_local_config_string(dir.path(), "other").is_err());
+    assert_eq!(
+      read_to_string(dir.path().join(".cargo-sdl-apk/.gitignore")).unwrap(),
+      "*\n!.gitignore\n!release.jks\n"
+    );
+
+    set_local_config_value(dir.path(), "default_device", None).unwrap();
+    assert_eq!(
+      get_local_config_string(dir.path(), "default_device"),
+      Ok(None)
+    );
+    assert!(read_to_string(get_local_config_path(dir.path()))
+      .unwrap()
+      .contains("other = 1"));
+
+    write(get_local_config_path(dir.path()), "default_device =").unwrap();
+    assert!(get_local_config_string(dir.path(), "default_device").is_err());
+  }
+}
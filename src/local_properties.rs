@@ -0,0 +1,162 @@
+use std::cmp::Ordering;
+use std::fs::{read_to_string, write};
+use std::path::Path;
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::gradle_dsl::*;
+use crate::sdk::*;
+use crate::sdl_cache::*;
+
+/// First version of the Android Gradle Plugin with `ndkVersion` in the android extension.
+const NDK_VERSION_AGP_VERSION: &str = "3.5";
+
+lazy_static! {
+  static ref NDK_VERSION_REGEX: Regex =
+    Regex::new(r"(?m)^([ \t]*)ndkVersion\b.*$").expect("invalid ndk version regex");
+}
+
+/// Escapes a value of a `.properties` file like Android Studio does, e.g. `C\:\\Android\\Sdk`.
+pub fn escape_properties_value(value: &str) -> String {
+  value.replace('\\', "\\\\").replace(':', "\\:")
+}
+
+/// The `local.properties` pointing gradle at the sdk and NDK this tool uses, `None` if neither is
+/// known.
+pub fn render_local_properties(sdk_dir: Option<&Path>, ndk_dir: Option<&Path>) -> Option<String> {
+  if sdk_dir.is_none() && ndk_dir.is_none() {
+    return None;
+  }
+  let mut content =
+    "## Written by cargo-sdl-apk on every build with the sdk and NDK it uses.\n".to_string();
+  for (key, dir) in [("sdk.dir", sdk_dir), ("ndk.dir", ndk_dir)] {
+    if let Some(dir) = dir {
+      content.push_str(&format!(
+        "{}={}\n",
+        key,
+        escape_properties_value(&dir.to_string_lossy())
+      ));
+    }
+  }
+  Some(content)
+}
+
+/// Writes the `local.properties` of the android project, unless it already has the content, e.g.
+/// after Android Studio replaced it or the env vars changed.
+pub fn write_local_properties(
+  project_dir: &Path,
+  sdk_dir: Option<&Path>,
+  ndk_dir: Option<&Path>,
+) -> Result<(), String> {
+  let Some(content) = render_local_properties(sdk_dir, ndk_dir) else {
+    return Ok(());
+  };
+  let path = project_dir.join("local.properties");
+  if read_to_string(&path).is_ok_and(|current| current == content) {
+    return Ok(());
+  }
+  write(&path, content).map_err(|e| format!("unable to write {}: {}", path.display(), e))
+}
+
+/// The version of the NDK in the dir, `Pkg.Revision` of its `source.properties`.
+pub fn get_ndk_version(ndk_dir: &Path) -> Option<String> {
+  parse_ndk_revision(&read_to_string(ndk_dir.join("source.properties")).ok()?)
+}
+
+/// Whether the Android Gradle Plugin of the template knows `ndkVersion`, assumed for unknown ones.
+pub fn supports_ndk_version(agp_version: Option<&str>) -> bool {
+  agp_version.is_none_or(|v| compare_versions(v, NDK_VERSION_AGP_VERSION) != Ordering::Less)
+}
+
+/// Sets `ndkVersion` of app/build.gradle, so AGP uses the NDK of `ndk.dir` rather than the one it
+/// bundles for C sources. An `ndkVersion` of the template is replaced.
+pub fn set_ndk_version(content: &str, ndk_version: &str, dsl: GradleDsl) -> String {
+  let assignment = dsl.assign("ndkVersion", &dsl.string(ndk_version));
+  if NDK_VERSION_REGEX.is_match(content) {
+    return NDK_VERSION_REGEX
+      .replace(content, |captures: &regex::Captures| {
+        format!("{}{}", &captures[1], assignment)
+      })
+      .to_string();
+  }
+  content.replace(
+    dsl.android_block_anchor(),
+    &dsl.insert_android_block(&format!("    {}", assignment)),
+  )
+}
+
+#[cfg(test)]
+mod test {
+  use std::fs::{read_to_string, write};
+  use std::path::Path;
+
+  use tempfile::tempdir;
+
+  use crate::gradle_dsl::GradleDsl;
+  use crate::local_properties::{
+    render_local_properties, set_ndk_version, supports_ndk_version, write_local_properties,
+  };
+  use crate::util::parse_local_properties_sdk_dir;
+
+  #[test]
+  fn local_properties() {
+    let windows_sdk = r"C:\Users\me\AppData\Local\Android\Sdk";
+    let content = render_local_properties(
+      Some(Path::new(windows_sdk)),
+      Some(Path::new(r"C:\Android\ndk\26.1.10909125")),
+    )
+    .unwrap();
+    assert!(content.contains("sdk.dir=C\\:\\\\Users\\\\me\\\\AppData\\\\Local\\\\Android\\\\Sdk\n"));
+    assert!(content.contains("ndk.dir=C\\:\\\\Android\\\\ndk\\\\26.1.10909125\n"));
+    //reads back like the local.properties of Android Studio
+    assert_eq!(
+      Some(Path::new(windows_sdk).to_path_buf()),
+      parse_local_properties_sdk_dir(&content)
+    );
+
+    assert_eq!(
+      Some(
+        "## Written by cargo-sdl-apk on every build with the sdk and NDK it uses.\n\
+        sdk.dir=/opt/android-sdk\n"
+          .to_string()
+      ),
+      render_local_properties(Some(Path::new("/opt/android-sdk")), None)
+    );
+    assert_eq!(None, render_local_properties(None, None));
+
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("local.properties");
+    write(&path, "sdk.dir=/studio/sdk\n").unwrap();
+    write_local_properties(dir.path(), Some(Path::new("/opt/sdk")), None).unwrap();
+    assert!(read_to_string(&path)
+      .unwrap()
+      .ends_with("sdk.dir=/opt/sdk\n"));
+    //nothing known, the file is left alone
+    write_local_properties(dir.path(), None, None).unwrap();
+    assert!(read_to_string(&path)
+      .unwrap()
+      .ends_with("sdk.dir=/opt/sdk\n"));
+  }
+
+  #[test]
+  fn ndk_version() {
+    let groovy =
+      "android {\n    compileSdk 34\n    lintOptions {\n        abortOnError false\n    }\n}\n";
+    assert_eq!(
+      "android {\n    compileSdk 34\n    ndkVersion '26.1.10909125'\n    lintOptions {\n        abortOnError false\n    }\n}\n",
+      set_ndk_version(groovy, "26.1.10909125", GradleDsl::Groovy)
+    );
+    let kotlin =
+      "android {\n    namespace = \"org.libsdl.app\"\n    ndkVersion = \"25.2.9519653\"\n}\n";
+    assert_eq!(
+      "android {\n    namespace = \"org.libsdl.app\"\n    ndkVersion = \"26.1.10909125\"\n}\n",
+      set_ndk_version(kotlin, "26.1.10909125", GradleDsl::Kotlin)
+    );
+
+    assert!(supports_ndk_version(None));
+    assert!(supports_ndk_version(Some("3.5.0")));
+    assert!(supports_ndk_version(Some("8.1.1")));
+    assert!(!supports_ndk_version(Some("3.4.2")));
+  }
+}
@@ -0,0 +1,1279 @@
+use std::collections::BTreeMap;
+use std::fs::{read_to_string, write};
+use std::ops::Range;
+use std::path::Path;
+
+use lazy_static::lazy_static;
+use regex::{Regex, RegexBuilder};
+use toml::value::Value;
+
+/// The attributes and end of a start tag. Quoted attribute values may contain `>`.
+const TAG_REST: &str = r#"(?:[^>"']|"[^"]*"|'[^']*')*>"#;
+
+fn start_tag_regex(tag: &str) -> Regex {
+  Regex::new(&format!(r"<{}\b{}", tag, TAG_REST)).expect("invalid start tag regex")
+}
+
+lazy_static! {
+  static ref MANIFEST_TAG_CONTENT_REGEX: Regex =
+    RegexBuilder::new(&format!(r"<manifest\b{}(.*)</manifest>", TAG_REST))
+      .dot_matches_new_line(true)
+      .build()
+      .expect("invalid manifest tag regex");
+  /// Comments, CDATA sections and processing instructions, whose text isn't markup.
+  static ref COMMENT_REGEX: Regex =
+    RegexBuilder::new(r"<!--.*?-->|<!\[CDATA\[.*?\]\]>|<\?.*?\?>")
+      .dot_matches_new_line(true)
+      .build()
+      .expect("invalid comment regex");
+  static ref ROOT_ELEMENT_REGEX: Regex =
+    start_tag_regex("(uses-permission-sdk-23|uses-permission|uses-sdk|uses-feature|application)");
+  static ref NAME_ATTRIBUTE_REGEX: Regex = attribute_regex("name");
+  static ref MAX_SDK_VERSION_ATTRIBUTE_REGEX: Regex = attribute_regex("maxSdkVersion");
+  static ref ACTIVITY_REGEX: Regex = start_tag_regex("activity");
+  static ref APPLICATION_REGEX: Regex = start_tag_regex("application");
+  static ref META_DATA_REGEX: Regex = start_tag_regex("meta-data");
+  static ref CATEGORY_REGEX: Regex = start_tag_regex("category");
+  static ref QUERIES_REGEX: Regex =
+    RegexBuilder::new(&format!(r"<queries\b{}.*?</queries>", TAG_REST))
+      .dot_matches_new_line(true)
+      .build()
+      .expect("invalid queries regex");
+  static ref MANIFEST_TAG_REGEX: Regex = start_tag_regex("manifest");
+  static ref COMPONENT_REGEX: Regex = start_tag_regex("(activity-alias|activity|service|receiver)");
+  static ref WHITESPACE_REGEX: Regex = Regex::new(r"\s+").expect("invalid whitespace regex");
+}
+
+/// First api level requiring `android:exported` on components with an intent filter.
+pub const EXPORTED_REQUIRED_SDK_VERSION: u32 = 31;
+
+/// Parses `exported` of the android metadata, the `android:exported` of components by name.
+pub fn parse_exported_overrides(value: &Value) -> Result<BTreeMap<String, bool>, String> {
+  let table = value
+    .as_table()
+    .ok_or("exported must be a table of component names to booleans")?;
+  table
+    .iter()
+    .map(|(name, exported)| match exported.as_bool() {
+      Some(exported) => Ok((name.clone(), exported)),
+      None => Err(format!("exported.{} must be a boolean", name)),
+    })
+    .collect()
+}
+
+/// Namespace of the manifest merger instructions, like `tools:node`.
+const TOOLS_NAMESPACE: &str = "http://schemas.android.com/tools";
+
+/// An attribute of the android namespace, not matching e.g. `tools:name` or `dist:name`.
+fn attribute_regex(name: &str) -> Regex {
+  Regex::new(&format!(
+    r#"\bandroid:{}\s*=\s*"([^"]*)""#,
+    regex::escape(name)
+  ))
+  .expect("invalid attribute regex")
+}
+
+/// The manifest element declaring a permission.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PermissionElement {
+  UsesPermission,
+  /// Only requested on api level 23 and above.
+  UsesPermissionSdk23,
+}
+
+impl PermissionElement {
+  pub fn tag(&self) -> &str {
+    match self {
+      PermissionElement::UsesPermission => "uses-permission",
+      PermissionElement::UsesPermissionSdk23 => "uses-permission-sdk-23",
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Permission {
+  pub name: String,
+  pub element: PermissionElement,
+  pub max_sdk_version: Option<u32>,
+}
+
+impl Permission {
+  pub fn new(name: &str) -> Self {
+    Self {
+      name: name.to_string(),
+      element: PermissionElement::UsesPermission,
+      max_sdk_version: None,
+    }
+  }
+
+  /// Fully qualified name of the permission, e.g. `android.permission.CAMERA` for `camera`.
+  pub fn qualified_name(&self) -> String {
+    format!("android.permission.{}", self.name.to_uppercase())
+  }
+
+  pub fn render(&self) -> String {
+    let mut element = format!(
+      "<{} android:name=\"{}\"",
+      self.element.tag(),
+      self.qualified_name()
+    );
+    if let Some(max_sdk_version) = self.max_sdk_version {
+      element.push_str(&format!(" android:maxSdkVersion=\"{}\"", max_sdk_version));
+    }
+    element.push_str(" />");
+    element
+  }
+}
+
+fn get_sdk_version(table: &toml::Table, key: &str) -> Result<Option<u32>, String> {
+  table
+    .get(key)
+    .map(|v| {
+      v.as_integer()
+        .and_then(|v| u32::try_from(v).ok())
+        .ok_or_else(|| format!("{} of permission must be an sdk version, found: {}", key, v))
+    })
+    .transpose()
+}
+
+/// Prefix of `permissions` entries naming a preset, which permission names can't contain, so
+/// e.g. `bluetooth` stays the `BLUETOOTH` permission.
+pub const PERMISSION_PRESET_PREFIX: &str = "preset:";
+
+/// A permission set a `permissions` entry can name instead of a permission, with
+/// [PERMISSION_PRESET_PREFIX].
+pub struct PermissionPreset {
+  pub name: &'static str,
+  /// The permissions with their max sdk version. Permissions added in later api levels need no
+  /// gate, older devices ignore them, while the ones they replace are limited to the api levels
+  /// before.
+  pub permissions: &'static [(&'static str, Option<u32>)],
+}
+
+/// Available permission presets. New presets only need an entry here.
+pub const PERMISSION_PRESETS: &[PermissionPreset] = &[
+  PermissionPreset {
+    name: "network",
+    permissions: &[("INTERNET", None), ("ACCESS_NETWORK_STATE", None)],
+  },
+  PermissionPreset {
+    name: "photos",
+    permissions: &[
+      ("READ_MEDIA_IMAGES", None),
+      ("READ_EXTERNAL_STORAGE", Some(32)),
+    ],
+  },
+  PermissionPreset {
+    name: "videos",
+    permissions: &[
+      ("READ_MEDIA_VIDEO", None),
+      ("READ_EXTERNAL_STORAGE", Some(32)),
+    ],
+  },
+  PermissionPreset {
+    name: "bluetooth",
+    permissions: &[
+      ("BLUETOOTH_CONNECT", None),
+      ("BLUETOOTH_SCAN", None),
+      ("BLUETOOTH", Some(30)),
+      ("BLUETOOTH_ADMIN", Some(30)),
+    ],
+  },
+];
+
+/// The permissions of the preset entry, `None` for names which aren't presets.
+pub fn expand_permission_preset(name: &str) -> Result<Option<Vec<Permission>>, String> {
+  let Some(preset_name) = name.strip_prefix(PERMISSION_PRESET_PREFIX) else {
+    return Ok(None);
+  };
+  let preset = PERMISSION_PRESETS
+    .iter()
+    .find(|preset| preset.name == preset_name)
+    .ok_or_else(|| {
+      format!(
+        "unknown permission preset {:?}, available: {}",
+        preset_name,
+        PERMISSION_PRESETS
+          .iter()
+          .map(|preset| preset.name)
+          .collect::<Vec<_>>()
+          .join(", ")
+      )
+    })?;
+  Ok(Some(
+    preset
+      .permissions
+      .iter()
+      .map(|(name, max_sdk_version)| Permission {
+        max_sdk_version: *max_sdk_version,
+        ..Permission::new(name)
+      })
+      .collect(),
+  ))
+}
+
+/// Parses the `permissions` android metadata. Entries are either permission names, presets of
+/// [PERMISSION_PRESETS] like `preset:network` or tables like
+/// `{ name = "CAMERA", min_declared_sdk = 23, max_sdk_version = 28 }`.
+pub fn parse_permissions(value: &Value) -> Result<Vec<Permission>, String> {
+  let entries = value
+    .as_array()
+    .ok_or_else(|| format!("permissions must be an array, found: {}", value))?;
+
+  let mut permissions = vec![];
+  for entry in entries {
+    match entry {
+      Value::String(name) => match expand_permission_preset(name)? {
+        Some(preset) => permissions.extend(preset),
+        None => permissions.push(Permission::new(name)),
+      },
+      Value::Table(table) => {
+        let name = table
+          .get("name")
+          .and_then(|name| name.as_str())
+          .ok_or_else(|| format!("permission entry needs a name: {}", entry))?;
+        let element = match get_sdk_version(table, "min_declared_sdk")? {
+          None => PermissionElement::UsesPermission,
+          Some(23) => PermissionElement::UsesPermissionSdk23,
+          Some(v) => {
+            return Err(format!(
+              "min_declared_sdk of permission {} must be 23, found: {}",
+              name, v
+            ))
+          }
+        };
+        permissions.push(Permission {
+          name: name.to_string(),
+          element,
+          max_sdk_version: get_sdk_version(table, "max_sdk_version")?,
+        });
+      }
+      _ => return Err(format!("invalid permission entry: {}", entry)),
+    }
+  }
+  Ok(permissions)
+}
+
+/// A `uses-feature` declaration of a hardware or software feature.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UsesFeature {
+  pub name: String,
+  pub required: bool,
+  pub version: Option<u32>,
+}
+
+impl UsesFeature {
+  pub fn new(name: &str, required: bool) -> Self {
+    Self {
+      name: name.to_string(),
+      required,
+      version: None,
+    }
+  }
+
+  pub fn render(&self) -> String {
+    let mut element = format!(
+      "<uses-feature android:name=\"{}\" android:required=\"{}\"",
+      self.name, self.required
+    );
+    if let Some(version) = self.version {
+      element.push_str(&format!(" android:version=\"{}\"", version));
+    }
+    element.push_str(" />");
+    element
+  }
+}
+
+/// An intent the app wants to resolve, declared in the queries of the manifest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryIntent {
+  pub action: String,
+  pub category: Option<String>,
+  pub data_scheme: Option<String>,
+  pub data_mime_type: Option<String>,
+}
+
+impl QueryIntent {
+  /// Renders the intent element, with its children indented relative to the given indentation of
+  /// the element.
+  pub fn render(&self, indent: &str) -> String {
+    let mut children = vec![format!("<action android:name=\"{}\" />", self.action)];
+    if let Some(category) = &self.category {
+      children.push(format!("<category android:name=\"{}\" />", category));
+    }
+    if self.data_scheme.is_some() || self.data_mime_type.is_some() {
+      let mut data = "<data".to_string();
+      if let Some(scheme) = &self.data_scheme {
+        data.push_str(&format!(" android:scheme=\"{}\"", scheme));
+      }
+      if let Some(mime_type) = &self.data_mime_type {
+        data.push_str(&format!(" android:mimeType=\"{}\"", mime_type));
+      }
+      data.push_str(" />");
+      children.push(data);
+    }
+
+    let mut element = "<intent>\n".to_string();
+    for child in children {
+      element.push_str(&format!("{}    {}\n", indent, child));
+    }
+    element.push_str(&format!("{}</intent>", indent));
+    element
+  }
+}
+
+/// Packages and intents the app needs to see despite package visibility filtering on api level 30
+/// and above.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Queries {
+  pub packages: Vec<String>,
+  pub intents: Vec<QueryIntent>,
+}
+
+impl Queries {
+  pub fn is_empty(&self) -> bool {
+    self.packages.is_empty() && self.intents.is_empty()
+  }
+
+  /// Renders the children of the queries element at the given indentation.
+  fn render_children(&self, indent: &str) -> Vec<String> {
+    let packages = self
+      .packages
+      .iter()
+      .map(|package| format!("<package android:name=\"{}\" />", package));
+    let intents = self.intents.iter().map(|intent| intent.render(indent));
+    packages.chain(intents).collect()
+  }
+}
+
+fn get_optional_string(table: &toml::Table, key: &str) -> Result<Option<String>, String> {
+  table
+    .get(key)
+    .map(|v| {
+      v.as_str()
+        .map(|v| v.to_string())
+        .ok_or_else(|| format!("{} of query intent must be a string, found: {}", key, v))
+    })
+    .transpose()
+}
+
+/// Parses the `queries` android metadata, a table like
+/// `{ packages = ["com.android.chrome"], intents = [{ action = "android.intent.action.VIEW", data_scheme = "https" }] }`.
+pub fn parse_queries(value: &Value) -> Result<Queries, String> {
+  let table = value
+    .as_table()
+    .ok_or_else(|| format!("queries must be a table, found: {}", value))?;
+  let array = |key: &str| -> Result<Vec<Value>, String> {
+    match table.get(key) {
+      None => Ok(vec![]),
+      Some(Value::Array(entries)) => Ok(entries.clone()),
+      Some(v) => Err(format!("queries.{} must be an array, found: {}", key, v)),
+    }
+  };
+
+  let packages = array("packages")?
+    .iter()
+    .map(|package| {
+      package
+        .as_str()
+        .map(|package| package.to_string())
+        .ok_or_else(|| format!("queried package must be a string, found: {}", package))
+    })
+    .collect::<Result<_, _>>()?;
+  let intents = array("intents")?
+    .iter()
+    .map(|intent| {
+      let intent = intent
+        .as_table()
+        .ok_or_else(|| format!("queried intent must be a table, found: {}", intent))?;
+      Ok(QueryIntent {
+        action: get_optional_string(intent, "action")?.ok_or_else(|| {
+          format!(
+            "queried intent needs an action: {}",
+            Value::Table(intent.clone())
+          )
+        })?,
+        category: get_optional_string(intent, "category")?,
+        data_scheme: get_optional_string(intent, "data_scheme")?,
+        data_mime_type: get_optional_string(intent, "data_mime_type")?,
+      })
+    })
+    .collect::<Result<_, String>>()?;
+  Ok(Queries { packages, intents })
+}
+
+/// An element directly below the manifest tag.
+struct RootElement {
+  tag: String,
+  name: Option<String>,
+  range: Range<usize>,
+}
+
+/// AndroidManifest.xml of the generated project, edited in place so the formatting and comments of
+/// the SDL template are kept.
+pub struct AndroidManifest {
+  content: String,
+}
+
+impl AndroidManifest {
+  pub fn new(content: String) -> Self {
+    Self { content }
+  }
+
+  pub fn load(path: &Path) -> Self {
+    Self::new(read_to_string(path).unwrap_or_else(|_| panic!("can't read manifest {:?}", path)))
+  }
+
+  pub fn save(&self, path: &Path) {
+    write(path, self.content()).unwrap_or_else(|_| panic!("can't write manifest {:?}", path));
+  }
+
+  pub fn content(&self) -> &str {
+    &self.content
+  }
+
+  /// The content with comments, CDATA sections and processing instructions blanked out, so tags
+  /// in them, like a commented out manifest tag of a template, aren't found. Offsets are the same
+  /// as in the content.
+  fn markup(&self) -> String {
+    let mut markup = self.content.clone().into_bytes();
+    for comment in COMMENT_REGEX.find_iter(&self.content) {
+      markup[comment.range()].fill(b' ');
+    }
+    String::from_utf8(markup).expect("comments are blanked out as a whole")
+  }
+
+  fn content_range(&self) -> Range<usize> {
+    MANIFEST_TAG_CONTENT_REGEX
+      .captures(&self.markup())
+      .and_then(|captures| captures.get(1))
+      .expect("can't find manifest tag content")
+      .range()
+  }
+
+  /// Finds the root elements this editor cares about, skipping commented out ones.
+  fn root_elements(&self) -> Vec<RootElement> {
+    let content_range = self.content_range();
+    let markup = self.markup();
+    let content = &markup[content_range.clone()];
+
+    ROOT_ELEMENT_REGEX
+      .captures_iter(content)
+      .map(|captures| {
+        let element = captures.get(0).unwrap();
+        RootElement {
+          tag: captures[1].to_string(),
+          name: NAME_ATTRIBUTE_REGEX
+            .captures(element.as_str())
+            .map(|name| name[1].to_string()),
+          range: content_range.start + element.start()..content_range.start + element.end(),
+        }
+      })
+      .collect()
+  }
+
+  /// Finds the matches of the regex outside of comments.
+  fn find_uncommented(&self, regex: &Regex) -> Vec<Range<usize>> {
+    regex.find_iter(&self.markup()).map(|m| m.range()).collect()
+  }
+
+  fn launcher_activity(&self) -> Range<usize> {
+    self
+      .find_uncommented(&ACTIVITY_REGEX)
+      .into_iter()
+      .next()
+      .expect("can't find activity in manifest")
+  }
+
+  fn line_indent(&self, position: usize) -> String {
+    let line_start = self.content[..position]
+      .rfind('\n')
+      .map(|i| i + 1)
+      .unwrap_or(0);
+    self.content[line_start..position]
+      .chars()
+      .take_while(|c| c.is_whitespace())
+      .collect()
+  }
+
+  fn insert_after(&mut self, element: &RootElement, text: &str) {
+    let indent = self.line_indent(element.range.start);
+    self
+      .content
+      .insert_str(element.range.end, &format!("\n{}{}", indent, text));
+  }
+
+  fn insert_before(&mut self, element: &RootElement, text: &str) {
+    let indent = self.line_indent(element.range.start);
+    self
+      .content
+      .insert_str(element.range.start, &format!("{}\n\n{}", text, indent));
+  }
+
+  /// Inserts the first root element, directly after the manifest tag.
+  fn insert_first(&mut self, text: &str) {
+    let start = self.content_range().start;
+    self.content.insert_str(start, &format!("\n\n    {}", text));
+  }
+
+  /// Adds the permission to the manifest. Permissions are grouped by element, with the sdk 23
+  /// variants following the regular ones. A declaration of the same permission with the same
+  /// element is replaced.
+  pub fn add_permission(&mut self, permission: &Permission) {
+    let elements = self.root_elements();
+    let qualified_name = permission.qualified_name();
+    if let Some(existing) = elements
+      .iter()
+      .find(|e| e.tag == permission.element.tag() && e.name.as_deref() == Some(&qualified_name))
+    {
+      self
+        .content
+        .replace_range(existing.range.clone(), &permission.render());
+      return;
+    }
+
+    let group: &[&str] = match permission.element {
+      PermissionElement::UsesPermission => &["uses-permission"],
+      PermissionElement::UsesPermissionSdk23 => &["uses-permission", "uses-permission-sdk-23"],
+    };
+    if let Some(last) = elements
+      .iter()
+      .rev()
+      .find(|e| group.contains(&e.tag.as_str()))
+    {
+      self.insert_after(last, &permission.render());
+    } else if let Some(application) = elements.iter().find(|e| e.tag == "application") {
+      self.insert_before(application, &permission.render());
+    } else {
+      self.insert_first(&permission.render());
+    }
+  }
+
+  /// Whether the permission, e.g. `android.permission.VIBRATE`, is declared by a
+  /// `uses-permission` or `uses-permission-sdk-23` element.
+  pub fn has_permission(&self, qualified_name: &str) -> bool {
+    self.root_elements().iter().any(|e| {
+      ["uses-permission", "uses-permission-sdk-23"].contains(&e.tag.as_str())
+        && e.name.as_deref() == Some(qualified_name)
+    })
+  }
+
+  /// Whether the feature is declared, required or not.
+  pub fn has_uses_feature(&self, name: &str) -> bool {
+    self
+      .root_elements()
+      .iter()
+      .any(|e| e.tag == "uses-feature" && e.name.as_deref() == Some(name))
+  }
+
+  /// Adds the feature declaration, replacing an existing declaration of the same feature. Features
+  /// are added after the existing ones.
+  pub fn add_uses_feature(&mut self, feature: &UsesFeature) {
+    self.put_uses_feature(&feature.name, &feature.render());
+  }
+
+  /// Replaces declarations of the feature with a manifest merger instruction removing it, so it is
+  /// neither declared by the app nor by the manifests of libraries merged into it.
+  pub fn remove_uses_feature(&mut self, name: &str) {
+    self.add_tools_namespace();
+    self.put_uses_feature(
+      name,
+      &format!(
+        "<uses-feature android:name=\"{}\" tools:node=\"remove\" />",
+        name
+      ),
+    );
+  }
+
+  fn put_uses_feature(&mut self, name: &str, element: &str) {
+    let elements = self.root_elements();
+    if let Some(existing) = elements
+      .iter()
+      .find(|e| e.tag == "uses-feature" && e.name.as_deref() == Some(name))
+    {
+      self.content.replace_range(existing.range.clone(), element);
+    } else if let Some(last) = elements.iter().rev().find(|e| e.tag == "uses-feature") {
+      self.insert_after(last, element);
+    } else if let Some(application) = elements.iter().find(|e| e.tag == "application") {
+      self.insert_before(application, element);
+    } else {
+      self.insert_first(element);
+    }
+  }
+
+  /// Declares the `tools` namespace on the manifest tag, if it isn't declared already.
+  fn add_tools_namespace(&mut self) {
+    let manifest_tag = self
+      .find_uncommented(&MANIFEST_TAG_REGEX)
+      .into_iter()
+      .next()
+      .expect("can't find manifest tag");
+    if self.content[manifest_tag.clone()].contains("xmlns:tools") {
+      return;
+    }
+    let name_end = manifest_tag.start + "<manifest".len();
+    self
+      .content
+      .insert_str(name_end, &format!(" xmlns:tools=\"{}\"", TOOLS_NAMESPACE));
+  }
+
+  fn application(&self) -> Range<usize> {
+    self
+      .find_uncommented(&APPLICATION_REGEX)
+      .into_iter()
+      .next()
+      .expect("can't find application in manifest")
+  }
+
+  fn tag_attribute(&self, tag: Range<usize>, name: &str) -> Option<String> {
+    attribute_regex(name)
+      .captures(&self.content[tag])
+      .map(|captures| captures[1].to_string())
+  }
+
+  /// Sets an attribute of the tag. New attributes are added after the existing ones, on a line of
+  /// their own if the tag spans multiple lines.
+  fn set_tag_attribute(&mut self, range: Range<usize>, name: &str, value: &str) {
+    let tag = &self.content[range.clone()];
+    let attribute = format!("android:{}=\"{}\"", name, value);
+    let regex = attribute_regex(name);
+
+    let tag = if regex.is_match(tag) {
+      regex.replace(tag, attribute.as_str()).to_string()
+    } else {
+      let tag_end = tag.trim_end_matches('>').trim_end_matches('/');
+      let last_attribute_end = tag_end.trim_end().len();
+      let separator = match tag[..last_attribute_end].rfind('\n') {
+        Some(i) => {
+          let indent: String = tag[i + 1..]
+            .chars()
+            .take_while(|c| c.is_whitespace())
+            .collect();
+          format!("\n{}", indent)
+        }
+        None => " ".to_string(),
+      };
+      format!(
+        "{}{}{}{}",
+        &tag[..last_attribute_end],
+        separator,
+        attribute,
+        &tag[last_attribute_end..]
+      )
+    };
+    self.content.replace_range(range, &tag);
+  }
+
+  /// Returns the value of an attribute of the launcher activity, e.g. `screenOrientation`.
+  pub fn activity_attribute(&self, name: &str) -> Option<String> {
+    self.tag_attribute(self.launcher_activity(), name)
+  }
+
+  pub fn set_activity_attribute(&mut self, name: &str, value: &str) {
+    self.set_tag_attribute(self.launcher_activity(), name, value);
+  }
+
+  /// Returns the value of an attribute of the application, e.g. `theme`.
+  pub fn application_attribute(&self, name: &str) -> Option<String> {
+    self.tag_attribute(self.application(), name)
+  }
+
+  pub fn set_application_attribute(&mut self, name: &str, value: &str) {
+    self.set_tag_attribute(self.application(), name, value);
+  }
+
+  /// Adds an intent category to the intent filter of the launcher activity, next to the
+  /// `android.intent.category.LAUNCHER` category.
+  pub fn add_launcher_category(&mut self, category: &str) {
+    let categories = self.find_uncommented(&CATEGORY_REGEX);
+    let name_of = |range: &Range<usize>| {
+      NAME_ATTRIBUTE_REGEX
+        .captures(&self.content[range.clone()])
+        .map(|name| name[1].to_string())
+    };
+    if categories
+      .iter()
+      .any(|c| name_of(c).as_deref() == Some(category))
+    {
+      return;
+    }
+    let launcher = categories
+      .iter()
+      .find(|c| name_of(c).as_deref() == Some("android.intent.category.LAUNCHER"))
+      .expect("can't find launcher category in manifest")
+      .clone();
+
+    let indent = self.line_indent(launcher.start);
+    self.content.insert_str(
+      launcher.end,
+      &format!("\n{}<category android:name=\"{}\" />", indent, category),
+    );
+  }
+
+  /// Sets a meta-data entry of the application, replacing an entry of the same name.
+  pub fn set_application_meta_data(&mut self, name: &str, value: &str) {
+    let meta_data = format!(
+      "<meta-data android:name=\"{}\" android:value=\"{}\" />",
+      name, value
+    );
+    let existing = self
+      .find_uncommented(&META_DATA_REGEX)
+      .into_iter()
+      .find(|m| {
+        NAME_ATTRIBUTE_REGEX
+          .captures(&self.content[m.clone()])
+          .is_some_and(|captures| &captures[1] == name)
+      });
+    if let Some(existing) = existing {
+      self.content.replace_range(existing, &meta_data);
+      return;
+    }
+
+    let application = self.application();
+    let indent = self.line_indent(application.start);
+    self
+      .content
+      .insert_str(application.end, &format!("\n\n{}    {}", indent, meta_data));
+  }
+
+  /// Adds the queries to the queries element, which is created after the permissions if there is
+  /// none. Queries already declared are skipped, so applying the same queries again doesn't change
+  /// the manifest.
+  pub fn add_queries(&mut self, queries: &Queries) {
+    if queries.is_empty() {
+      return;
+    }
+    let normalize = |text: &str| WHITESPACE_REGEX.replace_all(text, "").to_string();
+
+    match self.find_uncommented(&QUERIES_REGEX).into_iter().next() {
+      Some(existing) => {
+        let indent = format!("{}    ", self.line_indent(existing.start));
+        let declared = normalize(&self.content[existing.clone()]);
+        let close_tag = existing.end - "</queries>".len();
+        let insert_at = self.content[..close_tag]
+          .rfind('\n')
+          .map(|i| i + 1)
+          .unwrap_or(close_tag);
+        let missing: String = queries
+          .render_children(&indent)
+          .into_iter()
+          .filter(|child| !declared.contains(&normalize(child)))
+          .map(|child| format!("{}{}\n", indent, child))
+          .collect();
+        self.content.insert_str(insert_at, &missing);
+      }
+      None => {
+        let elements = self.root_elements();
+        let application = elements.iter().find(|e| e.tag == "application");
+        let indent = application
+          .map(|application| self.line_indent(application.range.start))
+          .unwrap_or("    ".to_string());
+        let child_indent = format!("{}    ", indent);
+        let mut element = "<queries>\n".to_string();
+        for child in queries.render_children(&child_indent) {
+          element.push_str(&format!("{}{}\n", child_indent, child));
+        }
+        element.push_str(&format!("{}</queries>", indent));
+
+        match application {
+          Some(application) => self.insert_before(application, &element),
+          None => self.insert_first(&element),
+        }
+      }
+    }
+  }
+
+  /// Adds `android:exported` to the activities, aliases, services and receivers with an intent
+  /// filter which lack it, as api level 31 requires. The launcher activity is exported, others
+  /// aren't, unless `overrides` has an entry for their name. Returns the components and values.
+  pub fn add_exported_attributes(&mut self, overrides: &BTreeMap<String, bool>) -> Vec<String> {
+    let markup = self.markup();
+    let mut components = vec![];
+    for captures in COMPONENT_REGEX.captures_iter(&markup) {
+      let start_tag = captures.get(0).unwrap();
+      if start_tag.as_str().ends_with("/>")
+        || attribute_regex("exported").is_match(start_tag.as_str())
+      {
+        continue;
+      }
+      let end_tag = format!("</{}", &captures[1]);
+      let body_end = markup[start_tag.end()..]
+        .find(&end_tag)
+        .map(|i| start_tag.end() + i)
+        .unwrap_or(markup.len());
+      let body = &markup[start_tag.end()..body_end];
+      if !body.contains("<intent-filter") {
+        continue;
+      }
+      let name = NAME_ATTRIBUTE_REGEX
+        .captures(start_tag.as_str())
+        .map(|name| name[1].to_string())
+        .unwrap_or_default();
+      let launcher = body.contains("\"android.intent.category.LAUNCHER\"");
+      let exported = overrides.get(&name).copied().unwrap_or(launcher);
+      components.push((start_tag.range(), name, exported));
+    }
+
+    //from the back, so the ranges of the earlier components stay valid
+    let mut added = vec![];
+    for (range, name, exported) in components.into_iter().rev() {
+      self.set_tag_attribute(range, "exported", &exported.to_string());
+      added.insert(0, format!("{} exported={}", name, exported));
+    }
+    added
+  }
+
+  /// Sets the maxSdkVersion of the uses-sdk element, adding the element if there is none.
+  pub fn set_max_sdk_version(&mut self, max_sdk_version: u32) {
+    let attribute = format!("android:maxSdkVersion=\"{}\"", max_sdk_version);
+    match self.root_elements().iter().find(|e| e.tag == "uses-sdk") {
+      Some(uses_sdk) => {
+        let element = &self.content[uses_sdk.range.clone()];
+        let element = if MAX_SDK_VERSION_ATTRIBUTE_REGEX.is_match(element) {
+          MAX_SDK_VERSION_ATTRIBUTE_REGEX
+            .replace(element, attribute.as_str())
+            .to_string()
+        } else {
+          element.replacen("<uses-sdk", &format!("<uses-sdk {}", attribute), 1)
+        };
+        self.content.replace_range(uses_sdk.range.clone(), &element);
+      }
+      None => self.insert_first(&format!("<uses-sdk {} />", attribute)),
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use crate::manifest::{
+    expand_permission_preset, parse_exported_overrides, parse_permissions, parse_queries,
+    AndroidManifest, Permission, PermissionElement, MANIFEST_TAG_CONTENT_REGEX,
+  };
+
+  const MANIFEST: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<manifest xmlns:android="http://schemas.android.com/apk/res/android"
+    package="org.libsdl.app">
+
+    <uses-feature android:glEsVersion="0x00020000" />
+
+    <!-- <uses-permission android:name="android.permission.BLUETOOTH" android:maxSdkVersion="30" /> -->
+
+    <uses-permission android:name="android.permission.VIBRATE" />
+
+    <application android:label="@string/app_name">
+    </application>
+
+</manifest>
+"#;
+
+  #[test]
+  fn manifest_regex() {
+    let mut manifest_file_content =
+      String::from("<some header>\n<manifest option1\n\toption2>\n\t<hello world>\n</manifest>\n");
+    let captures = MANIFEST_TAG_CONTENT_REGEX
+      .captures(&manifest_file_content)
+      .unwrap();
+    let content_match = captures.get(1).unwrap();
+    assert_eq!(content_match.as_str(), "\n\t<hello world>\n");
+
+    manifest_file_content.insert_str(content_match.end(), "\t<permission>\n");
+
+    let captures = MANIFEST_TAG_CONTENT_REGEX
+      .captures(&manifest_file_content)
+      .unwrap();
+    let content_match = captures.get(1).unwrap();
+    assert_eq!(
+      content_match.as_str(),
+      "\n\t<hello world>\n\t<permission>\n"
+    );
+  }
+
+  #[test]
+  fn permission_entries() {
+    let value: toml::Value = r#"permissions = [
+      "camera",
+      { name = "ACCESS_FINE_LOCATION", min_declared_sdk = 23 },
+      { name = "BLUETOOTH", max_sdk_version = 30 },
+    ]"#
+      .parse::<toml::Table>()
+      .unwrap()["permissions"]
+      .clone();
+    let permissions = parse_permissions(&value).unwrap();
+    assert_eq!(permissions[0], Permission::new("camera"));
+    assert_eq!(
+      permissions[1].element,
+      PermissionElement::UsesPermissionSdk23
+    );
+    assert_eq!(
+      permissions[2].render(),
+      r#"<uses-permission android:name="android.permission.BLUETOOTH" android:maxSdkVersion="30" />"#
+    );
+  }
+
+  #[test]
+  fn invalid_permission_entries() {
+    let error = |permissions: &str| -> String {
+      let value = format!("permissions = {}", permissions)
+        .parse::<toml::Table>()
+        .unwrap()["permissions"]
+        .clone();
+      parse_permissions(&value).unwrap_err()
+    };
+    assert_eq!(
+      "min_declared_sdk of permission CAMERA must be 23, found: 24",
+      error(r#"[{ name = "CAMERA", min_declared_sdk = 24 }]"#)
+    );
+    assert_eq!(
+      "permissions must be an array, found: \"CAMERA\"",
+      error(r#""CAMERA""#)
+    );
+    assert!(error(r#"[{ max_sdk_version = 28 }]"#).starts_with("permission entry needs a name"));
+    assert!(error(r#"[{ name = "CAMERA", max_sdk_version = -1 }]"#)
+      .starts_with("max_sdk_version of permission must be an sdk version"));
+    assert!(error(r#"["preset:camera"]"#).starts_with("unknown permission preset \"camera\""));
+  }
+
+  #[test]
+  fn permission_presets() {
+    let expansion = |preset: &str| -> Vec<String> {
+      expand_permission_preset(preset)
+        .unwrap()
+        .unwrap()
+        .iter()
+        .map(Permission::render)
+        .collect()
+    };
+    assert_eq!(
+      vec![
+        r#"<uses-permission android:name="android.permission.INTERNET" />"#,
+        r#"<uses-permission android:name="android.permission.ACCESS_NETWORK_STATE" />"#,
+      ],
+      expansion("preset:network")
+    );
+    assert_eq!(
+      vec![
+        r#"<uses-permission android:name="android.permission.READ_MEDIA_IMAGES" />"#,
+        r#"<uses-permission android:name="android.permission.READ_EXTERNAL_STORAGE" android:maxSdkVersion="32" />"#,
+      ],
+      expansion("preset:photos")
+    );
+    assert_eq!(
+      vec![
+        r#"<uses-permission android:name="android.permission.READ_MEDIA_VIDEO" />"#,
+        r#"<uses-permission android:name="android.permission.READ_EXTERNAL_STORAGE" android:maxSdkVersion="32" />"#,
+      ],
+      expansion("preset:videos")
+    );
+    assert_eq!(
+      vec![
+        r#"<uses-permission android:name="android.permission.BLUETOOTH_CONNECT" />"#,
+        r#"<uses-permission android:name="android.permission.BLUETOOTH_SCAN" />"#,
+        r#"<uses-permission android:name="android.permission.BLUETOOTH" android:maxSdkVersion="30" />"#,
+        r#"<uses-permission android:name="android.permission.BLUETOOTH_ADMIN" android:maxSdkVersion="30" />"#,
+      ],
+      expansion("preset:bluetooth")
+    );
+    //names without the prefix are permissions, even the ones of presets
+    assert_eq!(None, expand_permission_preset("bluetooth").unwrap());
+    assert_eq!(None, expand_permission_preset("camera").unwrap());
+
+    let value: toml::Value =
+      r#"permissions = ["preset:network", "preset:photos", "preset:videos", "bluetooth"]"#
+        .parse::<toml::Table>()
+        .unwrap()["permissions"]
+        .clone();
+    let mut manifest = AndroidManifest::new(MANIFEST.to_string());
+    for permission in parse_permissions(&value).unwrap() {
+      manifest.add_permission(&permission);
+    }
+    //the storage permission both media presets need is declared once
+    assert_eq!(
+      1,
+      manifest
+        .content()
+        .matches("android.permission.READ_EXTERNAL_STORAGE")
+        .count()
+    );
+    assert!(manifest
+      .content()
+      .contains(r#"<uses-permission android:name="android.permission.BLUETOOTH" />"#));
+  }
+
+  #[test]
+  fn permissions_are_grouped_by_element() {
+    let mut manifest = AndroidManifest::new(MANIFEST.to_string());
+    let mut location = Permission::new("ACCESS_FINE_LOCATION");
+    location.element = PermissionElement::UsesPermissionSdk23;
+    manifest.add_permission(&location);
+    manifest.add_permission(&Permission::new("camera"));
+    //same name with a different element is kept
+    let mut vibrate = Permission::new("VIBRATE");
+    vibrate.element = PermissionElement::UsesPermissionSdk23;
+    manifest.add_permission(&vibrate);
+    //same element replaces the existing declaration
+    let mut vibrate = Permission::new("VIBRATE");
+    vibrate.max_sdk_version = Some(32);
+    manifest.add_permission(&vibrate);
+
+    assert!(manifest.content().contains(
+      r#"    <uses-permission android:name="android.permission.VIBRATE" android:maxSdkVersion="32" />
+    <uses-permission android:name="android.permission.CAMERA" />
+    <uses-permission-sdk-23 android:name="android.permission.ACCESS_FINE_LOCATION" />
+    <uses-permission-sdk-23 android:name="android.permission.VIBRATE" />
+
+    <application"#
+    ));
+    //the commented out permission is untouched
+    assert!(manifest
+      .content()
+      .contains(r#"<!-- <uses-permission android:name="android.permission.BLUETOOTH""#));
+  }
+
+  #[test]
+  fn permissions_without_existing_ones() {
+    let mut manifest = AndroidManifest::new(MANIFEST.replace(
+      "    <uses-permission android:name=\"android.permission.VIBRATE\" />\n\n",
+      "",
+    ));
+    manifest.add_permission(&Permission::new("camera"));
+    assert!(manifest.content().contains(
+      "<uses-permission android:name=\"android.permission.CAMERA\" />\n\n    <application"
+    ));
+  }
+
+  #[test]
+  fn bom_and_commented_manifest_tag() {
+    let content = "\u{feff}<?xml version=\"1.0\" encoding=\"utf-8\"?>
+<!-- A template, structured like
+<manifest package=\"com.example\">
+    <application />
+</manifest>
+-->
+<manifest xmlns:android=\"http://schemas.android.com/apk/res/android\"
+    package=\"org.libsdl.app\"
+>
+
+    <application android:label=\"@string/app_name\">
+    </application>
+
+</manifest>
+";
+    let mut manifest = AndroidManifest::new(content.to_string());
+    manifest.add_permission(&Permission::new("camera"));
+    manifest.set_max_sdk_version(30);
+    let content = manifest.content();
+    assert!(content.starts_with("\u{feff}<?xml"));
+    assert!(
+      content.contains("<manifest package=\"com.example\">\n    <application />\n</manifest>\n-->")
+    );
+    assert!(content.contains(
+      "    <uses-permission android:name=\"android.permission.CAMERA\" />\n\n    <application"
+    ));
+    assert!(content
+      .contains(">\n\n    <uses-sdk android:maxSdkVersion=\"30\" />\n\n    <uses-permission"));
+  }
+
+  #[test]
+  fn namespaced_attributes() {
+    let content = r#"<manifest xmlns:android="http://schemas.android.com/apk/res/android"
+    xmlns:tools="http://schemas.android.com/tools" tools:ignore="a>b">
+
+    <uses-permission tools:name="android.permission.CAMERA" android:name="android.permission.VIBRATE" />
+
+    <application tools:replace="android:label" android:label="@string/app_name">
+        <![CDATA[ <activity android:name="Wrong"> ]]>
+        <activity android:name="MainActivity" tools:screenOrientation="portrait">
+        </activity>
+    </application>
+
+</manifest>
+"#;
+    let mut manifest = AndroidManifest::new(content.to_string());
+    assert_eq!(None, manifest.activity_attribute("screenOrientation"));
+    assert_eq!(
+      Some("@string/app_name".to_string()),
+      manifest.application_attribute("label")
+    );
+    manifest.set_activity_attribute("screenOrientation", "landscape");
+    assert!(manifest.content().contains(
+      r#"<activity android:name="MainActivity" tools:screenOrientation="portrait" android:screenOrientation="landscape">"#
+    ));
+
+    //the existing declaration is found by its android:name
+    manifest.add_permission(&Permission::new("VIBRATE"));
+    assert_eq!(1, manifest.content().matches("<uses-permission").count());
+  }
+
+  #[test]
+  fn exported_attributes() {
+    //SDL templates before 2.0.22 lack android:exported
+    let old_template = r#"<manifest xmlns:android="http://schemas.android.com/apk/res/android">
+    <application android:label="@string/app_name">
+        <activity android:name="SDLActivity"
+            android:configChanges="orientation"
+            >
+            <intent-filter>
+                <action android:name="android.intent.action.MAIN" />
+                <category android:name="android.intent.category.LAUNCHER" />
+            </intent-filter>
+            <intent-filter>
+                <action android:name="android.hardware.usb.action.USB_DEVICE_ATTACHED" />
+            </intent-filter>
+        </activity>
+        <receiver android:name=".BootReceiver">
+            <intent-filter>
+                <action android:name="android.intent.action.BOOT_COMPLETED" />
+            </intent-filter>
+        </receiver>
+        <service android:name=".AudioService" />
+        <receiver android:name=".ShareReceiver">
+            <intent-filter>
+                <action android:name="android.intent.action.SEND" />
+            </intent-filter>
+        </receiver>
+    </application>
+</manifest>
+"#;
+    let mut manifest = AndroidManifest::new(old_template.to_string());
+    let overrides = parse_exported_overrides(
+      &"exported = { \".ShareReceiver\" = true }"
+        .parse::<toml::Table>()
+        .unwrap()["exported"],
+    )
+    .unwrap();
+    assert_eq!(
+      vec![
+        "SDLActivity exported=true",
+        ".BootReceiver exported=false",
+        ".ShareReceiver exported=true",
+      ],
+      manifest.add_exported_attributes(&overrides)
+    );
+    let content = manifest.content();
+    assert!(content.contains(
+      "android:configChanges=\"orientation\"\n            android:exported=\"true\"\n            >"
+    ));
+    assert!(
+      content.contains("<receiver android:name=\".BootReceiver\" android:exported=\"false\">")
+    );
+    assert!(content.contains("<service android:name=\".AudioService\" />"));
+
+    //templates which declare it are left alone
+    let new_template = old_template.replace(
+      "android:configChanges=\"orientation\"",
+      "android:exported=\"true\"",
+    );
+    let mut manifest = AndroidManifest::new(new_template.clone());
+    assert_eq!(
+      2,
+      manifest.add_exported_attributes(&Default::default()).len()
+    );
+    assert_eq!(
+      1,
+      manifest
+        .content()
+        .matches("android:exported=\"true\"")
+        .count()
+    );
+    let before = manifest.content().to_string();
+    assert!(manifest
+      .add_exported_attributes(&Default::default())
+      .is_empty());
+    assert_eq!(before, manifest.content());
+  }
+
+  #[test]
+  fn uses_sdk_max_version() {
+    let mut manifest = AndroidManifest::new(MANIFEST.to_string());
+    manifest.set_max_sdk_version(30);
+    assert!(manifest.content().contains(
+      "package=\"org.libsdl.app\">\n\n    <uses-sdk android:maxSdkVersion=\"30\" />\n\n    <uses-feature"
+    ));
+
+    manifest.set_max_sdk_version(33);
+    assert!(manifest
+      .content()
+      .contains("<uses-sdk android:maxSdkVersion=\"33\" />"));
+    assert_eq!(manifest.content().matches("<uses-sdk").count(), 1);
+  }
+
+  #[test]
+  fn queries() {
+    let value: toml::Value = r#"queries = { packages = ["com.android.chrome"], intents = [
+      { action = "android.intent.action.VIEW", data_scheme = "https" },
+    ] }"#
+      .parse::<toml::Table>()
+      .unwrap()["queries"]
+      .clone();
+    let queries = parse_queries(&value).unwrap();
+
+    let mut manifest = AndroidManifest::new(MANIFEST.to_string());
+    manifest.add_queries(&queries);
+    assert!(manifest.content().contains(
+      r#"    <uses-permission android:name="android.permission.VIBRATE" />
+
+    <queries>
+        <package android:name="com.android.chrome" />
+        <intent>
+            <action android:name="android.intent.action.VIEW" />
+            <data android:scheme="https" />
+        </intent>
+    </queries>
+
+    <application"#
+    ));
+
+    let content = manifest.content().to_string();
+    manifest.add_queries(&queries);
+    assert_eq!(manifest.content(), content);
+
+    //permissions added later stay in front of the queries
+    manifest.add_permission(&Permission::new("camera"));
+    assert!(manifest
+      .content()
+      .contains("<uses-permission android:name=\"android.permission.CAMERA\" />\n\n    <queries>"));
+  }
+
+  #[test]
+  fn invalid_queries() {
+    let error = |queries: &str| -> String {
+      let value = format!("queries = {}", queries)
+        .parse::<toml::Table>()
+        .unwrap()["queries"]
+        .clone();
+      parse_queries(&value).unwrap_err()
+    };
+    assert_eq!(
+      "queries must be a table, found: [\"com.android.chrome\"]",
+      error(r#"["com.android.chrome"]"#)
+    );
+    assert_eq!(
+      "queries.packages must be an array, found: \"com.android.chrome\"",
+      error(r#"{ packages = "com.android.chrome" }"#)
+    );
+    assert!(error(r#"{ intents = [{ data_scheme = "https" }] }"#)
+      .starts_with("queried intent needs an action"));
+    assert_eq!(
+      "category of query intent must be a string, found: 1",
+      error(r#"{ intents = [{ action = "android.intent.action.VIEW", category = 1 }] }"#)
+    );
+  }
+
+  #[test]
+  fn queries_merged_into_existing() {
+    let mut manifest = AndroidManifest::new(MANIFEST.replace(
+      "    <application",
+      "    <queries>\n        <package android:name=\"com.android.chrome\"/>\n    </queries>\n\n    <application",
+    ));
+    let value: toml::Value =
+      r#"queries = { packages = ["com.android.chrome", "org.mozilla.firefox"] }"#
+        .parse::<toml::Table>()
+        .unwrap()["queries"]
+        .clone();
+    manifest.add_queries(&parse_queries(&value).unwrap());
+    assert!(manifest.content().contains(
+      "    <queries>\n        <package android:name=\"com.android.chrome\"/>\n        <package android:name=\"org.mozilla.firefox\" />\n    </queries>"
+    ));
+    assert_eq!(manifest.content().matches("<queries>").count(), 1);
+  }
+}
@@ -0,0 +1,338 @@
+use std::fmt::{Display, Formatter};
+use std::fs::read_to_string;
+use std::path::Path;
+
+use roxmltree::{Document, Node};
+
+const ANDROID_NAMESPACE: &str = "http://schemas.android.com/apk/res/android";
+
+/// Elements which can't be declared without `android:name`.
+const NAMED_ELEMENTS: &[&str] = &[
+  "activity",
+  "activity-alias",
+  "service",
+  "receiver",
+  "provider",
+  "permission",
+  "uses-permission",
+  "uses-permission-sdk-23",
+  "meta-data",
+  "action",
+  "category",
+  "package",
+];
+
+/// A problem found in the generated AndroidManifest.xml.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManifestProblem {
+  pub line: u32,
+  /// The offending element, e.g. `<uses-permission android:name="android.permission.CAMERA">`.
+  pub element: String,
+  pub message: String,
+  /// The android metadata key the element was generated from, if it is known.
+  pub metadata_key: Option<&'static str>,
+}
+
+impl Display for ManifestProblem {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    write!(
+      f,
+      "AndroidManifest.xml:{}: {}: {}",
+      self.line, self.element, self.message
+    )?;
+    if let Some(key) = self.metadata_key {
+      write!(f, " (generated from `{}` in the android metadata)", key)?;
+    }
+    Ok(())
+  }
+}
+
+fn android_attribute<'a>(node: Node<'a, '_>, name: &str) -> Option<&'a str> {
+  node.attribute((ANDROID_NAMESPACE, name))
+}
+
+fn describe_element(node: Node) -> String {
+  match android_attribute(node, "name") {
+    Some(name) => format!("<{} android:name=\"{}\">", node.tag_name().name(), name),
+    None => format!("<{}>", node.tag_name().name()),
+  }
+}
+
+/// The android metadata key producing the element, or the attribute of it.
+fn get_metadata_key(node: Node, attribute: Option<&str>) -> Option<&'static str> {
+  let name = android_attribute(node, "name").unwrap_or_default();
+  if node.ancestors().any(|n| n.has_tag_name("queries")) {
+    return Some("queries");
+  }
+  match (node.tag_name().name(), attribute) {
+    ("uses-permission" | "uses-permission-sdk-23", _) => Some("permissions"),
+    ("uses-sdk", _) => Some("max_sdk_version"),
+    ("uses-feature", _) if name == "android.hardware.type.watch" => Some("wear"),
+    ("uses-feature", _) => Some("preset or large_screens"),
+    ("meta-data", _) if name == "com.google.android.wearable.standalone" => Some("wear"),
+    ("meta-data", _) if name == "android.allow_multiple_resumed_activities" => {
+      Some("large_screens")
+    }
+    ("meta-data", _) if name.starts_with("com.oculus.") => Some("preset"),
+    ("category", _) if name.starts_with("com.oculus.") => Some("preset"),
+    ("activity", Some("theme")) => Some("splash"),
+    ("activity", Some("screenOrientation")) => Some("orientation"),
+    ("activity", Some("resizeableActivity")) => Some("large_screens"),
+    ("activity", Some("excludeFromRecents")) => Some("preset"),
+    ("application", Some("name")) => Some("application_class"),
+    ("application", Some("theme")) => Some("display_cutout or edge_to_edge"),
+    _ => None,
+  }
+}
+
+fn problem(
+  document: &Document,
+  node: Node,
+  attribute: Option<&str>,
+  message: String,
+) -> ManifestProblem {
+  ManifestProblem {
+    line: document.text_pos_at(node.range().start).row,
+    element: describe_element(node),
+    message,
+    metadata_key: get_metadata_key(node, attribute),
+  }
+}
+
+fn is_launcher_intent_filter(filter: Node) -> bool {
+  let has = |tag: &str, name: &str| {
+    filter
+      .children()
+      .any(|c| c.has_tag_name(tag) && android_attribute(c, "name") == Some(name))
+  };
+  has("action", "android.intent.action.MAIN") && has("category", "android.intent.category.LAUNCHER")
+}
+
+/// Checks the structure of the manifest: the root element, the application with a launcher
+/// activity, the attributes elements can't do without and that no attribute lacks a namespace.
+pub fn verify_manifest(content: &str) -> Result<(), Vec<ManifestProblem>> {
+  let document = Document::parse(content).map_err(|e| {
+    vec![ManifestProblem {
+      line: e.pos().row,
+      element: "document".to_string(),
+      message: format!("invalid XML: {}", e),
+      metadata_key: None,
+    }]
+  })?;
+
+  let root = document.root_element();
+  let mut problems = vec![];
+  if !root.has_tag_name("manifest") {
+    problems.push(problem(
+      &document,
+      root,
+      None,
+      "the root element must be <manifest>".to_string(),
+    ));
+  }
+  if !root.namespaces().any(|ns| ns.uri() == ANDROID_NAMESPACE) {
+    problems.push(problem(
+      &document,
+      root,
+      None,
+      format!(
+        "the android namespace {} is not declared",
+        ANDROID_NAMESPACE
+      ),
+    ));
+  }
+
+  let applications: Vec<Node> = root
+    .children()
+    .filter(|c| c.has_tag_name("application"))
+    .collect();
+  match applications.as_slice() {
+    [] => problems.push(problem(
+      &document,
+      root,
+      None,
+      "there is no <application> element".to_string(),
+    )),
+    [application] => {
+      let has_launcher = application
+        .children()
+        .filter(|c| c.has_tag_name("activity") || c.has_tag_name("activity-alias"))
+        .flat_map(|activity| activity.children())
+        .filter(|c| c.has_tag_name("intent-filter"))
+        .any(is_launcher_intent_filter);
+      if !has_launcher {
+        problems.push(problem(
+          &document,
+          *application,
+          None,
+          "no activity has the launcher intent filter with the MAIN action and the LAUNCHER \
+          category"
+            .to_string(),
+        ));
+      }
+    }
+    [_, second, ..] => problems.push(problem(
+      &document,
+      *second,
+      None,
+      "there is more than one <application> element".to_string(),
+    )),
+  }
+
+  for node in root.descendants().filter(|n| n.is_element() && *n != root) {
+    let tag = node.tag_name().name();
+    if NAMED_ELEMENTS.contains(&tag) && android_attribute(node, "name").is_none() {
+      problems.push(problem(
+        &document,
+        node,
+        None,
+        "android:name is missing".to_string(),
+      ));
+    }
+    if tag == "uses-feature"
+      && android_attribute(node, "name").is_none()
+      && android_attribute(node, "glEsVersion").is_none()
+    {
+      problems.push(problem(
+        &document,
+        node,
+        None,
+        "either android:name or android:glEsVersion is needed".to_string(),
+      ));
+    }
+    if tag == "meta-data"
+      && android_attribute(node, "value").is_none()
+      && android_attribute(node, "resource").is_none()
+    {
+      problems.push(problem(
+        &document,
+        node,
+        None,
+        "either android:value or android:resource is needed".to_string(),
+      ));
+    }
+
+    for attribute in node.attributes() {
+      //attributes of other namespaces, like tools: or dist: of feature modules, are left to aapt
+      if attribute.namespace().is_none() {
+        problems.push(problem(
+          &document,
+          node,
+          Some(attribute.name()),
+          format!(
+            "attribute {} is not in the android namespace",
+            attribute.name()
+          ),
+        ));
+      }
+    }
+  }
+
+  if problems.is_empty() {
+    Ok(())
+  } else {
+    Err(problems)
+  }
+}
+
+/// Verifies the manifest of the generated project, so problems with it are reported with their
+/// cause instead of failing the gradle build.
+pub fn check_generated_manifest(path: &Path) -> Result<(), String> {
+  let content = read_to_string(path).unwrap_or_else(|_| panic!("can't read manifest {:?}", path));
+  verify_manifest(&content).map_err(|problems| {
+    let mut message = format!("The generated {} is invalid:", path.display());
+    for problem in problems {
+      message.push_str(&format!("\n  {}", problem));
+    }
+    message
+  })
+}
+
+#[cfg(test)]
+mod test {
+  use crate::manifest_check::verify_manifest;
+
+  const MANIFEST: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<manifest xmlns:android="http://schemas.android.com/apk/res/android"
+    package="org.libsdl.app">
+
+    <uses-feature android:glEsVersion="0x00020000" />
+
+    <uses-permission android:name="android.permission.VIBRATE" />
+
+    <application android:label="@string/app_name">
+        <meta-data android:name="SDL_ENV.SDL_ACCELEROMETER_AS_JOYSTICK" android:value="0"/>
+        <activity android:name="MainActivity">
+            <intent-filter>
+                <action android:name="android.intent.action.MAIN" />
+                <category android:name="android.intent.category.LAUNCHER" />
+            </intent-filter>
+        </activity>
+    </application>
+
+</manifest>
+"#;
+
+  #[test]
+  fn valid_manifest() {
+    assert_eq!(verify_manifest(MANIFEST), Ok(()));
+  }
+
+  #[test]
+  fn invalid_xml() {
+    let problems =
+      verify_manifest(&MANIFEST.replace("<uses-permission", "<uses-permission <")).unwrap_err();
+    assert_eq!(problems.len(), 1);
+    assert_eq!(problems[0].line, 7);
+    assert!(problems[0].message.starts_with("invalid XML"));
+  }
+
+  #[test]
+  fn problems_point_at_metadata() {
+    let content = MANIFEST
+      .replace(
+        "<uses-permission android:name=",
+        "<uses-permission android:maxSdkVersion=\"30\" name=",
+      )
+      .replace(
+        "<activity android:name=\"MainActivity\">",
+        "<activity android:name=\"MainActivity\" theme=\"@style/CargoSdlApk.Splash\">",
+      );
+    let problems = verify_manifest(&content).unwrap_err();
+    let descriptions: Vec<String> = problems.iter().map(|p| p.to_string()).collect();
+    assert_eq!(
+      descriptions,
+      vec![
+        "AndroidManifest.xml:7: <uses-permission>: android:name is missing (generated from `permissions` in the android metadata)",
+        "AndroidManifest.xml:7: <uses-permission>: attribute name is not in the android namespace (generated from `permissions` in the android metadata)",
+        "AndroidManifest.xml:11: <activity android:name=\"MainActivity\">: attribute theme is not in the android namespace (generated from `splash` in the android metadata)",
+      ]
+    );
+  }
+
+  #[test]
+  fn foreign_namespaces_allowed() {
+    let content = MANIFEST
+      .replace(
+        "xmlns:android=",
+        "xmlns:dist=\"http://schemas.android.com/apk/distribution\"\n    xmlns:vendor=\"http://example.com/vendor\"\n    xmlns:android=",
+      )
+      .replace(
+        "<application android:label",
+        "<dist:module dist:instant=\"true\" />\n    <application vendor:theme=\"dark\" android:label",
+      );
+    assert_eq!(verify_manifest(&content), Ok(()));
+  }
+
+  #[test]
+  fn launcher_required() {
+    let problems = verify_manifest(&MANIFEST.replace(
+      "android.intent.category.LAUNCHER",
+      "android.intent.category.DEFAULT",
+    ))
+    .unwrap_err();
+    assert_eq!(problems.len(), 1);
+    assert!(problems[0].message.contains("launcher intent filter"));
+    assert_eq!(problems[0].element, "<application>");
+  }
+}
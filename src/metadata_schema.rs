@@ -0,0 +1,584 @@
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+
+use toml::value::Value;
+use toml::Table;
+
+use crate::util::*;
+
+/// The type a key of the android metadata must have.
+#[derive(Debug, Clone, Copy)]
+pub enum KeyType {
+  String,
+  Bool,
+  Integer,
+  Array(&'static KeyType),
+  /// A table with the given keys, others are reported as unknown.
+  Table(&'static [(&'static str, KeyType)]),
+  /// A table with arbitrary keys, e.g. target triples, whose values have the type.
+  Map(&'static KeyType),
+  /// A table whose content is checked where it is used, like `preset_overrides`.
+  AnyTable,
+  OneOf(&'static [KeyType]),
+  /// The `profile` table, overrides of the android metadata keyed by profile name.
+  Profiles,
+}
+
+impl KeyType {
+  /// Whether the value has the outer shape of the type, nested values aren't checked.
+  fn matches(&self, value: &Value) -> bool {
+    match (self, value) {
+      (KeyType::String, Value::String(_))
+      | (KeyType::Bool, Value::Boolean(_))
+      | (KeyType::Integer, Value::Integer(_))
+      | (KeyType::Array(_), Value::Array(_))
+      | (KeyType::Table(_), Value::Table(_))
+      | (KeyType::Map(_), Value::Table(_))
+      | (KeyType::AnyTable, Value::Table(_))
+      | (KeyType::Profiles, Value::Table(_)) => true,
+      (KeyType::OneOf(types), value) => types.iter().any(|t| t.matches(value)),
+      _ => false,
+    }
+  }
+}
+
+impl Display for KeyType {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match self {
+      KeyType::String => write!(f, "a string"),
+      KeyType::Bool => write!(f, "a boolean"),
+      KeyType::Integer => write!(f, "an integer"),
+      KeyType::Array(KeyType::String) => write!(f, "an array of strings"),
+      KeyType::Array(KeyType::Table(_)) => write!(f, "an array of tables"),
+      KeyType::Array(_) => write!(f, "an array"),
+      KeyType::Table(_) | KeyType::Map(_) | KeyType::AnyTable | KeyType::Profiles => {
+        write!(f, "a table")
+      }
+      KeyType::OneOf(types) => write!(
+        f,
+        "{}",
+        types
+          .iter()
+          .map(|t| t.to_string())
+          .collect::<Vec<String>>()
+          .join(" or ")
+      ),
+    }
+  }
+}
+
+const STRINGS: KeyType = KeyType::Array(&KeyType::String);
+
+const SIGNER_KEYS: &[(&str, KeyType)] = &[
+  ("keystore", KeyType::String),
+  ("keystore_type", KeyType::String),
+  ("keystore_pass", KeyType::String),
+  ("keystore_pass_env", KeyType::String),
+  ("key_alias", KeyType::String),
+  ("key_pass", KeyType::String),
+  ("key_pass_env", KeyType::String),
+];
+
+const SIGNING_KEYS: &[(&str, KeyType)] = &[
+  ("mode", KeyType::String),
+  ("keystore", KeyType::String),
+  ("keystore_type", KeyType::String),
+  ("keystore_pass", KeyType::String),
+  ("keystore_pass_env", KeyType::String),
+  ("key_alias", KeyType::String),
+  ("key_pass", KeyType::String),
+  ("key_pass_env", KeyType::String),
+  ("next_signer", KeyType::Table(SIGNER_KEYS)),
+  ("lineage", KeyType::String),
+  ("rotation_min_sdk_version", KeyType::Integer),
+];
+
+const FLAVOR_KEYS: &[(&str, KeyType)] = &[
+  ("name", KeyType::String),
+  ("application_id_suffix", KeyType::String),
+  ("cargo_features", STRINGS),
+  ("assets", KeyType::String),
+];
+
+const PERMISSION_KEYS: &[(&str, KeyType)] = &[
+  ("name", KeyType::String),
+  ("min_declared_sdk", KeyType::Integer),
+  ("max_sdk_version", KeyType::Integer),
+];
+
+const BRIDGE_METHOD_KEYS: &[(&str, KeyType)] = &[
+  ("name", KeyType::String),
+  ("params", STRINGS),
+  ("returns", KeyType::String),
+  ("body_file", KeyType::String),
+];
+
+const QUERY_INTENT_KEYS: &[(&str, KeyType)] = &[
+  ("action", KeyType::String),
+  ("category", KeyType::String),
+  ("data_scheme", KeyType::String),
+  ("data_mime_type", KeyType::String),
+];
+
+const QUERIES_KEYS: &[(&str, KeyType)] = &[
+  ("packages", STRINGS),
+  (
+    "intents",
+    KeyType::Array(&KeyType::Table(QUERY_INTENT_KEYS)),
+  ),
+];
+
+const HOOK: KeyType = KeyType::OneOf(&[
+  KeyType::String,
+  KeyType::Table(&[
+    ("command", KeyType::String),
+    ("args", STRINGS),
+    ("output_dir", KeyType::String),
+  ]),
+]);
+
+const RUSTFLAGS: KeyType = KeyType::OneOf(&[KeyType::String, STRINGS]);
+
+/// The keys of `[package.metadata.android]`.
+pub const ANDROID_METADATA_KEYS: &[(&str, KeyType)] = &[
+  ("activity_kind", KeyType::String),
+  (
+    "adb_reverse",
+    KeyType::Array(&KeyType::OneOf(&[KeyType::Integer, KeyType::String])),
+  ),
+  ("application_class", KeyType::String),
+  ("arm_mode", KeyType::String),
+  ("artifacts", STRINGS),
+  ("auto_install_sdk", KeyType::Bool),
+  (
+    "baseline_profile",
+    KeyType::OneOf(&[KeyType::Bool, KeyType::String]),
+  ),
+  (
+    "bridge_methods",
+    KeyType::Array(&KeyType::Table(BRIDGE_METHOD_KEYS)),
+  ),
+  ("debug_app_name_suffix", KeyType::String),
+  ("debug_application_id_suffix", KeyType::String),
+  ("display_cutout", KeyType::String),
+  ("edge_to_edge", KeyType::Bool),
+  ("extra_gradle_tasks", STRINGS),
+  ("exported", KeyType::Map(&KeyType::Bool)),
+  ("extra_targets", KeyType::Map(&KeyType::String)),
+  ("flavors", KeyType::Array(&KeyType::Table(FLAVOR_KEYS))),
+  ("google_services", KeyType::String),
+  ("gradle_user_home", KeyType::String),
+  ("icon", KeyType::String),
+  ("keep_sdl_libs", STRINGS),
+  ("large_screens", KeyType::Bool),
+  ("max_sdk_version", KeyType::Integer),
+  ("min_sdk_version", KeyType::Integer),
+  ("multidex", KeyType::Bool),
+  ("orientation", KeyType::String),
+  ("package_name", KeyType::String),
+  (
+    "packaging",
+    KeyType::Table(&[
+      ("no_compress", STRINGS),
+      ("page_align_shared_libs", KeyType::Bool),
+    ]),
+  ),
+  (
+    "permissions",
+    KeyType::Array(&KeyType::OneOf(&[
+      KeyType::String,
+      KeyType::Table(PERMISSION_KEYS),
+    ])),
+  ),
+  (
+    "placeholders",
+    KeyType::Map(&KeyType::OneOf(&[
+      KeyType::String,
+      KeyType::Integer,
+      KeyType::Bool,
+    ])),
+  ),
+  ("postbuild_hook", HOOK),
+  ("prebuilt_sdl_libs", KeyType::String),
+  ("prepackage_hook", HOOK),
+  ("preset", KeyType::String),
+  ("preset_overrides", KeyType::AnyTable),
+  ("profile", KeyType::Profiles),
+  ("project_template", KeyType::String),
+  ("prune_template", KeyType::Bool),
+  ("queries", KeyType::Table(QUERIES_KEYS)),
+  ("release_preset", KeyType::String),
+  (
+    "rustflags",
+    KeyType::OneOf(&[KeyType::String, STRINGS, KeyType::Map(&RUSTFLAGS)]),
+  ),
+  (
+    "sdl_build",
+    KeyType::Table(&[
+      ("build_script", KeyType::String),
+      ("args", STRINGS),
+      ("env", KeyType::Map(&KeyType::String)),
+    ]),
+  ),
+  ("sdl_checksums", KeyType::Map(&KeyType::String)),
+  ("sdl_defaults", KeyType::Bool),
+  ("sdl_defaults_exclude", STRINGS),
+  ("sdl_java", KeyType::String),
+  ("signing", KeyType::Table(SIGNING_KEYS)),
+  ("split_debuginfo", KeyType::String),
+  (
+    "splash",
+    KeyType::Table(&[
+      ("background_color", KeyType::String),
+      ("icon", KeyType::String),
+      ("branding_image", KeyType::String),
+    ]),
+  ),
+  ("target_features", KeyType::Map(&STRINGS)),
+  ("title", KeyType::String),
+  (
+    "wear",
+    KeyType::Table(&[("exclude", KeyType::Bool), ("standalone", KeyType::Bool)]),
+  ),
+];
+
+/// The outcome of validating the android metadata: unknown keys are only warnings, as they may
+/// belong to a newer version of this tool, type mismatches are errors.
+#[derive(Debug, Default)]
+pub struct MetadataReport {
+  pub warnings: Vec<String>,
+  pub errors: Vec<String>,
+}
+
+/// Computes the edit distance between the two keys, counting swapped neighbours as one edit.
+fn edit_distance(a: &str, b: &str) -> usize {
+  let a: Vec<char> = a.chars().collect();
+  let b: Vec<char> = b.chars().collect();
+  let mut distances = vec![vec![0; b.len() + 1]; a.len() + 1];
+  for (i, row) in distances.iter_mut().enumerate() {
+    row[0] = i;
+  }
+  distances[0] = (0..=b.len()).collect();
+  for i in 1..=a.len() {
+    for j in 1..=b.len() {
+      let cost = usize::from(a[i - 1] != b[j - 1]);
+      let mut distance = (distances[i - 1][j] + 1)
+        .min(distances[i][j - 1] + 1)
+        .min(distances[i - 1][j - 1] + cost);
+      if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+        distance = distance.min(distances[i - 2][j - 2] + 1);
+      }
+      distances[i][j] = distance;
+    }
+  }
+  distances[a.len()][b.len()]
+}
+
+/// Suggests the known key closest to the unknown one, if any is close enough to be a typo or an
+/// abbreviation like `min_sdk` for `min_sdk_version`.
+pub fn suggest_key<'a>(unknown: &str, known: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+  let max_distance = (unknown.len() / 3).max(1);
+  known
+    .into_iter()
+    .filter_map(|key| {
+      let distance = edit_distance(unknown, key);
+      if distance <= max_distance || (unknown.len() >= 3 && key.starts_with(unknown)) {
+        Some((distance, key))
+      } else {
+        None
+      }
+    })
+    .min()
+    .map(|(_, key)| key)
+}
+
+fn check_table(
+  path: &str,
+  table: &Table,
+  keys: &[(&str, KeyType)],
+  location: &str,
+  report: &mut MetadataReport,
+) {
+  for (key, value) in table {
+    let key_path = format!("{}.{}", path, key);
+    match keys.iter().find(|(known, _)| known == key) {
+      Some((_, expected)) => check_value(&key_path, value, expected, location, report),
+      None => report.warnings.push(
+        match suggest_key(key, keys.iter().map(|(known, _)| *known)) {
+          Some(suggestion) => format!(
+            "unknown key {} in {}, did you mean {}?",
+            key_path, location, suggestion
+          ),
+          None => format!("unknown key {} in {}", key_path, location),
+        },
+      ),
+    }
+  }
+}
+
+fn check_value(
+  path: &str,
+  value: &Value,
+  expected: &KeyType,
+  location: &str,
+  report: &mut MetadataReport,
+) {
+  if !expected.matches(value) {
+    report.errors.push(format!(
+      "{} in {} must be {}, found: {}",
+      path, location, expected, value
+    ));
+    return;
+  }
+  match (expected, value) {
+    (KeyType::Array(element), Value::Array(values)) => {
+      for (i, value) in values.iter().enumerate() {
+        check_value(
+          &format!("{}[{}]", path, i),
+          value,
+          element,
+          location,
+          report,
+        );
+      }
+    }
+    (KeyType::Table(keys), Value::Table(table)) => check_table(path, table, keys, location, report),
+    (KeyType::Map(values), Value::Table(table)) => {
+      for (key, value) in table {
+        check_value(
+          &format!("{}.{}", path, key),
+          value,
+          values,
+          location,
+          report,
+        );
+      }
+    }
+    (KeyType::Profiles, Value::Table(profiles)) => {
+      for (profile, overrides) in profiles {
+        check_value(
+          &format!("{}.{}", path, profile),
+          overrides,
+          &KeyType::Table(ANDROID_METADATA_KEYS),
+          location,
+          report,
+        );
+      }
+    }
+    (KeyType::OneOf(types), value) => {
+      if let Some(matching) = types.iter().find(|t| t.matches(value)) {
+        check_value(path, value, matching, location, report);
+      }
+    }
+    _ => {}
+  }
+}
+
+/// Checks the android metadata of a parsed manifest against the known keys. `location` names the
+/// manifest in the messages.
+pub fn check_android_metadata(manifest: &Table, location: &str) -> MetadataReport {
+  let mut report = MetadataReport::default();
+  let path = "package.metadata.android";
+  if let Some(android) = manifest
+    .get("package")
+    .and_then(|package| package.get("metadata"))
+    .and_then(|metadata| metadata.get("android"))
+  {
+    check_value(
+      path,
+      android,
+      &KeyType::Table(ANDROID_METADATA_KEYS),
+      location,
+      &mut report,
+    );
+  }
+  report
+}
+
+/// Validates `[package.metadata.android]` of the manifest, including the profile overrides and
+/// every flavor. Returns the warnings about unknown keys, or all type mismatches as error.
+pub fn validate_android_metadata(manifest_path: &Path) -> Result<Vec<String>, String> {
+  let location = manifest_path.display().to_string();
+  let manifest = read_toml_file(manifest_path)?;
+  let report = check_android_metadata(&manifest, &location);
+  if report.errors.is_empty() {
+    Ok(report.warnings)
+  } else {
+    Err(report.errors.join("\n"))
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::fs::{read_dir, read_to_string};
+  use std::path::Path;
+
+  use toml::Table;
+
+  use crate::metadata_schema::{check_android_metadata, suggest_key, ANDROID_METADATA_KEYS};
+
+  fn check(manifest: &str) -> (Vec<String>, Vec<String>) {
+    let report = check_android_metadata(&manifest.parse::<Table>().unwrap(), "Cargo.toml");
+    (report.warnings, report.errors)
+  }
+
+  #[test]
+  fn key_suggestions() {
+    let keys = || ANDROID_METADATA_KEYS.iter().map(|(key, _)| *key);
+    assert_eq!(Some("permissions"), suggest_key("premissions", keys()));
+    assert_eq!(Some("min_sdk_version"), suggest_key("min_sdk", keys()));
+    assert_eq!(Some("package_name"), suggest_key("packagename", keys()));
+    assert_eq!(None, suggest_key("colour_scheme", keys()));
+  }
+
+  #[test]
+  fn valid_metadata() {
+    let (warnings, errors) = check(include_str!(
+      "../tests/fixtures/manifests/full_metadata.toml"
+    ));
+    assert_eq!(Vec::<String>::new(), warnings);
+    assert_eq!(Vec::<String>::new(), errors);
+  }
+
+  #[test]
+  fn unknown_keys() {
+    let (warnings, errors) = check(
+      r#"
+[package.metadata.android]
+premissions = ["android.permission.INTERNET"]
+colour_scheme = "dark"
+
+[package.metadata.android.signing]
+keystore = "release.jks"
+
+[package.metadata.android.signing.next_signer]
+keystore = "next.jks"
+key_alais = "next"
+
+[package.metadata.android.profile.release]
+titel = "Release"
+"#,
+    );
+    assert!(errors.is_empty(), "{:?}", errors);
+    assert_eq!(
+      vec![
+        "unknown key package.metadata.android.colour_scheme in Cargo.toml",
+        "unknown key package.metadata.android.premissions in Cargo.toml, did you mean permissions?",
+        "unknown key package.metadata.android.profile.release.titel in Cargo.toml, did you mean title?",
+        "unknown key package.metadata.android.signing.next_signer.key_alais in Cargo.toml, did you mean key_alias?",
+      ],
+      warnings
+    );
+  }
+
+  #[test]
+  fn type_mismatches() {
+    let (warnings, errors) = check(
+      r#"
+[package.metadata.android]
+min_sdk_version = "26"
+permissions = ["android.permission.INTERNET", 5]
+
+[package.metadata.android.sdl_build.env]
+NDK_DEBUG = 1
+
+[package.metadata.android.profile.release.signing]
+next_signer = "next.jks"
+"#,
+    );
+    assert!(warnings.is_empty(), "{:?}", warnings);
+    assert_eq!(
+      vec![
+        "package.metadata.android.min_sdk_version in Cargo.toml must be an integer, found: \"26\"",
+        "package.metadata.android.permissions[1] in Cargo.toml must be a string or a table, found: 5",
+        "package.metadata.android.profile.release.signing.next_signer in Cargo.toml must be a table, found: \"next.jks\"",
+        "package.metadata.android.sdl_build.env.NDK_DEBUG in Cargo.toml must be a string, found: 1",
+      ],
+      errors
+    );
+  }
+
+  #[test]
+  fn arrays_of_tables() {
+    let (warnings, errors) = check(
+      r#"
+[[package.metadata.android.flavors]]
+name = "free"
+cargo_features = ["ads"]
+
+[[package.metadata.android.flavors]]
+name = "paid"
+application_id_sufix = ".paid"
+cargo_features = "premium"
+
+[[package.metadata.android.queries.intents]]
+action = "android.intent.action.VIEW"
+data_schema = "https"
+"#,
+    );
+    assert_eq!(
+      vec![
+        "unknown key package.metadata.android.flavors[1].application_id_sufix in Cargo.toml, did you mean application_id_suffix?",
+        "unknown key package.metadata.android.queries.intents[0].data_schema in Cargo.toml, did you mean data_scheme?",
+      ],
+      warnings
+    );
+    assert_eq!(
+      vec![
+        "package.metadata.android.flavors[1].cargo_features in Cargo.toml must be an array of strings, found: \"premium\""
+      ],
+      errors
+    );
+
+    let (_, errors) = check(
+      r#"
+[package.metadata.android]
+flavors = ["free", "paid"]
+"#,
+    );
+    assert_eq!(
+      vec![
+        "package.metadata.android.flavors[0] in Cargo.toml must be a table, found: \"free\"",
+        "package.metadata.android.flavors[1] in Cargo.toml must be a table, found: \"paid\"",
+      ],
+      errors
+    );
+  }
+
+  #[test]
+  fn schema_covers_metadata_lookups() {
+    let prefix = ["package", "metadata", "android"]
+      .map(|key| format!("\"{}\",", key))
+      .concat();
+    let src_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("src");
+    let mut looked_up = vec![];
+    for entry in read_dir(src_dir).unwrap() {
+      let path = entry.unwrap().path();
+      if path.extension().is_none_or(|extension| extension != "rs") {
+        continue;
+      }
+      //rustfmt may spread a lookup path over several lines
+      let source: String = read_to_string(&path)
+        .unwrap()
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect();
+      for (start, _) in source.match_indices(&prefix) {
+        let key = &source[start + prefix.len()..];
+        let Some(key) = key.strip_prefix('"').and_then(|key| key.split('"').next()) else {
+          continue;
+        };
+        looked_up.push((key.to_string(), path.clone()));
+      }
+    }
+    assert!(looked_up.len() > 10);
+    for (key, path) in looked_up {
+      assert!(
+        ANDROID_METADATA_KEYS.iter().any(|(known, _)| *known == key),
+        "{} looks up package.metadata.android.{}, which isn't in ANDROID_METADATA_KEYS",
+        path.display(),
+        key
+      );
+    }
+  }
+}
@@ -0,0 +1,205 @@
+use std::fs::{create_dir_all, write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::adb::*;
+use crate::android_project::*;
+use crate::anr::*;
+use crate::command_runner::*;
+use crate::last_build::*;
+use crate::ndk::*;
+use crate::split_debuginfo::*;
+use crate::util::*;
+use crate::BuildProfile;
+
+/// Result of a monkey run, parsed from its output.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct MonkeyReport {
+  /// `// CRASH:` lines, one per crash.
+  pub crashes: Vec<String>,
+  /// `// NOT RESPONDING:` lines, one per ANR.
+  pub anrs: Vec<String>,
+  pub native_crash: bool,
+  /// Whether monkey injected all events.
+  pub finished: bool,
+}
+
+impl MonkeyReport {
+  pub fn is_clean(&self) -> bool {
+    self.finished && self.crashes.is_empty() && self.anrs.is_empty()
+  }
+}
+
+pub fn parse_monkey_output(output: &str) -> MonkeyReport {
+  let mut report = MonkeyReport::default();
+  for line in output.lines().map(|line| line.trim()) {
+    if line.starts_with("// CRASH:") {
+      report.crashes.push(line.to_string());
+    } else if line.starts_with("// NOT RESPONDING:") {
+      report.anrs.push(line.to_string());
+    } else if line.starts_with("// Short Msg: Native crash") {
+      report.native_crash = true;
+    } else if line == "// Monkey finished" {
+      report.finished = true;
+    }
+  }
+  report
+}
+
+/// A seed for runs without `--seed`.
+pub fn random_seed() -> u64 {
+  SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map(|d| d.as_nanos() as u64 % 1_000_000_000)
+    .unwrap_or(0)
+}
+
+/// Directory of the unstripped libraries of the debug build for the given abi. Gradle only strips
+/// the copies packaged into the apk.
+pub fn get_symbol_dir(manifest_dir: &Path, abi: &str) -> PathBuf {
+  get_last_build_symbols_dir(manifest_dir, BuildProfile::Debug).join(abi)
+}
+
+/// Symbolicates the native crashes in the log with ndk-stack, returning its output.
+pub fn symbolicate(manifest_dir: &Path, log_path: &Path) -> Option<String> {
+  let abi = adb_shell(&["getprop", "ro.product.cpu.abi"])?;
+  let symbol_dir = get_symbol_dir(manifest_dir, &abi);
+  if !symbol_dir.is_dir() {
+    eprintln!(
+      "WARNING: No symbols for {} at {}, unable to symbolicate the native crash.",
+      abi,
+      symbol_dir.display()
+    );
+    return None;
+  }
+
+  if let Err(e) = check_split_debuginfo(&symbol_dir.join("libmain.so")) {
+    eprintln!("WARNING: {}.", e);
+  }
+  let Some(ndk_stack) = find_ndk_stack(&get_env_path("ANDROID_NDK_HOME")) else {
+    eprintln!("WARNING: ndk-stack not found in the NDK, unable to symbolicate the native crash.");
+    return None;
+  };
+  let output = run_command_output(
+    ndk_script_command(&ndk_stack)
+      .arg("-sym")
+      .arg(&symbol_dir)
+      .arg("-i")
+      .arg(log_path),
+  )
+  .ok()
+  .filter(|output| output.status.success())?;
+  Some(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Saves the logcat of the run, which contains the crash reports and tombstones, and symbolicates
+/// native crashes. Returns the saved files.
+fn collect_crash_logs(manifest_dir: &Path, seed: u64, report: &MonkeyReport) -> Vec<PathBuf> {
+  let output = run_command_output(adb_command().args(["logcat", "-d", "-b", "main,system,crash"]))
+    .ok()
+    .filter(|output| output.status.success());
+  let Some(output) = output else {
+    eprintln!("WARNING: Unable to read logcat from the device.");
+    return vec![];
+  };
+
+  let dir = manifest_dir.join("target/monkey");
+  create_dir_all(&dir).unwrap_or_else(|_| panic!("Unable to create directory {:?}", dir));
+  let log_path = dir.join(format!("seed-{}.log", seed));
+  write(&log_path, &output.stdout).unwrap_or_else(|_| panic!("Unable to write {:?}", log_path));
+  let mut files = vec![log_path.clone()];
+
+  if report.native_crash {
+    if let Some(stack) = symbolicate(manifest_dir, &log_path) {
+      println!("{}", stack);
+      let stack_path = dir.join(format!("seed-{}-symbolicated.txt", seed));
+      write(&stack_path, stack).unwrap_or_else(|_| panic!("Unable to write {:?}", stack_path));
+      files.push(stack_path);
+    }
+  }
+  files
+}
+
+/// Runs monkey against the installed debug build. Returns whether the run was clean.
+pub fn run_monkey(manifest_path: &Path, events: u32, seed: u64, throttle: u32) -> bool {
+  let manifest_dir = manifest_path.parent().unwrap();
+  let app_id = get_effective_app_id(manifest_path, BuildProfile::Debug)
+    .unwrap_or_else(|e| exit_with_error(&e.into()));
+
+  //start with an empty log, so only crashes of this run are collected
+  run_command_checked(adb_command().args(["logcat", "-c"]));
+
+  println!("Running monkey with seed {}", seed);
+  let output = run_command_captured(adb_command().args([
+    "shell",
+    "monkey",
+    "-p",
+    &app_id,
+    "--throttle",
+    &throttle.to_string(),
+    "-s",
+    &seed.to_string(),
+    "-v",
+    &events.to_string(),
+  ]));
+  let report = parse_monkey_output(&String::from_utf8_lossy(&output.stdout));
+  if report.is_clean() {
+    println!("Monkey finished {} events without problems.", events);
+    return true;
+  }
+
+  for crash in &report.crashes {
+    eprintln!("{}", crash);
+  }
+  for anr in &report.anrs {
+    eprintln!("{}", anr);
+  }
+  let mut files = collect_crash_logs(manifest_dir, seed, &report);
+  if !report.anrs.is_empty() {
+    files.extend(report_anr(manifest_dir, &app_id));
+  }
+  for file in files {
+    eprintln!("Saved {}", file.display());
+  }
+  eprintln!(
+    "Error: Monkey run failed ({} crashes, {} ANRs{}). Reproduce it with --seed {}.",
+    report.crashes.len(),
+    report.anrs.len(),
+    if report.finished { "" } else { ", aborted" },
+    seed
+  );
+  false
+}
+
+#[cfg(test)]
+mod test {
+  use crate::monkey::parse_monkey_output;
+
+  #[test]
+  fn clean_run() {
+    let report = parse_monkey_output(
+      ":Monkey: seed=42 count=500\n:AllowPackage: com.example.game\n    // Allowing start of Intent { cmp=com.example.game/.MainActivity } in package com.example.game\nEvents injected: 500\n:Dropped: keys=0 pointers=0 trackballs=0 flips=0 rotations=0\n## Network stats: elapsed time=25513ms\n// Monkey finished\n",
+    );
+    assert!(report.is_clean());
+  }
+
+  #[test]
+  fn crashed_run() {
+    let report = parse_monkey_output(
+      ":Monkey: seed=42 count=500\n// CRASH: com.example.game (pid 4242)\n// Short Msg: Native crash\n// Long Msg: Native crash: Segmentation fault\n** Monkey aborted due to error.\nEvents injected: 123\n** System appears to have crashed at event 123 of 500 using seed 42\n",
+    );
+    assert!(!report.is_clean());
+    assert!(!report.finished);
+    assert!(report.native_crash);
+    assert_eq!(
+      report.crashes,
+      vec!["// CRASH: com.example.game (pid 4242)"]
+    );
+
+    let report = parse_monkey_output(
+      "// NOT RESPONDING: com.example.game (pid 4242)\nANR in com.example.game\n// Monkey finished\n",
+    );
+    assert!(!report.is_clean());
+    assert_eq!(report.anrs.len(), 1);
+  }
+}
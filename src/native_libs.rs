@@ -0,0 +1,389 @@
+use std::collections::HashMap;
+use std::fs::{metadata, read};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Slack for file systems storing modification times with a coarse resolution, e.g. FAT with 2s.
+const MTIME_RESOLUTION: Duration = Duration::from_secs(2);
+
+const PT_NOTE: u32 = 4;
+const NT_GNU_BUILD_ID: u32 = 3;
+
+const SHT_DYNSYM: u64 = 11;
+const STB_GLOBAL: u8 = 1;
+const STB_WEAK: u8 = 2;
+const SHN_UNDEF: u64 = 0;
+
+/// The function SDL's Java glue calls in libmain.so.
+pub const SDL_MAIN_SYMBOL: &str = "SDL_main";
+/// The function the platform's NativeActivity calls in the library.
+pub const NATIVE_ACTIVITY_SYMBOL: &str = "ANativeActivity_onCreate";
+
+const EM_386: u16 = 3;
+const EM_ARM: u16 = 40;
+const EM_X86_64: u16 = 62;
+const EM_AARCH64: u16 = 183;
+const EM_RISCV: u16 = 243;
+
+/// Reads an unsigned integer of `len` bytes at `offset` of the ELF file.
+fn read_uint(data: &[u8], offset: usize, len: usize, little_endian: bool) -> Option<u64> {
+  let bytes = data.get(offset..offset.checked_add(len)?)?;
+  let fold = |value: u64, byte: &u8| value << 8 | *byte as u64;
+  Some(if little_endian {
+    bytes.iter().rev().fold(0, fold)
+  } else {
+    bytes.iter().fold(0, fold)
+  })
+}
+
+/// The GNU build id of an ELF file as hex, read from its `PT_NOTE` segments. The NDK linker adds
+/// one to every library, so libraries of different builds can be told apart.
+pub fn parse_elf_build_id(data: &[u8]) -> Option<String> {
+  if data.get(0..4)? != b"\x7fELF" {
+    return None;
+  }
+  let is_64 = *data.get(4)? == 2;
+  let little_endian = *data.get(5)? == 1;
+  let word = if is_64 { 8 } else { 4 };
+  let uint = |offset: usize, len: usize| read_uint(data, offset, len, little_endian);
+
+  let (phoff, phentsize, phnum) = if is_64 {
+    (uint(32, 8)?, uint(54, 2)?, uint(56, 2)?)
+  } else {
+    (uint(28, 4)?, uint(42, 2)?, uint(44, 2)?)
+  };
+  for i in 0..phnum {
+    let header = usize::try_from(phoff + i * phentsize).ok()?;
+    if uint(header, 4)? != PT_NOTE as u64 {
+      continue;
+    }
+    let (offset, size) = if is_64 {
+      (uint(header + 8, word)?, uint(header + 32, word)?)
+    } else {
+      (uint(header + 4, word)?, uint(header + 16, word)?)
+    };
+    let mut note = usize::try_from(offset).ok()?;
+    let end = note.checked_add(usize::try_from(size).ok()?)?;
+    while note + 12 <= end {
+      let name_size = uint(note, 4)? as usize;
+      let desc_size = uint(note + 4, 4)? as usize;
+      let note_type = uint(note + 8, 4)? as u32;
+      let name_start = note + 12;
+      let desc_start = name_start + name_size.next_multiple_of(4);
+      if note_type == NT_GNU_BUILD_ID && data.get(name_start..name_start + name_size)? == b"GNU\0" {
+        let desc = data.get(desc_start..desc_start + desc_size)?;
+        return Some(desc.iter().map(|b| format!("{:02x}", b)).collect());
+      }
+      note = desc_start + desc_size.next_multiple_of(4);
+    }
+  }
+  None
+}
+
+/// The `e_machine` of an ELF file, the architecture it is built for.
+pub fn parse_elf_machine(data: &[u8]) -> Option<u16> {
+  if data.get(0..4)? != b"\x7fELF" {
+    return None;
+  }
+  let little_endian = *data.get(5)? == 1;
+  read_uint(data, 18, 2, little_endian).map(|machine| machine as u16)
+}
+
+/// The ELF `e_machine` of the libraries of the abi, for the abis of the NDK.
+pub fn get_abi_elf_machine(abi: &str) -> Option<u16> {
+  match abi {
+    "armeabi-v7a" => Some(EM_ARM),
+    "arm64-v8a" => Some(EM_AARCH64),
+    "x86" => Some(EM_386),
+    "x86_64" => Some(EM_X86_64),
+    "riscv64" => Some(EM_RISCV),
+    _ => None,
+  }
+}
+
+/// The names of the symbols the ELF file exports from its dynamic symbol table: defined global or
+/// weak symbols of default visibility. `None` if the file has no section headers to find it.
+pub fn parse_elf_exported_symbols(data: &[u8]) -> Option<Vec<String>> {
+  if data.get(0..4)? != b"\x7fELF" {
+    return None;
+  }
+  let is_64 = *data.get(4)? == 2;
+  let little_endian = *data.get(5)? == 1;
+  let word = if is_64 { 8 } else { 4 };
+  let uint =
+    |offset: u64, len: usize| read_uint(data, usize::try_from(offset).ok()?, len, little_endian);
+
+  let (shoff, shentsize, shnum) = if is_64 {
+    (uint(40, 8)?, uint(58, 2)?, uint(60, 2)?)
+  } else {
+    (uint(32, 4)?, uint(46, 2)?, uint(48, 2)?)
+  };
+  if shoff == 0 {
+    return None;
+  }
+  //offset, size, link and entry size of a section
+  let section = |index: u64| {
+    let header = shoff + index * shentsize;
+    Some(if is_64 {
+      (
+        uint(header + 24, word)?,
+        uint(header + 32, word)?,
+        uint(header + 40, 4)?,
+        uint(header + 56, word)?,
+      )
+    } else {
+      (
+        uint(header + 16, word)?,
+        uint(header + 20, word)?,
+        uint(header + 24, 4)?,
+        uint(header + 36, word)?,
+      )
+    })
+  };
+  let dynsym = (0..shnum).find(|i| uint(shoff + i * shentsize + 4, 4) == Some(SHT_DYNSYM))?;
+  let (offset, size, link, entsize) = section(dynsym)?;
+  let (strings, strings_size, _, _) = section(link)?;
+  if entsize == 0 {
+    return None;
+  }
+
+  let mut symbols = vec![];
+  for symbol in (offset..offset + size).step_by(entsize as usize) {
+    let (name, info, other, shndx) = if is_64 {
+      (
+        uint(symbol, 4)?,
+        uint(symbol + 4, 1)?,
+        uint(symbol + 5, 1)?,
+        uint(symbol + 6, 2)?,
+      )
+    } else {
+      (
+        uint(symbol, 4)?,
+        uint(symbol + 12, 1)?,
+        uint(symbol + 13, 1)?,
+        uint(symbol + 14, 2)?,
+      )
+    };
+    let binding = (info >> 4) as u8;
+    if name == 0
+      || name >= strings_size
+      || shndx == SHN_UNDEF
+      || other & 3 != 0
+      || (binding != STB_GLOBAL && binding != STB_WEAK)
+    {
+      continue;
+    }
+    let start = usize::try_from(strings + name).ok()?;
+    let end = start + data.get(start..)?.iter().position(|b| *b == 0)?;
+    symbols.push(String::from_utf8_lossy(&data[start..end]).to_string());
+  }
+  Some(symbols)
+}
+
+/// Checks that the library exports the entry point the activity calls, which would otherwise
+/// crash the app right at its start with an `UnsatisfiedLinkError`. Libraries whose symbols can't
+/// be read pass.
+pub fn check_entry_point(library: &Path, symbol: &str) -> Result<(), String> {
+  let Some(symbols) = read(library)
+    .ok()
+    .and_then(|data| parse_elf_exported_symbols(&data))
+  else {
+    return Ok(());
+  };
+  if symbols.iter().any(|s| s == symbol) {
+    return Ok(());
+  }
+  let hint = match symbol {
+    SDL_MAIN_SYMBOL => "Define it without name mangling:\n\n\
+      #[no_mangle]\n\
+      pub extern \"C\" fn SDL_main(_argc: std::ffi::c_int, _argv: *const *const std::ffi::c_char) -> std::ffi::c_int {\n    \
+        //the game\n    \
+        0\n\
+      }\n\n\
+      A package without a bin is built from its [lib], which needs crate-type = [\"cdylib\"] in Cargo.toml"
+      .to_string(),
+    _ => "Define it with a crate like android-activity, or build a package whose [lib] has \
+      crate-type = [\"cdylib\"]"
+      .to_string(),
+  };
+  Err(format!(
+    "{} doesn't export {}, so the app would exit right after its start. {}",
+    library.display(),
+    symbol,
+    hint
+  ))
+}
+
+/// Reads the GNU build id of the library.
+pub fn read_build_id(library: &Path) -> Option<String> {
+  parse_elf_build_id(&read(library).ok()?)
+}
+
+/// Whether the library lies in the dir cargo builds the target with the profile into,
+/// `target/<triple>/<profile dir>/`, e.g. `debug` for the `dev` profile.
+pub fn is_in_profile_dir(library: &Path, rust_target: &str, profile_dir: &str) -> bool {
+  let components: Vec<_> = library.components().map(|c| c.as_os_str()).collect();
+  components
+    .windows(2)
+    .any(|pair| pair[0] == rust_target && pair[1] == profile_dir)
+}
+
+/// Checks that the library of the target was written by this build, so no library of a previous
+/// build or another profile is packaged.
+pub fn check_native_library(
+  rust_target: &str,
+  library: Option<&PathBuf>,
+  profile_dir: &str,
+  modified: Option<SystemTime>,
+  build_start: SystemTime,
+) -> Result<(), String> {
+  let Some(library) = library else {
+    return Err(format!(
+      "cargo reported no native library for {} in this build, refusing to package a library of a \
+      previous build",
+      rust_target
+    ));
+  };
+  if !is_in_profile_dir(library, rust_target, profile_dir) {
+    return Err(format!(
+      "the native library {} for {} isn't in the {} dir of the target, refusing to package it",
+      library.display(),
+      rust_target,
+      profile_dir
+    ));
+  }
+  match modified {
+    Some(modified) if modified + MTIME_RESOLUTION >= build_start => Ok(()),
+    _ => Err(format!(
+      "the native library {} for {} is older than this build, refusing to package a stale library. \
+      Run cargo sdl-apk clean and build again",
+      library.display(),
+      rust_target
+    )),
+  }
+}
+
+/// Checks the libraries cargo reported for all targets, see [check_native_library].
+pub fn check_native_libraries(
+  libraries: &HashMap<String, PathBuf>,
+  targets: &[&str],
+  profile_dir: &str,
+  build_start: SystemTime,
+) -> Result<(), String> {
+  for target in targets {
+    let library = libraries.get(*target);
+    let modified = library.and_then(|l| metadata(l).and_then(|m| m.modified()).ok());
+    check_native_library(target, library, profile_dir, modified, build_start)?;
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod test {
+  use std::fs::read;
+  use std::path::{Path, PathBuf};
+  use std::time::{Duration, SystemTime};
+
+  use crate::native_libs::{
+    check_entry_point, check_native_library, is_in_profile_dir, parse_elf_build_id,
+    parse_elf_exported_symbols, NATIVE_ACTIVITY_SYMBOL, SDL_MAIN_SYMBOL,
+  };
+
+  /// A little endian ELF64 header with one PT_NOTE program header pointing at `notes`.
+  fn elf64(notes: &[u8]) -> Vec<u8> {
+    let mut data = vec![0u8; 64 + 56];
+    data[0..4].copy_from_slice(b"\x7fELF");
+    data[4] = 2;
+    data[5] = 1;
+    data[32..40].copy_from_slice(&64u64.to_le_bytes());
+    data[54..56].copy_from_slice(&56u16.to_le_bytes());
+    data[56..58].copy_from_slice(&1u16.to_le_bytes());
+    data[64..68].copy_from_slice(&4u32.to_le_bytes());
+    data[72..80].copy_from_slice(&120u64.to_le_bytes());
+    data[96..104].copy_from_slice(&(notes.len() as u64).to_le_bytes());
+    data.extend_from_slice(notes);
+    data
+  }
+
+  fn note(name: &[u8], note_type: u32, desc: &[u8]) -> Vec<u8> {
+    let mut note = vec![];
+    note.extend_from_slice(&(name.len() as u32).to_le_bytes());
+    note.extend_from_slice(&(desc.len() as u32).to_le_bytes());
+    note.extend_from_slice(&note_type.to_le_bytes());
+    note.extend_from_slice(name);
+    note.resize(note.len().next_multiple_of(4), 0);
+    note.extend_from_slice(desc);
+    note
+  }
+
+  #[test]
+  fn build_id() {
+    let mut notes = note(b"Android\0", 1, &[0x22, 0, 0, 0]);
+    notes.extend(note(b"GNU\0", 3, &[0xde, 0xad, 0xbe, 0xef, 0x01]));
+    assert_eq!(
+      Some("deadbeef01".to_string()),
+      parse_elf_build_id(&elf64(&notes))
+    );
+    assert_eq!(
+      None,
+      parse_elf_build_id(&elf64(&note(b"Android\0", 1, &[0; 4])))
+    );
+    assert_eq!(None, parse_elf_build_id(b"not an elf file"));
+    assert_eq!(None, parse_elf_build_id(&elf64(&notes)[..100]));
+  }
+
+  #[test]
+  fn entry_point() {
+    let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/native");
+    let with_sdl_main = fixtures.join("sdl_main.so");
+    let without_sdl_main = fixtures.join("no_sdl_main.so");
+    assert_eq!(
+      Some(vec!["SDL_main".to_string(), "other".to_string()]),
+      parse_elf_exported_symbols(&read(&with_sdl_main).unwrap())
+    );
+    assert_eq!(Ok(()), check_entry_point(&with_sdl_main, SDL_MAIN_SYMBOL));
+
+    let error = check_entry_point(&without_sdl_main, SDL_MAIN_SYMBOL).unwrap_err();
+    assert!(error.contains("doesn't export SDL_main"));
+    assert!(error.contains("#[no_mangle]\npub extern \"C\" fn SDL_main("));
+    assert!(error.contains("crate-type = [\"cdylib\"]"));
+    assert!(check_entry_point(&with_sdl_main, NATIVE_ACTIVITY_SYMBOL).is_err());
+
+    //without section headers the symbols are unknown
+    assert_eq!(None, parse_elf_exported_symbols(&elf64(&[])));
+  }
+
+  #[test]
+  fn native_library_checks() {
+    let target = "aarch64-linux-android";
+    let release = PathBuf::from("/game/target/aarch64-linux-android/release/deps/libmain.so");
+    let debug = PathBuf::from("/game/target/aarch64-linux-android/debug/deps/libmain.so");
+    let dist = PathBuf::from("/game/target/aarch64-linux-android/dist/deps/libmain.so");
+    assert!(is_in_profile_dir(&release, target, "release"));
+    assert!(!is_in_profile_dir(&debug, target, "release"));
+    assert!(!is_in_profile_dir(
+      &release,
+      "x86_64-linux-android",
+      "release"
+    ));
+    assert!(is_in_profile_dir(&dist, target, "dist"));
+    assert!(!is_in_profile_dir(&dist, target, "release"));
+
+    let start = SystemTime::now();
+    let after = Some(start + Duration::from_secs(30));
+    let check =
+      |library, modified| check_native_library(target, library, "release", modified, start);
+    assert_eq!(Ok(()), check(Some(&release), after));
+    assert!(check(Some(&debug), after)
+      .unwrap_err()
+      .contains("isn't in the release dir"));
+    assert!(
+      check(Some(&release), Some(start - Duration::from_secs(3600)))
+        .unwrap_err()
+        .contains("older than this build")
+    );
+    assert!(check(None, after)
+      .unwrap_err()
+      .starts_with("cargo reported no native library for aarch64-linux-android"));
+  }
+}
@@ -0,0 +1,147 @@
+use std::env::consts::{ARCH, OS};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::util::*;
+
+/// Host tags of the prebuilt NDK toolchains which can run on the host, the native one first. Apple
+/// Silicon runs the x86_64 toolchain under Rosetta if the NDK has no arm64 binaries.
+pub fn get_host_tag_candidates(os: &str, arch: &str) -> &'static [&'static str] {
+  match (os, arch) {
+    ("macos", "aarch64") => &["darwin-arm64", "darwin-x86_64"],
+    ("macos", _) => &["darwin-x86_64"],
+    ("linux", "aarch64") => &["linux-aarch64", "linux-x86_64"],
+    ("windows", _) => &["windows-x86_64"],
+    _ => &["linux-x86_64"],
+  }
+}
+
+/// Finds the host tag of the prebuilt toolchain in the NDK, checking which of the candidates
+/// actually exist.
+pub fn find_host_tag(ndk_home: &Path, candidates: &[&'static str]) -> Option<&'static str> {
+  candidates
+    .iter()
+    .copied()
+    .find(|tag| ndk_home.join("toolchains/llvm/prebuilt").join(tag).is_dir())
+}
+
+/// The prebuilt toolchain dir of the NDK for the host, containing clang, llvm-ar and lldb. Falls
+/// back to the native one if none exists, for error messages naming the expected location.
+pub fn get_ndk_toolchain_dir_in(ndk_home: &Path) -> PathBuf {
+  let candidates = get_host_tag_candidates(OS, ARCH);
+  let tag = find_host_tag(ndk_home, candidates).unwrap_or(candidates[0]);
+  ndk_home.join("toolchains/llvm/prebuilt").join(tag)
+}
+
+/// The prebuilt toolchain dir of the NDK in `ANDROID_NDK_HOME` for the host.
+pub fn get_ndk_toolchain_dir() -> PathBuf {
+  get_ndk_toolchain_dir_in(&get_env_path("ANDROID_NDK_HOME"))
+}
+
+/// Checks that the NDK has a toolchain the host can run, returning its host tag.
+pub fn check_ndk_toolchain(ndk_home: &Path) -> Result<&'static str, String> {
+  let candidates = get_host_tag_candidates(OS, ARCH);
+  match find_host_tag(ndk_home, candidates) {
+    Some(tag) => Ok(tag),
+    None => Err(format!(
+      "The NDK at {:?} has no prebuilt toolchain for this host, expected one of {} in \
+      toolchains/llvm/prebuilt",
+      ndk_home,
+      candidates.join(", ")
+    )),
+  }
+}
+
+/// ndk-stack of the NDK. Older NDKs only have it in the prebuilt dir of the host.
+pub fn find_ndk_stack(ndk_home: &Path) -> Option<PathBuf> {
+  let candidates = get_host_tag_candidates(OS, ARCH);
+  std::iter::once(ndk_home.join("ndk-stack"))
+    .chain(
+      candidates
+        .iter()
+        .map(|tag| ndk_home.join("prebuilt").join(tag).join("bin/ndk-stack")),
+    )
+    .find(|path| path.is_file())
+}
+
+/// A command running a shell script of the NDK like ndk-build. The script is run with sh if it
+/// isn't executable, which happens when the NDK was unpacked without permissions.
+pub fn ndk_script_command(script: &Path) -> Command {
+  if is_executable(script) {
+    Command::new(script)
+  } else {
+    let mut command = Command::new("sh");
+    command.arg(script);
+    command
+  }
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+  use std::os::unix::fs::PermissionsExt;
+  path
+    .metadata()
+    .is_ok_and(|metadata| metadata.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(_path: &Path) -> bool {
+  true
+}
+
+#[cfg(test)]
+mod test {
+  use std::fs::{create_dir_all, write};
+
+  use tempfile::tempdir;
+
+  use crate::ndk::{find_host_tag, get_host_tag_candidates, ndk_script_command};
+
+  #[test]
+  fn host_tag_detection() {
+    let ndk = tempdir().unwrap();
+    let prebuilt = ndk.path().join("toolchains/llvm/prebuilt");
+    let apple_silicon = get_host_tag_candidates("macos", "aarch64");
+
+    //older NDKs only have x86_64 binaries, run with Rosetta
+    create_dir_all(prebuilt.join("darwin-x86_64")).unwrap();
+    assert_eq!(
+      find_host_tag(ndk.path(), apple_silicon),
+      Some("darwin-x86_64")
+    );
+    //native binaries are preferred
+    create_dir_all(prebuilt.join("darwin-arm64")).unwrap();
+    assert_eq!(
+      find_host_tag(ndk.path(), apple_silicon),
+      Some("darwin-arm64")
+    );
+    assert_eq!(
+      find_host_tag(ndk.path(), get_host_tag_candidates("macos", "x86_64")),
+      Some("darwin-x86_64")
+    );
+    assert_eq!(
+      find_host_tag(ndk.path(), get_host_tag_candidates("linux", "x86_64")),
+      None
+    );
+  }
+
+  #[test]
+  #[cfg(unix)]
+  fn scripts_without_permissions() {
+    use std::fs::{set_permissions, Permissions};
+    use std::os::unix::fs::PermissionsExt;
+
+    let ndk = tempdir().unwrap();
+    let script = ndk.path().join("ndk-build");
+    write(&script, "#!/bin/sh\necho built\n").unwrap();
+    let command = ndk_script_command(&script);
+    assert_eq!(command.get_program(), "sh");
+    assert_eq!(command.get_args().collect::<Vec<_>>(), vec![&script]);
+
+    set_permissions(&script, Permissions::from_mode(0o755)).unwrap();
+    assert_eq!(
+      ndk_script_command(&script).get_program(),
+      script.as_os_str()
+    );
+  }
+}
@@ -0,0 +1,377 @@
+use std::env;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use cargo::core::Workspace;
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::build_bin_as_lib::*;
+use crate::cli::NetworkArgs;
+use crate::project_template::*;
+use crate::util::*;
+
+lazy_static! {
+  static ref NETWORK_ARGS: Mutex<NetworkArgs> = Mutex::new(NetworkArgs::default());
+  static ref GRADLE_DISTRIBUTION_REGEX: Regex =
+    Regex::new(r"distributionUrl=.*/(gradle-[\w.\-]+)\.zip").expect("invalid distribution regex");
+  static ref GRADLE_DISTRIBUTION_URL_REGEX: Regex =
+    Regex::new(r"(?m)^\s*distributionUrl\s*=\s*(.+?)\s*$").expect("invalid distribution url regex");
+  static ref AGP_VERSION_REGEX: Regex = Regex::new(
+    r#"(?:com\.android\.tools\.build:gradle:|id\s*\(?\s*['"]com\.android\.application['"]\s*\)?\s*version\s*['"])([\w.\-]+)"#
+  )
+  .expect("invalid android gradle plugin regex");
+}
+
+/// Sets `--locked`, `--frozen` and `--offline` for all following cargo and gradle invocations.
+pub fn set_network_args(args: NetworkArgs) {
+  *NETWORK_ARGS.lock().unwrap() = args;
+}
+
+pub fn get_network_args() -> NetworkArgs {
+  *NETWORK_ARGS.lock().unwrap()
+}
+
+/// Whether nothing may be downloaded. `--frozen` implies `--offline`, like it does for cargo.
+pub fn is_offline() -> bool {
+  let args = get_network_args();
+  args.offline || args.frozen
+}
+
+/// The name of the gradle distribution in gradle-wrapper.properties, e.g. `gradle-7.3-bin`.
+pub fn parse_gradle_distribution(properties: &str) -> Option<String> {
+  GRADLE_DISTRIBUTION_REGEX
+    .captures(properties)
+    .map(|c| c[1].to_string())
+}
+
+/// The version of the android gradle plugin in the top-level build.gradle.
+pub fn parse_android_gradle_plugin_version(build_gradle: &str) -> Option<String> {
+  AGP_VERSION_REGEX
+    .captures(build_gradle)
+    .map(|c| c[1].to_string())
+}
+
+/// The url of the gradle distribution in gradle-wrapper.properties, with the `\:` escapes of the
+/// properties format removed.
+pub fn parse_gradle_distribution_url(properties: &str) -> Option<String> {
+  GRADLE_DISTRIBUTION_URL_REGEX
+    .captures(properties)
+    .map(|c| c[1].replace("\\:", ":"))
+}
+
+/// The gradle user home set with `--gradle-user-home`, or else the `GRADLE_USER_HOME` env var, or
+/// else `gradle_user_home` of the android metadata relative to the package. Gradle is started with
+/// it, so a cache dir mounted in CI is reused.
+pub fn get_configured_gradle_user_home(
+  manifest_path: &Path,
+  requested: Option<&Path>,
+) -> Result<Option<PathBuf>, TomlError> {
+  if let Some(dir) = requested
+    .map(PathBuf::from)
+    .or_else(|| env::var_os("GRADLE_USER_HOME").map(PathBuf::from))
+  {
+    return Ok(Some(dir));
+  }
+  Ok(
+    get_toml_string(
+      manifest_path,
+      ["package", "metadata", "android", "gradle_user_home"],
+    )?
+    .map(|dir| manifest_path.parent().unwrap().join(dir)),
+  )
+}
+
+/// The gradle user home gradle uses, see [get_configured_gradle_user_home], by default
+/// `~/.gradle`.
+pub fn get_gradle_user_home(
+  manifest_path: &Path,
+  requested: Option<&Path>,
+) -> Result<Option<PathBuf>, TomlError> {
+  Ok(
+    get_configured_gradle_user_home(manifest_path, requested)?
+      .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".gradle"))),
+  )
+}
+
+/// Checks for an unpacked wrapper distribution, e.g.
+/// `wrapper/dists/gradle-7.3-bin/<hash>/gradle-7.3`.
+pub fn is_gradle_distribution_cached(gradle_home: &Path, distribution: &str) -> bool {
+  let unpacked = distribution
+    .trim_end_matches("-bin")
+    .trim_end_matches("-all");
+  std::fs::read_dir(gradle_home.join("wrapper/dists").join(distribution))
+    .map(|entries| {
+      entries
+        .filter_map(|e| e.ok())
+        .any(|e| e.path().join(unpacked).is_dir())
+    })
+    .unwrap_or(false)
+}
+
+/// Checks the gradle dependency cache for a module.
+pub fn is_gradle_module_cached(gradle_home: &Path, group: &str, name: &str, version: &str) -> bool {
+  gradle_home
+    .join("caches/modules-2/files-2.1")
+    .join(group)
+    .join(name)
+    .join(version)
+    .is_dir()
+}
+
+/// Checks whether the distribution of the gradle wrapper is cached in the gradle user home and
+/// describes it. Offline, a missing distribution fails with the url to download instead of letting
+/// the wrapper wait for the network.
+pub fn check_wrapper_distribution(
+  wrapper_properties: &str,
+  gradle_home: &Path,
+  offline: bool,
+) -> Result<String, String> {
+  let url = parse_gradle_distribution_url(wrapper_properties)
+    .ok_or("no distributionUrl in gradle-wrapper.properties")?;
+  let distribution = parse_gradle_distribution(wrapper_properties)
+    .ok_or_else(|| format!("unable to determine the gradle version of {}", url))?;
+  if is_gradle_distribution_cached(gradle_home, &distribution) {
+    Ok(format!(
+      "Gradle distribution {} cached in {}",
+      url,
+      gradle_home.display()
+    ))
+  } else if offline {
+    Err(format!(
+      "Gradle distribution {} is not cached in {} and can't be downloaded offline. Pre-seed the \
+      cache by running a build with network access and the same --gradle-user-home, or download \
+      {} and set distributionUrl to the local file",
+      distribution,
+      gradle_home.display(),
+      url
+    ))
+  } else {
+    Ok(format!(
+      "Gradle distribution {} not cached, downloading into {}",
+      url,
+      gradle_home.display()
+    ))
+  }
+}
+
+/// Resolves the dependencies of the package without network access and makes sure all of them
+/// are downloaded.
+fn check_cargo_dependencies(manifest_path: &Path) -> Result<String, String> {
+  let cargo_config = create_cargo_config(None, &["net.offline=true".to_string()]);
+  let workspace = Workspace::new(manifest_path, &cargo_config).map_err(|e| e.to_string())?;
+  let (packages, resolve) = cargo::ops::resolve_ws(&workspace).map_err(|e| format!("{:#}", e))?;
+  let ids: Vec<_> = resolve.iter().collect();
+  packages
+    .get_many(ids.iter().copied())
+    .map_err(|e| format!("{:#}", e))?;
+  Ok(format!("{} crates available", ids.len()))
+}
+
+fn check_gradle(template: &ProjectTemplate, gradle_home: &Path) -> Result<String, String> {
+  if template.read("gradlew").is_none() {
+    //built with the gradle on the path
+    return Ok("using the installed gradle".to_string());
+  }
+  let distribution = template
+    .read("gradle/wrapper/gradle-wrapper.properties")
+    .and_then(|properties| parse_gradle_distribution(&properties))
+    .ok_or("unable to determine the gradle version of the template".to_string())?;
+  if is_gradle_distribution_cached(gradle_home, &distribution) {
+    Ok(format!("{} cached", distribution))
+  } else {
+    Err(format!(
+      "{} is not cached, run a build with network access first",
+      distribution
+    ))
+  }
+}
+
+fn check_android_gradle_plugin(
+  template: &ProjectTemplate,
+  gradle_home: &Path,
+) -> Result<String, String> {
+  let version = template
+    .read_gradle("build.gradle")
+    .and_then(|content| parse_android_gradle_plugin_version(&content))
+    .ok_or("unable to determine the android gradle plugin version of the template".to_string())?;
+  if is_gradle_module_cached(gradle_home, "com.android.tools.build", "gradle", &version) {
+    Ok(format!("{} cached", version))
+  } else {
+    Err(format!(
+      "{} is not cached, run a build with network access first",
+      version
+    ))
+  }
+}
+
+/// Checks that everything an offline build downloads otherwise is available locally, as named
+/// results for the doctor report.
+pub fn check_offline_build(manifest_path: &Path) -> Vec<(&'static str, Result<String, String>)> {
+  let mut results = vec![(
+    "cargo dependencies",
+    check_cargo_dependencies(manifest_path),
+  )];
+
+  let gradle_home = match get_gradle_user_home(manifest_path, None) {
+    Ok(gradle_home) => gradle_home,
+    Err(e) => {
+      results.push(("gradle cache", Err(e.to_string())));
+      return results;
+    }
+  };
+  let Some(gradle_home) = gradle_home else {
+    results.push((
+      "gradle cache",
+      Err("neither GRADLE_USER_HOME nor HOME is set".to_string()),
+    ));
+    return results;
+  };
+  let template = match ProjectTemplate::for_manifest(manifest_path) {
+    Ok(template) => template,
+    Err(e) => {
+      results.push(("project template", Err(e)));
+      return results;
+    }
+  };
+  results.push(("gradle distribution", check_gradle(&template, &gradle_home)));
+  results.push((
+    "android gradle plugin",
+    check_android_gradle_plugin(&template, &gradle_home),
+  ));
+  results
+}
+
+#[cfg(test)]
+mod test {
+  use std::fs::{create_dir_all, write};
+  use std::path::Path;
+
+  use tempfile::tempdir;
+
+  use crate::offline::{
+    check_wrapper_distribution, get_configured_gradle_user_home, is_gradle_distribution_cached,
+    is_gradle_module_cached, parse_android_gradle_plugin_version, parse_gradle_distribution,
+    parse_gradle_distribution_url,
+  };
+
+  #[test]
+  fn template_versions() {
+    assert_eq!(
+      parse_gradle_distribution(
+        "distributionBase=GRADLE_USER_HOME\ndistributionUrl=https\\://services.gradle.org/distributions/gradle-7.3-bin.zip\n"
+      ),
+      Some("gradle-7.3-bin".to_string())
+    );
+    assert_eq!(
+      parse_android_gradle_plugin_version(
+        "        classpath 'com.android.tools.build:gradle:7.0.3'\n"
+      ),
+      Some("7.0.3".to_string())
+    );
+    assert_eq!(
+      parse_android_gradle_plugin_version(
+        "plugins {\n    id 'com.android.application' version '8.1.0' apply false\n}\n"
+      ),
+      Some("8.1.0".to_string())
+    );
+    assert_eq!(
+      parse_android_gradle_plugin_version(
+        "plugins {\n    id(\"com.android.application\") version \"8.2.0\" apply false\n}\n"
+      ),
+      Some("8.2.0".to_string())
+    );
+  }
+
+  #[test]
+  fn gradle_cache() {
+    let home = tempdir().unwrap();
+    assert!(!is_gradle_distribution_cached(
+      home.path(),
+      "gradle-7.3-bin"
+    ));
+    create_dir_all(
+      home
+        .path()
+        .join("wrapper/dists/gradle-7.3-bin/abc123/gradle-7.3"),
+    )
+    .unwrap();
+    assert!(is_gradle_distribution_cached(home.path(), "gradle-7.3-bin"));
+
+    assert!(!is_gradle_module_cached(
+      home.path(),
+      "com.android.tools.build",
+      "gradle",
+      "7.0.3"
+    ));
+    create_dir_all(
+      home
+        .path()
+        .join("caches/modules-2/files-2.1/com.android.tools.build/gradle/7.0.3"),
+    )
+    .unwrap();
+    assert!(is_gradle_module_cached(
+      home.path(),
+      "com.android.tools.build",
+      "gradle",
+      "7.0.3"
+    ));
+  }
+
+  #[test]
+  fn wrapper_distribution() {
+    let properties = "distributionBase=GRADLE_USER_HOME\ndistributionUrl=https\\://services.gradle.org/distributions/gradle-8.4-bin.zip\n";
+    let url = "https://services.gradle.org/distributions/gradle-8.4-bin.zip";
+    assert_eq!(
+      Some(url.to_string()),
+      parse_gradle_distribution_url(properties)
+    );
+
+    let home = tempdir().unwrap();
+    assert_eq!(
+      Ok(format!(
+        "Gradle distribution {} not cached, downloading into {}",
+        url,
+        home.path().display()
+      )),
+      check_wrapper_distribution(properties, home.path(), false)
+    );
+    let e = check_wrapper_distribution(properties, home.path(), true).unwrap_err();
+    assert!(e.contains("gradle-8.4-bin is not cached"), "{}", e);
+    assert!(e.contains(url), "{}", e);
+
+    create_dir_all(
+      home
+        .path()
+        .join("wrapper/dists/gradle-8.4-bin/x1y2/gradle-8.4"),
+    )
+    .unwrap();
+    assert!(check_wrapper_distribution(properties, home.path(), true)
+      .unwrap()
+      .contains("cached in"));
+    assert!(
+      check_wrapper_distribution("distributionBase=GRADLE_USER_HOME", home.path(), false).is_err()
+    );
+  }
+
+  #[test]
+  fn configured_gradle_user_home() {
+    let dir = tempdir().unwrap();
+    let manifest_path = dir.path().join("Cargo.toml");
+    write(
+      &manifest_path,
+      "[package]\nname = \"game\"\n\n[package.metadata.android]\ngradle_user_home = \"ci/gradle\"\n",
+    )
+    .unwrap();
+    assert_eq!(
+      Some(Path::new("/cache/gradle").to_path_buf()),
+      get_configured_gradle_user_home(&manifest_path, Some(Path::new("/cache/gradle"))).unwrap()
+    );
+    if std::env::var_os("GRADLE_USER_HOME").is_none() {
+      assert_eq!(
+        Some(dir.path().join("ci/gradle")),
+        get_configured_gradle_user_home(&manifest_path, None).unwrap()
+      );
+    }
+  }
+}
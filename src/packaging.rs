@@ -0,0 +1,260 @@
+use std::cmp::Ordering;
+use std::fs::File;
+use std::io::{Read, Seek};
+use std::path::Path;
+
+use zip::{CompressionMethod, ZipArchive};
+
+use crate::gradle_dsl::*;
+use crate::sdk::*;
+use crate::util::*;
+
+/// First build-tools version whose zipalign can align shared libraries to 16 KB pages with `-P`.
+const PAGE_SIZE_BUILD_TOOLS_VERSION: &str = "35";
+
+/// How the APK is packaged, `packaging` of the android metadata.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Packaging {
+  /// File extensions stored uncompressed, e.g. assets SDL_RWops streams with mmap.
+  pub no_compress: Vec<String>,
+  /// Whether shared libraries are stored uncompressed and aligned to 16 KB pages instead of 4 KB,
+  /// which devices with 16 KB pages need to load them from the APK.
+  pub page_align_shared_libs: bool,
+}
+
+impl Packaging {
+  /// The page size in KB shared libraries are aligned to.
+  pub fn page_size_kb(&self) -> u32 {
+    if self.page_align_shared_libs {
+      16
+    } else {
+      4
+    }
+  }
+
+  /// The arguments of zipalign aligning shared libraries to the page size.
+  pub fn zipalign_args(&self) -> Vec<String> {
+    if self.page_align_shared_libs {
+      vec!["-P".to_string(), self.page_size_kb().to_string()]
+    } else {
+      vec!["-p".to_string()]
+    }
+  }
+
+  /// Fails if zipalign of the build-tools can't align to the page size.
+  pub fn check_build_tools(&self, build_tools_version: &str) -> Result<(), String> {
+    if self.page_align_shared_libs
+      && compare_versions(build_tools_version, PAGE_SIZE_BUILD_TOOLS_VERSION) == Ordering::Less
+    {
+      return Err(format!(
+        "page_align_shared_libs needs zipalign of build-tools {} or newer for 16 KB pages, \
+        found build-tools {}. Install them with sdkmanager",
+        PAGE_SIZE_BUILD_TOOLS_VERSION, build_tools_version
+      ));
+    }
+    Ok(())
+  }
+
+  /// The blocks of the android extension in app/build.gradle applying the policy, `aaptOptions`
+  /// before AGP 7 and `androidResources` from then on.
+  pub fn build_gradle_blocks(&self, agp_version: Option<&str>, dsl: GradleDsl) -> Vec<String> {
+    let mut blocks = vec![];
+    if !self.no_compress.is_empty() {
+      let resources = match agp_version {
+        Some(version) if compare_versions(version, "7") != Ordering::Less => "androidResources",
+        _ => "aaptOptions",
+      };
+      let extensions: Vec<String> = self.no_compress.iter().map(|e| dsl.string(e)).collect();
+      let extensions: Vec<&str> = extensions.iter().map(String::as_str).collect();
+      blocks.push(format!(
+        "    {} {{\n        {}\n    }}",
+        resources,
+        dsl.call("noCompress", &extensions)
+      ));
+    }
+    if self.page_align_shared_libs {
+      //AGP stores libraries uncompressed and page aligned unless packaged the legacy way
+      blocks.push(format!(
+        "    packagingOptions {{\n        jniLibs {{\n            {}\n        }}\n    }}",
+        dsl.assign("useLegacyPackaging", "false")
+      ));
+    }
+    blocks
+  }
+}
+
+/// Parses `packaging` of the android metadata.
+pub fn parse_packaging(value: &toml::Value) -> Result<Packaging, String> {
+  let table = value.as_table().ok_or("packaging must be a table")?;
+  let no_compress = match table.get("no_compress") {
+    Some(value) => value
+      .as_array()
+      .and_then(|extensions| {
+        extensions
+          .iter()
+          .map(|e| e.as_str().map(str::to_string))
+          .collect::<Option<Vec<String>>>()
+      })
+      .ok_or("packaging.no_compress must be an array of file extensions")?,
+    None => vec![],
+  };
+  let page_align_shared_libs = match table.get("page_align_shared_libs") {
+    Some(value) => value
+      .as_bool()
+      .ok_or("packaging.page_align_shared_libs must be a boolean")?,
+    None => false,
+  };
+  Ok(Packaging {
+    no_compress,
+    page_align_shared_libs,
+  })
+}
+
+/// The packaging policy of `packaging` of the android metadata.
+pub fn get_packaging(manifest_path: &Path) -> Result<Packaging, String> {
+  match get_toml_entry(
+    manifest_path,
+    ["package", "metadata", "android", "packaging"],
+  )? {
+    Some(value) => parse_packaging(&value),
+    None => Ok(Packaging::default()),
+  }
+}
+
+fn check_packaging_entries<R: Read + Seek>(
+  reader: R,
+  packaging: &Packaging,
+) -> zip::result::ZipResult<Vec<String>> {
+  let mut archive = ZipArchive::new(reader)?;
+  let page_size = packaging.page_size_kb() as u64 * 1024;
+  let mut violations = vec![];
+  for i in 0..archive.len() {
+    let file = archive.by_index_raw(i)?;
+    if file.is_dir() {
+      continue;
+    }
+    let name = file.name().to_string();
+    let stored = file.compression() == CompressionMethod::Stored;
+    let lower_name = name.to_lowercase();
+    let no_compress = packaging
+      .no_compress
+      .iter()
+      .any(|extension| lower_name.ends_with(&extension.to_lowercase()));
+    let shared_lib =
+      packaging.page_align_shared_libs && name.starts_with("lib/") && name.ends_with(".so");
+    if (no_compress || shared_lib) && !stored {
+      violations.push(format!("{} is compressed", name));
+    } else if shared_lib && file.data_start() % page_size != 0 {
+      violations.push(format!(
+        "{} isn't aligned to {} KB pages",
+        name,
+        packaging.page_size_kb()
+      ));
+    }
+  }
+  Ok(violations)
+}
+
+/// Checks that the APK stores the `no_compress` extensions uncompressed and, with
+/// `page_align_shared_libs`, its shared libraries uncompressed and page aligned.
+pub fn check_packaged_apk(apk_path: &Path, packaging: &Packaging) -> Result<(), String> {
+  if packaging == &Packaging::default() {
+    return Ok(());
+  }
+  let file = File::open(apk_path).map_err(|e| format!("can't open apk {:?}: {}", apk_path, e))?;
+  let violations = check_packaging_entries(file, packaging)
+    .map_err(|e| format!("unable to read apk {:?}: {}", apk_path, e))?;
+  if violations.is_empty() {
+    return Ok(());
+  }
+  Err(format!(
+    "{} doesn't follow package.metadata.android.packaging:\n  {}",
+    apk_path.display(),
+    violations.join("\n  ")
+  ))
+}
+
+#[cfg(test)]
+mod test {
+  use std::io::{Cursor, Write};
+
+  use toml::Table;
+  use zip::write::FileOptions;
+  use zip::{CompressionMethod, ZipWriter};
+
+  use crate::gradle_dsl::GradleDsl;
+  use crate::packaging::{check_packaging_entries, parse_packaging, Packaging};
+
+  fn create_apk(entries: &[(&str, CompressionMethod, u16)]) -> Vec<u8> {
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    for (name, compression, alignment) in entries {
+      let options = FileOptions::default().compression_method(*compression);
+      writer
+        .start_file_aligned(*name, options, *alignment)
+        .unwrap();
+      writer.write_all(&[0; 100]).unwrap();
+    }
+    writer.finish().unwrap().into_inner()
+  }
+
+  #[test]
+  fn packaging_policy() {
+    let table = "[packaging]\nno_compress = [\".ogg\", \".png\"]\npage_align_shared_libs = true"
+      .parse::<Table>()
+      .unwrap();
+    let packaging = parse_packaging(&table["packaging"]).unwrap();
+    assert_eq!(vec![".ogg", ".png"], packaging.no_compress);
+    assert_eq!(vec!["-P", "16"], packaging.zipalign_args());
+    assert_eq!(vec!["-p"], Packaging::default().zipalign_args());
+    assert!(packaging.check_build_tools("34.0.0").is_err());
+    assert_eq!(Ok(()), packaging.check_build_tools("35.0.1"));
+
+    assert_eq!(
+      vec![
+        "    aaptOptions {\n        noCompress '.ogg', '.png'\n    }",
+        "    packagingOptions {\n        jniLibs {\n            useLegacyPackaging false\n        }\n    }",
+      ],
+      packaging.build_gradle_blocks(Some("4.2.2"), GradleDsl::Groovy)
+    );
+    assert_eq!(
+      "    androidResources {\n        noCompress(\".ogg\", \".png\")\n    }",
+      packaging.build_gradle_blocks(Some("8.5.1"), GradleDsl::Kotlin)[0]
+    );
+
+    let table = "[packaging]\nno_compress = \".ogg\""
+      .parse::<Table>()
+      .unwrap();
+    assert!(parse_packaging(&table["packaging"]).is_err());
+  }
+
+  #[test]
+  fn packaged_apk_check() {
+    let packaging = Packaging {
+      no_compress: vec![".ogg".to_string()],
+      page_align_shared_libs: true,
+    };
+    let apk = create_apk(&[
+      ("assets/music.ogg", CompressionMethod::Stored, 4),
+      ("assets/level.json", CompressionMethod::Deflated, 0),
+      ("lib/arm64-v8a/libmain.so", CompressionMethod::Stored, 16384),
+    ]);
+    assert_eq!(
+      Vec::<String>::new(),
+      check_packaging_entries(Cursor::new(apk), &packaging).unwrap()
+    );
+
+    let apk = create_apk(&[
+      ("assets/SOUND.OGG", CompressionMethod::Deflated, 0),
+      ("lib/arm64-v8a/libmain.so", CompressionMethod::Stored, 4096),
+      ("lib/x86_64/libmain.so", CompressionMethod::Deflated, 0),
+    ]);
+    assert_eq!(
+      vec![
+        "assets/SOUND.OGG is compressed",
+        "lib/arm64-v8a/libmain.so isn't aligned to 16 KB pages",
+        "lib/x86_64/libmain.so is compressed",
+      ],
+      check_packaging_entries(Cursor::new(apk), &packaging).unwrap()
+    );
+  }
+}
@@ -0,0 +1,247 @@
+use std::collections::BTreeMap;
+use std::fs::{read_to_string, write};
+use std::path::Path;
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use toml::value::Value;
+
+use crate::android_project::*;
+use crate::flavors::*;
+use crate::project_template::*;
+use crate::sdk::*;
+use crate::util::*;
+use crate::BuildProfile;
+
+lazy_static! {
+  //`$${name}` is the escaped literal `${name}`
+  static ref PLACEHOLDER_REGEX: Regex =
+    Regex::new(r"(\$?)\$\{([^}\n]*)\}").expect("invalid placeholder regex");
+  static ref VERSION_CODE_REGEX: Regex =
+    Regex::new(r"versionCode\s*=?\s*(\d+)").expect("invalid version code regex");
+  static ref VERSION_NAME_REGEX: Regex =
+    Regex::new(r#"versionName\s*=?\s*["']([^"']*)["']"#).expect("invalid version name regex");
+}
+
+/// Names of the placeholders computed by the build, which user-defined ones can't replace.
+pub const BUILT_IN_PLACEHOLDERS: &[&str] = &[
+  "applicationId",
+  "versionName",
+  "versionCode",
+  "minSdk",
+  "targetSdk",
+  "profile",
+];
+
+/// Values for `${name}` placeholders in user-provided files, the ones of the build and the ones of
+/// `[package.metadata.android.placeholders]`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Placeholders(BTreeMap<String, String>);
+
+impl Placeholders {
+  pub fn insert(&mut self, name: &str, value: impl ToString) {
+    self.0.insert(name.to_string(), value.to_string());
+  }
+
+  /// Replaces all placeholders of the content. `$${name}` becomes the literal `${name}`, an
+  /// unknown placeholder is an error naming `file` and the line.
+  pub fn substitute(&self, content: &str, file: &str) -> Result<String, String> {
+    let mut result = String::with_capacity(content.len());
+    let mut last = 0;
+    for captures in PLACEHOLDER_REGEX.captures_iter(content) {
+      let whole = captures.get(0).unwrap();
+      result.push_str(&content[last..whole.start()]);
+      last = whole.end();
+      let name = &captures[2];
+      if !captures[1].is_empty() {
+        result.push_str(&format!("${{{}}}", name));
+        continue;
+      }
+      match self.0.get(name.trim()) {
+        Some(value) => result.push_str(value),
+        None => {
+          let line = content[..whole.start()].matches('\n').count() + 1;
+          return Err(format!(
+            "{}:{}: unknown placeholder ${{{}}}, known are {}. Define it in \
+            [package.metadata.android.placeholders] or write $${{{}}} for the literal text",
+            file,
+            line,
+            name,
+            self.0.keys().cloned().collect::<Vec<String>>().join(", "),
+            name
+          ));
+        }
+      }
+    }
+    result.push_str(&content[last..]);
+    Ok(result)
+  }
+}
+
+/// Parses the `placeholders` table of the android metadata. Strings, integers and booleans are
+/// substituted as written.
+pub fn parse_user_placeholders(value: &Value) -> Result<BTreeMap<String, String>, String> {
+  let table = value
+    .as_table()
+    .ok_or("placeholders must be a table of names to values")?;
+  let mut placeholders = BTreeMap::new();
+  for (name, value) in table {
+    if BUILT_IN_PLACEHOLDERS.contains(&name.as_str()) {
+      return Err(format!(
+        "placeholders.{} can't be defined, it is computed by the build",
+        name
+      ));
+    }
+    let value = match value {
+      Value::String(s) => s.clone(),
+      Value::Integer(i) => i.to_string(),
+      Value::Boolean(b) => b.to_string(),
+      _ => {
+        return Err(format!(
+          "placeholders.{} must be a string, integer or boolean",
+          name
+        ))
+      }
+    };
+    placeholders.insert(name.clone(), value);
+  }
+  Ok(placeholders)
+}
+
+/// Extracts the version code from the content of a build.gradle file.
+pub fn parse_version_code(build_gradle: &str) -> Option<u32> {
+  VERSION_CODE_REGEX
+    .captures(build_gradle)
+    .and_then(|c| c[1].parse().ok())
+}
+
+/// Extracts the version name from the content of a build.gradle file.
+pub fn parse_version_name(build_gradle: &str) -> Option<String> {
+  VERSION_NAME_REGEX
+    .captures(build_gradle)
+    .map(|c| c[1].to_string())
+}
+
+/// Collects the placeholders for the app of the profile and flavor. The version and target sdk
+/// are the ones of the project template, as gradle builds the app with those.
+pub fn get_placeholders(
+  manifest_path: &Path,
+  profile: BuildProfile,
+  flavor: Option<&Flavor>,
+) -> Result<Placeholders, String> {
+  let build_gradle = ProjectTemplate::for_manifest(manifest_path)?
+    .read_gradle("app/build.gradle")
+    .unwrap_or_default();
+  let mut placeholders = Placeholders::default();
+  placeholders.insert(
+    "applicationId",
+    get_flavor_app_id(manifest_path, profile, flavor)?,
+  );
+  if let Some(c) = VERSION_NAME_REGEX.captures(&build_gradle) {
+    placeholders.insert("versionName", &c[1]);
+  }
+  if let Some(c) = VERSION_CODE_REGEX.captures(&build_gradle) {
+    placeholders.insert("versionCode", &c[1]);
+  }
+  if let Some(min_sdk_version) = get_min_sdk_version(manifest_path)? {
+    placeholders.insert("minSdk", min_sdk_version);
+  }
+  if let Some(target_sdk_version) = parse_target_sdk_version(&build_gradle) {
+    placeholders.insert("targetSdk", target_sdk_version);
+  }
+  placeholders.insert("profile", profile);
+
+  if let Some(value) = get_toml_entry(
+    manifest_path,
+    ["package", "metadata", "android", "placeholders"],
+  )? {
+    for (name, value) in parse_user_placeholders(&value)? {
+      placeholders.insert(&name, value);
+    }
+  }
+  Ok(placeholders)
+}
+
+/// Writes the file of the custom project template into the project with its placeholders
+/// substituted.
+pub fn substitute_template_file(
+  template_dir: &Path,
+  project_dir: &Path,
+  file_name: &str,
+  placeholders: &Placeholders,
+) -> Result<(), String> {
+  let source = template_dir.join(file_name);
+  let content =
+    read_to_string(&source).map_err(|e| format!("unable to read {}: {}", source.display(), e))?;
+  let content = placeholders.substitute(&content, &source.display().to_string())?;
+  let path = project_dir.join(file_name);
+  write(&path, content).map_err(|e| format!("unable to write {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod test {
+  use toml::Table;
+
+  use crate::placeholders::{parse_user_placeholders, Placeholders};
+
+  fn placeholders() -> Placeholders {
+    let mut placeholders = Placeholders::default();
+    placeholders.insert("applicationId", "org.example.game");
+    placeholders.insert("versionCode", 3);
+    placeholders.insert("profile", "debug");
+    placeholders
+  }
+
+  #[test]
+  fn substitution() {
+    let manifest = "<manifest package=\"${applicationId}\">\n  <!-- ${ profile } build ${versionCode} -->\n  <meta-data android:value=\"$${gradlePlaceholder}\"/>\n</manifest>";
+    assert_eq!(
+      Ok("<manifest package=\"org.example.game\">\n  <!-- debug build 3 -->\n  <meta-data android:value=\"${gradlePlaceholder}\"/>\n</manifest>".to_string()),
+      placeholders().substitute(manifest, "AndroidManifest.xml")
+    );
+    assert_eq!(
+      Ok("cost: $5, ${}".to_string()),
+      placeholders().substitute("cost: $5, $${}", "strings.txt")
+    );
+  }
+
+  #[test]
+  fn unknown_placeholder() {
+    let error = placeholders()
+      .substitute(
+        "<manifest>\n\n  ${apiKey}\n</manifest>",
+        "AndroidManifest.xml",
+      )
+      .unwrap_err();
+    assert!(
+      error.starts_with(
+        "AndroidManifest.xml:3: unknown placeholder ${apiKey}, known are applicationId, profile, \
+        versionCode."
+      ),
+      "{}",
+      error
+    );
+  }
+
+  #[test]
+  fn user_placeholders() {
+    let table = "[placeholders]\napiKey = \"abc\"\nlevel = 3\nbeta = true"
+      .parse::<Table>()
+      .unwrap();
+    let placeholders = parse_user_placeholders(&table["placeholders"]).unwrap();
+    assert_eq!(
+      vec![("apiKey", "abc"), ("beta", "true"), ("level", "3")],
+      placeholders
+        .iter()
+        .map(|(k, v)| (k.as_str(), v.as_str()))
+        .collect::<Vec<(&str, &str)>>()
+    );
+
+    let table = "[placeholders]\napplicationId = \"x\""
+      .parse::<Table>()
+      .unwrap();
+    assert!(parse_user_placeholders(&table["placeholders"]).is_err());
+    let table = "[placeholders]\nlist = [1]".parse::<Table>().unwrap();
+    assert!(parse_user_placeholders(&table["placeholders"]).is_err());
+  }
+}
@@ -0,0 +1,140 @@
+use std::fs::read;
+use std::path::{Path, PathBuf};
+
+use crate::native_libs::*;
+use crate::util::*;
+
+/// Placeholder of `prebuilt_sdl_libs` replaced with the abi of each target.
+const ABI_PLACEHOLDER: &str = "{abi}";
+
+/// Reads `prebuilt_sdl_libs` of the android metadata, the path of a prebuilt `libSDL2.so` with
+/// `{abi}` in place of the abi, relative to the package. With it SDL isn't built with ndk-build.
+pub fn get_prebuilt_sdl_libs(manifest_path: &Path) -> Result<Option<String>, String> {
+  let pattern = get_toml_string(
+    manifest_path,
+    ["package", "metadata", "android", "prebuilt_sdl_libs"],
+  )?;
+  match pattern {
+    Some(pattern) if !pattern.contains(ABI_PLACEHOLDER) => Err(format!(
+      "package.metadata.android.prebuilt_sdl_libs must contain {} to tell the libraries of the \
+      abis apart, found: \"{}\"",
+      ABI_PLACEHOLDER, pattern
+    )),
+    pattern => Ok(pattern),
+  }
+}
+
+/// The prebuilt library of the abi.
+pub fn resolve_prebuilt_sdl_lib(manifest_dir: &Path, pattern: &str, abi: &str) -> PathBuf {
+  manifest_dir.join(pattern.replace(ABI_PLACEHOLDER, abi))
+}
+
+/// Checks that the prebuilt library exists and is an ELF file built for the abi.
+pub fn check_prebuilt_sdl_lib(library: &Path, abi: &str) -> Result<(), String> {
+  let data = read(library).map_err(|e| {
+    format!(
+      "prebuilt SDL library for {} {} can't be read: {}",
+      abi,
+      library.display(),
+      e
+    )
+  })?;
+  let Some(machine) = parse_elf_machine(&data) else {
+    return Err(format!(
+      "prebuilt SDL library for {} {} isn't an ELF file",
+      abi,
+      library.display()
+    ));
+  };
+  match get_abi_elf_machine(abi) {
+    Some(expected) if expected != machine => {
+      let found = ["armeabi-v7a", "arm64-v8a", "x86", "x86_64", "riscv64"]
+        .into_iter()
+        .find(|abi| get_abi_elf_machine(abi) == Some(machine))
+        .map(str::to_string)
+        .unwrap_or_else(|| format!("ELF machine {}", machine));
+      Err(format!(
+        "prebuilt SDL library for {} {} is built for {}",
+        abi,
+        library.display(),
+        found
+      ))
+    }
+    _ => Ok(()),
+  }
+}
+
+/// Resolves the prebuilt library of each abi, checking all of them before any is used.
+pub fn resolve_prebuilt_sdl_libs(
+  manifest_dir: &Path,
+  pattern: &str,
+  abis: &[String],
+) -> Result<Vec<PathBuf>, String> {
+  let libraries: Vec<PathBuf> = abis
+    .iter()
+    .map(|abi| resolve_prebuilt_sdl_lib(manifest_dir, pattern, abi))
+    .collect();
+  let errors: Vec<String> = libraries
+    .iter()
+    .zip(abis)
+    .filter_map(|(library, abi)| check_prebuilt_sdl_lib(library, abi).err())
+    .collect();
+  if !errors.is_empty() {
+    return Err(errors.join("\n"));
+  }
+  Ok(libraries)
+}
+
+#[cfg(test)]
+mod test {
+  use std::fs::{create_dir_all, write};
+  use std::path::Path;
+
+  use tempfile::tempdir;
+
+  use crate::prebuilt_sdl::{check_prebuilt_sdl_lib, resolve_prebuilt_sdl_libs};
+
+  /// An ELF64 header of a library for the machine.
+  fn write_elf(path: &Path, machine: u16) {
+    let mut data = vec![0u8; 64];
+    data[0..4].copy_from_slice(b"\x7fELF");
+    data[4] = 2;
+    data[5] = 1;
+    data[18..20].copy_from_slice(&machine.to_le_bytes());
+    create_dir_all(path.parent().unwrap()).unwrap();
+    write(path, data).unwrap();
+  }
+
+  #[test]
+  fn prebuilt_sdl_libs() {
+    let dir = tempdir().unwrap();
+    let pattern = "prebuilt/sdl/{abi}/libSDL2.so";
+    write_elf(&dir.path().join("prebuilt/sdl/arm64-v8a/libSDL2.so"), 183);
+    write_elf(&dir.path().join("prebuilt/sdl/x86_64/libSDL2.so"), 62);
+
+    let abis = ["arm64-v8a".to_string(), "x86_64".to_string()];
+    assert_eq!(
+      vec![
+        dir.path().join("prebuilt/sdl/arm64-v8a/libSDL2.so"),
+        dir.path().join("prebuilt/sdl/x86_64/libSDL2.so")
+      ],
+      resolve_prebuilt_sdl_libs(dir.path(), pattern, &abis).unwrap()
+    );
+
+    //the x86_64 library in the dir of x86, armeabi-v7a missing
+    write_elf(&dir.path().join("prebuilt/sdl/x86/libSDL2.so"), 62);
+    let abis = ["x86".to_string(), "armeabi-v7a".to_string()];
+    let errors = resolve_prebuilt_sdl_libs(dir.path(), pattern, &abis).unwrap_err();
+    let errors: Vec<&str> = errors.lines().collect();
+    assert_eq!(2, errors.len());
+    assert!(errors[0].starts_with("prebuilt SDL library for x86 "));
+    assert!(errors[0].ends_with(" is built for x86_64"));
+    assert!(errors[1].starts_with("prebuilt SDL library for armeabi-v7a "));
+
+    let text = dir.path().join("libSDL2.so");
+    write(&text, "not a library").unwrap();
+    assert!(check_prebuilt_sdl_lib(&text, "x86")
+      .unwrap_err()
+      .ends_with("isn't an ELF file"));
+  }
+}
@@ -0,0 +1,631 @@
+use std::env;
+use std::fmt::{Display, Formatter};
+use std::fs::read_to_string;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use toml::value::Value;
+use toml::Table;
+
+use crate::build_bin_as_lib::*;
+use crate::metadata_schema::*;
+use crate::ndk::*;
+use crate::offline::*;
+use crate::presets::*;
+use crate::project_template::*;
+use crate::sdk::*;
+use crate::sdl_java::*;
+use crate::targets::*;
+use crate::template_compat::*;
+use crate::util::*;
+use crate::warnings::*;
+
+lazy_static! {
+  static ref SDL_VERSION_DEFINE_REGEX: Regex =
+    Regex::new(r"#define\s+SDL_(MAJOR_VERSION|MINOR_VERSION|PATCHLEVEL|MICRO_VERSION)\s+(\d+)")
+      .expect("invalid sdl version regex");
+}
+
+/// Minimum SDL source version expected by the sys crate versions, as (crate, crate version
+/// prefix, SDL version). The SDL source may be newer within the same major version, as SDL keeps
+/// backwards compatibility, but not older.
+pub const SDL_COMPATIBILITY_TABLE: &[(&str, &str, SdlVersion)] = &[
+  ("sdl2-sys", "0.34.", SdlVersion(2, 0, 12)),
+  ("sdl2-sys", "0.35.", SdlVersion(2, 0, 22)),
+  ("sdl2-sys", "0.36.", SdlVersion(2, 26, 0)),
+  ("sdl2-sys", "0.37.", SdlVersion(2, 30, 0)),
+  ("sdl3-sys", "0.", SdlVersion(3, 2, 0)),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SdlVersion(pub u32, pub u32, pub u32);
+
+impl Display for SdlVersion {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}.{}.{}", self.0, self.1, self.2)
+  }
+}
+
+impl FromStr for SdlVersion {
+  type Err = String;
+
+  /// Parses a version like `2.30.2`.
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let mut parts = s.split('.').map(|part| part.parse::<u32>().ok());
+    match (parts.next(), parts.next(), parts.next(), parts.next()) {
+      (Some(Some(major)), Some(Some(minor)), Some(Some(patch)), None) => {
+        Ok(SdlVersion(major, minor, patch))
+      }
+      _ => Err(format!(
+        "invalid SDL version \"{}\", expected one like 2.30.2",
+        s
+      )),
+    }
+  }
+}
+
+/// Parses the version defines of SDL_version.h. SDL2 uses `SDL_PATCHLEVEL` while SDL3 uses
+/// `SDL_MICRO_VERSION` for the last component.
+pub fn parse_sdl_version(header: &str) -> Option<SdlVersion> {
+  let (mut major, mut minor, mut patch) = (None, None, None);
+  for captures in SDL_VERSION_DEFINE_REGEX.captures_iter(header) {
+    let value = captures[2].parse().ok();
+    match &captures[1] {
+      "MAJOR_VERSION" => major = value,
+      "MINOR_VERSION" => minor = value,
+      _ => patch = value,
+    }
+  }
+
+  Some(SdlVersion(major?, minor?, patch?))
+}
+
+/// Reads the version of the SDL source checkout, supporting both the SDL2 and the SDL3 layout.
+pub fn read_sdl_source_version(sdl_dir: &Path) -> Option<SdlVersion> {
+  ["include/SDL_version.h", "include/SDL3/SDL_version.h"]
+    .iter()
+    .filter_map(|header| read_to_string(sdl_dir.join(header)).ok())
+    .find_map(|content| parse_sdl_version(&content))
+}
+
+/// Major version of an SDL directory, recognized by the location of its headers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SdlLayout {
+  /// Headers directly in `include/` of the source, in `include/SDL2/` when installed.
+  Sdl2,
+  /// Headers in `include/SDL3/`, both in the source and when installed.
+  Sdl3,
+}
+
+impl SdlLayout {
+  fn detect(sdl_dir: &Path) -> Option<Self> {
+    if sdl_dir.join("include/SDL3/SDL.h").is_file() {
+      Some(SdlLayout::Sdl3)
+    } else if sdl_dir.join("include/SDL.h").is_file()
+      || sdl_dir.join("include/SDL2/SDL.h").is_file()
+    {
+      Some(SdlLayout::Sdl2)
+    } else {
+      None
+    }
+  }
+
+  fn source_url(&self) -> &'static str {
+    match self {
+      SdlLayout::Sdl2 => "https://github.com/libsdl-org/SDL/tree/SDL2",
+      SdlLayout::Sdl3 => "https://github.com/libsdl-org/SDL",
+    }
+  }
+
+  fn header(&self) -> &'static str {
+    match self {
+      SdlLayout::Sdl2 => "include/SDL.h",
+      SdlLayout::Sdl3 => "include/SDL3/SDL.h",
+    }
+  }
+}
+
+impl Display for SdlLayout {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match self {
+      SdlLayout::Sdl2 => write!(f, "SDL2"),
+      SdlLayout::Sdl3 => write!(f, "SDL3"),
+    }
+  }
+}
+
+/// Entries of an SDL source checkout which building for android needs.
+const SDL_SOURCE_ENTRIES: &[&str] = &["Android.mk", "android-project", "src"];
+
+/// Whether the directory looks like an installed SDL, headers and libraries without the source.
+fn is_installed_sdl(sdl_dir: &Path) -> bool {
+  sdl_dir.join("include/SDL2/SDL.h").is_file()
+    || std::fs::read_dir(sdl_dir.join("lib"))
+      .map(|entries| {
+        entries.filter_map(|e| e.ok()).any(|e| {
+          let name = e.file_name().to_string_lossy().to_string();
+          name.starts_with("libSDL2") || name.starts_with("libSDL3") || name == "cmake"
+        })
+      })
+      .unwrap_or(false)
+}
+
+/// Checks that the SDL dir is a source checkout containing everything needed to build SDL for
+/// android, without its android-project if a custom template replaces it. Returns the detected
+/// layout.
+pub fn check_sdl_dir(sdl_dir: &Path, needs_template: bool) -> Result<SdlLayout, String> {
+  if !sdl_dir.is_dir() {
+    return Err(format!(
+      "SDL is set to {:?}, which is not a directory",
+      sdl_dir
+    ));
+  }
+
+  let layout = SdlLayout::detect(sdl_dir);
+  let missing: Vec<&str> = SDL_SOURCE_ENTRIES
+    .iter()
+    .copied()
+    .filter(|entry| needs_template || *entry != "android-project")
+    .filter(|entry| !sdl_dir.join(entry).exists())
+    .collect();
+  if missing.is_empty() {
+    return layout.ok_or(format!(
+      "{:?} contains no SDL headers, expected include/SDL.h for SDL2 or include/SDL3/SDL.h for SDL3",
+      sdl_dir
+    ));
+  }
+
+  let expected = format!(
+    "SDL needs to point to a source checkout containing {}",
+    SDL_SOURCE_ENTRIES.join(", ")
+  );
+  match layout {
+    Some(layout) if missing.contains(&"android-project") && is_installed_sdl(sdl_dir) => {
+      Err(format!(
+      "{:?} looks like an installed {} with headers and libraries only, but building for android \
+      needs the source. {}, like a clone of {}",
+      sdl_dir,
+      layout,
+      expected,
+      layout.source_url()
+    ))
+    }
+    Some(layout) => Err(format!(
+      "{:?} is missing {} of the {} source layout. {} and {}, like a clone of {}",
+      sdl_dir,
+      missing.join(", "),
+      layout,
+      expected,
+      layout.header(),
+      layout.source_url()
+    )),
+    None => Err(format!(
+      "{:?} doesn't look like SDL, it is missing {}. {} and include/SDL.h for SDL2 or \
+      include/SDL3/SDL.h for SDL3",
+      sdl_dir,
+      missing.join(", "),
+      expected
+    )),
+  }
+}
+
+/// Finds the Cargo.lock of the package, which lives next to the manifest or in a parent workspace
+/// directory.
+pub fn find_cargo_lock(manifest_dir: &Path) -> Option<PathBuf> {
+  manifest_dir
+    .ancestors()
+    .map(|dir| dir.join("Cargo.lock"))
+    .find(|lock| lock.is_file())
+}
+
+/// Returns the name and the locked version of the SDL sys crate used by the package.
+pub fn parse_locked_sdl_sys_crate(lock_content: &str) -> Option<(String, String)> {
+  let lock = lock_content.parse::<Table>().ok()?;
+  let packages = match lock.get("package") {
+    Some(Value::Array(packages)) => packages,
+    _ => return None,
+  };
+
+  packages.iter().find_map(|package| {
+    let name = package.get("name")?.as_str()?;
+    if name != "sdl2-sys" && name != "sdl3-sys" {
+      return None;
+    }
+    let version = package.get("version")?.as_str()?;
+    Some((name.to_string(), version.to_string()))
+  })
+}
+
+/// Looks up the SDL version the given sys crate version expects.
+pub fn get_expected_sdl_version(crate_name: &str, crate_version: &str) -> Option<SdlVersion> {
+  SDL_COMPATIBILITY_TABLE
+    .iter()
+    .find(|(name, prefix, _)| *name == crate_name && crate_version.starts_with(prefix))
+    .map(|(_, _, sdl_version)| *sdl_version)
+}
+
+pub fn is_sdl_version_compatible(source: SdlVersion, expected: SdlVersion) -> bool {
+  source.0 == expected.0 && source >= expected
+}
+
+/// Compares the SDL source checkout against the version the locked sys crate expects. Returns a
+/// description of the problem if they don't fit together.
+pub fn check_sdl_version(manifest_dir: &Path, sdl_dir: &Path) -> Result<(), String> {
+  let source_version = match read_sdl_source_version(sdl_dir) {
+    Some(v) => v,
+    None => {
+      return Err(format!(
+        "Unable to determine the SDL version of {:?}",
+        sdl_dir
+      ))
+    }
+  };
+
+  let (crate_name, crate_version) = match find_cargo_lock(manifest_dir)
+    .and_then(|lock| read_to_string(lock).ok())
+    .and_then(|content| parse_locked_sdl_sys_crate(&content))
+  {
+    Some(locked) => locked,
+    //nothing to compare against
+    None => return Ok(()),
+  };
+
+  let expected = match get_expected_sdl_version(&crate_name, &crate_version) {
+    Some(v) => v,
+    None => return Ok(()),
+  };
+
+  if is_sdl_version_compatible(source_version, expected) {
+    Ok(())
+  } else {
+    Err(format!(
+      "SDL source at {:?} is version {}, but {} {} expects SDL {} or a newer {}.x",
+      sdl_dir, source_version, crate_name, crate_version, expected, expected.0
+    ))
+  }
+}
+
+/// Env vars pointing to the tools needed to build with the given activity kind.
+fn get_required_env_vars(activity_kind: ActivityKind) -> &'static [&'static str] {
+  match activity_kind {
+    ActivityKind::Sdl => &["ANDROID_HOME", "ANDROID_NDK_HOME", "SDL"],
+    ActivityKind::Native => &["ANDROID_HOME", "ANDROID_NDK_HOME"],
+  }
+}
+
+/// Validates the build environment before anything is built: required env vars and the SDL
+/// checkout matching the sdl sys crate. With `strict_sdl_check` a version mismatch is an error
+/// instead of a warning.
+pub fn run_preflight_checks(manifest_path: &Path, strict_sdl_check: bool) -> Result<(), String> {
+  for warning in validate_android_metadata(manifest_path)? {
+    warn(&METADATA, warning);
+  }
+  let activity_kind = get_activity_kind(manifest_path)?;
+  for k in get_required_env_vars(activity_kind) {
+    if env::var_os(k).is_none() {
+      return Err(format!(
+        "Need env var: {}, set it or run cargo sdl-apk setup",
+        k
+      ));
+    }
+  }
+  check_ndk_toolchain(&get_env_path("ANDROID_NDK_HOME"))?;
+
+  if let Some(preset) = get_preset(manifest_path)? {
+    check_preset_min_sdk_version(preset, get_min_sdk_version(manifest_path)?)?;
+  }
+
+  if activity_kind == ActivityKind::Native {
+    if strict_sdl_check {
+      return Err("--strict-sdl-check can't be used with activity_kind = \"native\"".to_string());
+    }
+    return Ok(());
+  }
+
+  get_sdl_java(manifest_path)?;
+  let manifest_dir = manifest_path.parent().unwrap();
+  let sdl_dir = get_env_path("SDL");
+  let custom_template = get_custom_template_dir(manifest_path)?;
+  check_sdl_dir(&sdl_dir, custom_template.is_none())?;
+  if let Some(template_dir) = &custom_template {
+    check_template_dir(template_dir)?;
+  }
+  if let Err(e) = check_sdl_version(manifest_dir, &sdl_dir) {
+    if strict_sdl_check {
+      return Err(e);
+    }
+    warn_with_fix(
+      &SDL_VERSION,
+      format!("{}.", e),
+      "This usually results in crashes at runtime, use --strict-sdl-check to make this an error.",
+    );
+  }
+  Ok(())
+}
+
+/// Looks up an executable on the PATH.
+fn find_on_path(name: &str) -> Option<PathBuf> {
+  let path = env::var_os("PATH")?;
+  env::split_paths(&path)
+    .flat_map(|dir| {
+      [
+        dir.join(name),
+        dir.join(format!("{}.exe", name)),
+        dir.join(format!("{}.bat", name)),
+      ]
+    })
+    .find(|p| p.is_file())
+}
+
+fn report_check(name: &str, result: Result<String, String>) -> bool {
+  match result {
+    Ok(info) => {
+      println!("[ok]    {}: {}", name, info);
+      true
+    }
+    Err(problem) => {
+      println!("[error] {}: {}", name, problem);
+      false
+    }
+  }
+}
+
+fn check_env_dir(key: &str) -> Result<String, String> {
+  match env::var(key) {
+    Ok(value) if Path::new(&value).is_dir() => Ok(value),
+    Ok(value) => Err(format!(
+      "{} is set to {}, which is not a directory",
+      key, value
+    )),
+    Err(_) => Err(format!("{} is not set", key)),
+  }
+}
+
+/// Runs all checks of the build environment and prints a report instead of aborting on the first
+/// problem. With `offline` it is also checked that nothing needs to be downloaded. Returns whether
+/// all checks passed.
+pub fn run_doctor(manifest_path: &Path, offline: bool) -> bool {
+  let manifest_dir = manifest_path.parent().unwrap();
+  let mut ok = true;
+  let activity_kind = get_activity_kind(manifest_path).unwrap_or_else(|e| {
+    ok &= report_check("activity kind", Err(e));
+    ActivityKind::Sdl
+  });
+
+  ok &= report_check(
+    "android metadata",
+    validate_android_metadata(manifest_path).map(|warnings| {
+      for warning in &warnings {
+        println!("        {}", warning);
+      }
+      match warnings.len() {
+        0 => "valid".to_string(),
+        n => format!("valid, {} unknown keys (see above)", n),
+      }
+    }),
+  );
+  ok &= report_check(
+    "64-bit targets",
+    get_android_targets(manifest_path)
+      .and_then(|targets| check_64bit_targets(&targets).map(|_| targets.join(", "))),
+  );
+  for key in get_required_env_vars(activity_kind) {
+    ok &= report_check(key, check_env_dir(key));
+  }
+  if let Some(ndk_home) = env::var_os("ANDROID_NDK_HOME") {
+    ok &= report_check(
+      "NDK toolchain",
+      check_ndk_toolchain(Path::new(&ndk_home)).map(|tag| tag.to_string()),
+    );
+  }
+
+  //the checks below read the project template from the SDL dir, so they need a valid one
+  let sdl_dir = match (activity_kind, env::var("SDL")) {
+    (ActivityKind::Sdl, Ok(sdl_dir)) => {
+      let sdl_dir = PathBuf::from(sdl_dir);
+      let custom_template = get_custom_template_dir(manifest_path).map_err(String::from);
+      let layout = check_sdl_dir(&sdl_dir, !matches!(custom_template, Ok(Some(_))));
+      let mut valid = layout.is_ok();
+      ok &= report_check(
+        "SDL directory",
+        layout.map(|layout| format!("{} source checkout", layout)),
+      );
+      match custom_template {
+        Ok(None) => {}
+        Ok(Some(template_dir)) => {
+          let template = check_template_dir(&template_dir);
+          valid &= template.is_ok();
+          ok &= report_check(
+            "project template dir",
+            template.map(|_| template_dir.display().to_string()),
+          );
+        }
+        Err(e) => {
+          valid = false;
+          ok &= report_check("project template dir", Err(e));
+        }
+      }
+      Some(sdl_dir).filter(|_| valid)
+    }
+    _ => None,
+  };
+  if let Some(sdl_dir) = &sdl_dir {
+    ok &= report_check(
+      "SDL version",
+      check_sdl_version(manifest_dir, sdl_dir).and_then(|_| {
+        read_sdl_source_version(sdl_dir)
+          .map(|v| v.to_string())
+          .ok_or("unknown".to_string())
+      }),
+    );
+  }
+
+  if activity_kind == ActivityKind::Native || sdl_dir.is_some() {
+    if let Ok(android_home) = env::var("ANDROID_HOME") {
+      ok &= report_check(
+        "sdk packages",
+        match ProjectTemplate::for_manifest(manifest_path)
+          .map(|template| get_template_compile_sdk_version(&template))
+        {
+          Ok(Some(compile_sdk)) => {
+            let missing = find_missing_sdk_packages(Path::new(&android_home), compile_sdk);
+            if missing.is_empty() {
+              Ok(format!("compile sdk {} installed", compile_sdk))
+            } else {
+              Err(format!("missing {}", missing.join(", ")))
+            }
+          }
+          Ok(None) => {
+            Err("unable to determine the compile sdk of the project template".to_string())
+          }
+          Err(e) => Err(e),
+        },
+      );
+    }
+  }
+
+  if activity_kind == ActivityKind::Native || sdl_dir.is_some() {
+    ok &= report_check(
+      "project template",
+      get_template_info(manifest_path).and_then(|template| {
+        let problems = find_template_incompatibilities(
+          &template,
+          get_metadata_min_sdk_version(manifest_path)?,
+          get_java_version(),
+        );
+        if problems.is_empty() {
+          Ok(template.to_string())
+        } else {
+          Err(format!("{}: {}", template, problems.join("; ")))
+        }
+      }),
+    );
+  }
+
+  if let Ok(android_home) = env::var("ANDROID_HOME") {
+    let adb = Path::new(&android_home).join("platform-tools/adb");
+    ok &= report_check(
+      "adb",
+      if adb.is_file() {
+        Ok(adb.display().to_string())
+      } else {
+        Err(format!(
+          "{} not found, install platform-tools",
+          adb.display()
+        ))
+      },
+    );
+  }
+
+  for tool in ["java", "keytool"] {
+    ok &= report_check(
+      tool,
+      find_on_path(tool)
+        .map(|p| p.display().to_string())
+        .ok_or("not found on PATH".to_string()),
+    );
+  }
+
+  if offline {
+    for (name, result) in check_offline_build(manifest_path) {
+      ok &= report_check(name, result);
+    }
+  }
+
+  ok
+}
+
+#[cfg(test)]
+mod test {
+  use std::fs::{create_dir_all, write};
+
+  use tempfile::tempdir;
+
+  use crate::preflight::{
+    check_sdl_dir, get_expected_sdl_version, is_sdl_version_compatible, parse_locked_sdl_sys_crate,
+    parse_sdl_version, SdlLayout, SdlVersion,
+  };
+
+  #[test]
+  fn sdl2_header_version() {
+    let header = "#define SDL_MAJOR_VERSION   2\n#define SDL_MINOR_VERSION   26\n#define SDL_PATCHLEVEL      4\n";
+    assert_eq!(parse_sdl_version(header), Some(SdlVersion(2, 26, 4)));
+  }
+
+  #[test]
+  fn sdl3_header_version() {
+    let header =
+      "#define SDL_MAJOR_VERSION   3\n#define SDL_MINOR_VERSION   2\n#define SDL_MICRO_VERSION   10\n";
+    assert_eq!(parse_sdl_version(header), Some(SdlVersion(3, 2, 10)));
+    assert_eq!(parse_sdl_version("#define SDL_MAJOR_VERSION 3\n"), None);
+  }
+
+  #[test]
+  fn locked_sys_crate() {
+    let lock = r#"
+version = 3
+
+[[package]]
+name = "sdl2"
+version = "0.36.0"
+
+[[package]]
+name = "sdl2-sys"
+version = "0.36.0"
+"#;
+    assert_eq!(
+      parse_locked_sdl_sys_crate(lock),
+      Some(("sdl2-sys".to_string(), "0.36.0".to_string()))
+    );
+    assert_eq!(parse_locked_sdl_sys_crate("version = 3\n"), None);
+  }
+
+  #[test]
+  fn compatibility() {
+    let expected = get_expected_sdl_version("sdl2-sys", "0.37.0").unwrap();
+    assert_eq!(expected, SdlVersion(2, 30, 0));
+    assert!(is_sdl_version_compatible(SdlVersion(2, 30, 9), expected));
+    assert!(!is_sdl_version_compatible(SdlVersion(2, 0, 14), expected));
+    assert!(!is_sdl_version_compatible(SdlVersion(3, 2, 0), expected));
+    assert_eq!(get_expected_sdl_version("sdl2-sys", "0.20.0"), None);
+  }
+
+  #[test]
+  fn sdl_dir_layouts() {
+    let dir = tempdir().unwrap();
+    let sdl = dir.path();
+    assert!(check_sdl_dir(&sdl.join("missing"), true)
+      .unwrap_err()
+      .contains("not a directory"));
+
+    //installed SDL2 prefix
+    create_dir_all(sdl.join("include/SDL2")).unwrap();
+    write(sdl.join("include/SDL2/SDL.h"), "").unwrap();
+    create_dir_all(sdl.join("lib")).unwrap();
+    write(sdl.join("lib/libSDL2.so"), "").unwrap();
+    let e = check_sdl_dir(sdl, true).unwrap_err();
+    assert!(e.contains("installed SDL2"), "{}", e);
+    assert!(e.contains("tree/SDL2"), "{}", e);
+
+    //partial SDL3 source checkout
+    let dir = tempdir().unwrap();
+    let sdl = dir.path();
+    create_dir_all(sdl.join("include/SDL3")).unwrap();
+    write(sdl.join("include/SDL3/SDL.h"), "").unwrap();
+    create_dir_all(sdl.join("src")).unwrap();
+    write(sdl.join("Android.mk"), "").unwrap();
+    let e = check_sdl_dir(sdl, true).unwrap_err();
+    assert!(
+      e.contains("missing android-project of the SDL3 source layout"),
+      "{}",
+      e
+    );
+    //a custom template replaces android-project
+    assert_eq!(check_sdl_dir(sdl, false), Ok(SdlLayout::Sdl3));
+
+    create_dir_all(sdl.join("android-project")).unwrap();
+    assert_eq!(check_sdl_dir(sdl, true), Ok(SdlLayout::Sdl3));
+  }
+}
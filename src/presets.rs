@@ -0,0 +1,447 @@
+use std::path::Path;
+
+use toml::value::Value;
+use toml::Table;
+
+use crate::manifest::*;
+use crate::util::*;
+
+/// A change of AndroidManifest.xml a preset is made of.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ManifestMutation {
+  /// Adds an intent category to the launcher intent filter.
+  LauncherCategory(&'static str),
+  /// Sets an attribute of the launcher activity, given as (name, value).
+  ActivityAttribute(&'static str, &'static str),
+  /// Declares a feature, given as (name, required, version).
+  UsesFeature(&'static str, bool, Option<u32>),
+  /// Sets a meta-data entry of the application, given as (name, value).
+  ApplicationMetaData(&'static str, &'static str),
+}
+
+impl ManifestMutation {
+  /// Applies the mutation, with the value replaced by `value` if given.
+  pub fn apply(&self, manifest: &mut AndroidManifest, value: Option<&str>) {
+    match *self {
+      ManifestMutation::LauncherCategory(category) => {
+        manifest.add_launcher_category(value.unwrap_or(category))
+      }
+      ManifestMutation::ActivityAttribute(name, default) => {
+        manifest.set_activity_attribute(name, value.unwrap_or(default))
+      }
+      ManifestMutation::UsesFeature(name, required, version) => {
+        manifest.add_uses_feature(&UsesFeature {
+          version,
+          ..UsesFeature::new(name, required)
+        })
+      }
+      ManifestMutation::ApplicationMetaData(name, default) => {
+        manifest.set_application_meta_data(name, value.unwrap_or(default))
+      }
+    }
+  }
+
+  /// Whether the mutation has a value which can be overridden.
+  fn has_value(&self) -> bool {
+    !matches!(self, ManifestMutation::UsesFeature(..))
+  }
+}
+
+/// A bundle of settings for a kind of device, selected by `preset` in the android metadata.
+pub struct Preset {
+  pub name: &'static str,
+  /// Manifest mutations keyed by the name they are overridden with in `preset_overrides`.
+  pub mutations: &'static [(&'static str, ManifestMutation)],
+  /// Targets built by default, instead of all android targets.
+  pub targets: Option<&'static [&'static str]>,
+  pub min_sdk_version: Option<u32>,
+}
+
+/// Available presets. New presets only need an entry here.
+pub const PRESETS: &[Preset] = &[Preset {
+  name: "quest",
+  mutations: &[
+    (
+      "vr_intent_category",
+      ManifestMutation::LauncherCategory("com.oculus.intent.category.VR"),
+    ),
+    (
+      "exclude_from_recents",
+      ManifestMutation::ActivityAttribute("excludeFromRecents", "true"),
+    ),
+    (
+      "head_tracking",
+      ManifestMutation::UsesFeature("android.hardware.vr.headtracking", true, Some(1)),
+    ),
+    (
+      "hand_tracking",
+      ManifestMutation::UsesFeature("oculus.software.handtracking", false, None),
+    ),
+    (
+      "supported_devices",
+      ManifestMutation::ApplicationMetaData(
+        "com.oculus.supportedDevices",
+        "quest|quest2|quest3|questpro",
+      ),
+    ),
+  ],
+  targets: Some(&["aarch64-linux-android"]),
+  min_sdk_version: Some(29),
+}];
+
+/// Manifest mutations of `large_screens = true`, which let the app adapt to tablets, foldables
+/// and Chromebooks.
+const LARGE_SCREEN_MUTATIONS: &[ManifestMutation] = &[
+  ManifestMutation::ActivityAttribute("resizeableActivity", "true"),
+  ManifestMutation::UsesFeature("android.hardware.touchscreen", false, None),
+  ManifestMutation::ApplicationMetaData("android.allow_multiple_resumed_activities", "true"),
+];
+
+/// Orientation set by `large_screens = true` if none was configured.
+const LARGE_SCREEN_ORIENTATION: &str = "fullUser";
+
+/// Orientations which follow the device instead of locking it.
+const UNLOCKED_ORIENTATIONS: &[&str] = &["unspecified", "user", "fullUser", "sensor", "fullSensor"];
+
+/// Applies the large screen mutations. Returns a warning if the configured orientation locks the
+/// orientation, which is kept but conflicts with large screen support.
+pub fn apply_large_screens(manifest: &mut AndroidManifest) -> Option<String> {
+  for mutation in LARGE_SCREEN_MUTATIONS {
+    mutation.apply(manifest, None);
+  }
+
+  match manifest.activity_attribute("screenOrientation") {
+    None => {
+      manifest.set_activity_attribute("screenOrientation", LARGE_SCREEN_ORIENTATION);
+      None
+    }
+    Some(orientation) if UNLOCKED_ORIENTATIONS.contains(&orientation.as_str()) => None,
+    Some(orientation) => Some(format!(
+      "The orientation is locked to \"{}\", although large_screens is enabled. Google Play \
+      flags apps locking the orientation on large screens and may show them less prominently to \
+      tablet and Chromebook users. Keeping the configured orientation, remove it to use \"{}\".",
+      orientation, LARGE_SCREEN_ORIENTATION
+    )),
+  }
+}
+
+/// Feature declared by apps running on Wear OS watches.
+const WATCH_FEATURE: &str = "android.hardware.type.watch";
+
+/// Meta-data telling Play whether the watch app works without the phone app.
+const WEAR_STANDALONE_META_DATA: &str = "com.google.android.wearable.standalone";
+
+/// Wear OS support, from `wear` in the android metadata.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WearConfig {
+  /// `exclude = true`, the app isn't offered for watches, even if a library declares watch
+  /// support.
+  Exclude,
+  /// `standalone = true|false`, the app runs on watches, either on its own or with the phone app.
+  Support { standalone: bool },
+}
+
+impl WearConfig {
+  pub fn parse(value: &Value) -> Result<Self, String> {
+    let table = value
+      .as_table()
+      .ok_or(format!("wear must be a table, found: {}", value))?;
+    let get_bool = |key: &str| match table.get(key) {
+      None => Ok(None),
+      Some(Value::Boolean(b)) => Ok(Some(*b)),
+      Some(v) => Err(format!("wear.{} must be a boolean, found: {}", key, v)),
+    };
+    if let Some(key) = table
+      .keys()
+      .find(|key| !["exclude", "standalone"].contains(&key.as_str()))
+    {
+      return Err(format!(
+        "Unknown wear setting \"{}\", expected \"exclude\" or \"standalone\"",
+        key
+      ));
+    }
+
+    match (get_bool("exclude")?, get_bool("standalone")?) {
+      (Some(true), Some(_)) => {
+        Err("wear.exclude and wear.standalone can't be combined".to_string())
+      }
+      (Some(true), None) => Ok(WearConfig::Exclude),
+      (_, standalone) => Ok(WearConfig::Support {
+        standalone: standalone.unwrap_or(false),
+      }),
+    }
+  }
+
+  pub fn apply(&self, manifest: &mut AndroidManifest) {
+    match *self {
+      WearConfig::Exclude => manifest.remove_uses_feature(WATCH_FEATURE),
+      WearConfig::Support { standalone } => {
+        //not required, so the app is still offered for phones
+        manifest.add_uses_feature(&UsesFeature::new(WATCH_FEATURE, false));
+        manifest.set_application_meta_data(WEAR_STANDALONE_META_DATA, &standalone.to_string());
+      }
+    }
+  }
+}
+
+/// Applies the `wear` android metadata to the manifest.
+pub fn apply_configured_wear(
+  manifest_path: &Path,
+  manifest: &mut AndroidManifest,
+) -> Result<(), String> {
+  if let Some(value) = get_toml_entry(manifest_path, ["package", "metadata", "android", "wear"])? {
+    WearConfig::parse(&value)?.apply(manifest);
+  }
+  Ok(())
+}
+
+pub fn find_preset(name: &str) -> Option<&'static Preset> {
+  PRESETS.iter().find(|preset| preset.name == name)
+}
+
+/// Returns the preset configured by `preset` in the android metadata.
+pub fn get_preset(manifest_path: &Path) -> Result<Option<&'static Preset>, String> {
+  let Some(name) = get_toml_string(manifest_path, ["package", "metadata", "android", "preset"])?
+  else {
+    return Ok(None);
+  };
+  find_preset(&name).map(Some).ok_or_else(|| {
+    let names: Vec<&str> = PRESETS.iter().map(|preset| preset.name).collect();
+    format!(
+      "Unknown preset \"{}\", expected one of: {}",
+      name,
+      names.join(", ")
+    )
+  })
+}
+
+/// Applies the manifest mutations of the preset. An override of `false` skips a mutation, a
+/// string replaces its value.
+pub fn apply_preset(
+  preset: &Preset,
+  overrides: &Table,
+  manifest: &mut AndroidManifest,
+) -> Result<(), String> {
+  if let Some(unknown) = overrides
+    .keys()
+    .find(|key| !preset.mutations.iter().any(|(name, _)| name == key))
+  {
+    return Err(format!("preset {} has no setting {}", preset.name, unknown));
+  }
+
+  for (name, mutation) in preset.mutations {
+    match overrides.get(*name) {
+      None | Some(Value::Boolean(true)) => mutation.apply(manifest, None),
+      Some(Value::Boolean(false)) => {}
+      Some(Value::String(value)) if mutation.has_value() => mutation.apply(manifest, Some(value)),
+      Some(value) => {
+        return Err(format!(
+          "invalid override of {}: {}, expected {}",
+          name,
+          value,
+          if mutation.has_value() {
+            "a boolean or a string"
+          } else {
+            "a boolean"
+          }
+        ))
+      }
+    }
+  }
+  Ok(())
+}
+
+/// Applies the configured preset to the manifest.
+pub fn apply_configured_preset(
+  manifest_path: &Path,
+  manifest: &mut AndroidManifest,
+) -> Result<(), String> {
+  let Some(preset) = get_preset(manifest_path)? else {
+    return Ok(());
+  };
+  let overrides = match get_toml_entry(
+    manifest_path,
+    ["package", "metadata", "android", "preset_overrides"],
+  )? {
+    Some(Value::Table(overrides)) => overrides,
+    Some(_) => return Err("package.metadata.android.preset_overrides must be a table".to_string()),
+    None => Table::new(),
+  };
+
+  println!("Applying preset {}", preset.name);
+  apply_preset(preset, &overrides, manifest)
+}
+
+/// Checks the min sdk version of the app against the one the preset requires.
+pub fn check_preset_min_sdk_version(
+  preset: &Preset,
+  min_sdk_version: Option<u32>,
+) -> Result<(), String> {
+  match (preset.min_sdk_version, min_sdk_version) {
+    (Some(required), Some(actual)) if actual < required => Err(format!(
+      "The {} preset needs a min sdk version of at least {}, but it is {}. Set min_sdk_version = {} in the android metadata",
+      preset.name, required, actual, required
+    )),
+    (Some(required), None) => Err(format!(
+      "The {} preset needs a min sdk version of at least {}, but it can't be determined. Set min_sdk_version = {} in the android metadata",
+      preset.name, required, required
+    )),
+    _ => Ok(()),
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use toml::Table;
+
+  use crate::manifest::AndroidManifest;
+  use crate::presets::{
+    apply_large_screens, apply_preset, check_preset_min_sdk_version, find_preset, WearConfig,
+  };
+
+  const MANIFEST: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<manifest xmlns:android="http://schemas.android.com/apk/res/android"
+    package="org.libsdl.app">
+
+    <uses-feature android:glEsVersion="0x00020000" />
+
+    <application android:label="@string/app_name">
+
+        <activity android:name="SDLActivity"
+            android:exported="true"
+            >
+            <intent-filter>
+                <action android:name="android.intent.action.MAIN" />
+                <category android:name="android.intent.category.LAUNCHER" />
+            </intent-filter>
+        </activity>
+    </application>
+
+</manifest>
+"#;
+
+  #[test]
+  fn quest_preset() {
+    let preset = find_preset("quest").unwrap();
+    let mut manifest = AndroidManifest::new(MANIFEST.to_string());
+    apply_preset(preset, &Table::new(), &mut manifest).unwrap();
+    let content = manifest.content();
+
+    assert!(content.contains(
+      "<category android:name=\"android.intent.category.LAUNCHER\" />\n                <category android:name=\"com.oculus.intent.category.VR\" />"
+    ));
+    assert!(content.contains(
+      "android:exported=\"true\"\n            android:excludeFromRecents=\"true\"\n            >"
+    ));
+    assert!(content.contains(
+      "<uses-feature android:glEsVersion=\"0x00020000\" />\n    <uses-feature android:name=\"android.hardware.vr.headtracking\" android:required=\"true\" android:version=\"1\" />\n    <uses-feature android:name=\"oculus.software.handtracking\" android:required=\"false\" />"
+    ));
+    assert!(content.contains(
+      "<application android:label=\"@string/app_name\">\n\n        <meta-data android:name=\"com.oculus.supportedDevices\" android:value=\"quest|quest2|quest3|questpro\" />"
+    ));
+
+    //applying it again changes nothing
+    let before = manifest.content().to_string();
+    apply_preset(preset, &Table::new(), &mut manifest).unwrap();
+    assert_eq!(manifest.content(), before);
+  }
+
+  #[test]
+  fn overrides() {
+    let preset = find_preset("quest").unwrap();
+    let overrides = "supported_devices = \"quest3\"\nhand_tracking = false"
+      .parse::<Table>()
+      .unwrap();
+    let mut manifest = AndroidManifest::new(MANIFEST.to_string());
+    apply_preset(preset, &overrides, &mut manifest).unwrap();
+    assert!(manifest
+      .content()
+      .contains("android:name=\"com.oculus.supportedDevices\" android:value=\"quest3\""));
+    assert!(!manifest.content().contains("handtracking"));
+
+    for invalid in ["head_tracking = \"yes\"", "passthrough = true"] {
+      let overrides = invalid.parse::<Table>().unwrap();
+      let mut manifest = AndroidManifest::new(MANIFEST.to_string());
+      assert!(apply_preset(preset, &overrides, &mut manifest).is_err());
+    }
+  }
+
+  #[test]
+  fn min_sdk_version() {
+    let preset = find_preset("quest").unwrap();
+    assert!(check_preset_min_sdk_version(preset, Some(29)).is_ok());
+    assert!(check_preset_min_sdk_version(preset, Some(16)).is_err());
+    assert!(check_preset_min_sdk_version(preset, None).is_err());
+  }
+
+  #[test]
+  fn large_screens() {
+    let mut manifest = AndroidManifest::new(MANIFEST.to_string());
+    assert_eq!(apply_large_screens(&mut manifest), None);
+    assert_eq!(
+      manifest.activity_attribute("screenOrientation").as_deref(),
+      Some("fullUser")
+    );
+    assert_eq!(
+      manifest.activity_attribute("resizeableActivity").as_deref(),
+      Some("true")
+    );
+    assert!(manifest.content().contains(
+      "<uses-feature android:name=\"android.hardware.touchscreen\" android:required=\"false\" />"
+    ));
+    assert!(manifest.content().contains(
+      "<meta-data android:name=\"android.allow_multiple_resumed_activities\" android:value=\"true\" />"
+    ));
+
+    let mut manifest = AndroidManifest::new(MANIFEST.to_string());
+    manifest.set_activity_attribute("screenOrientation", "sensorLandscape");
+    assert!(apply_large_screens(&mut manifest)
+      .unwrap()
+      .contains("\"sensorLandscape\""));
+    assert_eq!(
+      manifest.activity_attribute("screenOrientation").as_deref(),
+      Some("sensorLandscape")
+    );
+  }
+
+  #[test]
+  fn wear() {
+    let parse = |toml: &str| WearConfig::parse(&toml.parse::<Table>().unwrap()["wear"]);
+    assert_eq!(parse("wear = { exclude = true }"), Ok(WearConfig::Exclude));
+    assert_eq!(
+      parse("wear = { standalone = true }"),
+      Ok(WearConfig::Support { standalone: true })
+    );
+    assert!(parse("wear = { exclude = true, standalone = true }").is_err());
+    assert!(parse("wear = { standalone = \"yes\" }").is_err());
+    assert!(parse("wear = { watch = true }").is_err());
+
+    let mut manifest = AndroidManifest::new(MANIFEST.to_string());
+    WearConfig::Support { standalone: true }.apply(&mut manifest);
+    assert!(manifest.content().contains(
+      "<uses-feature android:name=\"android.hardware.type.watch\" android:required=\"false\" />"
+    ));
+    assert!(manifest.content().contains(
+      "<meta-data android:name=\"com.google.android.wearable.standalone\" android:value=\"true\" />"
+    ));
+
+    //exclusion replaces the declaration with a removal marker for the manifest merger
+    WearConfig::Exclude.apply(&mut manifest);
+    assert!(manifest.content().starts_with(
+      "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<manifest xmlns:tools=\"http://schemas.android.com/tools\" xmlns:android="
+    ));
+    assert!(manifest.content().contains(
+      "<uses-feature android:name=\"android.hardware.type.watch\" tools:node=\"remove\" />"
+    ));
+    assert_eq!(
+      manifest
+        .content()
+        .matches("android.hardware.type.watch")
+        .count(),
+      1
+    );
+    let content = manifest.content().to_string();
+    WearConfig::Exclude.apply(&mut manifest);
+    assert_eq!(manifest.content(), content);
+  }
+}
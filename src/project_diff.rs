@@ -0,0 +1,283 @@
+use std::fs::{read, read_dir, remove_dir_all};
+use std::path::{Path, PathBuf};
+
+use crate::android_build::*;
+use crate::error::*;
+use crate::project_template::*;
+
+/// Lines of context around each change of a diff.
+const CONTEXT_LINES: usize = 3;
+
+/// Above this many line pairs the changed middle of a file is shown as replaced as a whole, to
+/// bound the memory of the diff.
+const MAX_DIFF_CELLS: usize = 4_000_000;
+
+/// Dirs gradle and Android Studio write to, which generating the project doesn't.
+const IGNORED_DIRS: &[&str] = &["build", ".gradle", ".idea", ".cxx"];
+
+/// What the generated project is compared with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffBaseline {
+  /// The android project in the dir, as generated by the last build.
+  Project(PathBuf),
+  /// The unchanged project template.
+  Template,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Line<'a> {
+  Same(&'a str),
+  Removed(&'a str),
+  Added(&'a str),
+}
+
+/// The edit script turning the old lines into the new ones, by the longest common subsequence.
+fn diff_lines<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<Line<'a>> {
+  let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+  let suffix = old[prefix..]
+    .iter()
+    .rev()
+    .zip(new[prefix..].iter().rev())
+    .take_while(|(a, b)| a == b)
+    .count();
+  let old_middle = &old[prefix..old.len() - suffix];
+  let new_middle = &new[prefix..new.len() - suffix];
+
+  let mut lines: Vec<Line> = old[..prefix].iter().map(|l| Line::Same(l)).collect();
+  if old_middle.len() * new_middle.len() > MAX_DIFF_CELLS {
+    lines.extend(old_middle.iter().map(|l| Line::Removed(l)));
+    lines.extend(new_middle.iter().map(|l| Line::Added(l)));
+  } else {
+    //lengths of the longest common subsequences of the tails
+    let width = new_middle.len() + 1;
+    let mut lcs = vec![0u32; (old_middle.len() + 1) * width];
+    for i in (0..old_middle.len()).rev() {
+      for j in (0..new_middle.len()).rev() {
+        lcs[i * width + j] = if old_middle[i] == new_middle[j] {
+          lcs[(i + 1) * width + j + 1] + 1
+        } else {
+          lcs[(i + 1) * width + j].max(lcs[i * width + j + 1])
+        };
+      }
+    }
+    let (mut i, mut j) = (0, 0);
+    while i < old_middle.len() || j < new_middle.len() {
+      if i < old_middle.len() && j < new_middle.len() && old_middle[i] == new_middle[j] {
+        lines.push(Line::Same(old_middle[i]));
+        i += 1;
+        j += 1;
+      } else if i < old_middle.len()
+        && (j == new_middle.len() || lcs[(i + 1) * width + j] >= lcs[i * width + j + 1])
+      {
+        //removed lines go before the added ones replacing them
+        lines.push(Line::Removed(old_middle[i]));
+        i += 1;
+      } else {
+        lines.push(Line::Added(new_middle[j]));
+        j += 1;
+      }
+    }
+  }
+  lines.extend(old[old.len() - suffix..].iter().map(|l| Line::Same(l)));
+  lines
+}
+
+/// A unified diff of the contents with the given file names, `None` if they are the same.
+pub fn unified_diff(old: &str, new: &str, old_name: &str, new_name: &str) -> Option<String> {
+  if old == new {
+    return None;
+  }
+  let old_lines: Vec<&str> = old.lines().collect();
+  let new_lines: Vec<&str> = new.lines().collect();
+  let lines = diff_lines(&old_lines, &new_lines);
+
+  let mut diff = format!("--- {}\n+++ {}\n", old_name, new_name);
+  let changes: Vec<usize> = (0..lines.len())
+    .filter(|i| !matches!(lines[*i], Line::Same(_)))
+    .collect();
+  if changes.is_empty() {
+    //only the line endings differ
+    diff.push_str("@@ line endings differ @@\n");
+    return Some(diff);
+  }
+  //changes closer than twice the context share a hunk
+  let mut hunks: Vec<(usize, usize)> = vec![];
+  for change in changes {
+    let start = change.saturating_sub(CONTEXT_LINES);
+    let end = (change + CONTEXT_LINES + 1).min(lines.len());
+    match hunks.last_mut() {
+      Some((_, last_end)) if start <= *last_end => *last_end = end,
+      _ => hunks.push((start, end)),
+    }
+  }
+
+  let count_before = |end: usize, old_side: bool| {
+    lines[..end]
+      .iter()
+      .filter(|line| match line {
+        Line::Same(_) => true,
+        Line::Removed(_) => old_side,
+        Line::Added(_) => !old_side,
+      })
+      .count()
+  };
+  for (start, end) in hunks {
+    let old_start = count_before(start, true);
+    let new_start = count_before(start, false);
+    let old_count = count_before(end, true) - old_start;
+    let new_count = count_before(end, false) - new_start;
+    //an empty range names the line before it
+    let first_line = |start: usize, count: usize| if count == 0 { start } else { start + 1 };
+    diff.push_str(&format!(
+      "@@ -{},{} +{},{} @@\n",
+      first_line(old_start, old_count),
+      old_count,
+      first_line(new_start, new_count),
+      new_count
+    ));
+    for line in &lines[start..end] {
+      match line {
+        Line::Same(l) => diff.push_str(&format!(" {}\n", l)),
+        Line::Removed(l) => diff.push_str(&format!("-{}\n", l)),
+        Line::Added(l) => diff.push_str(&format!("+{}\n", l)),
+      }
+    }
+  }
+  Some(diff)
+}
+
+/// The regular files below the dir relative to it, leaving out symlinks and the dirs of gradle.
+fn collect_files(dir: &Path, relative: &Path, files: &mut Vec<PathBuf>) {
+  let Ok(entries) = read_dir(dir.join(relative)) else {
+    return;
+  };
+  for entry in entries.filter_map(|e| e.ok()) {
+    let Ok(file_type) = entry.file_type() else {
+      continue;
+    };
+    let path = relative.join(entry.file_name());
+    if file_type.is_dir() {
+      if !IGNORED_DIRS
+        .iter()
+        .any(|ignored| entry.file_name() == *ignored)
+      {
+        collect_files(dir, &path, files);
+      }
+    } else if file_type.is_file() {
+      files.push(path);
+    }
+  }
+}
+
+/// The unified diffs of the files of `new_dir` which are missing in `old_dir` or differ, sorted by
+/// path. Files only in `old_dir` aren't touched by generating and left out.
+pub fn diff_dirs(old_dir: &Path, new_dir: &Path) -> Vec<String> {
+  let mut files = vec![];
+  collect_files(new_dir, Path::new(""), &mut files);
+  files.sort();
+  files
+    .iter()
+    .filter_map(|file| {
+      let name = file.to_string_lossy().replace('\\', "/");
+      let new = read(new_dir.join(file)).ok()?;
+      let old = read(old_dir.join(file)).ok();
+      let old_name = match old {
+        Some(_) => format!("a/{}", name),
+        None => "/dev/null".to_string(),
+      };
+      let old = old.unwrap_or_default();
+      if old == new {
+        return None;
+      }
+      match (String::from_utf8(old), String::from_utf8(new)) {
+        (Ok(old), Ok(new)) => unified_diff(&old, &new, &old_name, &format!("b/{}", name)),
+        _ => Some(format!("Binary files {} and b/{} differ\n", old_name, name)),
+      }
+    })
+    .collect()
+}
+
+/// Generates the project of the build into a scratch dir in `target/` and diffs it against the
+/// baseline, without running cargo, ndk-build or gradle.
+pub fn diff_generated_project(
+  manifest_path: &Path,
+  build: AndroidBuild,
+  baseline: DiffBaseline,
+) -> Result<String> {
+  let manifest_dir = manifest_path.parent().unwrap();
+  let scratch_dir = manifest_dir.join("target/android-project-diff");
+  if scratch_dir.exists() {
+    remove_dir_all(&scratch_dir)
+      .map_err(|e| format!("unable to remove {}: {}", scratch_dir.display(), e))?;
+  }
+  let generated = build
+    .project_dir(scratch_dir.join("generated"))
+    .generate_project();
+  let diffs = generated.and_then(|generated| {
+    let baseline_dir = match baseline {
+      DiffBaseline::Project(project_dir) => project_dir,
+      DiffBaseline::Template => {
+        let template_dir = scratch_dir.join("template");
+        ProjectTemplate::for_manifest(manifest_path)
+          .map_err(Error::Config)?
+          .copy_to(&template_dir);
+        template_dir
+      }
+    };
+    Ok(diff_dirs(&baseline_dir, &generated))
+  });
+  let _ = remove_dir_all(&scratch_dir);
+  Ok(diffs?.concat())
+}
+
+#[cfg(test)]
+mod test {
+  use std::fs::{create_dir_all, write};
+
+  use tempfile::tempdir;
+
+  use crate::project_diff::{diff_dirs, unified_diff};
+
+  #[test]
+  fn unified_diffs() {
+    let old = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\nk\n";
+    let new = "a\nB\nc\nd\ne\nf\ng\nh\ni\nj\nk\nl\n";
+    assert_eq!(
+      Some(
+        "--- a/x\n+++ b/x\n\
+        @@ -1,5 +1,5 @@\n a\n-b\n+B\n c\n d\n e\n\
+        @@ -9,3 +9,4 @@\n i\n j\n k\n+l\n"
+          .to_string()
+      ),
+      unified_diff(old, new, "a/x", "b/x")
+    );
+    assert_eq!(None, unified_diff(old, old, "a/x", "b/x"));
+    assert_eq!(
+      Some("--- /dev/null\n+++ b/x\n@@ -0,0 +1,2 @@\n+a\n+b\n".to_string()),
+      unified_diff("", "a\nb\n", "/dev/null", "b/x")
+    );
+  }
+
+  #[test]
+  fn dir_diffs() {
+    let dir = tempdir().unwrap();
+    let old = dir.path().join("old");
+    let new = dir.path().join("new");
+    for project in [&old, &new] {
+      create_dir_all(project.join("app/build")).unwrap();
+      write(project.join("app/build.gradle"), "minSdk 19\n").unwrap();
+    }
+    write(new.join("app/build.gradle"), "minSdk 24\n").unwrap();
+    write(new.join("app/build/output.txt"), "gradle output").unwrap();
+    write(new.join("app/icon.png"), [0x89, 0xff, 0]).unwrap();
+    write(old.join("only-old.txt"), "left alone").unwrap();
+
+    assert_eq!(
+      vec![
+        "--- a/app/build.gradle\n+++ b/app/build.gradle\n@@ -1,1 +1,1 @@\n-minSdk 19\n+minSdk 24\n",
+        "Binary files /dev/null and b/app/icon.png differ\n",
+      ],
+      diff_dirs(&old, &new)
+    );
+  }
+}
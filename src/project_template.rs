@@ -0,0 +1,316 @@
+use std::env;
+use std::fs::{create_dir_all, read_to_string, write};
+use std::path::{Path, PathBuf};
+
+use fs_extra::dir::{self, CopyOptions};
+
+use crate::gradle_dsl::*;
+use crate::util::*;
+
+/// Android project template for apps using `android.app.NativeActivity` directly, e.g. through
+/// winit or ndk-glue, as (path, content) pairs.
+const NATIVE_TEMPLATE_FILES: &[(&str, &str)] = &[
+  ("build.gradle", include_str!("native_template/build.gradle")),
+  (
+    "settings.gradle",
+    include_str!("native_template/settings.gradle"),
+  ),
+  (
+    "gradle.properties",
+    include_str!("native_template/gradle.properties"),
+  ),
+  (
+    "gradle/wrapper/gradle-wrapper.properties",
+    include_str!("native_template/gradle/wrapper/gradle-wrapper.properties"),
+  ),
+  (
+    "app/build.gradle",
+    include_str!("native_template/app/build.gradle"),
+  ),
+  (
+    "app/src/main/AndroidManifest.xml",
+    include_str!("native_template/app/src/main/AndroidManifest.xml"),
+  ),
+  (
+    "app/src/main/res/values/strings.xml",
+    include_str!("native_template/app/src/main/res/values/strings.xml"),
+  ),
+];
+
+/// Environment variable with an android project template used instead of the one of the SDL
+/// source, e.g. a fork of just the template.
+pub const PROJECT_TEMPLATE_ENV: &str = "ANDROID_PROJECT_TEMPLATE";
+
+/// Java glue of SDL the template contains, unless it comes from a Maven dependency.
+pub const SDL_ACTIVITY_SOURCE: &str = "app/src/main/java/org/libsdl/app/SDLActivity.java";
+
+/// Group of the Maven artifacts SDL publishes its Java glue as.
+const SDL_MAVEN_GROUP: &str = "org.libsdl";
+
+/// The kind of activity the app is started with, configured by `activity_kind` in the android
+/// metadata.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ActivityKind {
+  /// A MainActivity extending SDLActivity, using the SDL Java glue and the SDL project template.
+  Sdl,
+  /// `android.app.NativeActivity` loading the built library, without any SDL involvement.
+  Native,
+}
+
+impl ActivityKind {
+  pub fn parse(value: &str) -> Option<Self> {
+    match value {
+      "sdl" => Some(ActivityKind::Sdl),
+      "native" => Some(ActivityKind::Native),
+      _ => None,
+    }
+  }
+
+  /// Fully qualified name of the activity class to launch. The generated MainActivity lives in the
+  /// package of the base app id, which differs from the installed app id if a suffix is applied.
+  pub fn activity_class(&self, base_app_id: &str) -> String {
+    match self {
+      ActivityKind::Sdl => format!("{}.MainActivity", base_app_id),
+      ActivityKind::Native => "android.app.NativeActivity".to_string(),
+    }
+  }
+}
+
+pub fn get_activity_kind(manifest_path: &Path) -> Result<ActivityKind, String> {
+  match get_toml_string(
+    manifest_path,
+    ["package", "metadata", "android", "activity_kind"],
+  )? {
+    None => Ok(ActivityKind::Sdl),
+    Some(kind) => ActivityKind::parse(&kind).ok_or_else(|| {
+      format!(
+        "Unknown activity_kind \"{}\", expected \"sdl\" or \"native\"",
+        kind
+      )
+    }),
+  }
+}
+
+/// The android project template configured instead of the one of the SDL source:
+/// `ANDROID_PROJECT_TEMPLATE`, or else `project_template` of the android metadata, relative to the
+/// package.
+pub fn get_custom_template_dir(manifest_path: &Path) -> Result<Option<PathBuf>, TomlError> {
+  if let Some(dir) = env::var_os(PROJECT_TEMPLATE_ENV).filter(|dir| !dir.is_empty()) {
+    return Ok(Some(PathBuf::from(dir)));
+  }
+  Ok(
+    get_toml_string(
+      manifest_path,
+      ["package", "metadata", "android", "project_template"],
+    )?
+    .map(|dir| manifest_path.parent().unwrap().join(dir)),
+  )
+}
+
+/// Checks that a custom template has the skeleton the project is generated from, naming what is
+/// missing. The SDL Java glue is either part of it or a Maven dependency of app/build.gradle.
+pub fn check_template_dir(template_dir: &Path) -> Result<(), String> {
+  if !template_dir.is_dir() {
+    return Err(format!(
+      "the android project template {:?} is not a directory",
+      template_dir
+    ));
+  }
+  let build_gradle = read_to_string(template_dir.join("app/build.gradle"))
+    .or_else(|_| read_to_string(template_dir.join("app/build.gradle.kts")))
+    .ok();
+  let mut missing = vec![];
+  if build_gradle.is_none() {
+    missing.push("app/build.gradle".to_string());
+  }
+  if !template_dir
+    .join("app/src/main/AndroidManifest.xml")
+    .is_file()
+  {
+    missing.push("app/src/main/AndroidManifest.xml".to_string());
+  }
+  let maven_glue = build_gradle
+    .as_deref()
+    .is_some_and(|content| content.contains(SDL_MAVEN_GROUP));
+  if !maven_glue && !template_dir.join(SDL_ACTIVITY_SOURCE).is_file() {
+    missing.push(format!(
+      "{} (or a dependency on the {} Maven artifacts in app/build.gradle)",
+      SDL_ACTIVITY_SOURCE, SDL_MAVEN_GROUP
+    ));
+  }
+  if missing.is_empty() {
+    Ok(())
+  } else {
+    Err(format!(
+      "the android project template {:?} is missing {}",
+      template_dir,
+      missing.join(", ")
+    ))
+  }
+}
+
+/// The template the android project is generated from.
+pub enum ProjectTemplate {
+  /// The android-project directory of the SDL source, or a custom template replacing it.
+  Sdl(PathBuf),
+  /// The embedded template for native activities.
+  Native,
+}
+
+impl ProjectTemplate {
+  pub fn for_manifest(manifest_path: &Path) -> Result<Self, String> {
+    Ok(match get_activity_kind(manifest_path)? {
+      ActivityKind::Sdl => ProjectTemplate::Sdl(
+        get_custom_template_dir(manifest_path)?
+          .unwrap_or_else(|| get_env_path("SDL").join("android-project")),
+      ),
+      ActivityKind::Native => ProjectTemplate::Native,
+    })
+  }
+
+  /// Reads the original content of a template file.
+  pub fn read(&self, file_name: &str) -> Option<String> {
+    match self {
+      ProjectTemplate::Sdl(template_dir) => read_to_string(template_dir.join(file_name)).ok(),
+      ProjectTemplate::Native => NATIVE_TEMPLATE_FILES
+        .iter()
+        .find(|(path, _)| *path == file_name)
+        .map(|(_, content)| content.to_string()),
+    }
+  }
+
+  /// The language of the build files, Kotlin if the template has an app/build.gradle.kts.
+  pub fn gradle_dsl(&self) -> GradleDsl {
+    match self {
+      ProjectTemplate::Sdl(template_dir) if template_dir.join("app/build.gradle.kts").is_file() => {
+        GradleDsl::Kotlin
+      }
+      _ => GradleDsl::Groovy,
+    }
+  }
+
+  /// Reads the original content of a build file like `app/build.gradle`, or its `.kts` variant if
+  /// the template uses the Kotlin DSL.
+  pub fn read_gradle(&self, file_name: &str) -> Option<String> {
+    self.read(&self.gradle_dsl().file_name(file_name))
+  }
+
+  /// Copies the template into the project dir, keeping files which already exist.
+  pub fn copy_to(&self, project_dir: &Path) {
+    create_dir_all(project_dir)
+      .unwrap_or_else(|_| panic!("Unable to create directory {:?}", project_dir));
+    match self {
+      ProjectTemplate::Sdl(template_dir) => {
+        dir::copy(
+          template_dir,
+          project_dir,
+          &CopyOptions::new().skip_exist(true).content_only(true),
+        )
+        .unwrap_or_else(|e| panic!("Unable to copy template {:?}: {}", template_dir, e));
+      }
+      ProjectTemplate::Native => {
+        for (file_name, content) in NATIVE_TEMPLATE_FILES {
+          let path = project_dir.join(file_name);
+          if path.exists() {
+            continue;
+          }
+          create_dir_all(path.parent().unwrap()).unwrap();
+          write(&path, content).unwrap_or_else(|_| panic!("unable to write file {:?}", path));
+        }
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::fs::{create_dir_all, write};
+
+  use tempfile::tempdir;
+
+  use crate::project_template::{
+    check_template_dir, get_custom_template_dir, ActivityKind, ProjectTemplate,
+  };
+  use crate::sdk::parse_compile_sdk_version;
+
+  #[test]
+  fn native_template() {
+    let template = ProjectTemplate::Native;
+    let manifest = template.read("app/src/main/AndroidManifest.xml").unwrap();
+    assert!(manifest.contains("android.app.NativeActivity"));
+    assert!(manifest.contains("android:name=\"android.app.lib_name\""));
+    assert!(!manifest.contains("SDLActivity"));
+    assert!(parse_compile_sdk_version(&template.read("app/build.gradle").unwrap()).is_some());
+
+    let project = tempdir().unwrap();
+    template.copy_to(project.path());
+    assert!(project.path().join("settings.gradle").is_file());
+    assert!(project
+      .path()
+      .join("app/src/main/res/values/strings.xml")
+      .is_file());
+  }
+
+  #[test]
+  fn activity_kinds() {
+    assert_eq!(ActivityKind::parse("native"), Some(ActivityKind::Native));
+    assert_eq!(ActivityKind::parse("sdl"), Some(ActivityKind::Sdl));
+    assert_eq!(ActivityKind::parse("java"), None);
+    assert_eq!(
+      ActivityKind::Sdl.activity_class("com.example.game"),
+      "com.example.game.MainActivity"
+    );
+  }
+
+  #[test]
+  fn custom_template() {
+    let dir = tempdir().unwrap();
+    let manifest_path = dir.path().join("Cargo.toml");
+    write(&manifest_path, "[package]\nname = \"game\"\n").unwrap();
+    assert_eq!(get_custom_template_dir(&manifest_path).unwrap(), None);
+    write(
+      &manifest_path,
+      "[package]\nname = \"game\"\n\n[package.metadata.android]\nproject_template = \"android-template\"\n",
+    )
+    .unwrap();
+    let template_dir = dir.path().join("android-template");
+    assert_eq!(
+      get_custom_template_dir(&manifest_path).unwrap(),
+      Some(template_dir.clone())
+    );
+
+    assert!(check_template_dir(&template_dir)
+      .unwrap_err()
+      .contains("is not a directory"));
+    create_dir_all(template_dir.join("app/src/main")).unwrap();
+    let e = check_template_dir(&template_dir).unwrap_err();
+    assert!(
+      e.ends_with("is missing app/build.gradle, app/src/main/AndroidManifest.xml, app/src/main/java/org/libsdl/app/SDLActivity.java (or a dependency on the org.libsdl Maven artifacts in app/build.gradle)"),
+      "{}",
+      e
+    );
+
+    write(template_dir.join("app/src/main/AndroidManifest.xml"), "").unwrap();
+    write(template_dir.join("app/build.gradle"), "android {\n}\n").unwrap();
+    let e = check_template_dir(&template_dir).unwrap_err();
+    assert!(e.ends_with("is missing app/src/main/java/org/libsdl/app/SDLActivity.java (or a dependency on the org.libsdl Maven artifacts in app/build.gradle)"), "{}", e);
+
+    //the Java glue from Maven
+    write(
+      template_dir.join("app/build.gradle"),
+      "dependencies {\n    implementation 'org.libsdl.android:SDL3:3.2.0'\n}\n",
+    )
+    .unwrap();
+    assert_eq!(check_template_dir(&template_dir), Ok(()));
+
+    //or as sources
+    write(template_dir.join("app/build.gradle"), "android {\n}\n").unwrap();
+    create_dir_all(template_dir.join("app/src/main/java/org/libsdl/app")).unwrap();
+    write(
+      template_dir.join("app/src/main/java/org/libsdl/app/SDLActivity.java"),
+      "",
+    )
+    .unwrap();
+    assert_eq!(check_template_dir(&template_dir), Ok(()));
+  }
+}
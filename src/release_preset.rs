@@ -0,0 +1,155 @@
+use std::path::Path;
+
+use cargo::core::Workspace;
+use toml::Value;
+
+use crate::build_bin_as_lib::*;
+use crate::util::*;
+
+/// Optimization settings for release builds, from `release_preset` in the android metadata.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReleasePreset {
+  Size,
+  Speed,
+}
+
+impl ReleasePreset {
+  pub fn parse(value: &str) -> Option<Self> {
+    match value {
+      "size" => Some(ReleasePreset::Size),
+      "speed" => Some(ReleasePreset::Speed),
+      _ => None,
+    }
+  }
+
+  /// The `[profile.release]` settings of the preset, as keys and toml values.
+  pub fn settings(&self) -> [(&'static str, &'static str); 4] {
+    [
+      (
+        "opt-level",
+        match self {
+          ReleasePreset::Size => "\"z\"",
+          ReleasePreset::Speed => "3",
+        },
+      ),
+      ("lto", "\"fat\""),
+      ("codegen-units", "1"),
+      ("panic", "\"abort\""),
+    ]
+  }
+}
+
+pub fn get_release_preset(manifest_path: &Path) -> Result<Option<ReleasePreset>, String> {
+  let Some(value) = get_toml_string(
+    manifest_path,
+    ["package", "metadata", "android", "release_preset"],
+  )?
+  else {
+    return Ok(None);
+  };
+  ReleasePreset::parse(&value).map(Some).ok_or_else(|| {
+    format!(
+      "Unknown release_preset \"{}\", expected \"size\" or \"speed\"",
+      value
+    )
+  })
+}
+
+/// Returns the `--config` values applying the preset settings which aren't set already, according
+/// to `is_set`.
+pub fn fill_release_profile(preset: ReleasePreset, is_set: impl Fn(&str) -> bool) -> Vec<String> {
+  preset
+    .settings()
+    .iter()
+    .filter(|(key, _)| !is_set(key))
+    .map(|(key, value)| format!("profile.release.{}={}", key, value))
+    .collect()
+}
+
+/// Whether the user set the profile setting, either in `[profile.release]` of the workspace root
+/// manifest, which is the only one cargo reads profiles from, or with a `CARGO_PROFILE_RELEASE_*`
+/// variable.
+fn is_release_profile_setting_set(release_profile: Option<&Value>, key: &str) -> bool {
+  let env_key = format!(
+    "CARGO_PROFILE_RELEASE_{}",
+    key.replace('-', "_").to_uppercase()
+  );
+  std::env::var_os(env_key).is_some() || release_profile.and_then(|p| p.get(key)).is_some()
+}
+
+/// Returns the `--config` values of the configured release preset, printing what is applied.
+pub fn get_release_preset_config(manifest_path: &Path) -> Result<Vec<String>, String> {
+  let Some(preset) = get_release_preset(manifest_path)? else {
+    return Ok(vec![]);
+  };
+
+  let cargo_config = create_cargo_config(None, &[]);
+  let workspace = Workspace::new(manifest_path, &cargo_config).unwrap();
+  let release_profile = get_toml_entry(workspace.root_manifest(), ["profile", "release"])?;
+  let config = fill_release_profile(preset, |key| {
+    is_release_profile_setting_set(release_profile.as_ref(), key)
+  });
+
+  if config.is_empty() {
+    println!(
+      "Release preset {:?}: all settings are set in the manifest already",
+      preset
+    );
+  } else {
+    println!(
+      "Release preset {:?}: applying {}",
+      preset,
+      config.join(", ")
+    );
+  }
+  Ok(config)
+}
+
+#[cfg(test)]
+mod test {
+  use crate::build_bin_as_lib::create_cargo_config;
+  use crate::release_preset::{fill_release_profile, ReleasePreset};
+
+  #[test]
+  fn preset_values() {
+    assert_eq!(ReleasePreset::parse("size"), Some(ReleasePreset::Size));
+    assert_eq!(ReleasePreset::parse("fast"), None);
+    assert_eq!(
+      fill_release_profile(ReleasePreset::Size, |_| false),
+      vec![
+        "profile.release.opt-level=\"z\"",
+        "profile.release.lto=\"fat\"",
+        "profile.release.codegen-units=1",
+        "profile.release.panic=\"abort\"",
+      ]
+    );
+  }
+
+  #[test]
+  fn user_settings_kept() {
+    assert_eq!(
+      fill_release_profile(ReleasePreset::Speed, |key| key == "lto" || key == "panic"),
+      vec![
+        "profile.release.opt-level=3",
+        "profile.release.codegen-units=1"
+      ]
+    );
+  }
+
+  #[test]
+  fn values_accepted_by_cargo() {
+    let config = create_cargo_config(None, &fill_release_profile(ReleasePreset::Size, |_| false));
+    assert_eq!(
+      config
+        .get::<Option<String>>("profile.release.opt-level")
+        .unwrap(),
+      Some("z".to_string())
+    );
+    assert_eq!(
+      config
+        .get::<Option<u32>>("profile.release.codegen-units")
+        .unwrap(),
+      Some(1)
+    );
+  }
+}
@@ -0,0 +1,176 @@
+use std::env;
+use std::fs::{read, write};
+use std::io::{Cursor, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use zip::write::FileOptions;
+use zip::{CompressionMethod, DateTime, ZipArchive, ZipWriter};
+
+use crate::command_runner::*;
+
+/// Content of the gradle init script which makes all archive tasks (and thereby the packaged apk
+/// contents) independent of file system timestamps and ordering.
+const REPRODUCIBLE_INIT_SCRIPT: &str = "
+allprojects {
+    tasks.withType(AbstractArchiveTask).configureEach {
+        preserveFileTimestamps = false
+        reproducibleFileOrder = true
+    }
+}
+";
+
+/// Determines the timestamp used for reproducible builds. An already set `SOURCE_DATE_EPOCH` is
+/// respected, otherwise the time of the last git commit is used, falling back to 0 outside of git
+/// repositories.
+pub fn get_source_date_epoch(manifest_dir: &Path) -> String {
+  if let Ok(epoch) = env::var("SOURCE_DATE_EPOCH") {
+    return epoch;
+  }
+
+  run_command_output(
+    Command::new("git")
+      .args(["log", "-1", "--format=%ct"])
+      .current_dir(manifest_dir),
+  )
+  .ok()
+  .filter(|output| output.status.success())
+  .and_then(|output| String::from_utf8(output.stdout).ok())
+  .map(|epoch| epoch.trim().to_string())
+  .filter(|epoch| !epoch.is_empty())
+  .unwrap_or("0".to_string())
+}
+
+/// Writes the gradle init script for reproducible builds into the android project and returns its
+/// path, to be passed to gradle with `--init-script`.
+pub fn write_reproducible_init_script(project_dir: &Path) -> PathBuf {
+  let path = project_dir.join("reproducible.gradle");
+  write(&path, REPRODUCIBLE_INIT_SCRIPT)
+    .unwrap_or_else(|_| panic!("unable to write init script {:?}", path));
+  path
+}
+
+/// Rewrites the zip file at the given path so that its content only depends on the contained
+/// entries: entries are sorted by name, all timestamps are reset and no extra fields are kept.
+pub fn normalize_zip(path: &Path) {
+  let content = read(path).unwrap_or_else(|_| panic!("can't read zip file {:?}", path));
+  let normalized = normalize_zip_content(&content)
+    .unwrap_or_else(|e| panic!("unable to normalize zip file {:?}: {}", path, e));
+  write(path, normalized).unwrap_or_else(|_| panic!("unable to write zip file {:?}", path));
+}
+
+fn normalize_zip_content(content: &[u8]) -> zip::result::ZipResult<Vec<u8>> {
+  let mut archive = ZipArchive::new(Cursor::new(content))?;
+
+  let mut names: Vec<String> = archive.file_names().map(|name| name.to_string()).collect();
+  names.sort();
+
+  let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+  for name in names {
+    let mut file = archive.by_name(&name)?;
+
+    if file.is_dir() {
+      writer.add_directory(
+        name,
+        FileOptions::default().last_modified_time(DateTime::default()),
+      )?;
+      continue;
+    }
+
+    //keep stored entries stored, apks rely on e.g. resources.arsc being uncompressed
+    let compression_method = match file.compression() {
+      CompressionMethod::Stored => CompressionMethod::Stored,
+      _ => CompressionMethod::Deflated,
+    };
+    let options = FileOptions::default()
+      .compression_method(compression_method)
+      .last_modified_time(DateTime::default())
+      .unix_permissions(0o644);
+
+    let mut data = Vec::with_capacity(file.size() as usize);
+    file.read_to_end(&mut data)?;
+
+    writer.start_file(name, options)?;
+    writer.write_all(&data)?;
+  }
+
+  Ok(writer.finish()?.into_inner())
+}
+
+#[cfg(test)]
+mod test {
+  use std::io::{Cursor, Write};
+
+  use zip::write::FileOptions;
+  use zip::{CompressionMethod, DateTime, ZipArchive, ZipWriter};
+
+  use crate::reproducible::normalize_zip_content;
+
+  fn create_zip(entries: &[(&str, &str, CompressionMethod)], time: DateTime) -> Vec<u8> {
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    for (name, content, compression_method) in entries {
+      let options = FileOptions::default()
+        .compression_method(*compression_method)
+        .last_modified_time(time);
+      writer.start_file(*name, options).unwrap();
+      writer.write_all(content.as_bytes()).unwrap();
+    }
+    writer.finish().unwrap().into_inner()
+  }
+
+  #[test]
+  fn normalized_zips_are_identical() {
+    let first = create_zip(
+      &[
+        ("classes.dex", "dex", CompressionMethod::Deflated),
+        (
+          "AndroidManifest.xml",
+          "manifest",
+          CompressionMethod::Deflated,
+        ),
+        ("resources.arsc", "resources", CompressionMethod::Stored),
+      ],
+      DateTime::from_date_and_time(2023, 4, 1, 12, 30, 0).unwrap(),
+    );
+    let second = create_zip(
+      &[
+        ("resources.arsc", "resources", CompressionMethod::Stored),
+        ("classes.dex", "dex", CompressionMethod::Deflated),
+        (
+          "AndroidManifest.xml",
+          "manifest",
+          CompressionMethod::Deflated,
+        ),
+      ],
+      DateTime::from_date_and_time(2024, 5, 2, 8, 15, 42).unwrap(),
+    );
+    assert_ne!(first, second);
+
+    let first = normalize_zip_content(&first).unwrap();
+    let second = normalize_zip_content(&second).unwrap();
+    assert_eq!(first, second);
+    assert_eq!(normalize_zip_content(&first).unwrap(), first);
+  }
+
+  #[test]
+  fn normalize_keeps_stored_entries() {
+    let content = create_zip(
+      &[
+        ("resources.arsc", "resources", CompressionMethod::Stored),
+        ("classes.dex", "dex", CompressionMethod::Deflated),
+      ],
+      DateTime::default(),
+    );
+    let normalized = normalize_zip_content(&content).unwrap();
+
+    let mut archive = ZipArchive::new(Cursor::new(normalized)).unwrap();
+    assert_eq!(
+      archive.by_name("resources.arsc").unwrap().compression(),
+      CompressionMethod::Stored
+    );
+    assert_eq!(
+      archive.by_name("classes.dex").unwrap().compression(),
+      CompressionMethod::Deflated
+    );
+  }
+}
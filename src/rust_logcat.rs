@@ -0,0 +1,494 @@
+use std::collections::{HashMap, HashSet};
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Stdio};
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::command_runner::*;
+use crate::ndk::*;
+use crate::split_debuginfo::*;
+
+lazy_static! {
+  static ref BRIEF_LINE_REGEX: Regex =
+    Regex::new(r"^([VDIWEFA])/(.*?)\(\s*(\d+)\): ?(.*)$").expect("invalid logcat line regex");
+  static ref NATIVE_FRAME_REGEX: Regex =
+    Regex::new(r"#(\d+) pc ([0-9a-f]+)\s+(\S*/libmain\.so)").expect("invalid native frame regex");
+  static ref BUILD_ID_REGEX: Regex =
+    Regex::new(r"\(BuildId: ([0-9a-f]+)\)").expect("invalid build id regex");
+}
+
+/// Tag of the stdout and stderr of Rust apps in logcat, as redirected by ndk-glue and
+/// android-activity.
+const RUST_TAG: &str = "RustStdoutStderr";
+
+/// Tag of the crash dumps debuggerd writes to logcat.
+const CRASH_DUMP_TAG: &str = "DEBUG";
+
+/// A line of `logcat -v brief`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogcatLine<'a> {
+  pub priority: &'a str,
+  pub tag: &'a str,
+  pub pid: u32,
+  pub message: &'a str,
+}
+
+pub fn parse_logcat_line(line: &str) -> Option<LogcatLine<'_>> {
+  let captures = BRIEF_LINE_REGEX.captures(line)?;
+  Some(LogcatLine {
+    priority: captures.get(1)?.as_str(),
+    tag: captures.get(2)?.as_str().trim_end(),
+    pid: captures[3].parse().ok()?,
+    message: captures.get(4)?.as_str(),
+  })
+}
+
+/// Replaces the `$..$` escapes of a path element of a legacy mangled Rust symbol.
+fn unescape_legacy_ident(ident: &str) -> Option<String> {
+  //idents starting with an escape get a leading underscore
+  let ident = if ident.starts_with("_$") {
+    &ident[1..]
+  } else {
+    ident
+  };
+  let mut result = String::new();
+  let mut rest = ident;
+  while !rest.is_empty() {
+    if let Some(after) = rest.strip_prefix("..") {
+      result.push_str("::");
+      rest = after;
+    } else if let Some(after) = rest.strip_prefix('$') {
+      let end = after.find('$')?;
+      let escape = &after[..end];
+      let c = match escape {
+        "SP" => '@',
+        "BP" => '*',
+        "RF" => '&',
+        "LT" => '<',
+        "GT" => '>',
+        "LP" => '(',
+        "RP" => ')',
+        "C" => ',',
+        _ => char::from_u32(u32::from_str_radix(escape.strip_prefix('u')?, 16).ok()?)?,
+      };
+      result.push(c);
+      rest = &after[end + 1..];
+    } else {
+      let c = rest.chars().next()?;
+      result.push(c);
+      rest = &rest[c.len_utf8()..];
+    }
+  }
+  Some(result)
+}
+
+/// Demangles a legacy Rust symbol at the start of the text, like
+/// `_ZN4core9panicking5panic17h0123456789abcdefE`, dropping the hash. Returns the demangled path
+/// and the length of the symbol in the text.
+pub fn demangle_legacy(text: &str) -> Option<(String, usize)> {
+  let mut rest = text.strip_prefix("_ZN")?;
+  let mut elements = vec![];
+  loop {
+    if let Some(after) = rest.strip_prefix('E') {
+      rest = after;
+      break;
+    }
+    let digits = rest.chars().take_while(|c| c.is_ascii_digit()).count();
+    let len: usize = rest[..digits].parse().ok()?;
+    let ident = rest.get(digits..digits + len)?;
+    elements.push(ident);
+    rest = &rest[digits + len..];
+  }
+  if let Some(hash) = elements.last() {
+    if hash.len() == 17 && hash.starts_with('h') && hash[1..].chars().all(|c| c.is_ascii_hexdigit())
+    {
+      elements.pop();
+    }
+  }
+  if elements.is_empty() {
+    return None;
+  }
+  let path = elements
+    .into_iter()
+    .map(unescape_legacy_ident)
+    .collect::<Option<Vec<String>>>()?
+    .join("::");
+  Some((path, text.len() - rest.len()))
+}
+
+/// Demangles the legacy Rust symbols in the text.
+pub fn demangle_symbols(text: &str) -> String {
+  let mut result = String::new();
+  let mut rest = text;
+  while let Some(start) = rest.find("_ZN") {
+    result.push_str(&rest[..start]);
+    match demangle_legacy(&rest[start..]) {
+      Some((demangled, len)) => {
+        result.push_str(&demangled);
+        rest = &rest[start + len..];
+      }
+      None => {
+        result.push_str("_ZN");
+        rest = &rest[start + 3..];
+      }
+    }
+  }
+  result.push_str(rest);
+  result
+}
+
+/// A frame in libmain.so of a crash dump, e.g.
+/// `#01 pc 00000000000a1b2c  /data/app/.../lib/arm64/libmain.so (BuildId: 4f3a...)`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NativeFrame {
+  /// The offset in the library, as printed.
+  pub pc: String,
+  /// The abi of the library, from its path on the device.
+  pub abi: Option<&'static str>,
+  pub build_id: Option<String>,
+}
+
+pub fn parse_native_frame(message: &str) -> Option<NativeFrame> {
+  let captures = NATIVE_FRAME_REGEX.captures(message)?;
+  Some(NativeFrame {
+    pc: captures[2].to_string(),
+    abi: get_device_lib_abi(&captures[3]),
+    build_id: BUILD_ID_REGEX
+      .captures(message)
+      .map(|build_id| build_id[1].to_string()),
+  })
+}
+
+/// The abi of a library installed on the device, from the name of its dir in the app.
+pub fn get_device_lib_abi(path: &str) -> Option<&'static str> {
+  let dir = Path::new(path).parent()?.file_name()?.to_str()?;
+  match dir {
+    "arm64" | "arm64-v8a" => Some("arm64-v8a"),
+    "arm" | "armeabi-v7a" => Some("armeabi-v7a"),
+    "x86" => Some("x86"),
+    "x86_64" => Some("x86_64"),
+    _ => None,
+  }
+}
+
+/// The build id in the output of `llvm-readelf -n`.
+pub fn parse_readelf_build_id(output: &str) -> Option<String> {
+  output
+    .lines()
+    .find_map(|line| line.trim().strip_prefix("Build ID: "))
+    .map(|id| id.trim().to_string())
+}
+
+/// Resolves the frames of libmain.so with the unstripped libraries of the build, as long as their
+/// build id matches the library on the device. Split debug info next to a library is used if it
+/// belongs to the same build.
+pub struct Symbolizer {
+  /// The jniLibs dir of the android project, one dir per abi.
+  symbols_dir: PathBuf,
+  build_ids: HashMap<&'static str, Option<String>>,
+  reported_mismatch: bool,
+  /// The abis whose split debug info was checked.
+  checked_split_debuginfo: HashSet<&'static str>,
+}
+
+impl Symbolizer {
+  pub fn new(symbols_dir: impl Into<PathBuf>) -> Self {
+    Self {
+      symbols_dir: symbols_dir.into(),
+      build_ids: HashMap::new(),
+      reported_mismatch: false,
+      checked_split_debuginfo: HashSet::new(),
+    }
+  }
+
+  fn get_library(&self, abi: &str) -> PathBuf {
+    self.symbols_dir.join(abi).join("libmain.so")
+  }
+
+  fn get_local_build_id(&mut self, abi: &'static str) -> Option<String> {
+    let library = self.get_library(abi);
+    self
+      .build_ids
+      .entry(abi)
+      .or_insert_with(|| {
+        let output = run_command_output(
+          Command::new(get_ndk_toolchain_dir().join("bin/llvm-readelf"))
+            .arg("-n")
+            .arg(&library),
+        )
+        .ok()
+        .filter(|output| output.status.success())?;
+        parse_readelf_build_id(&String::from_utf8_lossy(&output.stdout))
+      })
+      .clone()
+  }
+
+  /// The lines describing the frame, from its function to the inlined ones, or a warning once per
+  /// run if the build id doesn't match. Split debug info of another build is warned about once per
+  /// abi.
+  pub fn resolve(&mut self, frame: &NativeFrame) -> Vec<String> {
+    let Some(abi) = frame.abi else {
+      return vec![];
+    };
+    let local = self.get_local_build_id(abi);
+    if local.is_none() || frame.build_id != local {
+      if self.reported_mismatch {
+        return vec![];
+      }
+      self.reported_mismatch = true;
+      return vec![match (&frame.build_id, local) {
+        (_, None) => format!(
+          "WARNING: No symbols for {} at {}, unable to resolve the frames.",
+          abi,
+          self.get_library(abi).display()
+        ),
+        (None, Some(_)) => "WARNING: The crash dump has no build id to check the local symbols \
+          against, not resolving the frames."
+          .to_string(),
+        (Some(device), Some(local)) => format!(
+          "WARNING: libmain.so on the device (BuildId {}) isn't the local build (BuildId {}), \
+          install the current build to resolve the frames.",
+          device, local
+        ),
+      }];
+    }
+
+    let mut lines = vec![];
+    if self.checked_split_debuginfo.insert(abi) {
+      if let Err(e) = check_split_debuginfo(&self.get_library(abi)) {
+        lines.push(format!("WARNING: {}.", e));
+      }
+    }
+    let output = run_command_output(
+      Command::new(get_ndk_toolchain_dir().join("bin/llvm-addr2line"))
+        .args(["-C", "-f", "-i", "-e"])
+        .arg(self.get_library(abi))
+        .arg(format!("0x{}", frame.pc)),
+    )
+    .ok()
+    .filter(|output| output.status.success());
+    if let Some(output) = output {
+      lines.extend(format_addr2line_output(&String::from_utf8_lossy(
+        &output.stdout,
+      )));
+    }
+    lines
+  }
+}
+
+/// Formats the function and location pairs of `llvm-addr2line -f -i`, the innermost inlined
+/// function first.
+pub fn format_addr2line_output(output: &str) -> Vec<String> {
+  let lines: Vec<&str> = output.lines().collect();
+  lines
+    .chunks(2)
+    .filter(|pair| pair.len() == 2 && pair[0] != "??")
+    .map(|pair| format!("    {} at {}", demangle_symbols(pair[0]), pair[1]))
+    .collect()
+}
+
+/// Filters the logcat of the app for the `--rust` mode of `run`: panics split over several lines
+/// are printed as one block, Rust symbols are demangled and frames of libmain.so in crash dumps
+/// are resolved against the local symbols.
+pub struct RustLogFilter {
+  pid: u32,
+  panic: Vec<String>,
+  in_crash_dump: bool,
+  symbolizer: Option<Symbolizer>,
+}
+
+impl RustLogFilter {
+  pub fn new(pid: u32, symbolizer: Option<Symbolizer>) -> Self {
+    Self {
+      pid,
+      panic: vec![],
+      in_crash_dump: false,
+      symbolizer,
+    }
+  }
+
+  /// Processes a line of `logcat -v brief`, returning the lines to print.
+  pub fn process(&mut self, line: &str) -> Vec<String> {
+    let Some(line) = parse_logcat_line(line) else {
+      return vec![];
+    };
+
+    if line.tag == CRASH_DUMP_TAG {
+      if line.message.contains(&format!("pid: {},", self.pid)) {
+        self.in_crash_dump = true;
+      }
+      if !self.in_crash_dump {
+        return vec![];
+      }
+      let mut output = self.finish();
+      output.push(format!(
+        "{}/{}: {}",
+        line.priority,
+        line.tag,
+        demangle_symbols(line.message)
+      ));
+      if let Some(frame) = parse_native_frame(line.message) {
+        if let Some(symbolizer) = &mut self.symbolizer {
+          output.extend(symbolizer.resolve(&frame));
+        }
+      }
+      return output;
+    }
+    if line.pid != self.pid {
+      return vec![];
+    }
+
+    let message = demangle_symbols(line.message);
+    if line.tag == RUST_TAG {
+      if message.starts_with("thread '") && message.contains("panicked at") {
+        let output = self.finish();
+        self.panic.push(message);
+        return output;
+      }
+      if !self.panic.is_empty() {
+        let end = message.starts_with("note: run with `RUST_BACKTRACE");
+        self.panic.push(message);
+        return if end { self.finish() } else { vec![] };
+      }
+    }
+    let mut output = self.finish();
+    output.push(format!("{}/{}: {}", line.priority, line.tag, message));
+    output
+  }
+
+  /// The panic collected so far as one block.
+  pub fn finish(&mut self) -> Vec<String> {
+    if self.panic.is_empty() {
+      return vec![];
+    }
+    let mut block = vec![format!("Rust panic: {}", self.panic[0])];
+    block.extend(self.panic[1..].iter().map(|line| format!("  {}", line)));
+    self.panic.clear();
+    block
+  }
+}
+
+/// Streams the logcat of the process through the filter until logcat exits.
+pub fn stream_rust_logcat(logcat: &mut Command, filter: &mut RustLogFilter) -> ExitStatus {
+  let mut child = logcat
+    .args(["-v", "brief"])
+    .stdin(Stdio::null())
+    .stdout(Stdio::piped())
+    .spawn()
+    .unwrap_or_else(|e| panic!("Failed to execute command: adb logcat: {}", e));
+  let stdout = child.stdout.take().unwrap();
+  for line in BufReader::new(stdout).lines() {
+    let Ok(line) = line else { break };
+    for output in filter.process(&line) {
+      println!("{}", output);
+    }
+  }
+  for output in filter.finish() {
+    println!("{}", output);
+  }
+  child
+    .wait()
+    .unwrap_or_else(|e| panic!("Failed to wait for adb logcat: {}", e))
+}
+
+#[cfg(test)]
+mod test {
+  use crate::rust_logcat::{
+    demangle_legacy, demangle_symbols, format_addr2line_output, parse_logcat_line,
+    parse_native_frame, parse_readelf_build_id, RustLogFilter,
+  };
+
+  #[test]
+  fn legacy_demangling() {
+    assert_eq!(
+      demangle_legacy("_ZN4core9panicking5panic17h0123456789abcdefE"),
+      Some(("core::panicking::panic".to_string(), 44))
+    );
+    assert_eq!(
+      demangle_symbols(
+        "at _ZN70_$LT$alloc..vec..Vec$LT$T$C$A$GT$$u20$as$u20$core..ops..drop..Drop$GT$4drop17h1f2e3d4c5b6a7980E+12"
+      ),
+      "at <alloc::vec::Vec<T,A> as core::ops::drop::Drop>::drop+12"
+    );
+    assert_eq!(
+      demangle_symbols("_ZN4game4main17h0123456789abcdefE.llvm.42"),
+      "game::main.llvm.42"
+    );
+    assert_eq!(demangle_symbols("no _ZN symbols"), "no _ZN symbols");
+    assert_eq!(demangle_legacy("_ZN3fooE"), Some(("foo".to_string(), 8)));
+  }
+
+  #[test]
+  fn logcat_lines() {
+    let line =
+      parse_logcat_line("E/RustStdoutStderr( 4242): thread 'main' panicked at src/lib.rs:10:5:")
+        .unwrap();
+    assert_eq!(line.tag, "RustStdoutStderr");
+    assert_eq!(line.pid, 4242);
+    assert_eq!(line.message, "thread 'main' panicked at src/lib.rs:10:5:");
+    assert_eq!(parse_logcat_line("--------- beginning of main"), None);
+
+    let frame = parse_native_frame(
+      "      #01 pc 00000000000a1b2c  /data/app/~~Yx==/com.example.game-Q==/lib/arm64/libmain.so (BuildId: 4f3a9b)",
+    )
+    .unwrap();
+    assert_eq!(frame.pc, "00000000000a1b2c");
+    assert_eq!(frame.abi, Some("arm64-v8a"));
+    assert_eq!(frame.build_id.as_deref(), Some("4f3a9b"));
+    assert_eq!(
+      parse_native_frame("#00 pc 000000000004f6d0  /apex/com.android.runtime/lib64/bionic/libc.so"),
+      None
+    );
+
+    assert_eq!(
+      parse_readelf_build_id(
+        "Displaying notes found in: .note.gnu.build-id\n  Owner  Data size  Description\n  GNU  0x00000014  NT_GNU_BUILD_ID (unique build ID bitstring)\n    Build ID: 4f3a9b\n"
+      )
+      .as_deref(),
+      Some("4f3a9b")
+    );
+    assert_eq!(
+      format_addr2line_output(
+        "game::update\n/src/game/src/lib.rs:42:9\ngame::main\n/src/game/src/lib.rs:10:5\n"
+      ),
+      vec![
+        "    game::update at /src/game/src/lib.rs:42:9",
+        "    game::main at /src/game/src/lib.rs:10:5"
+      ]
+    );
+  }
+
+  #[test]
+  fn panic_reassembly() {
+    let mut filter = RustLogFilter::new(4242, None);
+    let mut output = vec![];
+    for line in [
+      "I/RustStdoutStderr( 4242): loading level 3",
+      "I/OtherApp( 1000): unrelated",
+      "I/RustStdoutStderr( 4242): thread 'main' panicked at src/lib.rs:10:5:",
+      "I/RustStdoutStderr( 4242): index out of bounds: the len is 3 but the index is 7",
+      "I/RustStdoutStderr( 4242): note: run with `RUST_BACKTRACE=1` environment variable to display a backtrace",
+      "F/libc    ( 4242): Fatal signal 6 (SIGABRT), code -1 (SI_QUEUE) in tid 4260 (SDLThread), pid 4242 (example.game)",
+      "F/DEBUG   ( 4300): pid: 1000, tid: 1000, name: other  >>> com.other <<<",
+      "F/DEBUG   ( 4301): pid: 4242, tid: 4260, name: SDLThread  >>> com.example.game <<<",
+      "F/DEBUG   ( 4301):       #05 pc 00000000000a1b2c  /data/app/com.example.game/lib/arm64/libmain.so (_ZN4game4main17h0123456789abcdefE+44) (BuildId: 4f3a9b)",
+    ] {
+      output.extend(filter.process(line));
+    }
+    output.extend(filter.finish());
+    assert_eq!(
+      output,
+      vec![
+        "I/RustStdoutStderr: loading level 3",
+        "Rust panic: thread 'main' panicked at src/lib.rs:10:5:",
+        "  index out of bounds: the len is 3 but the index is 7",
+        "  note: run with `RUST_BACKTRACE=1` environment variable to display a backtrace",
+        "F/libc: Fatal signal 6 (SIGABRT), code -1 (SI_QUEUE) in tid 4260 (SDLThread), pid 4242 (example.game)",
+        "F/DEBUG: pid: 4242, tid: 4260, name: SDLThread  >>> com.example.game <<<",
+        "F/DEBUG:       #05 pc 00000000000a1b2c  /data/app/com.example.game/lib/arm64/libmain.so (game::main+44) (BuildId: 4f3a9b)",
+      ]
+    );
+  }
+}
@@ -0,0 +1,174 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use cargo::core::compiler::{CompileKind, CompileTarget, RustcTargetData};
+use cargo::core::Workspace;
+use toml::value::Value;
+
+use crate::android_project::*;
+use crate::arm_mode::*;
+use crate::build_bin_as_lib::*;
+use crate::project_template::*;
+use crate::split_debuginfo::*;
+use crate::util::*;
+
+/// Separator of the flags in `CARGO_ENCODED_RUSTFLAGS`, which unlike `RUSTFLAGS` allows flags
+/// containing spaces.
+const ENCODED_RUSTFLAGS_SEPARATOR: &str = "\x1f";
+
+pub fn encode_rustflags(flags: &[String]) -> String {
+  flags.join(ENCODED_RUSTFLAGS_SEPARATOR)
+}
+
+fn parse_rustflags_list(value: &Value) -> Result<Vec<String>, String> {
+  match value {
+    Value::String(flags) => Ok(flags.split_whitespace().map(|f| f.to_string()).collect()),
+    Value::Array(flags) => flags
+      .iter()
+      .map(|f| {
+        f.as_str()
+          .map(|f| f.to_string())
+          .ok_or_else(|| format!("rustflags must be strings, found: {}", f))
+      })
+      .collect(),
+    _ => Err(format!(
+      "rustflags must be a string or an array, found: {}",
+      value
+    )),
+  }
+}
+
+/// Parses the `rustflags` android metadata for the given target. It is either a string or an
+/// array applied to all targets, or a table of those keyed by target triple.
+pub fn parse_metadata_rustflags(
+  value: &Value,
+  rust_target_name: &str,
+) -> Result<Vec<String>, String> {
+  match value {
+    Value::Table(targets) => targets
+      .get(rust_target_name)
+      .map(parse_rustflags_list)
+      .unwrap_or(Ok(vec![])),
+    _ => parse_rustflags_list(value),
+  }
+}
+
+pub fn get_metadata_rustflags(
+  manifest_path: &Path,
+  rust_target_name: &str,
+) -> Result<Vec<String>, String> {
+  match get_toml_entry(
+    manifest_path,
+    ["package", "metadata", "android", "rustflags"],
+  )? {
+    Some(value) => parse_metadata_rustflags(&value, rust_target_name),
+    None => Ok(vec![]),
+  }
+}
+
+/// Determines the rustflags cargo would use on its own for each of the targets, i.e. from
+/// `CARGO_ENCODED_RUSTFLAGS`, `RUSTFLAGS`, `target.<triple>.rustflags` or `build.rustflags`.
+fn get_inherited_rustflags(manifest_path: &Path, targets: &[&str]) -> HashMap<String, Vec<String>> {
+  let cargo_config = create_cargo_config(None, &[]);
+  let workspace = Workspace::new(manifest_path, &cargo_config).unwrap();
+  let kinds: Vec<CompileKind> = targets
+    .iter()
+    .map(|t| CompileKind::Target(CompileTarget::new(t).unwrap()))
+    .collect();
+  let target_data = RustcTargetData::new(&workspace, &kinds).unwrap();
+
+  targets
+    .iter()
+    .zip(kinds)
+    .map(|(t, kind)| (t.to_string(), target_data.info(kind).rustflags.clone()))
+    .collect()
+}
+
+/// Flags this tool needs for building the given target.
+fn get_tool_rustflags(manifest_path: &Path, rust_target_name: &str) -> Result<Vec<String>, String> {
+  let mut rustflags = vec![];
+  if get_activity_kind(manifest_path)? == ActivityKind::Sdl {
+    let staging_dir = get_sdl_staging_dir(manifest_path.parent().unwrap(), rust_target_name);
+    rustflags.push("-L".to_string());
+    rustflags.push(format!("native={}", staging_dir.display()));
+  }
+  if rust_target_name == ARM_MODE_TARGET {
+    if let Ok(Some(mode)) = get_arm_mode(manifest_path) {
+      rustflags.extend(mode.rustflags());
+    }
+  }
+  if let Ok(split_debuginfo) = get_split_debuginfo(manifest_path) {
+    rustflags.extend(split_debuginfo.rustflags());
+  }
+  Ok(rustflags)
+}
+
+/// Composes the rustflags for building each of the targets. Flags from the environment and cargo
+/// config come first, followed by the ones this tool needs and the ones from the package metadata,
+/// so all of them are added to rather than replace each other.
+pub fn get_target_rustflags(
+  manifest_path: &Path,
+  targets: &[&str],
+) -> Result<Vec<(String, Vec<String>)>, String> {
+  let mut inherited = get_inherited_rustflags(manifest_path, targets);
+  targets
+    .iter()
+    .map(|t| {
+      let mut rustflags = inherited.remove(*t).unwrap_or_default();
+      rustflags.extend(get_tool_rustflags(manifest_path, t)?);
+      rustflags.extend(get_metadata_rustflags(manifest_path, t)?);
+      Ok((t.to_string(), rustflags))
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod test {
+  use toml::Table;
+
+  use crate::rustflags::{encode_rustflags, parse_metadata_rustflags};
+
+  fn metadata(content: &str) -> toml::Value {
+    content.parse::<Table>().unwrap()["rustflags"].clone()
+  }
+
+  #[test]
+  fn rustflags_for_all_targets() {
+    let value = metadata("rustflags = \"-C opt-level=s  -C debuginfo=1\"");
+    assert_eq!(
+      parse_metadata_rustflags(&value, "aarch64-linux-android").unwrap(),
+      vec!["-C", "opt-level=s", "-C", "debuginfo=1"]
+    );
+    assert!(parse_metadata_rustflags(&metadata("rustflags = [\"-C\", 1]"), "").is_err());
+    assert!(parse_metadata_rustflags(&metadata("rustflags = 1"), "").is_err());
+  }
+
+  #[test]
+  fn rustflags_per_target() {
+    let value = metadata(
+      r#"
+[rustflags]
+armv7-linux-androideabi = "-C target-feature=+neon"
+x86_64-linux-android = ["-C", "link-arg=-Wl,-soname,with space"]
+"#,
+    );
+    assert_eq!(
+      parse_metadata_rustflags(&value, "armv7-linux-androideabi").unwrap(),
+      vec!["-C", "target-feature=+neon"]
+    );
+    assert_eq!(
+      parse_metadata_rustflags(&value, "x86_64-linux-android").unwrap(),
+      vec!["-C", "link-arg=-Wl,-soname,with space"]
+    );
+    assert_eq!(
+      parse_metadata_rustflags(&value, "aarch64-linux-android"),
+      Ok(vec![])
+    );
+  }
+
+  #[test]
+  fn encoded_rustflags_keep_spaces() {
+    let flags = vec!["-C".to_string(), "link-arg=a b".to_string()];
+    assert_eq!(encode_rustflags(&flags), "-C\x1flink-arg=a b");
+  }
+}
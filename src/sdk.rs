@@ -0,0 +1,339 @@
+use std::cmp::Ordering;
+use std::ffi::OsString;
+use std::fs::read_dir;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::command_runner::*;
+use crate::offline::*;
+use crate::project_template::*;
+use crate::template_compat::*;
+use crate::util::*;
+
+lazy_static! {
+  static ref COMPILE_SDK_REGEX: Regex =
+    Regex::new(r"compileSdk(?:Version)?\s*=?\s*(\d+)").expect("invalid compile sdk regex");
+  static ref MIN_SDK_REGEX: Regex =
+    Regex::new(r"(minSdk(?:Version)?\s*=?\s*)(\d+)").expect("invalid min sdk regex");
+  static ref TARGET_SDK_REGEX: Regex =
+    Regex::new(r"targetSdk(?:Version)?\s*=?\s*(\d+)").expect("invalid target sdk regex");
+}
+
+/// Compares version strings like `33.0.2` component wise, treating non numeric components as 0.
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+  let parse = |v: &str| -> Vec<u64> { v.split('.').map(|c| c.parse().unwrap_or(0)).collect() };
+  parse(a).cmp(&parse(b))
+}
+
+/// Returns the names of the sub directories of the given directory, or an empty list if it
+/// doesn't exist.
+fn list_dir_names(dir: &Path) -> Vec<String> {
+  match read_dir(dir) {
+    Ok(entries) => entries
+      .filter_map(|e| e.ok())
+      .filter(|e| e.path().is_dir())
+      .filter_map(|e| e.file_name().into_string().ok())
+      .collect(),
+    Err(_) => vec![],
+  }
+}
+
+/// Returns the highest installed build-tools version.
+pub fn find_build_tools_version(android_home: &Path) -> Option<String> {
+  list_dir_names(&android_home.join("build-tools"))
+    .into_iter()
+    .max_by(|a, b| compare_versions(a, b))
+}
+
+/// Locates sdkmanager, preferring `cmdline-tools/latest` over versioned cmdline-tools directories
+/// (highest version first) and falling back to the legacy `tools` directory.
+pub fn find_sdkmanager(android_home: &Path) -> Option<PathBuf> {
+  let file_name = if cfg!(windows) {
+    "sdkmanager.bat"
+  } else {
+    "sdkmanager"
+  };
+
+  let cmdline_tools = android_home.join("cmdline-tools");
+  let mut versions = list_dir_names(&cmdline_tools);
+  versions.retain(|v| v != "latest");
+  versions.sort_by(|a, b| compare_versions(b, a));
+
+  let mut candidates = vec![cmdline_tools.join("latest")];
+  candidates.extend(versions.into_iter().map(|v| cmdline_tools.join(v)));
+  candidates.push(android_home.join("tools"));
+
+  candidates
+    .into_iter()
+    .map(|dir| dir.join("bin").join(file_name))
+    .find(|p| p.is_file())
+}
+
+/// Extracts the compile sdk version from the content of a build.gradle file.
+pub fn parse_compile_sdk_version(build_gradle: &str) -> Option<u32> {
+  COMPILE_SDK_REGEX
+    .captures(build_gradle)
+    .and_then(|c| c[1].parse().ok())
+}
+
+/// Reads the compile sdk version the android project template was made for.
+pub fn get_template_compile_sdk_version(template: &ProjectTemplate) -> Option<u32> {
+  template
+    .read_gradle("app/build.gradle")
+    .and_then(|content| parse_compile_sdk_version(&content))
+}
+
+/// Extracts the min sdk version from the content of a build.gradle file.
+pub fn parse_min_sdk_version(build_gradle: &str) -> Option<u32> {
+  MIN_SDK_REGEX
+    .captures(build_gradle)
+    .and_then(|c| c[2].parse().ok())
+}
+
+/// Extracts the target sdk version from the content of a build.gradle file.
+pub fn parse_target_sdk_version(build_gradle: &str) -> Option<u32> {
+  TARGET_SDK_REGEX
+    .captures(build_gradle)
+    .and_then(|c| c[1].parse().ok())
+}
+
+/// Replaces the min sdk version in the content of a build.gradle file.
+pub fn replace_min_sdk_version(build_gradle: &str, min_sdk_version: u32) -> String {
+  MIN_SDK_REGEX
+    .replace(build_gradle, |c: &regex::Captures| {
+      format!("{}{}", &c[1], min_sdk_version)
+    })
+    .to_string()
+}
+
+/// Reads the min sdk version of the android project template.
+pub fn get_template_min_sdk_version(template: &ProjectTemplate) -> Option<u32> {
+  template
+    .read_gradle("app/build.gradle")
+    .and_then(|content| parse_min_sdk_version(&content))
+}
+
+/// Reads the target sdk version of the android project template.
+pub fn get_template_target_sdk_version(template: &ProjectTemplate) -> Option<u32> {
+  template
+    .read_gradle("app/build.gradle")
+    .and_then(|content| parse_target_sdk_version(&content))
+}
+
+/// Reads `min_sdk_version` of the android metadata, which overrides the one of the template.
+pub fn get_metadata_min_sdk_version(manifest_path: &Path) -> Result<Option<u32>, String> {
+  get_toml_integer(
+    manifest_path,
+    ["package", "metadata", "android", "min_sdk_version"],
+  )?
+  .map(|v| u32::try_from(v).map_err(|_| format!("invalid min_sdk_version: {}", v)))
+  .transpose()
+}
+
+/// The min sdk version of the app, from the android metadata or the project template.
+pub fn get_min_sdk_version(manifest_path: &Path) -> Result<Option<u32>, String> {
+  match get_metadata_min_sdk_version(manifest_path)? {
+    Some(min_sdk_version) => Ok(Some(min_sdk_version)),
+    None => Ok(get_template_min_sdk_version(
+      &ProjectTemplate::for_manifest(manifest_path)?,
+    )),
+  }
+}
+
+/// Determines the sdk packages the build needs but which aren't installed.
+pub fn find_missing_sdk_packages(android_home: &Path, compile_sdk: u32) -> Vec<String> {
+  let mut missing = vec![];
+
+  if find_build_tools_version(android_home).is_none() {
+    missing.push(format!("build-tools;{}.0.0", compile_sdk));
+  }
+
+  let platform = format!("android-{}", compile_sdk);
+  if !android_home.join("platforms").join(&platform).is_dir() {
+    missing.push(format!("platforms;{}", platform));
+  }
+
+  missing
+}
+
+fn format_sdkmanager_command(sdkmanager: &Path, packages: &[String]) -> String {
+  let mut command = format!("{}", sdkmanager.display());
+  for package in packages {
+    command.push_str(&format!(" \"{}\"", package));
+  }
+  command
+}
+
+/// Makes sure the build-tools and the platform required by the android project template are
+/// installed. If packages are missing they are installed with sdkmanager when requested (by
+/// `--install-sdk-packages` or `auto_install_sdk = true`), otherwise the command to install them
+/// is returned as error.
+pub fn ensure_sdk_packages(
+  manifest_path: &Path,
+  install: bool,
+  accept_licenses: bool,
+) -> Result<(), String> {
+  let android_home = get_env_path("ANDROID_HOME");
+
+  let compile_sdk = match get_template_compile_sdk_version(&ProjectTemplate::for_manifest(
+    manifest_path,
+  )?) {
+    Some(v) => v,
+    None => {
+      eprintln!("Unable to determine compile sdk version of the android project template, skipping sdk package check");
+      return Ok(());
+    }
+  };
+
+  let missing = find_missing_sdk_packages(&android_home, compile_sdk);
+  if missing.is_empty() {
+    return Ok(());
+  }
+
+  let install = install
+    || get_toml_bool(
+      manifest_path,
+      ["package", "metadata", "android", "auto_install_sdk"],
+    )?
+    .unwrap_or(false);
+
+  let sdkmanager = find_sdkmanager(&android_home);
+  let printable_sdkmanager = sdkmanager
+    .clone()
+    .unwrap_or(android_home.join("cmdline-tools/latest/bin/sdkmanager"));
+
+  if !install {
+    return Err(format!(
+      "Missing sdk packages: {}, needed by {}. Install them with:\n  {}\nor rerun with --install-sdk-packages",
+      missing.join(", "),
+      get_template_info(manifest_path)?,
+      format_sdkmanager_command(&printable_sdkmanager, &missing)
+    ));
+  }
+
+  if is_offline() {
+    return Err(format!(
+      "Can't install {} with --offline, install them with network access first",
+      missing.join(", ")
+    ));
+  }
+
+  let sdkmanager = sdkmanager.ok_or_else(|| {
+    format!(
+      "Can't install {}: sdkmanager not found, install the Android command line tools to {:?}",
+      missing.join(", "),
+      printable_sdkmanager
+    )
+  })?;
+
+  println!("Installing sdk packages: {}", missing.join(", "));
+  let mut command = Command::new(&sdkmanager);
+  let mut sdk_root = OsString::from("--sdk_root=");
+  sdk_root.push(&android_home);
+  command.arg(sdk_root).args(&missing);
+
+  let status = if accept_licenses {
+    run_command_with_stdin(&mut command, |mut stdin| {
+      // sdkmanager asks once per license, stop as soon as it closes stdin
+      while stdin.write_all(b"y\n").is_ok() {}
+    })
+  } else {
+    run_command(&mut command)
+  };
+  if !status.success() {
+    return Err(format!("Failed to install sdk packages ({})", status));
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod test {
+  use std::cmp::Ordering;
+  use std::fs::{create_dir_all, write};
+
+  use tempfile::tempdir;
+
+  use crate::sdk::{
+    compare_versions, find_build_tools_version, find_missing_sdk_packages, find_sdkmanager,
+    parse_compile_sdk_version, parse_min_sdk_version, replace_min_sdk_version,
+  };
+
+  #[test]
+  fn version_comparison() {
+    assert_eq!(compare_versions("33.0.2", "9.0.0"), Ordering::Greater);
+    assert_eq!(compare_versions("30.0.3", "30.0.10"), Ordering::Less);
+    assert_eq!(compare_versions("11.0", "11.0"), Ordering::Equal);
+  }
+
+  #[test]
+  fn compile_sdk_from_template() {
+    assert_eq!(
+      parse_compile_sdk_version("android {\n    compileSdkVersion 31\n"),
+      Some(31)
+    );
+    assert_eq!(
+      parse_compile_sdk_version("android {\n    compileSdk = 34\n"),
+      Some(34)
+    );
+    assert_eq!(parse_compile_sdk_version("android {\n}\n"), None);
+  }
+
+  #[test]
+  fn min_sdk_from_template() {
+    assert_eq!(
+      parse_min_sdk_version("    defaultConfig {\n        minSdkVersion 16\n"),
+      Some(16)
+    );
+    assert_eq!(parse_min_sdk_version("        minSdk = 26\n"), Some(26));
+    assert_eq!(
+      replace_min_sdk_version("        minSdk = 26\n        targetSdk 31\n", 29),
+      "        minSdk = 29\n        targetSdk 31\n"
+    );
+  }
+
+  #[test]
+  fn sdkmanager_location() {
+    let sdk = tempdir().unwrap();
+    assert_eq!(find_sdkmanager(sdk.path()), None);
+
+    for version in ["9.0", "11.0"] {
+      let bin = sdk.path().join("cmdline-tools").join(version).join("bin");
+      create_dir_all(&bin).unwrap();
+      write(bin.join("sdkmanager"), "").unwrap();
+    }
+    assert_eq!(
+      find_sdkmanager(sdk.path()).unwrap(),
+      sdk.path().join("cmdline-tools/11.0/bin/sdkmanager")
+    );
+
+    let latest = sdk.path().join("cmdline-tools/latest/bin");
+    create_dir_all(&latest).unwrap();
+    write(latest.join("sdkmanager"), "").unwrap();
+    assert_eq!(
+      find_sdkmanager(sdk.path()).unwrap(),
+      latest.join("sdkmanager")
+    );
+  }
+
+  #[test]
+  fn missing_packages() {
+    let sdk = tempdir().unwrap();
+    assert_eq!(
+      find_missing_sdk_packages(sdk.path(), 31),
+      vec!["build-tools;31.0.0", "platforms;android-31"]
+    );
+
+    create_dir_all(sdk.path().join("build-tools/9.0.0")).unwrap();
+    create_dir_all(sdk.path().join("build-tools/33.0.2")).unwrap();
+    create_dir_all(sdk.path().join("platforms/android-31")).unwrap();
+    assert!(find_missing_sdk_packages(sdk.path(), 31).is_empty());
+    assert_eq!(
+      find_build_tools_version(sdk.path()),
+      Some("33.0.2".to_string())
+    );
+  }
+}
@@ -0,0 +1,630 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::env;
+use std::fs::{
+  copy, create_dir_all, read, read_dir, read_to_string, remove_dir_all, rename, write,
+};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+use toml::value::Value;
+
+use crate::preflight::*;
+use crate::sdl_fetch::*;
+use crate::util::*;
+
+/// Env var overriding the directory SDL builds are cached in, e.g. for a cache mount on CI.
+pub const SDL_CACHE_DIR_ENV: &str = "CARGO_SDL_APK_SDL_CACHE";
+
+/// Platform SDL is built for, passed to ndk-build as `APP_PLATFORM`.
+pub const SDL_APP_PLATFORM: &str = "android-19";
+
+/// File inside a cache entry describing the inputs it was built from.
+const CACHE_KEY_FILE: &str = "key.txt";
+
+/// Entries of the SDL dir which aren't part of the source: git's and the outputs of ndk-build.
+const NON_SOURCE_ENTRIES: &[&str] = &[".git", "libs", "obj"];
+
+/// Directory the SDL builds of all projects are cached in, by default
+/// `~/.cache/cargo-sdl-apk/sdl-builds`.
+pub fn get_sdl_cache_dir() -> Option<PathBuf> {
+  if let Some(dir) = env::var_os(SDL_CACHE_DIR_ENV) {
+    return Some(PathBuf::from(dir));
+  }
+  env::var_os("XDG_CACHE_HOME")
+    .map(PathBuf::from)
+    .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))
+    .map(|cache| cache.join("cargo-sdl-apk/sdl-builds"))
+}
+
+/// Parses `Pkg.Revision` of the source.properties of the NDK, e.g. `25.2.9519653`.
+pub fn parse_ndk_revision(source_properties: &str) -> Option<String> {
+  source_properties.lines().find_map(|line| {
+    let (key, value) = line.split_once('=')?;
+    (key.trim() == "Pkg.Revision").then(|| value.trim().to_string())
+  })
+}
+
+/// How ndk-build builds SDL, from `sdl_build` of the android metadata, e.g. for a patched SDL with
+/// its own build script.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SdlBuildConfig {
+  /// `APP_BUILD_SCRIPT`, relative to the SDL dir. `./Android.mk` if not set.
+  pub build_script: Option<String>,
+  /// Additional ndk-build arguments, like variables `NAME=value`.
+  pub args: Vec<String>,
+  /// Environment variables of ndk-build.
+  pub env: BTreeMap<String, String>,
+}
+
+impl SdlBuildConfig {
+  /// The arguments of ndk-build. The additional ones come last, so they can override variables.
+  pub fn ndk_build_args(&self) -> Vec<String> {
+    let mut args = vec![
+      "NDK_PROJECT_PATH=.".to_string(),
+      format!(
+        "APP_BUILD_SCRIPT={}",
+        self.build_script.as_deref().unwrap_or("./Android.mk")
+      ),
+      format!("APP_PLATFORM={}", SDL_APP_PLATFORM),
+    ];
+    args.extend(self.args.iter().cloned());
+    args
+  }
+}
+
+/// Parses `sdl_build` of the android metadata.
+pub fn parse_sdl_build_config(value: &Value) -> Result<SdlBuildConfig, String> {
+  let table = value
+    .as_table()
+    .ok_or("sdl_build must be a table with build_script, args and env")?;
+  let build_script = match table.get("build_script") {
+    None => None,
+    Some(Value::String(script)) => Some(script.clone()),
+    Some(_) => return Err("sdl_build.build_script must be a string".to_string()),
+  };
+  let args = match table.get("args") {
+    None => vec![],
+    Some(args) => args
+      .as_array()
+      .and_then(|args| {
+        args
+          .iter()
+          .map(|a| a.as_str().map(|a| a.to_string()))
+          .collect::<Option<Vec<String>>>()
+      })
+      .ok_or("sdl_build.args must be a list of strings")?,
+  };
+  let env = match table.get("env") {
+    None => BTreeMap::new(),
+    Some(env) => env
+      .as_table()
+      .and_then(|env| {
+        env
+          .iter()
+          .map(|(k, v)| v.as_str().map(|v| (k.clone(), v.to_string())))
+          .collect::<Option<BTreeMap<String, String>>>()
+      })
+      .ok_or("sdl_build.env must be a table of strings")?,
+  };
+  Ok(SdlBuildConfig {
+    build_script,
+    args,
+    env,
+  })
+}
+
+/// Reads `sdl_build` of the android metadata.
+pub fn get_sdl_build_config(manifest_path: &Path) -> Result<SdlBuildConfig, String> {
+  match get_toml_entry(
+    manifest_path,
+    ["package", "metadata", "android", "sdl_build"],
+  )? {
+    Some(value) => parse_sdl_build_config(&value),
+    None => Ok(SdlBuildConfig::default()),
+  }
+}
+
+fn hash_source_dir(hasher: &mut Sha256, sdl_dir: &Path, dir: &Path) -> io::Result<()> {
+  let mut entries = read_dir(dir)?.collect::<io::Result<Vec<_>>>()?;
+  entries.sort_by_key(|entry| entry.file_name());
+  for entry in entries {
+    let path = entry.path();
+    if dir == sdl_dir
+      && NON_SOURCE_ENTRIES
+        .iter()
+        .any(|name| entry.file_name() == *name)
+    {
+      continue;
+    }
+    //symlinks aren't followed, they may point back into a project
+    let file_type = entry.file_type()?;
+    if file_type.is_dir() {
+      hash_source_dir(hasher, sdl_dir, &path)?;
+    } else if file_type.is_file() {
+      let relative = path.strip_prefix(sdl_dir).unwrap_or(&path);
+      hasher.update(relative.to_string_lossy().as_bytes());
+      let content = read(&path)?;
+      hasher.update((content.len() as u64).to_le_bytes());
+      hasher.update(content);
+    }
+  }
+  Ok(())
+}
+
+/// Hashes the paths and contents of the files of the SDL source, so a patched SDL or a snapshot
+/// between releases doesn't share the builds of the version it reports.
+pub fn fingerprint_sdl_source(sdl_dir: &Path) -> io::Result<String> {
+  let mut hasher = Sha256::new();
+  hash_source_dir(&mut hasher, sdl_dir, sdl_dir)?;
+  Ok(format!("{:x}", hasher.finalize())[..16].to_string())
+}
+
+/// The inputs the SDL build output depends on, besides the ABI which is a directory inside the
+/// cache entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SdlBuildKey {
+  pub sdl_version: SdlVersion,
+  /// The [fingerprint_sdl_source] of the SDL dir.
+  pub source: String,
+  pub ndk_revision: String,
+  pub platform: String,
+  pub build_config: SdlBuildConfig,
+  /// Hash of the contents of `build_script` of the build config, which may be outside the SDL dir.
+  pub build_script_hash: Option<String>,
+}
+
+impl SdlBuildKey {
+  /// Reads the key of the SDL source and the NDK. Returns `None` if a version can't be
+  /// determined or the source can't be read, in which case the build isn't cached.
+  pub fn read(sdl_dir: &Path, ndk_dir: &Path, build_config: &SdlBuildConfig) -> Option<Self> {
+    Some(Self {
+      sdl_version: read_sdl_source_version(sdl_dir)?,
+      source: fingerprint_sdl_source(sdl_dir).ok()?,
+      ndk_revision: read_to_string(ndk_dir.join("source.properties"))
+        .ok()
+        .and_then(|content| parse_ndk_revision(&content))?,
+      platform: SDL_APP_PLATFORM.to_string(),
+      build_config: build_config.clone(),
+      build_script_hash: match &build_config.build_script {
+        Some(build_script) => {
+          let content = read(sdl_dir.join(build_script)).ok()?;
+          Some(format!("{:x}", Sha256::digest(content))[..16].to_string())
+        }
+        None => None,
+      },
+    })
+  }
+
+  /// The inputs as text. The build config is only included if set.
+  pub fn describe(&self) -> String {
+    let mut description = format!(
+      "sdl={}\nsource={}\nndk={}\nplatform={}\n",
+      self.sdl_version, self.source, self.ndk_revision, self.platform
+    );
+    let config = &self.build_config;
+    if let Some(build_script) = &config.build_script {
+      description.push_str(&format!("build_script={}\n", build_script));
+    }
+    if let Some(hash) = &self.build_script_hash {
+      description.push_str(&format!("build_script_content={}\n", hash));
+    }
+    for arg in &config.args {
+      description.push_str(&format!("arg={}\n", arg));
+    }
+    for (key, value) in &config.env {
+      description.push_str(&format!("env.{}={}\n", key, value));
+    }
+    description
+  }
+
+  /// Name of the cache entry.
+  pub fn hash(&self) -> String {
+    format!("{:x}", Sha256::digest(self.describe()))[..16].to_string()
+  }
+}
+
+/// The cached library of the ABI inside a cache entry.
+pub fn get_cached_sdl_lib(cache_entry: &Path, android_abi: &str) -> PathBuf {
+  cache_entry.join(android_abi).join("libSDL2.so")
+}
+
+/// Copies the libraries of all ABIs built by ndk-build into the cache entry. Files are renamed
+/// into place, so concurrent builds never see a partial library.
+pub fn store_sdl_build(cache_entry: &Path, key: &SdlBuildKey, libs_dir: &Path) -> io::Result<()> {
+  for abi_dir in read_dir(libs_dir)?.filter_map(|e| e.ok()).map(|e| e.path()) {
+    let lib = abi_dir.join("libSDL2.so");
+    let Some(abi) = abi_dir.file_name().and_then(|name| name.to_str()) else {
+      continue;
+    };
+    if !lib.is_file() {
+      continue;
+    }
+    let dest = get_cached_sdl_lib(cache_entry, abi);
+    create_dir_all(dest.parent().unwrap())?;
+    let tmp = dest.with_extension(format!("so.{}.tmp", std::process::id()));
+    copy(&lib, &tmp)?;
+    rename(&tmp, &dest)?;
+  }
+  write(cache_entry.join(CACHE_KEY_FILE), key.describe())
+}
+
+fn dir_size(dir: &Path) -> u64 {
+  read_dir(dir)
+    .map(|entries| {
+      entries
+        .filter_map(|e| e.ok())
+        .map(|e| match e.metadata() {
+          Ok(metadata) if metadata.is_dir() => dir_size(&e.path()),
+          Ok(metadata) => metadata.len(),
+          Err(_) => 0,
+        })
+        .sum()
+    })
+    .unwrap_or(0)
+}
+
+/// Prints the cache dir and the cached SDL builds.
+pub fn print_sdl_cache_info() {
+  let Some(cache_dir) = get_sdl_cache_dir() else {
+    eprintln!(
+      "Error: Unable to determine the cache dir, set {} or HOME.",
+      SDL_CACHE_DIR_ENV
+    );
+    std::process::exit(1);
+  };
+  println!("SDL build cache: {}", cache_dir.display());
+
+  let mut entries: Vec<PathBuf> = read_dir(&cache_dir)
+    .map(|entries| {
+      entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|path| path.is_dir())
+        .collect()
+    })
+    .unwrap_or_default();
+  entries.sort();
+  if entries.is_empty() {
+    println!("No cached builds.");
+    return;
+  }
+
+  for entry in &entries {
+    let key = read_to_string(entry.join(CACHE_KEY_FILE)).unwrap_or_default();
+    let mut abis: Vec<String> = read_dir(entry)
+      .map(|abis| {
+        abis
+          .filter_map(|e| e.ok())
+          .filter(|e| e.path().is_dir())
+          .map(|e| e.file_name().to_string_lossy().to_string())
+          .collect()
+      })
+      .unwrap_or_default();
+    abis.sort();
+    println!();
+    println!(
+      "{} ({:.1} MiB)",
+      entry.file_name().unwrap().to_string_lossy(),
+      dir_size(entry) as f64 / (1024.0 * 1024.0)
+    );
+    for line in key.lines() {
+      println!("  {}", line);
+    }
+    println!("  abis={}", abis.join(","));
+  }
+  println!();
+  println!(
+    "{} cached builds, {:.1} MiB total",
+    entries.len(),
+    dir_size(&cache_dir) as f64 / (1024.0 * 1024.0)
+  );
+}
+
+/// The SDL versions of the builds in the cache dir.
+pub fn get_cached_sdl_versions(cache_dir: &Path) -> BTreeSet<SdlVersion> {
+  read_dir(cache_dir)
+    .map(|entries| {
+      entries
+        .filter_map(|e| e.ok())
+        .filter_map(|e| read_to_string(e.path().join(CACHE_KEY_FILE)).ok())
+        .filter_map(|key| {
+          key
+            .lines()
+            .find_map(|line| line.strip_prefix("sdl="))?
+            .parse()
+            .ok()
+        })
+        .collect()
+    })
+    .unwrap_or_default()
+}
+
+/// Lists the SDL versions the sys crates expect, with the downloaded sources and the cached builds
+/// of SDL versions each of them accepts, newer sys crates taking precedence.
+pub fn format_sdl_versions(
+  downloaded: &BTreeSet<SdlVersion>,
+  cached: &BTreeSet<SdlVersion>,
+) -> String {
+  let mut list = String::new();
+  for (i, (crate_name, prefix, version)) in SDL_COMPATIBILITY_TABLE.iter().enumerate() {
+    let newer = SDL_COMPATIBILITY_TABLE[i + 1..]
+      .iter()
+      .find(|(name, _, _)| name == crate_name)
+      .map(|(_, _, newer)| *newer);
+    let accepted = |versions: &BTreeSet<SdlVersion>| -> Vec<String> {
+      versions
+        .iter()
+        .filter(|v| is_sdl_version_compatible(**v, *version))
+        .filter(|v| newer.is_none_or(|newer| **v < newer))
+        .map(|v| v.to_string())
+        .collect()
+    };
+    list.push_str(&format!(
+      "{} {}*: SDL {} or newer",
+      crate_name, prefix, version
+    ));
+    for (label, versions) in [
+      ("downloaded", accepted(downloaded)),
+      ("cached", accepted(cached)),
+    ] {
+      if !versions.is_empty() {
+        list.push_str(&format!(", {} {}", label, versions.join(", ")));
+      }
+    }
+    list.push('\n');
+  }
+  list
+}
+
+/// Prints the SDL versions the sys crates expect, which of them are downloaded and which have
+/// cached builds.
+pub fn print_sdl_versions() {
+  let downloaded = get_sdl_sources_dir()
+    .map(|dir| get_fetched_sdl_versions(&dir).into_iter().collect())
+    .unwrap_or_default();
+  let cached = get_sdl_cache_dir()
+    .map(|dir| get_cached_sdl_versions(&dir))
+    .unwrap_or_default();
+  print!("{}", format_sdl_versions(&downloaded, &cached));
+  let pinned = get_pinned_sdl_versions();
+  if !pinned.is_empty() {
+    println!(
+      "Pinned for download: {}",
+      pinned
+        .iter()
+        .map(|v| v.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+    );
+  }
+}
+
+/// Removes all cached SDL builds.
+pub fn clean_sdl_cache() {
+  let Some(cache_dir) = get_sdl_cache_dir() else {
+    return;
+  };
+  if cache_dir.is_dir() {
+    remove_dir_all(&cache_dir)
+      .unwrap_or_else(|e| panic!("Unable to remove {:?}: {}", cache_dir, e));
+    println!("Removed {}", cache_dir.display());
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::collections::BTreeSet;
+  use std::fs::{create_dir_all, write};
+
+  use tempfile::tempdir;
+
+  use crate::preflight::SdlVersion;
+  use crate::sdl_cache::{
+    fingerprint_sdl_source, format_sdl_versions, get_cached_sdl_lib, get_cached_sdl_versions,
+    parse_ndk_revision, parse_sdl_build_config, store_sdl_build, SdlBuildConfig, SdlBuildKey,
+  };
+
+  #[test]
+  fn ndk_revision() {
+    assert_eq!(
+      parse_ndk_revision("Pkg.Desc = Android NDK\nPkg.Revision = 25.2.9519653\n").as_deref(),
+      Some("25.2.9519653")
+    );
+    assert_eq!(parse_ndk_revision("Pkg.Desc = Android NDK\n"), None);
+  }
+
+  #[test]
+  fn cache_entries() {
+    let key = SdlBuildKey {
+      sdl_version: SdlVersion(2, 26, 4),
+      source: "0123456789abcdef".to_string(),
+      ndk_revision: "25.2.9519653".to_string(),
+      platform: "android-19".to_string(),
+      build_config: SdlBuildConfig::default(),
+      build_script_hash: None,
+    };
+    let other_ndk = SdlBuildKey {
+      ndk_revision: "26.1.10909125".to_string(),
+      ..key.clone()
+    };
+    assert_eq!(key.hash().len(), 16);
+    assert_ne!(key.hash(), other_ndk.hash());
+    assert_eq!(
+      key.describe(),
+      "sdl=2.26.4\nsource=0123456789abcdef\nndk=25.2.9519653\nplatform=android-19\n"
+    );
+
+    let dir = tempdir().unwrap();
+    let libs = dir.path().join("libs");
+    create_dir_all(libs.join("arm64-v8a")).unwrap();
+    write(libs.join("arm64-v8a/libSDL2.so"), "lib").unwrap();
+    //ABIs which failed to build are skipped
+    create_dir_all(libs.join("x86")).unwrap();
+
+    let entry = dir.path().join("cache").join(key.hash());
+    store_sdl_build(&entry, &key, &libs).unwrap();
+    assert!(get_cached_sdl_lib(&entry, "arm64-v8a").is_file());
+    assert!(!get_cached_sdl_lib(&entry, "x86").exists());
+    assert!(entry.join("key.txt").is_file());
+  }
+
+  #[test]
+  fn sdl_source_fingerprint() {
+    let dir = tempdir().unwrap();
+    let sdl_dir = dir.path();
+    create_dir_all(sdl_dir.join("include")).unwrap();
+    write(
+      sdl_dir.join("include/SDL_version.h"),
+      "#define SDL_MAJOR_VERSION 2\n#define SDL_MINOR_VERSION 26\n#define SDL_PATCHLEVEL 4\n",
+    )
+    .unwrap();
+    create_dir_all(sdl_dir.join("src/audio")).unwrap();
+    write(sdl_dir.join("src/audio/SDL_audio.c"), "release").unwrap();
+    let ndk_dir = dir.path().join("ndk");
+    create_dir_all(&ndk_dir).unwrap();
+    write(
+      ndk_dir.join("source.properties"),
+      "Pkg.Revision = 25.2.9519653\n",
+    )
+    .unwrap();
+    let key = || SdlBuildKey::read(sdl_dir, &ndk_dir, &SdlBuildConfig::default()).unwrap();
+    let release = key();
+
+    //the outputs of ndk-build and git aren't part of the source
+    create_dir_all(sdl_dir.join("libs/arm64-v8a")).unwrap();
+    write(sdl_dir.join("libs/arm64-v8a/libSDL2.so"), "lib").unwrap();
+    create_dir_all(sdl_dir.join(".git")).unwrap();
+    write(sdl_dir.join(".git/HEAD"), "ref: refs/heads/main").unwrap();
+    assert_eq!(release.hash(), key().hash());
+
+    //a patched source of the same version gets its own entry
+    write(sdl_dir.join("src/audio/SDL_audio.c"), "patched").unwrap();
+    let patched = key();
+    assert_eq!(release.sdl_version, patched.sdl_version);
+    assert_ne!(release.hash(), patched.hash());
+    write(sdl_dir.join("src/audio/SDL_custom.c"), "driver").unwrap();
+    assert_ne!(patched.hash(), key().hash());
+
+    assert!(fingerprint_sdl_source(&dir.path().join("missing")).is_err());
+  }
+
+  #[test]
+  fn sdl_build_config() {
+    let value = r#"
+[sdl_build]
+build_script = "jni/Android-custom.mk"
+args = ["MY_FLAG=1"]
+env = { FOO = "bar" }
+"#
+    .parse::<toml::Table>()
+    .unwrap()["sdl_build"]
+      .clone();
+    let config = parse_sdl_build_config(&value).unwrap();
+    assert_eq!(
+      config.ndk_build_args(),
+      vec![
+        "NDK_PROJECT_PATH=.",
+        "APP_BUILD_SCRIPT=jni/Android-custom.mk",
+        "APP_PLATFORM=android-19",
+        "MY_FLAG=1"
+      ]
+    );
+    assert_eq!(config.env["FOO"], "bar");
+    assert_eq!(
+      SdlBuildConfig::default().ndk_build_args()[1],
+      "APP_BUILD_SCRIPT=./Android.mk"
+    );
+
+    //changing any setting changes the cache entry
+    let key = |build_config: SdlBuildConfig| SdlBuildKey {
+      sdl_version: SdlVersion(2, 26, 4),
+      source: "0123456789abcdef".to_string(),
+      ndk_revision: "25.2.9519653".to_string(),
+      platform: "android-19".to_string(),
+      build_config,
+      build_script_hash: None,
+    };
+    let custom = key(config.clone()).hash();
+    assert_ne!(custom, key(SdlBuildConfig::default()).hash());
+    let mut other_env = config.clone();
+    other_env.env.insert("FOO".to_string(), "baz".to_string());
+    assert_ne!(custom, key(other_env).hash());
+    let mut other_args = config;
+    other_args.args.push("OTHER=1".to_string());
+    assert_ne!(custom, key(other_args).hash());
+
+    assert!(parse_sdl_build_config(&toml::Value::from("jni/Android.mk")).is_err());
+
+    //editing the build script, here outside of the SDL dir, changes the cache entry
+    let dir = tempdir().unwrap();
+    let sdl_dir = dir.path().join("SDL");
+    create_dir_all(sdl_dir.join("include")).unwrap();
+    write(
+      sdl_dir.join("include/SDL_version.h"),
+      "#define SDL_MAJOR_VERSION 2\n#define SDL_MINOR_VERSION 26\n#define SDL_PATCHLEVEL 4\n",
+    )
+    .unwrap();
+    let ndk_dir = dir.path().join("ndk");
+    create_dir_all(&ndk_dir).unwrap();
+    write(
+      ndk_dir.join("source.properties"),
+      "Pkg.Revision = 25.2.9519653\n",
+    )
+    .unwrap();
+    create_dir_all(dir.path().join("patches")).unwrap();
+    let build_script = dir.path().join("patches/Android-custom.mk");
+    write(
+      &build_script,
+      "LOCAL_CFLAGS += -DSDL_AUDIO_DRIVER_CUSTOM=1\n",
+    )
+    .unwrap();
+    let config = SdlBuildConfig {
+      build_script: Some("../patches/Android-custom.mk".to_string()),
+      ..SdlBuildConfig::default()
+    };
+    let read = || SdlBuildKey::read(&sdl_dir, &ndk_dir, &config).unwrap();
+    let before = read();
+    write(
+      &build_script,
+      "LOCAL_CFLAGS += -DSDL_AUDIO_DRIVER_CUSTOM=2\n",
+    )
+    .unwrap();
+    let after = read();
+    assert_eq!(before.build_config, after.build_config);
+    assert_ne!(before.hash(), after.hash());
+    //a missing build script isn't cached, ndk-build reports it
+    std::fs::remove_file(&build_script).unwrap();
+    assert!(SdlBuildKey::read(&sdl_dir, &ndk_dir, &config).is_none());
+  }
+
+  #[test]
+  fn sdl_versions() {
+    let dir = tempdir().unwrap();
+    for (entry, key) in [
+      ("a", "sdl=2.26.5\nndk=25.2.9519653\n"),
+      ("b", "sdl=2.30.2\nndk=25.2.9519653\n"),
+      ("c", "sdl=2.30.2\nndk=26.1.10909125\n"),
+      ("d", "ndk=26.1.10909125\n"),
+    ] {
+      create_dir_all(dir.path().join(entry)).unwrap();
+      write(dir.path().join(entry).join("key.txt"), key).unwrap();
+    }
+    let cached = get_cached_sdl_versions(dir.path());
+    assert_eq!(
+      vec![SdlVersion(2, 26, 5), SdlVersion(2, 30, 2)],
+      cached.iter().copied().collect::<Vec<_>>()
+    );
+    assert_eq!(
+      "sdl2-sys 0.34.*: SDL 2.0.12 or newer\n\
+      sdl2-sys 0.35.*: SDL 2.0.22 or newer\n\
+      sdl2-sys 0.36.*: SDL 2.26.0 or newer, cached 2.26.5\n\
+      sdl2-sys 0.37.*: SDL 2.30.0 or newer, downloaded 2.30.2, cached 2.30.2\n\
+      sdl3-sys 0.*: SDL 3.2.0 or newer, downloaded 3.2.4\n",
+      format_sdl_versions(
+        &BTreeSet::from([SdlVersion(2, 30, 2), SdlVersion(3, 2, 4)]),
+        &cached
+      )
+    );
+  }
+}
@@ -0,0 +1,237 @@
+use std::path::Path;
+
+use crate::features::*;
+use crate::manifest::*;
+use crate::util::*;
+
+/// A manifest entry SDL's Android backend needs or benefits from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SdlDefaultEntry {
+  /// A `uses-permission`, by its name without the `android.permission.` prefix.
+  Permission(&'static str),
+  /// A `uses-feature` which isn't required, so devices without the feature can install the app.
+  OptionalFeature(&'static str),
+}
+
+/// An entry `sdl_defaults` adds to the manifest, unless it is in `sdl_defaults_exclude`.
+pub struct SdlDefault {
+  pub name: &'static str,
+  pub entry: SdlDefaultEntry,
+  /// Why SDL needs the entry, printed in verbose builds.
+  pub reason: &'static str,
+  /// Only added if the cargo features of the build mention haptics.
+  pub haptics_only: bool,
+}
+
+/// The entries of `sdl_defaults`. Entries the manifest already declares are left as they are.
+pub const SDL_DEFAULTS: &[SdlDefault] = &[
+  SdlDefault {
+    name: "modify_audio_settings",
+    entry: SdlDefaultEntry::Permission("MODIFY_AUDIO_SETTINGS"),
+    //without it AudioManager ignores the routing changes of SDL's audio backend, e.g. switching
+    //to a bluetooth headset, and audio stays on the speaker
+    reason: "SDL's audio backend changes the audio routing through AudioManager",
+    haptics_only: false,
+  },
+  SdlDefault {
+    name: "vibrate",
+    entry: SdlDefaultEntry::Permission("VIBRATE"),
+    //SDLHapticHandler drives the vibrator of the device, rumble silently does nothing without it
+    reason: "SDL haptics vibrate the device",
+    haptics_only: true,
+  },
+  SdlDefault {
+    name: "gamepad",
+    entry: SdlDefaultEntry::OptionalFeature("android.hardware.gamepad"),
+    //lists the app as controller capable, e.g. on Android TV, without excluding touch devices
+    reason: "SDL supports game controllers",
+    haptics_only: false,
+  },
+  SdlDefault {
+    name: "usb_host",
+    entry: SdlDefaultEntry::OptionalFeature("android.hardware.usb.host"),
+    //SDL's hidapi driver reads USB controllers through UsbManager, which needs USB host mode
+    reason: "SDL reads USB controllers in USB host mode",
+    haptics_only: false,
+  },
+];
+
+/// Words in feature names which mean the app uses haptics.
+const HAPTICS_FEATURE_WORDS: &[&str] = &["haptic", "rumble", "vibrat", "force_feedback"];
+
+/// Whether one of the enabled features, or what it enables, mentions haptics, like a `haptics`
+/// feature or `gilrs/ff`.
+pub fn mentions_haptics<'a>(features: impl IntoIterator<Item = &'a String>) -> bool {
+  features.into_iter().any(|feature| {
+    let feature = feature.to_lowercase();
+    HAPTICS_FEATURE_WORDS
+      .iter()
+      .any(|word| feature.contains(word))
+      || feature.ends_with("/ff")
+  })
+}
+
+/// Whether the features any of the targets is built with mention haptics.
+pub fn uses_haptics(
+  manifest_path: &Path,
+  selections: &[FeatureSelection],
+) -> Result<bool, TomlError> {
+  let features_table = get_toml_entry(manifest_path, ["features"])?
+    .and_then(|value| value.as_table().cloned())
+    .unwrap_or_default();
+  Ok(
+    selections
+      .iter()
+      .any(|selection| mentions_haptics(&selection.enabled_features(&features_table))),
+  )
+}
+
+/// Reads `sdl_defaults` of the android metadata, on by default.
+pub fn get_sdl_defaults_enabled(manifest_path: &Path) -> Result<bool, String> {
+  Ok(
+    get_toml_bool(
+      manifest_path,
+      ["package", "metadata", "android", "sdl_defaults"],
+    )?
+    .unwrap_or(true),
+  )
+}
+
+/// Parses `sdl_defaults_exclude` of the android metadata, names of [SDL_DEFAULTS].
+pub fn parse_sdl_defaults_exclude(value: &toml::Value) -> Result<Vec<String>, String> {
+  let names = value
+    .as_array()
+    .and_then(|names| {
+      names
+        .iter()
+        .map(|name| name.as_str().map(str::to_string))
+        .collect::<Option<Vec<String>>>()
+    })
+    .ok_or("sdl_defaults_exclude must be a list of sdl default names")?;
+  for name in &names {
+    if !SDL_DEFAULTS.iter().any(|default| default.name == name) {
+      return Err(format!(
+        "Unknown sdl default \"{}\" in sdl_defaults_exclude, expected one of {}",
+        name,
+        SDL_DEFAULTS
+          .iter()
+          .map(|default| default.name)
+          .collect::<Vec<_>>()
+          .join(", ")
+      ));
+    }
+  }
+  Ok(names)
+}
+
+/// Reads `sdl_defaults_exclude` of the android metadata.
+pub fn get_sdl_defaults_exclude(manifest_path: &Path) -> Result<Vec<String>, String> {
+  match get_toml_entry(
+    manifest_path,
+    ["package", "metadata", "android", "sdl_defaults_exclude"],
+  )? {
+    Some(value) => parse_sdl_defaults_exclude(&value),
+    None => Ok(vec![]),
+  }
+}
+
+/// Adds the SDL defaults which aren't excluded and not yet declared by the manifest. Returns the
+/// ones added.
+pub fn apply_sdl_defaults(
+  manifest: &mut AndroidManifest,
+  exclude: &[String],
+  haptics: bool,
+) -> Vec<&'static SdlDefault> {
+  let mut applied = vec![];
+  for default in SDL_DEFAULTS {
+    if exclude.iter().any(|name| name == default.name) || (default.haptics_only && !haptics) {
+      continue;
+    }
+    match default.entry {
+      SdlDefaultEntry::Permission(name) => {
+        let permission = Permission::new(name);
+        if manifest.has_permission(&permission.qualified_name()) {
+          continue;
+        }
+        manifest.add_permission(&permission);
+      }
+      SdlDefaultEntry::OptionalFeature(name) => {
+        if manifest.has_uses_feature(name) {
+          continue;
+        }
+        manifest.add_uses_feature(&UsesFeature::new(name, false));
+      }
+    }
+    applied.push(default);
+  }
+  applied
+}
+
+#[cfg(test)]
+mod test {
+  use crate::manifest::AndroidManifest;
+  use crate::sdl_defaults::{apply_sdl_defaults, mentions_haptics, parse_sdl_defaults_exclude};
+
+  #[test]
+  fn haptics_features() {
+    let features =
+      |features: &[&str]| -> Vec<String> { features.iter().map(|f| f.to_string()).collect() };
+    assert!(mentions_haptics(&features(&["default", "haptics"])));
+    assert!(mentions_haptics(&features(&["Rumble"])));
+    assert!(mentions_haptics(&features(&["gilrs/ff"])));
+    assert!(!mentions_haptics(&features(&["default", "sdl2/mixer"])));
+    assert!(!mentions_haptics(&features(&["buffer"])));
+  }
+
+  #[test]
+  fn sdl_defaults() {
+    let template = r#"<?xml version="1.0" encoding="utf-8"?>
+<manifest xmlns:android="http://schemas.android.com/apk/res/android"
+    package="org.libsdl.app">
+
+    <uses-feature android:name="android.hardware.gamepad" android:required="true" />
+
+    <!-- <uses-permission android:name="android.permission.MODIFY_AUDIO_SETTINGS" /> -->
+
+    <application android:label="Game">
+    </application>
+</manifest>
+"#;
+    let mut manifest = AndroidManifest::new(template.to_string());
+    let applied = apply_sdl_defaults(&mut manifest, &[], false);
+    assert_eq!(
+      vec!["modify_audio_settings", "usb_host"],
+      applied.iter().map(|d| d.name).collect::<Vec<_>>()
+    );
+    //a declaration of the template is kept
+    assert!(manifest.content().contains(
+      "<uses-feature android:name=\"android.hardware.gamepad\" android:required=\"true\" />\n    \
+      <uses-feature android:name=\"android.hardware.usb.host\" android:required=\"false\" />"
+    ));
+    assert!(manifest
+      .content()
+      .contains("<uses-permission android:name=\"android.permission.MODIFY_AUDIO_SETTINGS\" />"));
+    assert!(!manifest.content().contains("VIBRATE"));
+
+    //applying again changes nothing
+    let content = manifest.content().to_string();
+    assert!(apply_sdl_defaults(&mut manifest, &[], false).is_empty());
+    assert_eq!(content, manifest.content());
+
+    let mut manifest = AndroidManifest::new(template.to_string());
+    let exclude = parse_sdl_defaults_exclude(&toml::Value::Array(vec![
+      "modify_audio_settings".into(),
+      "usb_host".into(),
+    ]))
+    .unwrap();
+    let applied = apply_sdl_defaults(&mut manifest, &exclude, true);
+    assert_eq!(
+      vec!["vibrate"],
+      applied.iter().map(|d| d.name).collect::<Vec<_>>()
+    );
+    assert!(!manifest.content().contains("usb.host"));
+
+    assert!(parse_sdl_defaults_exclude(&toml::Value::Array(vec!["camera".into()])).is_err());
+    assert!(parse_sdl_defaults_exclude(&toml::Value::String("vibrate".into())).is_err());
+  }
+}
@@ -0,0 +1,568 @@
+use std::env;
+use std::fs::{create_dir_all, read_dir, remove_dir_all, remove_file, rename, File, OpenOptions};
+use std::io::{self, stderr, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::thread::sleep;
+use std::time::Duration;
+
+use curl::easy::Easy;
+use sha2::{Digest, Sha256};
+use toml::value::Value;
+use zip::ZipArchive;
+
+use crate::ci::*;
+use crate::cli::SdlArgs;
+use crate::preflight::*;
+use crate::util::*;
+use crate::warnings::*;
+
+/// Env var overriding the directory downloaded SDL sources are kept in.
+pub const SDL_SOURCES_DIR_ENV: &str = "CARGO_SDL_APK_SDL_SOURCES";
+
+/// SHA-256 of the source zip of SDL releases, which is the same file on every mirror. A version is
+/// added once the checksum of its zip is verified against the release page.
+pub const SDL_SOURCE_CHECKSUMS: &[(SdlVersion, &str)] = &[];
+
+/// Attempts per mirror, a failed attempt resumes the partial download of the previous one.
+const DOWNLOAD_ATTEMPTS: u32 = 3;
+/// Wait before the second attempt, doubled for each further one.
+const RETRY_DELAY: Duration = Duration::from_secs(2);
+
+/// Directory the downloaded SDL sources are kept in, by default
+/// `~/.cache/cargo-sdl-apk/sdl-sources`.
+pub fn get_sdl_sources_dir() -> Option<PathBuf> {
+  if let Some(dir) = env::var_os(SDL_SOURCES_DIR_ENV) {
+    return Some(PathBuf::from(dir));
+  }
+  env::var_os("XDG_CACHE_HOME")
+    .map(PathBuf::from)
+    .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))
+    .map(|cache| cache.join("cargo-sdl-apk/sdl-sources"))
+}
+
+/// Name of the source of the version, e.g. `SDL2-2.30.2`, which is also the dir inside its zip.
+pub fn get_sdl_source_name(version: SdlVersion) -> String {
+  format!("SDL{}-{}", version.0, version)
+}
+
+/// Where the source zip of the version is downloaded from, in order: the GitHub release asset,
+/// then libsdl.org.
+pub fn get_sdl_source_urls(version: SdlVersion) -> Vec<String> {
+  let name = get_sdl_source_name(version);
+  vec![
+    format!(
+      "https://github.com/libsdl-org/SDL/releases/download/release-{}/{}.zip",
+      version, name
+    ),
+    format!("https://www.libsdl.org/release/{}.zip", name),
+  ]
+}
+
+/// Parses `sdl_checksums` of the android metadata, the SHA-256 of the source zips of versions
+/// which aren't pinned, e.g. `{ "2.30.3" = "..." }`.
+pub fn parse_sdl_checksums(value: &Value) -> Result<Vec<(SdlVersion, String)>, String> {
+  let table = value
+    .as_table()
+    .ok_or("sdl_checksums must be a table of SDL versions and SHA-256 checksums")?;
+  table
+    .iter()
+    .map(|(version, checksum)| {
+      let version = version.parse::<SdlVersion>()?;
+      let checksum = checksum
+        .as_str()
+        .filter(|c| c.len() == 64 && c.chars().all(|c| c.is_ascii_hexdigit()))
+        .ok_or_else(|| {
+          format!(
+            "sdl_checksums.\"{}\" must be the SHA-256 of the source zip as 64 hex digits",
+            version
+          )
+        })?;
+      Ok((version, checksum.to_lowercase()))
+    })
+    .collect()
+}
+
+/// Reads `sdl_checksums` of the android metadata.
+pub fn get_sdl_checksums(manifest_path: &Path) -> Result<Vec<(SdlVersion, String)>, String> {
+  match get_toml_entry(
+    manifest_path,
+    ["package", "metadata", "android", "sdl_checksums"],
+  )? {
+    Some(value) => parse_sdl_checksums(&value),
+    None => Ok(vec![]),
+  }
+}
+
+/// The checksum the source zip of the version must have: the pinned one, else a configured one,
+/// which is warned about. A configured checksum can't replace a pinned one.
+pub fn resolve_sdl_checksum(
+  version: SdlVersion,
+  configured: Option<&str>,
+) -> Result<String, String> {
+  let pinned = SDL_SOURCE_CHECKSUMS
+    .iter()
+    .find(|(pinned, _)| *pinned == version)
+    .map(|(_, checksum)| checksum.to_string());
+  match (pinned, configured) {
+    (Some(pinned), Some(configured)) if !pinned.eq_ignore_ascii_case(configured) => Err(format!(
+      "The checksum of SDL {} is pinned to {}, but {} is configured. Remove it from sdl_checksums",
+      version, pinned, configured
+    )),
+    (Some(pinned), _) => Ok(pinned),
+    (None, Some(configured)) => {
+      warn(
+        &UNPINNED_SDL,
+        format!(
+          "The checksum of SDL {} isn't pinned by this version of cargo-sdl-apk, verifying the \
+          download with the configured {}.",
+          version, configured
+        ),
+      );
+      Ok(configured.to_lowercase())
+    }
+    (None, None) => Err(format!(
+      "The checksum of SDL {} isn't pinned by this version of cargo-sdl-apk{}. Add its SHA-256 to \
+      sdl_checksums of the android metadata, or pass it with --sha256",
+      version,
+      match get_pinned_sdl_versions().last() {
+        Some(newest) => format!(", the newest pinned version is {}", newest),
+        None => String::new(),
+      }
+    )),
+  }
+}
+
+/// The versions with a pinned checksum, oldest first.
+pub fn get_pinned_sdl_versions() -> Vec<SdlVersion> {
+  let mut versions: Vec<SdlVersion> = SDL_SOURCE_CHECKSUMS.iter().map(|(v, _)| *v).collect();
+  versions.sort();
+  versions
+}
+
+pub fn sha256_file(path: &Path) -> io::Result<String> {
+  let mut file = File::open(path)?;
+  let mut hasher = Sha256::new();
+  let mut buffer = vec![0; 64 * 1024];
+  loop {
+    let read = file.read(&mut buffer)?;
+    if read == 0 {
+      return Ok(format!("{:x}", hasher.finalize()));
+    }
+    hasher.update(&buffer[..read]);
+  }
+}
+
+fn print_progress(downloaded: u64, total: u64) {
+  const WIDTH: u64 = 30;
+  let mib = |bytes: u64| bytes as f64 / (1024.0 * 1024.0);
+  let line = match (downloaded.min(total) * WIDTH).checked_div(total) {
+    Some(done) => format!(
+      "[{}{}] {:>3}% {:.1}/{:.1} MiB",
+      "#".repeat(done as usize),
+      " ".repeat((WIDTH - done) as usize),
+      downloaded.min(total) * 100 / total,
+      mib(downloaded),
+      mib(total)
+    ),
+    //the size isn't known yet
+    None => format!("{:.1} MiB", mib(downloaded)),
+  };
+  eprint!("\r{}", line);
+}
+
+/// Downloads the url to the partial file, resuming it if it exists. If the server can't resume,
+/// the download starts over.
+fn download(url: &str, partial: &Path, progress: bool) -> Result<(), String> {
+  let mut file = OpenOptions::new()
+    .create(true)
+    .append(true)
+    .open(partial)
+    .map_err(|e| format!("Unable to open {}: {}", partial.display(), e))?;
+  let offset = file.metadata().map(|m| m.len()).unwrap_or(0);
+  let curl_error = |e: curl::Error| format!("{}: {}", url, e);
+
+  let mut easy = Easy::new();
+  easy.url(url).map_err(curl_error)?;
+  easy.follow_location(true).map_err(curl_error)?;
+  easy.fail_on_error(true).map_err(curl_error)?;
+  easy
+    .connect_timeout(Duration::from_secs(30))
+    .map_err(curl_error)?;
+  //a stalled connection is an attempt which failed
+  easy.low_speed_limit(1024).map_err(curl_error)?;
+  easy
+    .low_speed_time(Duration::from_secs(30))
+    .map_err(curl_error)?;
+  easy.resume_from(offset).map_err(curl_error)?;
+  easy.progress(progress).map_err(curl_error)?;
+
+  let mut write_error = None;
+  let result = {
+    let mut transfer = easy.transfer();
+    transfer
+      .write_function(|data| match file.write_all(data) {
+        Ok(()) => Ok(data.len()),
+        Err(e) => {
+          write_error = Some(e);
+          Ok(0)
+        }
+      })
+      .map_err(curl_error)?;
+    transfer
+      .progress_function(|total, downloaded, _, _| {
+        print_progress(offset + downloaded as u64, offset + total as u64);
+        true
+      })
+      .map_err(curl_error)?;
+    transfer.perform()
+  };
+  if progress {
+    eprintln!();
+  }
+  if let Some(e) = write_error {
+    return Err(format!("Unable to write {}: {}", partial.display(), e));
+  }
+  match result {
+    Err(e) if e.is_range_error() && offset > 0 => {
+      println!("{} can't resume the download, starting over", url);
+      file
+        .set_len(0)
+        .map_err(|e| format!("Unable to truncate {}: {}", partial.display(), e))?;
+      download(url, partial, progress)
+    }
+    result => result.map_err(curl_error),
+  }
+}
+
+/// Downloads the zip from the first mirror which delivers a file with the checksum. Only a verified
+/// download is renamed to the archive, a partial or corrupt one never is.
+pub fn download_verified(
+  urls: &[String],
+  archive: &Path,
+  checksum: &str,
+  progress: bool,
+  retry_delay: Duration,
+) -> Result<(), String> {
+  let partial = archive.with_extension("zip.part");
+  let mut errors = vec![];
+  for url in urls {
+    for attempt in 1..=DOWNLOAD_ATTEMPTS {
+      println!(
+        "Downloading {} (attempt {}/{})",
+        url, attempt, DOWNLOAD_ATTEMPTS
+      );
+      if let Err(e) = download(url, &partial, progress) {
+        eprintln!("  {}", e);
+        errors.push(e);
+        if attempt < DOWNLOAD_ATTEMPTS {
+          sleep(retry_delay * 2u32.pow(attempt - 1));
+        }
+        continue;
+      }
+      let actual = sha256_file(&partial)
+        .map_err(|e| format!("Unable to read {}: {}", partial.display(), e))?;
+      if actual.eq_ignore_ascii_case(checksum) {
+        return rename(&partial, archive).map_err(|e| {
+          format!(
+            "Unable to move {} to {}: {}",
+            partial.display(),
+            archive.display(),
+            e
+          )
+        });
+      }
+      //a corrupt download isn't resumed
+      let _ = remove_file(&partial);
+      let e = format!("{} has the SHA-256 {}, expected {}", url, actual, checksum);
+      eprintln!("  {}", e);
+      errors.push(e);
+      break;
+    }
+  }
+  Err(format!(
+    "Unable to download a verified {}:\n  {}",
+    archive
+      .file_name()
+      .map(|name| name.to_string_lossy().to_string())
+      .unwrap_or_default(),
+    errors.join("\n  ")
+  ))
+}
+
+/// Extracts the source zip into the sources dir. It is extracted next to its destination first, so
+/// the source dir only ever exists complete.
+pub fn extract_sdl_source(
+  archive: &Path,
+  sources_dir: &Path,
+  name: &str,
+) -> Result<PathBuf, String> {
+  let dest = sources_dir.join(name);
+  let tmp = sources_dir.join(format!(".{}.tmp-{}", name, std::process::id()));
+  let _ = remove_dir_all(&tmp);
+  let file = File::open(archive).map_err(|e| format!("can't open {:?}: {}", archive, e))?;
+  ZipArchive::new(file)
+    .and_then(|mut zip| zip.extract(&tmp))
+    .map_err(|e| format!("Unable to extract {}: {}", archive.display(), e))?;
+  let extracted = tmp.join(name);
+  if !extracted.is_dir() {
+    let _ = remove_dir_all(&tmp);
+    return Err(format!("{} has no {} dir", archive.display(), name));
+  }
+  let result = rename(&extracted, &dest)
+    .map_err(|e| format!("Unable to move the SDL source to {}: {}", dest.display(), e));
+  let _ = remove_dir_all(&tmp);
+  result.map(|_| dest)
+}
+
+/// The versions of the sources downloaded to the sources dir.
+pub fn get_fetched_sdl_versions(sources_dir: &Path) -> Vec<SdlVersion> {
+  let mut versions: Vec<SdlVersion> = read_dir(sources_dir)
+    .map(|entries| {
+      entries
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().is_dir())
+        .filter_map(|e| {
+          let name = e.file_name().to_string_lossy().to_string();
+          let (_, version) = name.strip_prefix("SDL")?.split_once('-')?;
+          version.parse().ok()
+        })
+        .collect()
+    })
+    .unwrap_or_default();
+  versions.sort();
+  versions
+}
+
+/// Downloads and extracts the source of the SDL version unless it is downloaded already. Returns
+/// its dir, to use as `SDL`.
+pub fn fetch_sdl(
+  version: SdlVersion,
+  configured_checksum: Option<&str>,
+) -> Result<PathBuf, String> {
+  let sources_dir = get_sdl_sources_dir().ok_or_else(|| {
+    format!(
+      "Unable to determine the dir for SDL sources, set {} or HOME",
+      SDL_SOURCES_DIR_ENV
+    )
+  })?;
+  let name = get_sdl_source_name(version);
+  let dest = sources_dir.join(&name);
+  if dest.is_dir() {
+    println!("Using downloaded {}", dest.display());
+    return Ok(dest);
+  }
+
+  let checksum = resolve_sdl_checksum(version, configured_checksum)?;
+  create_dir_all(&sources_dir)
+    .map_err(|e| format!("Unable to create {}: {}", sources_dir.display(), e))?;
+  let archive = sources_dir.join(format!("{}.zip", name));
+  let verified = sha256_file(&archive).is_ok_and(|actual| actual.eq_ignore_ascii_case(&checksum));
+  if !verified {
+    let progress = stderr().is_terminal() && !is_ci_mode();
+    download_verified(
+      &get_sdl_source_urls(version),
+      &archive,
+      &checksum,
+      progress,
+      RETRY_DELAY,
+    )?;
+  }
+  let dest = extract_sdl_source(&archive, &sources_dir, &name)?;
+  let _ = remove_file(&archive);
+  println!("Downloaded SDL {} to {}", version, dest.display());
+  Ok(dest)
+}
+
+/// `sdl --fetch`, verified with the checksum of `--sha256` or else of `sdl_checksums` of the
+/// package, if there is one.
+pub fn run_sdl_fetch(args: &SdlArgs) -> Result<(), String> {
+  let Some(version) = args.fetch else {
+    return Ok(());
+  };
+  let configured = match &args.sha256 {
+    Some(sha256) => Some(sha256.clone()),
+    None if args.manifest_path.is_file() => get_sdl_checksums(&args.manifest_path)?
+      .into_iter()
+      .find(|(v, _)| *v == version)
+      .map(|(_, checksum)| checksum),
+    None => None,
+  };
+  let dir = fetch_sdl(version, configured.as_deref())?;
+  println!("export SDL=\"{}\"", dir.display());
+  Ok(())
+}
+
+#[cfg(test)]
+mod test {
+  use std::fs::{create_dir_all, read, read_to_string, write, File};
+  use std::io::{Read, Write};
+  use std::net::TcpListener;
+  use std::path::Path;
+  use std::thread;
+  use std::time::Duration;
+
+  use tempfile::tempdir;
+  use toml::value::Value;
+  use zip::write::FileOptions;
+  use zip::ZipWriter;
+
+  use crate::preflight::SdlVersion;
+  use crate::sdl_fetch::{
+    download_verified, extract_sdl_source, get_fetched_sdl_versions, get_sdl_source_name,
+    get_sdl_source_urls, parse_sdl_checksums, resolve_sdl_checksum, sha256_file,
+  };
+
+  const CHECKSUM: &str = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+
+  fn file_url(path: &Path) -> String {
+    format!("file://{}", path.display())
+  }
+
+  #[test]
+  fn source_urls() {
+    let version = SdlVersion(2, 30, 2);
+    assert_eq!("SDL2-2.30.2", get_sdl_source_name(version));
+    assert_eq!(
+      vec![
+        "https://github.com/libsdl-org/SDL/releases/download/release-2.30.2/SDL2-2.30.2.zip",
+        "https://www.libsdl.org/release/SDL2-2.30.2.zip"
+      ],
+      get_sdl_source_urls(version)
+    );
+    assert_eq!("SDL3-3.2.0", get_sdl_source_name(SdlVersion(3, 2, 0)));
+  }
+
+  #[test]
+  fn checksums() {
+    let value = format!(
+      "sdl_checksums = {{ \"2.30.3\" = \"{}\" }}",
+      CHECKSUM.to_uppercase()
+    )
+    .parse::<toml::Table>()
+    .unwrap()["sdl_checksums"]
+      .clone();
+    assert_eq!(
+      Ok(vec![(SdlVersion(2, 30, 3), CHECKSUM.to_string())]),
+      parse_sdl_checksums(&value)
+    );
+    let invalid = |table: &str| {
+      parse_sdl_checksums(&table.parse::<toml::Table>().unwrap()["sdl_checksums"].clone())
+    };
+    assert!(invalid("sdl_checksums = { \"2.30\" = \"ab\" }").is_err());
+    assert!(invalid("sdl_checksums = { \"2.30.3\" = \"abc\" }").is_err());
+    assert!(parse_sdl_checksums(&Value::String(CHECKSUM.to_string())).is_err());
+
+    //unpinned versions need a configured checksum
+    assert!(resolve_sdl_checksum(SdlVersion(2, 99, 0), None).is_err());
+    assert_eq!(
+      Ok(CHECKSUM.to_string()),
+      resolve_sdl_checksum(SdlVersion(2, 99, 0), Some(CHECKSUM))
+    );
+  }
+
+  #[test]
+  fn verified_download() {
+    let dir = tempdir().unwrap();
+    let good = dir.path().join("good.zip");
+    write(&good, "hello").unwrap();
+    assert_eq!(CHECKSUM, sha256_file(&good).unwrap());
+    let corrupt = dir.path().join("corrupt.zip");
+    write(&corrupt, "hellO").unwrap();
+    let missing = dir.path().join("missing.zip");
+
+    //mirrors are tried in order until one delivers the checksum
+    let archive = dir.path().join("cache/SDL2-2.30.2.zip");
+    create_dir_all(archive.parent().unwrap()).unwrap();
+    download_verified(
+      &[file_url(&missing), file_url(&corrupt), file_url(&good)],
+      &archive,
+      CHECKSUM,
+      false,
+      Duration::ZERO,
+    )
+    .unwrap();
+    assert_eq!(b"hello".to_vec(), read(&archive).unwrap());
+    assert!(!archive.with_extension("zip.part").exists());
+
+    //a partial download is resumed
+    let archive = dir.path().join("cache/SDL2-2.30.3.zip");
+    write(archive.with_extension("zip.part"), "hel").unwrap();
+    download_verified(
+      &[file_url(&good)],
+      &archive,
+      CHECKSUM,
+      false,
+      Duration::ZERO,
+    )
+    .unwrap();
+    assert_eq!(b"hello".to_vec(), read(&archive).unwrap());
+
+    //a download which can't be verified never becomes the archive
+    let archive = dir.path().join("cache/SDL2-2.30.4.zip");
+    assert!(download_verified(
+      &[file_url(&corrupt)],
+      &archive,
+      CHECKSUM,
+      false,
+      Duration::ZERO
+    )
+    .is_err());
+    assert!(!archive.exists());
+    assert!(!archive.with_extension("zip.part").exists());
+  }
+
+  #[test]
+  fn download_restarted_by_server() {
+    //a server ignoring the range of the resumed download sends the whole file
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/SDL2-2.30.2.zip", listener.local_addr().unwrap());
+    let server = thread::spawn(move || {
+      let mut ranges = vec![];
+      for _ in 0..2 {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut request = [0; 1024];
+        let read = stream.read(&mut request).unwrap();
+        ranges.push(String::from_utf8_lossy(&request[..read]).contains("Range: bytes=3-"));
+        let _ = stream
+          .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\nConnection: close\r\n\r\nhello");
+      }
+      ranges
+    });
+
+    let dir = tempdir().unwrap();
+    let archive = dir.path().join("SDL2-2.30.2.zip");
+    write(archive.with_extension("zip.part"), "hel").unwrap();
+    download_verified(&[url], &archive, CHECKSUM, false, Duration::ZERO).unwrap();
+    //the download started over without a range
+    assert_eq!(vec![true, false], server.join().unwrap());
+    assert_eq!(b"hello".to_vec(), read(&archive).unwrap());
+  }
+
+  #[test]
+  fn source_extraction() {
+    let dir = tempdir().unwrap();
+    let archive = dir.path().join("SDL2-2.30.2.zip");
+    let mut writer = ZipWriter::new(File::create(&archive).unwrap());
+    writer
+      .start_file("SDL2-2.30.2/include/SDL_version.h", FileOptions::default())
+      .unwrap();
+    writer.write_all(b"#define SDL_MAJOR_VERSION 2").unwrap();
+    writer.finish().unwrap();
+
+    let sources = dir.path().join("sources");
+    create_dir_all(&sources).unwrap();
+    let dest = extract_sdl_source(&archive, &sources, "SDL2-2.30.2").unwrap();
+    assert_eq!(sources.join("SDL2-2.30.2"), dest);
+    assert!(read_to_string(dest.join("include/SDL_version.h"))
+      .unwrap()
+      .contains("SDL_MAJOR_VERSION"));
+    //the temporary dir is gone
+    assert_eq!(1, std::fs::read_dir(&sources).unwrap().count());
+    assert_eq!(
+      vec![SdlVersion(2, 30, 2)],
+      get_fetched_sdl_versions(&sources)
+    );
+
+    assert!(extract_sdl_source(&archive, &sources, "SDL2-2.30.3").is_err());
+  }
+}
@@ -0,0 +1,169 @@
+use std::fs::remove_dir_all;
+use std::path::Path;
+
+use toml::value::Value;
+
+use crate::gradle_dsl::*;
+use crate::util::*;
+
+/// Package of the SDL Java glue in the template and the Maven artifacts.
+const SDL_JAVA_SOURCE_DIR: &str = "app/src/main/java/org/libsdl";
+
+/// Property of the SDL template's app/build.gradle skipping its ndk-build of SDL. The libraries are
+/// packaged from the staging dir anyway.
+const EXCLUDE_NATIVE_LIBS_PROPERTY: &str = "EXCLUDE_NATIVE_LIBS";
+
+/// Where the SDL Java glue of the app comes from, `sdl_java` of the android metadata.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum SdlJava {
+  /// The sources of the project template.
+  #[default]
+  Template,
+  /// A Maven dependency like `org.libsdl.app:SDL2:2.30.9`, replacing the sources of the template.
+  Maven(String),
+}
+
+/// Parses `sdl_java` of the android metadata, e.g. `{ maven = "org.libsdl.app:SDL2:2.30.9" }`.
+pub fn parse_sdl_java(value: Option<&Value>) -> Result<SdlJava, String> {
+  let Some(value) = value else {
+    return Ok(SdlJava::Template);
+  };
+  let coordinates = value
+    .as_table()
+    .filter(|table| table.len() == 1)
+    .and_then(|table| table.get("maven"))
+    .and_then(|maven| maven.as_str())
+    .ok_or_else(|| {
+      "sdl_java must be a table like { maven = \"org.libsdl.app:SDL2:2.30.9\" }".to_string()
+    })?;
+  let parts: Vec<&str> = coordinates.split(':').collect();
+  if parts.len() != 3 || parts.iter().any(|part| part.trim().is_empty()) {
+    return Err(format!(
+      "sdl_java.maven \"{}\" must be Maven coordinates like \"org.libsdl.app:SDL2:2.30.9\"",
+      coordinates
+    ));
+  }
+  Ok(SdlJava::Maven(coordinates.to_string()))
+}
+
+pub fn get_sdl_java(manifest_path: &Path) -> Result<SdlJava, String> {
+  parse_sdl_java(
+    get_toml_entry(
+      manifest_path,
+      ["package", "metadata", "android", "sdl_java"],
+    )?
+    .as_ref(),
+  )
+}
+
+/// Adds the Maven artifact of the SDL Java glue to the dependencies of app/build.gradle. Content
+/// already depending on it is returned unchanged.
+pub fn add_sdl_java_dependency(build_gradle: &str, coordinates: &str, dsl: GradleDsl) -> String {
+  if build_gradle.contains(coordinates) {
+    return build_gradle.to_string();
+  }
+  let dependency = dsl.call("implementation", &[&dsl.string(coordinates)]);
+  insert_after_line(build_gradle, |l| l == "dependencies {", &dependency, "    ").unwrap_or_else(
+    || {
+      let mut content = build_gradle.to_string();
+      content.push_str(&format!("\ndependencies {{\n    {}\n}}\n", dependency));
+      content
+    },
+  )
+}
+
+/// Turns off the ndk-build of the SDL template in gradle.properties, which needs the SDL source
+/// linked into the project.
+pub fn exclude_native_libs(gradle_properties: &str) -> String {
+  if gradle_properties.contains(EXCLUDE_NATIVE_LIBS_PROPERTY) {
+    return gradle_properties.to_string();
+  }
+  let mut content = gradle_properties.to_string();
+  if !content.is_empty() && !content.ends_with('\n') {
+    content.push('\n');
+  }
+  content.push_str(&format!("{}=true\n", EXCLUDE_NATIVE_LIBS_PROPERTY));
+  content
+}
+
+/// Removes the SDL Java sources the template copied into the project, which would be duplicate
+/// classes of the Maven artifact.
+pub fn remove_template_sdl_java(project_dir: &Path) {
+  let dir = project_dir.join(SDL_JAVA_SOURCE_DIR);
+  if dir.is_dir() {
+    remove_dir_all(&dir).unwrap_or_else(|_| panic!("Unable to remove {:?}", dir));
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use toml::Table;
+
+  use crate::gradle_dsl::GradleDsl;
+  use crate::sdl_java::{add_sdl_java_dependency, exclude_native_libs, parse_sdl_java, SdlJava};
+
+  fn parse(value: &str) -> Result<SdlJava, String> {
+    let table = format!("sdl_java = {}", value).parse::<Table>().unwrap();
+    parse_sdl_java(table.get("sdl_java"))
+  }
+
+  #[test]
+  fn sdl_java_config() {
+    assert_eq!(parse_sdl_java(None), Ok(SdlJava::Template));
+    assert_eq!(
+      parse("{ maven = \"org.libsdl.app:SDL2:2.30.9\" }"),
+      Ok(SdlJava::Maven("org.libsdl.app:SDL2:2.30.9".to_string()))
+    );
+    assert!(parse("\"maven\"").unwrap_err().contains("must be a table"));
+    assert!(parse("{ maven = \"org.libsdl.app:SDL2\" }")
+      .unwrap_err()
+      .contains("must be Maven coordinates"));
+    assert!(parse("{ maven = \"org.libsdl.app:SDL2:2.30.9\", aar = \"x\" }").is_err());
+  }
+
+  #[test]
+  fn dependency() {
+    let build_gradle = "android {\n}\n\ndependencies {\n    implementation fileTree(include: ['*.jar'], dir: 'libs')\n}\n";
+    let content = add_sdl_java_dependency(
+      build_gradle,
+      "org.libsdl.app:SDL2:2.30.9",
+      GradleDsl::Groovy,
+    );
+    assert_eq!(
+      content,
+      "android {\n}\n\ndependencies {\n    implementation 'org.libsdl.app:SDL2:2.30.9'\n    implementation fileTree(include: ['*.jar'], dir: 'libs')\n}\n"
+    );
+    assert_eq!(
+      add_sdl_java_dependency(&content, "org.libsdl.app:SDL2:2.30.9", GradleDsl::Groovy),
+      content
+    );
+    assert_eq!(
+      add_sdl_java_dependency(
+        "android {\n}\n",
+        "org.libsdl.app:SDL2:2.30.9",
+        GradleDsl::Groovy
+      ),
+      "android {\n}\n\ndependencies {\n    implementation 'org.libsdl.app:SDL2:2.30.9'\n}\n"
+    );
+    assert_eq!(
+      add_sdl_java_dependency(
+        "dependencies {\n}\n",
+        "org.libsdl.app:SDL2:2.30.9",
+        GradleDsl::Kotlin
+      ),
+      "dependencies {\n    implementation(\"org.libsdl.app:SDL2:2.30.9\")\n}\n"
+    );
+  }
+
+  #[test]
+  fn native_libs_excluded() {
+    assert_eq!(
+      exclude_native_libs("org.gradle.jvmargs=-Xmx2048m"),
+      "org.gradle.jvmargs=-Xmx2048m\nEXCLUDE_NATIVE_LIBS=true\n"
+    );
+    assert_eq!(
+      exclude_native_libs("EXCLUDE_NATIVE_LIBS=true\n"),
+      "EXCLUDE_NATIVE_LIBS=true\n"
+    );
+  }
+}
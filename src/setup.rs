@@ -0,0 +1,303 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::path::{Path, PathBuf};
+
+use crate::ci::*;
+use crate::cli::SetupArgs;
+use crate::ndk::*;
+use crate::preflight::*;
+use crate::sdl_fetch::*;
+use crate::user_config::*;
+use crate::util::*;
+
+/// Where to get SDL if there is no checkout yet and it isn't downloaded.
+const SDL_CLONE_HINT: &str =
+  "Clone an SDL checkout with: git clone -b release-2.30.x https://github.com/libsdl-org/SDL";
+
+/// How to get SDL without being asked.
+const SDL_FLAGS_HINT: &str = "Pass --fetch-sdl <VERSION> or --sdl <DIR> with --non-interactive.";
+
+/// A dir the setup asks for, written to the user config as the env var `key`.
+struct SetupItem {
+  key: &'static str,
+  flag: &'static str,
+  required: bool,
+  check: fn(&Path) -> Result<String, String>,
+}
+
+const SETUP_ITEMS: &[SetupItem] = &[
+  SetupItem {
+    key: "ANDROID_HOME",
+    flag: "--android-home",
+    required: true,
+    check: check_android_home,
+  },
+  SetupItem {
+    key: "ANDROID_NDK_HOME",
+    flag: "--ndk",
+    required: true,
+    check: check_ndk_home,
+  },
+  SetupItem {
+    key: "JAVA_HOME",
+    flag: "--java-home",
+    required: false,
+    check: check_java_home,
+  },
+  SetupItem {
+    key: "SDL",
+    flag: "--sdl",
+    required: true,
+    check: check_sdl_checkout,
+  },
+];
+
+/// Checks that the dir is an Android sdk with at least one of its tool packages.
+pub fn check_android_home(dir: &Path) -> Result<String, String> {
+  if ["platform-tools", "build-tools", "cmdline-tools"]
+    .iter()
+    .any(|package| dir.join(package).is_dir())
+  {
+    Ok("Android sdk".to_string())
+  } else {
+    Err(format!(
+      "{} is no Android sdk, it has neither platform-tools, build-tools nor cmdline-tools",
+      dir.display()
+    ))
+  }
+}
+
+fn check_ndk_home(dir: &Path) -> Result<String, String> {
+  check_ndk_toolchain(dir).map(|tag| format!("NDK for {}", tag))
+}
+
+fn check_java_home(dir: &Path) -> Result<String, String> {
+  if dir.join("bin/java").is_file() || dir.join("bin/java.exe").is_file() {
+    Ok("JDK".to_string())
+  } else {
+    Err(format!("{} has no bin/java", dir.display()))
+  }
+}
+
+fn check_sdl_checkout(dir: &Path) -> Result<String, String> {
+  check_sdl_dir(dir, true).map(|layout| format!("{} source checkout", layout))
+}
+
+/// Guesses the dir of the env var from the environment and the usual install locations.
+fn detect(key: &str, found: &BTreeMap<String, String>) -> Option<PathBuf> {
+  if let Some(value) = env::var_os(key) {
+    return Some(PathBuf::from(value));
+  }
+  //ANDROID_HOME is already set at startup if the sdk could be found
+  match key {
+    "ANDROID_NDK_HOME" => found
+      .get("ANDROID_HOME")
+      .and_then(|android_home| find_newest_ndk(Path::new(android_home))),
+    _ => None,
+  }
+}
+
+/// Asks for the dir until it passes its check, the detected one is taken on enter. An optional
+/// item is skipped with `-`.
+fn ask(item: &SetupItem, detected: Option<PathBuf>) -> Result<Option<PathBuf>, String> {
+  loop {
+    let prompt = match (&detected, item.required) {
+      (Some(dir), _) => format!("{} [{}]:", item.key, dir.display()),
+      (None, true) => format!("{}:", item.key),
+      (None, false) => format!("{} (- to skip):", item.key),
+    };
+    let answer = prompt_or_fail(
+      &prompt,
+      &format!("Pass it with {} and --non-interactive.", item.flag),
+    )?;
+    let dir = match answer.trim() {
+      "" => match &detected {
+        Some(dir) => dir.clone(),
+        None => continue,
+      },
+      "-" if !item.required => return Ok(None),
+      answer => PathBuf::from(answer),
+    };
+    match (item.check)(&dir) {
+      Ok(info) => {
+        println!("  ok: {}", info);
+        return Ok(Some(dir));
+      }
+      Err(e) => println!("  {}", e),
+    }
+  }
+}
+
+/// The version offered for download without an SDL checkout, the newest SDL2 of the pinned
+/// versions, which are sorted oldest first.
+fn get_default_sdl_download(pinned: &[SdlVersion]) -> Option<SdlVersion> {
+  pinned.iter().rev().find(|version| version.0 == 2).copied()
+}
+
+/// The version to download for the answer to the download prompt, `None` to give a checkout
+/// instead.
+fn parse_sdl_download_answer(
+  answer: &str,
+  default: Option<SdlVersion>,
+) -> Result<Option<SdlVersion>, String> {
+  match (answer.trim(), default) {
+    ("", Some(version)) => Ok(Some(version)),
+    ("" | "n" | "N", _) => Ok(None),
+    (answer, _) => answer.parse().map(Some),
+  }
+}
+
+/// Whether the checksum of the version to download has to be asked for, as it isn't pinned.
+fn needs_sdl_checksum(version: SdlVersion, pinned: &[SdlVersion]) -> bool {
+  !pinned.contains(&version)
+}
+
+/// Offers to download SDL when there is no checkout, by default the newest SDL2 release with a
+/// pinned checksum. For other versions the checksum is asked for. Returns the downloaded dir.
+fn offer_sdl_download() -> Result<Option<PathBuf>, String> {
+  let pinned = get_pinned_sdl_versions();
+  let default = get_default_sdl_download(&pinned);
+  let prompt = match default {
+    Some(version) => format!(
+      "No SDL checkout found. Download SDL {}? Enter another version, or n to give a checkout [{}]:",
+      version, version
+    ),
+    None => "No SDL checkout found. SDL version to download, e.g. 2.30.2, or enter to give a \
+      checkout:"
+      .to_string(),
+  };
+  let version = loop {
+    let answer = prompt_or_fail(&prompt, SDL_FLAGS_HINT)?;
+    match parse_sdl_download_answer(&answer, default) {
+      Ok(Some(version)) => break version,
+      Ok(None) => {
+        println!("{}", SDL_CLONE_HINT);
+        return Ok(None);
+      }
+      Err(e) => println!("  {}", e),
+    }
+  };
+  let checksum = if needs_sdl_checksum(version, &pinned) {
+    Some(prompt_or_fail(
+      &format!(
+        "The checksum of SDL {} isn't pinned. SHA-256 of {}.zip from the SDL release page:",
+        version,
+        get_sdl_source_name(version)
+      ),
+      SDL_FLAGS_HINT,
+    )?)
+  } else {
+    None
+  };
+  fetch_sdl(version, checksum.as_deref().map(str::trim)).map(Some)
+}
+
+/// Locates the Android sdk, NDK, JDK and SDL checkout, checks them like `doctor` and saves them
+/// to the user config, so the env vars become optional. Dirs given as flags are only checked,
+/// with `--non-interactive` (or in CI mode) nothing is asked.
+pub fn run_setup(args: &SetupArgs) -> Result<(), String> {
+  let interactive = !args.non_interactive && !is_ci_mode();
+  let fetched_sdl = match args.fetch_sdl {
+    Some(version) => Some(fetch_sdl(version, args.sdl_sha256.as_deref())?),
+    None => None,
+  };
+  let given = [
+    args.android_home.clone(),
+    args.ndk.clone(),
+    args.java_home.clone(),
+    args.sdl.clone().or(fetched_sdl),
+  ];
+  let mut found = BTreeMap::new();
+  for (item, given) in SETUP_ITEMS.iter().zip(given) {
+    let mut detected = given.clone().or_else(|| detect(item.key, &found));
+    if item.key == "SDL" && detected.is_none() {
+      if interactive {
+        detected = offer_sdl_download()?;
+      } else {
+        println!("No SDL checkout found. {}", SDL_CLONE_HINT);
+      }
+    }
+    let dir = if interactive && given.is_none() {
+      ask(item, detected)?
+    } else {
+      match detected {
+        Some(dir) => {
+          let info = (item.check)(&dir).map_err(|e| format!("{}: {}", item.key, e))?;
+          println!("{} = {} ({})", item.key, dir.display(), info);
+          Some(dir)
+        }
+        None if item.required && item.key == "SDL" => {
+          return Err(format!("SDL not found. {}", SDL_FLAGS_HINT))
+        }
+        None if item.required => {
+          return Err(format!(
+            "{} not found, pass it with {}",
+            item.key, item.flag
+          ))
+        }
+        None => None,
+      }
+    };
+    if let Some(dir) = dir {
+      found.insert(item.key.to_string(), dir.display().to_string());
+    }
+  }
+
+  let path = get_user_config_path().ok_or("unable to locate the user config dir, set HOME")?;
+  write_user_config_env(&path, &found)?;
+  println!();
+  println!(
+    "Saved to {}, these env vars are now optional. To use env vars instead:",
+    path.display()
+  );
+  for (key, value) in &found {
+    println!("export {}=\"{}\"", key, value);
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod test {
+  use std::fs::create_dir_all;
+
+  use tempfile::tempdir;
+
+  use crate::preflight::SdlVersion;
+  use crate::setup::{
+    check_android_home, get_default_sdl_download, needs_sdl_checksum, parse_sdl_download_answer,
+  };
+
+  #[test]
+  fn android_home_check() {
+    let dir = tempdir().unwrap();
+    assert!(check_android_home(dir.path()).is_err());
+    create_dir_all(dir.path().join("platform-tools")).unwrap();
+    assert!(check_android_home(dir.path()).is_ok());
+  }
+
+  #[test]
+  fn sdl_download_default() {
+    let pinned = [
+      SdlVersion(2, 26, 0),
+      SdlVersion(2, 30, 0),
+      SdlVersion(3, 2, 0),
+    ];
+    let default = get_default_sdl_download(&pinned);
+    assert_eq!(Some(SdlVersion(2, 30, 0)), default);
+    //enter downloads the default, whose checksum is pinned
+    let version = parse_sdl_download_answer("\n", default).unwrap().unwrap();
+    assert_eq!(SdlVersion(2, 30, 0), version);
+    assert!(!needs_sdl_checksum(version, &pinned));
+
+    let version = parse_sdl_download_answer("2.30.2", default)
+      .unwrap()
+      .unwrap();
+    assert!(needs_sdl_checksum(version, &pinned));
+    assert_eq!(None, parse_sdl_download_answer("n", default).unwrap());
+    assert!(parse_sdl_download_answer("latest", default).is_err());
+
+    //without pinned versions there is no default, enter gives a checkout
+    assert_eq!(None, get_default_sdl_download(&[]));
+    assert_eq!(None, parse_sdl_download_answer("", None).unwrap());
+  }
+}
@@ -0,0 +1,897 @@
+use std::cmp::Ordering;
+use std::env;
+use std::ffi::OsString;
+use std::fmt::{Display, Formatter};
+use std::fs::read_to_string;
+use std::path::{Path, PathBuf};
+
+use toml::value::Value;
+use toml::Table;
+
+use crate::gradle_dsl::*;
+use crate::keystore::*;
+use crate::sdk::*;
+use crate::util::*;
+
+/// Oldest build-tools whose apksigner can create v4 signatures.
+const V4_SIGNING_MIN_BUILD_TOOLS_VERSION: &str = "30.0.0";
+/// The v4 signature complements the v2 signature, which devices verify from this sdk version on.
+const V4_SIGNING_MIN_SDK_VERSION: u32 = 24;
+/// Android 11 refuses to install APKs targeting it with only a v1 signature.
+const V2_SIGNING_REQUIRED_MIN_SDK_VERSION: u32 = 30;
+
+/// A key to sign with, from a keystore.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignerConfig {
+  pub keystore: PathBuf,
+  /// Keystore type like `pkcs12` or `jks`, detected by apksigner if not given.
+  pub keystore_type: Option<String>,
+  /// Store password in apksigner's format, e.g. `pass:secret` or `env:KS_PASS`.
+  pub store_pass: String,
+  pub key_alias: Option<String>,
+  /// Key password in apksigner's format, the store password is used if not given.
+  pub key_pass: Option<String>,
+}
+
+/// Everything needed to sign the release apk, from `[package.metadata.android.signing]` and the
+/// command line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SigningConfig {
+  pub signer: SignerConfig,
+  /// The key rotated to, signing together with `signer`.
+  pub next_signer: Option<SignerConfig>,
+  /// Signing certificate lineage proving the rotation from `signer` to `next_signer`.
+  pub lineage: Option<PathBuf>,
+  pub rotation_min_sdk_version: Option<u32>,
+}
+
+/// Gradle properties the passwords are passed in with gradle signing.
+pub const GRADLE_STORE_PASSWORD_PROPERTY: &str = "cargoSdlApkStorePassword";
+pub const GRADLE_KEY_PASSWORD_PROPERTY: &str = "cargoSdlApkKeyPassword";
+
+/// Environment variables the passwords are passed to jarsigner in when signing an AAB.
+const JARSIGNER_STORE_PASSWORD_ENV: &str = "CARGO_SDL_APK_STORE_PASS";
+const JARSIGNER_KEY_PASSWORD_ENV: &str = "CARGO_SDL_APK_KEY_PASS";
+
+/// Who signs the release APK, `mode` of the signing metadata.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SigningMode {
+  /// zipalign and apksigner are run on the unsigned APK built by gradle.
+  #[default]
+  Standalone,
+  /// gradle signs the APK with a `signingConfigs` block of the generated build.gradle.
+  Gradle,
+}
+
+impl Display for SigningMode {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match self {
+      SigningMode::Standalone => write!(f, "standalone (apksigner)"),
+      SigningMode::Gradle => write!(f, "gradle (signingConfigs)"),
+    }
+  }
+}
+
+/// The tool signing the release APK in the standalone signing mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApkSigner {
+  Apksigner,
+  /// jarsigner of the JDK, as the build-tools lack apksigner. Only creates a v1 signature.
+  Jarsigner,
+}
+
+impl Display for ApkSigner {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match self {
+      ApkSigner::Apksigner => write!(f, "standalone (apksigner)"),
+      ApkSigner::Jarsigner => write!(
+        f,
+        "standalone (jarsigner, v1 signature only as apksigner is missing)"
+      ),
+    }
+  }
+}
+
+/// Decides who signs the APK. Without apksigner jarsigner creates a v1 signature, unless devices
+/// of the min sdk version refuse it.
+pub fn choose_apk_signer(
+  apksigner: bool,
+  jarsigner: bool,
+  min_sdk_version: Option<u32>,
+) -> Result<ApkSigner, String> {
+  if apksigner {
+    return Ok(ApkSigner::Apksigner);
+  }
+  if let Some(min_sdk) = min_sdk_version.filter(|v| *v >= V2_SIGNING_REQUIRED_MIN_SDK_VERSION) {
+    return Err(format!(
+      "apksigner is missing from the build-tools and jarsigner only creates v1 signatures, which devices refuse for min_sdk_version {} (from {} on). Install build-tools with apksigner with sdkmanager",
+      min_sdk, V2_SIGNING_REQUIRED_MIN_SDK_VERSION
+    ));
+  }
+  if !jarsigner {
+    return Err("apksigner is missing from the build-tools and jarsigner of a JDK wasn't found. Install build-tools with apksigner with sdkmanager or set JAVA_HOME".to_string());
+  }
+  Ok(ApkSigner::Jarsigner)
+}
+
+/// Parses `mode` of the signing metadata, standalone if not set.
+pub fn parse_signing_mode(value: Option<&Value>) -> Result<SigningMode, String> {
+  match value {
+    None => Ok(SigningMode::Standalone),
+    Some(Value::String(mode)) if mode == "standalone" => Ok(SigningMode::Standalone),
+    Some(Value::String(mode)) if mode == "gradle" => Ok(SigningMode::Gradle),
+    Some(_) => Err("signing.mode must be \"standalone\" or \"gradle\"".to_string()),
+  }
+}
+
+/// Reads the signing mode of the package.
+pub fn get_signing_mode(manifest_path: &Path) -> Result<SigningMode, String> {
+  parse_signing_mode(
+    get_toml_entry(
+      manifest_path,
+      ["package", "metadata", "android", "signing", "mode"],
+    )?
+    .as_ref(),
+  )
+}
+
+/// Reads a password from either `<key>` (in apksigner's format) or `<key>_env` (name of an env
+/// var).
+fn get_password(table: &Table, key: &str) -> Result<Option<String>, String> {
+  let env_key = format!("{}_env", key);
+  match (table.get(key), table.get(&env_key)) {
+    (Some(_), Some(_)) => Err(format!("only one of {} and {} may be set", key, env_key)),
+    (Some(Value::String(pass)), None) => Ok(Some(pass.clone())),
+    (None, Some(Value::String(var))) => Ok(Some(format!("env:{}", var))),
+    (None, None) => Ok(None),
+    _ => Err(format!("{} must be a string", key)),
+  }
+}
+
+fn get_string(table: &Table, key: &str) -> Result<Option<String>, String> {
+  match table.get(key) {
+    None => Ok(None),
+    Some(Value::String(s)) => Ok(Some(s.clone())),
+    Some(_) => Err(format!("{} must be a string", key)),
+  }
+}
+
+/// Parses a signer table. Relative paths are resolved against the manifest dir, a store password
+/// given on the command line takes precedence.
+fn parse_signer(
+  table: &Table,
+  manifest_dir: &Path,
+  store_pass_override: Option<String>,
+) -> Result<SignerConfig, String> {
+  let keystore = get_string(table, "keystore")?.ok_or("keystore is required")?;
+  let store_pass = match store_pass_override {
+    Some(pass) => pass,
+    None => get_password(table, "keystore_pass")?.ok_or(format!(
+      "no password for keystore {}, set keystore_pass_env or pass --ks-pass",
+      keystore
+    ))?,
+  };
+
+  Ok(SignerConfig {
+    keystore: manifest_dir.join(keystore),
+    keystore_type: get_string(table, "keystore_type")?,
+    store_pass,
+    key_alias: get_string(table, "key_alias")?,
+    key_pass: get_password(table, "key_pass")?,
+  })
+}
+
+/// Combines the signing metadata with the keystore options given on the command line. Returns
+/// `None` if no keystore is configured at all.
+pub fn parse_signing_config(
+  metadata: Option<&Table>,
+  manifest_dir: &Path,
+  ks_file: Option<String>,
+  ks_pass: Option<String>,
+  key_pass: Option<String>,
+) -> Result<Option<SigningConfig>, String> {
+  let mut table = metadata.cloned().unwrap_or_default();
+  if let Some(ks_file) = ks_file {
+    table.insert("keystore".to_string(), Value::String(ks_file));
+  }
+  if let Some(key_pass) = key_pass {
+    table.remove("key_pass_env");
+    table.insert("key_pass".to_string(), Value::String(key_pass));
+  }
+  if !table.contains_key("keystore") {
+    return Ok(None);
+  }
+
+  let signer = parse_signer(&table, manifest_dir, ks_pass)?;
+  let next_signer = match table.get("next_signer") {
+    None => None,
+    Some(Value::Table(next)) => Some(parse_signer(next, manifest_dir, None)?),
+    Some(_) => return Err("next_signer must be a table".to_string()),
+  };
+  let lineage = get_string(&table, "lineage")?.map(|l| manifest_dir.join(l));
+  let rotation_min_sdk_version = match table.get("rotation_min_sdk_version") {
+    None => None,
+    Some(v) => Some(
+      v.as_integer()
+        .and_then(|v| u32::try_from(v).ok())
+        .ok_or("rotation_min_sdk_version must be an sdk version")?,
+    ),
+  };
+
+  if next_signer.is_some() && lineage.is_none() {
+    return Err("next_signer requires a lineage file".to_string());
+  }
+
+  Ok(Some(SigningConfig {
+    signer,
+    next_signer,
+    lineage,
+    rotation_min_sdk_version,
+  }))
+}
+
+/// Reads the signing configuration of the package, combined with the keystore options.
+pub fn get_signing_config(
+  manifest_path: &Path,
+  ks_file: Option<String>,
+  ks_pass: Option<String>,
+  key_pass: Option<String>,
+) -> Result<Option<SigningConfig>, String> {
+  let metadata = match get_toml_entry(manifest_path, ["package", "metadata", "android", "signing"])?
+  {
+    Some(Value::Table(table)) => Some(table),
+    Some(_) => return Err("package.metadata.android.signing must be a table".to_string()),
+    None => None,
+  };
+
+  parse_signing_config(
+    metadata.as_ref(),
+    manifest_path.parent().unwrap(),
+    ks_file,
+    ks_pass,
+    key_pass,
+  )
+  .map_err(|e| format!("Invalid signing configuration: {}", e))
+}
+
+/// Signing configuration for the Android debug keystore.
+pub fn get_debug_signing_config(keystore: PathBuf) -> SigningConfig {
+  SigningConfig {
+    signer: SignerConfig {
+      keystore,
+      keystore_type: None,
+      store_pass: format!("pass:{}", DEBUG_KEYSTORE_PASS),
+      key_alias: Some(DEBUG_KEY_ALIAS.to_string()),
+      key_pass: None,
+    },
+    next_signer: None,
+    lineage: None,
+    rotation_min_sdk_version: None,
+  }
+}
+
+/// Signing configuration for the release key older versions generated, see
+/// [migrate_legacy_release_keystore].
+pub fn get_generated_release_signing_config(keystore: PathBuf) -> SigningConfig {
+  SigningConfig {
+    signer: SignerConfig {
+      keystore,
+      keystore_type: None,
+      store_pass: format!("pass:{}", GENERATED_RELEASE_KEYSTORE_PASS),
+      key_alias: Some(GENERATED_RELEASE_KEY_ALIAS.to_string()),
+      key_pass: None,
+    },
+    next_signer: None,
+    lineage: None,
+    rotation_min_sdk_version: None,
+  }
+}
+
+/// Decides whether to create a v4 signature. Unless requested explicitly it is created if both the
+/// min sdk version of the app and the installed build-tools allow it.
+pub fn resolve_v4_signing(
+  requested: Option<bool>,
+  min_sdk_version: Option<u32>,
+  build_tools_version: Option<&str>,
+) -> Result<bool, String> {
+  let build_tools_support = build_tools_version
+    .map(|v| compare_versions(v, V4_SIGNING_MIN_BUILD_TOOLS_VERSION) != Ordering::Less)
+    .unwrap_or(false);
+
+  match requested {
+    Some(false) => Ok(false),
+    Some(true) if !build_tools_support => Err(format!(
+      "v4 signing needs build-tools {} or newer",
+      V4_SIGNING_MIN_BUILD_TOOLS_VERSION
+    )),
+    Some(true) => Ok(true),
+    None => Ok(
+      build_tools_support
+        && min_sdk_version
+          .map(|v| v >= V4_SIGNING_MIN_SDK_VERSION)
+          .unwrap_or(false),
+    ),
+  }
+}
+
+/// Decides whether to create a v4 signature for the package, returning an error if it was
+/// requested but isn't possible.
+pub fn get_v4_signing(manifest_path: &Path, requested: Option<bool>) -> Result<bool, String> {
+  let min_sdk_version = get_min_sdk_version(manifest_path)?;
+  let build_tools_version = find_build_tools_version(&get_env_path("ANDROID_HOME"));
+  resolve_v4_signing(requested, min_sdk_version, build_tools_version.as_deref())
+    .map_err(|e| format!("{}, install them with sdkmanager", e))
+}
+
+/// Resolves a password in apksigner's format to the password itself.
+fn resolve_password(password: &str) -> Result<String, String> {
+  if let Some(pass) = password.strip_prefix("pass:") {
+    Ok(pass.to_string())
+  } else if let Some(var) = password.strip_prefix("env:") {
+    env::var(var).map_err(|_| format!("the password env var {} is not set", var))
+  } else if let Some(file) = password.strip_prefix("file:") {
+    read_to_string(file)
+      .map(|content| content.lines().next().unwrap_or_default().to_string())
+      .map_err(|e| format!("unable to read the password file {}: {}", file, e))
+  } else {
+    Err("gradle signing needs passwords in the pass:, env: or file: format".to_string())
+  }
+}
+
+/// Quotes a string for a gradle build script.
+pub fn groovy_string(s: &str) -> String {
+  format!("'{}'", s.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+/// The signing configuration for gradle signing, with the passwords resolved.
+#[derive(Clone, PartialEq, Eq)]
+pub struct GradleSigning {
+  pub keystore: PathBuf,
+  pub keystore_type: Option<String>,
+  pub key_alias: String,
+  store_password: String,
+  key_password: String,
+}
+
+impl GradleSigning {
+  /// Resolves the configuration for gradle, which can't rotate keys and needs the key alias.
+  pub fn from_config(config: &SigningConfig) -> Result<Self, String> {
+    if config.next_signer.is_some() {
+      return Err("key rotation with next_signer needs the standalone signing mode".to_string());
+    }
+    let signer = &config.signer;
+    let key_alias = signer
+      .key_alias
+      .clone()
+      .ok_or("gradle signing needs the key_alias of the signing metadata")?;
+    let store_password = resolve_password(&signer.store_pass)?;
+    let key_password = match &signer.key_pass {
+      Some(key_pass) => resolve_password(key_pass)?,
+      None => store_password.clone(),
+    };
+    Ok(Self {
+      keystore: signer.keystore.clone(),
+      keystore_type: signer.keystore_type.clone(),
+      key_alias,
+      store_password,
+      key_password,
+    })
+  }
+
+  /// The `signingConfigs` block of app/build.gradle, used by the release build type. The passwords
+  /// are read from gradle properties, so they are never written to the project.
+  pub fn build_gradle_block(&self, v4_signing: bool, dsl: GradleDsl) -> String {
+    let mut lines = vec![
+      "    signingConfigs {".to_string(),
+      format!("        {}", dsl.element_block("release", false)),
+      format!(
+        "            {}",
+        dsl.assign(
+          "storeFile",
+          &format!("file({})", dsl.string(&self.keystore.to_string_lossy()))
+        )
+      ),
+    ];
+    if let Some(keystore_type) = &self.keystore_type {
+      lines.push(format!(
+        "            {}",
+        dsl.assign("storeType", &dsl.string(keystore_type))
+      ));
+    }
+    lines.push(format!(
+      "            {}",
+      dsl.assign(
+        "storePassword",
+        &dsl.project_property(GRADLE_STORE_PASSWORD_PROPERTY)
+      )
+    ));
+    lines.push(format!(
+      "            {}",
+      dsl.assign("keyAlias", &dsl.string(&self.key_alias))
+    ));
+    lines.push(format!(
+      "            {}",
+      dsl.assign(
+        "keyPassword",
+        &dsl.project_property(GRADLE_KEY_PASSWORD_PROPERTY)
+      )
+    ));
+    if v4_signing {
+      lines.push(format!(
+        "            {}",
+        dsl.assign("enableV4Signing", "true")
+      ));
+    }
+    let release_config = match dsl {
+      GradleDsl::Groovy => "signingConfigs.release",
+      GradleDsl::Kotlin => "signingConfigs.getByName(\"release\")",
+    };
+    lines.extend([
+      "        }".to_string(),
+      "    }".to_string(),
+      "    buildTypes {".to_string(),
+      format!("        {}", dsl.element_block("release", true)),
+      format!(
+        "            {}",
+        dsl.assign("signingConfig", release_config)
+      ),
+      "        }".to_string(),
+      "    }".to_string(),
+    ]);
+    lines.join("\n")
+  }
+
+  /// The gradle command line arguments passing the passwords.
+  pub fn gradle_args(&self) -> Vec<String> {
+    vec![
+      format!(
+        "-P{}={}",
+        GRADLE_STORE_PASSWORD_PROPERTY, self.store_password
+      ),
+      format!("-P{}={}", GRADLE_KEY_PASSWORD_PROPERTY, self.key_password),
+    ]
+  }
+
+  /// Resolves the configuration for signing an AAB with jarsigner, which like gradle can't rotate
+  /// keys and needs the key alias.
+  pub fn for_aab(config: &SigningConfig) -> Result<Self, String> {
+    if config.next_signer.is_some() {
+      return Err(
+        "an AAB can't be signed with key rotation, remove next_signer or build only the APK"
+          .to_string(),
+      );
+    }
+    Self::for_jarsigner(config, "AAB")
+  }
+
+  /// Resolves the configuration for signing the package with jarsigner.
+  pub fn for_jarsigner(config: &SigningConfig, package: &str) -> Result<Self, String> {
+    if config.next_signer.is_some() {
+      return Err(format!(
+        "the {} is signed by jarsigner, which doesn't support key rotation, remove next_signer",
+        package
+      ));
+    }
+    if config.signer.key_alias.is_none() {
+      return Err(format!(
+        "signing the {} needs the key_alias of the signing metadata",
+        package
+      ));
+    }
+    Self::from_config(config)
+  }
+
+  /// The arguments of `jarsigner` signing the AAB, or the APK without apksigner, in place. The
+  /// passwords are read from the environment of [GradleSigning::jarsigner_envs].
+  pub fn jarsigner_args(&self, aab: &Path) -> Vec<OsString> {
+    let mut args: Vec<OsString> = vec!["-keystore".into(), self.keystore.clone().into()];
+    if let Some(keystore_type) = &self.keystore_type {
+      args.push("-storetype".into());
+      args.push(keystore_type.into());
+    }
+    args.extend(
+      [
+        "-storepass:env",
+        JARSIGNER_STORE_PASSWORD_ENV,
+        "-keypass:env",
+        JARSIGNER_KEY_PASSWORD_ENV,
+      ]
+      .map(OsString::from),
+    );
+    args.push(aab.into());
+    args.push(self.key_alias.clone().into());
+    args
+  }
+
+  /// The environment of jarsigner with the passwords.
+  pub fn jarsigner_envs(&self) -> [(&'static str, &str); 2] {
+    [
+      (JARSIGNER_STORE_PASSWORD_ENV, &self.store_password),
+      (JARSIGNER_KEY_PASSWORD_ENV, &self.key_password),
+    ]
+  }
+}
+
+fn push_signer_args(args: &mut Vec<OsString>, signer: &SignerConfig) {
+  args.push("--ks".into());
+  args.push(signer.keystore.clone().into());
+  if let Some(keystore_type) = &signer.keystore_type {
+    args.push("--ks-type".into());
+    args.push(keystore_type.to_uppercase().into());
+  }
+  args.push("--ks-pass".into());
+  args.push(signer.store_pass.clone().into());
+  if let Some(key_alias) = &signer.key_alias {
+    args.push("--ks-key-alias".into());
+    args.push(key_alias.into());
+  }
+  if let Some(key_pass) = &signer.key_pass {
+    args.push("--key-pass".into());
+    args.push(key_pass.into());
+  }
+}
+
+/// Builds the arguments of `apksigner sign` for the configuration.
+/// With v4 signing apksigner writes the v4 signature next to the output as `<output>.idsig`.
+pub fn build_apksigner_args(
+  config: &SigningConfig,
+  v4_signing: bool,
+  input: &Path,
+  output: &Path,
+) -> Vec<OsString> {
+  let mut args: Vec<OsString> = vec!["sign".into()];
+  push_signer_args(&mut args, &config.signer);
+  if let Some(next_signer) = &config.next_signer {
+    args.push("--next-signer".into());
+    push_signer_args(&mut args, next_signer);
+  }
+  if let Some(lineage) = &config.lineage {
+    args.push("--lineage".into());
+    args.push(lineage.clone().into());
+  }
+  if let Some(rotation_min_sdk_version) = config.rotation_min_sdk_version {
+    args.push("--rotation-min-sdk-version".into());
+    args.push(rotation_min_sdk_version.to_string().into());
+  }
+  if v4_signing {
+    args.push("--v4-signing-enabled".into());
+    args.push("true".into());
+  }
+  args.push("--out".into());
+  args.push(output.into());
+  args.push(input.into());
+  args
+}
+
+/// Explains an apksigner failure caused by a wrong password. apksigner reports a wrong store
+/// password as an IOException from loading the keystore, and a wrong key password as a failure to
+/// obtain the key.
+pub fn diagnose_apksigner_failure(output: &str) -> Option<&'static str> {
+  if output.contains("Wrong password?") || output.contains("Cannot recover key") {
+    Some("The key password is wrong. Check key_pass or key_pass_env of the signing metadata, or --key-pass.")
+  } else if output.contains("password was incorrect")
+    || output.contains("Keystore was tampered with")
+  {
+    Some("The keystore password is wrong. Check keystore_pass or keystore_pass_env of the signing metadata, or --ks-pass.")
+  } else {
+    None
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::ffi::OsString;
+  use std::path::Path;
+
+  use toml::Table;
+
+  use crate::gradle_dsl::GradleDsl;
+  use crate::signing::{
+    build_apksigner_args, choose_apk_signer, diagnose_apksigner_failure, parse_signing_config,
+    parse_signing_mode, resolve_v4_signing, ApkSigner, GradleSigning, SigningMode,
+  };
+
+  fn parse(metadata: &str) -> Result<Option<crate::signing::SigningConfig>, String> {
+    let table = metadata.parse::<Table>().unwrap();
+    parse_signing_config(Some(&table), Path::new("/project"), None, None, None)
+  }
+
+  #[test]
+  fn rotation_args() {
+    let config = parse(
+      r#"
+keystore = "keys/old.p12"
+keystore_type = "pkcs12"
+keystore_pass_env = "OLD_STORE_PASS"
+key_alias = "release"
+key_pass = "file:keys/old.pass"
+lineage = "keys/lineage.bin"
+rotation_min_sdk_version = 33
+
+[next_signer]
+keystore = "keys/new.p12"
+keystore_pass_env = "NEW_STORE_PASS"
+key_pass_env = "NEW_KEY_PASS"
+"#,
+    )
+    .unwrap()
+    .unwrap();
+
+    let args = build_apksigner_args(&config, false, Path::new("in.apk"), Path::new("out.apk"));
+    let args: Vec<&str> = args.iter().map(|a| a.to_str().unwrap()).collect();
+    assert_eq!(
+      args,
+      vec![
+        "sign",
+        "--ks",
+        "/project/keys/old.p12",
+        "--ks-type",
+        "PKCS12",
+        "--ks-pass",
+        "env:OLD_STORE_PASS",
+        "--ks-key-alias",
+        "release",
+        "--key-pass",
+        "file:keys/old.pass",
+        "--next-signer",
+        "--ks",
+        "/project/keys/new.p12",
+        "--ks-pass",
+        "env:NEW_STORE_PASS",
+        "--key-pass",
+        "env:NEW_KEY_PASS",
+        "--lineage",
+        "/project/keys/lineage.bin",
+        "--rotation-min-sdk-version",
+        "33",
+        "--out",
+        "out.apk",
+        "in.apk"
+      ]
+    );
+  }
+
+  #[test]
+  fn command_line_overrides() {
+    let table = "keystore = \"release.jks\"\nkeystore_pass_env = \"X\"\nkey_pass_env = \"Y\""
+      .parse::<Table>()
+      .unwrap();
+    let config = parse_signing_config(
+      Some(&table),
+      Path::new("/project"),
+      Some("/keys/other.jks".to_string()),
+      Some("pass:a".to_string()),
+      Some("pass:b".to_string()),
+    )
+    .unwrap()
+    .unwrap();
+    assert_eq!(config.signer.keystore, Path::new("/keys/other.jks"));
+    assert_eq!(config.signer.store_pass, "pass:a");
+    assert_eq!(config.signer.key_pass.as_deref(), Some("pass:b"));
+
+    let args = build_apksigner_args(&config, true, Path::new("in.apk"), Path::new("out.apk"));
+    assert!(!args.contains(&OsString::from("--ks-type")));
+    assert!(args.contains(&OsString::from("--v4-signing-enabled")));
+  }
+
+  #[test]
+  fn invalid_configs() {
+    assert_eq!(parse("").unwrap(), None);
+    assert!(parse("keystore = \"a.jks\"").is_err());
+    assert!(parse("keystore = \"a.jks\"\nkey_pass = \"pass:a\"\nkey_pass_env = \"A\"").is_err());
+    assert!(parse(
+      "keystore = \"a.jks\"\nkeystore_pass = \"pass:a\"\n[next_signer]\nkeystore = \"b.jks\"\nkeystore_pass = \"pass:b\""
+    )
+    .is_err());
+  }
+
+  #[test]
+  fn password_errors() {
+    assert!(diagnose_apksigner_failure(
+      "Failed to load signer \"signer #1\"\njava.io.IOException: keystore password was incorrect"
+    )
+    .unwrap()
+    .starts_with("The keystore password"));
+    assert!(diagnose_apksigner_failure(
+      "Failed to load signer \"signer #1\": Failed to obtain key with alias \"release\" from release.p12. Wrong password?"
+    )
+    .unwrap()
+    .starts_with("The key password"));
+    assert_eq!(diagnose_apksigner_failure("Unsupported option"), None);
+  }
+
+  #[test]
+  fn v4_signing_defaults() {
+    assert_eq!(resolve_v4_signing(None, Some(26), Some("33.0.2")), Ok(true));
+    assert_eq!(
+      resolve_v4_signing(None, Some(16), Some("33.0.2")),
+      Ok(false)
+    );
+    assert_eq!(
+      resolve_v4_signing(None, Some(26), Some("29.0.3")),
+      Ok(false)
+    );
+    assert_eq!(resolve_v4_signing(None, None, Some("33.0.2")), Ok(false));
+    assert_eq!(
+      resolve_v4_signing(Some(true), Some(16), Some("30.0.0")),
+      Ok(true)
+    );
+    assert_eq!(
+      resolve_v4_signing(Some(false), Some(26), Some("33.0.2")),
+      Ok(false)
+    );
+    assert!(resolve_v4_signing(Some(true), Some(26), Some("29.0.3")).is_err());
+    assert!(resolve_v4_signing(Some(true), Some(26), None).is_err());
+  }
+
+  #[test]
+  fn signing_modes() {
+    assert_eq!(parse_signing_mode(None), Ok(SigningMode::Standalone));
+    assert_eq!(
+      parse_signing_mode(Some(&"gradle".into())),
+      Ok(SigningMode::Gradle)
+    );
+    assert!(parse_signing_mode(Some(&"apksigner".into())).is_err());
+  }
+
+  #[test]
+  fn gradle_signing() {
+    let config = parse(
+      r#"
+mode = "gradle"
+keystore = "it's keys/release.p12"
+keystore_type = "pkcs12"
+keystore_pass = "pass:store secret"
+key_alias = "release"
+"#,
+    )
+    .unwrap()
+    .unwrap();
+    let gradle_signing = GradleSigning::from_config(&config).unwrap();
+    let block = gradle_signing.build_gradle_block(true, GradleDsl::Groovy);
+    assert_eq!(
+      block,
+      r#"    signingConfigs {
+        release {
+            storeFile file('/project/it\'s keys/release.p12')
+            storeType 'pkcs12'
+            storePassword project.property('cargoSdlApkStorePassword')
+            keyAlias 'release'
+            keyPassword project.property('cargoSdlApkKeyPassword')
+            enableV4Signing true
+        }
+    }
+    buildTypes {
+        release {
+            signingConfig signingConfigs.release
+        }
+    }"#
+    );
+    assert!(!block.contains("secret"));
+    assert_eq!(
+      gradle_signing.build_gradle_block(false, GradleDsl::Kotlin),
+      r#"    signingConfigs {
+        create("release") {
+            storeFile = file("/project/it's keys/release.p12")
+            storeType = "pkcs12"
+            storePassword = project.property("cargoSdlApkStorePassword") as String
+            keyAlias = "release"
+            keyPassword = project.property("cargoSdlApkKeyPassword") as String
+        }
+    }
+    buildTypes {
+        getByName("release") {
+            signingConfig = signingConfigs.getByName("release")
+        }
+    }"#
+    );
+    //the key password defaults to the store password
+    assert_eq!(
+      gradle_signing.gradle_args(),
+      vec![
+        "-PcargoSdlApkStorePassword=store secret",
+        "-PcargoSdlApkKeyPassword=store secret"
+      ]
+    );
+
+    let without_alias = parse("keystore = \"k.p12\"\nkeystore_pass = \"pass:x\"")
+      .unwrap()
+      .unwrap();
+    assert!(GradleSigning::from_config(&without_alias).is_err());
+    let stdin_pass = parse("keystore = \"k.p12\"\nkeystore_pass = \"stdin\"\nkey_alias = \"a\"")
+      .unwrap()
+      .unwrap();
+    assert!(GradleSigning::from_config(&stdin_pass).is_err());
+  }
+
+  #[test]
+  fn aab_signing() {
+    let config = parse(
+      "keystore = \"keys/release.p12\"\nkeystore_type = \"pkcs12\"\nkeystore_pass = \"pass:secret\"\nkey_alias = \"release\"",
+    )
+    .unwrap()
+    .unwrap();
+    let signing = GradleSigning::for_aab(&config).unwrap();
+    let args = signing.jarsigner_args(Path::new("app-release.aab"));
+    let args: Vec<&str> = args.iter().map(|a| a.to_str().unwrap()).collect();
+    assert_eq!(
+      args,
+      vec![
+        "-keystore",
+        "/project/keys/release.p12",
+        "-storetype",
+        "pkcs12",
+        "-storepass:env",
+        "CARGO_SDL_APK_STORE_PASS",
+        "-keypass:env",
+        "CARGO_SDL_APK_KEY_PASS",
+        "app-release.aab",
+        "release"
+      ]
+    );
+    assert_eq!(
+      signing.jarsigner_envs(),
+      [
+        ("CARGO_SDL_APK_STORE_PASS", "secret"),
+        ("CARGO_SDL_APK_KEY_PASS", "secret")
+      ]
+    );
+
+    let without_alias = parse("keystore = \"k.p12\"\nkeystore_pass = \"pass:x\"")
+      .unwrap()
+      .unwrap();
+    assert!(GradleSigning::for_aab(&without_alias)
+      .err()
+      .unwrap()
+      .contains("signing the AAB needs the key_alias"));
+    let rotation = parse(
+      "keystore = \"old.p12\"\nkeystore_pass = \"pass:x\"\nkey_alias = \"a\"\nlineage = \"lineage.bin\"\n[next_signer]\nkeystore = \"new.p12\"\nkeystore_pass = \"pass:y\"",
+    )
+    .unwrap()
+    .unwrap();
+    assert!(GradleSigning::for_aab(&rotation)
+      .err()
+      .unwrap()
+      .contains("key rotation"));
+    assert!(GradleSigning::for_jarsigner(&rotation, "APK")
+      .err()
+      .unwrap()
+      .contains("the APK is signed by jarsigner"));
+  }
+
+  #[test]
+  fn apk_signer_choice() {
+    use ApkSigner::*;
+    //(apksigner, jarsigner, min sdk) -> signer, None if the APK can't be signed
+    let cases = [
+      ((true, true, Some(21)), Some(Apksigner)),
+      ((true, false, Some(30)), Some(Apksigner)),
+      ((true, false, None), Some(Apksigner)),
+      ((false, true, Some(21)), Some(Jarsigner)),
+      ((false, true, Some(29)), Some(Jarsigner)),
+      ((false, true, None), Some(Jarsigner)),
+      ((false, true, Some(30)), None),
+      ((false, true, Some(33)), None),
+      ((false, false, Some(21)), None),
+      ((false, false, None), None),
+    ];
+    for ((apksigner, jarsigner, min_sdk), expected) in cases {
+      assert_eq!(
+        choose_apk_signer(apksigner, jarsigner, min_sdk).ok(),
+        expected,
+        "{:?}",
+        (apksigner, jarsigner, min_sdk)
+      );
+    }
+
+    //a v1 signature isn't enough for the min sdk, whether or not there is a jarsigner
+    for jarsigner in [true, false] {
+      assert!(choose_apk_signer(false, jarsigner, Some(30))
+        .unwrap_err()
+        .contains("jarsigner only creates v1 signatures"));
+    }
+    assert!(choose_apk_signer(false, false, Some(21))
+      .unwrap_err()
+      .contains("set JAVA_HOME"));
+  }
+}
@@ -0,0 +1,323 @@
+use std::fs::write;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::process::{Child, Stdio};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::adb::*;
+use crate::command_runner::*;
+
+/// Upper bound for single adb calls during a launch test, so an unresponsive device can't stall
+/// CI.
+const ADB_COMMAND_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Something the launch test watches for in logcat.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LogcatEvent {
+  Ready,
+  Crash(String),
+  Anr(String),
+}
+
+/// How the launch test ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LaunchOutcome {
+  Ready,
+  StartFailed(String),
+  Crash(String),
+  Anr(String),
+  Timeout,
+  LogcatEnded,
+}
+
+impl LaunchOutcome {
+  pub fn is_success(&self) -> bool {
+    self == &LaunchOutcome::Ready
+  }
+
+  pub fn describe(&self, timeout: Duration) -> String {
+    match self {
+      LaunchOutcome::Ready => "App launched successfully.".to_string(),
+      LaunchOutcome::StartFailed(error) => format!("Unable to start the activity: {}", error),
+      LaunchOutcome::Crash(line) => format!("App crashed during launch: {}", line),
+      LaunchOutcome::Anr(line) => format!("App stopped responding during launch: {}", line),
+      LaunchOutcome::Timeout => format!(
+        "App didn't report being ready within {}s.",
+        timeout.as_secs()
+      ),
+      LaunchOutcome::LogcatEnded => {
+        "logcat stopped unexpectedly, is the device still connected?".to_string()
+      }
+    }
+  }
+}
+
+/// Checks a line of `logcat -v brief` output for the ready marker or a crash or ANR of the app.
+/// Without a ready marker, the app counts as ready once SDL's activity logs `onCreate()` or the
+/// system reports the first frame of the app as displayed.
+pub fn classify_logcat_line(
+  line: &str,
+  app_id: &str,
+  ready_marker: Option<&str>,
+) -> Option<LogcatEvent> {
+  let line = line.trim();
+  let crash = line.contains(&format!("Process: {}, PID:", app_id))
+    || line.contains(&format!(">>> {} <<<", app_id))
+    || (line.contains("Fatal signal") && line.contains(&format!("({})", app_id)))
+    || (line.contains(&format!("Process {} (pid", app_id)) && line.ends_with("has died"));
+  if crash {
+    return Some(LogcatEvent::Crash(line.to_string()));
+  }
+  if line.contains(&format!("ANR in {}", app_id)) {
+    return Some(LogcatEvent::Anr(line.to_string()));
+  }
+
+  let ready = match ready_marker {
+    Some(marker) => line.contains(marker),
+    None => {
+      (line.starts_with("V/SDL") && line.ends_with("): onCreate()"))
+        || line.contains(&format!("Displayed {}/", app_id))
+    }
+  };
+  ready.then_some(LogcatEvent::Ready)
+}
+
+/// Extracts the error reported by `am start -W`, if any.
+pub fn parse_am_start_error(output: &str) -> Option<String> {
+  output
+    .lines()
+    .map(|line| line.trim())
+    .find(|line| line.starts_with("Error:") || line.starts_with("Exception"))
+    .map(|line| line.to_string())
+}
+
+/// Runs a bounded adb command on the device, returning its output if it succeeded in time.
+fn adb_with_timeout(args: &[&str]) -> Option<Vec<u8>> {
+  run_command_output_with_timeout(adb_command().args(args), ADB_COMMAND_TIMEOUT)
+    .ok()
+    .flatten()
+    .filter(|output| output.status.success())
+    .map(|output| output.stdout)
+}
+
+/// Streams the logcat of the device line by line through the returned receiver.
+fn spawn_logcat() -> (Child, Receiver<String>) {
+  let mut child = adb_command()
+    .args(["logcat", "-v", "brief"])
+    .stdin(Stdio::null())
+    .stdout(Stdio::piped())
+    .stderr(Stdio::null())
+    .spawn()
+    .unwrap_or_else(|e| panic!("Failed to execute command: adb logcat: {}", e));
+
+  let (sender, receiver) = channel();
+  let stdout = child.stdout.take().unwrap();
+  thread::spawn(move || {
+    for line in BufReader::new(stdout).lines() {
+      let Ok(line) = line else { break };
+      if sender.send(line).is_err() {
+        break;
+      }
+    }
+  });
+  (child, receiver)
+}
+
+/// Stops logcat and removes the app from the device. Runs on drop, so the device is also cleaned
+/// up if the launch test panics.
+struct Teardown<'a> {
+  app_id: &'a str,
+  logcat: Option<Child>,
+}
+
+impl Drop for Teardown<'_> {
+  fn drop(&mut self) {
+    if let Some(mut logcat) = self.logcat.take() {
+      let _ = logcat.kill();
+      let _ = logcat.wait();
+    }
+    let _ = adb_with_timeout(&["shell", "am", "force-stop", self.app_id]);
+    println!("Uninstalling {}...", self.app_id);
+    if adb_with_timeout(&["uninstall", self.app_id]).is_none() {
+      eprintln!("WARNING: Unable to uninstall {}.", self.app_id);
+    }
+  }
+}
+
+fn take_screenshot(path: &Path) {
+  match adb_with_timeout(&["exec-out", "screencap", "-p"]) {
+    Some(png) if !png.is_empty() => match write(path, png) {
+      Ok(()) => println!("Saved screenshot to {}", path.display()),
+      Err(e) => eprintln!("WARNING: Unable to write screenshot {:?}: {}", path, e),
+    },
+    _ => eprintln!("WARNING: Unable to take a screenshot."),
+  }
+}
+
+fn wait_for_launch(
+  logcat: &Receiver<String>,
+  app_id: &str,
+  ready_marker: Option<&str>,
+  deadline: Instant,
+) -> LaunchOutcome {
+  loop {
+    let remaining = deadline.saturating_duration_since(Instant::now());
+    match logcat.recv_timeout(remaining) {
+      Ok(line) => match classify_logcat_line(&line, app_id, ready_marker) {
+        Some(LogcatEvent::Ready) => return LaunchOutcome::Ready,
+        Some(LogcatEvent::Crash(line)) => return LaunchOutcome::Crash(line),
+        Some(LogcatEvent::Anr(line)) => return LaunchOutcome::Anr(line),
+        None => {}
+      },
+      Err(RecvTimeoutError::Timeout) => return LaunchOutcome::Timeout,
+      Err(RecvTimeoutError::Disconnected) => return LaunchOutcome::LogcatEnded,
+    }
+  }
+}
+
+/// Starts the installed app and watches logcat until it is ready, crashed or the timeout passed.
+/// The app is uninstalled afterwards, after taking a screenshot if a path is given.
+pub fn run_launch_test(
+  app_id: &str,
+  component: &str,
+  ready_marker: Option<&str>,
+  timeout: Duration,
+  screenshot: Option<&Path>,
+) -> LaunchOutcome {
+  let deadline = Instant::now() + timeout;
+  let mut teardown = Teardown {
+    app_id,
+    logcat: None,
+  };
+
+  if adb_with_timeout(&["logcat", "-c"]).is_none() {
+    eprintln!("WARNING: Unable to clear logcat.");
+  }
+  let (logcat, lines) = spawn_logcat();
+  teardown.logcat = Some(logcat);
+
+  println!("Starting {}...", component);
+  let remaining = deadline.saturating_duration_since(Instant::now());
+  let outcome = match run_command_output_with_timeout(
+    adb_command().args(["shell", "am", "start", "-W", "-n", component]),
+    remaining,
+  ) {
+    Err(e) => LaunchOutcome::StartFailed(e.to_string()),
+    Ok(None) => LaunchOutcome::Timeout,
+    Ok(Some(output)) => {
+      let stdout = String::from_utf8_lossy(&output.stdout);
+      match parse_am_start_error(&stdout) {
+        Some(error) => LaunchOutcome::StartFailed(error),
+        None if !output.status.success() => {
+          LaunchOutcome::StartFailed(String::from_utf8_lossy(&output.stderr).trim().to_string())
+        }
+        None => wait_for_launch(&lines, app_id, ready_marker, deadline),
+      }
+    }
+  };
+
+  if let Some(path) = screenshot {
+    take_screenshot(path);
+  }
+  drop(teardown);
+  outcome
+}
+
+#[cfg(test)]
+mod test {
+  use crate::smoke_test::{classify_logcat_line, parse_am_start_error, LogcatEvent};
+
+  const APP_ID: &str = "com.example.game";
+
+  #[test]
+  fn ready_lines() {
+    assert_eq!(
+      classify_logcat_line("V/SDL     ( 4242): onCreate()", APP_ID, None),
+      Some(LogcatEvent::Ready)
+    );
+    assert_eq!(
+      classify_logcat_line(
+        "I/ActivityTaskManager(  600): Displayed com.example.game/android.app.NativeActivity: +412ms",
+        APP_ID,
+        None
+      ),
+      Some(LogcatEvent::Ready)
+    );
+    assert_eq!(
+      classify_logcat_line(
+        "I/ActivityTaskManager(  600): Displayed com.other/.Main: +1s",
+        APP_ID,
+        None
+      ),
+      None
+    );
+    assert_eq!(
+      classify_logcat_line(
+        "I/game    ( 4242): main menu shown",
+        APP_ID,
+        Some("main menu shown")
+      ),
+      Some(LogcatEvent::Ready)
+    );
+    assert_eq!(
+      classify_logcat_line(
+        "V/SDL     ( 4242): onCreate()",
+        APP_ID,
+        Some("main menu shown")
+      ),
+      None
+    );
+  }
+
+  #[test]
+  fn crash_lines() {
+    for line in [
+      "E/AndroidRuntime( 4242): Process: com.example.game, PID: 4242",
+      "F/libc    ( 4242): Fatal signal 11 (SIGSEGV), code 1 (SEGV_MAPERR), fault addr 0x0 in tid 4260 (SDLThread), pid 4242 (com.example.game)",
+      "F/DEBUG   ( 4300): pid: 4242, tid: 4260, name: SDLThread  >>> com.example.game <<<",
+      "I/ActivityManager(  600): Process com.example.game (pid 4242) has died",
+    ] {
+      assert!(
+        matches!(
+          classify_logcat_line(line, APP_ID, None),
+          Some(LogcatEvent::Crash(_))
+        ),
+        "{}",
+        line
+      );
+    }
+    assert!(matches!(
+      classify_logcat_line(
+        "E/ActivityManager(  600): ANR in com.example.game (com.example.game/.MainActivity)",
+        APP_ID,
+        None
+      ),
+      Some(LogcatEvent::Anr(_))
+    ));
+    assert_eq!(
+      classify_logcat_line(
+        "E/AndroidRuntime( 1234): Process: com.other, PID: 1234",
+        APP_ID,
+        None
+      ),
+      None
+    );
+  }
+
+  #[test]
+  fn am_start_errors() {
+    assert_eq!(
+      parse_am_start_error("Starting: Intent { cmp=com.example.game/.MainActivity }\nStatus: ok\nLaunchState: COLD\nTotalTime: 412\n"),
+      None
+    );
+    assert_eq!(
+      parse_am_start_error("Starting: Intent { cmp=com.example.game/.Missing }\nError type 3\nError: Activity class {com.example.game/com.example.game.Missing} does not exist.\n"),
+      Some(
+        "Error: Activity class {com.example.game/com.example.game.Missing} does not exist."
+          .to_string()
+      )
+    );
+  }
+}
@@ -0,0 +1,294 @@
+use std::fs::{create_dir_all, write};
+use std::path::{Path, PathBuf};
+
+use image::imageops::FilterType;
+
+use crate::generated_files::*;
+use crate::icon::*;
+use crate::util::*;
+
+/// Name of the generated splash theme, set on the activity.
+pub const SPLASH_THEME_NAME: &str = "CargoSdlApk.Splash";
+
+/// Drawable of the legacy splash screen, the background color with the centered icon.
+const SPLASH_DRAWABLE_NAME: &str = "cargo_sdl_apk_splash";
+const SPLASH_ICON_NAME: &str = "cargo_sdl_apk_splash_icon";
+const SPLASH_BRANDING_NAME: &str = "cargo_sdl_apk_splash_branding";
+
+/// Size in dp of the splash screen icon and the box the branding image is fitted into, as
+/// specified by the SplashScreen API.
+const SPLASH_ICON_SIZE: u32 = 240;
+const SPLASH_BRANDING_SIZE: (u32, u32) = (200, 80);
+
+/// Scale of the drawable densities relative to mdpi.
+const DRAWABLE_DENSITIES: &[(&str, f32)] = &[
+  ("mdpi", 1.0),
+  ("hdpi", 1.5),
+  ("xhdpi", 2.0),
+  ("xxhdpi", 3.0),
+  ("xxxhdpi", 4.0),
+];
+
+/// Splash screen shown while the app starts, from `splash` in the android metadata.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SplashConfig {
+  pub background_color: String,
+  pub icon: PathBuf,
+  pub branding_image: Option<PathBuf>,
+}
+
+/// Checks for a color in one of the hex formats android resources accept, `#RGB`, `#ARGB`,
+/// `#RRGGBB` or `#AARRGGBB`.
+pub fn is_valid_color(value: &str) -> bool {
+  value.strip_prefix('#').is_some_and(|hex| {
+    matches!(hex.len(), 3 | 4 | 6 | 8) && hex.chars().all(|c| c.is_ascii_hexdigit())
+  })
+}
+
+/// Reads `background_color`, `icon` and `branding_image` of the splash android metadata. Paths are
+/// relative to the manifest.
+pub fn get_splash_config(manifest_path: &Path) -> Result<Option<SplashConfig>, String> {
+  if get_toml_entry(manifest_path, ["package", "metadata", "android", "splash"])?.is_none() {
+    return Ok(None);
+  }
+  let manifest_dir = manifest_path.parent().unwrap();
+  let get_splash_string = |key: &str| {
+    get_toml_string(
+      manifest_path,
+      ["package", "metadata", "android", "splash", key],
+    )
+  };
+
+  let background_color = get_splash_string("background_color")?
+    .ok_or("splash needs a background_color, e.g. \"#1E1E1E\"")?;
+  if !is_valid_color(&background_color) {
+    return Err(format!(
+      "Invalid splash background_color \"{}\", expected #RGB, #ARGB, #RRGGBB or #AARRGGBB",
+      background_color
+    ));
+  }
+  let icon = get_splash_string("icon")?.ok_or("splash needs an icon")?;
+
+  Ok(Some(SplashConfig {
+    background_color,
+    icon: manifest_dir.join(icon),
+    branding_image: get_splash_string("branding_image")?.map(|path| manifest_dir.join(path)),
+  }))
+}
+
+impl SplashConfig {
+  /// Renders the layer-list drawable the legacy splash theme uses as window background.
+  pub fn render_legacy_drawable(&self) -> String {
+    let mut drawable = format!(
+      "<?xml version=\"1.0\" encoding=\"utf-8\"?>
+<layer-list xmlns:android=\"http://schemas.android.com/apk/res/android\">
+    <item>
+        <shape android:shape=\"rectangle\">
+            <solid android:color=\"{}\" />
+        </shape>
+    </item>
+    <item>
+        <bitmap android:gravity=\"center\" android:src=\"@drawable/{}\" />
+    </item>
+",
+      self.background_color, SPLASH_ICON_NAME
+    );
+    if self.branding_image.is_some() {
+      drawable.push_str(&format!(
+        "    <item android:bottom=\"48dp\">
+        <bitmap android:gravity=\"bottom|center_horizontal\" android:src=\"@drawable/{}\" />
+    </item>
+",
+        SPLASH_BRANDING_NAME
+      ));
+    }
+    drawable.push_str("</layer-list>\n");
+    drawable
+  }
+
+  /// Renders the splash style. With `platform_splash_screen` it uses the SplashScreen attributes
+  /// of android 12, which replace the window background while starting.
+  ///
+  /// There is no `postSplashScreenTheme` for the platform API, that attribute is part of the
+  /// androidx compat library. The activity keeps the splash theme after the splash screen is
+  /// dismissed, so it inherits the theme of the app and keeps the splash drawable as window
+  /// background until SDL draws its surface, instead of flashing black in between.
+  pub fn render_style(&self, parent: &str, platform_splash_screen: bool) -> String {
+    let window_background = format!("@drawable/{}", SPLASH_DRAWABLE_NAME);
+    let icon = format!("@drawable/{}", SPLASH_ICON_NAME);
+    let branding = format!("@drawable/{}", SPLASH_BRANDING_NAME);
+
+    let mut items = vec![("android:windowBackground", &*window_background)];
+    if platform_splash_screen {
+      items.push((
+        "android:windowSplashScreenBackground",
+        &*self.background_color,
+      ));
+      items.push(("android:windowSplashScreenAnimatedIcon", &*icon));
+      if self.branding_image.is_some() {
+        items.push(("android:windowSplashScreenBrandingImage", &*branding));
+      }
+    }
+
+    let mut style = format!(
+      "    <style name=\"{}\" parent=\"{}\">\n",
+      SPLASH_THEME_NAME, parent
+    );
+    for (name, value) in items {
+      style.push_str(&format!(
+        "        <item name=\"{}\">{}</item>\n",
+        name, value
+      ));
+    }
+    style.push_str("    </style>\n");
+    style
+  }
+}
+
+/// Writes the image scaled to fit into the given size in dp into all drawable densities.
+fn write_drawables(
+  source: &Path,
+  name: &str,
+  (width, height): (u32, u32),
+  project_dir: &Path,
+  generated: &mut GeneratedFiles,
+) -> Result<(), String> {
+  let image = load_image(source)?;
+  for (density, scale) in DRAWABLE_DENSITIES {
+    let file_name = format!("app/src/main/res/drawable-{}/{}.png", density, name);
+    if !generated.claim(&file_name) {
+      continue;
+    }
+    let scaled = image.resize(
+      (width as f32 * scale) as u32,
+      (height as f32 * scale) as u32,
+      FilterType::Lanczos3,
+    );
+    let dest = project_dir.join(file_name);
+    create_dir_all(dest.parent().unwrap())
+      .map_err(|e| format!("Unable to create directory {:?}: {}", dest.parent(), e))?;
+    write(&dest, encode_png(&scaled))
+      .map_err(|e| format!("Unable to write image {:?}: {}", dest, e))?;
+  }
+  Ok(())
+}
+
+/// Writes the splash images into the drawable densities and the layer-list drawable of the legacy
+/// splash screen.
+pub fn write_splash_drawables(
+  config: &SplashConfig,
+  project_dir: &Path,
+  generated: &mut GeneratedFiles,
+) -> Result<(), String> {
+  write_drawables(
+    &config.icon,
+    SPLASH_ICON_NAME,
+    (SPLASH_ICON_SIZE, SPLASH_ICON_SIZE),
+    project_dir,
+    generated,
+  )?;
+  if let Some(branding_image) = &config.branding_image {
+    write_drawables(
+      branding_image,
+      SPLASH_BRANDING_NAME,
+      SPLASH_BRANDING_SIZE,
+      project_dir,
+      generated,
+    )?;
+  }
+
+  let file_name = format!("app/src/main/res/drawable/{}.xml", SPLASH_DRAWABLE_NAME);
+  if generated.claim(&file_name) {
+    let dest = project_dir.join(file_name);
+    create_dir_all(dest.parent().unwrap())
+      .map_err(|e| format!("Unable to create directory {:?}: {}", dest.parent(), e))?;
+    write(&dest, config.render_legacy_drawable())
+      .map_err(|e| format!("Unable to write file {:?}: {}", dest, e))?;
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod test {
+  use std::path::PathBuf;
+
+  use image::{DynamicImage, ImageReader, RgbImage};
+  use tempfile::tempdir;
+
+  use crate::generated_files::GeneratedFiles;
+  use crate::splash::{is_valid_color, write_splash_drawables, SplashConfig};
+
+  #[test]
+  fn colors() {
+    for valid in ["#fff", "#8000", "#1E1E1E", "#ff1e1e1e"] {
+      assert!(is_valid_color(valid), "{}", valid);
+    }
+    for invalid in ["fff", "#ff", "#12345", "#1e1e1g", "red", "#"] {
+      assert!(!is_valid_color(invalid), "{}", invalid);
+    }
+  }
+
+  #[test]
+  fn styles() {
+    let config = SplashConfig {
+      background_color: "#1E1E1E".to_string(),
+      icon: PathBuf::from("splash.png"),
+      branding_image: None,
+    };
+    assert_eq!(
+      config.render_style("@style/CargoSdlApk.Theme", true),
+      "    <style name=\"CargoSdlApk.Splash\" parent=\"@style/CargoSdlApk.Theme\">
+        <item name=\"android:windowBackground\">@drawable/cargo_sdl_apk_splash</item>
+        <item name=\"android:windowSplashScreenBackground\">#1E1E1E</item>
+        <item name=\"android:windowSplashScreenAnimatedIcon\">@drawable/cargo_sdl_apk_splash_icon</item>
+    </style>
+"
+    );
+    assert!(!config
+      .render_style("p", false)
+      .contains("windowSplashScreen"));
+    assert!(!config.render_legacy_drawable().contains("branding"));
+  }
+
+  #[test]
+  fn drawables() {
+    let dir = tempdir().unwrap();
+    let icon = dir.path().join("splash.png");
+    DynamicImage::ImageRgb8(RgbImage::new(512, 512))
+      .save(&icon)
+      .unwrap();
+    let branding = dir.path().join("branding.png");
+    DynamicImage::ImageRgb8(RgbImage::new(1000, 200))
+      .save(&branding)
+      .unwrap();
+    let config = SplashConfig {
+      background_color: "#000".to_string(),
+      icon,
+      branding_image: Some(branding),
+    };
+
+    let project = dir.path().join("project");
+    let mut generated = GeneratedFiles::load(&project, false);
+    write_splash_drawables(&config, &project, &mut generated).unwrap();
+
+    let dimensions = |file: &str| {
+      let image = ImageReader::open(project.join("app/src/main/res").join(file))
+        .unwrap()
+        .decode()
+        .unwrap();
+      (image.width(), image.height())
+    };
+    assert_eq!(
+      dimensions("drawable-hdpi/cargo_sdl_apk_splash_icon.png"),
+      (360, 360)
+    );
+    //the branding image keeps its aspect ratio
+    assert_eq!(
+      dimensions("drawable-mdpi/cargo_sdl_apk_splash_branding.png"),
+      (200, 40)
+    );
+    assert!(project
+      .join("app/src/main/res/drawable/cargo_sdl_apk_splash.xml")
+      .is_file());
+  }
+}
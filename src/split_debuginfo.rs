@@ -0,0 +1,176 @@
+use std::fs::{copy, read_to_string, remove_file, write};
+use std::path::{Path, PathBuf};
+
+use crate::native_libs::*;
+use crate::util::*;
+
+/// Suffix of the file next to a split debug file recording the build id of its library.
+const BUILD_ID_SUFFIX: &str = ".build-id";
+
+/// Where the DWARF of the native library goes, configured by `split_debuginfo` in the android
+/// metadata.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SplitDebuginfo {
+  /// Inside the library, the default of rustc.
+  Off,
+  /// Packed into a `.dwp` next to the library, which keeps the unstripped library small.
+  Packed,
+}
+
+impl SplitDebuginfo {
+  pub fn parse(value: &str) -> Result<Self, String> {
+    match value {
+      "off" => Ok(SplitDebuginfo::Off),
+      "packed" => Ok(SplitDebuginfo::Packed),
+      "unpacked" => Err(
+        "split_debuginfo \"unpacked\" leaves a .dwo per object file in the target dir, which \
+        isn't collected for symbolicating, use \"packed\""
+          .to_string(),
+      ),
+      _ => Err(format!(
+        "Unknown split_debuginfo \"{}\", expected \"packed\" or \"off\"",
+        value
+      )),
+    }
+  }
+
+  /// Rustflags splitting the debug info of an android target. Without debug info in the profile
+  /// rustc writes no `.dwp`.
+  pub fn rustflags(&self) -> Vec<String> {
+    match self {
+      SplitDebuginfo::Off => vec![],
+      SplitDebuginfo::Packed => vec!["-C".to_string(), "split-debuginfo=packed".to_string()],
+    }
+  }
+}
+
+/// Reads `split_debuginfo` of the android metadata, `off` without it.
+pub fn get_split_debuginfo(manifest_path: &Path) -> Result<SplitDebuginfo, String> {
+  match get_toml_string(
+    manifest_path,
+    ["package", "metadata", "android", "split_debuginfo"],
+  )? {
+    None => Ok(SplitDebuginfo::Off),
+    Some(value) => SplitDebuginfo::parse(&value),
+  }
+}
+
+/// The packed debug info of the library, `<library>.dwp` like rustc writes it. llvm-symbolizer,
+/// which llvm-addr2line and ndk-stack use, finds it there on its own.
+pub fn get_split_debug_file(library: &Path) -> PathBuf {
+  let mut path = library.as_os_str().to_os_string();
+  path.push(".dwp");
+  PathBuf::from(path)
+}
+
+fn get_build_id_file(split_debug_file: &Path) -> PathBuf {
+  let mut path = split_debug_file.as_os_str().to_os_string();
+  path.push(BUILD_ID_SUFFIX);
+  PathBuf::from(path)
+}
+
+/// Copies the packed debug info cargo wrote next to `artifact` next to `library`, the copy of the
+/// artifact in the symbols dir, with the build id of the artifact to check it against later.
+/// Returns whether there was debug info, stale debug info of `library` is removed otherwise.
+pub fn collect_split_debuginfo(artifact: &Path, library: &Path) -> Result<bool, String> {
+  let source = get_split_debug_file(artifact);
+  let target = get_split_debug_file(library);
+  let build_id_file = get_build_id_file(&target);
+  if !source.is_file() {
+    let _ = remove_file(&target);
+    let _ = remove_file(&build_id_file);
+    return Ok(false);
+  }
+  copy(&source, &target).map_err(|e| format!("unable to copy {}: {}", source.display(), e))?;
+  let build_id = read_build_id(artifact).unwrap_or_default();
+  write(&build_id_file, format!("{}\n", build_id))
+    .map_err(|e| format!("unable to write {}: {}", build_id_file.display(), e))?;
+  Ok(true)
+}
+
+/// Checks that the packed debug info next to the library was split off this build of it, by the
+/// recorded build id. Libraries without split debug info pass.
+pub fn check_split_debuginfo(library: &Path) -> Result<(), String> {
+  let split_debug_file = get_split_debug_file(library);
+  if !split_debug_file.is_file() {
+    return Ok(());
+  }
+  let recorded = read_to_string(get_build_id_file(&split_debug_file))
+    .map(|build_id| build_id.trim().to_string())
+    .unwrap_or_default();
+  let actual = read_build_id(library);
+  if recorded.is_empty() || actual.as_deref() != Some(recorded.as_str()) {
+    return Err(format!(
+      "{} (BuildId {}) wasn't split off {} (BuildId {}), its file and line info can't be used",
+      split_debug_file.display(),
+      if recorded.is_empty() {
+        "unknown"
+      } else {
+        &recorded
+      },
+      library.display(),
+      actual.as_deref().unwrap_or("unknown")
+    ));
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod test {
+  use std::fs::{copy, write};
+  use std::path::Path;
+
+  use tempfile::tempdir;
+
+  use crate::split_debuginfo::{
+    check_split_debuginfo, collect_split_debuginfo, get_split_debug_file, SplitDebuginfo,
+  };
+
+  #[test]
+  fn split_debuginfo_values() {
+    assert_eq!(Ok(SplitDebuginfo::Packed), SplitDebuginfo::parse("packed"));
+    assert_eq!(Ok(SplitDebuginfo::Off), SplitDebuginfo::parse("off"));
+    assert!(SplitDebuginfo::parse("unpacked")
+      .unwrap_err()
+      .contains("use \"packed\""));
+    assert!(SplitDebuginfo::parse("yes").is_err());
+    assert_eq!(
+      vec!["-C", "split-debuginfo=packed"],
+      SplitDebuginfo::Packed.rustflags()
+    );
+  }
+
+  #[test]
+  fn split_debug_files() {
+    let dir = tempdir().unwrap();
+    let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/native");
+    let artifact = dir.path().join("libgame-1234.so");
+    let library = dir.path().join("libmain.so");
+    copy(fixtures.join("sdl_main.so"), &artifact).unwrap();
+    copy(&artifact, &library).unwrap();
+    assert_eq!(
+      dir.path().join("libmain.so.dwp"),
+      get_split_debug_file(&library)
+    );
+
+    //without debug info nothing is collected
+    assert_eq!(Ok(false), collect_split_debuginfo(&artifact, &library));
+    assert_eq!(Ok(()), check_split_debuginfo(&library));
+
+    write(get_split_debug_file(&artifact), "dwarf").unwrap();
+    assert_eq!(Ok(true), collect_split_debuginfo(&artifact, &library));
+    assert!(get_split_debug_file(&library).is_file());
+    assert_eq!(Ok(()), check_split_debuginfo(&library));
+
+    //a library of another build next to the old debug info
+    copy(fixtures.join("no_sdl_main.so"), &library).unwrap();
+    assert!(check_split_debuginfo(&library)
+      .unwrap_err()
+      .contains("wasn't split off"));
+
+    //the debug info of a build without it is removed
+    std::fs::remove_file(get_split_debug_file(&artifact)).unwrap();
+    assert_eq!(Ok(false), collect_split_debuginfo(&artifact, &library));
+    assert!(!get_split_debug_file(&library).exists());
+  }
+}
@@ -0,0 +1,283 @@
+use std::collections::BTreeMap;
+use std::fs::{copy, create_dir_all, read, read_dir, remove_dir_all, remove_file, write, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use zip::write::FileOptions;
+use zip::ZipWriter;
+
+use crate::native_libs::*;
+
+/// Name of the metadata of a symbols bundle, describing its files for upload steps.
+const SYMBOLS_METADATA_FILE: &str = "symbols.json";
+
+/// The debug symbols of a release build collected for crash reporting services, see
+/// [AndroidBuild::symbols_bundle](crate::AndroidBuild::symbols_bundle). Paths are absolute.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct SymbolsBundle {
+  /// The directory the bundle was collected in.
+  pub dir: PathBuf,
+  pub app_id: String,
+  pub version_code: Option<u32>,
+  pub version_name: Option<String>,
+  /// The unstripped libraries by abi, e.g. libmain.so and libSDL2.so.
+  pub libraries: BTreeMap<String, Vec<PathBuf>>,
+  /// The GNU build id of libmain.so by abi, which crash dumps refer to.
+  pub build_ids: BTreeMap<String, String>,
+  /// The libraries zipped by abi like gradle's `native-debug-symbols.zip`, the format the Play
+  /// Console takes.
+  pub native_debug_symbols: PathBuf,
+  /// The R8 mapping, if the build was minified.
+  pub mapping: Option<PathBuf>,
+  /// `symbols.json` describing the bundle.
+  pub metadata: PathBuf,
+}
+
+/// What the symbols are of, read from the android project.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SymbolsVersion {
+  pub app_id: String,
+  pub version_code: Option<u32>,
+  pub version_name: Option<String>,
+}
+
+fn json_string(s: &str) -> String {
+  let mut json = String::from("\"");
+  for c in s.chars() {
+    match c {
+      '"' => json.push_str("\\\""),
+      '\\' => json.push_str("\\\\"),
+      '\n' => json.push_str("\\n"),
+      c if c.is_control() => json.push_str(&format!("\\u{:04x}", c as u32)),
+      c => json.push(c),
+    }
+  }
+  json.push('"');
+  json
+}
+
+impl SymbolsBundle {
+  /// A path of the bundle relative to its directory, as written to `symbols.json`.
+  fn relative_path(&self, path: &Path) -> String {
+    path
+      .strip_prefix(&self.dir)
+      .unwrap_or(path)
+      .to_string_lossy()
+      .replace('\\', "/")
+  }
+
+  /// The content of `symbols.json`, with the paths relative to the bundle.
+  pub fn to_json(&self) -> String {
+    let optional = |value: Option<String>| value.unwrap_or_else(|| "null".to_string());
+    let abis: Vec<String> = self
+      .libraries
+      .iter()
+      .map(|(abi, libraries)| {
+        let libraries: Vec<String> = libraries
+          .iter()
+          .map(|library| json_string(&self.relative_path(library)))
+          .collect();
+        format!(
+          "    {}: {{\n      \"build_id\": {},\n      \"libraries\": [{}]\n    }}",
+          json_string(abi),
+          optional(self.build_ids.get(abi).map(|id| json_string(id))),
+          libraries.join(", ")
+        )
+      })
+      .collect();
+    format!(
+      "{{\n  \"app_id\": {},\n  \"version_code\": {},\n  \"version_name\": {},\n  \"abis\": {{\n{}\n  }},\n  \"native_debug_symbols\": {},\n  \"mapping\": {}\n}}\n",
+      json_string(&self.app_id),
+      optional(self.version_code.map(|code| code.to_string())),
+      optional(self.version_name.as_deref().map(json_string)),
+      abis.join(",\n"),
+      json_string(&self.relative_path(&self.native_debug_symbols)),
+      optional(
+        self
+          .mapping
+          .as_ref()
+          .map(|mapping| json_string(&self.relative_path(mapping)))
+      ),
+    )
+  }
+}
+
+/// Zips the libraries into `<abi>/<library>` entries.
+fn write_native_debug_symbols(
+  path: &Path,
+  libraries: &BTreeMap<String, Vec<PathBuf>>,
+) -> zip::result::ZipResult<()> {
+  let mut writer = ZipWriter::new(File::create(path)?);
+  for (abi, libraries) in libraries {
+    for library in libraries {
+      writer.start_file(
+        format!("{}/{}", abi, library.file_name().unwrap().to_string_lossy()),
+        FileOptions::default(),
+      )?;
+      writer.write_all(&read(library)?)?;
+    }
+  }
+  writer.finish()?;
+  Ok(())
+}
+
+/// Collects the unstripped libraries of `symbols_dir`, one dir per abi like jniLibs, and the
+/// mapping into `dir`, zips the libraries and describes all of it in `symbols.json`.
+pub fn create_symbols_bundle(
+  dir: &Path,
+  symbols_dir: &Path,
+  mapping: Option<&Path>,
+  version: SymbolsVersion,
+) -> Result<SymbolsBundle, String> {
+  create_dir_all(dir).map_err(|e| format!("unable to create {}: {}", dir.display(), e))?;
+  let libraries_dir = dir.join("symbols");
+  if libraries_dir.exists() {
+    remove_dir_all(&libraries_dir)
+      .map_err(|e| format!("unable to remove {}: {}", libraries_dir.display(), e))?;
+  }
+
+  let mut libraries: BTreeMap<String, Vec<PathBuf>> = BTreeMap::new();
+  let mut build_ids = BTreeMap::new();
+  let abi_dirs = read_dir(symbols_dir)
+    .map_err(|e| format!("can't read symbols dir {}: {}", symbols_dir.display(), e))?;
+  for abi_dir in abi_dirs.filter_map(|entry| entry.ok()) {
+    let abi = abi_dir.file_name().to_string_lossy().to_string();
+    let Ok(entries) = read_dir(abi_dir.path()) else {
+      continue;
+    };
+    let mut sources: Vec<PathBuf> = entries
+      .filter_map(|entry| entry.ok())
+      .map(|entry| entry.path())
+      .filter(|path| path.extension().is_some_and(|extension| extension == "so"))
+      .collect();
+    sources.sort();
+    for source in sources {
+      let file_name = source.file_name().unwrap();
+      if file_name == "libmain.so" {
+        if let Some(build_id) = read_build_id(&source) {
+          build_ids.insert(abi.clone(), build_id);
+        }
+      }
+      let target = libraries_dir.join(&abi).join(file_name);
+      create_dir_all(target.parent().unwrap())
+        .and_then(|_| copy(&source, &target))
+        .map_err(|e| {
+          format!(
+            "unable to copy {} to the symbols bundle: {}",
+            source.display(),
+            e
+          )
+        })?;
+      libraries.entry(abi.clone()).or_default().push(target);
+    }
+  }
+  if libraries.is_empty() {
+    return Err(format!(
+      "no native libraries in {} to collect symbols of",
+      symbols_dir.display()
+    ));
+  }
+
+  let native_debug_symbols = dir.join("native-debug-symbols.zip");
+  write_native_debug_symbols(&native_debug_symbols, &libraries)
+    .map_err(|e| format!("unable to write {}: {}", native_debug_symbols.display(), e))?;
+
+  let target = dir.join("mapping.txt");
+  let mapping = match mapping {
+    Some(source) => {
+      copy(source, &target).map_err(|e| {
+        format!(
+          "unable to copy {} to the symbols bundle: {}",
+          source.display(),
+          e
+        )
+      })?;
+      Some(target)
+    }
+    None => {
+      //of an earlier, minified build
+      let _ = remove_file(&target);
+      None
+    }
+  };
+
+  let bundle = SymbolsBundle {
+    dir: dir.to_path_buf(),
+    app_id: version.app_id,
+    version_code: version.version_code,
+    version_name: version.version_name,
+    libraries,
+    build_ids,
+    native_debug_symbols,
+    mapping,
+    metadata: dir.join(SYMBOLS_METADATA_FILE),
+  };
+  write(&bundle.metadata, bundle.to_json())
+    .map_err(|e| format!("unable to write {}: {}", bundle.metadata.display(), e))?;
+  Ok(bundle)
+}
+
+#[cfg(test)]
+mod test {
+  use std::fs::{create_dir_all, read_to_string, write, File};
+
+  use tempfile::tempdir;
+  use zip::ZipArchive;
+
+  use crate::symbols_bundle::{create_symbols_bundle, SymbolsVersion};
+
+  #[test]
+  fn symbols_bundle() {
+    let project = tempdir().unwrap();
+    let jni_libs = project.path().join("jniLibs");
+    for abi in ["arm64-v8a", "x86_64"] {
+      create_dir_all(jni_libs.join(abi)).unwrap();
+      write(jni_libs.join(abi).join("libmain.so"), "not an elf file").unwrap();
+      write(jni_libs.join(abi).join("libSDL2.so"), "sdl").unwrap();
+    }
+    let mapping = project.path().join("mapping.txt");
+    write(&mapping, "a.b -> c:").unwrap();
+
+    let dir = project.path().join("bundle");
+    let bundle = create_symbols_bundle(
+      &dir,
+      &jni_libs,
+      Some(&mapping),
+      SymbolsVersion {
+        app_id: "com.example.game".to_string(),
+        version_code: Some(12),
+        version_name: Some("1.2 \"beta\"".to_string()),
+      },
+    )
+    .unwrap();
+
+    assert_eq!(
+      vec![
+        dir.join("symbols/arm64-v8a/libSDL2.so"),
+        dir.join("symbols/arm64-v8a/libmain.so")
+      ],
+      bundle.libraries["arm64-v8a"]
+    );
+    assert_eq!(Some(dir.join("mapping.txt")), bundle.mapping);
+    let mut zip = ZipArchive::new(File::open(&bundle.native_debug_symbols).unwrap()).unwrap();
+    assert_eq!(4, zip.len());
+    assert!(zip.by_name("x86_64/libmain.so").is_ok());
+
+    let json = read_to_string(&bundle.metadata).unwrap();
+    assert!(json.contains("\"version_code\": 12,\n  \"version_name\": \"1.2 \\\"beta\\\"\""));
+    assert!(json.contains(
+      "\"arm64-v8a\": {\n      \"build_id\": null,\n      \"libraries\": [\"symbols/arm64-v8a/libSDL2.so\", \"symbols/arm64-v8a/libmain.so\"]\n    }"
+    ));
+    assert!(json.ends_with("\"native_debug_symbols\": \"native-debug-symbols.zip\",\n  \"mapping\": \"mapping.txt\"\n}\n"));
+
+    //without jniLibs there is nothing to collect
+    assert!(create_symbols_bundle(
+      &dir,
+      &project.path().join("missing"),
+      None,
+      SymbolsVersion::default()
+    )
+    .is_err());
+  }
+}
@@ -0,0 +1,221 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::util::*;
+
+/// An android target rust can build for, with the abi of its libraries in the APK.
+pub struct AndroidTarget {
+  pub rust: &'static str,
+  pub abi: &'static str,
+  /// The clang of the NDK toolchain linking for the target.
+  pub linker: &'static str,
+  /// For 32-bit targets the 64-bit target of the same architecture, which Google Play requires
+  /// next to it.
+  pub counterpart_64bit: Option<&'static str>,
+}
+
+/// The known android targets. Others can be added with `extra_targets` of the android metadata.
+pub const ANDROID_TARGETS: &[AndroidTarget] = &[
+  AndroidTarget {
+    rust: "aarch64-linux-android",
+    abi: "arm64-v8a",
+    linker: "aarch64-linux-android26-clang",
+    counterpart_64bit: None,
+  },
+  AndroidTarget {
+    rust: "armv7-linux-androideabi",
+    abi: "armeabi-v7a",
+    linker: "armv7a-linux-androideabi26-clang",
+    counterpart_64bit: Some("aarch64-linux-android"),
+  },
+  AndroidTarget {
+    rust: "i686-linux-android",
+    abi: "x86",
+    linker: "i686-linux-android26-clang",
+    counterpart_64bit: Some("x86_64-linux-android"),
+  },
+  AndroidTarget {
+    rust: "x86_64-linux-android",
+    abi: "x86_64",
+    linker: "x86_64-linux-android26-clang",
+    counterpart_64bit: None,
+  },
+  //the NDK only supports riscv64 from API level 35 on
+  AndroidTarget {
+    rust: "riscv64-linux-android",
+    abi: "riscv64",
+    linker: "riscv64-linux-android35-clang",
+    counterpart_64bit: None,
+  },
+];
+
+fn find_android_target(rust_target_name: &str) -> Option<&'static AndroidTarget> {
+  ANDROID_TARGETS.iter().find(|t| t.rust == rust_target_name)
+}
+
+/// The clang of the NDK toolchain linking for the target. Targets outside the table use the NDK
+/// naming `<triple><api level>-clang`.
+pub fn get_target_linker(rust_target_name: &str) -> String {
+  match find_android_target(rust_target_name) {
+    Some(target) => target.linker.to_string(),
+    None => format!("{}26-clang", rust_target_name),
+  }
+}
+
+/// Parses `extra_targets` of the android metadata, a table of rust targets to their abi.
+pub fn parse_extra_targets(value: &toml::Value) -> Result<BTreeMap<String, String>, String> {
+  let table = value
+    .as_table()
+    .ok_or("extra_targets must be a table of rust targets to abis")?;
+  table
+    .iter()
+    .map(|(target, abi)| match abi.as_str() {
+      Some(abi) => Ok((target.clone(), abi.to_string())),
+      None => Err(format!("extra_targets.{} must be a string", target)),
+    })
+    .collect()
+}
+
+/// The targets of `extra_targets` of the android metadata.
+pub fn get_extra_targets(manifest_path: &Path) -> Result<BTreeMap<String, String>, String> {
+  match get_toml_entry(
+    manifest_path,
+    ["package", "metadata", "android", "extra_targets"],
+  )? {
+    Some(value) => parse_extra_targets(&value),
+    None => Ok(BTreeMap::new()),
+  }
+}
+
+/// The abi of the target, looked up in the table and then in `extra_targets`.
+pub fn resolve_target_android_name(
+  rust_target_name: &str,
+  extra_targets: &BTreeMap<String, String>,
+) -> Result<String, String> {
+  if let Some(target) = find_android_target(rust_target_name) {
+    return Ok(target.abi.to_string());
+  }
+  extra_targets.get(rust_target_name).cloned().ok_or(format!(
+    "Unknown target {}, expected one of {}. Add its abi with \
+    package.metadata.android.extra_targets = {{ \"{}\" = \"<abi>\" }}",
+    rust_target_name,
+    ANDROID_TARGETS
+      .iter()
+      .map(|t| t.rust)
+      .collect::<Vec<&str>>()
+      .join(", "),
+    rust_target_name
+  ))
+}
+
+/// The abi of the target, the name of its dir in jniLibs and of the SDL build.
+pub fn get_target_android_name(
+  manifest_path: &Path,
+  rust_target_name: &str,
+) -> Result<String, String> {
+  resolve_target_android_name(rust_target_name, &get_extra_targets(manifest_path)?)
+}
+
+/// Checks that every 32-bit target comes with its 64-bit counterpart, as Google Play rejects
+/// releases with 32-bit native code only.
+pub fn check_64bit_targets(targets: &[&str]) -> Result<(), String> {
+  let missing: Vec<String> = targets
+    .iter()
+    .filter_map(|target| find_android_target(target))
+    .filter_map(|target| {
+      target
+        .counterpart_64bit
+        .map(|counterpart| (target.rust, counterpart))
+    })
+    .filter(|(_, counterpart)| !targets.contains(counterpart))
+    .map(|(target, counterpart)| format!("--target {} for {}", counterpart, target))
+    .collect();
+  if missing.is_empty() {
+    return Ok(());
+  }
+  Err(format!(
+    "Google Play rejects 32-bit native code without the 64-bit one of the same architecture. Add {}, \
+    or pass --allow-32bit-only to build for sideloading",
+    missing.join(" and ")
+  ))
+}
+
+/// The rust target building libraries for an android abi.
+pub fn get_rust_target_for_abi(abi: &str) -> Option<&'static str> {
+  ANDROID_TARGETS
+    .iter()
+    .find(|target| target.abi == abi)
+    .map(|target| target.rust)
+}
+
+#[cfg(test)]
+mod test {
+  use toml::Table;
+
+  use crate::targets::{
+    check_64bit_targets, get_rust_target_for_abi, get_target_linker, parse_extra_targets,
+    resolve_target_android_name,
+  };
+
+  #[test]
+  fn target_table() {
+    let table = "[extra_targets]\n\"loongarch64-linux-android\" = \"loongarch64\""
+      .parse::<Table>()
+      .unwrap();
+    let extra_targets = parse_extra_targets(&table["extra_targets"]).unwrap();
+    assert_eq!(
+      Ok("riscv64".to_string()),
+      resolve_target_android_name("riscv64-linux-android", &extra_targets)
+    );
+    assert_eq!(
+      Ok("loongarch64".to_string()),
+      resolve_target_android_name("loongarch64-linux-android", &extra_targets)
+    );
+    let error = resolve_target_android_name("mips-linux-android", &extra_targets).unwrap_err();
+    assert!(
+      error.ends_with(
+        "package.metadata.android.extra_targets = { \"mips-linux-android\" = \"<abi>\" }"
+      ),
+      "{}",
+      error
+    );
+
+    assert_eq!(
+      "armv7a-linux-androideabi26-clang",
+      get_target_linker("armv7-linux-androideabi")
+    );
+    assert_eq!(
+      "loongarch64-linux-android26-clang",
+      get_target_linker("loongarch64-linux-android")
+    );
+    assert_eq!(
+      Some("riscv64-linux-android"),
+      get_rust_target_for_abi("riscv64")
+    );
+
+    let table = "[extra_targets]\n\"x-linux-android\" = 1"
+      .parse::<Table>()
+      .unwrap();
+    assert!(parse_extra_targets(&table["extra_targets"]).is_err());
+  }
+
+  #[test]
+  fn targets_64bit() {
+    assert_eq!(
+      Ok(()),
+      check_64bit_targets(&["armv7-linux-androideabi", "aarch64-linux-android"])
+    );
+    assert_eq!(Ok(()), check_64bit_targets(&["aarch64-linux-android"]));
+    let error = check_64bit_targets(&[
+      "armv7-linux-androideabi",
+      "i686-linux-android",
+      "x86_64-linux-android",
+    ])
+    .unwrap_err();
+    assert!(
+      error.contains("Add --target aarch64-linux-android for armv7-linux-androideabi, or"),
+      "{}",
+      error
+    );
+  }
+}
@@ -0,0 +1,295 @@
+use std::cmp::Ordering;
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::command_runner::*;
+use crate::offline::*;
+use crate::preflight::*;
+use crate::project_template::*;
+use crate::sdk::*;
+use crate::util::*;
+
+lazy_static! {
+  static ref JAVA_VERSION_REGEX: Regex =
+    Regex::new(r#"version "(\d+)(?:\.(\d+))?"#).expect("invalid java version regex");
+}
+
+/// Newest java version each gradle version runs on, as (first gradle version, java version),
+/// newest gradle first.
+const GRADLE_MAX_JAVA_TABLE: &[(&str, u32)] = &[
+  ("8.10", 23),
+  ("8.8", 22),
+  ("8.5", 21),
+  ("8.3", 20),
+  ("7.6", 19),
+  ("7.5", 18),
+  ("7.3", 17),
+  ("7.0", 16),
+  ("6.7", 15),
+];
+
+/// The versions the android project template was made for, e.g. compileSdk 31 and the Android
+/// Gradle Plugin 7 for SDL 2.26, compileSdk 34 and AGP 8 for SDL 2.30.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TemplateInfo {
+  /// The SDL source the template comes from, `None` for the native activity template.
+  pub sdl_version: Option<SdlVersion>,
+  /// The custom template replacing the one of the SDL source.
+  pub custom_dir: Option<PathBuf>,
+  pub compile_sdk: Option<u32>,
+  pub agp_version: Option<String>,
+  pub gradle_version: Option<String>,
+}
+
+impl Display for TemplateInfo {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match (&self.custom_dir, self.sdl_version) {
+      (Some(custom_dir), _) => write!(f, "the android project template {}", custom_dir.display())?,
+      (None, Some(sdl_version)) => {
+        write!(f, "the android-project template of SDL {}", sdl_version)?
+      }
+      (None, None) => write!(f, "the native activity template")?,
+    }
+    let mut versions = vec![];
+    if let Some(compile_sdk) = self.compile_sdk {
+      versions.push(format!("compileSdk {}", compile_sdk));
+    }
+    if let Some(agp_version) = &self.agp_version {
+      versions.push(format!("Android Gradle Plugin {}", agp_version));
+    }
+    if let Some(gradle_version) = &self.gradle_version {
+      versions.push(format!("gradle {}", gradle_version));
+    }
+    if !versions.is_empty() {
+      write!(f, " ({})", versions.join(", "))?;
+    }
+    Ok(())
+  }
+}
+
+/// The gradle version of a distribution name like `gradle-7.3-bin`.
+pub fn get_distribution_gradle_version(distribution: &str) -> &str {
+  let version = distribution.strip_prefix("gradle-").unwrap_or(distribution);
+  version
+    .strip_suffix("-bin")
+    .or_else(|| version.strip_suffix("-all"))
+    .unwrap_or(version)
+}
+
+/// Extracts the major java version from the output of `java -version`, where java 8 and older
+/// report themselves as `1.8`.
+pub fn parse_java_version(output: &str) -> Option<u32> {
+  let captures = JAVA_VERSION_REGEX.captures(output)?;
+  let major: u32 = captures[1].parse().ok()?;
+  if major == 1 {
+    captures.get(2)?.as_str().parse().ok()
+  } else {
+    Some(major)
+  }
+}
+
+/// The oldest java version the Android Gradle Plugin runs on.
+pub fn get_agp_min_java_version(agp_version: &str) -> u32 {
+  if compare_versions(agp_version, "8") != Ordering::Less {
+    17
+  } else if compare_versions(agp_version, "7") != Ordering::Less {
+    11
+  } else {
+    8
+  }
+}
+
+/// The newest java version the gradle version runs on, `None` for gradle versions too old to
+/// know.
+pub fn get_gradle_max_java_version(gradle_version: &str) -> Option<u32> {
+  GRADLE_MAX_JAVA_TABLE
+    .iter()
+    .find(|(first, _)| compare_versions(gradle_version, first) != Ordering::Less)
+    .map(|(_, java)| *java)
+}
+
+/// Reads the versions of the project template.
+pub fn get_template_info(manifest_path: &Path) -> Result<TemplateInfo, String> {
+  let activity_kind = get_activity_kind(manifest_path)?;
+  let template = ProjectTemplate::for_manifest(manifest_path)?;
+  let custom_dir = match activity_kind {
+    ActivityKind::Sdl => get_custom_template_dir(manifest_path)?,
+    ActivityKind::Native => None,
+  };
+  Ok(TemplateInfo {
+    sdl_version: match activity_kind {
+      ActivityKind::Sdl if custom_dir.is_none() => read_sdl_source_version(&get_env_path("SDL")),
+      _ => None,
+    },
+    custom_dir,
+    compile_sdk: get_template_compile_sdk_version(&template),
+    agp_version: template
+      .read_gradle("build.gradle")
+      .and_then(|content| parse_android_gradle_plugin_version(&content)),
+    gradle_version: template
+      .read("gradle/wrapper/gradle-wrapper.properties")
+      .and_then(|content| parse_gradle_distribution(&content))
+      .map(|distribution| get_distribution_gradle_version(&distribution).to_string()),
+  })
+}
+
+/// A tool of the JDK gradle is started with like `java`, from `JAVA_HOME` or else the PATH.
+pub fn get_jdk_tool(name: &str) -> PathBuf {
+  match std::env::var_os("JAVA_HOME") {
+    Some(java_home) => Path::new(&java_home).join("bin").join(name),
+    None => PathBuf::from(name),
+  }
+}
+
+/// Whether the tool of the JDK can be run.
+pub fn is_jdk_tool_available(name: &str) -> bool {
+  run_command_output(Command::new(get_jdk_tool(name)).arg("-help")).is_ok()
+}
+
+/// The major version of the java gradle is started with, `None` if it can't be run.
+pub fn get_java_version() -> Option<u32> {
+  let output = run_command_output(Command::new(get_jdk_tool("java")).arg("-version")).ok()?;
+  //java prints its version to stderr
+  parse_java_version(&String::from_utf8_lossy(&output.stderr))
+    .or_else(|| parse_java_version(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Compares the template against the configured min sdk version and the java version, returning
+/// the problems with how to fix them.
+pub fn find_template_incompatibilities(
+  template: &TemplateInfo,
+  min_sdk_version: Option<u32>,
+  java_version: Option<u32>,
+) -> Vec<String> {
+  let mut problems = vec![];
+
+  if let (Some(min_sdk), Some(compile_sdk)) = (min_sdk_version, template.compile_sdk) {
+    if min_sdk > compile_sdk {
+      problems.push(format!(
+        "min_sdk_version {} is newer than compileSdk {}. Set min_sdk_version to at most {} in \
+        [package.metadata.android] or use SDL with a newer template",
+        min_sdk, compile_sdk, compile_sdk
+      ));
+    }
+  }
+
+  if let (Some(java), Some(agp_version)) = (java_version, &template.agp_version) {
+    let min_java = get_agp_min_java_version(agp_version);
+    if java < min_java {
+      problems.push(format!(
+        "Android Gradle Plugin {} needs java {} or newer, but java is {}. Point JAVA_HOME to a \
+        JDK {}",
+        agp_version, min_java, java, min_java
+      ));
+    }
+  }
+
+  if let (Some(java), Some(gradle_version)) = (java_version, &template.gradle_version) {
+    if let Some(max_java) = get_gradle_max_java_version(gradle_version) {
+      if java > max_java {
+        problems.push(format!(
+          "gradle {} runs on java {} at most, but java is {}. Point JAVA_HOME to a JDK between \
+          {} and {}",
+          gradle_version,
+          max_java,
+          java,
+          template
+            .agp_version
+            .as_deref()
+            .map_or(8, get_agp_min_java_version),
+          max_java
+        ));
+      }
+    }
+  }
+
+  problems
+}
+
+/// Checks before gradle runs that the project template fits the configured sdk settings and the
+/// installed java, so a mismatch is reported with its fix instead of as a gradle failure.
+pub fn check_template_compatibility(manifest_path: &Path) -> Result<(), String> {
+  let template = get_template_info(manifest_path)?;
+  let problems = find_template_incompatibilities(
+    &template,
+    get_metadata_min_sdk_version(manifest_path)?,
+    get_java_version(),
+  );
+  if problems.is_empty() {
+    return Ok(());
+  }
+  Err(format!(
+    "The project doesn't fit {}:\n  {}",
+    template,
+    problems.join("\n  ")
+  ))
+}
+
+#[cfg(test)]
+mod test {
+  use crate::preflight::SdlVersion;
+  use crate::template_compat::{
+    find_template_incompatibilities, get_agp_min_java_version, get_distribution_gradle_version,
+    get_gradle_max_java_version, parse_java_version, TemplateInfo,
+  };
+
+  fn template(compile_sdk: u32, agp_version: &str, gradle_version: &str) -> TemplateInfo {
+    TemplateInfo {
+      sdl_version: Some(SdlVersion(2, 26, 4)),
+      custom_dir: None,
+      compile_sdk: Some(compile_sdk),
+      agp_version: Some(agp_version.to_string()),
+      gradle_version: Some(gradle_version.to_string()),
+    }
+  }
+
+  #[test]
+  fn template_versions() {
+    assert_eq!(get_distribution_gradle_version("gradle-8.1.1-bin"), "8.1.1");
+    assert_eq!(get_distribution_gradle_version("gradle-7.3-all"), "7.3");
+    assert_eq!(
+      template(31, "7.0.3", "7.3").to_string(),
+      "the android-project template of SDL 2.26.4 (compileSdk 31, Android Gradle Plugin 7.0.3, \
+      gradle 7.3)"
+    );
+  }
+
+  #[test]
+  fn java_versions() {
+    assert_eq!(
+      parse_java_version("openjdk version \"17.0.8\" 2023-07-18\n"),
+      Some(17)
+    );
+    assert_eq!(parse_java_version("java version \"1.8.0_292\"\n"), Some(8));
+    assert_eq!(
+      parse_java_version("openjdk version \"21\" 2023-09-19\n"),
+      Some(21)
+    );
+    assert_eq!(get_agp_min_java_version("7.0.3"), 11);
+    assert_eq!(get_agp_min_java_version("8.1.1"), 17);
+    assert_eq!(get_gradle_max_java_version("7.3"), Some(17));
+    assert_eq!(get_gradle_max_java_version("8.1.1"), Some(19));
+    assert_eq!(get_gradle_max_java_version("5.6"), None);
+  }
+
+  #[test]
+  fn incompatibilities() {
+    let sdl_2_26 = template(31, "7.0.3", "7.3");
+    assert!(find_template_incompatibilities(&sdl_2_26, Some(21), Some(17)).is_empty());
+    assert!(find_template_incompatibilities(&sdl_2_26, None, None).is_empty());
+
+    let problems = find_template_incompatibilities(&sdl_2_26, Some(33), Some(21));
+    assert_eq!(problems.len(), 2, "{:?}", problems);
+    assert!(problems[0].contains("min_sdk_version to at most 31"));
+    assert!(problems[1].contains("JDK between 11 and 17"));
+
+    let sdl_2_30 = template(34, "8.1.1", "8.1.1");
+    let problems = find_template_incompatibilities(&sdl_2_30, None, Some(11));
+    assert_eq!(problems.len(), 1, "{:?}", problems);
+    assert!(problems[0].contains("needs java 17"));
+  }
+}
@@ -0,0 +1,506 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{read, read_dir, read_to_string, remove_dir, remove_file, write};
+use std::path::{Path, PathBuf};
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::gradle_dsl::*;
+use crate::sdk::*;
+use crate::util::*;
+
+/// SDL libraries the ndk-build of the template builds next to libSDL2.so which an app can go
+/// without, packaged unless kept by `keep_sdl_libs`.
+pub const OPTIONAL_SDL_LIBS: &[&str] = &["hidapi"];
+
+/// Name of the file inside the android project recording what the last generation pruned.
+const PRUNE_RECORD: &str = ".cargo-sdl-apk-pruned";
+
+/// First version of the Android Gradle Plugin with the `jniLibs` block of `packagingOptions`.
+const JNI_LIBS_AGP_VERSION: &str = "4.2";
+
+lazy_static! {
+  static ref GET_LIBRARIES_REGEX: Regex =
+    Regex::new(r"(?s)String\[\]\s+getLibraries\s*\(\s*\)\s*\{.*?\}\s*;")
+      .expect("invalid getLibraries regex");
+  static ref JAVA_STRING_REGEX: Regex =
+    Regex::new(r#""([^"\\]*)""#).expect("invalid java string regex");
+  static ref XML_REFERENCE_REGEX: Regex =
+    Regex::new(r"@\+?(?:([\w.]+):)?(\w+)/(\w+)").expect("invalid resource reference regex");
+  static ref CODE_REFERENCE_REGEX: Regex =
+    Regex::new(r"\bR\.(\w+)\.(\w+)").expect("invalid resource reference regex");
+}
+
+/// Whether `prune_template` of the android metadata is set.
+pub fn get_prune_template(manifest_path: &Path) -> Result<bool, String> {
+  Ok(
+    get_toml_bool(
+      manifest_path,
+      ["package", "metadata", "android", "prune_template"],
+    )?
+    .unwrap_or(false),
+  )
+}
+
+/// Parses `keep_sdl_libs` of the android metadata, names of optional SDL libraries without the
+/// `lib` prefix.
+pub fn parse_keep_sdl_libs(value: &toml::Value) -> Result<Vec<String>, String> {
+  let libs = value
+    .as_array()
+    .and_then(|libs| {
+      libs
+        .iter()
+        .map(|lib| lib.as_str().map(str::to_string))
+        .collect::<Option<Vec<String>>>()
+    })
+    .ok_or("keep_sdl_libs must be a list of SDL library names")?;
+  for lib in &libs {
+    if !OPTIONAL_SDL_LIBS.contains(&lib.as_str()) {
+      return Err(format!(
+        "Unknown SDL library \"{}\" in keep_sdl_libs, expected one of {}",
+        lib,
+        OPTIONAL_SDL_LIBS.join(", ")
+      ));
+    }
+  }
+  Ok(libs)
+}
+
+/// Reads `keep_sdl_libs` of the android metadata.
+pub fn get_keep_sdl_libs(manifest_path: &Path) -> Result<Vec<String>, String> {
+  match get_toml_entry(
+    manifest_path,
+    ["package", "metadata", "android", "keep_sdl_libs"],
+  )? {
+    Some(value) => parse_keep_sdl_libs(&value),
+    None => Ok(vec![]),
+  }
+}
+
+/// The libraries `getLibraries()` of the SDLActivity source loads on startup. Older SDL versions
+/// load hidapi there, which then can't be left out.
+pub fn loaded_sdl_libs(sdl_activity_source: &str) -> Vec<String> {
+  GET_LIBRARIES_REGEX
+    .find(sdl_activity_source)
+    .map(|method| {
+      JAVA_STRING_REGEX
+        .captures_iter(method.as_str())
+        .map(|captures| captures[1].to_string())
+        .collect()
+    })
+    .unwrap_or_default()
+}
+
+/// The optional SDL libraries to leave out of the APK, neither kept nor loaded by SDLActivity.
+pub fn get_excluded_sdl_libs(keep: &[String], loaded: &[String]) -> Vec<&'static str> {
+  OPTIONAL_SDL_LIBS
+    .iter()
+    .copied()
+    .filter(|lib| !keep.iter().any(|k| k == lib) && !loaded.iter().any(|l| l == lib))
+    .collect()
+}
+
+fn exclude_pattern(lib: &str) -> String {
+  format!("lib/*/lib{}.so", lib)
+}
+
+/// The `packagingOptions` block of the android extension in app/build.gradle leaving the libraries
+/// out, with the `jniLibs` block from AGP 4.2 on.
+pub fn sdl_lib_excludes_block(
+  libs: &[&str],
+  agp_version: Option<&str>,
+  dsl: GradleDsl,
+) -> Option<String> {
+  if libs.is_empty() {
+    return None;
+  }
+  let legacy = dsl == GradleDsl::Groovy
+    && agp_version.is_some_and(|v| compare_versions(v, JNI_LIBS_AGP_VERSION) == Ordering::Less);
+  let block = if legacy {
+    let excludes: Vec<String> = libs
+      .iter()
+      .map(|lib| format!("        exclude {}", dsl.string(&exclude_pattern(lib))))
+      .collect();
+    format!("    packagingOptions {{\n{}\n    }}", excludes.join("\n"))
+  } else {
+    let excludes: Vec<String> = libs
+      .iter()
+      .map(|lib| {
+        format!(
+          "            excludes.add({})",
+          dsl.string(&exclude_pattern(lib))
+        )
+      })
+      .collect();
+    format!(
+      "    packagingOptions {{\n        jniLibs {{\n{}\n        }}\n    }}",
+      excludes.join("\n")
+    )
+  };
+  Some(block)
+}
+
+/// The resource a file in a res dir defines, e.g. `("drawable", "splash")` for
+/// `drawable-hdpi/splash.9.png`. Files of values dirs define any number of resources.
+fn file_resource(res_subdir: &str, file_name: &str) -> Option<(String, String)> {
+  let resource_type = res_subdir.split('-').next()?;
+  if resource_type == "values" {
+    return None;
+  }
+  let name = file_name.split('.').next()?;
+  Some((resource_type.to_string(), name.to_string()))
+}
+
+/// The resources the text references, as `@type/name` in XML and `R.type.name` in code.
+/// References into the android namespace are left out.
+fn collect_references(content: &str, references: &mut BTreeSet<(String, String)>) {
+  for captures in XML_REFERENCE_REGEX.captures_iter(content) {
+    if captures.get(1).is_some_and(|ns| ns.as_str() == "android") {
+      continue;
+    }
+    references.insert((captures[2].to_string(), captures[3].to_string()));
+  }
+  for captures in CODE_REFERENCE_REGEX.captures_iter(content) {
+    references.insert((captures[1].to_string(), captures[2].to_string()));
+  }
+}
+
+fn collect_source_files(dir: &Path, files: &mut Vec<PathBuf>) {
+  let Ok(entries) = read_dir(dir) else {
+    return;
+  };
+  for path in entries.filter_map(|e| e.ok()).map(|e| e.path()) {
+    if path.is_symlink() {
+      continue;
+    }
+    if path.is_dir() {
+      collect_source_files(&path, files);
+    } else if path
+      .extension()
+      .is_some_and(|ext| ext == "xml" || ext == "java" || ext == "kt")
+    {
+      files.push(path);
+    }
+  }
+}
+
+/// Removes resources of the template in the res dir of the main source set no source of the app
+/// references, the manifest, values like strings, other resources and code. Only files which are
+/// unchanged copies of `stock_template_dir` are candidates, so files of a custom template and files
+/// generated or added to the project are never removed. Returns the removed files relative to the
+/// project dir, with their sizes.
+pub fn prune_template_resources(
+  project_dir: &Path,
+  stock_template_dir: &Path,
+) -> Vec<(String, u64)> {
+  const RES_DIR: &str = "app/src/main/res";
+  let mut candidates: BTreeMap<String, (String, String)> = BTreeMap::new();
+  if let Ok(subdirs) = read_dir(project_dir.join(RES_DIR)) {
+    for subdir in subdirs.filter_map(|e| e.ok()).map(|e| e.path()) {
+      let subdir_name = subdir.file_name().unwrap().to_string_lossy().to_string();
+      let Ok(files) = read_dir(&subdir) else {
+        continue;
+      };
+      for file in files.filter_map(|e| e.ok()).map(|e| e.path()) {
+        let file_name = file.file_name().unwrap().to_string_lossy().to_string();
+        let Some(resource) = file_resource(&subdir_name, &file_name) else {
+          continue;
+        };
+        let relative = format!("{}/{}/{}", RES_DIR, subdir_name, file_name);
+        let stock = read(stock_template_dir.join(&relative)).ok();
+        if stock.is_some() && stock == read(&file).ok() {
+          candidates.insert(relative, resource);
+        }
+      }
+    }
+  }
+
+  let mut sources = vec![];
+  collect_source_files(&project_dir.join("app/src"), &mut sources);
+  let sources: Vec<(String, String)> = sources
+    .into_iter()
+    .filter_map(|path| {
+      let relative = path
+        .strip_prefix(project_dir)
+        .ok()?
+        .to_string_lossy()
+        .replace('\\', "/");
+      Some((relative, read_to_string(&path).ok()?))
+    })
+    .collect();
+
+  //unreferenced candidates go until the rest references nothing more of them
+  let mut unreferenced: BTreeSet<String> = BTreeSet::new();
+  loop {
+    let mut references = BTreeSet::new();
+    for (relative, content) in &sources {
+      if !unreferenced.contains(relative) {
+        collect_references(content, &mut references);
+      }
+    }
+    let before = unreferenced.len();
+    for (relative, resource) in &candidates {
+      if !references.contains(resource) {
+        unreferenced.insert(relative.clone());
+      }
+    }
+    if unreferenced.len() == before {
+      break;
+    }
+  }
+
+  let mut pruned = vec![];
+  for relative in unreferenced {
+    let path = project_dir.join(&relative);
+    let size = path.metadata().map(|m| m.len()).unwrap_or(0);
+    if remove_file(&path).is_ok() {
+      //only succeeds for dirs left empty
+      let _ = remove_dir(path.parent().unwrap());
+      pruned.push((relative, size));
+    }
+  }
+  pruned
+}
+
+/// What the last generation of the android project pruned.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PruneRecord {
+  /// Removed resources relative to the project dir, with their sizes.
+  pub resources: Vec<(String, u64)>,
+  /// SDL libraries left out of the APK.
+  pub sdl_libs: Vec<String>,
+}
+
+impl PruneRecord {
+  fn render(&self) -> String {
+    let mut content = String::new();
+    for lib in &self.sdl_libs {
+      content.push_str(&format!("lib  {}\n", lib));
+    }
+    for (path, size) in &self.resources {
+      content.push_str(&format!("res  {}  {}\n", size, path));
+    }
+    content
+  }
+
+  fn parse(content: &str) -> Self {
+    let mut record = PruneRecord::default();
+    for line in content.lines() {
+      match line.split_once("  ") {
+        Some(("lib", lib)) => record.sdl_libs.push(lib.to_string()),
+        Some(("res", entry)) => {
+          if let Some((size, path)) = entry.split_once("  ") {
+            record
+              .resources
+              .push((path.to_string(), size.parse().unwrap_or(0)));
+          }
+        }
+        _ => {}
+      }
+    }
+    record
+  }
+
+  /// Records what was pruned in the project dir, removing the record if nothing was.
+  pub fn save(&self, project_dir: &Path) -> Result<(), String> {
+    let path = project_dir.join(PRUNE_RECORD);
+    if self == &PruneRecord::default() {
+      let _ = remove_file(&path);
+      return Ok(());
+    }
+    write(&path, self.render()).map_err(|e| format!("unable to write {}: {}", path.display(), e))
+  }
+
+  /// What the last generation of the project pruned, `None` if nothing.
+  pub fn load(project_dir: &Path) -> Option<Self> {
+    read_to_string(project_dir.join(PRUNE_RECORD))
+      .ok()
+      .map(|content| PruneRecord::parse(&content))
+  }
+
+  /// The total size of the removed resources.
+  pub fn resources_size(&self) -> u64 {
+    self.resources.iter().map(|(_, size)| size).sum()
+  }
+}
+
+fn find_files(dir: &Path, file_name: &str, found: &mut Vec<PathBuf>) {
+  let Ok(entries) = read_dir(dir) else {
+    return;
+  };
+  for path in entries.filter_map(|e| e.ok()).map(|e| e.path()) {
+    if path.is_dir() && !path.is_symlink() {
+      find_files(&path, file_name, found);
+    } else if path.file_name().is_some_and(|name| name == file_name) {
+      found.push(path);
+    }
+  }
+}
+
+/// The sizes of an excluded SDL library gradle still built, by ABI. The smallest build of an ABI
+/// counts, the stripped one if there is one.
+pub fn get_excluded_sdl_lib_sizes(project_dir: &Path, lib: &str) -> BTreeMap<String, u64> {
+  let mut found = vec![];
+  find_files(
+    &project_dir.join("app/build/intermediates"),
+    &format!("lib{}.so", lib),
+    &mut found,
+  );
+  let mut sizes: BTreeMap<String, u64> = BTreeMap::new();
+  for path in found {
+    let Some(abi) = path
+      .parent()
+      .and_then(|dir| dir.file_name())
+      .map(|abi| abi.to_string_lossy().to_string())
+    else {
+      continue;
+    };
+    let size = path.metadata().map(|m| m.len()).unwrap_or(0);
+    sizes
+      .entry(abi)
+      .and_modify(|s| *s = (*s).min(size))
+      .or_insert(size);
+  }
+  sizes
+}
+
+#[cfg(test)]
+mod test {
+  use std::fs::{create_dir_all, write};
+  use std::path::Path;
+
+  use tempfile::tempdir;
+
+  use crate::gradle_dsl::GradleDsl;
+  use crate::template_prune::{
+    get_excluded_sdl_libs, loaded_sdl_libs, parse_keep_sdl_libs, prune_template_resources,
+    sdl_lib_excludes_block, PruneRecord,
+  };
+
+  #[test]
+  fn sdl_lib_excludes() {
+    let old_activity = "    protected String[] getLibraries() {\n        return new String[] {\n            \"hidapi\",\n            \"SDL2\",\n            // \"SDL2_image\",\n            \"main\"\n        };\n    }\n";
+    assert_eq!(
+      vec!["hidapi", "SDL2", "SDL2_image", "main"],
+      loaded_sdl_libs(old_activity)
+    );
+    let loaded = loaded_sdl_libs(&old_activity.replace("\"hidapi\",", ""));
+    assert!(get_excluded_sdl_libs(&[], &loaded_sdl_libs(old_activity)).is_empty());
+    assert_eq!(vec!["hidapi"], get_excluded_sdl_libs(&[], &loaded));
+    assert!(get_excluded_sdl_libs(&["hidapi".to_string()], &loaded).is_empty());
+
+    let keep: toml::Table = "keep_sdl_libs = [\"hidapi\"]".parse().unwrap();
+    assert_eq!(
+      Ok(vec!["hidapi".to_string()]),
+      parse_keep_sdl_libs(&keep["keep_sdl_libs"])
+    );
+    let unknown: toml::Table = "keep_sdl_libs = [\"SDL2\"]".parse().unwrap();
+    assert!(parse_keep_sdl_libs(&unknown["keep_sdl_libs"])
+      .unwrap_err()
+      .contains("Unknown SDL library \"SDL2\""));
+
+    assert_eq!(
+      Some(
+        "    packagingOptions {\n        jniLibs {\n            excludes.add('lib/*/libhidapi.so')\n        }\n    }"
+          .to_string()
+      ),
+      sdl_lib_excludes_block(&["hidapi"], Some("8.1.1"), GradleDsl::Groovy)
+    );
+    assert_eq!(
+      Some("    packagingOptions {\n        exclude 'lib/*/libhidapi.so'\n    }".to_string()),
+      sdl_lib_excludes_block(&["hidapi"], Some("3.6.0"), GradleDsl::Groovy)
+    );
+    assert!(sdl_lib_excludes_block(&["hidapi"], None, GradleDsl::Kotlin)
+      .unwrap()
+      .contains("excludes.add(\"lib/*/libhidapi.so\")"));
+    assert_eq!(None, sdl_lib_excludes_block(&[], None, GradleDsl::Groovy));
+  }
+
+  fn write_file(dir: &Path, path: &str, content: &str) {
+    let path = dir.join(path);
+    create_dir_all(path.parent().unwrap()).unwrap();
+    write(path, content).unwrap();
+  }
+
+  #[test]
+  fn prune_resources() {
+    let template = tempdir().unwrap();
+    let project = tempdir().unwrap();
+    let res = "app/src/main/res";
+    for dir in [template.path(), project.path()] {
+      write_file(dir, &format!("{}/mipmap-hdpi/ic_launcher.png", res), "icon");
+      write_file(dir, &format!("{}/drawable/unused.xml", res), "<shape/>");
+      write_file(
+        dir,
+        &format!("{}/drawable/layered.xml", res),
+        "<layer-list><item android:drawable=\"@drawable/inner\"/></layer-list>",
+      );
+      write_file(dir, &format!("{}/drawable/inner.png", res), "inner");
+      write_file(dir, &format!("{}/drawable/from_code.png", res), "code");
+      write_file(
+        dir,
+        &format!("{}/values/strings.xml", res),
+        "<resources><string name=\"app_name\">Game</string></resources>",
+      );
+    }
+    write_file(
+      project.path(),
+      "app/src/main/AndroidManifest.xml",
+      "<application android:icon=\"@mipmap/ic_launcher\" android:theme=\"@android:style/Theme\"/>",
+    );
+    write_file(
+      project.path(),
+      "app/src/main/java/com/example/MainActivity.java",
+      "int id = R.drawable.from_code;",
+    );
+    //changed by the user, or not part of the template at all
+    write_file(
+      project.path(),
+      &format!("{}/drawable/unused.xml", res),
+      "<shape></shape>",
+    );
+    write_file(project.path(), &format!("{}/raw/mine.ogg", res), "ogg");
+    //unreferenced, like what only it references
+    write_file(
+      template.path(),
+      &format!("{}/drawable-v24/orphan.xml", res),
+      "x",
+    );
+    write_file(
+      project.path(),
+      &format!("{}/drawable-v24/orphan.xml", res),
+      "x",
+    );
+
+    let pruned = prune_template_resources(project.path(), template.path());
+    assert_eq!(
+      vec![
+        (format!("{}/drawable-v24/orphan.xml", res), 1),
+        (format!("{}/drawable/inner.png", res), 5),
+        (format!("{}/drawable/layered.xml", res), 67),
+      ],
+      pruned
+    );
+    assert!(!project.path().join(res).join("drawable-v24").exists());
+    for kept in [
+      "mipmap-hdpi/ic_launcher.png",
+      "drawable/unused.xml",
+      "drawable/from_code.png",
+      "raw/mine.ogg",
+      "values/strings.xml",
+    ] {
+      assert!(project.path().join(res).join(kept).is_file(), "{}", kept);
+    }
+
+    let record = PruneRecord {
+      resources: pruned,
+      sdl_libs: vec!["hidapi".to_string()],
+    };
+    record.save(project.path()).unwrap();
+    assert_eq!(Some(record.clone()), PruneRecord::load(project.path()));
+    assert_eq!(73, record.resources_size());
+    PruneRecord::default().save(project.path()).unwrap();
+    assert_eq!(None, PruneRecord::load(project.path()));
+  }
+}
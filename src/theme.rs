@@ -0,0 +1,209 @@
+use std::path::Path;
+
+use lazy_static::lazy_static;
+use regex::{Regex, RegexBuilder};
+
+use crate::util::*;
+
+/// Name of the generated theme. It is prefixed so it doesn't clash with styles of the template or
+/// resources added by the user.
+pub const GENERATED_THEME_NAME: &str = "CargoSdlApk.Theme";
+
+/// Theme used as parent if the manifest doesn't set one.
+const DEFAULT_PARENT_THEME: &str = "@android:style/Theme.NoTitleBar.Fullscreen";
+
+lazy_static! {
+  static ref GENERATED_STYLE_REGEX: Regex = RegexBuilder::new(&format!(
+    r#"[ \t]*<style name="{}".*?</style>\n?"#,
+    regex::escape(GENERATED_THEME_NAME)
+  ))
+  .dot_matches_new_line(true)
+  .build()
+  .expect("invalid generated style regex");
+}
+
+/// How the window is laid out around display cutouts, from `display_cutout` in the android
+/// metadata.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisplayCutoutMode {
+  ShortEdges,
+  Never,
+  Always,
+}
+
+impl DisplayCutoutMode {
+  pub fn parse(value: &str) -> Option<Self> {
+    match value {
+      "shortEdges" => Some(DisplayCutoutMode::ShortEdges),
+      "never" => Some(DisplayCutoutMode::Never),
+      "always" => Some(DisplayCutoutMode::Always),
+      _ => None,
+    }
+  }
+
+  pub fn value(&self) -> &'static str {
+    match self {
+      DisplayCutoutMode::ShortEdges => "shortEdges",
+      DisplayCutoutMode::Never => "never",
+      DisplayCutoutMode::Always => "always",
+    }
+  }
+}
+
+/// Window configuration applied through the generated theme.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ThemeConfig {
+  pub display_cutout: Option<DisplayCutoutMode>,
+  pub edge_to_edge: bool,
+}
+
+impl ThemeConfig {
+  /// Renders the generated style. The display cutout mode is only included for the `values-v28`
+  /// variant, as older platforms don't know the attribute.
+  pub fn render_style(&self, parent: &str, with_display_cutout: bool) -> String {
+    let mut items = vec![];
+    if self.edge_to_edge {
+      items.push(("android:windowTranslucentStatus", "true"));
+      items.push(("android:windowTranslucentNavigation", "true"));
+      items.push(("android:windowDrawsSystemBarBackgrounds", "true"));
+    }
+    if let (Some(display_cutout), true) = (self.display_cutout, with_display_cutout) {
+      items.push((
+        "android:windowLayoutInDisplayCutoutMode",
+        display_cutout.value(),
+      ));
+    }
+
+    let mut style = format!(
+      "    <style name=\"{}\" parent=\"{}\">\n",
+      GENERATED_THEME_NAME, parent
+    );
+    for (name, value) in items {
+      style.push_str(&format!(
+        "        <item name=\"{}\">{}</item>\n",
+        name, value
+      ));
+    }
+    style.push_str("    </style>\n");
+    style
+  }
+}
+
+/// Reads `display_cutout` and `edge_to_edge` of the android metadata. Returns `None` if the theme
+/// doesn't need to be changed.
+pub fn get_theme_config(manifest_path: &Path) -> Result<Option<ThemeConfig>, String> {
+  let display_cutout = get_toml_string(
+    manifest_path,
+    ["package", "metadata", "android", "display_cutout"],
+  )?
+  .map(|value| {
+    DisplayCutoutMode::parse(&value).ok_or_else(|| {
+      format!(
+        "Unknown display_cutout \"{}\", expected \"shortEdges\", \"never\" or \"always\"",
+        value
+      )
+    })
+  })
+  .transpose()?;
+  let edge_to_edge = get_toml_bool(
+    manifest_path,
+    ["package", "metadata", "android", "edge_to_edge"],
+  )?
+  .unwrap_or(false);
+
+  if display_cutout.is_none() && !edge_to_edge {
+    return Ok(None);
+  }
+  Ok(Some(ThemeConfig {
+    display_cutout,
+    edge_to_edge,
+  }))
+}
+
+/// The parent of the generated theme, which is the theme the manifest used before.
+pub fn get_parent_theme(manifest_theme: Option<String>) -> String {
+  manifest_theme
+    .filter(|theme| theme != &format!("@style/{}", GENERATED_THEME_NAME))
+    .unwrap_or_else(|| DEFAULT_PARENT_THEME.to_string())
+}
+
+/// Adds the generated style to the content of a values resource file, keeping everything else it
+/// contains. A previously generated style is replaced.
+pub fn merge_style(resources: Option<&str>, style: &str) -> String {
+  let resources = match resources {
+    Some(resources) if resources.contains("</resources>") => resources,
+    _ => {
+      return format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<resources>\n{}</resources>\n",
+        style
+      )
+    }
+  };
+
+  let resources = GENERATED_STYLE_REGEX.replace_all(resources, "");
+  let end = resources.rfind("</resources>").unwrap();
+  let mut merged = resources[..end].to_string();
+  if !merged.ends_with('\n') {
+    merged.push('\n');
+  }
+  merged.push_str(style);
+  merged.push_str(&resources[end..]);
+  merged
+}
+
+#[cfg(test)]
+mod test {
+  use crate::theme::{get_parent_theme, merge_style, DisplayCutoutMode, ThemeConfig};
+
+  #[test]
+  fn display_cutout_values() {
+    assert_eq!(
+      DisplayCutoutMode::parse("shortEdges"),
+      Some(DisplayCutoutMode::ShortEdges)
+    );
+    assert_eq!(DisplayCutoutMode::parse("short_edges"), None);
+  }
+
+  #[test]
+  fn styles() {
+    let config = ThemeConfig {
+      display_cutout: Some(DisplayCutoutMode::ShortEdges),
+      edge_to_edge: false,
+    };
+    let style = config.render_style("@android:style/Theme.NoTitleBar.Fullscreen", true);
+    assert_eq!(
+      style,
+      "    <style name=\"CargoSdlApk.Theme\" parent=\"@android:style/Theme.NoTitleBar.Fullscreen\">
+        <item name=\"android:windowLayoutInDisplayCutoutMode\">shortEdges</item>
+    </style>
+"
+    );
+    assert!(!config.render_style("p", false).contains("DisplayCutout"));
+  }
+
+  #[test]
+  fn merge_with_existing_resources() {
+    let user = "<resources>\n    <style name=\"AppTheme\" parent=\"p\" />\n</resources>\n";
+    let style = "    <style name=\"CargoSdlApk.Theme\" parent=\"p\">\n    </style>\n";
+    let merged = merge_style(Some(user), style);
+    assert_eq!(
+      merged,
+      "<resources>\n    <style name=\"AppTheme\" parent=\"p\" />\n    <style name=\"CargoSdlApk.Theme\" parent=\"p\">\n    </style>\n</resources>\n"
+    );
+    assert_eq!(merge_style(Some(&merged), style), merged);
+
+    assert!(merge_style(None, style).starts_with("<?xml"));
+  }
+
+  #[test]
+  fn parent_theme() {
+    assert_eq!(
+      get_parent_theme(Some("@style/AppTheme".to_string())),
+      "@style/AppTheme"
+    );
+    assert_eq!(
+      get_parent_theme(Some("@style/CargoSdlApk.Theme".to_string())),
+      "@android:style/Theme.NoTitleBar.Fullscreen"
+    );
+  }
+}
@@ -0,0 +1,136 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::{create_dir_all, read_to_string, write};
+use std::path::{Path, PathBuf};
+
+use toml::value::Value;
+use toml::Table;
+
+/// Env vars the user config can provide if they aren't set, written by `cargo sdl-apk setup`.
+pub const USER_CONFIG_ENV_VARS: &[&str] = &["ANDROID_HOME", "ANDROID_NDK_HOME", "JAVA_HOME", "SDL"];
+
+/// Config of the user for all packages, `$XDG_CONFIG_HOME/cargo-sdl-apk/config.toml` or
+/// `~/.config/cargo-sdl-apk/config.toml`.
+pub fn get_user_config_path() -> Option<PathBuf> {
+  env::var_os("XDG_CONFIG_HOME")
+    .map(PathBuf::from)
+    .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+    .map(|config| config.join("cargo-sdl-apk/config.toml"))
+}
+
+fn read_config_table(path: &Path) -> Result<Table, String> {
+  match read_to_string(path) {
+    Ok(content) => content
+      .parse::<Table>()
+      .map_err(|e| format!("Invalid user config {}: {}", path.display(), e)),
+    Err(_) => Ok(Table::new()),
+  }
+}
+
+/// Reads the `[env]` table of the user config, empty if there is none.
+pub fn read_user_config_env(path: &Path) -> Result<BTreeMap<String, String>, String> {
+  let mut values = BTreeMap::new();
+  match read_config_table(path)?.get("env") {
+    None => {}
+    Some(Value::Table(env)) => {
+      for (key, value) in env {
+        let value = value
+          .as_str()
+          .ok_or_else(|| format!("env.{} in {} must be a string", key, path.display()))?;
+        values.insert(key.clone(), value.to_string());
+      }
+    }
+    Some(_) => return Err(format!("env in {} must be a table", path.display())),
+  }
+  Ok(values)
+}
+
+/// Writes the values into the `[env]` table of the user config, keeping the other values.
+pub fn write_user_config_env(path: &Path, values: &BTreeMap<String, String>) -> Result<(), String> {
+  let mut config = read_config_table(path)?;
+  let mut env = match config.remove("env") {
+    Some(Value::Table(env)) => env,
+    _ => Table::new(),
+  };
+  for (key, value) in values {
+    env.insert(key.clone(), Value::String(value.clone()));
+  }
+  config.insert("env".to_string(), Value::Table(env));
+
+  let dir = path.parent().unwrap();
+  create_dir_all(dir).map_err(|e| format!("Unable to create {}: {}", dir.display(), e))?;
+  write(path, config.to_string()).map_err(|e| format!("Unable to write {}: {}", path.display(), e))
+}
+
+/// The values of the user config for the env vars which aren't set, as set ones take precedence.
+pub fn select_unset_env_vars(
+  values: &BTreeMap<String, String>,
+  is_set: impl Fn(&str) -> bool,
+) -> Vec<(String, String)> {
+  values
+    .iter()
+    .filter(|(key, _)| USER_CONFIG_ENV_VARS.contains(&key.as_str()) && !is_set(key))
+    .map(|(key, value)| (key.clone(), value.clone()))
+    .collect()
+}
+
+/// Sets the env vars missing from the environment from the user config, so they don't need to be
+/// exported after `cargo sdl-apk setup`. A broken config is only a warning.
+pub fn apply_user_config_env() {
+  let Some(path) = get_user_config_path() else {
+    return;
+  };
+  match read_user_config_env(&path) {
+    Ok(values) => {
+      for (key, value) in select_unset_env_vars(&values, |key| env::var_os(key).is_some()) {
+        env::set_var(key, value);
+      }
+    }
+    Err(e) => eprintln!("WARNING: {}", e),
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::collections::BTreeMap;
+  use std::fs::{read_to_string, write};
+
+  use tempfile::tempdir;
+
+  use crate::user_config::{read_user_config_env, select_unset_env_vars, write_user_config_env};
+
+  #[test]
+  fn user_config_env() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("cargo-sdl-apk/config.toml");
+    assert_eq!(Ok(BTreeMap::new()), read_user_config_env(&path));
+
+    let values = BTreeMap::from([
+      ("ANDROID_HOME".to_string(), "/opt/android-sdk".to_string()),
+      ("SDL".to_string(), "/src/SDL".to_string()),
+    ]);
+    write_user_config_env(&path, &values).unwrap();
+    write_user_config_env(
+      &path,
+      &BTreeMap::from([("SDL".to_string(), "/src/SDL2".to_string())]),
+    )
+    .unwrap();
+    let read = read_user_config_env(&path).unwrap();
+    assert_eq!(
+      Some("/opt/android-sdk"),
+      read.get("ANDROID_HOME").map(|v| v.as_str())
+    );
+    assert_eq!(Some("/src/SDL2"), read.get("SDL").map(|v| v.as_str()));
+
+    assert_eq!(
+      vec![("SDL".to_string(), "/src/SDL2".to_string())],
+      select_unset_env_vars(&read, |key| key == "ANDROID_HOME")
+    );
+
+    write(&path, "other = 1\nenv = \"x\"").unwrap();
+    assert!(read_user_config_env(&path).is_err());
+    write(&path, "other = 1\n").unwrap();
+    write_user_config_env(&path, &values).unwrap();
+    assert!(read_to_string(&path).unwrap().contains("other = 1"));
+  }
+}
@@ -1,77 +1,714 @@
 use std::collections::VecDeque;
 use std::env;
-use std::fs::read_to_string;
-use std::path::Path;
+use std::fmt::{Display, Formatter};
+use std::fs::{canonicalize, read_dir, read_to_string};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 
+use lazy_static::lazy_static;
 use toml::value::Value;
 use toml::Table;
 
-pub fn get_env_var(key: &str) -> String {
-  for (k, v) in env::vars() {
-    if k == key {
-      return v;
+use crate::error::Error;
+use crate::sdk::*;
+
+lazy_static! {
+  static ref METADATA_PROFILE: Mutex<Option<String>> = Mutex::new(None);
+}
+
+/// Sets the profile whose `[package.metadata.android.profile.<name>]` overrides apply to all
+/// following reads of the android metadata.
+pub fn set_metadata_profile(profile: Option<String>) {
+  *METADATA_PROFILE.lock().unwrap() = profile;
+}
+
+pub fn get_metadata_profile() -> Option<String> {
+  METADATA_PROFILE.lock().unwrap().clone()
+}
+
+/// Merges the override into the table. Nested tables are merged per key, other values replaced.
+fn merge_toml_tables(table: &mut Table, overrides: &Table) {
+  for (key, value) in overrides {
+    match (table.get_mut(key), value) {
+      (Some(Value::Table(base)), Value::Table(nested)) => merge_toml_tables(base, nested),
+      _ => {
+        table.insert(key.clone(), value.clone());
+      }
+    }
+  }
+}
+
+/// Applies the overrides of the profile in `profile.<name>` to the android metadata.
+pub fn apply_profile_overrides(android: &mut Table, profile: &str) {
+  let overrides = android
+    .get("profile")
+    .and_then(|profiles| profiles.get(profile))
+    .and_then(|overrides| overrides.as_table())
+    .cloned();
+  if let Some(overrides) = overrides {
+    merge_toml_tables(android, &overrides);
+  }
+}
+
+/// The values of the android metadata the profile overrides, as `key.path = value`.
+pub fn get_profile_overrides(
+  manifest_path: &Path,
+  profile: &str,
+) -> Result<Vec<String>, TomlError> {
+  fn flatten(prefix: &str, table: &Table, out: &mut Vec<String>) {
+    for (key, value) in table {
+      let path = format!("{}.{}", prefix, key);
+      match value {
+        Value::Table(nested) => flatten(&path, nested, out),
+        value => out.push(format!("{} = {}", path, value)),
+      }
+    }
+  }
+  let mut overrides = vec![];
+  if let Some(Value::Table(table)) = get_toml_entry(
+    manifest_path,
+    ["package", "metadata", "android", "profile", profile],
+  )? {
+    flatten("package.metadata.android", &table, &mut overrides);
+  }
+  Ok(overrides)
+}
+
+/// Env vars with dirs which commands running elsewhere get, like ndk-build in the SDL source, gradle
+/// in the android project or the SDL symlink in it.
+const PATH_ENV_VARS: &[&str] = &[
+  "SDL",
+  "ANDROID_HOME",
+  "ANDROID_SDK_ROOT",
+  "ANDROID_NDK_HOME",
+  "ANDROID_PROJECT_TEMPLATE",
+  "JAVA_HOME",
+];
+
+/// Makes relative paths in the env vars absolute, relative to the working directory they were
+/// given in, so they point at the same dirs wherever the manifest is.
+pub fn anchor_env_paths() {
+  for key in PATH_ENV_VARS {
+    let Some(value) = env::var_os(key).filter(|value| !value.is_empty()) else {
+      continue;
+    };
+    if Path::new(&value).is_relative() {
+      if let Ok(path) = std::path::absolute(&value) {
+        env::set_var(key, path);
+      }
+    }
+  }
+}
+
+/// The path in the environment variable. It doesn't need to be valid unicode.
+pub fn get_env_path(key: &str) -> PathBuf {
+  match env::var_os(key) {
+    Some(value) => PathBuf::from(value),
+    None => panic!("Need env var: {}", key),
+  }
+}
+
+/// Where Android Studio installs the sdk by default on Linux, macOS and Windows.
+pub fn get_default_android_home_candidates(
+  home: Option<&Path>,
+  local_app_data: Option<&Path>,
+) -> Vec<PathBuf> {
+  let mut candidates = vec![];
+  if let Some(home) = home {
+    candidates.push(home.join("Android/Sdk"));
+    candidates.push(home.join("Library/Android/sdk"));
+  }
+  if let Some(local_app_data) = local_app_data {
+    candidates.push(local_app_data.join("Android").join("Sdk"));
+  }
+  candidates
+}
+
+/// Reads `sdk.dir` of a local.properties Android Studio writes, unescaping `\:` and `\\` of
+/// Windows paths.
+pub fn parse_local_properties_sdk_dir(content: &str) -> Option<PathBuf> {
+  content.lines().find_map(|line| {
+    let value = line.trim().strip_prefix("sdk.dir")?.trim_start();
+    let value = value.strip_prefix(['=', ':'])?.trim();
+    let mut unescaped = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+      match c {
+        '\\' => unescaped.extend(chars.next()),
+        c => unescaped.push(c),
+      }
     }
+    Some(PathBuf::from(unescaped))
+  })
+}
+
+/// Finds the sdk dir recorded in the local.properties of an android project in the dir or one of
+/// its subdirs, e.g. `android/local.properties`.
+pub fn find_local_properties_sdk_dir(root: &Path) -> Option<PathBuf> {
+  let mut candidates = vec![root.join("local.properties")];
+  if let Ok(entries) = read_dir(root) {
+    let mut subdirs: Vec<PathBuf> = entries
+      .filter_map(|e| e.ok())
+      .map(|e| e.path())
+      .filter(|path| path.is_dir())
+      .collect();
+    subdirs.sort();
+    candidates.extend(subdirs.iter().map(|dir| dir.join("local.properties")));
+  }
+  //a relative sdk.dir is relative to the project of the local.properties
+  candidates
+    .iter()
+    .filter_map(|path| {
+      Some(
+        path
+          .parent()?
+          .join(parse_local_properties_sdk_dir(&read_to_string(path).ok()?)?),
+      )
+    })
+    .find(|dir| dir.is_dir())
+}
+
+/// Looks for the sdk if ANDROID_HOME isn't set: ANDROID_SDK_ROOT, the default install locations,
+/// then the local.properties of an android project below `root`. Returns the dir and where it was
+/// found.
+pub fn find_android_home(
+  sdk_root: Option<PathBuf>,
+  home: Option<&Path>,
+  local_app_data: Option<&Path>,
+  root: &Path,
+) -> Option<(PathBuf, String)> {
+  if let Some(sdk_root) = sdk_root {
+    return Some((sdk_root, "ANDROID_SDK_ROOT".to_string()));
+  }
+  get_default_android_home_candidates(home, local_app_data)
+    .into_iter()
+    .find(|dir| dir.is_dir())
+    .map(|dir| (dir, "the default install location".to_string()))
+    .or_else(|| {
+      find_local_properties_sdk_dir(root).map(|dir| (dir, "local.properties".to_string()))
+    })
+}
+
+/// Sets ANDROID_HOME for the rest of the run if it isn't set but the sdk can be found, printing
+/// where it was found.
+pub fn resolve_android_home_env() {
+  if env::var_os("ANDROID_HOME").is_some() {
+    return;
+  }
+  let home = env::var_os("HOME").or_else(|| env::var_os("USERPROFILE"));
+  let local_app_data = env::var_os("LOCALAPPDATA");
+  let found = find_android_home(
+    env::var_os("ANDROID_SDK_ROOT").map(PathBuf::from),
+    home.as_deref().map(Path::new),
+    local_app_data.as_deref().map(Path::new),
+    &env::current_dir().unwrap_or_default(),
+  );
+  if let Some((dir, source)) = found {
+    eprintln!("Using ANDROID_HOME={} found by {}", dir.display(), source);
+    env::set_var("ANDROID_HOME", dir);
+  }
+}
+
+/// The newest NDK installed side by side in the sdk, else the legacy `ndk-bundle`.
+pub fn find_newest_ndk(android_home: &Path) -> Option<PathBuf> {
+  let mut versions: Vec<String> = read_dir(android_home.join("ndk"))
+    .map(|entries| {
+      entries
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().is_dir())
+        .map(|e| e.file_name().to_string_lossy().to_string())
+        .collect()
+    })
+    .unwrap_or_default();
+  versions.sort_by(|a, b| compare_versions(b, a));
+  versions
+    .first()
+    .map(|version| android_home.join("ndk").join(version))
+    .or_else(|| Some(android_home.join("ndk-bundle")).filter(|dir| dir.is_dir()))
+}
+
+/// A toml file that can't be read or parsed.
+#[derive(Debug)]
+pub struct TomlError {
+  pub path: PathBuf,
+  pub kind: TomlErrorKind,
+}
+
+#[derive(Debug)]
+pub enum TomlErrorKind {
+  Io(io::Error),
+  /// The message of the parser and, if it knows it, the 1-based line and column of the error.
+  Parse {
+    message: String,
+    location: Option<(usize, usize)>,
+  },
+}
+
+impl Display for TomlError {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match &self.kind {
+      TomlErrorKind::Io(e) => write!(f, "unable to read {}: {}", self.path.display(), e),
+      TomlErrorKind::Parse {
+        message,
+        location: Some((line, column)),
+      } => write!(
+        f,
+        "invalid toml in {}:{}:{}: {}",
+        self.path.display(),
+        line,
+        column,
+        message
+      ),
+      TomlErrorKind::Parse {
+        message,
+        location: None,
+      } => write!(f, "invalid toml in {}: {}", self.path.display(), message),
+    }
+  }
+}
+
+impl std::error::Error for TomlError {}
+
+impl From<TomlError> for String {
+  fn from(error: TomlError) -> Self {
+    error.to_string()
+  }
+}
+
+impl From<TomlError> for Error {
+  fn from(error: TomlError) -> Self {
+    Error::Config(error.to_string())
+  }
+}
+
+/// The 1-based line and column of the byte offset in the content.
+fn line_and_column(content: &str, offset: usize) -> (usize, usize) {
+  let before = &content[..offset.min(content.len())];
+  let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+  (
+    before.matches('\n').count() + 1,
+    before[line_start..].chars().count() + 1,
+  )
+}
+
+/// Parses the toml content of the file at the path.
+pub fn parse_toml(content: &str, path: &Path) -> Result<Table, TomlError> {
+  content.parse::<Table>().map_err(|e| TomlError {
+    path: path.to_path_buf(),
+    kind: TomlErrorKind::Parse {
+      message: e.message().trim_end().to_string(),
+      location: e.span().map(|span| line_and_column(content, span.start)),
+    },
+  })
+}
+
+/// Reads and parses the toml file.
+pub fn read_toml_file(toml_file: &Path) -> Result<Table, TomlError> {
+  let content = read_to_string(toml_file).map_err(|e| TomlError {
+    path: toml_file.to_path_buf(),
+    kind: TomlErrorKind::Io(e),
+  })?;
+  parse_toml(&content, toml_file)
+}
+
+/// Whether the value is the `{ workspace = true }` marker of a field inherited from the workspace.
+fn is_workspace_inherited(value: &Value) -> bool {
+  value
+    .as_table()
+    .and_then(|t| t.get("workspace"))
+    .and_then(|w| w.as_bool())
+    .unwrap_or(false)
+}
+
+/// Finds the manifest of the workspace root the package belongs to: the one `package.workspace`
+/// points to, or the closest manifest with a `[workspace]` table, which may be the package
+/// manifest itself.
+pub fn find_workspace_root_manifest(package_manifest: &Path) -> Result<Option<PathBuf>, TomlError> {
+  let Ok(package_manifest) = canonicalize(package_manifest) else {
+    return Ok(None);
+  };
+  let package_dir = package_manifest.parent().unwrap();
+  let explicit_root = read_toml_file(&package_manifest)?
+    .get("package")
+    .and_then(|package| package.get("workspace"))
+    .and_then(|workspace| workspace.as_str())
+    .map(|workspace| package_dir.join(workspace).join("Cargo.toml"));
+  if explicit_root.is_some() {
+    return Ok(explicit_root.filter(|root| root.is_file()));
   }
 
-  panic!("Need env var: {}", key);
+  for manifest in package_dir.ancestors().map(|dir| dir.join("Cargo.toml")) {
+    if manifest.is_file() && read_toml_file(&manifest)?.contains_key("workspace") {
+      return Ok(Some(manifest));
+    }
+  }
+  Ok(None)
 }
 
-pub fn get_toml_entry<P, V, S>(toml_file: P, path: V) -> Option<Value>
+/// Reads the entry at the path of the already loaded table of the toml file, which is only read
+/// again to resolve package fields inherited with `field.workspace = true` from
+/// `[workspace.package]` of the workspace root. The overrides of the metadata profile apply to the
+/// android metadata.
+pub fn get_table_entry<V, S>(
+  mut table: Table,
+  toml_file: &Path,
+  path: V,
+) -> Result<Option<Value>, TomlError>
 where
-  P: AsRef<Path>,
   V: Into<VecDeque<S>>,
   S: ToString,
 {
-  let toml_content = read_to_string(toml_file).expect("unable to read toml file");
-  let mut table = toml_content.parse::<Table>().expect("invalid toml content");
+  if let Some(profile) = get_metadata_profile() {
+    if let Some(Value::Table(android)) = table
+      .get_mut("package")
+      .and_then(|package| package.get_mut("metadata"))
+      .and_then(|metadata| metadata.get_mut("android"))
+    {
+      apply_profile_overrides(android, &profile);
+    }
+  }
 
   let mut path = path.into();
   if path.is_empty() {
-    return Some(Value::Table(table));
+    return Ok(Some(Value::Table(table)));
   }
 
+  let mut traversed = vec![];
   loop {
     let next_sub_path = path.pop_front().unwrap().to_string();
-    let value = table.get(&next_sub_path)?.clone();
+    let Some(mut value) = table.remove(&next_sub_path) else {
+      return Ok(None);
+    };
+    traversed.push(next_sub_path);
+
+    if traversed.len() == 2 && traversed[0] == "package" && is_workspace_inherited(&value) {
+      let Some(root) = find_workspace_root_manifest(toml_file)? else {
+        return Ok(None);
+      };
+      match get_toml_entry(&root, ["workspace", "package", &traversed[1]])? {
+        Some(inherited) => value = inherited,
+        None => return Ok(None),
+      }
+    }
 
     if path.is_empty() {
-      return Some(value);
+      return Ok(Some(value));
     }
 
     if let Value::Table(t) = value {
       table = t;
     } else {
-      return None;
+      return Ok(None);
     }
   }
 }
 
-pub fn get_toml_string<P, V, S>(toml_file: P, path: V) -> Option<String>
+/// Reads the entry at the path of the toml file, see [get_table_entry].
+pub fn get_toml_entry<P, V, S>(toml_file: P, path: V) -> Result<Option<Value>, TomlError>
 where
   P: AsRef<Path>,
   V: Into<VecDeque<S>>,
   S: ToString,
 {
-  match get_toml_entry(toml_file, path) {
+  let toml_file = toml_file.as_ref();
+  get_table_entry(read_toml_file(toml_file)?, toml_file, path)
+}
+
+pub fn get_toml_string<P, V, S>(toml_file: P, path: V) -> Result<Option<String>, TomlError>
+where
+  P: AsRef<Path>,
+  V: Into<VecDeque<S>>,
+  S: ToString,
+{
+  Ok(match get_toml_entry(toml_file, path)? {
     Some(Value::String(s)) => Some(s),
     _ => None,
-  }
+  })
 }
 
-pub fn get_toml_string_vec<P, V, S>(toml_file: P, path: V) -> Option<Vec<String>>
+pub fn get_toml_bool<P, V, S>(toml_file: P, path: V) -> Result<Option<bool>, TomlError>
 where
   P: AsRef<Path>,
   V: Into<VecDeque<S>>,
   S: ToString,
 {
-  match get_toml_entry(toml_file, path) {
-    Some(Value::Array(a)) => a
-      .into_iter()
-      .map(|v| match v {
-        Value::String(s) => Some(s),
-        _ => None,
-      })
-      .collect(),
+  Ok(match get_toml_entry(toml_file, path)? {
+    Some(Value::Boolean(b)) => Some(b),
     _ => None,
+  })
+}
+
+pub fn get_toml_integer<P, V, S>(toml_file: P, path: V) -> Result<Option<i64>, TomlError>
+where
+  P: AsRef<Path>,
+  V: Into<VecDeque<S>>,
+  S: ToString,
+{
+  Ok(match get_toml_entry(toml_file, path)? {
+    Some(Value::Integer(i)) => Some(i),
+    _ => None,
+  })
+}
+
+/// Returns the leading whitespace of the line.
+pub fn indentation(line: &str) -> &str {
+  &line[..line.len() - line.trim_start().len()]
+}
+
+/// Inserts a line after the first line matching the predicate, indented like that line plus
+/// `extra_indentation`.
+pub fn insert_after_line(
+  content: &str,
+  predicate: impl Fn(&str) -> bool,
+  new_line: &str,
+  extra_indentation: &str,
+) -> Option<String> {
+  let mut lines: Vec<String> = content.lines().map(|l| l.to_string()).collect();
+  let index = lines.iter().position(|l| predicate(l.trim()))?;
+  let indented = format!(
+    "{}{}{}",
+    indentation(&lines[index]),
+    extra_indentation,
+    new_line
+  );
+  lines.insert(index + 1, indented);
+  let mut result = lines.join("\n");
+  if content.ends_with('\n') {
+    result.push('\n');
+  }
+  Some(result)
+}
+
+#[cfg(test)]
+mod test {
+  use std::fs::{create_dir_all, write};
+  use std::path::PathBuf;
+
+  use tempfile::tempdir;
+  use toml::Value;
+
+  use crate::util::{
+    apply_profile_overrides, find_android_home, find_newest_ndk, find_workspace_root_manifest,
+    get_table_entry, get_toml_entry, get_toml_string, parse_local_properties_sdk_dir,
+    read_toml_file, TomlErrorKind,
+  };
+
+  #[test]
+  fn android_home_detection() {
+    let dir = tempdir().unwrap();
+    let home = dir.path().join("home");
+    let local_app_data = dir.path().join("AppData/Local");
+    let project = dir.path().join("project");
+    create_dir_all(&project).unwrap();
+    let find = || {
+      find_android_home(None, Some(&home), Some(&local_app_data), &project).map(
+        |(found, source)| {
+          (
+            found.strip_prefix(dir.path()).unwrap().to_path_buf(),
+            source,
+          )
+        },
+      )
+    };
+    assert_eq!(None, find());
+    assert_eq!(
+      Some((PathBuf::from("/opt/sdk"), "ANDROID_SDK_ROOT".to_string())),
+      find_android_home(Some(PathBuf::from("/opt/sdk")), Some(&home), None, &project)
+    );
+
+    //Android Studio records the sdk of the project
+    let studio_sdk = dir.path().join("studio-sdk");
+    create_dir_all(&studio_sdk).unwrap();
+    create_dir_all(project.join("android")).unwrap();
+    write(
+      project.join("android/local.properties"),
+      format!("## comment\nsdk.dir={}\n", studio_sdk.display()),
+    )
+    .unwrap();
+    assert_eq!(
+      Some((PathBuf::from("studio-sdk"), "local.properties".to_string())),
+      find()
+    );
+    //relative to the project of the local.properties, not the working directory
+    write(
+      project.join("android/local.properties"),
+      "sdk.dir=../../studio-sdk
+",
+    )
+    .unwrap();
+    assert_eq!(
+      Some(PathBuf::from("project/android/../../studio-sdk")),
+      find().map(|(dir, _)| dir)
+    );
+
+    create_dir_all(local_app_data.join("Android/Sdk")).unwrap();
+    assert_eq!(
+      Some((
+        PathBuf::from("AppData/Local/Android/Sdk"),
+        "the default install location".to_string()
+      )),
+      find()
+    );
+    create_dir_all(home.join("Library/Android/sdk")).unwrap();
+    assert_eq!(
+      Some(PathBuf::from("home/Library/Android/sdk")),
+      find().map(|(dir, _)| dir)
+    );
+    create_dir_all(home.join("Android/Sdk")).unwrap();
+    assert_eq!(
+      Some(PathBuf::from("home/Android/Sdk")),
+      find().map(|(dir, _)| dir)
+    );
+  }
+
+  #[test]
+  fn local_properties() {
+    assert_eq!(
+      Some(PathBuf::from("C:\\Users\\me\\AppData\\Local\\Android\\Sdk")),
+      parse_local_properties_sdk_dir(
+        "#Mon Jan 01 12:00:00 CET 2024\nsdk.dir=C\\:\\\\Users\\\\me\\\\AppData\\\\Local\\\\Android\\\\Sdk\n"
+      )
+    );
+    assert_eq!(
+      Some(PathBuf::from("/home/me/Android/Sdk")),
+      parse_local_properties_sdk_dir("ndk.dir=/x\nsdk.dir = /home/me/Android/Sdk")
+    );
+    assert_eq!(None, parse_local_properties_sdk_dir("ndk.dir=/x"));
+  }
+
+  #[test]
+  fn newest_ndk() {
+    let dir = tempdir().unwrap();
+    assert_eq!(None, find_newest_ndk(dir.path()));
+    create_dir_all(dir.path().join("ndk-bundle")).unwrap();
+    assert_eq!(
+      Some(dir.path().join("ndk-bundle")),
+      find_newest_ndk(dir.path())
+    );
+    for version in ["25.2.9519653", "26.1.10909125", "9.0.0"] {
+      create_dir_all(dir.path().join("ndk").join(version)).unwrap();
+    }
+    assert_eq!(
+      Some(dir.path().join("ndk/26.1.10909125")),
+      find_newest_ndk(dir.path())
+    );
+  }
+
+  #[test]
+  fn workspace_inheritance() {
+    let dir = tempdir().unwrap();
+    write(
+      dir.path().join("Cargo.toml"),
+      r#"[workspace]
+members = ["game"]
+
+[workspace.package]
+version = "1.2.3"
+authors = ["Jane Doe"]
+"#,
+    )
+    .unwrap();
+    create_dir_all(dir.path().join("game")).unwrap();
+    let manifest = dir.path().join("game/Cargo.toml");
+    write(
+      &manifest,
+      r#"[package]
+name = "game"
+version.workspace = true
+authors = { workspace = true }
+description.workspace = true
+"#,
+    )
+    .unwrap();
+
+    assert_eq!(
+      find_workspace_root_manifest(&manifest).unwrap(),
+      Some(dir.path().canonicalize().unwrap().join("Cargo.toml"))
+    );
+    assert_eq!(
+      get_toml_string(&manifest, ["package", "version"])
+        .unwrap()
+        .as_deref(),
+      Some("1.2.3")
+    );
+    assert_eq!(
+      get_toml_entry(&manifest, ["package", "authors"]).unwrap(),
+      Some(Value::Array(vec![Value::String("Jane Doe".to_string())]))
+    );
+    assert_eq!(
+      get_toml_string(&manifest, ["package", "name"])
+        .unwrap()
+        .as_deref(),
+      Some("game")
+    );
+    //inherited, but not set in the workspace
+    assert_eq!(
+      get_toml_entry(&manifest, ["package", "description"]).unwrap(),
+      None
+    );
+  }
+
+  #[test]
+  fn toml_file_errors() {
+    let dir = tempdir().unwrap();
+    let manifest = dir.path().join("Cargo.toml");
+    let error = read_toml_file(&manifest).unwrap_err();
+    assert!(matches!(error.kind, TomlErrorKind::Io(_)));
+    assert!(error.to_string().starts_with("unable to read "));
+
+    write(
+      &manifest,
+      "[package]\nname = \"game\"\n\n[package.metadata.android]\ntitle = \"Rocks\n",
+    )
+    .unwrap();
+    let error = get_toml_string(&manifest, ["package", "name"]).unwrap_err();
+    assert_eq!(manifest, error.path);
+    let TomlErrorKind::Parse { location, .. } = error.kind else {
+      panic!("expected a parse error");
+    };
+    assert_eq!(Some((5, 15)), location);
+
+    //an already loaded table is read without the file
+    let table = "[package]\nname = \"game\"".parse::<toml::Table>().unwrap();
+    assert_eq!(
+      Some(Value::String("game".to_string())),
+      get_table_entry(table, &manifest, ["package", "name"]).unwrap()
+    );
+  }
+
+  #[test]
+  fn profile_overrides() {
+    let mut android = r#"
+title = "Rocks"
+icon = "icon.png"
+
+[application]
+debuggable = false
+allow_backup = true
+
+[profile.debug]
+icon = "icon-debug.png"
+
+[profile.debug.application]
+debuggable = true
+uses_cleartext_traffic = true
+"#
+    .parse::<toml::Table>()
+    .unwrap();
+    let base = android.clone();
+    apply_profile_overrides(&mut android, "debug");
+    assert_eq!(android["title"].as_str(), Some("Rocks"));
+    assert_eq!(android["icon"].as_str(), Some("icon-debug.png"));
+    //nested tables are merged per key
+    let application = android["application"].as_table().unwrap();
+    assert_eq!(application["debuggable"].as_bool(), Some(true));
+    assert_eq!(application["allow_backup"].as_bool(), Some(true));
+    assert_eq!(application["uses_cleartext_traffic"].as_bool(), Some(true));
+
+    //profiles without overrides keep the base values
+    let mut release = base.clone();
+    apply_profile_overrides(&mut release, "release");
+    assert_eq!(release, base);
   }
 }
@@ -0,0 +1,391 @@
+use std::collections::BTreeSet;
+use std::io::{stderr, IsTerminal};
+use std::sync::Mutex;
+
+use lazy_static::lazy_static;
+
+use crate::ci::*;
+
+/// A kind of warning the build raises, with what it means and how to address it, shown by
+/// `--explain <id>`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct WarningKind {
+  pub id: &'static str,
+  pub explanation: &'static str,
+}
+
+pub const METADATA: WarningKind = WarningKind {
+  id: "metadata",
+  explanation: "The android metadata has a key this version doesn't know, or a value it ignores. \
+    Usually a typo or a key of another version, check the README for the keys and their types.",
+};
+
+pub const SDL_VERSION: WarningKind = WarningKind {
+  id: "sdl-version",
+  explanation: "The SDL source in $SDL isn't the version the sdl2 crate was written against. The \
+    Java glue of the source and the native library of the crate then disagree about their JNI \
+    functions, which usually crashes the app on startup. Check out the matching SDL branch, or \
+    pass --strict-sdl-check to make this an error.",
+};
+
+pub const ENTRY_POINT: WarningKind = WarningKind {
+  id: "entry-point",
+  explanation: "The native library doesn't export ANativeActivity_onCreate, which \
+    android.app.NativeActivity calls to start the app. Without it the app closes right after \
+    launching. Depend on a glue crate like android-activity, or export the function yourself.",
+};
+
+pub const SDL_CACHE: WarningKind = WarningKind {
+  id: "sdl-cache",
+  explanation:
+    "SDL was built but the build couldn't be stored in the SDL build cache, so the next \
+    build runs ndk-build again. Check that the cache dir is writable.",
+};
+
+pub const DEFAULT_ICON: WarningKind = WarningKind {
+  id: "default-icon",
+  explanation: "The icon of the package couldn't be read or is too small, so the app gets the icon \
+    of the template. Configure a square PNG, JPEG or WebP of at least 192x192 pixels with `icon` in \
+    the android metadata.",
+};
+
+pub const SPLIT_DEBUGINFO: WarningKind = WarningKind {
+  id: "split-debuginfo",
+  explanation: "split_debuginfo = \"packed\" is set, but cargo wrote no .dwp because the profile \
+    builds without debug info. Set `debug = true` or `debug = \"line-tables-only\"` in the profile \
+    to get file and line info for crashes.",
+};
+
+pub const LARGE_SCREENS: WarningKind = WarningKind {
+  id: "large-screens",
+  explanation: "large_screens is enabled, but the configured orientation locks the activity to \
+    portrait or landscape. Google Play flags apps locking the orientation on large screens and may \
+    show them less prominently to tablet and Chromebook users. Remove the orientation to let the \
+    activity rotate.",
+};
+
+pub const MODIFIED_FILES: WarningKind = WarningKind {
+  id: "modified-files",
+  explanation: "Files of the android project which this tool generates were edited by hand since \
+    the last build. They are regenerated, losing the edits, unless --keep-local is given. Move the \
+    changes into the android metadata or a custom project template to keep them.",
+};
+
+pub const APPLICATION_CLASS: WarningKind = WarningKind {
+  id: "application-class",
+  explanation: "No Java or Kotlin source of the application class was found in the package. The \
+    build fails in gradle unless the class comes from a dependency.",
+};
+
+pub const MULTIDEX: WarningKind = WarningKind {
+  id: "multidex",
+  explanation: "multidex couldn't be enabled because app/build.gradle of the template has no \
+    minSdkVersion. Set min_sdk_version in the android metadata.",
+};
+
+pub const BUILD_CONFIG: WarningKind = WarningKind {
+  id: "build-config",
+  explanation: "The BuildConfig fields with the build info couldn't be added because \
+    app/build.gradle of the template has no defaultConfig block.",
+};
+
+pub const GOOGLE_SERVICES: WarningKind = WarningKind {
+  id: "google-services",
+  explanation: "The google services gradle plugin couldn't be added to the build files of the \
+    template, so google-services.json isn't processed. Add the plugin to the template by hand.",
+};
+
+pub const DEBUG_KEYSTORE: WarningKind = WarningKind {
+  id: "debug-keystore",
+  explanation: "The release APK is signed with the Android debug keystore because no keystore is \
+    configured. Stores reject such APKs and devices can't update it once it is signed with a \
+    release key. Configure [package.metadata.android.signing] or pass --ks-file and --ks-pass.",
+};
+
+pub const JARSIGNER: WarningKind = WarningKind {
+  id: "jarsigner",
+  explanation:
+    "The build-tools have no apksigner, so the APK is signed with jarsigner of the JDK, \
+    which only creates a v1 signature and no v4 one. Devices from Android 11 on require v2 or \
+    newer for min_sdk_version 30 and above, which then fails the build. Install build-tools with \
+    apksigner with sdkmanager.",
+};
+
+pub const UNNORMALIZED_APK: WarningKind = WarningKind {
+  id: "unnormalized-apk",
+  explanation: "--reproducible normalizes the APK before signing it, which isn't possible when \
+    gradle signs it with signing mode \"gradle\". Use the default signing mode for reproducible \
+    builds.",
+};
+
+pub const SYMBOLS_BUNDLE: WarningKind = WarningKind {
+  id: "symbols-bundle",
+  explanation: "--symbols-bundle was given for a debug build. Symbols bundles are for crash \
+    reporting of releases, build with --release to collect one.",
+};
+
+pub const LAST_BUILD: WarningKind = WarningKind {
+  id: "last-build",
+  explanation: "The outputs of the build couldn't be recorded in the target dir, so commands like \
+    `install` without building and `debug` don't find them. Check that the target dir is \
+    writable.",
+};
+
+pub const UNPINNED_SDL: WarningKind = WarningKind {
+  id: "unpinned-sdl",
+  explanation: "The SDL version being downloaded has no checksum pinned by this version of \
+    cargo-sdl-apk, so the download is verified with the checksum of sdl_checksums or --sha256 \
+    instead. Make sure it comes from a trusted source, like the release page of SDL.",
+};
+
+/// All kinds of warnings, for `--allow` and `--explain`.
+pub const WARNING_KINDS: &[&WarningKind] = &[
+  &METADATA,
+  &SDL_VERSION,
+  &ENTRY_POINT,
+  &SDL_CACHE,
+  &DEFAULT_ICON,
+  &SPLIT_DEBUGINFO,
+  &LARGE_SCREENS,
+  &MODIFIED_FILES,
+  &APPLICATION_CLASS,
+  &MULTIDEX,
+  &BUILD_CONFIG,
+  &GOOGLE_SERVICES,
+  &DEBUG_KEYSTORE,
+  &JARSIGNER,
+  &UNNORMALIZED_APK,
+  &SYMBOLS_BUNDLE,
+  &LAST_BUILD,
+  &UNPINNED_SDL,
+];
+
+/// A warning raised during the run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Warning {
+  pub id: &'static str,
+  pub message: String,
+  /// What to do about it, printed on its own line below the message.
+  pub remediation: Option<String>,
+}
+
+/// The warnings raised so far, and which are allowed or denied by the command line.
+#[derive(Debug, Default)]
+pub struct WarningCollector {
+  raised: Vec<Warning>,
+  allowed: BTreeSet<String>,
+  deny: bool,
+}
+
+impl WarningCollector {
+  /// Records the warning. Returns whether it should be printed, which it shouldn't if it's allowed
+  /// or was raised with the same message before.
+  pub fn raise(&mut self, warning: Warning) -> bool {
+    if self.allowed.contains(warning.id)
+      || self
+        .raised
+        .iter()
+        .any(|w| w.id == warning.id && w.message == warning.message)
+    {
+      return false;
+    }
+    self.raised.push(warning);
+    true
+  }
+
+  /// The block listing the raised warnings printed before the result of the build, `None` without
+  /// warnings.
+  pub fn summary(&self) -> Option<String> {
+    if self.raised.is_empty() {
+      return None;
+    }
+    let mut summary = format!(
+      "WARNING: {} {}, rerun with --explain <id> for details:\n",
+      self.raised.len(),
+      if self.raised.len() == 1 {
+        "issue"
+      } else {
+        "issues"
+      }
+    );
+    for warning in &self.raised {
+      summary.push_str(&format!("WARNING:   {}: {}\n", warning.id, warning.message));
+    }
+    Some(summary)
+  }
+
+  /// Fails with `--deny-warnings` if any warning was raised.
+  pub fn check_denied(&self) -> Result<(), String> {
+    if !self.deny || self.raised.is_empty() {
+      return Ok(());
+    }
+    let ids: BTreeSet<&str> = self.raised.iter().map(|w| w.id).collect();
+    Err(format!(
+      "{} {} denied by --deny-warnings ({}), fix them or allow them with --allow <id>",
+      self.raised.len(),
+      if self.raised.len() == 1 {
+        "warning"
+      } else {
+        "warnings"
+      },
+      ids.into_iter().collect::<Vec<_>>().join(", ")
+    ))
+  }
+}
+
+lazy_static! {
+  static ref WARNINGS: Mutex<WarningCollector> = Mutex::new(WarningCollector::default());
+}
+
+pub fn find_warning_kind(id: &str) -> Option<&'static WarningKind> {
+  WARNING_KINDS.iter().copied().find(|kind| kind.id == id)
+}
+
+fn unknown_warning_error(id: &str) -> String {
+  let ids: Vec<&str> = WARNING_KINDS.iter().map(|kind| kind.id).collect();
+  format!(
+    "Unknown warning \"{}\", expected one of: {}",
+    id,
+    ids.join(", ")
+  )
+}
+
+/// Allows the warnings of the ids for the rest of the run, and turns the others into errors with
+/// `deny`.
+pub fn configure_warnings(allowed: &[String], deny: bool) -> Result<(), String> {
+  for id in allowed {
+    find_warning_kind(id).ok_or_else(|| unknown_warning_error(id))?;
+  }
+  let mut warnings = WARNINGS.lock().unwrap();
+  warnings.allowed = allowed.iter().cloned().collect();
+  warnings.deny = deny;
+  Ok(())
+}
+
+/// What the warning of the id means and how to address it.
+pub fn explain_warning(id: &str) -> Result<String, String> {
+  find_warning_kind(id)
+    .map(|kind| format!("{}: {}", kind.id, kind.explanation))
+    .ok_or_else(|| unknown_warning_error(id))
+}
+
+fn print_warning(warning: &Warning) {
+  //yellow like the warnings of cargo, unless the output is read by a machine
+  let (start, end) = if stderr().is_terminal() && !is_ci_mode() {
+    ("\x1b[33m", "\x1b[0m")
+  } else {
+    ("", "")
+  };
+  eprintln!(
+    "{}WARNING[{}]: {}{}",
+    start, warning.id, warning.message, end
+  );
+  if let Some(remediation) = &warning.remediation {
+    eprintln!("{}WARNING: {}{}", start, remediation, end);
+  }
+}
+
+fn raise(warning: Warning) {
+  if WARNINGS.lock().unwrap().raise(warning.clone()) {
+    print_warning(&warning);
+  }
+}
+
+/// Prints the warning unless it's allowed or was printed before, and keeps it for the summary.
+pub fn warn(kind: &WarningKind, message: impl Into<String>) {
+  raise(Warning {
+    id: kind.id,
+    message: message.into(),
+    remediation: None,
+  });
+}
+
+/// Like [warn], with what to do about it on its own line.
+pub fn warn_with_fix(
+  kind: &WarningKind,
+  message: impl Into<String>,
+  remediation: impl Into<String>,
+) {
+  raise(Warning {
+    id: kind.id,
+    message: message.into(),
+    remediation: Some(remediation.into()),
+  });
+}
+
+/// Prints the summary of the warnings raised so far, if any.
+pub fn print_warning_summary() {
+  if let Some(summary) = WARNINGS.lock().unwrap().summary() {
+    eprint!("{}", summary);
+  }
+}
+
+/// Fails with `--deny-warnings` if any warning was raised.
+pub fn check_denied_warnings() -> Result<(), String> {
+  WARNINGS.lock().unwrap().check_denied()
+}
+
+#[cfg(test)]
+mod test {
+  use std::collections::BTreeSet;
+
+  use crate::warnings::{
+    explain_warning, Warning, WarningCollector, DEBUG_KEYSTORE, DEFAULT_ICON, WARNING_KINDS,
+  };
+
+  fn warning(id: &'static str, message: &str) -> Warning {
+    Warning {
+      id,
+      message: message.to_string(),
+      remediation: None,
+    }
+  }
+
+  #[test]
+  fn warning_kinds() {
+    let ids: BTreeSet<&str> = WARNING_KINDS.iter().map(|kind| kind.id).collect();
+    assert_eq!(WARNING_KINDS.len(), ids.len());
+    assert!(explain_warning("debug-keystore")
+      .unwrap()
+      .starts_with("debug-keystore: The release APK is signed with the Android debug keystore"));
+    assert!(explain_warning("typo")
+      .unwrap_err()
+      .starts_with("Unknown warning \"typo\", expected one of: metadata, sdl-version"));
+  }
+
+  #[test]
+  fn collected_warnings() {
+    let mut warnings = WarningCollector::default();
+    assert_eq!(None, warnings.summary());
+    assert!(warnings.raise(warning(DEBUG_KEYSTORE.id, "No keystore configured.")));
+    //printed once
+    assert!(!warnings.raise(warning(DEBUG_KEYSTORE.id, "No keystore configured.")));
+    assert_eq!(
+      Some(
+        "WARNING: 1 issue, rerun with --explain <id> for details:\n\
+        WARNING:   debug-keystore: No keystore configured.\n"
+          .to_string()
+      ),
+      warnings.summary()
+    );
+    assert_eq!(Ok(()), warnings.check_denied());
+
+    warnings.allowed.insert(DEFAULT_ICON.id.to_string());
+    assert!(!warnings.raise(warning(DEFAULT_ICON.id, "icon.png is too small.")));
+    assert!(warnings.raise(warning("metadata", "Unknown key android.titel")));
+    assert!(warnings
+      .summary()
+      .unwrap()
+      .starts_with("WARNING: 2 issues,"));
+
+    warnings.deny = true;
+    assert_eq!(
+      Err(
+        "2 warnings denied by --deny-warnings (debug-keystore, metadata), fix them or allow them \
+        with --allow <id>"
+          .to_string()
+      ),
+      warnings.check_denied()
+    );
+  }
+}
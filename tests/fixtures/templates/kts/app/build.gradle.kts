@@ -0,0 +1,24 @@
+plugins {
+    id("com.android.application")
+}
+
+android {
+    namespace = "org.libsdl.app"
+    compileSdk = 34
+    defaultConfig {
+        applicationId = "org.libsdl.app"
+        minSdk = 21
+        targetSdk = 34
+        versionCode = 1
+        versionName = "1.0"
+    }
+    buildTypes {
+        getByName("release") {
+            isMinifyEnabled = false
+        }
+    }
+}
+
+dependencies {
+    implementation(fileTree(mapOf("include" to listOf("*.jar"), "dir" to "libs")))
+}
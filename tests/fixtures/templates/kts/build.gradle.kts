@@ -0,0 +1,3 @@
+plugins {
+    id("com.android.application") version "8.2.0" apply false
+}
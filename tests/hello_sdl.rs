@@ -0,0 +1,149 @@
+use std::env;
+use std::fs::{create_dir_all, read_to_string, write};
+use std::path::{Path, PathBuf};
+
+use fs_extra::dir::CopyOptions;
+use tempfile::{tempdir, TempDir};
+use toml::{Table, Value};
+
+use cargo_sdl_apk::AndroidBuild;
+
+/// Copies `examples/hello-sdl` into a temporary dir and points it at a copy of the vendored
+/// fixture template with the Java glue of SDL 2.26.4, which the example's SDL2 bindings match, so
+/// no SDL checkout or Android sdk is needed.
+fn copy_example(template: &str) -> (TempDir, PathBuf) {
+  let dir = tempdir().unwrap();
+  let crate_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+  fs_extra::dir::copy(
+    crate_dir.join("examples/hello-sdl"),
+    dir.path(),
+    &CopyOptions::new().content_only(true),
+  )
+  .unwrap();
+
+  let template_dir = dir.path().join("android-template");
+  fs_extra::dir::copy(
+    crate_dir.join("tests/fixtures/templates").join(template),
+    &template_dir,
+    &CopyOptions::new().content_only(true),
+  )
+  .unwrap();
+  let java_dir = template_dir.join("app/src/main/java");
+  create_dir_all(&java_dir).unwrap();
+  fs_extra::dir::copy(
+    crate_dir.join("tests/fixtures/sdl2-java/org"),
+    &java_dir,
+    &CopyOptions::new(),
+  )
+  .unwrap();
+
+  //the fixture templates don't build SDL with ndk-build, the sources only need to exist for the
+  //link into the project
+  if env::var_os("SDL").is_none() {
+    let sdl_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("SDL");
+    create_dir_all(&sdl_dir).unwrap();
+    env::set_var("SDL", sdl_dir);
+  }
+
+  let manifest_path = dir.path().join("Cargo.toml");
+  let mut manifest: Table = read_to_string(&manifest_path).unwrap().parse().unwrap();
+  let mut android = manifest["package"]["metadata"]["android"]
+    .as_table()
+    .cloned()
+    .unwrap();
+  android.insert(
+    "project_template".to_string(),
+    Value::String(template_dir.display().to_string()),
+  );
+  manifest["package"]
+    .as_table_mut()
+    .unwrap()
+    .get_mut("metadata")
+    .unwrap()
+    .as_table_mut()
+    .unwrap()
+    .insert("android".to_string(), Value::Table(android));
+  write(&manifest_path, manifest.to_string()).unwrap();
+  (dir, manifest_path)
+}
+
+#[test]
+fn generate_groovy_project() {
+  let (_dir, manifest_path) = copy_example("groovy");
+  let project_dir = AndroidBuild::new(&manifest_path)
+    .generate_project()
+    .unwrap();
+
+  let android_manifest =
+    read_to_string(project_dir.join("app/src/main/AndroidManifest.xml")).unwrap();
+  assert!(android_manifest.contains("android.permission.VIBRATE"));
+  assert!(android_manifest.contains("android:screenOrientation=\"landscape\""));
+  assert!(android_manifest.contains("android:exported=\"true\""));
+
+  let build_gradle = read_to_string(project_dir.join("app/build.gradle")).unwrap();
+  assert!(build_gradle.contains("applicationId 'com.example.hellosdl'"));
+  assert!(!build_gradle.contains("org.libsdl.app"));
+  assert!(project_dir
+    .join("app/src/main/java/org/libsdl/app/SDLActivity.java")
+    .is_file());
+
+  let strings = read_to_string(project_dir.join("app/src/main/res/values/strings.xml")).unwrap();
+  assert!(strings.contains("Hello SDL"));
+}
+
+#[test]
+fn generate_kotlin_dsl_project() {
+  let (_dir, manifest_path) = copy_example("kts");
+  let project_dir = AndroidBuild::new(&manifest_path)
+    .generate_project()
+    .unwrap();
+
+  let build_gradle = read_to_string(project_dir.join("app/build.gradle.kts")).unwrap();
+  assert!(build_gradle.contains("applicationId = \"com.example.hellosdl\""));
+  assert!(project_dir
+    .join("app/src/main/java/org/libsdl/app/SDLActivity.java")
+    .is_file());
+  assert!(!project_dir.join("app/build.gradle").exists());
+}
+
+/// Builds the example with gradle, and installs and launches it when a device or emulator is
+/// connected. Needs a full Android build environment, so it only runs with the `ci-device-tests`
+/// feature and is skipped without `ANDROID_HOME`.
+#[cfg(feature = "ci-device-tests")]
+#[test]
+fn build_and_launch() {
+  use std::process::Command;
+
+  if env::var_os("ANDROID_HOME").is_none() {
+    eprintln!("ANDROID_HOME not set, skipping");
+    return;
+  }
+  let (_dir, manifest_path) = copy_example("groovy");
+  let project_dir = manifest_path.parent().unwrap();
+  let cargo_sdl_apk = |command: &str| {
+    Command::new(env!("CARGO_BIN_EXE_cargo-sdl-apk"))
+      .arg(command)
+      .current_dir(project_dir)
+      .status()
+      .unwrap()
+      .success()
+  };
+
+  assert!(cargo_sdl_apk("build"));
+  assert!(project_dir
+    .join("target/android-project-debug/app/build/outputs/apk/debug/app-debug.apk")
+    .is_file());
+
+  let devices = Command::new("adb").arg("devices").output();
+  let connected = devices.is_ok_and(|output| {
+    String::from_utf8_lossy(&output.stdout)
+      .lines()
+      .skip(1)
+      .any(|line| line.ends_with("\tdevice"))
+  });
+  if connected {
+    assert!(cargo_sdl_apk("test-launch"));
+  } else {
+    eprintln!("no device connected, skipping the launch");
+  }
+}
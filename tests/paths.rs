@@ -0,0 +1,153 @@
+use std::env;
+use std::fs::{create_dir_all, read_to_string, write};
+use std::path::Path;
+use std::process::Command;
+
+use fs_extra::dir::CopyOptions;
+use tempfile::tempdir;
+
+/// Directory name with spaces and non-ASCII characters, which paths must survive unchanged on
+/// their way to cargo, gradle and the NDK tools.
+const PROJECT_DIR_NAME: &str = "my projects/Spiel für Jürgen";
+
+#[test]
+fn clean_in_path_with_spaces() {
+  let dir = tempdir().unwrap();
+  let project_dir = dir.path().join(PROJECT_DIR_NAME);
+  create_dir_all(project_dir.join("target/android-project-debug/app")).unwrap();
+  write(
+    project_dir.join("Cargo.toml"),
+    "[package]\nname = \"spiel\"\nversion = \"0.1.0\"\n",
+  )
+  .unwrap();
+
+  assert!(Command::new(env!("CARGO_BIN_EXE_cargo-sdl-apk"))
+    .arg("clean")
+    .arg("--manifest-path")
+    .arg(project_dir.join("Cargo.toml"))
+    .status()
+    .unwrap()
+    .success());
+  assert!(!project_dir.join("target/android-project-debug").exists());
+}
+
+/// Builds a copy of the example project given by `CARGO_SDL_APK_EXAMPLE_PROJECT` in a directory
+/// with spaces and non-ASCII characters. Requires a full Android build environment, so the test is
+/// skipped when the variable is not set.
+#[test]
+fn build_in_path_with_spaces() {
+  let example_dir = match env::var_os("CARGO_SDL_APK_EXAMPLE_PROJECT") {
+    Some(dir) => dir,
+    None => {
+      eprintln!("CARGO_SDL_APK_EXAMPLE_PROJECT not set, skipping");
+      return;
+    }
+  };
+  let dir = tempdir().unwrap();
+  let project_dir = dir.path().join(PROJECT_DIR_NAME);
+  create_dir_all(&project_dir).unwrap();
+  fs_extra::dir::copy(
+    Path::new(&example_dir),
+    &project_dir,
+    &CopyOptions::new().content_only(true),
+  )
+  .unwrap();
+
+  assert!(Command::new(env!("CARGO_BIN_EXE_cargo-sdl-apk"))
+    .arg("build")
+    .current_dir(&project_dir)
+    .status()
+    .unwrap()
+    .success());
+  assert!(project_dir
+    .join("target/android-project-debug/app/build/outputs/apk/debug/app-debug.apk")
+    .is_file());
+}
+
+/// Writes a package named `game` into the dir whose android project is generated from a copy of
+/// the vendored groovy fixture template, referenced relative to the manifest. SDL's java glue comes
+/// from maven, so no SDL checkout or Android sdk is needed.
+fn write_game_package(package_dir: &Path) {
+  let template_dir = package_dir.join("../templates/groovy");
+  create_dir_all(&template_dir).unwrap();
+  fs_extra::dir::copy(
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/templates/groovy"),
+    &template_dir,
+    &CopyOptions::new().content_only(true),
+  )
+  .unwrap();
+  create_dir_all(package_dir.join("src")).unwrap();
+  write(package_dir.join("src/lib.rs"), "").unwrap();
+  write(
+    package_dir.join("Cargo.toml"),
+    "[package]\nname = \"game\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n\
+    [lib]\ncrate-type = [\"cdylib\"]\n\n\
+    [package.metadata.android]\npackage_name = \"com.example.game\"\n\
+    project_template = \"../templates/groovy\"\n\
+    sdl_java = { maven = \"org.libsdl.android:SDL3:3.2.0\" }\n",
+  )
+  .unwrap();
+}
+
+/// Runs `generate` with the manifest path in the working dir.
+fn generate(working_dir: &Path, manifest_path: &Path) {
+  let status = Command::new(env!("CARGO_BIN_EXE_cargo-sdl-apk"))
+    .arg("generate")
+    .arg("--manifest-path")
+    .arg(manifest_path)
+    .current_dir(working_dir)
+    .env_remove("ANDROID_PROJECT_TEMPLATE")
+    .status()
+    .unwrap();
+  assert!(status.success());
+}
+
+/// Checks that the project was generated next to the manifest, from the template of the package.
+fn assert_generated(package_dir: &Path) {
+  let build_gradle =
+    read_to_string(package_dir.join("target/android-project-debug/app/build.gradle")).unwrap();
+  assert!(build_gradle.contains("applicationId 'com.example.game'"));
+  assert!(build_gradle.contains("implementation 'org.libsdl.android:SDL3:3.2.0'"));
+  assert!(package_dir.join("target/android-project").exists());
+}
+
+#[test]
+fn generate_from_sibling_dir() {
+  let dir = tempdir().unwrap();
+  let package_dir = dir.path().join("game");
+  write_game_package(&package_dir);
+  let working_dir = dir.path().join("tools");
+  create_dir_all(&working_dir).unwrap();
+
+  generate(&working_dir, Path::new("../game/Cargo.toml"));
+  assert_generated(&package_dir);
+  assert!(!working_dir.join("target").exists());
+}
+
+#[test]
+fn generate_workspace_member_from_root() {
+  let dir = tempdir().unwrap();
+  let package_dir = dir.path().join("game");
+  write_game_package(&package_dir);
+  write(
+    dir.path().join("Cargo.toml"),
+    "[workspace]\nmembers = [\"game\"]\nresolver = \"2\"\n",
+  )
+  .unwrap();
+
+  generate(dir.path(), Path::new("game/Cargo.toml"));
+  assert_generated(&package_dir);
+  assert!(!dir.path().join("target/android-project-debug").exists());
+}
+
+#[test]
+fn generate_with_absolute_manifest_path() {
+  let dir = tempdir().unwrap();
+  let package_dir = dir.path().join(PROJECT_DIR_NAME).join("game");
+  write_game_package(&package_dir);
+  let working_dir = tempdir().unwrap();
+
+  generate(working_dir.path(), &package_dir.join("Cargo.toml"));
+  assert_generated(&package_dir);
+  assert!(!working_dir.path().join("target").exists());
+}
@@ -0,0 +1,44 @@
+use std::env;
+use std::fs::read;
+use std::path::Path;
+use std::process::Command;
+
+use fs_extra::dir::CopyOptions;
+use tempfile::tempdir;
+
+/// Builds a copy of `examples/hello-sdl` twice in reproducible release mode and checks that the
+/// unsigned apks are byte-identical. Requires a full Android build environment, so the test is
+/// skipped without `ANDROID_HOME`.
+#[test]
+fn release_builds_are_reproducible() {
+  if env::var_os("ANDROID_HOME").is_none() {
+    eprintln!("ANDROID_HOME not set, skipping");
+    return;
+  }
+  let dir = tempdir().unwrap();
+  let project_dir = dir.path();
+  fs_extra::dir::copy(
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("examples/hello-sdl"),
+    project_dir,
+    &CopyOptions::new().content_only(true),
+  )
+  .unwrap();
+  let unsigned_apk = project_dir
+    .join("target/android-project-release/app/build/outputs/apk/release/app-release-unsigned.apk");
+
+  let mut builds = vec![];
+  for _ in 0..2 {
+    assert!(Command::new(env!("CARGO_BIN_EXE_cargo-sdl-apk"))
+      .args(["build", "--release", "--reproducible"])
+      .current_dir(project_dir)
+      .status()
+      .unwrap()
+      .success());
+    builds.push(read(&unsigned_apk).unwrap());
+  }
+
+  assert!(
+    builds[0] == builds[1],
+    "unsigned apks differ between builds"
+  );
+}